@@ -1,6 +1,11 @@
-use anyhow::{anyhow, bail, Result};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
 use clap::ArgMatches;
-use common::database::Db;
+use common::{
+    bookmark::BookmarkReset,
+    database::{export_bookmarks, import_bookmarks, set_source_bookmark, Db},
+};
 
 use crate::utils;
 
@@ -15,6 +20,15 @@ pub async fn run(db: &Db, matches: &ArgMatches) -> Result<()> {
         Some(("copy", matches)) => {
             copy(db, matches).await?;
         }
+        Some(("set", matches)) => {
+            set(db, matches).await?;
+        }
+        Some(("export", matches)) => {
+            export(db, matches).await?;
+        }
+        Some(("import", matches)) => {
+            import(db, matches).await?;
+        }
         _ => {
             bail!("Invalid subcommand")
         }
@@ -171,3 +185,88 @@ async fn copy(db: &Db, matches: &ArgMatches) -> Result<()> {
 
     Ok(())
 }
+
+async fn set(db: &Db, matches: &ArgMatches) -> Result<()> {
+    let subscription_identifier = matches
+        .get_one::<String>("subscription")
+        .expect("Required by clap");
+    let machine = matches
+        .get_one::<String>("machine")
+        .expect("Required by clap");
+    let reset = BookmarkReset::from_str(
+        matches
+            .get_one::<String>("bookmark")
+            .expect("Required by clap"),
+    )?;
+
+    let subscription = utils::find_subscription(db, subscription_identifier)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not find subscription with identifier {}",
+                subscription_identifier
+            )
+        })?;
+
+    let target = match &reset {
+        BookmarkReset::Earliest => "replay all its events".to_string(),
+        BookmarkReset::Now => "begin at its next event".to_string(),
+        BookmarkReset::Value(value) => format!("use bookmark {}", value),
+    };
+    if !utils::confirm(format!("You are about to make {} {} within subscription \"{}\".\nOther machines are not affected.\nWARNING: You may lose logs or receive duplicates!\nAre you sure?", machine, target, subscription.name()).as_str()) {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    set_source_bookmark(db.as_ref(), &subscription.uuid_string(), machine, &reset).await?;
+    println!("Done");
+    Ok(())
+}
+
+async fn export(db: &Db, matches: &ArgMatches) -> Result<()> {
+    let subscription_identifier = matches
+        .get_one::<String>("subscription")
+        .expect("Required by clap");
+
+    let subscription = utils::find_subscription(db, subscription_identifier)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not find subscription with identifier {}",
+                subscription_identifier
+            )
+        })?;
+
+    println!(
+        "{}",
+        export_bookmarks(db.as_ref(), subscription.uuid()).await?
+    );
+    Ok(())
+}
+
+async fn import(db: &Db, matches: &ArgMatches) -> Result<()> {
+    let subscription_identifier = matches
+        .get_one::<String>("subscription")
+        .expect("Required by clap");
+    let path = matches.get_one::<String>("path").expect("Required by clap");
+
+    let subscription = utils::find_subscription(db, subscription_identifier)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not find subscription with identifier {}",
+                subscription_identifier
+            )
+        })?;
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read exported bookmarks {}", path))?;
+
+    if !utils::confirm(format!("You are about to import the bookmarks of {} in subscription \"{}\".\nExisting bookmarks of the same machines will be replaced.\nWARNING: You may lose logs or receive duplicates!\nAre you sure?", path, subscription.name()).as_str()) {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let count = import_bookmarks(db.as_ref(), subscription.uuid(), &content).await?;
+    println!("{} bookmarks imported", count);
+    Ok(())
+}
@@ -16,6 +16,15 @@ mod config;
 mod skell;
 
 pub async fn run(matches: ArgMatches, help_str: StyledStr) -> Result<()> {
+    // Checking configuration files requires neither the settings nor the
+    // database
+    if let Some(matches) = matches
+        .subcommand_matches("subscriptions")
+        .and_then(|matches| matches.subcommand_matches("check"))
+    {
+        return subscriptions::check(matches);
+    }
+
     let settings = Settings::new(matches.get_one::<String>("config"))
         .map_err(|e| anyhow!("Failed to retrieve configuration: {}", e))?;
     let db = db_from_settings(&settings)
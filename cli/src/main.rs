@@ -29,6 +29,7 @@ async fn main() {
                 .arg(arg!(-e --enabled "Only show enabled subscriptions"))
                 .arg(arg!(-d --disabled "Only show disabled subscriptions"))
                 .group(ArgGroup::new("subscription_list_status").args(["enabled", "disabled"]).required(false))
+                .arg(arg!(-t --tenant <TENANT> "Only show subscriptions of this tenant"))
                 .subcommand(
                     Command::new("new")
                     .about("Creates a new subscription. The newly created subscription will have to be enabled afterward.")
@@ -252,10 +253,20 @@ async fn main() {
                     Command::new("load")
                     .about("Load subscriptions from configuration files")
                     .arg(arg!(<path> "Directory of configuration files or a single configuration file"))
+                    .arg(arg!(--format <FORMAT> "Format of the configuration files: a single subscription in `toml`, or an array of subscriptions in `json` or `yaml`. Detected from the extension of each file by default (`.json`, `.yaml` or `.yml`, `toml` otherwise).").value_parser(["toml", "json", "yaml"]))
                     .arg(arg!(-k --keep "Do not delete subscriptions that are not present in the configuration"))
                     .arg(arg!(-y --yes "Do not prompt for confirmation when <path> is a configuration file and --keep is not used"))
                     .arg(arg!(-e --"allow-empty" "Allow loading from empty directories"))
                     .arg(arg!(-r --revision <REVISION> "Revision name of the configuration. If present, it will be added by openwec as metadata of all events received using this subscription."))
+                    .arg(arg!(--"no-strict-compat" "Only warn about outputs whose format is not compatible with their driver"))
+                    .arg(arg!(-t --tenant <TENANT> "Tenant of the loaded subscriptions. Subscriptions without tenant are assigned to it, and only subscriptions of this tenant are deleted."))
+                )
+                .subcommand(
+                    Command::new("check")
+                    .about("Check subscriptions configuration files without loading them. Neither the database nor the openwec configuration file is used.")
+                    .arg(arg!(<path> "Directory of configuration files or a single configuration file"))
+                    .arg(arg!(--format <FORMAT> "Format of the configuration files: a single subscription in `toml`, or an array of subscriptions in `json` or `yaml`. Detected from the extension of each file by default (`.json`, `.yaml` or `.yml`, `toml` otherwise).").value_parser(["toml", "json", "yaml"]))
+                    .arg(arg!(--"no-strict-compat" "Only warn about outputs whose format is not compatible with their driver"))
                 )
                 .subcommand(
                     Command::new("delete")
@@ -336,6 +347,24 @@ async fn main() {
                     .arg(arg!(<source> "Name or UUID of the source subscription"))
                     .arg(arg!(<destination> "Name or UUID of the destination subscription"))
                 )
+                .subcommand(
+                    Command::new("set")
+                    .about("Set the bookmark of a single machine within a subscription (dangerous!)")
+                    .arg(arg!(<subscription> "Name or UUID of a subscription"))
+                    .arg(arg!(<machine> "Name of the machine"))
+                    .arg(arg!(<bookmark> "\"earliest\" to replay all events, \"now\" to begin at the next event, or a <BookmarkList> element"))
+                )
+                .subcommand(
+                    Command::new("export")
+                    .about("Export the bookmarks of a subscription as JSON")
+                    .arg(arg!(<subscription> "Name or UUID of a subscription"))
+                )
+                .subcommand(
+                    Command::new("import")
+                    .about("Import bookmarks exported by \"openwec bookmarks export\" in a subscription (dangerous!)")
+                    .arg(arg!(<subscription> "Name or UUID of a subscription"))
+                    .arg(arg!(<path> "Path of the exported bookmarks"))
+                )
         )
         .subcommand(
             Command::new("stats")
@@ -22,9 +22,13 @@ fn get_header(uuid: Uuid, name: &str, now: DateTime<Local>) -> String {
 
 # Unique identifier of the subscription
 uuid = "{}"
-# Unique name of the subscription
+# Unique name of the subscription within its tenant
 name = "{}"
 
+# Tenant owning the subscription (optional). Subscriptions of different
+# tenants may share the same name.
+# tenant =
+
 # Subscription query
 query = """
 <QueryList>
@@ -65,6 +69,12 @@ fn get_options() -> String {
 # is unreachable.
 # connection_retry_interval = {}
 
+# Percentage by which the connection retry interval is spread between
+# clients, so that they do not all reconnect at the same time after an
+# outage of the collector. Each client is always given the same interval.
+# Defaults to unset, meaning that all clients use connection_retry_interval.
+# connection_retry_jitter = 20
+
 # The maximum time, in seconds, that the client should aggregate new
 # events before sending them.
 # max_time = {}
@@ -76,7 +86,8 @@ fn get_options() -> String {
 # max_elements =
 
 # The maximum number of bytes in the SOAP envelope used to deliver
-# the events.
+# the events. Must be at least 8192, as required by WS-Management. Lower
+# values than the default may prevent large events from being delivered.
 # max_envelope_size = {}
 
 # If `true`, the event source should replay all possible events that
@@ -84,6 +95,11 @@ fn get_options() -> String {
 # event source.
 # read_existing_events = {}
 
+# Only replay the existing events created after this RFC 3339 timestamp.
+# Older events are left out of the query sent to clients.
+# Requires `read_existing_events = true`.
+# read_existing_events_since = "2024-01-01T00:00:00Z"
+
 # This option determines whether rendering information are to be passed
 # with events or not. `Raw` means that only event data will be passed
 # without any rendering information, whereas `RenderedText` adds
@@ -94,17 +110,106 @@ fn get_options() -> String {
 # are to result in termination of the processing by clients.
 # ignore_channel_error = {}
 
+# Channels whose errors are ignored by clients, such as missing custom
+# channels. When set, `ignore_channel_error` is ignored. Clients apply
+# the setting to the whole query: errors are only ignored if every
+# channel of the query is listed, so that a missing Security channel
+# still fails. Put tolerated channels in their own subscription.
+# ignore_channel_error_for = ["Microsoft-Windows-Sysmon/Operational"]
+
 # This option determines the language in which openwec wants the
 # rendering info data to be translated.
+# It must be a BCP 47 language tag.
 # Example: en-US
 # Defaults to unset, meaning OpenWEC lets the client choose.
 # locale =
 
 # This option determines the language in which openwec wants the
 # numerical data to be formatted.
+# It must be a BCP 47 language tag.
 # Example: en-US
 # Defaults to unset, meaning OpenWEC lets the client choose.
 # data_locale =
+
+# This option determines which authentication mechanism clients must use
+# to read the subscription, regardless of the mechanisms allowed by the
+# server listeners. Clients using another mechanism do not see the
+# subscription and their heartbeats and events are rejected.
+# Possible values are "any", "kerberos" and "tls_cert".
+# require_auth = "any"
+
+# Compression of event batches advertised to clients. With "none", clients
+# are asked to send uncompressed batches, which makes captures easier to
+# read. Compressed batches are accepted anyway.
+# Possible values are "sldc" and "none".
+# compression = "sldc"
+
+# Maximum number of batches written at the same time to outputs whose last
+# write failed. Beyond this limit, clients wait for a running retry to finish.
+# Not limited by default.
+# max_concurrent_retries =
+
+# Maximum number of events and bytes of the batches of this subscription
+# being processed at the same time. When a limit is reached, new batches
+# are handled according to the `server.queue_overflow_policy` setting.
+# Not limited by default.
+# max_queued_events =
+# max_queued_bytes =
+
+# Maximum number of batches of this subscription being parsed and delivered
+# at the same time. Beyond this limit, new batches wait for a running one to
+# finish. Not limited by default.
+# max_concurrent_batches =
+
+# Maximum number of distinct clients sending events for this subscription at
+# the same time. Beyond this limit, events of new clients are rejected and
+# sent again later by the clients. Not limited by default.
+# max_concurrent_clients =
+
+# Maximum number of events remembered to drop the events sent again by
+# clients, identified by their (Computer, Channel, EventRecordID). When the
+# window is full, the least recently seen events are forgotten. Duplicated
+# events are not dropped by default.
+# dedup_window = 100000
+
+# Time (in seconds) after which events are forgotten by the dedup window.
+# Events are only forgotten when the window is full by default.
+# dedup_window_ttl = 3600
+
+# How the events of a batch are delivered to the outputs: "parallel" or
+# "sequential". In "sequential" mode, each event is written by every output,
+# in declaration order, before the next one. This is slower, as events are
+# written one at a time.
+# Defaults to "parallel".
+# output_mode = "parallel"
+
+# Time (in seconds) after which events written to outputs expire, using the
+# native expiration mechanism of their sink: Redis lists expire as a whole
+# after the last write and older entries of Redis streams are trimmed.
+# Other drivers ignore it (a warning is logged when the subscription is
+# loaded). Events do not expire by default.
+# event_ttl =
+
+# Sub-elements of RenderingInfo removed from events before they are
+# formatted, to save space when only structured data is needed.
+# Possible values are "message", "level", "task", "opcode", "channel",
+# "provider" and "keywords". Only used when content_format is "RenderedText".
+# Nothing is removed by default.
+# trim_rendering = ["message"]
+
+# Manifest options left out of the subscription sent to clients which
+# rejected it (for example because of an unsupported locale), so that they
+# can enroll again. Possible values are "content_format",
+# "ignore_channel_error", "read_existing_events", "cdata", "locale",
+# "data_locale" and "max_elements". Rejected manifests are only logged and
+# counted by default.
+# enrollment_fallback = ["locale", "data_locale"]
+
+# Whether the query is checked when the subscription is loaded: it must be
+# well-formed XML, with a <QueryList> root element containing at least one
+# <Query> element, each of them containing at least one <Select Path=...>
+# element. Queries are not checked by default.
+# validate_query = true
 "#,
         format_bool(DEFAULT_ENABLED),
         DEFAULT_HEARTBEAT_INTERVAL,
@@ -127,9 +232,12 @@ fn get_filter() -> String {
 # - "Only": only the listed clients will be able to read the subscription
 # - "Except": everyone but the listed clients will be able to read the subscription
 #
-# Types: KerberosPrinc, TLSCertSubject, MachineID
+# Types: KerberosPrinc, TLSCertSubject, TLSCertSan (DNS and URI SubjectAltName
+# entries of the client certificate), MachineID, IpRange (targets are CIDRs such
+# as "10.0.0.0/8")
 #
-# Flags: GlobPattern, CaseInsensitive
+# Flags: GlobPattern, Regex (targets are regular expressions which must match the
+# whole value), CaseInsensitive. GlobPattern and Regex can not be used together.
 # Filters are case-sensitive by default.
 #
 # By default, everyone can read the subscription.
@@ -152,8 +260,64 @@ fn get_outputs() -> String {
 #
 
 # For each output, you must configure a driver and a format.
-# The format can be one of: "Raw", "RawJson", "Json", "Nxlog"
-# The driver can be one of: "Files", "Kafka", "Tcp", "Redis", "UnixDatagram"
+# The format can be one of: "Raw", "RawJson", "Json", "Nxlog", "Csv", "Rfc5424", "Cef",
+# "Leef", "Protobuf", "Ecs", "Msgpack", "Template", "Avro"
+# The "Csv" format requires a `format_config`, for example:
+# format_config = { columns = ["System.EventID", "EventData.TargetUserName"], delimiter = ",", header = true }
+# The "Template" format requires a `format_config`, for example:
+# format_config = { template = '{TimeCreated} {Computer} [{EventID}] {RenderingInfo}' }
+# The driver can be one of: "Files", "Kafka", "Tcp", "Redis", "UnixDatagram", "SyslogTls",
+# "Http", "Syslog", "UnixStream", "Stdout", "Stderr", "Nats", "Mqtt", "EventHubs", "Grpc",
+# "SplunkHec", "Loki", "Zmq"
+# Some formats can not be used with some drivers: "Raw" with "Stdout", "Stderr" or with
+# "Tcp" or "UnixStream" using "newline" framing, "Protobuf" and "Msgpack" with "Files",
+# "Protobuf", "Msgpack" and "Avro" with "Stdout", "Stderr", "SplunkHec", "Loki" or with
+# "Tcp", "UnixStream" or "Http" using "newline" framing, "Csv" with `header = true`
+# with any driver but "Files", any format but "Rfc5424" with "SyslogTls", any format
+# but "Json", "RawJson" and "Nxlog" with "Http" using "json_array" framing, and
+# "Rfc5424" with "Syslog".
+# An output with `source_first_seen = true` only receives a JSON event the first
+# time a source sends events for this subscription.
+# An output with `summary = true` only receives one JSON record per received
+# batch of events, containing the number of events and their size.
+# An output with `dead_letter = true` only receives JSON records describing the
+# events that other outputs failed to format or skipped because they are degraded.
+# An output can keep only a percentage of the events of some levels, for example
+# `sampling = { Information = 10, Verbose = 0 }`. Unlisted levels are always kept.
+# The "Rfc5424" format accepts structured data elements in `format_config`, for example:
+# format_config = { enterprise_number = 32473, structured_data = [{ id = "winlog", params = { event_id = "System.EventID" } }] }
+# The "Cef" format accepts an optional `format_config`, for example:
+# format_config = { version = 0, device_vendor = "OpenWEC", device_product = "OpenWEC" }
+# The "Protobuf" format emits the built-in `openwec.event.v1.Event` message, unless its
+# `format_config` gives a serialized FileDescriptorSet (`protoc -o`) and the full name
+# of the message to emit, for example:
+# format_config = { descriptor_set = "/etc/openwec/event.binpb", message = "my.package.Event" }
+# Json, Nxlog, Csv and Rfc5424 outputs can rename fields using a map from source field
+# path to target field path, for example
+# `field_map = { "System.Computer" = "host", "System.EventID" = "event_code" }`.
+# Unmapped fields are kept unless `drop_unmapped = true`.
+# Events can be routed to outputs depending on their provider using a
+# `[route_by_provider]` table, mapping provider name patterns to output names
+# (set with the `name` output option), for example
+# `"Microsoft-Windows-Sysmon" = "sysmon"`. Outputs that are not referenced
+# receive the events that match no pattern.
+# An output whose writes are repeatedly slow can be skipped for a while, for example
+# `degradation = { send_timeout_ms = 500, slow_responses = 3, cooldown_secs = 60 }`.
+# An output can only receive the events whose fields changed since the last event
+# of the same source, for example
+# `dedupe_on_change = { fields = ["EventData.Setting", "EventData.Value"] }`.
+# Kafka outputs and Tcp outputs using "length_prefixed" framing can compress each
+# formatted event with `payload_compression = "gzip"` ("none", "gzip", "zstd" or "lz4").
+# An output can drop the events created more than a number of seconds ago, which
+# clients send once the output recovers from an outage, with `max_queue_age = 300`.
+# Outputs using the "Json" or "Nxlog" formats can add the version, build and node
+# of the collector to each event under a "collector" key with
+# `include_collector_info = true`.
+# Outputs using the "Json" format can pretty-print events, copy their creation
+# time to another field and add their raw XML under "OpenWEC.RawXml", for example
+# `format_options = { pretty = true, timestamp_field = "@timestamp", include_raw_xml = true }`.
+# Writes to an output taking more than a number of milliseconds can be aborted
+# and handled as failed writes with `write_timeout_ms = 5000`.
 
 # Configure a Files output
 # [[outputs]]
@@ -163,7 +327,14 @@ fn get_outputs() -> String {
 # Files driver has the following parameters:
 # - path (required): the path in which files will be written. It can be parameterized
 #       with variables using the syntax {variable} (see available variables in documentation)
+# - rotation (optional, defaults to undefined): rotate each file to <path>.<timestamp> when
+#       writing events would make it larger than `max_size_bytes` or when it has been
+#       created more than `max_age_secs` seconds ago
+# - compression (optional, defaults to "none"): "none" or "gzip". Compressed files
+#       are written to <path>.gz
 # config = { path = "/var/log/openwec/{ip:2}/{ip:3}/{ip}/{principal}/messages" }
+# config = { path = "/var/log/openwec/{ip}/messages", rotation = { max_size_bytes = 104857600, max_age_secs = 86400 } }
+# config = { path = "/var/log/openwec/{ip}/messages", compression = "gzip" }
 
 
 # Configure a Kafka output
@@ -177,13 +348,21 @@ fn get_outputs() -> String {
 #      sent to librdkafka (https://docs.confluent.io/platform/current/clients/librdkafka/html/md_CONFIGURATION.html)
 #      You should probably configure this in OpenWEC settings `outputs.kafka.options` if all your
 #      outputs using the Kafka driver connect to the same Kafka cluster.
+# - headers (optional, defaults to undefined): headers attached to each message.
+#      Values may contain the tokens {subscription}, {ip}, {event_id}, {channel}
+#      and {format}, resolved for each event.
+# - acks (optional, defaults to undefined): producer acks, one of "0", "1" or "all"
+# - compression_type (optional, defaults to undefined): producer compression, one of
+#      "none", "gzip", "snappy", "lz4" or "zstd"
+# - enable_idempotence (optional, defaults to undefined): producer idempotence
+#      These settings are merged with `options` and take precedence over them.
 # config = { topic = "openwec", options = { "bootstrap.servers" = "localhost:9092" } }
 
 
 # Configure a Tcp output
 # [[outputs]]
 # driver = "Tcp"
-# format = "Raw"
+# format = "Json"
 
 # Tcp driver has the following parameters:
 # - host (required): Hostname or IP Address to send events to
@@ -197,7 +376,26 @@ fn get_outputs() -> String {
 #       This certificate will be presented to the server.
 # - tls_key (optional, defaults to undefined): Path to the private key corresponding to the
 #       specified certificate (PEM format).
+# - tls_pinned_sha256 (optional, defaults to undefined): SHA-256 fingerprints of the accepted
+#       server certificates (e.g. from `openssl x509 -noout -fingerprint -sha256`). The server
+#       certificate must match one of them, in addition to being validated against
+#       tls_certificate_authorities if set. tls_certificate_authorities may be omitted if
+#       fingerprints are pinned.
+# - framing (optional, defaults to "newline"): How events are delimited in the stream.
+#       One of "newline", "length_prefixed" (4-byte big-endian length) or
+#       "octet_counting" (`MSG-LEN SP`).
+# - reconnect_initial_ms (optional, defaults to 500): Delay (in milliseconds) before trying to
+#       connect again after a failure. It is doubled after each consecutive failure.
+# - reconnect_max_ms (optional, defaults to 30000): Maximum delay between connection attempts
+# - buffer_max_events (optional, defaults to undefined): Keep up to this number of events in
+#       memory while the connection is down and send them in order once it is restored.
+#       The oldest events are dropped when the buffer is full.
+# - tcp_keepalive_secs (optional, defaults to undefined): Enable TCP keepalive. Probes are sent
+#       after this number of seconds of inactivity and then at this interval, so that a dead
+#       connection is detected and established again before the next write.
+# - tcp_nodelay (optional, defaults to false): Disable Nagle's algorithm (`TCP_NODELAY`)
 # config = { host = "localhost", port = 5000 }
+# config = { host = "localhost", port = 5000, buffer_max_events = 10000 }
 
 
 # Configure a Redis output
@@ -207,7 +405,11 @@ fn get_outputs() -> String {
 
 # Redis driver has the following parameters:
 # - addr (required): Hostname or IP Address of the Redis server
-# - list (required): Name of the Redis list to push events to
+# - list (required): Name of the Redis list to push events to, or key of the
+#       stream to append events to in "stream" mode
+# - mode (optional, defaults to "list"): "list" (LPUSH) or "stream" (XADD)
+# - max_len (optional, defaults to undefined): approximate maximum length of the
+#       stream (MAXLEN ~). Only supported in "stream" mode
 # config = { addr = "localhost", list = "openwec" }
 
 
@@ -219,6 +421,215 @@ fn get_outputs() -> String {
 # UnixDatagram driver has the following parameters:
 # - path (required): Path of the Unix socket to send events to
 # config = { path = "/tmp/openwec.socket" }
+
+
+# Configure a UnixStream output
+# [[outputs]]
+# driver = "UnixStream"
+# format = "Json"
+
+# UnixStream driver has the following parameters:
+# - path (required): Path of the Unix socket to send events to
+# - framing (optional, defaults to "newline"): How events are delimited in the stream.
+#       One of "newline", "length_prefixed" (4-byte big-endian length) or
+#       "octet_counting" (`MSG-LEN SP`).
+# - reconnect_initial_ms (optional, defaults to 500): Delay (in milliseconds) before trying to
+#       connect again after a failure. It is doubled after each consecutive failure.
+# - reconnect_max_ms (optional, defaults to 30000): Maximum delay between connection attempts
+# config = { path = "/run/openwec/events.socket", framing = "length_prefixed" }
+
+
+# Configure a Stdout output (or "Stderr")
+# [[outputs]]
+# driver = "Stdout"
+# format = "Json"
+
+# Stdout and Stderr drivers have the following parameters:
+# - flush_each (optional, defaults to false): Flush the stream after each event instead of
+#       after each batch
+# config = { flush_each = true }
+
+
+# Configure a SyslogTls output (RFC 5425)
+# [[outputs]]
+# driver = "SyslogTls"
+# format = "Rfc5424"
+
+# SyslogTls driver has the following parameters:
+# - host (required): Hostname of the syslog relay
+# - port (optional, defaults to 6514): Port of the syslog relay
+# - tls_certificate_authorities (required): Validate server certificate chain against
+#       these authorities. You can define multiple files or paths.
+# - tls_certificate (required): Path to the client certificate in PEM format.
+# - tls_key (required): Path to the private key of the client certificate (PEM format).
+# Events are always framed using octet counting.
+# config = { host = "syslog.windomain.local", tls_certificate_authorities = ["/etc/openwec/ca.pem"], tls_certificate = "/etc/openwec/client.pem", tls_key = "/etc/openwec/client.key" }
+
+
+# Configure an Http output
+# [[outputs]]
+# driver = "Http"
+# format = "Json"
+
+# Http driver has the following parameters:
+# - url (required): URL events are sent to using POST requests (http:// or https://)
+# - headers (optional): Headers added to each request
+# - batch_size (optional, defaults to 100): Maximum number of events sent in a request
+# - batch_timeout_ms (optional, defaults to 1000): Maximum time (in milliseconds) events
+#       wait for their batch to fill before being sent
+# - idle_flush_ms (optional, defaults to undefined): Send an incomplete batch once no
+#       event has been written for this time (in milliseconds), lower than batch_timeout_ms
+# - tls_certificate_authorities (required with https): Validate server certificate chain
+#       against these authorities. You can define multiple files or paths.
+# - framing (optional, defaults to "newline"): How events are delimited in the body of
+#       requests, either "newline" or "json_array"
+# Requests answered with a 5xx status are retried with an exponential backoff.
+# config = { url = "https://collector.windomain.local/events", headers = { "Authorization" = "Bearer <token>" }, tls_certificate_authorities = ["/etc/openwec/ca.pem"], framing = "json_array" }
+
+
+# Configure a Syslog output (RFC 5424)
+# [[outputs]]
+# driver = "Syslog"
+# format = "Json"
+
+# Syslog driver has the following parameters:
+# - host (required): Hostname or IP address of the syslog server
+# - port (optional, defaults to 514, or 6514 with "tls"): Port of the syslog server
+# - transport (optional, defaults to "udp"): "udp", "tcp" or "tls"
+# - facility (optional, defaults to "user"): Syslog facility of messages, such as "auth"
+#       or "local4"
+# - app_name (optional, defaults to "openwec"): APP-NAME of messages
+# - tls_certificate_authorities (required with "tls"): Validate server certificate chain
+#       against these authorities. You can define multiple files or paths.
+# - tls_certificate (optional, "tls" only): Path to the client certificate in PEM format.
+# - tls_key (optional, "tls" only): Path to the private key of the client certificate
+#       (PEM format).
+# Messages are framed using octet counting with "tcp" and "tls", and truncated to fit in
+# a datagram with "udp".
+# config = { host = "syslog.windomain.local", transport = "tcp", facility = "local4" }
+
+
+# Configure a Nats output
+# [[outputs]]
+# driver = "Nats"
+# format = "Json"
+
+# Nats driver has the following parameters:
+# - servers (required): URL of the NATS server (nats:// or tls://). You can define
+#       multiple servers.
+# - subject (required): Subject of messages, which may contain {ip}, {principal} and
+#       {node}. Dots, whitespaces and wildcards are replaced by "_" in their values.
+# - jetstream (optional, defaults to false): Publish messages to JetStream and wait for
+#       their acknowledgement, retrying up to 2 times
+# - credentials_file (optional): NATS credentials file used to authenticate
+# - tls_certificate_authorities (required with tls://): Validate server certificate chain
+#       against these authorities. You can define multiple files or paths.
+# config = { servers = ["tls://nats.windomain.local:4222"], subject = "openwec.{ip}", jetstream = true, tls_certificate_authorities = ["/etc/openwec/ca.pem"] }
+
+
+# Configure a Mqtt output
+# [[outputs]]
+# driver = "Mqtt"
+# format = "Json"
+
+# Mqtt driver has the following parameters:
+# - broker (required): Hostname or IP address of the broker
+# - port (optional, defaults to 1883, or 8883 with TLS): Port of the broker
+# - topic (required): Topic of messages, which may contain {ip}, {principal} and {node}.
+#       Slashes and wildcards are replaced by "_" in their values.
+# - qos (optional, defaults to 0): Quality of service of messages (0, 1 or 2). With 1 and
+#       2, writes wait for the broker to acknowledge each message.
+# - client_id (required): Client identifier of the MQTT session, which must be unique
+# - username (optional): User name used to authenticate
+# - password (optional): Password used to authenticate, along with username
+# - tls_certificate_authorities (optional): Connect using TLS and validate broker
+#       certificate chain against these authorities. You can define multiple files or paths.
+# config = { broker = "mqtt.windomain.local", topic = "openwec/{ip}", qos = 1, client_id = "openwec", tls_certificate_authorities = ["/etc/openwec/ca.pem"] }
+
+# Configure an EventHubs output
+# [[outputs]]
+# driver = "EventHubs"
+# format = "Json"
+
+# EventHubs driver has the following parameters:
+# - namespace (required): Name of the Event Hubs namespace, or its fully qualified domain
+#       name outside of the Azure public cloud
+# - hub (required): Name of the Event Hub
+# - connection_string (optional): Shared access connection string of the namespace or of
+#       the hub
+# - managed_identity (optional, defaults to false): Authenticate using the managed identity
+#       of the host instead of a connection string
+# - partition_key (optional): Partition key of events, which may contain {ip}, {principal}
+#       and {node}. Events which share a partition key are kept in order.
+# config = { namespace = "openwec", hub = "events", managed_identity = true, partition_key = "{ip}" }
+
+# Configure a Grpc output
+# [[outputs]]
+# driver = "Grpc"
+# format = "Json"
+
+# Grpc driver has the following parameters:
+# - endpoint (required): URL of the gRPC service implementing openwec.output.v1.EventIngest
+#       (http:// or https://)
+# - metadata (optional): Metadata (headers) sent when the stream is opened
+# - tls_certificate_authorities (required with https://): Validate server certificate chain
+#       against these authorities. You can define multiple files or paths.
+# config = { endpoint = "https://ingest.windomain.local:50051", metadata = { "authorization" = "Bearer <token>" }, tls_certificate_authorities = ["/etc/openwec/ca.pem"] }
+
+# Configure a SplunkHec output
+# [[outputs]]
+# driver = "SplunkHec"
+# format = "Json"
+
+# SplunkHec driver has the following parameters:
+# - url (required): URL of the HTTP Event Collector event endpoint (http:// or https://),
+#       usually ending with /services/collector/event
+# - token (required): HEC token used to authenticate requests
+# - index (optional): Index of events, defaults to the default index of the token
+# - sourcetype (optional): Source type of events
+# - source (optional): Source of events, which may contain {ip}, {principal} and {node}
+# - batch_size (optional, defaults to 100): Maximum number of events sent in a request
+# - batch_timeout_ms (optional, defaults to 1000): Maximum time (in milliseconds) events
+#       wait for their batch to fill before being sent
+# - idle_flush_ms (optional, defaults to undefined): Send an incomplete batch once no
+#       event has been written for this time (in milliseconds), lower than batch_timeout_ms
+# - tls_certificate_authorities (required with https://): Validate server certificate chain
+#       against these authorities. You can define multiple files or paths.
+# config = { url = "https://splunk.windomain.local:8088/services/collector/event", token = "<token>", index = "wineventlog", sourcetype = "openwec:json", source = "openwec:{ip}", tls_certificate_authorities = ["/etc/openwec/ca.pem"] }
+
+# Configure a Loki output
+# [[outputs]]
+# driver = "Loki"
+# format = "Json"
+
+# Loki driver has the following parameters:
+# - url (required): URL of Loki (http:// or https://). Events are pushed to /loki/api/v1/push.
+# - labels (optional): Labels of the stream with a fixed value
+# - label_templates (optional): Labels of the stream whose value may contain {ip}, {principal}
+#       and {node}. At least one label must be set.
+# - allow_high_cardinality_labels (optional, defaults to false): Allow {principal} in label
+#       templates, which creates a stream per client
+# - batch_size (optional, defaults to 100): Maximum number of events sent in a request
+# - batch_timeout_ms (optional, defaults to 1000): Maximum time (in milliseconds) events
+#       wait for their batch to fill before being sent
+# - idle_flush_ms (optional, defaults to undefined): Send an incomplete batch once no
+#       event has been written for this time (in milliseconds), lower than batch_timeout_ms
+# - tls_certificate_authorities (required with https://): Validate server certificate chain
+#       against these authorities. You can define multiple files or paths.
+# config = { url = "https://loki.windomain.local", labels = { "job" = "openwec" }, label_templates = { "host" = "{ip}" }, tls_certificate_authorities = ["/etc/openwec/ca.pem"] }
+
+# Configure a Zmq output
+# [[outputs]]
+# driver = "Zmq"
+# format = "Json"
+
+# Zmq driver has the following parameters:
+# - endpoint (required): ZeroMQ endpoint the socket connects to (tcp:// or ipc://)
+# - socket_type (optional, defaults to "push"): "push" or "pub". Each event is sent as
+#       a message.
+# - hwm (optional, defaults to 1000): Maximum number of messages queued by the socket.
+#       Once it is reached, writes wait or fail depending on `server.queue_overflow_policy`.
+# config = { endpoint = "tcp://collector.windomain.local:5555", socket_type = "push", hwm = 10000 }
 "#
     .to_string()
 }
@@ -1,13 +1,15 @@
 use common::{
     database::Db,
     encoding::decode_utf16le,
+    models::config::{check_config_files, diff_subscriptions, ConfigFormat},
     settings::Settings,
     subscription::{
-        ContentFormat, FilesConfiguration, KafkaConfiguration, ClientFilter, ClientFilterOperation,
-        RedisConfiguration, SubscriptionData, SubscriptionMachineState, SubscriptionOutput,
-        SubscriptionOutputDriver, SubscriptionOutputFormat, TcpConfiguration,
-        UnixDatagramConfiguration,
+        check_locale, check_max_envelope_size, check_tenant, ClientFilter, ClientFilterOperation,
+        ContentFormat, FilesConfiguration, KafkaConfiguration, RedisConfiguration,
+        SubscriptionData, SubscriptionMachineState, SubscriptionOutput, SubscriptionOutputDriver,
+        SubscriptionOutputFormat, TcpConfiguration, UnixDatagramConfiguration,
     },
+    utils::timestamp_to_local_date,
 };
 use roxmltree::{Document, Node};
 use std::{
@@ -98,22 +100,69 @@ async fn list(db: &Db, matches: &ArgMatches) -> Result<()> {
     if enabled && disabled {
         bail!("Enabled and disabled both set");
     }
+    let tenant = matches.get_one::<String>("tenant");
 
+    let now: i64 = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs()
+        .try_into()?;
     for subscription in db
         .get_subscriptions()
         .await
         .context("Failed to retrieve subscriptions from database")?
     {
+        if tenant.is_some() && subscription.tenant() != tenant {
+            continue;
+        }
         if enabled && subscription.enabled()
             || (disabled && !subscription.enabled())
             || (!disabled && !enabled)
         {
-            println!("{}", subscription.short());
+            println!(
+                "{} - {}",
+                subscription.short(),
+                sources(db, &subscription, now).await?
+            );
         }
     }
     Ok(())
 }
 
+/// Describes the sources enrolled in a subscription, i.e. the machines that
+/// have sent heartbeats or events during its heartbeat interval. Without any,
+/// tells since when the subscription has had no enrolled source.
+async fn sources(db: &Db, subscription: &SubscriptionData, now: i64) -> Result<String> {
+    let stats = db
+        .get_stats(
+            &subscription.uuid_string(),
+            now - (subscription.heartbeat_interval() as i64),
+        )
+        .await
+        .context("Failed to retrieve subscription stats from database")?;
+    let enrolled = stats.enrolled_machines_count();
+    if enrolled > 0 {
+        return Ok(format!("{} enrolled sources", enrolled));
+    }
+    let last_seen = db
+        .get_heartbeats_by_subscription(&subscription.uuid_string())
+        .await
+        .context("Failed to retrieve heartbeats from database")?
+        .iter()
+        .map(|heartbeat| {
+            heartbeat
+                .last_seen()
+                .max(heartbeat.last_event_seen().unwrap_or_default())
+        })
+        .max();
+    Ok(match last_seen {
+        Some(last_seen) => format!(
+            "no enrolled source since {}",
+            timestamp_to_local_date(last_seen)?.to_rfc3339()
+        ),
+        None => "no enrolled source".to_string(),
+    })
+}
+
 async fn show(db: &Db, matches: &ArgMatches) -> Result<()> {
     let subscription = find_subscription(db, matches)
         .await
@@ -323,6 +372,7 @@ async fn edit(db: &Db, matches: &ArgMatches) -> Result<()> {
             subscription.max_envelope_size(),
             max_envelope_size
         );
+        check_max_envelope_size(*max_envelope_size)?;
         subscription.set_max_envelope_size(*max_envelope_size);
     }
     if let Some(true) = matches.get_one::<bool>("enable") {
@@ -357,6 +407,7 @@ async fn edit(db: &Db, matches: &ArgMatches) -> Result<()> {
 
     if matches.contains_id("locale") {
         if let Some(locale) = matches.get_one::<String>("locale") {
+            check_locale(locale)?;
             debug!(
                 "Update locale from {:?} to {:?}",
                 subscription.locale(),
@@ -370,6 +421,7 @@ async fn edit(db: &Db, matches: &ArgMatches) -> Result<()> {
 
     if matches.contains_id("data-locale") {
         if let Some(data_locale) = matches.get_one::<String>("data-locale") {
+            check_locale(data_locale)?;
             debug!(
                 "Update data-locale from {:?} to {:?}",
                 subscription.data_locale(),
@@ -377,7 +429,7 @@ async fn edit(db: &Db, matches: &ArgMatches) -> Result<()> {
             );
             subscription.set_data_locale(Some(data_locale.to_string()));
         } else {
-            subscription.set_locale(None);
+            subscription.set_data_locale(None);
         }
     }
 
@@ -447,6 +499,7 @@ async fn new(db: &Db, matches: &ArgMatches) -> Result<()> {
     }
 
     if let Some(max_envelope_size) = matches.get_one::<u32>("max-envelope-size") {
+        check_max_envelope_size(*max_envelope_size)?;
         subscription.set_max_envelope_size(*max_envelope_size);
     }
 
@@ -975,14 +1028,69 @@ async fn reload(db: &Db, matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Format of the config files given by `--format`, detected from their
+/// extension if it is not set
+fn config_format(matches: &ArgMatches) -> Result<Option<ConfigFormat>> {
+    matches
+        .get_one::<String>("format")
+        .map(|format| ConfigFormat::from_str(format))
+        .transpose()
+        .context("Invalid config format")
+}
+
+pub fn check(matches: &ArgMatches) -> Result<()> {
+    let path = matches
+        .get_one::<String>("path")
+        .ok_or_else(|| anyhow!("Missing argument path"))?;
+    let no_strict_compat = matches
+        .get_one::<bool>("no-strict-compat")
+        .expect("Defaulted by clap");
+
+    let format = config_format(matches)?;
+    let reports = check_config_files(Path::new(path), format, !no_strict_compat, |_| Ok(()))?;
+    let mut errors = 0;
+    let mut warnings = 0;
+    for report in reports.iter() {
+        if report.errors().is_empty() && report.warnings().is_empty() {
+            println!("{}: ok", report.path().display());
+        }
+        for error in report.errors() {
+            println!("{}: error: {}", report.path().display(), error);
+        }
+        for warning in report.warnings() {
+            println!("{}: warning: {}", report.path().display(), warning);
+        }
+        errors += report.errors().len();
+        warnings += report.warnings().len();
+    }
+    println!(
+        "Checked {} files: {} errors, {} warnings",
+        reports.len(),
+        errors,
+        warnings
+    );
+
+    ensure!(errors == 0, "Some configuration files are invalid");
+    Ok(())
+}
+
 async fn load(db: &Db, matches: &ArgMatches) -> Result<()> {
     let path = matches
         .get_one::<String>("path")
         .ok_or_else(|| anyhow!("Missing argument path"))?;
     let keep = matches.get_one::<bool>("keep").expect("Defaulted by clap");
     let yes = matches.get_one::<bool>("yes").expect("Defaulted by clap");
-    let allow_empty = matches.get_one::<bool>("allow-empty").expect("Defaulted by clap");
+    let allow_empty = matches
+        .get_one::<bool>("allow-empty")
+        .expect("Defaulted by clap");
     let revision = matches.get_one::<String>("revision");
+    let no_strict_compat = matches
+        .get_one::<bool>("no-strict-compat")
+        .expect("Defaulted by clap");
+    let tenant = matches.get_one::<String>("tenant");
+    if let Some(tenant) = tenant {
+        check_tenant(tenant)?;
+    }
 
     let path_obj = Path::new(path);
     if !path_obj.exists() {
@@ -994,8 +1102,26 @@ async fn load(db: &Db, matches: &ArgMatches) -> Result<()> {
         return Ok(())
     }
 
-    let subscriptions =
-        config::load_from_path(path, revision).context("Failed to load config files")?;
+    let format = config_format(matches)?;
+    let mut subscriptions = config::load_from_path(path, format, revision, !no_strict_compat)
+        .context("Failed to load config files")?;
+
+    if let Some(tenant) = tenant {
+        for subscription in subscriptions.iter_mut() {
+            match subscription.tenant() {
+                None => {
+                    subscription.set_tenant(Some(tenant.clone()));
+                }
+                Some(other) if other != tenant => bail!(
+                    "Subscription {} belongs to tenant {}, not {}",
+                    subscription.name(),
+                    other,
+                    tenant
+                ),
+                Some(_) => (),
+            }
+        }
+    }
 
     if subscriptions.is_empty() && !allow_empty {
         bail!("Could not find any subscriptions");
@@ -1013,24 +1139,34 @@ async fn load(db: &Db, matches: &ArgMatches) -> Result<()> {
             return Ok(());
         }
     }
-    // Build a set of subscriptions uuids
-    let mut uuids = HashSet::new();
+    let stored_subscriptions = db.get_subscriptions().await?;
+    let diff = diff_subscriptions(&stored_subscriptions, &subscriptions);
 
-    // Insert or update subscriptions
+    // Insert or update subscriptions. Unchanged subscriptions are left
+    // untouched, so that openwecd does not reload them.
     for subscription in subscriptions.iter() {
-        println!("+ Load subscription {}", subscription.name());
+        if diff.unchanged.contains(subscription.uuid()) {
+            println!(
+                "= Subscription {} is unchanged",
+                subscription.qualified_name()
+            );
+            continue;
+        }
+        println!("+ Load subscription {}", subscription.qualified_name());
         db.store_subscription(subscription)
             .await
             .context("Failed to store subscription in db")?;
-        uuids.insert(subscription.uuid());
     }
 
     if !keep {
         // Remove other subscriptions
-        let all_subscriptions = db.get_subscriptions().await?;
-        for subscription in all_subscriptions.iter() {
-            if !uuids.contains(subscription.uuid()) {
-                println!("+ Remove subscription {}", subscription.name());
+        for subscription in stored_subscriptions.iter() {
+            // Subscriptions of other tenants are left untouched
+            if tenant.is_some() && subscription.tenant() != tenant {
+                continue;
+            }
+            if diff.deleted.contains(subscription.uuid()) {
+                println!("+ Remove subscription {}", subscription.qualified_name());
                 db.delete_subscription(&subscription.uuid_string()).await?;
             }
         }
@@ -0,0 +1,116 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use uuid::Uuid;
+
+fn subscription(uuid: &str, name: &str, output_enabled: bool) -> String {
+    format!(
+        r#"
+uuid = "{}"
+name = "{}"
+query = """
+<QueryList></QueryList>
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+enabled = {}
+config = {{ path = "/tmp/{}.socket" }}
+"#,
+        uuid, name, output_enabled, name
+    )
+}
+
+fn check(path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_openwec"))
+        // The configuration file must not be needed
+        .args([
+            "-c",
+            "/nonexistent/openwec.conf.toml",
+            "subscriptions",
+            "check",
+        ])
+        .arg(path)
+        .output()
+        .expect("Failed to run openwec")
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("openwec-check-{}", Uuid::new_v4()));
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    dir
+}
+
+#[test]
+fn test_subscriptions_check() {
+    let dir = temp_dir();
+    fs::write(
+        dir.join("a.toml"),
+        subscription("b00bf259-3ba9-4faf-b58e-d0e9a3757798", "first", true),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("nested/b.toml"),
+        subscription("c6e6dd10-8b7e-4a57-9e7d-3c8a4a3f7d6e", "second", false),
+    )
+    .unwrap();
+
+    // Valid files, with a warning for the subscription whose outputs are
+    // all disabled
+    let output = check(&dir);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(stdout.contains(&format!("{}: ok", dir.join("a.toml").display())));
+    assert!(stdout.contains(&format!(
+        "{}: warning: Subscription has no enabled output",
+        dir.join("nested/b.toml").display()
+    )));
+    assert!(stdout.contains("Checked 2 files: 0 errors, 1 warnings"));
+
+    // An unknown option and a duplicated uuid
+    fs::write(
+        dir.join("c.toml"),
+        subscription("4cc6cd7a-38c3-4fbe-8cd0-7d5a6e3c2d1b", "third", true)
+            .replace("query =", "querry ="),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("d.toml"),
+        subscription("b00bf259-3ba9-4faf-b58e-d0e9a3757798", "fourth", true),
+    )
+    .unwrap();
+
+    let output = check(&dir);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains(&format!(
+        "{}: error: Failed to parse file {}",
+        dir.join("c.toml").display(),
+        dir.join("c.toml").display()
+    )));
+    assert!(stdout.contains("unknown field `querry`"));
+    assert!(stdout.contains(&format!(
+        "{}: error: Subscription uuid B00BF259-3BA9-4FAF-B58E-D0E9A3757798 is also used in {}",
+        dir.join("d.toml").display(),
+        dir.join("a.toml").display()
+    )));
+    assert!(stdout.contains("Checked 4 files: 2 errors, 1 warnings"));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Some configuration files are invalid")
+    );
+
+    // A single file can be checked
+    let output = check(&dir.join("a.toml"));
+    assert_eq!(output.status.code(), Some(0));
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    // A missing path is an error
+    let output = check(&dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not exist"));
+}
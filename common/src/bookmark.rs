@@ -1,6 +1,133 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+/// Reserved bookmark asking sources to replay all the events matching the
+/// subscription query (DSP0226 10.2.6)
+pub const BOOKMARK_EARLIEST: &str = "http://schemas.dmtf.org/wbem/wsman/1/wsman/bookmark/earliest";
+
+/// Stored bookmark asking a source to begin at its next event, whatever
+/// `read_existing_events` is. It is never sent to sources: the absence of
+/// bookmark means "begin at the next available event".
+pub const BOOKMARK_NOW: &str = "openwec:now";
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BookmarkData {
     pub subscription: String,
     pub machine: String,
     pub bookmark: String,
 }
+
+/// Bookmarks of a subscription, exported as JSON to be imported in another
+/// database, for example when the subscription is moved to a new collector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BookmarksExport {
+    /// UUID of the exported subscription
+    pub subscription: String,
+    pub bookmarks: Vec<MachineBookmark>,
+}
+
+/// Bookmark list of a single machine, as sent by the machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineBookmark {
+    pub machine: String,
+    pub bookmark: String,
+}
+
+/// New bookmark of a single source, set by an administrator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookmarkReset {
+    /// Replay all the events matching the subscription query
+    Earliest,
+    /// Begin at the next event
+    Now,
+    /// A bookmark list, as sent by sources
+    Value(String),
+}
+
+impl BookmarkReset {
+    /// Returns the bookmark stored in database
+    pub fn bookmark(&self) -> &str {
+        match self {
+            BookmarkReset::Earliest => BOOKMARK_EARLIEST,
+            BookmarkReset::Now => BOOKMARK_NOW,
+            BookmarkReset::Value(value) => value,
+        }
+    }
+}
+
+impl FromStr for BookmarkReset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "earliest" => Ok(BookmarkReset::Earliest),
+            "now" => Ok(BookmarkReset::Now),
+            value => {
+                // Bookmarks sent by sources are <BookmarkList> elements
+                if !value.starts_with("<BookmarkList")
+                    || !(value.ends_with("</BookmarkList>") || value.ends_with("/>"))
+                {
+                    bail!(
+                        "Invalid bookmark: expected \"earliest\", \"now\" or a <BookmarkList> element"
+                    );
+                }
+                Ok(BookmarkReset::Value(value.to_owned()))
+            }
+        }
+    }
+}
+
+/// Returns the bookmark sent to a source during enumeration, given the one
+/// stored in database
+pub fn bookmark_to_send(stored: Option<String>, read_existing_events: bool) -> Option<String> {
+    match stored {
+        Some(bookmark) if bookmark == BOOKMARK_NOW => None,
+        Some(bookmark) => Some(bookmark),
+        None if read_existing_events => Some(BOOKMARK_EARLIEST.to_owned()),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmark_reset() {
+        assert_eq!(
+            BookmarkReset::from_str("earliest").unwrap(),
+            BookmarkReset::Earliest
+        );
+        assert_eq!(BookmarkReset::from_str("now").unwrap(), BookmarkReset::Now);
+        let value = "<BookmarkList><Bookmark Channel=\"Security\" RecordId=\"42\" IsCurrent=\"true\"/></BookmarkList>";
+        assert_eq!(
+            BookmarkReset::from_str(value).unwrap(),
+            BookmarkReset::Value(value.to_owned())
+        );
+        assert_eq!(BookmarkReset::from_str(value).unwrap().bookmark(), value);
+        assert!(BookmarkReset::from_str("yesterday").is_err());
+        assert!(BookmarkReset::from_str("<Bookmark RecordId=\"42\"/>").is_err());
+    }
+
+    #[test]
+    fn test_bookmark_to_send() {
+        assert_eq!(bookmark_to_send(None, false), None);
+        assert_eq!(
+            bookmark_to_send(None, true),
+            Some(BOOKMARK_EARLIEST.to_owned())
+        );
+        assert_eq!(bookmark_to_send(Some(BOOKMARK_NOW.to_owned()), true), None);
+        assert_eq!(
+            bookmark_to_send(Some(BOOKMARK_EARLIEST.to_owned()), false),
+            Some(BOOKMARK_EARLIEST.to_owned())
+        );
+        assert_eq!(
+            bookmark_to_send(Some("<BookmarkList/>".to_owned()), false),
+            Some("<BookmarkList/>".to_owned())
+        );
+    }
+}
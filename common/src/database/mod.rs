@@ -4,16 +4,17 @@ use std::{
 };
 
 use crate::{
-    bookmark::BookmarkData,
+    bookmark::{BookmarkData, BookmarkReset, BookmarksExport, MachineBookmark},
     database::postgres::PostgresDatabase,
     database::sqlite::SQLiteDatabase,
     heartbeat::{HeartbeatData, HeartbeatsCache},
     settings::Settings,
     subscription::{
         SubscriptionData, SubscriptionMachine, SubscriptionMachineState, SubscriptionStatsCounters,
+        SubscriptionUuid,
     },
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 
 use self::schema::{Migration, Version};
@@ -78,6 +79,9 @@ pub trait Database {
         is_event: bool,
     ) -> Result<()>;
     async fn store_heartbeats(&self, heartbeats: &HeartbeatsCache) -> Result<()>;
+    /// Deletes at most `limit` heartbeats whose `last_seen` is before
+    /// `last_seen_before` and returns the number of deleted heartbeats
+    async fn delete_heartbeats_before(&self, last_seen_before: i64, limit: u32) -> Result<u64>;
 
     async fn get_subscriptions(&self) -> Result<Vec<SubscriptionData>>;
     async fn get_subscription_by_identifier(
@@ -117,14 +121,125 @@ pub async fn schema_is_up_to_date(db: Db) -> Result<bool> {
     Ok(migrated_versions == migrations)
 }
 
+/// Sets the bookmark of a single source of a subscription, leaving its
+/// other sources untouched. The subscription must exist and the source must
+/// be known within it, i.e. have sent a bookmark or a heartbeat.
+pub async fn set_source_bookmark<D: Database + ?Sized>(
+    db: &D,
+    subscription: &str,
+    machine: &str,
+    reset: &BookmarkReset,
+) -> Result<SubscriptionData> {
+    let subscription = db
+        .get_subscription_by_identifier(subscription)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not find subscription with identifier {}",
+                subscription
+            )
+        })?;
+    let uuid = subscription.uuid_string();
+
+    if db.get_bookmark(machine, &uuid).await?.is_none()
+        && db
+            .get_heartbeats_by_machine(machine, Some(&uuid))
+            .await?
+            .is_empty()
+    {
+        bail!(
+            "Could not find source {} within subscription \"{}\"",
+            machine,
+            subscription.name()
+        );
+    }
+
+    db.store_bookmark(machine, &uuid, reset.bookmark())
+        .await
+        .context("Failed to store bookmark")?;
+    Ok(subscription)
+}
+
+/// Exports the bookmarks of a subscription as JSON, so that they can be
+/// imported in another database using `import_bookmarks`
+pub async fn export_bookmarks<D: Database + ?Sized>(
+    db: &D,
+    subscription: &SubscriptionUuid,
+) -> Result<String> {
+    let uuid = subscription.to_string().to_uppercase();
+    let mut bookmarks: Vec<MachineBookmark> = db
+        .get_bookmarks(&uuid)
+        .await
+        .context("Failed to retrieve bookmarks")?
+        .into_iter()
+        .map(|data| MachineBookmark {
+            machine: data.machine,
+            bookmark: data.bookmark,
+        })
+        .collect();
+    bookmarks.sort_by(|a, b| a.machine.cmp(&b.machine));
+    Ok(serde_json::to_string_pretty(&BookmarksExport {
+        subscription: uuid,
+        bookmarks,
+    })?)
+}
+
+/// Imports bookmarks exported by `export_bookmarks` in a subscription, which
+/// is not necessarily the exported one. The bookmarks of other machines are
+/// left untouched. Returns the number of imported bookmarks.
+pub async fn import_bookmarks<D: Database + ?Sized>(
+    db: &D,
+    subscription: &SubscriptionUuid,
+    content: &str,
+) -> Result<usize> {
+    let export: BookmarksExport =
+        serde_json::from_str(content).context("Failed to parse exported bookmarks")?;
+    let uuid = subscription.to_string().to_uppercase();
+    if db.get_subscription_by_identifier(&uuid).await?.is_none() {
+        bail!("Could not find subscription with identifier {}", uuid);
+    }
+    for data in export.bookmarks.iter() {
+        db.store_bookmark(&data.machine, &uuid, &data.bookmark)
+            .await
+            .with_context(|| format!("Failed to store bookmark of {}", data.machine))?;
+    }
+    Ok(export.bookmarks.len())
+}
+
+/// Deletes the heartbeats whose `last_seen` is before `last_seen_before`.
+/// Heartbeats are deleted by batches of `batch_size`, so that the table is
+/// never locked for long.
+pub async fn prune_heartbeats<D: Database + ?Sized>(
+    db: &D,
+    last_seen_before: i64,
+    batch_size: u32,
+) -> Result<u64> {
+    let mut deleted = 0;
+    loop {
+        let count = db
+            .delete_heartbeats_before(last_seen_before, batch_size)
+            .await
+            .context("Failed to delete heartbeats")?;
+        deleted += count;
+        if count < u64::from(batch_size) {
+            return Ok(deleted);
+        }
+        // Let other queries run between two batches
+        tokio::task::yield_now().await;
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use anyhow::ensure;
+    use chrono::{TimeZone, Utc};
 
     use crate::{
+        bookmark::{bookmark_to_send, BOOKMARK_EARLIEST},
         heartbeat::{HeartbeatKey, HeartbeatValue},
         subscription::{
-            ContentFormat, FilesConfiguration, ClientFilter, ClientFilterOperation,
+            ClientFilter, ClientFilterOperation, ContentFormat, EventsCompression,
+            FilesConfiguration, ManifestOption, OutputMode, RenderingElement, RequireAuth,
             SubscriptionOutput, SubscriptionOutputDriver, SubscriptionOutputFormat,
             DEFAULT_CONTENT_FORMAT, DEFAULT_IGNORE_CHANNEL_ERROR, DEFAULT_READ_EXISTING_EVENTS,
         },
@@ -175,6 +290,22 @@ pub mod tests {
         assert_eq!(toto.data_locale(), None);
         assert_eq!(toto.locale(), None);
         assert_eq!(toto.max_elements(), None);
+        assert_eq!(toto.require_auth(), RequireAuth::Any);
+        assert_eq!(toto.compression(), EventsCompression::Sldc);
+        assert_eq!(toto.output_mode(), OutputMode::Parallel);
+        assert_eq!(toto.read_existing_events_since(), None);
+        assert_eq!(toto.ignore_channel_error_for(), None);
+        assert_eq!(toto.connection_retry_jitter(), None);
+        assert_eq!(toto.max_concurrent_retries(), None);
+        assert_eq!(toto.max_queued_events(), None);
+        assert_eq!(toto.max_queued_bytes(), None);
+        assert_eq!(toto.max_concurrent_batches(), None);
+        assert_eq!(toto.event_ttl(), None);
+        assert_eq!(toto.max_concurrent_clients(), None);
+        assert_eq!(toto.dedup_window(), None);
+        assert_eq!(toto.dedup_window_ttl(), None);
+        assert!(toto.trim_rendering().is_empty());
+        assert!(toto.enrollment_fallback().is_empty());
 
         let toto2 = db.get_subscription_by_identifier("toto").await?.unwrap();
         assert_eq!(toto, &toto2);
@@ -215,7 +346,34 @@ pub mod tests {
             .set_revision(Some("1472".to_string()))
             .set_locale(Some("fr-FR".to_string()))
             .set_data_locale(Some("en-US".to_string()))
-            .set_max_elements(Some(10));
+            .set_max_elements(Some(10))
+            .set_require_auth(RequireAuth::TlsCert)
+            .set_compression(EventsCompression::None)
+            .set_output_mode(OutputMode::Sequential)
+            .set_read_existing_events_since(Some(
+                Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap(),
+            ))
+            .set_ignore_channel_error_for(Some(BTreeSet::from([
+                "Microsoft-Windows-Sysmon/Operational".to_string(),
+                "Setup".to_string(),
+            ])))
+            .set_connection_retry_jitter(Some(20))
+            .set_max_concurrent_retries(Some(8))
+            .set_max_queued_events(Some(10_000))
+            .set_max_queued_bytes(Some(64 * 1024 * 1024))
+            .set_max_concurrent_batches(Some(16))
+            .set_event_ttl(Some(86_400))
+            .set_max_concurrent_clients(Some(1000))
+            .set_dedup_window(Some(100_000))
+            .set_dedup_window_ttl(Some(3600))
+            .set_trim_rendering(BTreeSet::from([
+                RenderingElement::Message,
+                RenderingElement::Keywords,
+            ]))
+            .set_enrollment_fallback(BTreeSet::from([
+                ManifestOption::Locale,
+                ManifestOption::ReadExistingEvents,
+            ]));
         db.store_subscription(&subscription2).await?;
 
         assert!(db.get_subscriptions().await?.len() == 2);
@@ -261,6 +419,37 @@ pub mod tests {
         assert_eq!(tata.locale(), Some("fr-FR".to_string()).as_ref());
         assert_eq!(tata.data_locale(), Some("en-US".to_string()).as_ref());
         assert_eq!(tata.max_elements(), Some(10));
+        assert_eq!(tata.require_auth(), RequireAuth::TlsCert);
+        assert_eq!(tata.compression(), EventsCompression::None);
+        assert_eq!(tata.output_mode(), OutputMode::Sequential);
+        assert_eq!(
+            tata.read_existing_events_since(),
+            Some(&Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap())
+        );
+        assert_eq!(
+            tata.ignore_channel_error_for(),
+            Some(&BTreeSet::from([
+                "Microsoft-Windows-Sysmon/Operational".to_string(),
+                "Setup".to_string(),
+            ]))
+        );
+        assert_eq!(tata.connection_retry_jitter(), Some(20));
+        assert_eq!(tata.max_concurrent_retries(), Some(8));
+        assert_eq!(tata.max_queued_events(), Some(10_000));
+        assert_eq!(tata.max_queued_bytes(), Some(64 * 1024 * 1024));
+        assert_eq!(tata.max_concurrent_batches(), Some(16));
+        assert_eq!(tata.event_ttl(), Some(86_400));
+        assert_eq!(tata.max_concurrent_clients(), Some(1000));
+        assert_eq!(tata.dedup_window(), Some(100_000));
+        assert_eq!(tata.dedup_window_ttl(), Some(3600));
+        assert_eq!(
+            tata.trim_rendering(),
+            &BTreeSet::from([RenderingElement::Message, RenderingElement::Keywords])
+        );
+        assert_eq!(
+            tata.enrollment_fallback(),
+            &BTreeSet::from([ManifestOption::Locale, ManifestOption::ReadExistingEvents])
+        );
 
         let tata_save = tata.clone();
         tata.set_name("titi".to_string())
@@ -273,7 +462,22 @@ pub mod tests {
             .set_content_format(ContentFormat::Raw)
             .set_ignore_channel_error(true)
             .set_revision(Some("1890".to_string()))
-            .set_data_locale(Some("fr-FR".to_string()));
+            .set_data_locale(Some("fr-FR".to_string()))
+            .set_require_auth(RequireAuth::Kerberos)
+            .set_compression(EventsCompression::Sldc)
+            .set_output_mode(OutputMode::Parallel)
+            .set_read_existing_events_since(None)
+            .set_ignore_channel_error_for(Some(BTreeSet::new()))
+            .set_connection_retry_jitter(None)
+            .set_max_concurrent_retries(None)
+            .set_max_queued_events(None)
+            .set_max_concurrent_batches(Some(4))
+            .set_event_ttl(None)
+            .set_max_concurrent_clients(None)
+            .set_dedup_window(Some(1000))
+            .set_dedup_window_ttl(None)
+            .set_trim_rendering(BTreeSet::new())
+            .set_enrollment_fallback(BTreeSet::from([ManifestOption::Cdata]));
 
 
         let orig_filter = &tata.client_filter().unwrap();
@@ -321,6 +525,25 @@ pub mod tests {
         assert_eq!(tata2.revision(), Some("1890".to_string()).as_ref());
         assert_eq!(tata2.locale(), Some("fr-FR".to_string()).as_ref()); // Unchanged
         assert_eq!(tata2.data_locale(), Some("fr-FR".to_string()).as_ref());
+        assert_eq!(tata2.require_auth(), RequireAuth::Kerberos);
+        assert_eq!(tata2.compression(), EventsCompression::Sldc);
+        assert_eq!(tata2.output_mode(), OutputMode::Parallel);
+        assert_eq!(tata2.read_existing_events_since(), None);
+        assert_eq!(tata2.ignore_channel_error_for(), Some(&BTreeSet::new()));
+        assert_eq!(tata2.connection_retry_jitter(), None);
+        assert_eq!(tata2.max_concurrent_retries(), None);
+        assert_eq!(tata2.max_queued_events(), None);
+        assert_eq!(tata2.max_queued_bytes(), Some(64 * 1024 * 1024)); // Unchanged
+        assert_eq!(tata2.max_concurrent_batches(), Some(4));
+        assert_eq!(tata2.event_ttl(), None);
+        assert_eq!(tata2.max_concurrent_clients(), None);
+        assert_eq!(tata2.dedup_window(), Some(1000));
+        assert_eq!(tata2.dedup_window_ttl(), None);
+        assert!(tata2.trim_rendering().is_empty());
+        assert_eq!(
+            tata2.enrollment_fallback(),
+            &BTreeSet::from([ManifestOption::Cdata])
+        );
 
         assert!(tata2.public_version()? != tata_save.public_version()?);
 
@@ -383,6 +606,78 @@ pub mod tests {
         Ok(())
     }
 
+    pub async fn test_subscriptions_tenants(db: Arc<dyn Database>) -> Result<()> {
+        setup_db(db.clone()).await?;
+
+        let toto = SubscriptionData::new("toto", "query");
+        db.store_subscription(&toto).await?;
+        let mut toto_a = SubscriptionData::new("toto", "query");
+        toto_a.set_tenant(Some("team-a".to_string()));
+        db.store_subscription(&toto_a).await?;
+        let mut toto_b = SubscriptionData::new("toto", "query");
+        toto_b.set_tenant(Some("team-b".to_string()));
+        db.store_subscription(&toto_b).await?;
+
+        // Names are unique within a tenant
+        let mut toto_a2 = SubscriptionData::new("toto", "query");
+        toto_a2.set_tenant(Some("team-a".to_string()));
+        assert!(db.store_subscription(&toto_a2).await.is_err());
+        assert!(db
+            .store_subscription(&SubscriptionData::new("toto", "query"))
+            .await
+            .is_err());
+        assert_eq!(db.get_subscriptions().await?.len(), 3);
+
+        // UUIDs are unique across all tenants
+        let mut toto_b2 = toto_a.clone();
+        toto_b2.set_tenant(Some("team-b".to_string()));
+        toto_b2.set_name("titi".to_string());
+        assert!(db.store_subscription(&toto_b2).await.is_err());
+        let mut toto_none = toto_a.clone();
+        toto_none.set_tenant(None);
+        toto_none.set_name("titi".to_string());
+        assert!(db.store_subscription(&toto_none).await.is_err());
+        let stored = db
+            .get_subscription_by_identifier(&toto_a.uuid_string())
+            .await?
+            .unwrap();
+        assert_eq!(stored, toto_a);
+        assert_eq!(stored.tenant(), Some(&"team-a".to_string()));
+
+        // Subscriptions can still be updated within their tenant
+        let mut toto_a3 = toto_a.clone();
+        toto_a3.set_name("tata".to_string());
+        db.store_subscription(&toto_a3).await?;
+        assert_eq!(
+            db.get_subscription_by_identifier("team-a/tata")
+                .await?
+                .unwrap(),
+            toto_a3
+        );
+
+        // Names without tenant only identify subscriptions without tenant
+        assert_eq!(
+            db.get_subscription_by_identifier("toto").await?.unwrap(),
+            toto
+        );
+        assert_eq!(
+            db.get_subscription_by_identifier("team-b/toto")
+                .await?
+                .unwrap(),
+            toto_b
+        );
+        assert!(db.get_subscription_by_identifier("tata").await?.is_none());
+        assert!(db
+            .get_subscription_by_identifier("team-c/toto")
+            .await?
+            .is_none());
+
+        // Names must be unique again to revert the migration
+        db.delete_subscription(&toto_b.uuid_string()).await?;
+        clean_db(db.clone()).await?;
+        Ok(())
+    }
+
     pub async fn test_bookmarks(db: Arc<dyn Database>) -> Result<()> {
         setup_db(db.clone()).await?;
         let subscription_tutu = SubscriptionData::new("tutu", "query");
@@ -596,6 +891,65 @@ pub mod tests {
         Ok(())
     }
 
+    pub async fn test_set_source_bookmark(db: Arc<dyn Database>) -> Result<()> {
+        setup_db(db.clone()).await?;
+        let mut subscription = SubscriptionData::new("tutu", "query");
+        subscription.set_read_existing_events(true);
+        db.store_subscription(&subscription).await?;
+        let uuid = subscription.uuid_string();
+
+        db.store_bookmark("m1", &uuid, "m1b1").await?;
+        db.store_bookmark("m2", &uuid, "m2b1").await?;
+        // m3 only sent a heartbeat
+        db.store_heartbeat("m3", "127.0.0.1".to_string(), &uuid, false)
+            .await?;
+
+        // Unknown subscription or source
+        assert!(set_source_bookmark(&*db, "titi", "m1", &BookmarkReset::Now)
+            .await
+            .is_err());
+        assert!(set_source_bookmark(&*db, "tutu", "m4", &BookmarkReset::Now)
+            .await
+            .is_err());
+        assert!(db.get_bookmark("m4", &uuid).await?.is_none());
+
+        // Only m1 begins at its next event, even if existing events are read
+        let res = set_source_bookmark(&*db, "tutu", "m1", &BookmarkReset::Now).await?;
+        assert_eq!(res.uuid_string(), uuid);
+        assert_eq!(
+            bookmark_to_send(db.get_bookmark("m1", &uuid).await?, true),
+            None
+        );
+        assert_eq!(
+            bookmark_to_send(db.get_bookmark("m2", &uuid).await?, true),
+            Some("m2b1".to_owned())
+        );
+
+        set_source_bookmark(&*db, &uuid, "m3", &BookmarkReset::Earliest).await?;
+        assert_eq!(
+            db.get_bookmark("m3", &uuid).await?,
+            Some(BOOKMARK_EARLIEST.to_owned())
+        );
+
+        let value = "<BookmarkList><Bookmark Channel=\"Security\" RecordId=\"42\" IsCurrent=\"true\"/></BookmarkList>";
+        set_source_bookmark(&*db, "tutu", "m2", &BookmarkReset::Value(value.to_owned())).await?;
+        assert_eq!(db.get_bookmark("m2", &uuid).await?, Some(value.to_owned()));
+        assert_eq!(
+            bookmark_to_send(db.get_bookmark("m1", &uuid).await?, true),
+            None
+        );
+
+        // Sources overwrite the bookmark when they send events again
+        db.store_bookmark("m1", &uuid, "m1b2").await?;
+        assert_eq!(
+            bookmark_to_send(db.get_bookmark("m1", &uuid).await?, true),
+            Some("m1b2".to_owned())
+        );
+
+        clean_db(db.clone()).await?;
+        Ok(())
+    }
+
     pub async fn test_heartbeats(db: Arc<dyn Database>) -> Result<()> {
         setup_db(db.clone()).await?;
         ensure!(
@@ -835,6 +1189,105 @@ pub mod tests {
         Ok(())
     }
 
+    pub async fn test_export_import_bookmarks(db: Arc<dyn Database>) -> Result<()> {
+        setup_db(db.clone()).await?;
+        let subscription_tutu = SubscriptionData::new("tutu", "query");
+        db.store_subscription(&subscription_tutu).await?;
+        let uuid = subscription_tutu.uuid_string();
+
+        let m1_bookmark = r#"<BookmarkList><Bookmark Channel="Security" RecordId="1234" IsCurrent="true"/></BookmarkList>"#;
+        let m2_bookmark = r#"<BookmarkList Direction="backward"><Bookmark Channel="Application" RecordId="5"/><Bookmark Channel="System" RecordId="42" IsCurrent="true"/></BookmarkList>"#;
+        db.store_bookmark("m1", &uuid, m1_bookmark).await?;
+        db.store_bookmark("m2", &uuid, m2_bookmark).await?;
+        let sorted_bookmarks = |mut bookmarks: Vec<BookmarkData>| {
+            bookmarks.sort_by(|a, b| a.machine.cmp(&b.machine));
+            bookmarks
+        };
+        let expected = sorted_bookmarks(db.get_bookmarks(&uuid).await?);
+        assert_eq!(expected.len(), 2);
+
+        let exported = export_bookmarks(db.as_ref(), subscription_tutu.uuid()).await?;
+
+        db.delete_bookmarks(None, Some(&uuid)).await?;
+        assert!(db.get_bookmarks(&uuid).await?.is_empty());
+
+        assert_eq!(
+            import_bookmarks(db.as_ref(), subscription_tutu.uuid(), &exported).await?,
+            2
+        );
+        assert_eq!(sorted_bookmarks(db.get_bookmarks(&uuid).await?), expected);
+        assert_eq!(db.get_bookmark("m1", &uuid).await?.unwrap(), m1_bookmark);
+        assert_eq!(db.get_bookmark("m2", &uuid).await?.unwrap(), m2_bookmark);
+
+        // Bookmarks can be imported in another subscription
+        let subscription_titi = SubscriptionData::new("titi", "query");
+        db.store_subscription(&subscription_titi).await?;
+        import_bookmarks(db.as_ref(), subscription_titi.uuid(), &exported).await?;
+        assert_eq!(
+            db.get_bookmark("m2", &subscription_titi.uuid_string())
+                .await?
+                .unwrap(),
+            m2_bookmark
+        );
+
+        // The subscription must exist and the content must be valid
+        let unknown = SubscriptionData::new("unknown", "query");
+        assert!(import_bookmarks(db.as_ref(), unknown.uuid(), &exported)
+            .await
+            .is_err());
+        assert!(
+            import_bookmarks(db.as_ref(), subscription_tutu.uuid(), "not json")
+                .await
+                .is_err()
+        );
+
+        clean_db(db.clone()).await?;
+        Ok(())
+    }
+
+    pub async fn test_prune_heartbeats(db: Arc<dyn Database>) -> Result<()> {
+        setup_db(db.clone()).await?;
+
+        let subscription_tutu = SubscriptionData::new("tutu", "query");
+        db.store_subscription(&subscription_tutu).await?;
+
+        let mut heartbeats = HeartbeatsCache::new();
+        for (machine, last_seen) in [
+            ("old1", 100),
+            ("old2", 200),
+            ("old3", 999),
+            ("recent", 1000),
+        ] {
+            heartbeats.insert(
+                HeartbeatKey {
+                    machine: machine.to_string(),
+                    subscription: subscription_tutu.uuid_string().to_owned(),
+                },
+                HeartbeatValue {
+                    ip: "127.0.0.1".to_string(),
+                    last_seen,
+                    last_event_seen: None,
+                },
+            );
+        }
+        db.store_heartbeats(&heartbeats).await?;
+
+        // Old heartbeats are deleted by batches of 2
+        assert_eq!(db.delete_heartbeats_before(1000, 2).await?, 2);
+        assert_eq!(db.get_heartbeats().await?.len(), 2);
+        assert_eq!(prune_heartbeats(db.as_ref(), 1000, 2).await?, 1);
+
+        let db_heartbeats = db.get_heartbeats().await?;
+        assert_eq!(db_heartbeats.len(), 1);
+        assert_eq!(db_heartbeats[0].machine(), "recent");
+        assert_eq!(db_heartbeats[0].last_seen(), 1000);
+
+        assert_eq!(prune_heartbeats(db.as_ref(), 1000, 2).await?, 0);
+        assert_eq!(db.get_heartbeats().await?.len(), 1);
+        clean_db(db.clone()).await?;
+        Ok(())
+    }
+
     pub async fn test_stats_and_machines(db: Arc<dyn Database>) -> Result<()> {
         setup_db(db.clone()).await?;
 
@@ -31,15 +31,18 @@ use crate::bookmark::BookmarkData;
 use crate::heartbeat::{HeartbeatKey, HeartbeatsCache};
 use crate::settings::PostgresSslMode;
 use crate::subscription::{
-    ContentFormat, InternalVersion, ClientFilter, SubscriptionMachine, SubscriptionMachineState,
+    ClientFilter, ContentFormat, EventsCompression, InternalVersion, ManifestOption, OutputMode,
+    RenderingElement, RequireAuth, SubscriptionMachine, SubscriptionMachineState,
     SubscriptionStatsCounters, SubscriptionUuid,
 };
+use crate::utils::parse_rfc3339_utc;
 use crate::{
     database::Database, heartbeat::HeartbeatData, settings::Postgres,
     subscription::SubscriptionData,
 };
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use async_trait::async_trait;
+use chrono::SecondsFormat;
 use deadpool_postgres::{Config, Pool, Runtime, SslMode, Transaction};
 use log::{error, warn};
 use openssl::ssl::{SslConnector, SslMethod};
@@ -212,6 +215,15 @@ fn row_to_subscription(row: &Row) -> Result<SubscriptionData> {
     let max_envelope_size: i32 = row.try_get("max_envelope_size")?;
     let max_time: i32 = row.try_get("max_time")?;
     let max_elements: Option<i32> = row.try_get("max_elements")?;
+    let max_concurrent_retries: Option<i32> = row.try_get("max_concurrent_retries")?;
+    let max_queued_events: Option<i32> = row.try_get("max_queued_events")?;
+    let max_queued_bytes: Option<i64> = row.try_get("max_queued_bytes")?;
+    let max_concurrent_batches: Option<i32> = row.try_get("max_concurrent_batches")?;
+    let event_ttl: Option<i32> = row.try_get("event_ttl")?;
+    let max_concurrent_clients: Option<i32> = row.try_get("max_concurrent_clients")?;
+    let connection_retry_jitter: Option<i32> = row.try_get("connection_retry_jitter")?;
+    let dedup_window: Option<i32> = row.try_get("dedup_window")?;
+    let dedup_window_ttl: Option<i32> = row.try_get("dedup_window_ttl")?;
 
     let client_filter_op: Option<String> = row.try_get("client_filter_op")?;
 
@@ -227,11 +239,60 @@ fn row_to_subscription(row: &Row) -> Result<SubscriptionData> {
         None => None
     };
 
+    let require_auth: Option<&str> = row.try_get("require_auth")?;
+    let require_auth = match require_auth {
+        Some(require_auth) => RequireAuth::from_str(require_auth)?,
+        None => RequireAuth::default(),
+    };
+
+    let compression: Option<&str> = row.try_get("compression")?;
+    let compression = match compression {
+        Some(compression) => EventsCompression::from_str(compression)?,
+        None => EventsCompression::default(),
+    };
+
+    let output_mode: Option<&str> = row.try_get("output_mode")?;
+    let output_mode = match output_mode {
+        Some(output_mode) => OutputMode::from_str(output_mode)?,
+        None => OutputMode::default(),
+    };
+
+    let read_existing_events_since: Option<&str> = row.try_get("read_existing_events_since")?;
+    let read_existing_events_since = match read_existing_events_since {
+        Some(since) => Some(parse_rfc3339_utc(since)?),
+        None => None,
+    };
+
+    let ignore_channel_error_for: Option<&str> = row.try_get("ignore_channel_error_for")?;
+    let ignore_channel_error_for = match ignore_channel_error_for {
+        Some(channels) => Some(
+            serde_json::from_str(channels)
+                .context("Failed to parse subscription ignore_channel_error_for")?,
+        ),
+        None => None,
+    };
+
+    let trim_rendering: Option<&str> = row.try_get("trim_rendering")?;
+    let trim_rendering = match trim_rendering {
+        Some(trim_rendering) => RenderingElement::from_list(trim_rendering)?,
+        None => BTreeSet::new(),
+    };
+
+    let enrollment_fallback: Option<&str> = row.try_get("enrollment_fallback")?;
+    let enrollment_fallback = match enrollment_fallback {
+        Some(enrollment_fallback) => ManifestOption::from_list(enrollment_fallback)?,
+        None => BTreeSet::new(),
+    };
+
+    // Subscriptions without tenant are stored with an empty tenant
+    let tenant: String = row.try_get("tenant")?;
+
     let mut subscription = SubscriptionData::new(row.try_get("name")?, row.try_get("query")?);
     subscription
         .set_uuid(SubscriptionUuid(Uuid::parse_str(row.try_get("uuid")?)?))
         .set_uri(row.try_get("uri")?)
         .set_revision(row.try_get("revision")?)
+        .set_tenant(Some(tenant).filter(|tenant| !tenant.is_empty()))
         .set_heartbeat_interval(heartbeat_interval.try_into()?)
         .set_connection_retry_count(connection_retry_count.try_into()?)
         .set_connection_retry_interval(connection_retry_interval.try_into()?)
@@ -248,6 +309,49 @@ fn row_to_subscription(row: &Row) -> Result<SubscriptionData> {
         .set_locale(row.try_get("locale")?)
         .set_data_locale(row.try_get("data_locale")?)
         .set_client_filter(client_filter)
+        .set_require_auth(require_auth)
+        .set_compression(compression)
+        .set_output_mode(output_mode)
+        .set_read_existing_events_since(read_existing_events_since)
+        .set_ignore_channel_error_for(ignore_channel_error_for)
+        .set_max_concurrent_retries(match max_concurrent_retries {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_max_queued_events(match max_queued_events {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_max_queued_bytes(match max_queued_bytes {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_max_concurrent_batches(match max_concurrent_batches {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_event_ttl(match event_ttl {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_max_concurrent_clients(match max_concurrent_clients {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_connection_retry_jitter(match connection_retry_jitter {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_dedup_window(match dedup_window {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_dedup_window_ttl(match dedup_window_ttl {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_trim_rendering(trim_rendering)
+        .set_enrollment_fallback(enrollment_fallback)
         .set_outputs(outputs);
 
     // This needs to be done at the end because version is updated each time
@@ -579,6 +683,26 @@ impl Database for PostgresDatabase {
         Ok(())
     }
 
+    async fn delete_heartbeats_before(&self, last_seen_before: i64, limit: u32) -> Result<u64> {
+        let limit = i64::from(limit);
+        let count = self
+            .pool
+            .get()
+            .await?
+            .execute(
+                r#"DELETE FROM heartbeats
+                    WHERE (machine, subscription) IN (
+                        SELECT machine, subscription
+                        FROM heartbeats
+                        WHERE last_seen < $1
+                        LIMIT $2
+                    )"#,
+                &[&last_seen_before, &limit],
+            )
+            .await?;
+        Ok(count)
+    }
+
     async fn get_subscriptions(&self) -> Result<Vec<SubscriptionData>> {
         let rows = self
             .pool
@@ -612,7 +736,9 @@ impl Database for PostgresDatabase {
             .query_opt(
                 r#"SELECT *
                         FROM subscriptions
-                        WHERE uuid = $1 OR name = $1"#,
+                        WHERE uuid = $1
+                            OR (tenant = '' AND name = $1)
+                            OR tenant || '/' || name = $1"#,
                 &[&identifier],
             )
             .await?;
@@ -633,6 +759,57 @@ impl Database for PostgresDatabase {
             None => None,
         };
 
+        let max_concurrent_retries: Option<i32> = match subscription.max_concurrent_retries() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let max_queued_events: Option<i32> = match subscription.max_queued_events() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let max_queued_bytes: Option<i64> = match subscription.max_queued_bytes() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let max_concurrent_batches: Option<i32> = match subscription.max_concurrent_batches() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let event_ttl: Option<i32> = match subscription.event_ttl() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let max_concurrent_clients: Option<i32> = match subscription.max_concurrent_clients() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let connection_retry_jitter: Option<i32> =
+            subscription.connection_retry_jitter().map(i32::from);
+
+        let dedup_window: Option<i32> = match subscription.dedup_window() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let dedup_window_ttl: Option<i32> = match subscription.dedup_window_ttl() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+
+        let read_existing_events_since: Option<String> = subscription
+            .read_existing_events_since()
+            .map(|since| since.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        let ignore_channel_error_for = match subscription.ignore_channel_error_for() {
+            Some(channels) => Some(serde_json::to_string(channels)?),
+            None => None,
+        };
+
         let max_envelope_size: i32 = subscription.max_envelope_size().try_into()?;
         let client_filter_op: Option<String> = subscription.client_filter().map(|f| f.operation().to_string());
         let client_filter_kind = subscription.client_filter().map(|f| f.kind().to_string());
@@ -648,8 +825,11 @@ impl Database for PostgresDatabase {
                     heartbeat_interval, connection_retry_count, connection_retry_interval,
                     max_time, max_elements, max_envelope_size, enabled, read_existing_events, content_format,
                     ignore_channel_error, client_filter_op, client_filter_kind, client_filter_flags, client_filter_targets, outputs, locale,
-                    data_locale)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23)
+                    data_locale, require_auth, max_concurrent_retries, max_queued_events, max_queued_bytes, max_concurrent_batches,
+                    trim_rendering, enrollment_fallback, tenant, event_ttl, max_concurrent_clients, compression,
+                    connection_retry_jitter, dedup_window, dedup_window_ttl, output_mode, read_existing_events_since,
+                    ignore_channel_error_for)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40)
                     ON CONFLICT (uuid) DO UPDATE SET
                         version = excluded.version,
                         revision = excluded.revision,
@@ -672,7 +852,24 @@ impl Database for PostgresDatabase {
                         client_filter_targets = excluded.client_filter_targets,
                         outputs = excluded.outputs,
                         locale = excluded.locale,
-                        data_locale = excluded.data_locale"#,
+                        data_locale = excluded.data_locale,
+                        require_auth = excluded.require_auth,
+                        max_concurrent_retries = excluded.max_concurrent_retries,
+                        max_queued_events = excluded.max_queued_events,
+                        max_queued_bytes = excluded.max_queued_bytes,
+                        max_concurrent_batches = excluded.max_concurrent_batches,
+                        trim_rendering = excluded.trim_rendering,
+                        enrollment_fallback = excluded.enrollment_fallback,
+                        event_ttl = excluded.event_ttl,
+                        max_concurrent_clients = excluded.max_concurrent_clients,
+                        compression = excluded.compression,
+                        connection_retry_jitter = excluded.connection_retry_jitter,
+                        dedup_window = excluded.dedup_window,
+                        dedup_window_ttl = excluded.dedup_window_ttl,
+                        output_mode = excluded.output_mode,
+                        read_existing_events_since = excluded.read_existing_events_since,
+                        ignore_channel_error_for = excluded.ignore_channel_error_for
+                    WHERE subscriptions.tenant = excluded.tenant"#,
                 &[
                     &subscription.uuid_string(),
                     &subscription.internal_version().to_string(),
@@ -696,11 +893,34 @@ impl Database for PostgresDatabase {
                     &client_filter_targets,
                     &serde_json::to_string(subscription.outputs())?.as_str(),
                     &subscription.locale(),
-                    &subscription.data_locale()
+                    &subscription.data_locale(),
+                    &subscription.require_auth().to_string(),
+                    &max_concurrent_retries,
+                    &max_queued_events,
+                    &max_queued_bytes,
+                    &max_concurrent_batches,
+                    &subscription.trim_rendering_to_opt_string(),
+                    &subscription.enrollment_fallback_to_opt_string(),
+                    &subscription.tenant().map(String::as_str).unwrap_or_default(),
+                    &event_ttl,
+                    &max_concurrent_clients,
+                    &subscription.compression().to_string(),
+                    &connection_retry_jitter,
+                    &dedup_window,
+                    &dedup_window_ttl,
+                    &subscription.output_mode().to_string(),
+                    &read_existing_events_since,
+                    &ignore_channel_error_for
                 ],
             )
             .await?;
 
+        // The upsert does not update subscriptions of other tenants
+        ensure!(
+            count != 0,
+            "Subscription {} already exists in another tenant",
+            subscription.uuid()
+        );
         ensure!(count == 1, "Only one row must have been updated");
 
         Ok(())
@@ -980,6 +1200,20 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_set_source_bookmark() -> Result<()> {
+        crate::database::tests::test_set_source_bookmark(db_with_migrations().await?).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_subscriptions_tenants() -> Result<()> {
+        crate::database::tests::test_subscriptions_tenants(db_with_migrations().await?).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_heartbeats() -> Result<()> {
@@ -994,6 +1228,20 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_export_import_bookmarks() -> Result<()> {
+        crate::database::tests::test_export_import_bookmarks(db_with_migrations().await?).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_prune_heartbeats() -> Result<()> {
+        crate::database::tests::test_prune_heartbeats(db_with_migrations().await?).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_subscriptions() -> Result<()> {
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddMaxConcurrentRetriesFieldInSubscriptionsTable;
+migration!(
+    AddMaxConcurrentRetriesFieldInSubscriptionsTable,
+    16,
+    "add max_concurrent_retries field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddMaxConcurrentRetriesFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS max_concurrent_retries INT4;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS max_concurrent_retries",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
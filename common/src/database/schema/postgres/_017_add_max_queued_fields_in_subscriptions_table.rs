@@ -0,0 +1,43 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddMaxQueuedFieldsInSubscriptionsTable;
+migration!(
+    AddMaxQueuedFieldsInSubscriptionsTable,
+    17,
+    "add max_queued fields in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddMaxQueuedFieldsInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS max_queued_events INT4;",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS max_queued_bytes INT8;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS max_queued_events",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS max_queued_bytes",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
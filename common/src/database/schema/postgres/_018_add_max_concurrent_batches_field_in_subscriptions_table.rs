@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddMaxConcurrentBatchesFieldInSubscriptionsTable;
+migration!(
+    AddMaxConcurrentBatchesFieldInSubscriptionsTable,
+    18,
+    "add max_concurrent_batches field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddMaxConcurrentBatchesFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS max_concurrent_batches INT4;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS max_concurrent_batches",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddTrimRenderingFieldInSubscriptionsTable;
+migration!(
+    AddTrimRenderingFieldInSubscriptionsTable,
+    19,
+    "add trim_rendering field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddTrimRenderingFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS trim_rendering TEXT;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS trim_rendering",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
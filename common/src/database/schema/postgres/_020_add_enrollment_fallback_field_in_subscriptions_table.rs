@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddEnrollmentFallbackFieldInSubscriptionsTable;
+migration!(
+    AddEnrollmentFallbackFieldInSubscriptionsTable,
+    20,
+    "add enrollment_fallback field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddEnrollmentFallbackFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS enrollment_fallback TEXT;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS enrollment_fallback",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddTenantFieldInSubscriptionsTable;
+migration!(
+    AddTenantFieldInSubscriptionsTable,
+    21,
+    "add tenant field in subscriptions table and make names unique per tenant"
+);
+
+#[async_trait]
+impl PostgresMigration for AddTenantFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS tenant TEXT NOT NULL DEFAULT '';",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions DROP CONSTRAINT IF EXISTS subscriptions_name_key;",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS subscriptions_tenant_name ON subscriptions (tenant, name);",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute("DROP INDEX IF EXISTS subscriptions_tenant_name;", &[])
+            .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS tenant",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_name_key UNIQUE (name);",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
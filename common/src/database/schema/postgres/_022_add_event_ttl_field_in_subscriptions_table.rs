@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddEventTtlFieldInSubscriptionsTable;
+migration!(
+    AddEventTtlFieldInSubscriptionsTable,
+    22,
+    "add event_ttl field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddEventTtlFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS event_ttl INT4;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS event_ttl",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddLastSeenIndexInHeartbeatsTable;
+migration!(
+    AddLastSeenIndexInHeartbeatsTable,
+    23,
+    "add last_seen index in heartbeats table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddLastSeenIndexInHeartbeatsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "CREATE INDEX IF NOT EXISTS heartbeats_last_seen_idx ON heartbeats (last_seen);",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute("DROP INDEX IF EXISTS heartbeats_last_seen_idx;", &[])
+            .await?;
+        Ok(())
+    }
+}
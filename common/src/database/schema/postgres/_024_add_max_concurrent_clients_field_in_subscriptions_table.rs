@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddMaxConcurrentClientsFieldInSubscriptionsTable;
+migration!(
+    AddMaxConcurrentClientsFieldInSubscriptionsTable,
+    24,
+    "add max_concurrent_clients field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddMaxConcurrentClientsFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS max_concurrent_clients INT4;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS max_concurrent_clients",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddCompressionFieldInSubscriptionsTable;
+migration!(
+    AddCompressionFieldInSubscriptionsTable,
+    25,
+    "add compression field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddCompressionFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS compression TEXT;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS compression",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddConnectionRetryJitterFieldInSubscriptionsTable;
+migration!(
+    AddConnectionRetryJitterFieldInSubscriptionsTable,
+    26,
+    "add connection_retry_jitter field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddConnectionRetryJitterFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS connection_retry_jitter INT4;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS connection_retry_jitter",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
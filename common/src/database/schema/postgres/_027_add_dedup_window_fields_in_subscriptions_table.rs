@@ -0,0 +1,43 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddDedupWindowFieldsInSubscriptionsTable;
+migration!(
+    AddDedupWindowFieldsInSubscriptionsTable,
+    27,
+    "add dedup_window fields in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddDedupWindowFieldsInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS dedup_window INT4;",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS dedup_window_ttl INT4;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS dedup_window",
+            &[],
+        )
+        .await?;
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS dedup_window_ttl",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddOutputModeFieldInSubscriptionsTable;
+migration!(
+    AddOutputModeFieldInSubscriptionsTable,
+    28,
+    "add output_mode field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddOutputModeFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS output_mode TEXT;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS output_mode",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddReadExistingEventsSinceFieldInSubscriptionsTable;
+migration!(
+    AddReadExistingEventsSinceFieldInSubscriptionsTable,
+    29,
+    "add read_existing_events_since field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddReadExistingEventsSinceFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS read_existing_events_since TEXT;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS read_existing_events_since",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
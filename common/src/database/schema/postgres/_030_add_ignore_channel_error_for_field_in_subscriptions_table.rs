@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool_postgres::Transaction;
+
+use crate::{database::postgres::PostgresMigration, migration};
+
+pub(super) struct AddIgnoreChannelErrorForFieldInSubscriptionsTable;
+migration!(
+    AddIgnoreChannelErrorForFieldInSubscriptionsTable,
+    30,
+    "add ignore_channel_error_for field in subscriptions table"
+);
+
+#[async_trait]
+impl PostgresMigration for AddIgnoreChannelErrorForFieldInSubscriptionsTable {
+    async fn up(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS ignore_channel_error_for TEXT;",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, tx: &mut Transaction) -> Result<()> {
+        tx.execute(
+            "ALTER TABLE subscriptions DROP COLUMN IF EXISTS ignore_channel_error_for",
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+
+use crate::database::sqlite::SQLiteMigration;
+use crate::migration;
+
+pub(super) struct AddMaxConcurrentRetriesFieldInSubscriptionsTable;
+migration!(
+    AddMaxConcurrentRetriesFieldInSubscriptionsTable,
+    16,
+    "add max_concurrent_retries field in subscriptions table"
+);
+
+impl SQLiteMigration for AddMaxConcurrentRetriesFieldInSubscriptionsTable {
+    fn up(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions ADD COLUMN max_concurrent_retries INTEGER",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+
+    fn down(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions DROP COLUMN max_concurrent_retries",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+
+use crate::database::sqlite::SQLiteMigration;
+use crate::migration;
+
+pub(super) struct AddMaxQueuedFieldsInSubscriptionsTable;
+migration!(
+    AddMaxQueuedFieldsInSubscriptionsTable,
+    17,
+    "add max_queued fields in subscriptions table"
+);
+
+impl SQLiteMigration for AddMaxQueuedFieldsInSubscriptionsTable {
+    fn up(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions ADD COLUMN max_queued_events INTEGER",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        conn.execute(
+            "ALTER TABLE subscriptions ADD COLUMN max_queued_bytes INTEGER",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+
+    fn down(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions DROP COLUMN max_queued_events",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        conn.execute("ALTER TABLE subscriptions DROP COLUMN max_queued_bytes", [])
+            .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+
+use crate::database::sqlite::SQLiteMigration;
+use crate::migration;
+
+pub(super) struct AddMaxConcurrentBatchesFieldInSubscriptionsTable;
+migration!(
+    AddMaxConcurrentBatchesFieldInSubscriptionsTable,
+    18,
+    "add max_concurrent_batches field in subscriptions table"
+);
+
+impl SQLiteMigration for AddMaxConcurrentBatchesFieldInSubscriptionsTable {
+    fn up(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions ADD COLUMN max_concurrent_batches INTEGER",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+
+    fn down(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions DROP COLUMN max_concurrent_batches",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+}
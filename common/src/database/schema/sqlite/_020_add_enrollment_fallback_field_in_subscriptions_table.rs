@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+
+use crate::database::sqlite::SQLiteMigration;
+use crate::migration;
+
+pub(super) struct AddEnrollmentFallbackFieldInSubscriptionsTable;
+migration!(
+    AddEnrollmentFallbackFieldInSubscriptionsTable,
+    20,
+    "add enrollment_fallback field in subscriptions table"
+);
+
+impl SQLiteMigration for AddEnrollmentFallbackFieldInSubscriptionsTable {
+    fn up(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions ADD COLUMN enrollment_fallback TEXT",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+
+    fn down(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions DROP COLUMN enrollment_fallback",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, bail, Result};
+use rusqlite::Connection;
+
+use crate::database::sqlite::SQLiteMigration;
+use crate::migration;
+
+pub(super) struct AddTenantFieldInSubscriptionsTable;
+migration!(
+    AddTenantFieldInSubscriptionsTable,
+    21,
+    "add tenant field in subscriptions table and make names unique per tenant"
+);
+
+/// SQLite can not drop a UNIQUE constraint: the subscriptions table is
+/// rebuilt with `from` replaced by `to` in its definition.
+fn rebuild_subscriptions_table(conn: &Connection, from: &str, to: &str) -> Result<()> {
+    let sql: String = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'subscriptions'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+    if !sql.contains(from) {
+        bail!(
+            "Could not find \"{}\" in subscriptions table definition",
+            from
+        );
+    }
+    let sql = sql
+        .replacen("subscriptions", "subscriptions_new", 1)
+        .replacen(from, to, 1);
+
+    conn.execute(&sql, [])
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+    conn.execute(
+        "INSERT INTO subscriptions_new SELECT * FROM subscriptions",
+        [],
+    )
+    .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+    conn.execute("DROP TABLE subscriptions", [])
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+    conn.execute("ALTER TABLE subscriptions_new RENAME TO subscriptions", [])
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+    Ok(())
+}
+
+impl SQLiteMigration for AddTenantFieldInSubscriptionsTable {
+    fn up(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "ALTER TABLE subscriptions ADD COLUMN tenant TEXT NOT NULL DEFAULT ''",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        rebuild_subscriptions_table(conn, "name TEXT NOT NULL UNIQUE", "name TEXT NOT NULL")?;
+        conn.execute(
+            "CREATE UNIQUE INDEX subscriptions_tenant_name ON subscriptions (tenant, name)",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+
+    fn down(&self, conn: &Connection) -> Result<()> {
+        conn.execute("DROP INDEX subscriptions_tenant_name", [])
+            .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        conn.execute("ALTER TABLE subscriptions DROP COLUMN tenant", [])
+            .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        rebuild_subscriptions_table(conn, "name TEXT NOT NULL,", "name TEXT NOT NULL UNIQUE,")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+
+use crate::database::sqlite::SQLiteMigration;
+use crate::migration;
+
+pub(super) struct AddLastSeenIndexInHeartbeatsTable;
+migration!(
+    AddLastSeenIndexInHeartbeatsTable,
+    23,
+    "add last_seen index in heartbeats table"
+);
+
+impl SQLiteMigration for AddLastSeenIndexInHeartbeatsTable {
+    fn up(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS heartbeats_last_seen_idx ON heartbeats (last_seen)",
+            [],
+        )
+        .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+
+    fn down(&self, conn: &Connection) -> Result<()> {
+        conn.execute("DROP INDEX IF EXISTS heartbeats_last_seen_idx", [])
+            .map_err(|err| anyhow!("SQLiteError: {}", err))?;
+        Ok(())
+    }
+}
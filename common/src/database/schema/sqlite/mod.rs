@@ -17,6 +17,22 @@ use self::{
     _012_alter_outputs_files_config::AlterOutputsFilesConfig,
     _013_add_max_elements_field_in_subscriptions_table::AddMaxElementsFieldInSubscriptionsTable,
     _014_alter_client_filter_in_subscriptions::AlterClientFilterInSubscriptionsTable,
+    _015_add_require_auth_field_in_subscriptions_table::AddRequireAuthFieldInSubscriptionsTable,
+    _016_add_max_concurrent_retries_field_in_subscriptions_table::AddMaxConcurrentRetriesFieldInSubscriptionsTable,
+    _017_add_max_queued_fields_in_subscriptions_table::AddMaxQueuedFieldsInSubscriptionsTable,
+    _018_add_max_concurrent_batches_field_in_subscriptions_table::AddMaxConcurrentBatchesFieldInSubscriptionsTable,
+    _019_add_trim_rendering_field_in_subscriptions_table::AddTrimRenderingFieldInSubscriptionsTable,
+    _020_add_enrollment_fallback_field_in_subscriptions_table::AddEnrollmentFallbackFieldInSubscriptionsTable,
+    _021_add_tenant_field_in_subscriptions_table::AddTenantFieldInSubscriptionsTable,
+    _022_add_event_ttl_field_in_subscriptions_table::AddEventTtlFieldInSubscriptionsTable,
+    _023_add_last_seen_index_in_heartbeats_table::AddLastSeenIndexInHeartbeatsTable,
+    _024_add_max_concurrent_clients_field_in_subscriptions_table::AddMaxConcurrentClientsFieldInSubscriptionsTable,
+    _025_add_compression_field_in_subscriptions_table::AddCompressionFieldInSubscriptionsTable,
+    _026_add_connection_retry_jitter_field_in_subscriptions_table::AddConnectionRetryJitterFieldInSubscriptionsTable,
+    _027_add_dedup_window_fields_in_subscriptions_table::AddDedupWindowFieldsInSubscriptionsTable,
+    _028_add_output_mode_field_in_subscriptions_table::AddOutputModeFieldInSubscriptionsTable,
+    _029_add_read_existing_events_since_field_in_subscriptions_table::AddReadExistingEventsSinceFieldInSubscriptionsTable,
+    _030_add_ignore_channel_error_for_field_in_subscriptions_table::AddIgnoreChannelErrorForFieldInSubscriptionsTable,
 };
 
 mod _001_create_subscriptions_table;
@@ -33,6 +49,22 @@ mod _011_add_locale_fields_in_subscriptions_table;
 mod _012_alter_outputs_files_config;
 mod _013_add_max_elements_field_in_subscriptions_table;
 mod _014_alter_client_filter_in_subscriptions;
+mod _015_add_require_auth_field_in_subscriptions_table;
+mod _016_add_max_concurrent_retries_field_in_subscriptions_table;
+mod _017_add_max_queued_fields_in_subscriptions_table;
+mod _018_add_max_concurrent_batches_field_in_subscriptions_table;
+mod _019_add_trim_rendering_field_in_subscriptions_table;
+mod _020_add_enrollment_fallback_field_in_subscriptions_table;
+mod _021_add_tenant_field_in_subscriptions_table;
+mod _022_add_event_ttl_field_in_subscriptions_table;
+mod _023_add_last_seen_index_in_heartbeats_table;
+mod _024_add_max_concurrent_clients_field_in_subscriptions_table;
+mod _025_add_compression_field_in_subscriptions_table;
+mod _026_add_connection_retry_jitter_field_in_subscriptions_table;
+mod _027_add_dedup_window_fields_in_subscriptions_table;
+mod _028_add_output_mode_field_in_subscriptions_table;
+mod _029_add_read_existing_events_since_field_in_subscriptions_table;
+mod _030_add_ignore_channel_error_for_field_in_subscriptions_table;
 
 pub fn register_migrations(sqlite_db: &mut SQLiteDatabase) {
     sqlite_db.register_migration(Arc::new(CreateSubscriptionsTable));
@@ -49,4 +81,22 @@ pub fn register_migrations(sqlite_db: &mut SQLiteDatabase) {
     sqlite_db.register_migration(Arc::new(AlterOutputsFilesConfig));
     sqlite_db.register_migration(Arc::new(AddMaxElementsFieldInSubscriptionsTable));
     sqlite_db.register_migration(Arc::new(AlterClientFilterInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddRequireAuthFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddMaxConcurrentRetriesFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddMaxQueuedFieldsInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddMaxConcurrentBatchesFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddTrimRenderingFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddEnrollmentFallbackFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddTenantFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddEventTtlFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddLastSeenIndexInHeartbeatsTable));
+    sqlite_db.register_migration(Arc::new(AddMaxConcurrentClientsFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddCompressionFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddConnectionRetryJitterFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddDedupWindowFieldsInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(AddOutputModeFieldInSubscriptionsTable));
+    sqlite_db.register_migration(Arc::new(
+        AddReadExistingEventsSinceFieldInSubscriptionsTable,
+    ));
+    sqlite_db.register_migration(Arc::new(AddIgnoreChannelErrorForFieldInSubscriptionsTable));
 }
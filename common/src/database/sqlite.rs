@@ -27,6 +27,7 @@
 //
 use anyhow::{anyhow, ensure, Context, Error, Result};
 use async_trait::async_trait;
+use chrono::SecondsFormat;
 use deadpool_sqlite::{Config, Pool, Runtime};
 use log::warn;
 use rusqlite::{named_params, params, Connection, OptionalExtension, Row};
@@ -41,8 +42,11 @@ use crate::bookmark::BookmarkData;
 use crate::database::Database;
 use crate::heartbeat::{HeartbeatData, HeartbeatsCache};
 use crate::subscription::{
-    ContentFormat, InternalVersion, ClientFilter, SubscriptionData, SubscriptionMachine, SubscriptionMachineState, SubscriptionStatsCounters, SubscriptionUuid
+    ClientFilter, ContentFormat, EventsCompression, InternalVersion, ManifestOption, OutputMode,
+    RenderingElement, RequireAuth, SubscriptionData, SubscriptionMachine, SubscriptionMachineState,
+    SubscriptionStatsCounters, SubscriptionUuid,
 };
+use crate::utils::parse_rfc3339_utc;
 
 use super::schema::{Migration, MigrationBase, Version};
 
@@ -166,6 +170,9 @@ fn row_to_subscription(row: &Row) -> Result<SubscriptionData> {
 
     let content_format = ContentFormat::from_str(row.get::<&str, String>("content_format")?.as_ref())?;
 
+    // SQLite integers are signed
+    let max_queued_bytes: Option<i64> = row.get("max_queued_bytes")?;
+
     let client_filter_op: Option<String> = row.get("client_filter_op")?;
 
     let client_filter = match client_filter_op {
@@ -176,10 +183,54 @@ fn row_to_subscription(row: &Row) -> Result<SubscriptionData> {
         None => None
     };
 
+    let require_auth = match row.get::<&str, Option<String>>("require_auth")? {
+        Some(require_auth) => RequireAuth::from_str(&require_auth)?,
+        None => RequireAuth::default(),
+    };
+
+    let compression = match row.get::<&str, Option<String>>("compression")? {
+        Some(compression) => EventsCompression::from_str(&compression)?,
+        None => EventsCompression::default(),
+    };
+
+    let output_mode = match row.get::<&str, Option<String>>("output_mode")? {
+        Some(output_mode) => OutputMode::from_str(&output_mode)?,
+        None => OutputMode::default(),
+    };
+
+    let read_existing_events_since =
+        match row.get::<&str, Option<String>>("read_existing_events_since")? {
+            Some(since) => Some(parse_rfc3339_utc(&since)?),
+            None => None,
+        };
+
+    let ignore_channel_error_for =
+        match row.get::<&str, Option<String>>("ignore_channel_error_for")? {
+            Some(channels) => Some(
+                serde_json::from_str(&channels)
+                    .context("Failed to parse subscription ignore_channel_error_for")?,
+            ),
+            None => None,
+        };
+
+    let trim_rendering = match row.get::<&str, Option<String>>("trim_rendering")? {
+        Some(trim_rendering) => RenderingElement::from_list(&trim_rendering)?,
+        None => BTreeSet::new(),
+    };
+
+    let enrollment_fallback = match row.get::<&str, Option<String>>("enrollment_fallback")? {
+        Some(enrollment_fallback) => ManifestOption::from_list(&enrollment_fallback)?,
+        None => BTreeSet::new(),
+    };
+
+    // Subscriptions without tenant are stored with an empty tenant
+    let tenant: String = row.get("tenant")?;
+
     let mut subscription= SubscriptionData::new(&name, &query);
     subscription.set_uuid(SubscriptionUuid(Uuid::parse_str(&uuid)?))
         .set_uri(row.get("uri")?)
         .set_revision(row.get("revision")?)
+        .set_tenant(Some(tenant).filter(|tenant| !tenant.is_empty()))
         .set_heartbeat_interval(row.get("heartbeat_interval")?)
         .set_connection_retry_count(row.get("connection_retry_count")?)
         .set_connection_retry_interval(row.get("connection_retry_interval")?)
@@ -193,6 +244,25 @@ fn row_to_subscription(row: &Row) -> Result<SubscriptionData> {
         .set_locale(row.get("locale")?)
         .set_data_locale(row.get("data_locale")?)
         .set_client_filter(client_filter)
+        .set_require_auth(require_auth)
+        .set_compression(compression)
+        .set_output_mode(output_mode)
+        .set_read_existing_events_since(read_existing_events_since)
+        .set_ignore_channel_error_for(ignore_channel_error_for)
+        .set_max_concurrent_retries(row.get("max_concurrent_retries")?)
+        .set_max_queued_events(row.get("max_queued_events")?)
+        .set_max_queued_bytes(match max_queued_bytes {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        })
+        .set_max_concurrent_batches(row.get("max_concurrent_batches")?)
+        .set_event_ttl(row.get("event_ttl")?)
+        .set_max_concurrent_clients(row.get("max_concurrent_clients")?)
+        .set_connection_retry_jitter(row.get("connection_retry_jitter")?)
+        .set_dedup_window(row.get("dedup_window")?)
+        .set_dedup_window_ttl(row.get("dedup_window_ttl")?)
+        .set_trim_rendering(trim_rendering)
+        .set_enrollment_fallback(enrollment_fallback)
         .set_outputs(outputs);
 
     // This needs to be done at the end because version is updated each time
@@ -508,6 +578,28 @@ impl Database for SQLiteDatabase {
         Ok(())
     }
 
+    async fn delete_heartbeats_before(&self, last_seen_before: i64, limit: u32) -> Result<u64> {
+        let count = self
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                conn.execute(
+                    r#"DELETE FROM heartbeats
+                        WHERE rowid IN (
+                            SELECT rowid
+                            FROM heartbeats
+                            WHERE last_seen < ?1
+                            LIMIT ?2
+                        )"#,
+                    params![last_seen_before, limit],
+                )
+            })
+            .await
+            .map_err(|err| anyhow!(format!("{}", err)))??;
+        Ok(count.try_into()?)
+    }
+
     async fn get_subscriptions(&self) -> Result<Vec<SubscriptionData>> {
         self.pool
             .get()
@@ -543,7 +635,9 @@ impl Database for SQLiteDatabase {
                     conn.query_row_and_then(
                         r#"SELECT *
                         FROM subscriptions
-                        WHERE name = :identifier OR uuid = :identifier"#,
+                        WHERE uuid = :identifier
+                            OR (tenant = '' AND name = :identifier)
+                            OR tenant || '/' || name = :identifier"#,
                         &[(":identifier", &identifier)],
                         row_to_subscription,
                     )
@@ -559,6 +653,14 @@ impl Database for SQLiteDatabase {
         let client_filter_kind = subscription.client_filter().map(|f| f.kind().to_string());
         let client_filter_flags = subscription.client_filter().map(|f| f.flags().bits());
         let client_filter_targets = subscription.client_filter().and_then(|f| f.targets_to_opt_string());
+        let max_queued_bytes: Option<i64> = match subscription.max_queued_bytes() {
+            Some(x) => Some(x.try_into()?),
+            None => None,
+        };
+        let ignore_channel_error_for = match subscription.ignore_channel_error_for() {
+            Some(channels) => Some(serde_json::to_string(channels)?),
+            None => None,
+        };
 
         let count = self
             .pool
@@ -570,12 +672,18 @@ impl Database for SQLiteDatabase {
                     heartbeat_interval, connection_retry_count, connection_retry_interval,
                     max_time, max_elements, max_envelope_size, enabled, read_existing_events, content_format,
                     ignore_channel_error, client_filter_op, client_filter_kind, client_filter_flags, client_filter_targets, outputs, locale,
-                    data_locale)
+                    data_locale, require_auth, max_concurrent_retries, max_queued_events, max_queued_bytes, max_concurrent_batches,
+                    trim_rendering, enrollment_fallback, tenant, event_ttl, max_concurrent_clients, compression,
+                    connection_retry_jitter, dedup_window, dedup_window_ttl, output_mode, read_existing_events_since,
+                    ignore_channel_error_for)
                     VALUES (:uuid, :version, :revision, :name, :uri, :query,
                         :heartbeat_interval, :connection_retry_count, :connection_retry_interval,
                         :max_time, :max_elements, :max_envelope_size, :enabled, :read_existing_events, :content_format,
                         :ignore_channel_error, :client_filter_op, :client_filter_kind, :client_filter_flags, :client_filter_targets, :outputs,
-                        :locale, :data_locale)
+                        :locale, :data_locale, :require_auth, :max_concurrent_retries, :max_queued_events, :max_queued_bytes, :max_concurrent_batches,
+                        :trim_rendering, :enrollment_fallback, :tenant, :event_ttl, :max_concurrent_clients, :compression,
+                        :connection_retry_jitter, :dedup_window, :dedup_window_ttl, :output_mode, :read_existing_events_since,
+                        :ignore_channel_error_for)
                     ON CONFLICT (uuid) DO UPDATE SET
                         version = excluded.version,
                         revision = excluded.revision,
@@ -598,7 +706,24 @@ impl Database for SQLiteDatabase {
                         client_filter_targets = excluded.client_filter_targets,
                         outputs = excluded.outputs,
                         locale = excluded.locale,
-                        data_locale = excluded.data_locale"#,
+                        data_locale = excluded.data_locale,
+                        require_auth = excluded.require_auth,
+                        max_concurrent_retries = excluded.max_concurrent_retries,
+                        max_queued_events = excluded.max_queued_events,
+                        max_queued_bytes = excluded.max_queued_bytes,
+                        max_concurrent_batches = excluded.max_concurrent_batches,
+                        trim_rendering = excluded.trim_rendering,
+                        enrollment_fallback = excluded.enrollment_fallback,
+                        event_ttl = excluded.event_ttl,
+                        max_concurrent_clients = excluded.max_concurrent_clients,
+                        compression = excluded.compression,
+                        connection_retry_jitter = excluded.connection_retry_jitter,
+                        dedup_window = excluded.dedup_window,
+                        dedup_window_ttl = excluded.dedup_window_ttl,
+                        output_mode = excluded.output_mode,
+                        read_existing_events_since = excluded.read_existing_events_since,
+                        ignore_channel_error_for = excluded.ignore_channel_error_for
+                    WHERE subscriptions.tenant = excluded.tenant"#,
                     named_params! {
                         ":uuid": subscription.uuid_string(),
                         ":version": subscription.internal_version().to_string(),
@@ -623,6 +748,25 @@ impl Database for SQLiteDatabase {
                         ":outputs": serde_json::to_string(subscription.outputs())?,
                         ":locale": subscription.locale(),
                         ":data_locale": subscription.data_locale(),
+                        ":require_auth": subscription.require_auth().to_string(),
+                        ":max_concurrent_retries": subscription.max_concurrent_retries(),
+                        ":max_queued_events": subscription.max_queued_events(),
+                        ":max_queued_bytes": max_queued_bytes,
+                        ":max_concurrent_batches": subscription.max_concurrent_batches(),
+                        ":trim_rendering": subscription.trim_rendering_to_opt_string(),
+                        ":enrollment_fallback": subscription.enrollment_fallback_to_opt_string(),
+                        ":tenant": subscription.tenant().map(String::as_str).unwrap_or_default(),
+                        ":event_ttl": subscription.event_ttl(),
+                        ":max_concurrent_clients": subscription.max_concurrent_clients(),
+                        ":compression": subscription.compression().to_string(),
+                        ":connection_retry_jitter": subscription.connection_retry_jitter(),
+                        ":dedup_window": subscription.dedup_window(),
+                        ":dedup_window_ttl": subscription.dedup_window_ttl(),
+                        ":output_mode": subscription.output_mode().to_string(),
+                        ":read_existing_events_since": subscription
+                            .read_existing_events_since()
+                            .map(|since| since.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                        ":ignore_channel_error_for": ignore_channel_error_for,
                     },
                 )
                 .map_err(|err| anyhow!(err))
@@ -630,6 +774,12 @@ impl Database for SQLiteDatabase {
             .await
             .map_err(|err| anyhow!(format!("{}", err)))??;
 
+        // The upsert does not update subscriptions of other tenants
+        ensure!(
+            count != 0,
+            "Subscription {} already exists in another tenant",
+            subscription.uuid()
+        );
         ensure!(count == 1, "Only one row must have been updated");
 
         Ok(())
@@ -966,6 +1116,30 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_set_source_bookmark() -> Result<()> {
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let path = temp_file.into_temp_path();
+        {
+            crate::database::tests::test_set_source_bookmark(db_with_migrations(&path).await?)
+                .await?;
+        }
+        path.close()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_tenants() -> Result<()> {
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let path = temp_file.into_temp_path();
+        {
+            crate::database::tests::test_subscriptions_tenants(db_with_migrations(&path).await?)
+                .await?;
+        }
+        path.close()?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_heartbeats() -> Result<()> {
         let temp_file = tempfile::NamedTempFile::new()?;
@@ -977,6 +1151,29 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_export_import_bookmarks() -> Result<()> {
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let path = temp_file.into_temp_path();
+        {
+            crate::database::tests::test_export_import_bookmarks(db_with_migrations(&path).await?)
+                .await?;
+        }
+        path.close()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prune_heartbeats() -> Result<()> {
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let path = temp_file.into_temp_path();
+        {
+            crate::database::tests::test_prune_heartbeats(db_with_migrations(&path).await?).await?;
+        }
+        path.close()?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_subscriptions() -> Result<()> {
         let temp_file = tempfile::NamedTempFile::new()?;
@@ -1,15 +1,27 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use glob::Pattern;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use strum::{Display, AsRefStr, EnumString};
 use bitflags::bitflags;
+use log::warn;
 use std::fmt::{Display, Formatter};
 
 use crate::{
-    subscription::{SubscriptionData, DEFAULT_OUTPUT_ENABLED},
+    subscription::{
+        FieldMap, ProviderRoute, SubscriptionData, SubscriptionUuid, DEFAULT_CEF_DEVICE_PRODUCT,
+        DEFAULT_CEF_DEVICE_VENDOR, DEFAULT_CEF_VERSION, DEFAULT_CSV_DELIMITER, DEFAULT_CSV_HEADER,
+        DEFAULT_DEDUPE_MAX_SOURCES, DEFAULT_DEGRADATION_COOLDOWN_SECS,
+        DEFAULT_DEGRADATION_SLOW_RESPONSES, DEFAULT_HTTP_BATCH_SIZE, DEFAULT_HTTP_BATCH_TIMEOUT_MS,
+        DEFAULT_OUTPUT_ENABLED, DEFAULT_RFC5424_ENTERPRISE_NUMBER, DEFAULT_SYSLOG_APP_NAME,
+        DEFAULT_SYSLOG_PORT, DEFAULT_SYSLOG_TLS_PORT,
+    },
     transformers::output_files_use_path::transform_files_config_to_path,
+    utils::parse_rfc3339_utc,
 };
 
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
@@ -18,11 +30,74 @@ struct KafkaConfiguration {
     pub topic: String,
     #[serde(default)]
     pub options: HashMap<String, String>,
+    pub headers: Option<BTreeMap<String, String>>,
+    pub acks: Option<String>,
+    pub compression_type: Option<String>,
+    pub enable_idempotence: Option<bool>,
 }
 
-impl From<KafkaConfiguration> for crate::subscription::KafkaConfiguration {
-    fn from(value: KafkaConfiguration) -> Self {
-        crate::subscription::KafkaConfiguration::new(value.topic, value.options)
+const KAFKA_ACKS: [&str; 3] = ["0", "1", "all"];
+const KAFKA_COMPRESSION_TYPES: [&str; 5] = ["none", "gzip", "snappy", "lz4", "zstd"];
+
+impl KafkaConfiguration {
+    /// Merges the typed producer settings into `options`. Typed settings
+    /// take precedence over the values of the `options` map.
+    fn producer_options(&self) -> Result<HashMap<String, String>> {
+        if let Some(acks) = &self.acks {
+            if !KAFKA_ACKS.contains(&acks.as_str()) {
+                bail!(
+                    "Invalid Kafka acks {:?}, expected one of {:?}",
+                    acks,
+                    KAFKA_ACKS
+                );
+            }
+        }
+        if let Some(compression_type) = &self.compression_type {
+            if !KAFKA_COMPRESSION_TYPES.contains(&compression_type.as_str()) {
+                bail!(
+                    "Invalid Kafka compression_type {:?}, expected one of {:?}",
+                    compression_type,
+                    KAFKA_COMPRESSION_TYPES
+                );
+            }
+        }
+
+        let mut options = self.options.clone();
+        let typed = [
+            ("acks", self.acks.clone()),
+            ("compression.type", self.compression_type.clone()),
+            (
+                "enable.idempotence",
+                self.enable_idempotence.map(|value| value.to_string()),
+            ),
+        ];
+        for (key, value) in typed {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            match options.insert(key.to_string(), value.clone()) {
+                Some(previous) if previous != value => warn!(
+                    "Kafka option {:?} = {:?} of topic {} is overridden by {:?}",
+                    key, previous, self.topic, value
+                ),
+                _ => (),
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl TryFrom<KafkaConfiguration> for crate::subscription::KafkaConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: KafkaConfiguration) -> std::result::Result<Self, Self::Error> {
+        let options = value.producer_options()?;
+        let mut config = crate::subscription::KafkaConfiguration::new(value.topic, options);
+        if let Some(headers) = value.headers {
+            config.set_headers(headers)?;
+        }
+        Ok(config)
     }
 }
 
@@ -31,11 +106,36 @@ impl From<KafkaConfiguration> for crate::subscription::KafkaConfiguration {
 struct RedisConfiguration {
     pub addr: String,
     pub list: String,
+    pub mode: Option<RedisMode>,
+    pub max_len: Option<u64>,
 }
 
-impl From<RedisConfiguration> for crate::subscription::RedisConfiguration {
-    fn from(value: RedisConfiguration) -> Self {
-        crate::subscription::RedisConfiguration::new(value.addr, value.list)
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum RedisMode {
+    List,
+    Stream,
+}
+
+impl From<RedisMode> for crate::subscription::RedisMode {
+    fn from(value: RedisMode) -> Self {
+        match value {
+            RedisMode::List => crate::subscription::RedisMode::List,
+            RedisMode::Stream => crate::subscription::RedisMode::Stream,
+        }
+    }
+}
+
+impl TryFrom<RedisConfiguration> for crate::subscription::RedisConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: RedisConfiguration) -> std::result::Result<Self, Self::Error> {
+        let mut config = crate::subscription::RedisConfiguration::new(value.addr, value.list);
+        config.set_mode(
+            value.mode.map(RedisMode::into).unwrap_or_default(),
+            value.max_len,
+        )?;
+        Ok(config)
     }
 }
 
@@ -46,6 +146,24 @@ enum StringOrVecString {
     Vec(Vec<String>),
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum TcpFraming {
+    Newline,
+    LengthPrefixed,
+    OctetCounting,
+}
+
+impl From<TcpFraming> for crate::subscription::TcpFraming {
+    fn from(value: TcpFraming) -> Self {
+        match value {
+            TcpFraming::Newline => crate::subscription::TcpFraming::Newline,
+            TcpFraming::LengthPrefixed => crate::subscription::TcpFraming::LengthPrefixed,
+            TcpFraming::OctetCounting => crate::subscription::TcpFraming::OctetCounting,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
 struct TcpConfiguration {
@@ -58,13 +176,21 @@ struct TcpConfiguration {
     pub tls_certificate_authorities: Option<StringOrVecString>,
     pub tls_certificate: Option<String>,
     pub tls_key: Option<String>,
+    pub framing: Option<TcpFraming>,
+    pub reconnect_initial_ms: Option<u64>,
+    pub reconnect_max_ms: Option<u64>,
+    pub buffer_max_events: Option<u64>,
+    pub tcp_keepalive_secs: Option<u64>,
+    pub tcp_nodelay: Option<bool>,
+    // Accept String or Vec<String>
+    pub tls_pinned_sha256: Option<StringOrVecString>,
 }
 
 impl TryFrom<TcpConfiguration> for crate::subscription::TcpConfiguration {
     type Error = anyhow::Error;
 
     fn try_from(value: TcpConfiguration) -> std::result::Result<Self, Self::Error> {
-        crate::subscription::TcpConfiguration::new(
+        let mut config = crate::subscription::TcpConfiguration::new(
             value.host.clone(),
             value.port,
             value.tls_enabled.unwrap_or(false),
@@ -76,10 +202,222 @@ impl TryFrom<TcpConfiguration> for crate::subscription::TcpConfiguration {
             value.tls_certificate.clone(),
             value.tls_key.clone(),
         )
+        .with_context(|| format!("Loading {:?}", value))?;
+        if let Some(framing) = value.framing {
+            config.set_framing(framing.into());
+        }
+        config
+            .set_reconnect(value.reconnect_initial_ms, value.reconnect_max_ms)
+            .with_context(|| format!("Loading {:?}", value))?;
+        config
+            .set_buffer_max_events(value.buffer_max_events)
+            .with_context(|| format!("Loading {:?}", value))?;
+        config
+            .set_tcp_keepalive_secs(value.tcp_keepalive_secs)
+            .with_context(|| format!("Loading {:?}", value))?;
+        config.set_tcp_nodelay(value.tcp_nodelay.unwrap_or(false));
+        config
+            .set_tls_pinned_sha256(match &value.tls_pinned_sha256 {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            })
+            .with_context(|| format!("Loading {:?}", value))?;
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SyslogTlsConfiguration {
+    pub host: String,
+    pub port: Option<u16>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: StringOrVecString,
+    pub tls_certificate: String,
+    pub tls_key: String,
+}
+
+impl TryFrom<SyslogTlsConfiguration> for crate::subscription::SyslogTlsConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SyslogTlsConfiguration) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::SyslogTlsConfiguration::new(
+            value.host.clone(),
+            value.port.unwrap_or(DEFAULT_SYSLOG_TLS_PORT),
+            match &value.tls_certificate_authorities {
+                StringOrVecString::String(s) => Vec::from([s.clone()]),
+                StringOrVecString::Vec(v) => v.clone(),
+            },
+            value.tls_certificate.clone(),
+            value.tls_key.clone(),
+        )
+        .with_context(|| format!("Loading {:?}", value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum SyslogTransport {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+impl From<SyslogTransport> for crate::subscription::SyslogTransport {
+    fn from(value: SyslogTransport) -> Self {
+        match value {
+            SyslogTransport::Udp => crate::subscription::SyslogTransport::Udp,
+            SyslogTransport::Tcp => crate::subscription::SyslogTransport::Tcp,
+            SyslogTransport::Tls => crate::subscription::SyslogTransport::Tls,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SyslogConfiguration {
+    pub host: String,
+    pub port: Option<u16>,
+    pub transport: Option<SyslogTransport>,
+    pub facility: Option<String>,
+    pub app_name: Option<String>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
+    pub tls_certificate: Option<String>,
+    pub tls_key: Option<String>,
+}
+
+impl TryFrom<SyslogConfiguration> for crate::subscription::SyslogConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SyslogConfiguration) -> std::result::Result<Self, Self::Error> {
+        let transport = value
+            .transport
+            .map(SyslogTransport::into)
+            .unwrap_or_default();
+        let facility = match &value.facility {
+            Some(facility) => facility.parse().map_err(|_| {
+                anyhow!(
+                    "Invalid syslog facility {:?}, expected one of {:?}",
+                    facility,
+                    <crate::subscription::SyslogFacility as strum::VariantNames>::VARIANTS
+                )
+            })?,
+            None => crate::subscription::SyslogFacility::default(),
+        };
+        let default_port = if transport == crate::subscription::SyslogTransport::Tls {
+            DEFAULT_SYSLOG_TLS_PORT
+        } else {
+            DEFAULT_SYSLOG_PORT
+        };
+        crate::subscription::SyslogConfiguration::new(
+            value.host.clone(),
+            value.port.unwrap_or(default_port),
+            transport,
+            facility,
+            value
+                .app_name
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SYSLOG_APP_NAME.to_string()),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+            value.tls_certificate.clone(),
+            value.tls_key.clone(),
+        )
         .with_context(|| format!("Loading {:?}", value))
     }
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum HttpFraming {
+    Newline,
+    JsonArray,
+}
+
+impl From<HttpFraming> for crate::subscription::HttpFraming {
+    fn from(value: HttpFraming) -> Self {
+        match value {
+            HttpFraming::Newline => crate::subscription::HttpFraming::Newline,
+            HttpFraming::JsonArray => crate::subscription::HttpFraming::JsonArray,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct HttpConfiguration {
+    pub url: String,
+    pub headers: Option<BTreeMap<String, String>>,
+    pub batch_size: Option<u32>,
+    pub batch_timeout_ms: Option<u64>,
+    pub idle_flush_ms: Option<u64>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
+    pub framing: Option<HttpFraming>,
+}
+
+impl TryFrom<HttpConfiguration> for crate::subscription::HttpConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: HttpConfiguration) -> std::result::Result<Self, Self::Error> {
+        let mut config = crate::subscription::HttpConfiguration::new(
+            value.url.clone(),
+            value.headers.clone().unwrap_or_default(),
+            value.batch_size.unwrap_or(DEFAULT_HTTP_BATCH_SIZE),
+            value
+                .batch_timeout_ms
+                .unwrap_or(DEFAULT_HTTP_BATCH_TIMEOUT_MS),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+            value.framing.map(HttpFraming::into).unwrap_or_default(),
+        )
+        .with_context(|| format!("Loading {:?}", value))?;
+        config
+            .set_idle_flush_ms(value.idle_flush_ms)
+            .with_context(|| format!("Loading {:?}", value))?;
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct FilesRotation {
+    pub max_size_bytes: Option<u64>,
+    pub max_age_secs: Option<u64>,
+}
+
+impl TryFrom<FilesRotation> for crate::subscription::FilesRotation {
+    type Error = anyhow::Error;
+
+    fn try_from(value: FilesRotation) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::FilesRotation::new(value.max_size_bytes, value.max_age_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum FilesCompression {
+    None,
+    Gzip,
+}
+
+impl From<FilesCompression> for crate::subscription::FilesCompression {
+    fn from(value: FilesCompression) -> Self {
+        match value {
+            FilesCompression::None => crate::subscription::FilesCompression::None,
+            FilesCompression::Gzip => crate::subscription::FilesCompression::Gzip,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
 struct FilesConfiguration {
@@ -88,6 +426,8 @@ struct FilesConfiguration {
     pub split_on_addr_index: Option<u8>,
     pub append_node_name: Option<bool>,
     pub filename: Option<String>,
+    pub rotation: Option<FilesRotation>,
+    pub compression: Option<FilesCompression>,
 }
 
 impl TryFrom<FilesConfiguration> for crate::subscription::FilesConfiguration {
@@ -110,7 +450,14 @@ impl TryFrom<FilesConfiguration> for crate::subscription::FilesConfiguration {
                 )
             })?,
         };
-        Ok(crate::subscription::FilesConfiguration::new(path))
+        let mut config = crate::subscription::FilesConfiguration::new(path);
+        if let Some(rotation) = value.rotation {
+            config.set_rotation(Some(rotation.try_into()?));
+        }
+        if let Some(compression) = value.compression {
+            config.set_compression(compression.into());
+        }
+        Ok(config)
     }
 }
 
@@ -127,885 +474,6309 @@ impl From<UnixDatagramConfiguration> for crate::subscription::UnixDatagramConfig
 }
 
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
-#[serde(tag = "driver", content = "config")]
-enum SubscriptionOutputDriver {
-    Files(FilesConfiguration),
-    Kafka(KafkaConfiguration),
-    Tcp(TcpConfiguration),
-    Redis(RedisConfiguration),
-    UnixDatagram(UnixDatagramConfiguration),
+#[serde(deny_unknown_fields)]
+struct UnixStreamConfiguration {
+    pub path: String,
+    pub framing: Option<TcpFraming>,
+    pub reconnect_initial_ms: Option<u64>,
+    pub reconnect_max_ms: Option<u64>,
 }
 
-impl TryFrom<SubscriptionOutputDriver> for crate::subscription::SubscriptionOutputDriver {
+impl TryFrom<UnixStreamConfiguration> for crate::subscription::UnixStreamConfiguration {
     type Error = anyhow::Error;
 
-    fn try_from(value: SubscriptionOutputDriver) -> std::result::Result<Self, Self::Error> {
-        Ok(match value {
-            SubscriptionOutputDriver::Files(config) => {
-                crate::subscription::SubscriptionOutputDriver::Files(config.try_into()?)
-            }
-            SubscriptionOutputDriver::Kafka(config) => {
-                crate::subscription::SubscriptionOutputDriver::Kafka(config.into())
-            }
-            SubscriptionOutputDriver::Tcp(config) => {
-                crate::subscription::SubscriptionOutputDriver::Tcp(config.try_into()?)
-            }
-            SubscriptionOutputDriver::Redis(config) => {
-                crate::subscription::SubscriptionOutputDriver::Redis(config.into())
-            }
-            SubscriptionOutputDriver::UnixDatagram(config) => {
-                crate::subscription::SubscriptionOutputDriver::UnixDatagram(config.into())
-            }
-        })
+    fn try_from(value: UnixStreamConfiguration) -> std::result::Result<Self, Self::Error> {
+        let mut config = crate::subscription::UnixStreamConfiguration::new(value.path.clone());
+        if let Some(framing) = value.framing {
+            config.set_framing(framing.into());
+        }
+        config
+            .set_reconnect(value.reconnect_initial_ms, value.reconnect_max_ms)
+            .with_context(|| format!("Loading {:?}", value))?;
+        Ok(config)
     }
 }
 
-#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
-struct SubscriptionOutput {
-    pub format: SubscriptionOutputFormat,
-    #[serde(flatten)]
-    pub driver: SubscriptionOutputDriver,
-    pub enabled: Option<bool>,
+struct StdStreamConfiguration {
+    pub flush_each: Option<bool>,
 }
 
-impl TryFrom<SubscriptionOutput> for crate::subscription::SubscriptionOutput {
-    type Error = anyhow::Error;
-
-    fn try_from(value: SubscriptionOutput) -> std::result::Result<Self, Self::Error> {
-        Ok(crate::subscription::SubscriptionOutput::new(
-            value.format.into(),
-            value.driver.try_into()?,
-            value.enabled.unwrap_or(DEFAULT_OUTPUT_ENABLED),
-        ))
+impl From<StdStreamConfiguration> for crate::subscription::StdStreamConfiguration {
+    fn from(value: StdStreamConfiguration) -> Self {
+        let mut config = crate::subscription::StdStreamConfiguration::new();
+        if let Some(flush_each) = value.flush_each {
+            config.set_flush_each(flush_each);
+        }
+        config
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
-enum SubscriptionOutputFormat {
-    Json,
-    Raw,
-    RawJson,
-    Nxlog,
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct NatsConfiguration {
+    // Accept String or Vec<String>
+    pub servers: StringOrVecString,
+    pub subject: String,
+    pub jetstream: Option<bool>,
+    pub credentials_file: Option<String>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
 }
 
-impl From<SubscriptionOutputFormat> for crate::subscription::SubscriptionOutputFormat {
-    fn from(value: SubscriptionOutputFormat) -> Self {
-        match value {
-            SubscriptionOutputFormat::Json => crate::subscription::SubscriptionOutputFormat::Json,
-            SubscriptionOutputFormat::Raw => crate::subscription::SubscriptionOutputFormat::Raw,
-            SubscriptionOutputFormat::RawJson => {
-                crate::subscription::SubscriptionOutputFormat::RawJson
-            }
-            SubscriptionOutputFormat::Nxlog => crate::subscription::SubscriptionOutputFormat::Nxlog,
-        }
+impl TryFrom<NatsConfiguration> for crate::subscription::NatsConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: NatsConfiguration) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::NatsConfiguration::new(
+            match &value.servers {
+                StringOrVecString::String(s) => Vec::from([s.clone()]),
+                StringOrVecString::Vec(v) => v.clone(),
+            },
+            value.subject.clone(),
+            value.jetstream.unwrap_or(false),
+            value.credentials_file.clone(),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+        )
+        .with_context(|| format!("Loading {:?}", value))
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
-enum ClientFilterOperation {
-    Only,
-    Except,
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct MqttConfiguration {
+    pub broker: String,
+    pub port: Option<u16>,
+    pub topic: String,
+    pub qos: Option<u8>,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
 }
 
-impl From<ClientFilterOperation> for crate::subscription::ClientFilterOperation {
-    fn from(value: ClientFilterOperation) -> Self {
-        match value {
-            ClientFilterOperation::Except => crate::subscription::ClientFilterOperation::Except,
-            ClientFilterOperation::Only => crate::subscription::ClientFilterOperation::Only,
-        }
+impl TryFrom<MqttConfiguration> for crate::subscription::MqttConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: MqttConfiguration) -> std::result::Result<Self, Self::Error> {
+        let mut config = crate::subscription::MqttConfiguration::new(
+            value.broker.clone(),
+            value.port,
+            value.topic.clone(),
+            value.qos.unwrap_or(0),
+            value.client_id.clone(),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+        )
+        .with_context(|| format!("Loading {:?}", value))?;
+        config
+            .set_credentials(value.username.clone(), value.password.clone())
+            .with_context(|| format!("Loading {:?}", value))?;
+        Ok(config)
     }
 }
 
-#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Display, AsRefStr, EnumString)]
-#[strum(ascii_case_insensitive)]
-pub enum ClientFilterType {
-    #[default]
-    KerberosPrinc,
-    TLSCertSubject,
-    MachineID,
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct EventHubsConfiguration {
+    pub namespace: String,
+    pub hub: String,
+    pub connection_string: Option<String>,
+    pub managed_identity: Option<bool>,
+    pub partition_key: Option<String>,
 }
 
-impl From<ClientFilterType> for crate::subscription::ClientFilterType {
-    fn from(value: ClientFilterType) -> Self {
-        match value {
-            ClientFilterType::KerberosPrinc => crate::subscription::ClientFilterType::KerberosPrinc,
-            ClientFilterType::TLSCertSubject => crate::subscription::ClientFilterType::TLSCertSubject,
-            ClientFilterType::MachineID => crate::subscription::ClientFilterType::MachineID,
-        }
+impl TryFrom<EventHubsConfiguration> for crate::subscription::EventHubsConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: EventHubsConfiguration) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::EventHubsConfiguration::new(
+            value.namespace.clone(),
+            value.hub.clone(),
+            value.connection_string.clone(),
+            value.managed_identity.unwrap_or(false),
+            value.partition_key.clone(),
+        )
+        .with_context(|| format!("Loading {:?}", value))
     }
 }
 
-bitflags! {
-    #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-    pub struct ClientFilterFlags: u32 {
-        const CaseInsensitive = 1 << 0;
-        const GlobPattern = 1 << 1;
-    }
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct GrpcConfiguration {
+    pub endpoint: String,
+    pub metadata: Option<BTreeMap<String, String>>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
 }
 
-impl From<ClientFilterFlags> for crate::subscription::ClientFilterFlags {
-    fn from(value: ClientFilterFlags) -> Self {
-        crate::subscription::ClientFilterFlags::from_bits(value.bits()).unwrap()
+impl TryFrom<GrpcConfiguration> for crate::subscription::GrpcConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: GrpcConfiguration) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::GrpcConfiguration::new(
+            value.endpoint.clone(),
+            value.metadata.clone().unwrap_or_default(),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+        )
+        .with_context(|| format!("Loading {:?}", value))
     }
 }
 
-impl Display for ClientFilterFlags {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        bitflags::parser::to_writer_strict(self, f)
-    }
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SplunkHecConfiguration {
+    pub url: String,
+    pub token: String,
+    pub index: Option<String>,
+    pub sourcetype: Option<String>,
+    pub source: Option<String>,
+    pub batch_size: Option<u32>,
+    pub batch_timeout_ms: Option<u64>,
+    pub idle_flush_ms: Option<u64>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
 }
 
-impl Default for ClientFilterFlags {
-    fn default() -> Self {
-        Self::empty()
+impl TryFrom<SplunkHecConfiguration> for crate::subscription::SplunkHecConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SplunkHecConfiguration) -> std::result::Result<Self, Self::Error> {
+        let mut config = crate::subscription::SplunkHecConfiguration::new(
+            value.url.clone(),
+            value.token.clone(),
+            value.index.clone(),
+            value.sourcetype.clone(),
+            value.source.clone(),
+            value.batch_size.unwrap_or(DEFAULT_HTTP_BATCH_SIZE),
+            value
+                .batch_timeout_ms
+                .unwrap_or(DEFAULT_HTTP_BATCH_TIMEOUT_MS),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+        )
+        .with_context(|| format!("Loading {:?}", value))?;
+        config
+            .set_idle_flush_ms(value.idle_flush_ms)
+            .with_context(|| format!("Loading {:?}", value))?;
+        Ok(config)
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
-struct ClientFilter {
-    pub operation: ClientFilterOperation,
-    #[serde(rename = "type", default)]
-    pub kind: ClientFilterType,
-    #[serde(default)]
-    pub flags: ClientFilterFlags,
-    #[serde(alias = "cert_subjects", alias = "princs")]
-    pub targets: HashSet<String>,
+struct LokiConfiguration {
+    pub url: String,
+    pub labels: Option<BTreeMap<String, String>>,
+    pub label_templates: Option<BTreeMap<String, String>>,
+    pub allow_high_cardinality_labels: Option<bool>,
+    pub batch_size: Option<u32>,
+    pub batch_timeout_ms: Option<u64>,
+    pub idle_flush_ms: Option<u64>,
+    // Accept String or Vec<String>
+    pub tls_certificate_authorities: Option<StringOrVecString>,
 }
 
-impl TryFrom<ClientFilter> for crate::subscription::ClientFilter {
+impl TryFrom<LokiConfiguration> for crate::subscription::LokiConfiguration {
     type Error = anyhow::Error;
 
-    fn try_from(value: ClientFilter) -> std::prelude::v1::Result<Self, Self::Error> {
-        crate::subscription::ClientFilter::try_new(value.operation.into(), value.kind.into(), value.flags.into(), value.targets)
+    fn try_from(value: LokiConfiguration) -> std::result::Result<Self, Self::Error> {
+        let mut config = crate::subscription::LokiConfiguration::new(
+            value.url.clone(),
+            value.labels.clone().unwrap_or_default(),
+            value.label_templates.clone().unwrap_or_default(),
+            value.allow_high_cardinality_labels.unwrap_or(false),
+            value.batch_size.unwrap_or(DEFAULT_HTTP_BATCH_SIZE),
+            value
+                .batch_timeout_ms
+                .unwrap_or(DEFAULT_HTTP_BATCH_TIMEOUT_MS),
+            match &value.tls_certificate_authorities {
+                Some(StringOrVecString::String(s)) => Vec::from([s.clone()]),
+                Some(StringOrVecString::Vec(v)) => v.clone(),
+                _ => Vec::new(),
+            },
+        )
+        .with_context(|| format!("Loading {:?}", value))?;
+        config
+            .set_idle_flush_ms(value.idle_flush_ms)
+            .with_context(|| format!("Loading {:?}", value))?;
+        Ok(config)
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
-enum ContentFormat {
-    Raw,
-    RenderedText,
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct ZmqConfiguration {
+    pub endpoint: String,
+    pub socket_type: Option<ZmqSocketType>,
+    pub hwm: Option<u32>,
 }
 
-impl From<ContentFormat> for crate::subscription::ContentFormat {
-    fn from(value: ContentFormat) -> Self {
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum ZmqSocketType {
+    #[serde(alias = "PUSH")]
+    Push,
+    #[serde(alias = "PUB")]
+    Pub,
+}
+
+impl From<ZmqSocketType> for crate::subscription::ZmqSocketType {
+    fn from(value: ZmqSocketType) -> Self {
         match value {
-            ContentFormat::Raw => crate::subscription::ContentFormat::Raw,
-            ContentFormat::RenderedText => crate::subscription::ContentFormat::RenderedText,
+            ZmqSocketType::Push => crate::subscription::ZmqSocketType::Push,
+            ZmqSocketType::Pub => crate::subscription::ZmqSocketType::Pub,
         }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct SubscriptionOptions {
-    pub uri: Option<String>,
-    pub heartbeat_interval: Option<u32>,
-    pub connection_retry_count: Option<u16>,
-    pub connection_retry_interval: Option<u32>,
-    pub max_time: Option<u32>,
-    pub max_elements: Option<u32>,
-    pub max_envelope_size: Option<u32>,
-    pub enabled: Option<bool>,
-    pub read_existing_events: Option<bool>,
-    pub content_format: Option<ContentFormat>,
-    pub ignore_channel_error: Option<bool>,
-    pub locale: Option<String>,
-    pub data_locale: Option<String>,
+impl TryFrom<ZmqConfiguration> for crate::subscription::ZmqConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ZmqConfiguration) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::ZmqConfiguration::new(
+            value.endpoint.clone(),
+            value
+                .socket_type
+                .map(ZmqSocketType::into)
+                .unwrap_or_default(),
+            value.hwm,
+        )
+        .with_context(|| format!("Loading {:?}", value))
+    }
 }
 
-impl SubscriptionOptions {
-    pub fn feed_subscription_data(&self, data: &mut SubscriptionData) {
-        data.set_uri(self.uri.clone());
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(tag = "driver", content = "config")]
+enum SubscriptionOutputDriver {
+    Files(FilesConfiguration),
+    Kafka(KafkaConfiguration),
+    Tcp(TcpConfiguration),
+    Redis(RedisConfiguration),
+    UnixDatagram(UnixDatagramConfiguration),
+    SyslogTls(SyslogTlsConfiguration),
+    Http(HttpConfiguration),
+    Syslog(SyslogConfiguration),
+    UnixStream(UnixStreamConfiguration),
+    // `config` may be omitted
+    Stdout(Option<StdStreamConfiguration>),
+    Stderr(Option<StdStreamConfiguration>),
+    Nats(NatsConfiguration),
+    Mqtt(MqttConfiguration),
+    EventHubs(EventHubsConfiguration),
+    Grpc(GrpcConfiguration),
+    SplunkHec(SplunkHecConfiguration),
+    Loki(LokiConfiguration),
+    Zmq(ZmqConfiguration),
+}
 
-        if let Some(heartbeat_interval) = self.heartbeat_interval {
-            data.set_heartbeat_interval(heartbeat_interval);
-        }
+impl TryFrom<SubscriptionOutputDriver> for crate::subscription::SubscriptionOutputDriver {
+    type Error = anyhow::Error;
 
-        if let Some(connection_retry_count) = self.connection_retry_count {
-            data.set_connection_retry_count(connection_retry_count);
-        }
+    fn try_from(value: SubscriptionOutputDriver) -> std::result::Result<Self, Self::Error> {
+        Ok(match value {
+            SubscriptionOutputDriver::Files(config) => {
+                crate::subscription::SubscriptionOutputDriver::Files(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Kafka(config) => {
+                crate::subscription::SubscriptionOutputDriver::Kafka(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Tcp(config) => {
+                crate::subscription::SubscriptionOutputDriver::Tcp(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Redis(config) => {
+                crate::subscription::SubscriptionOutputDriver::Redis(config.try_into()?)
+            }
+            SubscriptionOutputDriver::UnixDatagram(config) => {
+                crate::subscription::SubscriptionOutputDriver::UnixDatagram(config.into())
+            }
+            SubscriptionOutputDriver::SyslogTls(config) => {
+                crate::subscription::SubscriptionOutputDriver::SyslogTls(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Http(config) => {
+                crate::subscription::SubscriptionOutputDriver::Http(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Syslog(config) => {
+                crate::subscription::SubscriptionOutputDriver::Syslog(config.try_into()?)
+            }
+            SubscriptionOutputDriver::UnixStream(config) => {
+                crate::subscription::SubscriptionOutputDriver::UnixStream(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Stdout(config) => {
+                crate::subscription::SubscriptionOutputDriver::Stdout(
+                    config.map(Into::into).unwrap_or_default(),
+                )
+            }
+            SubscriptionOutputDriver::Stderr(config) => {
+                crate::subscription::SubscriptionOutputDriver::Stderr(
+                    config.map(Into::into).unwrap_or_default(),
+                )
+            }
+            SubscriptionOutputDriver::Nats(config) => {
+                crate::subscription::SubscriptionOutputDriver::Nats(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Mqtt(config) => {
+                crate::subscription::SubscriptionOutputDriver::Mqtt(config.try_into()?)
+            }
+            SubscriptionOutputDriver::EventHubs(config) => {
+                crate::subscription::SubscriptionOutputDriver::EventHubs(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Grpc(config) => {
+                crate::subscription::SubscriptionOutputDriver::Grpc(config.try_into()?)
+            }
+            SubscriptionOutputDriver::SplunkHec(config) => {
+                crate::subscription::SubscriptionOutputDriver::SplunkHec(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Loki(config) => {
+                crate::subscription::SubscriptionOutputDriver::Loki(config.try_into()?)
+            }
+            SubscriptionOutputDriver::Zmq(config) => {
+                crate::subscription::SubscriptionOutputDriver::Zmq(config.try_into()?)
+            }
+        })
+    }
+}
 
-        if let Some(connection_retry_interval) = self.connection_retry_interval {
-            data.set_connection_retry_interval(connection_retry_interval);
-        }
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SubscriptionOutput {
+    #[serde(flatten)]
+    pub format: SubscriptionOutputFormat,
+    #[serde(flatten)]
+    pub driver: SubscriptionOutputDriver,
+    pub name: Option<String>,
+    pub enabled: Option<bool>,
+    pub source_first_seen: Option<bool>,
+    pub summary: Option<bool>,
+    pub dead_letter: Option<bool>,
+    pub sampling: Option<HashMap<EventLevel, u8>>,
+    pub field_map: Option<BTreeMap<String, String>>,
+    pub drop_unmapped: Option<bool>,
+    pub degradation: Option<OutputDegradation>,
+    pub dedupe_on_change: Option<OutputDedupeOnChange>,
+    pub payload_compression: Option<PayloadCompression>,
+    pub max_queue_age: Option<u64>,
+    pub include_collector_info: Option<bool>,
+    pub write_timeout_ms: Option<u64>,
+    pub format_options: Option<FormatOptions>,
+}
 
-        if let Some(max_time) = self.max_time {
-            data.set_max_time(max_time);
-        }
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct FormatOptions {
+    pub pretty: Option<bool>,
+    pub timestamp_field: Option<String>,
+    pub include_raw_xml: Option<bool>,
+}
 
-        data.set_max_elements(self.max_elements);
+impl TryFrom<FormatOptions> for crate::subscription::FormatOptions {
+    type Error = anyhow::Error;
 
-        if let Some(max_envelope_size) = self.max_envelope_size {
-            data.set_max_envelope_size(max_envelope_size);
-        }
+    fn try_from(value: FormatOptions) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::FormatOptions::try_new(
+            value.pretty.unwrap_or(false),
+            value.timestamp_field,
+            value.include_raw_xml.unwrap_or(false),
+        )
+    }
+}
 
-        if let Some(enabled) = self.enabled {
-            data.set_enabled(enabled);
-        }
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum PayloadCompression {
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+}
 
-        if let Some(read_existing_events) = self.read_existing_events {
-            data.set_read_existing_events(read_existing_events);
+impl From<PayloadCompression> for crate::subscription::PayloadCompression {
+    fn from(value: PayloadCompression) -> Self {
+        match value {
+            PayloadCompression::None => crate::subscription::PayloadCompression::None,
+            PayloadCompression::Gzip => crate::subscription::PayloadCompression::Gzip,
+            PayloadCompression::Zstd => crate::subscription::PayloadCompression::Zstd,
+            PayloadCompression::Lz4 => crate::subscription::PayloadCompression::Lz4,
         }
+    }
+}
 
-        if let Some(content_format) = self.content_format.clone() {
-            data.set_content_format(content_format.into());
-        }
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct OutputDegradation {
+    pub send_timeout_ms: u64,
+    pub slow_responses: Option<u32>,
+    pub cooldown_secs: Option<u64>,
+}
 
-        if let Some(ignore_channel_error) = self.ignore_channel_error {
-            data.set_ignore_channel_error(ignore_channel_error);
-        }
+impl TryFrom<OutputDegradation> for crate::subscription::OutputDegradation {
+    type Error = anyhow::Error;
 
-        data.set_locale(self.locale.clone());
-        data.set_data_locale(self.data_locale.clone());
+    fn try_from(value: OutputDegradation) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::OutputDegradation::try_new(
+            value.send_timeout_ms,
+            value
+                .slow_responses
+                .unwrap_or(DEFAULT_DEGRADATION_SLOW_RESPONSES),
+            value
+                .cooldown_secs
+                .unwrap_or(DEFAULT_DEGRADATION_COOLDOWN_SECS),
+        )
     }
 }
-#[derive(Debug, PartialEq, Clone, Eq, Deserialize)]
+
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
-struct Subscription {
-    pub uuid: Uuid,
-    #[serde(default)]
-    pub version: Uuid,
-    pub name: String,
-    pub query: String,
-    pub filter: Option<ClientFilter>,
-    pub outputs: Vec<SubscriptionOutput>,
-    pub options: Option<SubscriptionOptions>,
+struct OutputDedupeOnChange {
+    pub fields: Vec<String>,
+    pub max_sources: Option<u32>,
 }
 
-impl TryFrom<Subscription> for crate::subscription::SubscriptionData {
+impl TryFrom<OutputDedupeOnChange> for crate::subscription::OutputDedupeOnChange {
     type Error = anyhow::Error;
 
-    fn try_from(subscription: Subscription) -> std::prelude::v1::Result<Self, Self::Error> {
-        let mut data =
-            crate::subscription::SubscriptionData::new(&subscription.name, &subscription.query);
-        data.set_uuid(crate::subscription::SubscriptionUuid(subscription.uuid));
-        data.set_name(subscription.name.clone());
-        data.set_query(subscription.query.clone());
-        if let Some(filter) = subscription.filter {
-            data.set_client_filter(Some(filter.try_into()?));
-        }
+    fn try_from(value: OutputDedupeOnChange) -> std::result::Result<Self, Self::Error> {
+        crate::subscription::OutputDedupeOnChange::try_new(
+            value.fields,
+            value.max_sources.unwrap_or(DEFAULT_DEDUPE_MAX_SOURCES),
+        )
+    }
+}
 
-        if subscription.outputs.is_empty() {
-            bail!("Missing subscription outputs");
-        }
+impl TryFrom<SubscriptionOutput> for crate::subscription::SubscriptionOutput {
+    type Error = anyhow::Error;
 
-        for output in subscription.outputs.iter() {
-            data.add_output(output.clone().try_into().with_context(|| {
-                format!(
-                    "Loading subscription {} output {:?}",
-                    subscription.name, output
-                )
-            })?);
+    fn try_from(value: SubscriptionOutput) -> std::result::Result<Self, Self::Error> {
+        let mut output = crate::subscription::SubscriptionOutput::new(
+            value.format.try_into()?,
+            value.driver.try_into()?,
+            value.enabled.unwrap_or(DEFAULT_OUTPUT_ENABLED),
+        );
+        output.set_source_first_seen(value.source_first_seen.unwrap_or(false));
+        output.set_summary(value.summary.unwrap_or(false));
+        if output.source_first_seen() && output.summary() {
+            bail!("An output can not use both source_first_seen and summary");
         }
-
-        if let Some(options) = subscription.options {
-            options.feed_subscription_data(&mut data);
+        output.set_dead_letter(value.dead_letter.unwrap_or(false));
+        if output.dead_letter() && (output.source_first_seen() || output.summary()) {
+            bail!("A dead_letter output can not use source_first_seen or summary");
         }
-
-        Ok(data)
+        if let Some(sampling) = value.sampling {
+            let mut rates = BTreeMap::new();
+            for (level, rate) in sampling {
+                if rate > 100 {
+                    bail!(
+                        "Sampling rate of level {:?} must be a percentage between 0 and 100",
+                        level
+                    );
+                }
+                rates.insert(level.into(), rate);
+            }
+            output.set_sampling(rates);
+        }
+        if value.field_map.is_some() || value.drop_unmapped.is_some() {
+            if !output.format().supports_field_map() {
+                bail!(
+                    "Format {} does not support field_map",
+                    output.format().as_ref()
+                );
+            }
+            output.set_field_map(FieldMap::try_new(
+                value.field_map.unwrap_or_default(),
+                value.drop_unmapped.unwrap_or(false),
+            )?);
+        }
+        if let Some(degradation) = value.degradation {
+            output.set_degradation(Some(degradation.try_into()?));
+        }
+        if let Some(dedupe_on_change) = value.dedupe_on_change {
+            if output.source_first_seen() || output.summary() || output.dead_letter() {
+                bail!("Outputs using source_first_seen, summary or dead_letter can not use dedupe_on_change");
+            }
+            output.set_dedupe_on_change(Some(dedupe_on_change.try_into()?));
+        }
+        if let Some(payload_compression) = value.payload_compression {
+            output.set_payload_compression(payload_compression.into());
+            if output.payload_compression() != crate::subscription::PayloadCompression::None
+                && !output.driver().supports_payload_compression()
+            {
+                bail!(
+                    "payload_compression is only supported by Kafka outputs and Tcp outputs using length_prefixed framing"
+                );
+            }
+        }
+        if let Some(max_queue_age) = value.max_queue_age {
+            if max_queue_age == 0 {
+                bail!("max_queue_age must be greater than 0");
+            }
+            if output.source_first_seen() || output.summary() || output.dead_letter() {
+                bail!("Outputs using source_first_seen, summary or dead_letter can not use max_queue_age");
+            }
+            output.set_max_queue_age(Some(max_queue_age));
+        }
+        if value.include_collector_info == Some(true) {
+            if !output.format().supports_collector_info() {
+                bail!(
+                    "Format {} does not support include_collector_info",
+                    output.format().as_ref()
+                );
+            }
+            output.set_include_collector_info(true);
+        }
+        if let Some(write_timeout_ms) = value.write_timeout_ms {
+            if write_timeout_ms == 0 {
+                bail!("write_timeout_ms must be greater than 0");
+            }
+            output.set_write_timeout_ms(Some(write_timeout_ms));
+        }
+        if let Some(format_options) = value.format_options {
+            if !output.format().supports_format_options() {
+                bail!(
+                    "Format {} does not support format_options",
+                    output.format().as_ref()
+                );
+            }
+            let format_options: crate::subscription::FormatOptions = format_options.try_into()?;
+            if format_options.pretty() && output.driver().delimits_events_by_line() {
+                bail!("pretty can not be used with a driver delimiting events by line feeds");
+            }
+            output.set_format_options(format_options);
+        }
+        Ok(output)
     }
 }
 
-pub fn parse(
-    content: &str,
-    revision: Option<&String>,
-) -> Result<crate::subscription::SubscriptionData> {
-    let subscription: Subscription = toml::from_str(content).context("Error while parsing TOML")?;
-    let mut data: SubscriptionData = subscription.try_into()?;
-    data.set_revision(revision.cloned());
-    Ok(data)
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize)]
+enum EventLevel {
+    LogAlways,
+    Critical,
+    Error,
+    Warning,
+    Information,
+    Verbose,
 }
 
-#[cfg(test)]
+impl From<EventLevel> for crate::subscription::EventLevel {
+    fn from(value: EventLevel) -> Self {
+        match value {
+            EventLevel::LogAlways => crate::subscription::EventLevel::LogAlways,
+            EventLevel::Critical => crate::subscription::EventLevel::Critical,
+            EventLevel::Error => crate::subscription::EventLevel::Error,
+            EventLevel::Warning => crate::subscription::EventLevel::Warning,
+            EventLevel::Information => crate::subscription::EventLevel::Information,
+            EventLevel::Verbose => crate::subscription::EventLevel::Verbose,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct CsvConfiguration {
+    pub columns: Vec<String>,
+    pub delimiter: Option<char>,
+    pub header: Option<bool>,
+}
+
+impl TryFrom<CsvConfiguration> for crate::subscription::CsvConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: CsvConfiguration) -> Result<Self, Self::Error> {
+        if value.columns.is_empty() {
+            bail!("Csv format requires at least one column");
+        }
+        let delimiter = value.delimiter.unwrap_or(DEFAULT_CSV_DELIMITER);
+        if matches!(delimiter, '"' | '\r' | '\n') {
+            bail!("Invalid Csv delimiter {:?}", delimiter);
+        }
+        Ok(crate::subscription::CsvConfiguration::new(
+            value.columns,
+            delimiter,
+            value.header.unwrap_or(DEFAULT_CSV_HEADER),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Rfc5424Configuration {
+    pub enterprise_number: Option<u32>,
+    pub structured_data: Option<Vec<StructuredDataElement>>,
+}
+
+impl TryFrom<Rfc5424Configuration> for crate::subscription::Rfc5424Configuration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Rfc5424Configuration) -> Result<Self, Self::Error> {
+        let mut structured_data = Vec::new();
+        for element in value.structured_data.unwrap_or_default() {
+            structured_data.push(crate::subscription::StructuredDataElement::try_new(
+                element.id,
+                element.params,
+            )?);
+        }
+        crate::subscription::Rfc5424Configuration::try_new(
+            value
+                .enterprise_number
+                .unwrap_or(DEFAULT_RFC5424_ENTERPRISE_NUMBER),
+            structured_data,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct StructuredDataElement {
+    pub id: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct CefConfiguration {
+    pub version: Option<u8>,
+    pub device_vendor: Option<String>,
+    pub device_product: Option<String>,
+    pub device_version: Option<String>,
+}
+
+impl TryFrom<CefConfiguration> for crate::subscription::CefConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: CefConfiguration) -> Result<Self, Self::Error> {
+        crate::subscription::CefConfiguration::try_new(
+            value.version.unwrap_or(DEFAULT_CEF_VERSION),
+            value
+                .device_vendor
+                .unwrap_or_else(|| DEFAULT_CEF_DEVICE_VENDOR.to_string()),
+            value
+                .device_product
+                .unwrap_or_else(|| DEFAULT_CEF_DEVICE_PRODUCT.to_string()),
+            value.device_version,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct ProtobufConfiguration {
+    /// Path of a serialized `FileDescriptorSet`
+    pub descriptor_set: String,
+    pub message: String,
+}
+
+impl TryFrom<ProtobufConfiguration> for crate::subscription::ProtobufConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ProtobufConfiguration) -> Result<Self, Self::Error> {
+        // The descriptor set is loaded once, so that every openwec node
+        // can format events without having access to the file
+        let descriptor_set = fs::read(&value.descriptor_set).with_context(|| {
+            format!(
+                "Failed to read protobuf descriptor set {}",
+                value.descriptor_set
+            )
+        })?;
+        crate::subscription::ProtobufConfiguration::try_new(descriptor_set, value.message)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct TemplateConfiguration {
+    pub template: String,
+}
+
+impl TryFrom<TemplateConfiguration> for crate::subscription::TemplateConfiguration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: TemplateConfiguration) -> Result<Self, Self::Error> {
+        crate::subscription::TemplateConfiguration::try_new(value.template)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(tag = "format", content = "format_config")]
+enum SubscriptionOutputFormat {
+    Json,
+    Raw,
+    RawJson,
+    Nxlog,
+    Csv(CsvConfiguration),
+    Rfc5424(Option<Rfc5424Configuration>),
+    Cef(Option<CefConfiguration>),
+    Leef,
+    Protobuf(Option<ProtobufConfiguration>),
+    Ecs,
+    Msgpack,
+    Template(TemplateConfiguration),
+    Avro,
+}
+
+impl TryFrom<SubscriptionOutputFormat> for crate::subscription::SubscriptionOutputFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SubscriptionOutputFormat) -> Result<Self, Self::Error> {
+        Ok(match value {
+            SubscriptionOutputFormat::Json => crate::subscription::SubscriptionOutputFormat::Json,
+            SubscriptionOutputFormat::Raw => crate::subscription::SubscriptionOutputFormat::Raw,
+            SubscriptionOutputFormat::RawJson => {
+                crate::subscription::SubscriptionOutputFormat::RawJson
+            }
+            SubscriptionOutputFormat::Nxlog => crate::subscription::SubscriptionOutputFormat::Nxlog,
+            SubscriptionOutputFormat::Csv(config) => {
+                crate::subscription::SubscriptionOutputFormat::Csv(config.try_into()?)
+            }
+            SubscriptionOutputFormat::Rfc5424(config) => {
+                crate::subscription::SubscriptionOutputFormat::Rfc5424(match config {
+                    Some(config) => config.try_into()?,
+                    None => crate::subscription::Rfc5424Configuration::default(),
+                })
+            }
+            SubscriptionOutputFormat::Cef(config) => {
+                crate::subscription::SubscriptionOutputFormat::Cef(match config {
+                    Some(config) => config.try_into()?,
+                    None => crate::subscription::CefConfiguration::default(),
+                })
+            }
+            SubscriptionOutputFormat::Leef => crate::subscription::SubscriptionOutputFormat::Leef,
+            SubscriptionOutputFormat::Protobuf(config) => {
+                crate::subscription::SubscriptionOutputFormat::Protobuf(match config {
+                    Some(config) => Some(config.try_into()?),
+                    None => None,
+                })
+            }
+            SubscriptionOutputFormat::Ecs => crate::subscription::SubscriptionOutputFormat::Ecs,
+            SubscriptionOutputFormat::Msgpack => {
+                crate::subscription::SubscriptionOutputFormat::Msgpack
+            }
+            SubscriptionOutputFormat::Template(config) => {
+                crate::subscription::SubscriptionOutputFormat::Template(config.try_into()?)
+            }
+            SubscriptionOutputFormat::Avro => crate::subscription::SubscriptionOutputFormat::Avro,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+enum ClientFilterOperation {
+    Only,
+    Except,
+}
+
+impl From<ClientFilterOperation> for crate::subscription::ClientFilterOperation {
+    fn from(value: ClientFilterOperation) -> Self {
+        match value {
+            ClientFilterOperation::Except => crate::subscription::ClientFilterOperation::Except,
+            ClientFilterOperation::Only => crate::subscription::ClientFilterOperation::Only,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Display, AsRefStr, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum ClientFilterType {
+    #[default]
+    KerberosPrinc,
+    TLSCertSubject,
+    MachineID,
+    IpRange,
+    TLSCertSan,
+}
+
+impl From<ClientFilterType> for crate::subscription::ClientFilterType {
+    fn from(value: ClientFilterType) -> Self {
+        match value {
+            ClientFilterType::KerberosPrinc => crate::subscription::ClientFilterType::KerberosPrinc,
+            ClientFilterType::TLSCertSubject => crate::subscription::ClientFilterType::TLSCertSubject,
+            ClientFilterType::MachineID => crate::subscription::ClientFilterType::MachineID,
+            ClientFilterType::IpRange => crate::subscription::ClientFilterType::IpRange,
+            ClientFilterType::TLSCertSan => crate::subscription::ClientFilterType::TLSCertSan,
+        }
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+    pub struct ClientFilterFlags: u32 {
+        const CaseInsensitive = 1 << 0;
+        const GlobPattern = 1 << 1;
+        const Regex = 1 << 2;
+        // Every flag (rejected by filters, which can not use GlobPattern and
+        // Regex together). Declared last, so that it is never used by Display.
+        const All = Self::CaseInsensitive.bits() | Self::GlobPattern.bits() | Self::Regex.bits();
+    }
+}
+
+impl From<ClientFilterFlags> for crate::subscription::ClientFilterFlags {
+    fn from(value: ClientFilterFlags) -> Self {
+        crate::subscription::ClientFilterFlags::from_bits(value.bits()).unwrap()
+    }
+}
+
+impl Display for ClientFilterFlags {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        bitflags::parser::to_writer_strict(self, f)
+    }
+}
+
+impl Default for ClientFilterFlags {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientFilter {
+    pub operation: ClientFilterOperation,
+    #[serde(rename = "type", default)]
+    pub kind: ClientFilterType,
+    #[serde(default)]
+    pub flags: ClientFilterFlags,
+    #[serde(alias = "cert_subjects", alias = "princs")]
+    pub targets: HashSet<String>,
+}
+
+impl TryFrom<ClientFilter> for crate::subscription::ClientFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ClientFilter) -> std::prelude::v1::Result<Self, Self::Error> {
+        crate::subscription::ClientFilter::try_new(value.operation.into(), value.kind.into(), value.flags.into(), value.targets)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+enum ContentFormat {
+    Raw,
+    RenderedText,
+}
+
+impl From<ContentFormat> for crate::subscription::ContentFormat {
+    fn from(value: ContentFormat) -> Self {
+        match value {
+            ContentFormat::Raw => crate::subscription::ContentFormat::Raw,
+            ContentFormat::RenderedText => crate::subscription::ContentFormat::RenderedText,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RequireAuth {
+    Any,
+    Kerberos,
+    TlsCert,
+}
+
+impl From<RequireAuth> for crate::subscription::RequireAuth {
+    fn from(value: RequireAuth) -> Self {
+        match value {
+            RequireAuth::Any => crate::subscription::RequireAuth::Any,
+            RequireAuth::Kerberos => crate::subscription::RequireAuth::Kerberos,
+            RequireAuth::TlsCert => crate::subscription::RequireAuth::TlsCert,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EventsCompression {
+    Sldc,
+    None,
+}
+
+impl From<EventsCompression> for crate::subscription::EventsCompression {
+    fn from(value: EventsCompression) -> Self {
+        match value {
+            EventsCompression::Sldc => crate::subscription::EventsCompression::Sldc,
+            EventsCompression::None => crate::subscription::EventsCompression::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputMode {
+    Parallel,
+    Sequential,
+}
+
+impl From<OutputMode> for crate::subscription::OutputMode {
+    fn from(value: OutputMode) -> Self {
+        match value {
+            OutputMode::Parallel => crate::subscription::OutputMode::Parallel,
+            OutputMode::Sequential => crate::subscription::OutputMode::Sequential,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RenderingElement {
+    Message,
+    Level,
+    Task,
+    Opcode,
+    Channel,
+    Provider,
+    Keywords,
+}
+
+impl From<RenderingElement> for crate::subscription::RenderingElement {
+    fn from(value: RenderingElement) -> Self {
+        match value {
+            RenderingElement::Message => crate::subscription::RenderingElement::Message,
+            RenderingElement::Level => crate::subscription::RenderingElement::Level,
+            RenderingElement::Task => crate::subscription::RenderingElement::Task,
+            RenderingElement::Opcode => crate::subscription::RenderingElement::Opcode,
+            RenderingElement::Channel => crate::subscription::RenderingElement::Channel,
+            RenderingElement::Provider => crate::subscription::RenderingElement::Provider,
+            RenderingElement::Keywords => crate::subscription::RenderingElement::Keywords,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ManifestOption {
+    ContentFormat,
+    IgnoreChannelError,
+    ReadExistingEvents,
+    Cdata,
+    Locale,
+    DataLocale,
+    MaxElements,
+}
+
+impl From<ManifestOption> for crate::subscription::ManifestOption {
+    fn from(value: ManifestOption) -> Self {
+        match value {
+            ManifestOption::ContentFormat => crate::subscription::ManifestOption::ContentFormat,
+            ManifestOption::IgnoreChannelError => {
+                crate::subscription::ManifestOption::IgnoreChannelError
+            }
+            ManifestOption::ReadExistingEvents => {
+                crate::subscription::ManifestOption::ReadExistingEvents
+            }
+            ManifestOption::Cdata => crate::subscription::ManifestOption::Cdata,
+            ManifestOption::Locale => crate::subscription::ManifestOption::Locale,
+            ManifestOption::DataLocale => crate::subscription::ManifestOption::DataLocale,
+            ManifestOption::MaxElements => crate::subscription::ManifestOption::MaxElements,
+        }
+    }
+}
+
+/// A date, written either as a string or as a TOML date-time
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum DateTimeValue {
+    String(String),
+    Toml(toml::value::Datetime),
+}
+
+impl Display for DateTimeValue {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            DateTimeValue::String(value) => write!(f, "{}", value),
+            DateTimeValue::Toml(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubscriptionOptions {
+    pub uri: Option<String>,
+    pub heartbeat_interval: Option<u32>,
+    pub connection_retry_count: Option<u16>,
+    pub connection_retry_interval: Option<u32>,
+    pub connection_retry_jitter: Option<u8>,
+    pub max_time: Option<u32>,
+    pub max_elements: Option<u32>,
+    pub max_envelope_size: Option<u32>,
+    pub enabled: Option<bool>,
+    pub read_existing_events: Option<bool>,
+    pub read_existing_events_since: Option<DateTimeValue>,
+    pub content_format: Option<ContentFormat>,
+    pub ignore_channel_error: Option<bool>,
+    pub ignore_channel_error_for: Option<Vec<String>>,
+    pub locale: Option<String>,
+    pub data_locale: Option<String>,
+    pub require_auth: Option<RequireAuth>,
+    pub compression: Option<EventsCompression>,
+    pub output_mode: Option<OutputMode>,
+    pub max_concurrent_retries: Option<u32>,
+    pub max_queued_events: Option<u32>,
+    pub max_queued_bytes: Option<u64>,
+    pub max_concurrent_batches: Option<u32>,
+    pub max_concurrent_clients: Option<u32>,
+    pub dedup_window: Option<u32>,
+    pub dedup_window_ttl: Option<u32>,
+    pub event_ttl: Option<u32>,
+    pub trim_rendering: Option<Vec<RenderingElement>>,
+    pub enrollment_fallback: Option<Vec<ManifestOption>>,
+    // Only used when the config is parsed
+    pub validate_query: Option<bool>,
+}
+
+impl SubscriptionOptions {
+    pub fn feed_subscription_data(&self, data: &mut SubscriptionData) -> Result<()> {
+        data.set_uri(self.uri.clone());
+
+        if let Some(heartbeat_interval) = self.heartbeat_interval {
+            data.set_heartbeat_interval(heartbeat_interval);
+        }
+
+        if let Some(connection_retry_count) = self.connection_retry_count {
+            data.set_connection_retry_count(connection_retry_count);
+        }
+
+        if let Some(connection_retry_interval) = self.connection_retry_interval {
+            data.set_connection_retry_interval(connection_retry_interval);
+        }
+
+        if let Some(connection_retry_jitter) = self.connection_retry_jitter {
+            if connection_retry_jitter == 0 || connection_retry_jitter > 100 {
+                bail!("connection_retry_jitter must be a percentage between 1 and 100");
+            }
+        }
+        data.set_connection_retry_jitter(self.connection_retry_jitter);
+
+        if let Some(max_time) = self.max_time {
+            data.set_max_time(max_time);
+        }
+
+        data.set_max_elements(self.max_elements);
+
+        if let Some(max_envelope_size) = self.max_envelope_size {
+            crate::subscription::check_max_envelope_size(max_envelope_size)?;
+            data.set_max_envelope_size(max_envelope_size);
+        }
+
+        if let Some(enabled) = self.enabled {
+            data.set_enabled(enabled);
+        }
+
+        if let Some(read_existing_events) = self.read_existing_events {
+            data.set_read_existing_events(read_existing_events);
+        }
+
+        if let Some(since) = &self.read_existing_events_since {
+            if !data.read_existing_events() {
+                bail!("read_existing_events_since requires read_existing_events to be true");
+            }
+            data.set_read_existing_events_since(Some(
+                parse_rfc3339_utc(&since.to_string())
+                    .context("Invalid read_existing_events_since")?,
+            ));
+        }
+
+        if let Some(content_format) = self.content_format.clone() {
+            data.set_content_format(content_format.into());
+        }
+
+        if let Some(ignore_channel_error) = self.ignore_channel_error {
+            data.set_ignore_channel_error(ignore_channel_error);
+        }
+
+        if self.ignore_channel_error.is_some() && self.ignore_channel_error_for.is_some() {
+            warn!(
+                "Subscription {}: ignore_channel_error is ignored because ignore_channel_error_for is set",
+                data.name()
+            );
+        }
+        data.set_ignore_channel_error_for(
+            self.ignore_channel_error_for
+                .as_ref()
+                .map(|channels| channels.iter().cloned().collect()),
+        );
+
+        if let Some(locale) = &self.locale {
+            crate::subscription::check_locale(locale)?;
+        }
+        data.set_locale(self.locale.clone());
+
+        if let Some(data_locale) = &self.data_locale {
+            crate::subscription::check_locale(data_locale)?;
+        }
+        data.set_data_locale(self.data_locale.clone());
+
+        if let Some(require_auth) = self.require_auth.clone() {
+            data.set_require_auth(require_auth.into());
+        }
+
+        if let Some(compression) = self.compression {
+            data.set_compression(compression.into());
+        }
+
+        if let Some(output_mode) = self.output_mode {
+            data.set_output_mode(output_mode.into());
+        }
+
+        if self.max_concurrent_retries == Some(0) {
+            bail!("max_concurrent_retries must be greater than 0");
+        }
+        data.set_max_concurrent_retries(self.max_concurrent_retries);
+
+        if self.max_queued_events == Some(0) {
+            bail!("max_queued_events must be greater than 0");
+        }
+        data.set_max_queued_events(self.max_queued_events);
+
+        if self.max_queued_bytes == Some(0) {
+            bail!("max_queued_bytes must be greater than 0");
+        }
+        data.set_max_queued_bytes(self.max_queued_bytes);
+
+        if self.max_concurrent_batches == Some(0) {
+            bail!("max_concurrent_batches must be greater than 0");
+        }
+        data.set_max_concurrent_batches(self.max_concurrent_batches);
+
+        if self.max_concurrent_clients == Some(0) {
+            bail!("max_concurrent_clients must be greater than 0");
+        }
+        data.set_max_concurrent_clients(self.max_concurrent_clients);
+
+        if self.dedup_window == Some(0) {
+            bail!("dedup_window must be greater than 0");
+        }
+        if self.dedup_window_ttl == Some(0) {
+            bail!("dedup_window_ttl must be greater than 0");
+        }
+        if self.dedup_window.is_none() && self.dedup_window_ttl.is_some() {
+            bail!("dedup_window_ttl requires dedup_window to be set");
+        }
+        data.set_dedup_window(self.dedup_window);
+        data.set_dedup_window_ttl(self.dedup_window_ttl);
+
+        if self.event_ttl == Some(0) {
+            bail!("event_ttl must be greater than 0");
+        }
+        data.set_event_ttl(self.event_ttl);
+
+        data.set_trim_rendering(
+            self.trim_rendering
+                .iter()
+                .flatten()
+                .map(|element| (*element).into())
+                .collect(),
+        );
+
+        data.set_enrollment_fallback(
+            self.enrollment_fallback
+                .iter()
+                .flatten()
+                .map(|option| (*option).into())
+                .collect(),
+        );
+        Ok(())
+    }
+}
+#[derive(Debug, PartialEq, Clone, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Subscription {
+    pub uuid: Uuid,
+    #[serde(default)]
+    pub version: Uuid,
+    pub name: String,
+    pub tenant: Option<String>,
+    pub query: String,
+    pub filter: Option<ClientFilter>,
+    pub outputs: Vec<SubscriptionOutput>,
+    pub route_by_provider: Option<BTreeMap<String, StringOrVecString>>,
+    pub options: Option<SubscriptionOptions>,
+}
+
+/// Resolves `route_by_provider` into the provider route of each output.
+/// Outputs that are not referenced by any route receive the events of
+/// unmatched providers.
+fn route_by_provider(
+    config: &[SubscriptionOutput],
+    outputs: &mut [crate::subscription::SubscriptionOutput],
+    routes: &BTreeMap<String, StringOrVecString>,
+) -> Result<()> {
+    let mut names = HashMap::new();
+    for (index, output) in config.iter().enumerate() {
+        if let Some(name) = &output.name {
+            if names.insert(name.as_str(), index).is_some() {
+                bail!("Output name {:?} is used by multiple outputs", name);
+            }
+        }
+    }
+
+    let mut patterns: Vec<Vec<String>> = vec![Vec::new(); outputs.len()];
+    for (pattern, targets) in routes.iter() {
+        Pattern::new(pattern).with_context(|| format!("Invalid provider pattern {:?}", pattern))?;
+        let targets = match targets {
+            StringOrVecString::String(s) => Vec::from([s.clone()]),
+            StringOrVecString::Vec(v) => v.clone(),
+        };
+        if targets.is_empty() {
+            bail!("Provider pattern {:?} is not routed to any output", pattern);
+        }
+        for target in targets {
+            let index = names.get(target.as_str()).ok_or_else(|| {
+                anyhow!(
+                    "Provider pattern {:?} is routed to an unknown output {:?}",
+                    pattern,
+                    target
+                )
+            })?;
+            patterns[*index].push(pattern.clone());
+        }
+    }
+
+    let all_patterns: Vec<String> = routes.keys().cloned().collect();
+    for (output, patterns) in outputs.iter_mut().zip(patterns) {
+        // "Source first seen", "summary" and "dead letter" outputs do not
+        // receive the events themselves
+        if output.source_first_seen() || output.summary() || output.dead_letter() {
+            if !patterns.is_empty() {
+                bail!("Outputs using source_first_seen, summary or dead_letter can not be routed by provider");
+            }
+            continue;
+        }
+        if patterns.is_empty() {
+            output.set_provider_route(ProviderRoute::Unmatched(all_patterns.clone()));
+        } else {
+            output.set_provider_route(ProviderRoute::Matching(patterns));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `query` is a `QueryList` containing at least one `Query`,
+/// each of them selecting events of at least one channel
+fn validate_query(query: &str) -> Result<()> {
+    let doc = roxmltree::Document::parse(query).context("Query is not well-formed XML")?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "QueryList" {
+        bail!(
+            "Query root element must be <QueryList>, found <{}>",
+            root.tag_name().name()
+        );
+    }
+    let mut queries = 0;
+    for node in root.children().filter(|node| node.is_element()) {
+        if node.tag_name().name() != "Query" {
+            bail!(
+                "Unexpected element <{}> in <QueryList>, expected <Query>",
+                node.tag_name().name()
+            );
+        }
+        let mut selects = 0;
+        for child in node.children().filter(|child| child.is_element()) {
+            match child.tag_name().name() {
+                "Select" if child.attribute("Path").is_some() => selects += 1,
+                "Select" => bail!("<Select> element without a Path attribute in query"),
+                "Suppress" => (),
+                other => bail!(
+                    "Unexpected element <{}> in <Query>, expected <Select> or <Suppress>",
+                    other
+                ),
+            }
+        }
+        if selects == 0 {
+            bail!("<Query> element without any <Select Path=...> element in query");
+        }
+        queries += 1;
+    }
+    if queries == 0 {
+        bail!("<QueryList> does not contain any <Query> element");
+    }
+    Ok(())
+}
+
+impl TryFrom<Subscription> for crate::subscription::SubscriptionData {
+    type Error = anyhow::Error;
+
+    fn try_from(subscription: Subscription) -> std::prelude::v1::Result<Self, Self::Error> {
+        let mut data =
+            crate::subscription::SubscriptionData::new(&subscription.name, &subscription.query);
+        data.set_uuid(crate::subscription::SubscriptionUuid(subscription.uuid));
+        data.set_name(subscription.name.clone());
+        if subscription
+            .options
+            .as_ref()
+            .and_then(|options| options.validate_query)
+            .unwrap_or(false)
+        {
+            validate_query(&subscription.query).context("Invalid subscription query")?;
+        }
+        data.set_query(subscription.query.clone());
+        if let Some(tenant) = subscription.tenant {
+            crate::subscription::check_tenant(&tenant)?;
+            data.set_tenant(Some(tenant));
+        }
+        if let Some(filter) = subscription.filter {
+            data.set_client_filter(Some(filter.try_into()?));
+        }
+
+        if subscription.outputs.is_empty() {
+            bail!("Missing subscription outputs");
+        }
+
+        let mut outputs = Vec::with_capacity(subscription.outputs.len());
+        for output in subscription.outputs.iter() {
+            outputs.push(output.clone().try_into().with_context(|| {
+                format!(
+                    "Loading subscription {} output {:?}",
+                    subscription.name, output
+                )
+            })?);
+        }
+
+        if let Some(routes) = &subscription.route_by_provider {
+            route_by_provider(&subscription.outputs, &mut outputs, routes).with_context(|| {
+                format!(
+                    "Loading subscription {} route_by_provider",
+                    subscription.name
+                )
+            })?;
+        }
+
+        for output in outputs {
+            data.add_output(output);
+        }
+
+        if let Some(options) = subscription.options {
+            options.feed_subscription_data(&mut data)?;
+        }
+
+        if data.read_existing_events_since().is_some() {
+            data.client_query()
+                .context("read_existing_events_since can not be applied to the query")?;
+        }
+
+        Ok(data)
+    }
+}
+
+/// Replaces the references to environment variables of `value`: `${VAR}`
+/// is replaced by the value of `VAR`, which must be set, and `${VAR:-default}`
+/// by `default` if `VAR` is unset or empty. `$${` is kept as a literal `${`.
+fn interpolate_env(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(escaped) = after.strip_prefix("${") {
+            result.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix('{') {
+            let end = reference.find('}').ok_or_else(|| {
+                anyhow!("Unterminated environment variable reference in {:?}", value)
+            })?;
+            let (name, default) = match reference[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&reference[..end], None),
+            };
+            if name.is_empty()
+                || name.starts_with(|c: char| c.is_ascii_digit())
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                bail!(
+                    "Invalid environment variable name {:?} in {:?}",
+                    name,
+                    value
+                );
+            }
+            match (std::env::var(name), default) {
+                (Ok(var), Some(default)) if var.is_empty() => result.push_str(default),
+                (Ok(var), _) => result.push_str(&var),
+                (Err(std::env::VarError::NotPresent), Some(default)) => result.push_str(default),
+                (Err(std::env::VarError::NotPresent), None) => bail!(
+                    "Environment variable {} is not set (use ${{{}:-default}} to provide a default value)",
+                    name,
+                    name
+                ),
+                (Err(err), _) => bail!("Failed to read environment variable {}: {}", name, err),
+            }
+            rest = &reference[end + 1..];
+        } else {
+            result.push('$');
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Interpolates environment variables in the strings (keys excepted) of
+/// `value`, and returns whether any string has been modified
+fn interpolate_env_in_value(value: &mut toml::Value) -> Result<bool> {
+    Ok(match value {
+        toml::Value::String(string) if string.contains('$') => {
+            let interpolated = interpolate_env(string)?;
+            let changed = interpolated != *string;
+            *string = interpolated;
+            changed
+        }
+        toml::Value::Array(array) => {
+            let mut changed = false;
+            for item in array.iter_mut() {
+                changed |= interpolate_env_in_value(item)?;
+            }
+            changed
+        }
+        toml::Value::Table(table) => {
+            let mut changed = false;
+            for (_, item) in table.iter_mut() {
+                changed |= interpolate_env_in_value(item)?;
+            }
+            changed
+        }
+        _ => false,
+    })
+}
+
+/// Interpolates environment variables in the outputs of a subscription,
+/// and returns whether any of them has been modified
+fn interpolate_env_in_outputs(table: &mut toml::Table) -> Result<bool> {
+    match table.get_mut("outputs") {
+        Some(outputs) => interpolate_env_in_value(outputs)
+            .context("Failed to interpolate environment variables in outputs"),
+        None => Ok(false),
+    }
+}
+
+/// Deserializes a subscription, after having interpolated environment
+/// variables in its outputs. Other fields, such as the query, are left
+/// untouched so that they may contain literal `$`.
+fn deserialize_subscription(content: &str) -> Result<Subscription> {
+    let mut table: toml::Table = toml::from_str(content).context("Error while parsing TOML")?;
+    if interpolate_env_in_outputs(&mut table)? {
+        return toml::Value::Table(table)
+            .try_into()
+            .context("Error while parsing TOML");
+    }
+    // Parse the content itself to keep the location of errors
+    toml::from_str(content).context("Error while parsing TOML")
+}
+
+/// Format of a config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum ConfigFormat {
+    /// A single subscription
+    #[default]
+    Toml,
+    /// An array of subscriptions
+    Json,
+    /// An array of subscriptions
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format of a config file from its extension. Files which
+    /// are neither `.json`, `.yaml` nor `.yml` are TOML files.
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// Deserializes the subscriptions of a JSON or YAML array, which follow the
+/// schema of TOML config files. Each subscription goes through TOML values
+/// so that environment variables are interpolated in its outputs as well.
+fn deserialize_subscriptions(content: &str, format: ConfigFormat) -> Result<Vec<Subscription>> {
+    let tables: Vec<toml::Table> = match format {
+        ConfigFormat::Toml => return Ok(vec![deserialize_subscription(content)?]),
+        ConfigFormat::Json => serde_json::from_str(content).context("Error while parsing JSON")?,
+        ConfigFormat::Yaml => serde_yaml::from_str(content).context("Error while parsing YAML")?,
+    };
+    tables
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut table)| {
+            interpolate_env_in_outputs(&mut table)
+                .and_then(|_| {
+                    toml::Value::Table(table)
+                        .try_into()
+                        .context("Error while parsing subscription")
+                })
+                .with_context(|| format!("Subscription {}", index))
+        })
+        .collect()
+}
+
+pub fn parse(
+    content: &str,
+    revision: Option<&String>,
+) -> Result<crate::subscription::SubscriptionData> {
+    parse_with_compat(content, revision, true)
+}
+
+/// Parses a config. Outputs whose format and driver are not compatible
+/// are rejected if `strict_compat` is set, and only logged otherwise.
+pub fn parse_with_compat(
+    content: &str,
+    revision: Option<&String>,
+    strict_compat: bool,
+) -> Result<crate::subscription::SubscriptionData> {
+    check_subscription(deserialize_subscription(content)?, revision, strict_compat)
+}
+
+/// Parses the subscriptions of a config written in `format`: a TOML config
+/// contains a single subscription, JSON and YAML ones an array of
+/// subscriptions. Subscriptions of the same config must have distinct uuids.
+pub fn parse_all(
+    content: &str,
+    format: ConfigFormat,
+    revision: Option<&String>,
+    strict_compat: bool,
+) -> Result<Vec<SubscriptionData>> {
+    let mut subscriptions: Vec<SubscriptionData> = Vec::new();
+    for (index, subscription) in deserialize_subscriptions(content, format)?
+        .into_iter()
+        .enumerate()
+    {
+        let data = check_subscription(subscription, revision, strict_compat)
+            .with_context(|| format!("Subscription {}", index))?;
+        if let Some(other) = subscriptions
+            .iter()
+            .position(|other| other.uuid() == data.uuid())
+        {
+            bail!(
+                "Subscriptions {} and {} have the same uuid {}",
+                other,
+                index,
+                data.uuid_string()
+            );
+        }
+        subscriptions.push(data);
+    }
+    Ok(subscriptions)
+}
+
+/// Converts a deserialized subscription and checks its outputs
+fn check_subscription(
+    subscription: Subscription,
+    revision: Option<&String>,
+    strict_compat: bool,
+) -> Result<SubscriptionData> {
+    let mut data: SubscriptionData = subscription.try_into()?;
+    data.set_revision(revision.cloned());
+    for issue in compatibility_issues(&data) {
+        if strict_compat {
+            bail!("{} (use --no-strict-compat to ignore)", issue);
+        }
+        warn!("Subscription {}: {}", data.name(), issue);
+    }
+    for issue in event_ttl_issues(&data) {
+        warn!("Subscription {}: {}", data.name(), issue);
+    }
+    for issue in template_issues(&data) {
+        warn!("Subscription {}: {}", data.name(), issue);
+    }
+    for issue in ignore_channel_error_issues(&data) {
+        warn!("Subscription {}: {}", data.name(), issue);
+    }
+    Ok(data)
+}
+
+/// Returns the outputs of `data` whose driver can not make events expire,
+/// on which `event_ttl` has no effect
+pub fn event_ttl_issues(data: &SubscriptionData) -> Vec<String> {
+    if data.event_ttl().is_none() {
+        return Vec::new();
+    }
+    data.outputs()
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| !output.driver().supports_event_ttl())
+        .map(|(index, output)| {
+            format!(
+                "Output {}: event_ttl has no effect with driver {}",
+                index,
+                output.driver().as_ref()
+            )
+        })
+        .collect()
+}
+
+/// Returns the tokens of `Template` formats which do not refer to a known
+/// field, and which are therefore rendered as empty strings
+pub fn template_issues(data: &SubscriptionData) -> Vec<String> {
+    let mut issues = Vec::new();
+    for (index, output) in data.outputs().iter().enumerate() {
+        if let crate::subscription::SubscriptionOutputFormat::Template(config) = output.format() {
+            for token in config.unknown_tokens() {
+                issues.push(format!(
+                    "Output {}: unknown token {{{}}} in template will be empty",
+                    index, token
+                ));
+            }
+        }
+    }
+    issues
+}
+
+/// Returns the reasons why `ignore_channel_error_for` does not behave as
+/// configured. Clients ignore channel errors for the whole query, so errors
+/// of listed channels are only ignored if every channel is listed.
+pub fn ignore_channel_error_issues(data: &SubscriptionData) -> Vec<String> {
+    let Some(tolerated) = data.ignore_channel_error_for() else {
+        return Vec::new();
+    };
+    let untolerated = match data.untolerated_channels() {
+        Ok(Some(untolerated)) => untolerated,
+        Ok(None) => return Vec::new(),
+        Err(err) => {
+            return vec![format!(
+                "ignore_channel_error_for has no effect because the channels of the query can not be read: {:#}",
+                err
+            )]
+        }
+    };
+    let channels = crate::subscription::query_channels(data.query()).unwrap_or_default();
+
+    let mut issues = Vec::new();
+    for channel in tolerated {
+        if !channels
+            .iter()
+            .any(|read| read.eq_ignore_ascii_case(channel))
+        {
+            issues.push(format!(
+                "ignore_channel_error_for: channel {} is not read by the query",
+                channel
+            ));
+        }
+    }
+    if !untolerated.is_empty() && untolerated.len() < channels.len() {
+        issues.push(format!(
+            "ignore_channel_error_for: channel errors are not ignored because the query also reads {}. Move the tolerated channels to another subscription.",
+            untolerated.join(", ")
+        ));
+    }
+    issues
+}
+
+/// Returns the outputs of `data` whose format can not be delivered
+/// correctly by their driver
+pub fn compatibility_issues(data: &SubscriptionData) -> Vec<String> {
+    data.outputs()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, output)| {
+            output
+                .format()
+                .incompatibility(output.driver())
+                .map(|reason| format!("Output {}: {}", index, reason))
+        })
+        .collect()
+}
+
+/// Returns the config files found in `path`, which may be a file or a
+/// directory explored recursively
+pub fn config_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.exists() {
+        bail!("{} does not exist", path.display());
+    }
+    let mut config_files = Vec::new();
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_dir() {
+                config_files.append(&mut config_files(&path)?);
+            } else {
+                config_files.push(entry.path())
+            }
+        }
+    } else if path.is_file() {
+        config_files.push(path.to_path_buf())
+    }
+    Ok(config_files)
+}
+
+/// Reads the content of a config file
+fn read_file(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    String::from_utf8(content).with_context(|| {
+        format!(
+            "Failed to decode the content of {} using UTF-8",
+            path.display()
+        )
+    })
+}
+
+/// Reads and parses a config file
+pub fn load_file(
+    path: &Path,
+    revision: Option<&String>,
+    strict_compat: bool,
+) -> Result<SubscriptionData> {
+    let content_str = read_file(path)?;
+    parse_with_compat(&content_str, revision, strict_compat)
+        .with_context(|| format!("Failed to parse file {}", path.display()))
+}
+
+/// Result of the validation of a config file
+#[derive(Debug, Default)]
+pub struct FileReport {
+    path: PathBuf,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl FileReport {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+/// Validates the subscription config files found in `path` without using
+/// the database. Files are parsed like `load_file_all` does, and
+/// subscriptions sharing a uuid or a name with another one are errors.
+/// `check_subscription` may reject each parsed subscription with an error.
+pub fn check_config_files<F>(
+    path: &Path,
+    format: Option<ConfigFormat>,
+    strict_compat: bool,
+    mut check_subscription: F,
+) -> Result<Vec<FileReport>>
+where
+    F: FnMut(SubscriptionData) -> Result<()>,
+{
+    let mut reports = Vec::new();
+    let mut uuids: HashMap<String, PathBuf> = HashMap::new();
+    let mut names: HashMap<String, PathBuf> = HashMap::new();
+
+    let mut files = config_files(path).context("Failed to list config files")?;
+    files.sort();
+    for file in files {
+        let mut report = FileReport::new(&file);
+        let subscriptions = match load_file_all(&file, format, None, strict_compat) {
+            Ok(subscriptions) => subscriptions,
+            Err(err) => {
+                report.errors.push(format!("{:#}", err));
+                reports.push(report);
+                continue;
+            }
+        };
+
+        // Messages are prefixed by the subscription name when the file
+        // contains several subscriptions
+        let several = subscriptions.len() > 1;
+        for data in subscriptions {
+            let prefix = if several {
+                format!("{}: ", data.name())
+            } else {
+                String::new()
+            };
+            let mut errors = Vec::new();
+            let mut warnings = Vec::new();
+            if let Some(other) = uuids.insert(data.uuid_string(), file.clone()) {
+                errors.push(format!(
+                    "Subscription uuid {} is also used in {}",
+                    data.uuid_string(),
+                    other.display()
+                ));
+            }
+            if let Some(other) = names.insert(data.name().to_string(), file.clone()) {
+                errors.push(format!(
+                    "Subscription name {:?} is also used in {}",
+                    data.name(),
+                    other.display()
+                ));
+            }
+            if !data.enabled() {
+                warnings.push("Subscription is disabled and will not be served".to_string());
+            }
+            // Incompatible outputs fail to load if compatibility is strict
+            warnings.extend(compatibility_issues(&data));
+            warnings.extend(template_issues(&data));
+            warnings.extend(ignore_channel_error_issues(&data));
+            if !data.outputs().iter().any(|output| output.enabled()) {
+                warnings.push(
+                    "Subscription has no enabled output: received events will be dropped"
+                        .to_string(),
+                );
+            }
+
+            if let Err(err) = check_subscription(data) {
+                errors.push(format!("{:#}", err));
+            }
+            report.errors.extend(
+                errors
+                    .into_iter()
+                    .map(|error| format!("{}{}", prefix, error)),
+            );
+            report.warnings.extend(
+                warnings
+                    .into_iter()
+                    .map(|warning| format!("{}{}", prefix, warning)),
+            );
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Reads and parses the subscriptions of a config file, whose format is
+/// detected from its extension unless `format` is set
+pub fn load_file_all(
+    path: &Path,
+    format: Option<ConfigFormat>,
+    revision: Option<&String>,
+    strict_compat: bool,
+) -> Result<Vec<SubscriptionData>> {
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+    let content_str = read_file(path)?;
+    parse_all(&content_str, format, revision, strict_compat)
+        .with_context(|| format!("Failed to parse file {}", path.display()))
+}
+
+/// Two config files declare subscriptions with the same uuid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateUuidError {
+    pub uuid: SubscriptionUuid,
+    pub first: PathBuf,
+    pub second: PathBuf,
+}
+
+impl Display for DuplicateUuidError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Subscription uuid {} is declared in both {} and {}",
+            self.uuid.to_string().to_uppercase(),
+            self.first.display(),
+            self.second.display()
+        )
+    }
+}
+
+impl std::error::Error for DuplicateUuidError {}
+
+/// Checks that the subscriptions parsed from several config files do not
+/// share a uuid, in which case the last one would silently replace the
+/// others. `loaded` contains each file with the subscription parsed from it.
+pub fn check_unique_uuids(loaded: &[(PathBuf, SubscriptionData)]) -> Result<()> {
+    let mut files: HashMap<SubscriptionUuid, &PathBuf> = HashMap::new();
+    for (path, data) in loaded {
+        if let Some(first) = files.insert(*data.uuid(), path) {
+            return Err(DuplicateUuidError {
+                uuid: *data.uuid(),
+                first: first.clone(),
+                second: path.clone(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Changes to apply to the stored subscriptions so that they match a set of
+/// loaded subscriptions. Subscriptions are matched on their uuid.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SubscriptionsDiff {
+    /// Loaded subscriptions which are not stored yet
+    pub created: Vec<SubscriptionUuid>,
+    /// Loaded subscriptions whose content differs from the stored one
+    pub updated: Vec<SubscriptionUuid>,
+    /// Loaded subscriptions which are identical to the stored ones
+    pub unchanged: Vec<SubscriptionUuid>,
+    /// Stored subscriptions which are not loaded anymore
+    pub deleted: Vec<SubscriptionUuid>,
+}
+
+/// Compares two subscriptions, whatever their internal version
+fn same_content(stored: &SubscriptionData, loaded: &SubscriptionData) -> bool {
+    let mut loaded = loaded.clone();
+    loaded.set_internal_version(stored.internal_version());
+    stored == &loaded
+}
+
+/// Computes the changes between the stored subscriptions and the loaded
+/// ones.
+///
+/// Parsing a config file always gives a new internal version, so the content
+/// of subscriptions is compared instead. Unchanged subscriptions must not be
+/// stored again: their internal version would change and openwecd would
+/// reload them.
+pub fn diff_subscriptions(
+    stored: &[SubscriptionData],
+    loaded: &[SubscriptionData],
+) -> SubscriptionsDiff {
+    let stored_by_uuid: HashMap<&SubscriptionUuid, &SubscriptionData> = stored
+        .iter()
+        .map(|subscription| (subscription.uuid(), subscription))
+        .collect();
+
+    let mut diff = SubscriptionsDiff::default();
+    for subscription in loaded {
+        let uuid = *subscription.uuid();
+        match stored_by_uuid.get(&uuid) {
+            None => diff.created.push(uuid),
+            Some(previous) if same_content(previous, subscription) => diff.unchanged.push(uuid),
+            Some(_) => diff.updated.push(uuid),
+        }
+    }
+
+    let loaded_uuids: HashSet<&SubscriptionUuid> = loaded
+        .iter()
+        .map(|subscription| subscription.uuid())
+        .collect();
+    diff.deleted = stored
+        .iter()
+        .filter(|subscription| !loaded_uuids.contains(subscription.uuid()))
+        .map(|subscription| *subscription.uuid())
+        .collect();
+    diff
+}
+
+#[cfg(test)]
 pub mod tests {
-    use std::str::FromStr;
+    use std::{collections::BTreeSet, str::FromStr};
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::subscription::InternalVersion;
+
+    use super::*;
+
+    const FULL_CONTENT: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3275778"
+name = "my-subscription"
+
+query = """
+a wonderful query
+"""
+
+[options]
+enabled = true
+uri = "toto"
+heartbeat_interval = 32
+connection_retry_count = 11
+connection_retry_interval = 12
+max_time = 13
+max_elements = 15
+max_envelope_size = 8192
+read_existing_events = false
+content_format = "Raw" # or RenderedText
+ignore_channel_error = true
+
+[filter]
+operation = "Only" # or Except
+princs = ["toto@windomain.local", "tutu@windomain.local"]
+
+## Files output with old config format
+[[outputs]]
+driver = "Files"
+format = "Json" # or "Raw"
+enabled = true
+
+[outputs.config]
+base = "/tmp/"
+split_on_addr_index = 2
+append_node_name = true
+filename = "courgette"
+
+## Kafka output
+[[outputs]]
+driver = "Kafka"
+format = "Raw"
+enabled = false
+
+[outputs.config]
+topic = "my-topic"
+
+[outputs.config.options]
+"bootstrap.server" = "localhost:9092"
+
+## Tcp output
+[[outputs]]
+driver = "Tcp"
+format = "RawJson"
+enabled = true
+
+[outputs.config]
+addr = "127.0.0.1"
+port = 8080
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+enabled = true
+
+[outputs.config]
+host = "localhost"
+port = 8080
+tls_enabled = true
+tls_certificate_authorities = "test_path"
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+enabled = true
+
+[outputs.config]
+host = "localhost"
+port = 8080
+tls_enabled = true
+tls_certificate_authorities = ["test_path1", "test_path2"]
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+enabled = true
+
+[outputs.config]
+host = "localhost"
+port = 8080
+tls_enabled = true
+tls_certificate_authorities = ["test_path1", "test_path2"]
+tls_certificate = "test_cert_path"
+tls_key = "test_key_path"
+
+## Redis output
+[[outputs]]
+driver = "Redis"
+format = "Nxlog"
+enabled = false
+
+[outputs.config]
+addr = "localhost"
+list = "my-list"
+
+## UnixDatagram output
+[[outputs]]
+format = "Raw"
+enabled =  true
+driver = "UnixDatagram"
+
+[outputs.config]
+path = "/tmp/openwec.socket"
+
+## Files output with new config format
+[[outputs]]
+driver = "Files"
+format = "Json" # or "Raw"
+enabled = true
+
+[outputs.config]
+path = "/whatever/you/{ip}/want/{principal}/{ip:2}/{node}/end"
+    "#;
+
+    #[test]
+    fn test_deserialize_full() -> Result<()> {
+        let revision = "My-revision".to_string();
+        let mut data = parse(FULL_CONTENT, Some(&revision))?;
+
+        let mut expected =
+            crate::subscription::SubscriptionData::new("my-subscription", "a wonderful query\n");
+        expected
+            .set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
+                "b00bf259-3ba9-4faf-b58e-d0e9a3275778",
+            )?))
+            .set_uri(Some("toto".to_string()))
+            .set_enabled(true)
+            .set_heartbeat_interval(32)
+            .set_connection_retry_count(11)
+            .set_connection_retry_interval(12)
+            .set_max_time(13)
+            .set_max_elements(Some(15))
+            .set_max_envelope_size(8192)
+            .set_read_existing_events(false)
+            .set_content_format(crate::subscription::ContentFormat::Raw)
+            .set_ignore_channel_error(true)
+            .set_revision(Some(revision));
+
+        let mut kafka_options = HashMap::new();
+        kafka_options.insert("bootstrap.server".to_string(), "localhost:9092".to_string());
+
+        let outputs = vec![
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Json,
+                crate::subscription::SubscriptionOutputDriver::Files(
+                    crate::subscription::FilesConfiguration::new(
+                        "/tmp/{ip:2}/{ip:3}/{ip}/{principal}/{node}/courgette".to_string(),
+                    ),
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Raw,
+                crate::subscription::SubscriptionOutputDriver::Kafka(
+                    crate::subscription::KafkaConfiguration::new(
+                        "my-topic".to_string(),
+                        kafka_options,
+                    ),
+                ),
+                false,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::RawJson,
+                crate::subscription::SubscriptionOutputDriver::Tcp(
+                    crate::subscription::TcpConfiguration::new(
+                        "127.0.0.1".to_string(),
+                        8080,
+                        false,
+                        Vec::new(),
+                        None,
+                        None,
+                    )?,
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Json,
+                crate::subscription::SubscriptionOutputDriver::Tcp(
+                    crate::subscription::TcpConfiguration::new(
+                        "localhost".to_string(),
+                        8080,
+                        true,
+                        vec!["test_path".to_string()],
+                        None,
+                        None,
+                    )?,
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Json,
+                crate::subscription::SubscriptionOutputDriver::Tcp(
+                    crate::subscription::TcpConfiguration::new(
+                        "localhost".to_string(),
+                        8080,
+                        true,
+                        vec!["test_path1".to_string(), "test_path2".to_string()],
+                        None,
+                        None,
+                    )?,
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Json,
+                crate::subscription::SubscriptionOutputDriver::Tcp(
+                    crate::subscription::TcpConfiguration::new(
+                        "localhost".to_string(),
+                        8080,
+                        true,
+                        vec!["test_path1".to_string(), "test_path2".to_string()],
+                        Some("test_cert_path".to_string()),
+                        Some("test_key_path".to_string()),
+                    )?,
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Nxlog,
+                crate::subscription::SubscriptionOutputDriver::Redis(
+                    crate::subscription::RedisConfiguration::new(
+                        "localhost".to_string(),
+                        "my-list".to_string(),
+                    ),
+                ),
+                false,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Raw,
+                crate::subscription::SubscriptionOutputDriver::UnixDatagram(
+                    crate::subscription::UnixDatagramConfiguration::new(
+                        "/tmp/openwec.socket".to_string(),
+                    ),
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Json,
+                crate::subscription::SubscriptionOutputDriver::Files(
+                    crate::subscription::FilesConfiguration::new(
+                        "/whatever/you/{ip}/want/{principal}/{ip:2}/{node}/end".to_string(),
+                    ),
+                ),
+                true,
+            ),
+        ];
+
+        expected.set_outputs(outputs);
+
+        let mut targets = HashSet::new();
+        targets.insert("toto@windomain.local".to_string());
+        targets.insert("tutu@windomain.local".to_string());
+        let kind = crate::subscription::ClientFilterType::KerberosPrinc;
+        let flags = crate::subscription::ClientFilterFlags::empty();
+        let filter = crate::subscription::ClientFilter::try_new(crate::subscription::ClientFilterOperation::Only, kind, flags, targets)?;
+
+        expected.set_client_filter(Some(filter));
+
+        // The only difference between both subscriptions should be the
+        // internal version, so we set both the same value
+        let version = Uuid::new_v4();
+        // Must be done last
+        expected.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+        data.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    const MINIMAL_CONTENT: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "minimal"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+
+[outputs.config]
+path = "/tmp/my.socket"
+    "#;
+
+    #[test]
+    fn test_serialize_minimal() -> Result<()> {
+        let mut data = parse(MINIMAL_CONTENT, None)?;
+
+        let mut expected =
+            crate::subscription::SubscriptionData::new("minimal", "a very small query\n");
+        expected
+            .set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
+                "b00bf259-3ba9-4faf-b58e-d0e9a3757798",
+            )?))
+            .set_outputs(vec![crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Json,
+                crate::subscription::SubscriptionOutputDriver::UnixDatagram(
+                    crate::subscription::UnixDatagramConfiguration::new(
+                        "/tmp/my.socket".to_string(),
+                    ),
+                ),
+                true,
+            )]);
+
+        // Must be done last
+        let version = Uuid::new_v4();
+        data.set_internal_version(InternalVersion(version));
+        expected.set_internal_version(InternalVersion(version));
+
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    const MISSING_UUID: &str = r#"
+name = "minimal"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+
+[outputs.config]
+path = "/tmp/my.socket"
+    "#;
+
+    #[test]
+    #[should_panic(expected = "missing field `uuid`")]
+    fn test_serialize_missing_uuid() {
+        parse(MISSING_UUID, None).unwrap();
+    }
+
+    const MISSING_NAME: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+
+[outputs.config]
+path = "/tmp/my.socket"
+    "#;
+
+    #[test]
+    #[should_panic(expected = "missing field `name`")]
+    fn test_serialize_missing_name() {
+        parse(MISSING_NAME, None).unwrap();
+    }
+
+    const MISSING_QUERY: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "minimal"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+
+[outputs.config]
+path = "/tmp/my.socket"
+    "#;
+
+    #[test]
+    #[should_panic(expected = "missing field `query`")]
+    fn test_serialize_missing_query() {
+        parse(MISSING_QUERY, None).unwrap();
+    }
+
+    const MISSING_OUTPUTS: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "minimal"
+
+query = """
+a very small query
+"""
+
+outputs = []
+    "#;
+
+    #[test]
+    #[should_panic(expected = "Missing subscription outputs")]
+    fn test_serialize_missing_outputs() {
+        parse(MISSING_OUTPUTS, None).unwrap();
+    }
+
+    const RANDOM_FIELD: &str = r#"
+babar = "courgette"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "minimal"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+
+[outputs.config]
+path = "/tmp/my.socket"
+    "#;
+
+    #[test]
+    #[should_panic(expected = "unknown field `babar`")]
+    fn test_random_field() {
+        parse(RANDOM_FIELD, None).unwrap();
+    }
+
+    const GETTING_STARTED_CONF: &str = r#"
+# conf/my-test-subscription.toml
+
+# Unique identifier of the subscription
+uuid = "28fcc206-1336-4e4a-b76b-18b0ab46e585"
+# Unique name of the subscription
+name = "my-test-subscription"
+
+# Subscription query
+query = """
+<QueryList>
+    <Query Id="0">
+        <Select Path="Application">*</Select>
+        <Select Path="Security">*</Select>
+        <Select Path="Setup">*</Select>
+        <Select Path="System">*</Select>
+    </Query>
+</QueryList>
+"""
+
+# Subscription outputs
+[[outputs]]
+driver = "Files"
+format = "Raw"
+config = { path = "/data/logs/{ip}/{principal}/messages" }
+
+# Subscription outputs
+[[outputs]]
+driver = "Kafka"
+format = "RawJson"
+# FIXME: `config.options` should be configured in OpenWEC settings (`outputs.kafka.options`)
+# to use only one kafka producer client for all kafka outputs
+config = { topic = "my-kafka-topic", options = { "bootstrap.servers" = "localhost:9092" } }
+    "#;
+    const GETTING_STARTED_QUERY: &str = r#"<QueryList>
+    <Query Id="0">
+        <Select Path="Application">*</Select>
+        <Select Path="Security">*</Select>
+        <Select Path="Setup">*</Select>
+        <Select Path="System">*</Select>
+    </Query>
+</QueryList>
+"#;
+
+    #[test]
+    fn test_getting_started_conf() -> Result<()> {
+        let mut data = parse(GETTING_STARTED_CONF, None)?;
+
+        let mut expected = crate::subscription::SubscriptionData::new(
+            "my-test-subscription",
+            GETTING_STARTED_QUERY,
+        );
+        expected.set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
+            "28fcc206-1336-4e4a-b76b-18b0ab46e585",
+        )?));
+
+        let mut kafka_options = HashMap::new();
+        kafka_options.insert(
+            "bootstrap.servers".to_string(),
+            "localhost:9092".to_string(),
+        );
+
+        let outputs = vec![
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Raw,
+                crate::subscription::SubscriptionOutputDriver::Files(
+                    crate::subscription::FilesConfiguration::new(
+                        "/data/logs/{ip}/{principal}/messages".to_string(),
+                    ),
+                ),
+                true,
+            ),
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::RawJson,
+                crate::subscription::SubscriptionOutputDriver::Kafka(
+                    crate::subscription::KafkaConfiguration::new(
+                        "my-kafka-topic".to_string(),
+                        kafka_options,
+                    ),
+                ),
+                true,
+            ),
+        ];
+
+        expected.set_outputs(outputs);
+
+        // The only difference between both subscriptions should be the
+        // internal version, so we set both the same value
+        let version = Uuid::new_v4();
+        // Must be done last
+        expected.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+        data.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_query() -> Result<()> {
+        let validated = format!(
+            "{}\n[options]\nvalidate_query = true\n",
+            GETTING_STARTED_CONF
+        );
+        let data = parse(&validated, None)?;
+        assert_eq!(data.query(), GETTING_STARTED_QUERY);
+
+        let invalid_queries = [
+            // Typo in the root element
+            GETTING_STARTED_QUERY.replace("QueryList>", "QueryLst>"),
+            // Not well-formed
+            GETTING_STARTED_QUERY.replace("</QueryList>", ""),
+            GETTING_STARTED_QUERY.replace("Path=\"Setup\"", "Path=\"Setup"),
+            // Typo in a child element
+            GETTING_STARTED_QUERY.replace("<Query Id=\"0\">", "<Qeury Id=\"0\">"),
+            GETTING_STARTED_QUERY.replace("<Select Path=\"Setup\">*</Select>", "<Selct Path=\"Setup\">*</Selct>"),
+            // Nothing selected
+            "<QueryList></QueryList>".to_string(),
+            r#"<QueryList><Query Id="0"><Suppress Path="Security">*</Suppress></Query></QueryList>"#
+                .to_string(),
+            r#"<QueryList><Query Id="0"><Select>*</Select></Query></QueryList>"#.to_string(),
+        ];
+        for query in invalid_queries.iter() {
+            let conf = validated.replace(GETTING_STARTED_QUERY, query);
+            assert!(parse(&conf, None).is_err(), "{} should be rejected", query);
+            // Queries are only validated if asked to
+            let conf = conf.replace("validate_query = true", "validate_query = false");
+            assert_eq!(parse(&conf, None)?.query(), query);
+            let conf = GETTING_STARTED_CONF.replace(GETTING_STARTED_QUERY, query);
+            assert!(parse(&conf, None).is_ok());
+        }
+        Ok(())
+    }
+
+    const CLIENT_FILTER_CONF: &str = r#"
+uuid = "28fcc206-1336-4e4a-b76b-18b0ab46e585"
+name = "my-test-subscription"
+query = "<QueryList></QueryList>"
+
+[[outputs]]
+driver = "Files"
+format = "Raw"
+config = { path = "/data/logs/{ip}/{principal}/messages" }
+
+[filter]
+operation = "Only"
+type = "KerberosPrinc"
+flags = "GlobPattern | CaseInsensitive"
+targets = ["radis*@REALM"]
+
+    "#;
+
+    #[test]
+    fn test_client_filter() -> Result<()> {
+        let mut data = parse(CLIENT_FILTER_CONF, None)?;
+
+        let mut expected = crate::subscription::SubscriptionData::new(
+            "my-test-subscription",
+            "<QueryList></QueryList>",
+        );
+        expected.set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
+            "28fcc206-1336-4e4a-b76b-18b0ab46e585",
+        )?));
+
+        let outputs = vec![
+            crate::subscription::SubscriptionOutput::new(
+                crate::subscription::SubscriptionOutputFormat::Raw,
+                crate::subscription::SubscriptionOutputDriver::Files(
+                    crate::subscription::FilesConfiguration::new(
+                        "/data/logs/{ip}/{principal}/messages".to_string(),
+                    ),
+                ),
+                true,
+            ),
+        ];
+
+        expected.set_outputs(outputs);
+
+        let operation = crate::subscription::ClientFilterOperation::Only;
+        let kind = crate::subscription::ClientFilterType::KerberosPrinc;
+        let flags = crate::subscription::ClientFilterFlags::GlobPattern | crate::subscription::ClientFilterFlags::CaseInsensitive;
+
+        let mut targets = HashSet::new();
+        targets.insert("radis*@REALM".to_string());
+
+        let filter = crate::subscription::ClientFilter::try_new(operation, kind, flags, targets)?;
+        expected.set_client_filter(Some(filter));
+
+        let version = Uuid::new_v4();
+        // Must be done last
+        expected.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+        data.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_filter_machine_id() -> Result<()> {
+        let conf = CLIENT_FILTER_CONF
+            .replace("type = \"KerberosPrinc\"", "type = \"MachineID\"")
+            .replace("radis*@REALM", "WIN10-*$@DOMAIN");
+        let data = parse(&conf, None)?;
+
+        let filter = data.client_filter().unwrap();
+        assert_eq!(
+            *filter.kind(),
+            crate::subscription::ClientFilterType::MachineID
+        );
+        assert_eq!(
+            *filter.flags(),
+            crate::subscription::ClientFilterFlags::GlobPattern
+                | crate::subscription::ClientFilterFlags::CaseInsensitive
+        );
+
+        // Clients are filtered using the MachineID they send, not their principal
+        assert!(data.is_active_for("DC$@WINDOMAIN.LOCAL", Some("win10-abc$@domain")));
+        assert!(!data.is_active_for("win10-abc$@domain", Some("win11-abc$@domain")));
+        assert!(!data.is_active_for("win10-abc$@domain", None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_filter_ip_range() -> Result<()> {
+        let conf = CLIENT_FILTER_CONF
+            .replace("type = \"KerberosPrinc\"", "type = \"IpRange\"")
+            .replace("flags = \"GlobPattern | CaseInsensitive\"", "")
+            .replace("\"radis*@REALM\"", "\"10.0.0.0/8\", \"2001:db8::/32\"");
+        let data = parse(&conf, None)?;
+
+        let filter = data.client_filter().unwrap();
+        assert_eq!(
+            *filter.kind(),
+            crate::subscription::ClientFilterType::IpRange
+        );
+        assert_eq!(
+            filter.targets(),
+            HashSet::from(["10.0.0.0/8", "2001:db8::/32"])
+        );
+        let ip = |ip: &str| {
+            crate::subscription::ClientContext::new("DC$@WINDOMAIN.LOCAL", None)
+                .with_ip(Some(std::net::IpAddr::from_str(ip).unwrap()))
+        };
+        assert!(data.is_active_for_context(&ip("10.1.2.3")));
+        assert!(data.is_active_for_context(&ip("2001:db8:1::1")));
+        assert!(!data.is_active_for_context(&ip("192.168.58.100")));
+
+        // Targets must be valid IP ranges
+        let malformed = conf.replace("10.0.0.0/8", "10.0.0.0/33");
+        assert!(parse(&malformed, None).is_err());
+        let malformed = conf.replace("10.0.0.0/8", "radis*@REALM");
+        assert!(parse(&malformed, None).is_err());
+
+        // IP ranges are not glob patterns
+        let glob = conf.replace(
+            "type = \"IpRange\"",
+            "type = \"IpRange\"\nflags = \"GlobPattern\"",
+        );
+        assert!(parse(&glob, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_filter_regex() -> Result<()> {
+        let conf = CLIENT_FILTER_CONF
+            .replace("GlobPattern | CaseInsensitive", "Regex | CaseInsensitive")
+            .replace("radis*@REALM", "radis[0-9]+@REALM");
+        let data = parse(&conf, None)?;
+
+        let filter = data.client_filter().unwrap();
+        assert_eq!(
+            *filter.flags(),
+            crate::subscription::ClientFilterFlags::Regex
+                | crate::subscription::ClientFilterFlags::CaseInsensitive
+        );
+        assert_eq!(filter.targets(), HashSet::from(["radis[0-9]+@REALM"]));
+        assert!(data.is_active_for("RADIS01@realm", None));
+        assert!(!data.is_active_for("radis@REALM", None));
+
+        let conf = conf.replace("Regex | CaseInsensitive", "Regex");
+        let data = parse(&conf, None)?;
+        assert_eq!(
+            *data.client_filter().unwrap().flags(),
+            crate::subscription::ClientFilterFlags::Regex
+        );
+        assert!(data.is_active_for("radis01@REALM", None));
+        assert!(!data.is_active_for("RADIS01@realm", None));
+
+        // Expressions are compiled when the config is parsed
+        let invalid = conf.replace("radis[0-9]+@REALM", "radis[0-9+@REALM");
+        assert!(parse(&invalid, None).is_err());
+        // Targets are either regular expressions or glob patterns
+        let both = conf.replace("flags = \"Regex\"", "flags = \"Regex | GlobPattern\"");
+        assert!(parse(&both, None).is_err());
+        let unknown = conf.replace("flags = \"Regex\"", "flags = \"Regexp\"");
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_filter_flags_round_trip() -> Result<()> {
+        #[derive(Deserialize)]
+        struct Flags {
+            flags: ClientFilterFlags,
+        }
+
+        // Flags written by Display are parsed back, whatever the combination
+        for bits in 0..=ClientFilterFlags::All.bits() {
+            let flags = ClientFilterFlags::from_bits(bits).unwrap();
+            let parsed: Flags = toml::from_str(&format!("flags = \"{}\"", flags))?;
+            assert_eq!(parsed.flags, flags);
+            assert_eq!(
+                crate::subscription::ClientFilterFlags::from(parsed.flags).bits(),
+                bits
+            );
+        }
+        let parsed: Flags = toml::from_str(r#"flags = "All""#)?;
+        assert_eq!(
+            crate::subscription::ClientFilterFlags::from(parsed.flags),
+            crate::subscription::ClientFilterFlags::All
+        );
+        Ok(())
+    }
+
+    const SOURCE_FIRST_SEEN_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "first-seen"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+source_first_seen = true
+config = { path = "/tmp/first_seen.socket" }
+    "#;
+
+    #[test]
+    fn test_source_first_seen() -> Result<()> {
+        let data = parse(SOURCE_FIRST_SEEN_CONF, None)?;
+
+        assert_eq!(data.outputs().len(), 2);
+        assert!(!data.outputs()[0].source_first_seen());
+        assert!(data.outputs()[1].source_first_seen());
+        Ok(())
+    }
+
+    const SUMMARY_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "summary"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+summary = true
+config = { path = "/tmp/summary.socket" }
+    "#;
+
+    #[test]
+    fn test_summary() -> Result<()> {
+        let data = parse(SUMMARY_CONF, None)?;
+
+        assert_eq!(data.outputs().len(), 2);
+        assert!(!data.outputs()[0].summary());
+        assert!(data.outputs()[1].summary());
+
+        let both = SUMMARY_CONF.replace("summary = true", "summary = true\nsource_first_seen = true");
+        assert!(parse(&both, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dead_letter() -> Result<()> {
+        let conf = SUMMARY_CONF.replace("summary = true", "dead_letter = true");
+        let data = parse(&conf, None)?;
+
+        assert!(!data.outputs()[0].dead_letter());
+        assert!(data.outputs()[1].dead_letter());
+        assert!(!data.outputs()[1].summary());
+
+        let both = SUMMARY_CONF.replace("summary = true", "summary = true\ndead_letter = true");
+        assert!(parse(&both, None).is_err());
+        Ok(())
+    }
+
+    const FIELD_MAP_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "field_map"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+field_map = { "System.Computer" = "host", "System.EventID" = "event_code" }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Nxlog"
+config = { path = "/tmp/nxlog.socket" }
+field_map = { "Hostname" = "host" }
+drop_unmapped = true
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/raw.socket" }
+    "#;
+
+    #[test]
+    fn test_field_map() -> Result<()> {
+        let data = parse(FIELD_MAP_CONF, None)?;
+
+        let mut fields = BTreeMap::new();
+        fields.insert("System.Computer".to_string(), "host".to_string());
+        fields.insert("System.EventID".to_string(), "event_code".to_string());
+        assert_eq!(
+            data.outputs()[0].field_map(),
+            &FieldMap::try_new(fields, false)?
+        );
+
+        let mut fields = BTreeMap::new();
+        fields.insert("Hostname".to_string(), "host".to_string());
+        assert_eq!(
+            data.outputs()[1].field_map(),
+            &FieldMap::try_new(fields, true)?
+        );
+        assert!(data.outputs()[1].field_map().drop_unmapped());
+
+        // Fields are not renamed by default
+        assert!(data.outputs()[2].field_map().is_empty());
+
+        // Two fields can not be renamed to the same target
+        let duplicate = FIELD_MAP_CONF.replace(
+            r#""System.EventID" = "event_code""#,
+            r#""System.EventID" = "host""#,
+        );
+        assert!(parse(&duplicate, None).is_err());
+
+        // A target can not be nested in another one
+        let nested = FIELD_MAP_CONF.replace(
+            r#""System.EventID" = "event_code""#,
+            r#""System.EventID" = "host.code""#,
+        );
+        assert!(parse(&nested, None).is_err());
+
+        let empty_segment = FIELD_MAP_CONF.replace(
+            r#""Hostname" = "host""#,
+            r#""Hostname" = "host.""#,
+        );
+        assert!(parse(&empty_segment, None).is_err());
+
+        // Raw formats do not support field renaming
+        let raw = FIELD_MAP_CONF.replace(r#"format = "Nxlog""#, r#"format = "Raw""#);
+        assert!(parse(&raw, None).is_err());
+        Ok(())
+    }
+
+    const TCP_FRAMING_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "tcp_framing"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000 }
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000, framing = "newline" }
+
+[[outputs]]
+driver = "Tcp"
+format = "Raw"
+config = { host = "localhost", port = 5000, framing = "length_prefixed" }
+
+[[outputs]]
+driver = "Tcp"
+format = "Nxlog"
+config = { host = "localhost", port = 5000, framing = "octet_counting" }
+    "#;
+
+    #[test]
+    fn test_tcp_framing() -> Result<()> {
+        let data = parse(TCP_FRAMING_CONF, None)?;
+
+        let framings: Vec<crate::subscription::TcpFraming> = data
+            .outputs()
+            .iter()
+            .map(|output| match output.driver() {
+                crate::subscription::SubscriptionOutputDriver::Tcp(config) => config.framing(),
+                _ => panic!("Unexpected driver"),
+            })
+            .collect();
+        assert_eq!(
+            framings,
+            vec![
+                crate::subscription::TcpFraming::Newline,
+                crate::subscription::TcpFraming::Newline,
+                crate::subscription::TcpFraming::LengthPrefixed,
+                crate::subscription::TcpFraming::OctetCounting,
+            ]
+        );
+
+        let unknown = TCP_FRAMING_CONF.replace("octet_counting", "syslog");
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    const TCP_RECONNECT_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "tcp_reconnect"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000 }
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000, reconnect_initial_ms = 100, reconnect_max_ms = 10000, buffer_max_events = 5000 }
+    "#;
+
+    #[test]
+    fn test_tcp_reconnect() -> Result<()> {
+        let data = parse(TCP_RECONNECT_CONF, None)?;
+
+        let configs: Vec<&crate::subscription::TcpConfiguration> = data
+            .outputs()
+            .iter()
+            .map(|output| match output.driver() {
+                crate::subscription::SubscriptionOutputDriver::Tcp(config) => config,
+                _ => panic!("Unexpected driver"),
+            })
+            .collect();
+        assert_eq!(configs[0].reconnect_initial_ms(), None);
+        assert_eq!(configs[0].reconnect_max_ms(), None);
+        assert_eq!(configs[0].buffer_max_events(), None);
+        assert_eq!(configs[1].reconnect_initial_ms(), Some(100));
+        assert_eq!(configs[1].reconnect_max_ms(), Some(10000));
+        assert_eq!(configs[1].buffer_max_events(), Some(5000));
+
+        // The maximum delay can not be lower than the initial one
+        let lower = TCP_RECONNECT_CONF.replace("reconnect_max_ms = 10000", "reconnect_max_ms = 50");
+        assert!(parse(&lower, None).is_err());
+        let zero =
+            TCP_RECONNECT_CONF.replace("reconnect_initial_ms = 100", "reconnect_initial_ms = 0");
+        assert!(parse(&zero, None).is_err());
+        let empty = TCP_RECONNECT_CONF.replace("buffer_max_events = 5000", "buffer_max_events = 0");
+        assert!(parse(&empty, None).is_err());
+        Ok(())
+    }
+
+    const TCP_SOCKET_OPTIONS_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "tcp_socket_options"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000 }
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000, tcp_keepalive_secs = 30, tcp_nodelay = true }
+    "#;
+
+    #[test]
+    fn test_tcp_socket_options() -> Result<()> {
+        let data = parse(TCP_SOCKET_OPTIONS_CONF, None)?;
+
+        let configs: Vec<&crate::subscription::TcpConfiguration> = data
+            .outputs()
+            .iter()
+            .map(|output| match output.driver() {
+                crate::subscription::SubscriptionOutputDriver::Tcp(config) => config,
+                _ => panic!("Unexpected driver"),
+            })
+            .collect();
+        assert_eq!(configs[0].tcp_keepalive_secs(), None);
+        assert!(!configs[0].tcp_nodelay());
+        assert_eq!(configs[1].tcp_keepalive_secs(), Some(30));
+        assert!(configs[1].tcp_nodelay());
+
+        let zero =
+            TCP_SOCKET_OPTIONS_CONF.replace("tcp_keepalive_secs = 30", "tcp_keepalive_secs = 0");
+        assert!(parse(&zero, None).is_err());
+        Ok(())
+    }
+
+    const TCP_TLS_PINNED_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "tcp_tls_pinned"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000, tls_enabled = true, tls_pinned_sha256 = ["5C:1F:07:C2:E0:6C:4C:6E:9A:8F:29:E0:E2:B1:AD:1F:33:C9:D3:A6:E0:BF:C0:C9:DB:C4:F7:A1:C1:F6:B4:E2", "0f0e0d0c0b0a09080706050403020100f0e0d0c0b0a090807060504030201000"] }
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000, tls_enabled = true, tls_certificate_authorities = "/etc/ca.pem", tls_pinned_sha256 = "0f0e0d0c0b0a09080706050403020100f0e0d0c0b0a090807060504030201000" }
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "localhost", port = 5000, tls_enabled = true, tls_certificate_authorities = "/etc/ca.pem" }
+    "#;
+
+    #[test]
+    fn test_tcp_tls_pinned_sha256() -> Result<()> {
+        let data = parse(TCP_TLS_PINNED_CONF, None)?;
+
+        let configs: Vec<&crate::subscription::TcpConfiguration> = data
+            .outputs()
+            .iter()
+            .map(|output| match output.driver() {
+                crate::subscription::SubscriptionOutputDriver::Tcp(config) => config,
+                _ => panic!("Unexpected driver"),
+            })
+            .collect();
+        // Pins are normalized
+        assert_eq!(
+            configs[0].tls_pinned_sha256(),
+            &[
+                "5c1f07c2e06c4c6e9a8f29e0e2b1ad1f33c9d3a6e0bfc0c9dbc4f7a1c1f6b4e2".to_string(),
+                "0f0e0d0c0b0a09080706050403020100f0e0d0c0b0a090807060504030201000".to_string()
+            ]
+        );
+        assert!(configs[0].tls_certificate_authorities().is_empty());
+        assert_eq!(
+            configs[1].tls_pinned_sha256(),
+            &["0f0e0d0c0b0a09080706050403020100f0e0d0c0b0a090807060504030201000".to_string()]
+        );
+        assert_eq!(configs[1].tls_certificate_authorities(), &["/etc/ca.pem"]);
+        assert!(configs[2].tls_pinned_sha256().is_empty());
+
+        // Fingerprints must be SHA-256 hashes
+        let short = TCP_TLS_PINNED_CONF.replace(
+            "tls_pinned_sha256 = \"0f0e0d0c0b0a09080706050403020100f0e0d0c0b0a090807060504030201000\"",
+            "tls_pinned_sha256 = \"0f0e0d0c\"",
+        );
+        assert!(parse(&short, None).is_err());
+        let not_hex = TCP_TLS_PINNED_CONF.replace("5C:1F:07", "ZZ:1F:07");
+        assert!(parse(&not_hex, None).is_err());
+
+        // Server certificates can not be left unverified
+        let unverified =
+            TCP_TLS_PINNED_CONF.replace(", tls_certificate_authorities = \"/etc/ca.pem\" }", " }");
+        assert!(parse(&unverified, None).is_err());
+        Ok(())
+    }
+
+    const KAFKA_HEADERS_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "kafka_headers"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Kafka"
+format = "Json"
+config = { topic = "events" }
+
+[[outputs]]
+driver = "Kafka"
+format = "Json"
+
+[outputs.config]
+topic = "events"
+
+[outputs.config.headers]
+"x-subscription" = "{subscription}"
+"x-source" = "{ip}"
+"x-route" = "{channel}/{event_id}"
+"x-format" = "{format}"
+    "#;
+
+    #[test]
+    fn test_kafka_headers() -> Result<()> {
+        let data = parse(KAFKA_HEADERS_CONF, None)?;
+
+        let configs: Vec<&crate::subscription::KafkaConfiguration> = data
+            .outputs()
+            .iter()
+            .map(|output| match output.driver() {
+                crate::subscription::SubscriptionOutputDriver::Kafka(config) => config,
+                _ => panic!("Unexpected driver"),
+            })
+            .collect();
+        assert!(configs[0].headers().is_empty());
+        assert!(!configs[0].headers_need_event_fields());
+        assert_eq!(
+            configs[1].headers(),
+            &BTreeMap::from([
+                ("x-format".to_string(), "{format}".to_string()),
+                ("x-route".to_string(), "{channel}/{event_id}".to_string()),
+                ("x-source".to_string(), "{ip}".to_string()),
+                ("x-subscription".to_string(), "{subscription}".to_string()),
+            ])
+        );
+        assert!(configs[1].headers_need_event_fields());
+
+        // Unknown tokens and malformed templates are rejected
+        let unknown = KAFKA_HEADERS_CONF.replace("{ip}", "{principal}");
+        assert!(parse(&unknown, None).is_err());
+        let unclosed = KAFKA_HEADERS_CONF.replace("{ip}", "{ip");
+        assert!(parse(&unclosed, None).is_err());
+        Ok(())
+    }
+
+    const KAFKA_PRODUCER_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "kafka_producer"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Kafka"
+format = "Json"
+
+[outputs.config]
+topic = "events"
+acks = "all"
+compression_type = "zstd"
+enable_idempotence = true
+
+[outputs.config.options]
+"bootstrap.servers" = "localhost:9092"
+"acks" = "1"
+"enable.idempotence" = "true"
+    "#;
+
+    #[test]
+    fn test_kafka_producer_options() -> Result<()> {
+        let data = parse(KAFKA_PRODUCER_CONF, None)?;
+        let config = match data.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::Kafka(config) => config,
+            _ => panic!("Unexpected driver"),
+        };
+        // Typed settings are merged with options and take precedence
+        assert_eq!(
+            config.options(),
+            &HashMap::from([
+                (
+                    "bootstrap.servers".to_string(),
+                    "localhost:9092".to_string()
+                ),
+                ("acks".to_string(), "all".to_string()),
+                ("compression.type".to_string(), "zstd".to_string()),
+                ("enable.idempotence".to_string(), "true".to_string()),
+            ])
+        );
+
+        // Without typed settings, options are left as is
+        let data = parse(
+            &KAFKA_PRODUCER_CONF
+                .replace("acks = \"all\"\n", "")
+                .replace("compression_type = \"zstd\"\n", "")
+                .replace("enable_idempotence = true\n", ""),
+            None,
+        )?;
+        let config = match data.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::Kafka(config) => config,
+            _ => panic!("Unexpected driver"),
+        };
+        assert_eq!(
+            config.options(),
+            &HashMap::from([
+                (
+                    "bootstrap.servers".to_string(),
+                    "localhost:9092".to_string()
+                ),
+                ("acks".to_string(), "1".to_string()),
+                ("enable.idempotence".to_string(), "true".to_string()),
+            ])
+        );
+
+        // Invalid values are rejected
+        let invalid_acks = KAFKA_PRODUCER_CONF.replace("acks = \"all\"", "acks = \"al\"");
+        assert!(parse(&invalid_acks, None).is_err());
+        let invalid_compression = KAFKA_PRODUCER_CONF.replace(
+            "compression_type = \"zstd\"",
+            "compression_type = \"zstandard\"",
+        );
+        assert!(parse(&invalid_compression, None).is_err());
+        let invalid_idempotence = KAFKA_PRODUCER_CONF
+            .replace("enable_idempotence = true", "enable_idempotence = \"yes\"");
+        assert!(parse(&invalid_idempotence, None).is_err());
+        Ok(())
+    }
+
+    const REDIS_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "redis"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Redis"
+format = "Json"
+config = { addr = "localhost:6379", list = "events" }
+
+[[outputs]]
+driver = "Redis"
+format = "Json"
+config = { addr = "localhost:6379", list = "events", mode = "list" }
+
+[[outputs]]
+driver = "Redis"
+format = "Json"
+config = { addr = "localhost:6379", list = "events-stream", mode = "stream", max_len = 10000 }
+    "#;
+
+    #[test]
+    fn test_redis_mode() -> Result<()> {
+        let data = parse(REDIS_CONF, None)?;
+        let configs: Vec<&crate::subscription::RedisConfiguration> = data
+            .outputs()
+            .iter()
+            .map(|output| match output.driver() {
+                crate::subscription::SubscriptionOutputDriver::Redis(config) => config,
+                _ => panic!("Unexpected driver"),
+            })
+            .collect();
+        // List mode is the default
+        assert_eq!(configs[0].mode(), crate::subscription::RedisMode::List);
+        assert_eq!(configs[0].max_len(), None);
+        assert_eq!(configs[0], configs[1]);
+        assert_eq!(configs[2].mode(), crate::subscription::RedisMode::Stream);
+        assert_eq!(configs[2].list(), "events-stream");
+        assert_eq!(configs[2].max_len(), Some(10000));
+
+        // max_len is only supported in stream mode
+        let list_max_len = REDIS_CONF.replace("mode = \"list\"", "mode = \"list\", max_len = 10");
+        assert!(parse(&list_max_len, None).is_err());
+        let default_max_len =
+            REDIS_CONF.replace("list = \"events\" }", "list = \"events\", max_len = 10 }");
+        assert!(parse(&default_max_len, None).is_err());
+        let zero_max_len = REDIS_CONF.replace("max_len = 10000", "max_len = 0");
+        assert!(parse(&zero_max_len, None).is_err());
+        let unknown_mode = REDIS_CONF.replace("mode = \"stream\"", "mode = \"pubsub\"");
+        assert!(parse(&unknown_mode, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_ttl() -> Result<()> {
+        let data = parse(REDIS_CONF, None)?;
+        assert_eq!(data.event_ttl(), None);
+        assert!(event_ttl_issues(&data).is_empty());
+
+        let content = format!("{}\n[options]\nevent_ttl = 86400\n", REDIS_CONF);
+        let data = parse(&content, None)?;
+        assert_eq!(data.event_ttl(), Some(86400));
+        // Redis supports expiration
+        assert!(event_ttl_issues(&data).is_empty());
+
+        let zero = format!("{}\n[options]\nevent_ttl = 0\n", REDIS_CONF);
+        assert!(parse(&zero, None).is_err());
+
+        // The option is accepted but has no effect on other drivers
+        let content = format!(
+            r#"{}
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = {{ addr = "localhost", port = 5000 }}
+
+[options]
+event_ttl = 3600
+"#,
+            REDIS_CONF
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.event_ttl(), Some(3600));
+        assert_eq!(
+            event_ttl_issues(&data),
+            vec!["Output 3: event_ttl has no effect with driver tcp".to_string()]
+        );
+        Ok(())
+    }
+
+    const SAMPLING_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "sampling"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+sampling = { Critical = 100, Error = 100, Information = 10 }
+    "#;
+
+    #[test]
+    fn test_sampling() -> Result<()> {
+        let data = parse(SAMPLING_CONF, None)?;
+
+        let mut expected = BTreeMap::new();
+        expected.insert(crate::subscription::EventLevel::Critical, 100);
+        expected.insert(crate::subscription::EventLevel::Error, 100);
+        expected.insert(crate::subscription::EventLevel::Information, 10);
+        assert_eq!(data.outputs()[0].sampling(), &expected);
+
+        // Outputs without sampling keep every events
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert!(data.outputs()[0].sampling().is_empty());
+
+        let invalid_rate = SAMPLING_CONF.replace("Information = 10", "Information = 110");
+        assert!(parse(&invalid_rate, None).is_err());
+
+        let invalid_level = SAMPLING_CONF.replace("Information = 10", "Info = 10");
+        assert!(parse(&invalid_level, None).is_err());
+        Ok(())
+    }
+
+    const CSV_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "csv"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Files"
+format = "Csv"
+format_config = { columns = ["System.EventID", "EventData.TargetUserName"], delimiter = ";", header = true }
+config = { path = "/tmp/events.csv" }
+
+[[outputs]]
+driver = "Files"
+format = "Csv"
+format_config = { columns = ["System.Computer"] }
+config = { path = "/tmp/computers.csv" }
+    "#;
+
+    #[test]
+    fn test_csv_format() -> Result<()> {
+        let data = parse(CSV_CONF, None)?;
+
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Csv(
+                crate::subscription::CsvConfiguration::new(
+                    vec![
+                        "System.EventID".to_string(),
+                        "EventData.TargetUserName".to_string()
+                    ],
+                    ';',
+                    true
+                )
+            )
+        );
+        // Defaults
+        assert_eq!(
+            data.outputs()[1].format(),
+            &crate::subscription::SubscriptionOutputFormat::Csv(
+                crate::subscription::CsvConfiguration::new(
+                    vec!["System.Computer".to_string()],
+                    ',',
+                    false
+                )
+            )
+        );
+
+        let missing_config = CSV_CONF.replace(
+            r#"format_config = { columns = ["System.Computer"] }"#,
+            "",
+        );
+        assert!(parse(&missing_config, None).is_err());
+
+        let no_columns = CSV_CONF.replace(r#"["System.Computer"]"#, "[]");
+        assert!(parse(&no_columns, None).is_err());
+
+        let invalid_delimiter = CSV_CONF.replace(r#"delimiter = ";""#, r#"delimiter = ";;""#);
+        assert!(parse(&invalid_delimiter, None).is_err());
+
+        let quote_delimiter = CSV_CONF.replace(r#"delimiter = ";""#, r#"delimiter = "\"""#);
+        assert!(parse(&quote_delimiter, None).is_err());
+        Ok(())
+    }
+
+    const RFC5424_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "rfc5424"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Rfc5424"
+config = { host = "127.0.0.1", port = 6514, framing = "octet_counting" }
+
+[outputs.format_config]
+enterprise_number = 12345
+structured_data = [
+    { id = "openwec", params = { subscription = "OpenWEC.Subscription.Name", ip = "OpenWEC.IpAddress" } },
+    { id = "winlog", params = { event_id = "System.EventID", channel = "System.Channel" } },
+]
+
+[[outputs]]
+driver = "Tcp"
+format = "Rfc5424"
+config = { host = "127.0.0.1", port = 6514, framing = "octet_counting" }
+    "#;
+
+    #[test]
+    fn test_rfc5424_format() -> Result<()> {
+        let data = parse(RFC5424_CONF, None)?;
+
+        let mut openwec_params = BTreeMap::new();
+        openwec_params.insert(
+            "subscription".to_string(),
+            "OpenWEC.Subscription.Name".to_string(),
+        );
+        openwec_params.insert("ip".to_string(), "OpenWEC.IpAddress".to_string());
+        let mut winlog_params = BTreeMap::new();
+        winlog_params.insert("event_id".to_string(), "System.EventID".to_string());
+        winlog_params.insert("channel".to_string(), "System.Channel".to_string());
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Rfc5424(
+                crate::subscription::Rfc5424Configuration::try_new(
+                    12345,
+                    vec![
+                        crate::subscription::StructuredDataElement::try_new(
+                            "openwec".to_string(),
+                            openwec_params
+                        )?,
+                        crate::subscription::StructuredDataElement::try_new(
+                            "winlog".to_string(),
+                            winlog_params
+                        )?,
+                    ]
+                )?
+            )
+        );
+        // Defaults
+        assert_eq!(
+            data.outputs()[1].format(),
+            &crate::subscription::SubscriptionOutputFormat::Rfc5424(
+                crate::subscription::Rfc5424Configuration::default()
+            )
+        );
+
+        let duplicated_id = RFC5424_CONF.replace(r#"id = "winlog""#, r#"id = "openwec""#);
+        assert!(parse(&duplicated_id, None).is_err());
+
+        let invalid_id = RFC5424_CONF.replace(r#"id = "winlog""#, r#"id = "winlog@1""#);
+        assert!(parse(&invalid_id, None).is_err());
+
+        let invalid_param = RFC5424_CONF.replace("event_id =", r#""event id" ="#);
+        assert!(parse(&invalid_param, None).is_err());
+        Ok(())
+    }
+
+    const CEF_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "cef"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Cef"
+config = { host = "127.0.0.1", port = 5000 }
+format_config = { version = 1, device_vendor = "Windomain", device_product = "WEC", device_version = "2.0" }
+
+[[outputs]]
+driver = "Tcp"
+format = "Cef"
+config = { host = "127.0.0.1", port = 5000 }
+    "#;
+
+    #[test]
+    fn test_cef_format() -> Result<()> {
+        let data = parse(CEF_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Cef(
+                crate::subscription::CefConfiguration::try_new(
+                    1,
+                    "Windomain".to_string(),
+                    "WEC".to_string(),
+                    Some("2.0".to_string())
+                )?
+            )
+        );
+        // Defaults
+        assert_eq!(
+            data.outputs()[1].format(),
+            &crate::subscription::SubscriptionOutputFormat::Cef(
+                crate::subscription::CefConfiguration::default()
+            )
+        );
+
+        let invalid_version = CEF_CONF.replace("version = 1", "version = 2");
+        assert!(parse(&invalid_version, None).is_err());
+        Ok(())
+    }
+
+    const LEEF_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "leef"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Leef"
+config = { host = "127.0.0.1", port = 5000 }
+    "#;
+
+    #[test]
+    fn test_leef_format() -> Result<()> {
+        let data = parse(LEEF_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Leef
+        );
+
+        // Leef does not have any configuration
+        let with_config = LEEF_CONF.replace(
+            r#"format = "Leef""#,
+            r#"format = "Leef"
+format_config = { delimiter = "^" }"#,
+        );
+        assert!(parse(&with_config, None).is_err());
+        Ok(())
+    }
+
+    const ECS_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "ecs"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Stdout"
+format = "Ecs"
+    "#;
+
+    #[test]
+    fn test_ecs_format() -> Result<()> {
+        let data = parse(ECS_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Ecs
+        );
+
+        // Ecs does not have any configuration
+        let with_config = ECS_CONF.replace(
+            r#"format = "Ecs""#,
+            r#"format = "Ecs"
+format_config = { version = "8.11" }"#,
+        );
+        assert!(parse(&with_config, None).is_err());
+        Ok(())
+    }
+
+    const MSGPACK_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "msgpack"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Msgpack"
+config = { host = "127.0.0.1", port = 5000, framing = "length_prefixed" }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Msgpack"
+config = { path = "/run/openwec/events.socket" }
+    "#;
+
+    #[test]
+    fn test_msgpack_format() -> Result<()> {
+        let data = parse(MSGPACK_CONF, None)?;
+        for output in data.outputs() {
+            assert_eq!(
+                output.format(),
+                &crate::subscription::SubscriptionOutputFormat::Msgpack
+            );
+        }
+
+        // Binary events may contain line feeds
+        let newline = MSGPACK_CONF.replacen("length_prefixed", "newline", 1);
+        assert!(parse(&newline, None).is_err());
+        let files = MSGPACK_CONF.replacen(r#"driver = "UnixDatagram""#, r#"driver = "Files""#, 1);
+        assert!(parse(&files, None).is_err());
+        Ok(())
+    }
+
+    const TEMPLATE_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "template"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Stdout"
+format = "Template"
+format_config = { template = '{TimeCreated} {Computer} [{EventID}] \{{EventData.TargetUserName}\}' }
+    "#;
+
+    #[test]
+    fn test_template_format() -> Result<()> {
+        let data = parse(TEMPLATE_CONF, None)?;
+        let expected = crate::subscription::TemplateConfiguration::try_new(
+            r"{TimeCreated} {Computer} [{EventID}] \{{EventData.TargetUserName}\}".to_string(),
+        )?;
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Template(expected)
+        );
+        assert!(template_issues(&data).is_empty());
+
+        // Unknown tokens are accepted but reported
+        let unknown = TEMPLATE_CONF.replace("{Computer}", "{Hostname}");
+        let data = parse(&unknown, None)?;
+        assert_eq!(
+            template_issues(&data),
+            vec!["Output 0: unknown token {Hostname} in template will be empty".to_string()]
+        );
+
+        // Malformed templates are rejected
+        for template in ["{Computer", "Computer}", "{}"] {
+            let malformed = TEMPLATE_CONF.replace("{Computer}", template);
+            assert!(parse(&malformed, None).is_err());
+        }
+        let missing = TEMPLATE_CONF.replace(r#"format_config = "#, "# ");
+        assert!(parse(&missing, None).is_err());
+        Ok(())
+    }
+
+    const AVRO_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "avro"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Files"
+format = "Avro"
+config = { path = "/var/events.avro" }
+
+[[outputs]]
+driver = "Tcp"
+format = "Avro"
+config = { host = "127.0.0.1", port = 5000, framing = "length_prefixed" }
+    "#;
+
+    #[test]
+    fn test_avro_format() -> Result<()> {
+        let data = parse(AVRO_CONF, None)?;
+        for output in data.outputs() {
+            assert_eq!(
+                output.format(),
+                &crate::subscription::SubscriptionOutputFormat::Avro
+            );
+        }
+
+        // Files are Object Container Files, but streams still need framing
+        let newline = AVRO_CONF.replacen("length_prefixed", "newline", 1);
+        assert!(parse(&newline, None).is_err());
+        let stdout = AVRO_CONF
+            .replacen(r#"driver = "Files""#, r#"driver = "Stdout""#, 1)
+            .replacen(r#"config = { path = "/var/events.avro" }"#, "", 1);
+        assert!(parse(&stdout, None).is_err());
+        Ok(())
+    }
+
+    const PROTOBUF_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "protobuf"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Protobuf"
+config = { host = "127.0.0.1", port = 5000, framing = "length_prefixed" }
+format_config = { descriptor_set = "{descriptor_set}", message = "openwec.test.Event" }
+    "#;
+
+    fn protobuf_path(name: &str) -> String {
+        let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        path.pop();
+        path.push("tests/protobuf");
+        path.push(name);
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_protobuf_format() -> Result<()> {
+        let descriptor_set = protobuf_path("event.binpb");
+        let conf = PROTOBUF_CONF.replace("{descriptor_set}", &descriptor_set);
+        let data = parse(&conf, None)?;
+        let expected = crate::subscription::ProtobufConfiguration::try_new(
+            fs::read(&descriptor_set)?,
+            "openwec.test.Event".to_string(),
+        )?;
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Protobuf(Some(expected.clone()))
+        );
+        let descriptor = expected.message_descriptor()?;
+        assert_eq!(descriptor.full_name(), "openwec.test.Event");
+        assert!(descriptor.get_field_by_name("event_data").unwrap().is_map());
+
+        // The message must be defined in the descriptor set
+        let unknown_message = conf.replace("openwec.test.Event", "openwec.test.Unknown");
+        assert!(parse(&unknown_message, None).is_err());
+        // The descriptor set must exist and be a serialized FileDescriptorSet
+        let missing = PROTOBUF_CONF.replace("{descriptor_set}", &protobuf_path("missing.binpb"));
+        assert!(parse(&missing, None).is_err());
+        let not_serialized =
+            PROTOBUF_CONF.replace("{descriptor_set}", &protobuf_path("event.proto"));
+        assert!(parse(&not_serialized, None).is_err());
+
+        // Protobuf messages are binary: they can not be delimited by line feeds
+        let newline = conf.replace(", framing = \"length_prefixed\"", "");
+        assert!(parse(&newline, None).is_err());
+        let files = conf
+            .replace("driver = \"Tcp\"", "driver = \"Files\"")
+            .replace(
+                "{ host = \"127.0.0.1\", port = 5000, framing = \"length_prefixed\" }",
+                "{ path = \"/tmp/events\" }",
+            );
+        assert!(parse(&files, None).is_err());
+
+        // Without format_config, the built-in message is used
+        let builtin = PROTOBUF_CONF.replace(
+            r#"format_config = { descriptor_set = "{descriptor_set}", message = "openwec.test.Event" }"#,
+            "",
+        );
+        let data = parse(&builtin, None)?;
+        assert_eq!(
+            data.outputs()[0].format(),
+            &crate::subscription::SubscriptionOutputFormat::Protobuf(None)
+        );
+        Ok(())
+    }
+
+    const REQUIRE_AUTH_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "require_auth"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/my.socket" }
+
+[options]
+require_auth = "tls_cert"
+    "#;
+
+    #[test]
+    fn test_require_auth() -> Result<()> {
+        let data = parse(REQUIRE_AUTH_CONF, None)?;
+        assert_eq!(
+            data.require_auth(),
+            crate::subscription::RequireAuth::TlsCert
+        );
+
+        let data = parse(
+            &REQUIRE_AUTH_CONF.replace("tls_cert", "kerberos"),
+            None,
+        )?;
+        assert_eq!(
+            data.require_auth(),
+            crate::subscription::RequireAuth::Kerberos
+        );
+
+        // Any authentication method is accepted by default
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.require_auth(), crate::subscription::RequireAuth::Any);
+
+        let invalid = REQUIRE_AUTH_CONF.replace("tls_cert", "ntlm");
+        assert!(parse(&invalid, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_existing_events_since() -> Result<()> {
+        let data = parse(GETTING_STARTED_CONF, None)?;
+        assert_eq!(data.read_existing_events_since(), None);
+        assert_eq!(data.client_query()?, GETTING_STARTED_QUERY);
+
+        let expected = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        for since in [
+            r#""2024-03-01T10:00:00Z""#,
+            r#""2024-03-01T12:00:00+02:00""#,
+            "2024-03-01T10:00:00Z",
+            "2024-03-01 12:00:00+02:00",
+        ] {
+            let content = format!(
+                "{}\n[options]\nread_existing_events = true\nread_existing_events_since = {}\n",
+                GETTING_STARTED_CONF, since
+            );
+            let data = parse(&content, None)?;
+            assert_eq!(
+                data.read_existing_events_since(),
+                Some(&expected),
+                "{}",
+                since
+            );
+            // The query sent to clients leaves out older events of each channel
+            let query = data.client_query()?;
+            for channel in ["Application", "Security", "Setup", "System"] {
+                assert!(query.contains(&format!(
+                    "<Suppress Path=\"{}\">*[System[TimeCreated[@SystemTime&lt;'2024-03-01T10:00:00.000Z']]]</Suppress>",
+                    channel
+                )));
+            }
+        }
+
+        for since in [
+            // Not a timestamp
+            r#""yesterday""#,
+            // No time zone
+            r#""2024-03-01T10:00:00""#,
+            "2024-03-01T10:00:00",
+            "2024-03-01",
+            "1709287200",
+        ] {
+            let content = format!(
+                "{}\n[options]\nread_existing_events = true\nread_existing_events_since = {}\n",
+                GETTING_STARTED_CONF, since
+            );
+            assert!(parse(&content, None).is_err(), "{}", since);
+        }
+
+        // Existing events must be read
+        let content = format!(
+            "{}\n[options]\nread_existing_events_since = \"2024-03-01T10:00:00Z\"\n",
+            GETTING_STARTED_CONF
+        );
+        assert!(parse(&content, None).is_err());
+
+        // The query must be a QueryList
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "read_existing_events = true\nread_existing_events_since = \"2024-03-01T10:00:00Z\"",
+        );
+        assert!(parse(&content, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_channel_error_for() -> Result<()> {
+        let data = parse(GETTING_STARTED_CONF, None)?;
+        assert_eq!(data.ignore_channel_error_for(), None);
+        assert!(data.client_ignore_channel_error());
+        assert!(ignore_channel_error_issues(&data).is_empty());
+
+        // A missing Security channel must not be ignored, even though
+        // ignore_channel_error is set
+        let content = format!(
+            "{}\n[options]\nignore_channel_error = true\nignore_channel_error_for = [\"Setup\", \"Microsoft-Windows-Sysmon/Operational\"]\n",
+            GETTING_STARTED_CONF
+        );
+        let data = parse(&content, None)?;
+        assert!(data.ignore_channel_error());
+        assert_eq!(
+            data.ignore_channel_error_for(),
+            Some(&BTreeSet::from([
+                "Microsoft-Windows-Sysmon/Operational".to_string(),
+                "Setup".to_string()
+            ]))
+        );
+        assert_eq!(
+            data.untolerated_channels()?,
+            Some(vec![
+                "Application".to_string(),
+                "Security".to_string(),
+                "System".to_string()
+            ])
+        );
+        assert!(!data.client_ignore_channel_error());
+        assert_eq!(
+            ignore_channel_error_issues(&data),
+            vec![
+                "ignore_channel_error_for: channel Microsoft-Windows-Sysmon/Operational is not read by the query".to_string(),
+                "ignore_channel_error_for: channel errors are not ignored because the query also reads Application, Security, System. Move the tolerated channels to another subscription.".to_string(),
+            ]
+        );
+
+        // Errors are ignored when every channel is tolerated
+        let content = format!(
+            "{}\n[options]\nignore_channel_error = false\nignore_channel_error_for = [\"application\", \"security\", \"setup\", \"system\"]\n",
+            GETTING_STARTED_CONF
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.untolerated_channels()?, Some(Vec::new()));
+        assert!(data.client_ignore_channel_error());
+        assert!(ignore_channel_error_issues(&data).is_empty());
+
+        // No channel is tolerated
+        let content = format!(
+            "{}\n[options]\nignore_channel_error_for = []\n",
+            GETTING_STARTED_CONF
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.ignore_channel_error_for(), Some(&BTreeSet::new()));
+        assert!(!data.client_ignore_channel_error());
+
+        let content = format!(
+            "{}\n[options]\nignore_channel_error_for = \"Setup\"\n",
+            GETTING_STARTED_CONF
+        );
+        assert!(parse(&content, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_mode() -> Result<()> {
+        // Outputs run independently by default
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(
+            data.output_mode(),
+            crate::subscription::OutputMode::Parallel
+        );
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            r#"output_mode = "sequential""#,
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(
+            data.output_mode(),
+            crate::subscription::OutputMode::Sequential
+        );
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            r#"output_mode = "parallel""#,
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(
+            data.output_mode(),
+            crate::subscription::OutputMode::Parallel
+        );
+
+        let invalid =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, r#"output_mode = "random""#);
+        assert!(parse(&invalid, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compression() -> Result<()> {
+        // SLDC compression is advertised by default
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(
+            data.compression(),
+            crate::subscription::EventsCompression::Sldc
+        );
+
+        let content =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, r#"compression = "none""#);
+        let data = parse(&content, None)?;
+        assert_eq!(
+            data.compression(),
+            crate::subscription::EventsCompression::None
+        );
+
+        let content =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, r#"compression = "sldc""#);
+        let data = parse(&content, None)?;
+        assert_eq!(
+            data.compression(),
+            crate::subscription::EventsCompression::Sldc
+        );
+
+        let invalid =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, r#"compression = "gzip""#);
+        assert!(parse(&invalid, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_concurrent_retries() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.max_concurrent_retries(), None);
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "max_concurrent_retries = 4",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.max_concurrent_retries(), Some(4));
+
+        let invalid = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "max_concurrent_retries = -1",
+        );
+        assert!(parse(&invalid, None).is_err());
+
+        let zero = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "max_concurrent_retries = 0",
+        );
+        assert!(parse(&zero, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_queued() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.max_queued_events(), None);
+        assert_eq!(data.max_queued_bytes(), None);
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "max_queued_events = 10000\nmax_queued_bytes = 67108864",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.max_queued_events(), Some(10000));
+        assert_eq!(data.max_queued_bytes(), Some(67108864));
+
+        for zero in ["max_queued_events = 0", "max_queued_bytes = 0"] {
+            let content = REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, zero);
+            assert!(parse(&content, None).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_concurrent_batches() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.max_concurrent_batches(), None);
+
+        let content =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, "max_concurrent_batches = 8");
+        let data = parse(&content, None)?;
+        assert_eq!(data.max_concurrent_batches(), Some(8));
+
+        let zero =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, "max_concurrent_batches = 0");
+        assert!(parse(&zero, None).is_err());
+        Ok(())
+    }
+
+    fn diff_conf(uuid: &str, name: &str, query: &str) -> String {
+        format!(
+            r#"
+uuid = "{}"
+name = "{}"
+query = "{}"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = {{ path = "/tmp/my.socket" }}
+"#,
+            uuid, name, query
+        )
+    }
+
+    fn diff_set(confs: &[String]) -> Result<Vec<SubscriptionData>> {
+        confs.iter().map(|conf| parse(conf, None)).collect()
+    }
+
+    #[test]
+    fn test_diff_subscriptions() -> Result<()> {
+        const A: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a01";
+        const B: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a02";
+        const C: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a03";
+        const D: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a04";
+        let uuid = |uuid: &str| -> Result<SubscriptionUuid> {
+            Ok(SubscriptionUuid(Uuid::parse_str(uuid)?))
+        };
+
+        let stored = diff_set(&[
+            diff_conf(A, "a", "query a"),
+            diff_conf(B, "b", "query b"),
+            diff_conf(C, "c", "query c"),
+        ])?;
+
+        // Parsing the same files again gives new internal versions, but
+        // nothing changes
+        let loaded = diff_set(&[
+            diff_conf(A, "a", "query a"),
+            diff_conf(B, "b", "query b"),
+            diff_conf(C, "c", "query c"),
+        ])?;
+        assert_ne!(stored[0].internal_version(), loaded[0].internal_version());
+        assert_eq!(
+            diff_subscriptions(&stored, &loaded),
+            SubscriptionsDiff {
+                unchanged: vec![uuid(A)?, uuid(B)?, uuid(C)?],
+                ..Default::default()
+            }
+        );
+
+        // Only the modified subscription is updated
+        let loaded = diff_set(&[
+            diff_conf(A, "a", "query a"),
+            diff_conf(B, "b", "another query b"),
+            diff_conf(C, "c", "query c"),
+        ])?;
+        assert_eq!(
+            diff_subscriptions(&stored, &loaded),
+            SubscriptionsDiff {
+                updated: vec![uuid(B)?],
+                unchanged: vec![uuid(A)?, uuid(C)?],
+                ..Default::default()
+            }
+        );
+
+        // Subscriptions are matched on their uuid
+        let loaded = diff_set(&[
+            diff_conf(A, "a", "query a"),
+            diff_conf(D, "b", "query b"),
+            diff_conf(C, "c", "query c"),
+        ])?;
+        assert_eq!(
+            diff_subscriptions(&stored, &loaded),
+            SubscriptionsDiff {
+                created: vec![uuid(D)?],
+                unchanged: vec![uuid(A)?, uuid(C)?],
+                deleted: vec![uuid(B)?],
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            diff_subscriptions(&[], &stored),
+            SubscriptionsDiff {
+                created: vec![uuid(A)?, uuid(B)?, uuid(C)?],
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_unique_uuids() -> Result<()> {
+        const A: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a01";
+        const B: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a02";
+
+        let loaded = vec![
+            (
+                PathBuf::from("/etc/openwec/a.toml"),
+                parse(&diff_conf(A, "a", "query a"), None)?,
+            ),
+            (
+                PathBuf::from("/etc/openwec/b.toml"),
+                parse(&diff_conf(B, "b", "query b"), None)?,
+            ),
+        ];
+        check_unique_uuids(&loaded)?;
+
+        let mut loaded = loaded;
+        loaded.push((
+            PathBuf::from("/etc/openwec/c.toml"),
+            parse(&diff_conf(A, "c", "query c"), None)?,
+        ));
+        let err = check_unique_uuids(&loaded).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DuplicateUuidError>(),
+            Some(&DuplicateUuidError {
+                uuid: SubscriptionUuid(Uuid::parse_str(A)?),
+                first: PathBuf::from("/etc/openwec/a.toml"),
+                second: PathBuf::from("/etc/openwec/c.toml"),
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "Subscription uuid 7C6A5B5E-1E31-4F0B-9E6F-6B8B6C9A0A01 is declared in both /etc/openwec/a.toml and /etc/openwec/c.toml"
+        );
+        Ok(())
+    }
+
+    const MERGED_TOML_A: &str = r#"
+uuid = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a01"
+name = "security"
+query = """
+<QueryList><Query Id="0"><Select Path="Security">*</Select></Query></QueryList>
+"""
+
+[filter]
+operation = "Only"
+type = "KerberosPrinc"
+flags = "GlobPattern | CaseInsensitive"
+targets = ["dc*@WINDOMAIN.LOCAL"]
+
+[options]
+heartbeat_interval = 32
+read_existing_events = true
+
+[[outputs]]
+driver = "Files"
+format = "Raw"
+config = { path = "/data/logs/{ip}/{principal}/messages" }
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "${OPENWEC_TEST_MERGED_HOST}", port = 5000 }
+"#;
+
+    const MERGED_TOML_B: &str = r#"
+uuid = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a02"
+name = "system"
+query = "<QueryList><Query Id=\"0\"><Select Path=\"System\">*</Select></Query></QueryList>"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+enabled = false
+config = { path = "/tmp/my.socket" }
+"#;
+
+    const MERGED_YAML: &str = r#"
+- uuid: 7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a01
+  name: security
+  query: |
+    <QueryList><Query Id="0"><Select Path="Security">*</Select></Query></QueryList>
+  filter:
+    operation: Only
+    type: KerberosPrinc
+    flags: GlobPattern | CaseInsensitive
+    targets:
+      - dc*@WINDOMAIN.LOCAL
+  options:
+    heartbeat_interval: 32
+    read_existing_events: true
+  outputs:
+    - driver: Files
+      format: Raw
+      config:
+        path: "/data/logs/{ip}/{principal}/messages"
+    - driver: Tcp
+      format: Json
+      config:
+        host: "${OPENWEC_TEST_MERGED_HOST}"
+        port: 5000
+- uuid: 7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a02
+  name: system
+  query: '<QueryList><Query Id="0"><Select Path="System">*</Select></Query></QueryList>'
+  outputs:
+    - driver: UnixDatagram
+      format: Json
+      enabled: false
+      config: { path: /tmp/my.socket }
+"#;
+
+    const MERGED_JSON: &str = r#"[
+  {
+    "uuid": "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a01",
+    "name": "security",
+    "query": "<QueryList><Query Id=\"0\"><Select Path=\"Security\">*</Select></Query></QueryList>\n",
+    "filter": {
+      "operation": "Only",
+      "type": "KerberosPrinc",
+      "flags": "GlobPattern | CaseInsensitive",
+      "targets": ["dc*@WINDOMAIN.LOCAL"]
+    },
+    "options": { "heartbeat_interval": 32, "read_existing_events": true },
+    "outputs": [
+      {
+        "driver": "Files",
+        "format": "Raw",
+        "config": { "path": "/data/logs/{ip}/{principal}/messages" }
+      },
+      {
+        "driver": "Tcp",
+        "format": "Json",
+        "config": { "host": "${OPENWEC_TEST_MERGED_HOST}", "port": 5000 }
+      }
+    ]
+  },
+  {
+    "uuid": "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a02",
+    "name": "system",
+    "query": "<QueryList><Query Id=\"0\"><Select Path=\"System\">*</Select></Query></QueryList>",
+    "outputs": [
+      {
+        "driver": "UnixDatagram",
+        "format": "Json",
+        "enabled": false,
+        "config": { "path": "/tmp/my.socket" }
+      }
+    ]
+  }
+]"#;
+
+    #[test]
+    fn test_parse_all() -> Result<()> {
+        std::env::set_var("OPENWEC_TEST_MERGED_HOST", "logs.windomain.local");
+        let revision = "1.2".to_string();
+
+        let expected = vec![
+            parse(MERGED_TOML_A, Some(&revision))?,
+            parse(MERGED_TOML_B, Some(&revision))?,
+        ];
+        assert_eq!(
+            parse_all(MERGED_TOML_A, ConfigFormat::Toml, Some(&revision), true)?.len(),
+            1
+        );
+        for (content, format) in [
+            (MERGED_YAML, ConfigFormat::Yaml),
+            (MERGED_JSON, ConfigFormat::Json),
+        ] {
+            let mut parsed = parse_all(content, format, Some(&revision), true)?;
+            assert_eq!(parsed.len(), expected.len());
+            for (data, expected) in parsed.iter_mut().zip(expected.iter()) {
+                // Each parsing gives a new internal version
+                data.set_internal_version(expected.internal_version());
+                assert_eq!(data, expected);
+            }
+        }
+
+        // Subscriptions of a document must have distinct uuids
+        let duplicated = MERGED_YAML.replace("6b8b6c9a0a02", "6b8b6c9a0a01");
+        let err = parse_all(&duplicated, ConfigFormat::Yaml, None, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Subscriptions 0 and 1 have the same uuid 7C6A5B5E-1E31-4F0B-9E6F-6B8B6C9A0A01"
+        );
+
+        // Errors point to the invalid subscription
+        let invalid = MERGED_JSON.replace(r#""name": "system""#, r#""babar": "system""#);
+        let err = parse_all(&invalid, ConfigFormat::Json, None, true).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Subscription 1: "));
+
+        // A document must be an array of subscriptions
+        assert!(parse_all(MERGED_TOML_A, ConfigFormat::Yaml, None, true).is_err());
+        assert!(parse_all(r#"{"uuid": "x"}"#, ConfigFormat::Json, None, true).is_err());
+        assert!(parse_all(MERGED_YAML, ConfigFormat::Toml, None, true).is_err());
+        assert!(parse_all("[]", ConfigFormat::Json, None, true)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_format() -> Result<()> {
+        for (path, format) in [
+            ("/etc/openwec/a.toml", ConfigFormat::Toml),
+            ("/etc/openwec/a", ConfigFormat::Toml),
+            ("/etc/openwec/a.conf", ConfigFormat::Toml),
+            ("/etc/openwec/a.json", ConfigFormat::Json),
+            ("/etc/openwec/a.yaml", ConfigFormat::Yaml),
+            ("/etc/openwec/a.YML", ConfigFormat::Yaml),
+        ] {
+            assert_eq!(ConfigFormat::from_path(Path::new(path)), format);
+        }
+        assert_eq!(ConfigFormat::from_str("yaml")?, ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_str("JSON")?, ConfigFormat::Json);
+        assert!(ConfigFormat::from_str("xml").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_concurrent_clients() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.max_concurrent_clients(), None);
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "max_concurrent_clients = 500",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.max_concurrent_clients(), Some(500));
+
+        let zero =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, "max_concurrent_clients = 0");
+        assert!(parse(&zero, None).is_err());
+
+        let negative = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "max_concurrent_clients = -1",
+        );
+        assert!(parse(&negative, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_envelope_size() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(
+            data.max_envelope_size(),
+            crate::subscription::DEFAULT_MAX_ENVELOPE_SIZE
+        );
+
+        for (value, expected) in [("512000", 512_000), ("153600", 153_600), ("8192", 8192)] {
+            let content = REQUIRE_AUTH_CONF.replace(
+                r#"require_auth = "tls_cert""#,
+                &format!("max_envelope_size = {}", value),
+            );
+            assert_eq!(parse(&content, None)?.max_envelope_size(), expected);
+        }
+
+        for invalid in ["0", "14", "8191", "-1"] {
+            let content = REQUIRE_AUTH_CONF.replace(
+                r#"require_auth = "tls_cert""#,
+                &format!("max_envelope_size = {}", invalid),
+            );
+            assert!(parse(&content, None).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_window() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.dedup_window(), None);
+        assert_eq!(data.dedup_window_ttl(), None);
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "dedup_window = 100000\ndedup_window_ttl = 3600",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.dedup_window(), Some(100000));
+        assert_eq!(data.dedup_window_ttl(), Some(3600));
+
+        let content =
+            REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, "dedup_window = 10");
+        let data = parse(&content, None)?;
+        assert_eq!(data.dedup_window(), Some(10));
+        assert_eq!(data.dedup_window_ttl(), None);
+
+        for invalid in [
+            "dedup_window = 0",
+            "dedup_window = -1",
+            "dedup_window = 10\ndedup_window_ttl = 0",
+            // The TTL alone does not enable deduplication
+            "dedup_window_ttl = 3600",
+        ] {
+            let content = REQUIRE_AUTH_CONF.replace(r#"require_auth = "tls_cert""#, invalid);
+            assert!(parse(&content, None).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_retry_jitter() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.connection_retry_jitter(), None);
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "connection_retry_interval = 60\nconnection_retry_jitter = 20",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.connection_retry_interval(), 60);
+        assert_eq!(data.connection_retry_jitter(), Some(20));
+
+        for invalid in ["0", "101", "-1", "12.5"] {
+            let content = REQUIRE_AUTH_CONF.replace(
+                r#"require_auth = "tls_cert""#,
+                &format!("connection_retry_jitter = {}", invalid),
+            );
+            assert!(parse(&content, None).is_err());
+        }
+        Ok(())
+    }
+
+    const ROUTE_BY_PROVIDER_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "route_by_provider"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+name = "sysmon"
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/sysmon.socket" }
+
+[[outputs]]
+name = "others"
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/others.socket" }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+summary = true
+config = { path = "/tmp/summary.socket" }
+
+[route_by_provider]
+"Microsoft-Windows-Sysmon" = "sysmon"
+"Microsoft-Windows-Security-*" = ["sysmon"]
+    "#;
+
+    #[test]
+    fn test_route_by_provider() -> Result<()> {
+        let data = parse(ROUTE_BY_PROVIDER_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].provider_route(),
+            &ProviderRoute::Matching(vec![
+                "Microsoft-Windows-Security-*".to_string(),
+                "Microsoft-Windows-Sysmon".to_string()
+            ])
+        );
+        // Unreferenced outputs are the default bucket
+        assert_eq!(
+            data.outputs()[1].provider_route(),
+            &ProviderRoute::Unmatched(vec![
+                "Microsoft-Windows-Security-*".to_string(),
+                "Microsoft-Windows-Sysmon".to_string()
+            ])
+        );
+        assert_eq!(data.outputs()[2].provider_route(), &ProviderRoute::All);
+
+        // Without route_by_provider, outputs receive all events
+        let data = parse(SAMPLING_CONF, None)?;
+        assert_eq!(data.outputs()[0].provider_route(), &ProviderRoute::All);
+
+        let unknown = ROUTE_BY_PROVIDER_CONF.replace(
+            r#""Microsoft-Windows-Sysmon" = "sysmon""#,
+            r#""Microsoft-Windows-Sysmon" = "unknown""#,
+        );
+        assert!(parse(&unknown, None).is_err());
+
+        let invalid_pattern =
+            ROUTE_BY_PROVIDER_CONF.replace("Microsoft-Windows-Security-*", "Microsoft-[");
+        assert!(parse(&invalid_pattern, None).is_err());
+
+        let duplicated_name =
+            ROUTE_BY_PROVIDER_CONF.replace(r#"name = "others""#, r#"name = "sysmon""#);
+        assert!(parse(&duplicated_name, None).is_err());
+
+        let routed_summary = ROUTE_BY_PROVIDER_CONF
+            .replace("summary = true", "summary = true\nname = \"summary\"")
+            + "\"Other\" = \"summary\"\n";
+        assert!(parse(&routed_summary, None).is_err());
+        Ok(())
+    }
+
+    const DEGRADATION_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "degradation"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+degradation = { send_timeout_ms = 500, slow_responses = 5, cooldown_secs = 30 }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+degradation = { send_timeout_ms = 1000 }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+    "#;
+
+    #[test]
+    fn test_degradation() -> Result<()> {
+        let data = parse(DEGRADATION_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].degradation(),
+            Some(&crate::subscription::OutputDegradation::try_new(
+                500, 5, 30
+            )?)
+        );
+        assert_eq!(
+            data.outputs()[1].degradation(),
+            Some(&crate::subscription::OutputDegradation::try_new(
+                1000,
+                DEFAULT_DEGRADATION_SLOW_RESPONSES,
+                DEFAULT_DEGRADATION_COOLDOWN_SECS
+            )?)
+        );
+        assert_eq!(data.outputs()[2].degradation(), None);
+
+        let zero = DEGRADATION_CONF.replace("slow_responses = 5", "slow_responses = 0");
+        assert!(parse(&zero, None).is_err());
+
+        let unknown = DEGRADATION_CONF.replace("cooldown_secs = 30", "cooldown = 30");
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    const DEDUPE_ON_CHANGE_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "dedupe_on_change"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/changes.socket" }
+dedupe_on_change = { fields = ["System.EventID", "EventData.Value"], max_sources = 500 }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Raw"
+config = { path = "/tmp/changes.socket" }
+dedupe_on_change = { fields = ["EventData.Value"] }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+    "#;
+
+    #[test]
+    fn test_dedupe_on_change() -> Result<()> {
+        let data = parse(DEDUPE_ON_CHANGE_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].dedupe_on_change(),
+            Some(&crate::subscription::OutputDedupeOnChange::try_new(
+                vec!["System.EventID".to_string(), "EventData.Value".to_string()],
+                500
+            )?)
+        );
+        assert_eq!(
+            data.outputs()[1].dedupe_on_change(),
+            Some(&crate::subscription::OutputDedupeOnChange::try_new(
+                vec!["EventData.Value".to_string()],
+                DEFAULT_DEDUPE_MAX_SOURCES
+            )?)
+        );
+        assert_eq!(data.outputs()[2].dedupe_on_change(), None);
+
+        let no_fields =
+            DEDUPE_ON_CHANGE_CONF.replace(r#"fields = ["EventData.Value"]"#, "fields = []");
+        assert!(parse(&no_fields, None).is_err());
+
+        let zero = DEDUPE_ON_CHANGE_CONF.replace("max_sources = 500", "max_sources = 0");
+        assert!(parse(&zero, None).is_err());
+
+        let summary = DEDUPE_ON_CHANGE_CONF
+            .replace("max_sources = 500 }", "max_sources = 500 }\nsummary = true");
+        assert!(parse(&summary, None).is_err());
+        Ok(())
+    }
+
+    const PAYLOAD_COMPRESSION_CONF: &str = r#"
+uuid = "5d5e2a3c-7d63-4bd4-bd1a-2bd1b0c4f7a1"
+name = "payload_compression"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "127.0.0.1", port = 5000, framing = "length_prefixed" }
+payload_compression = "zstd"
+
+[[outputs]]
+driver = "Kafka"
+format = "Json"
+config = { topic = "events", options = { "bootstrap.servers" = "localhost:9092" } }
+payload_compression = "gzip"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = { path = "/tmp/events.socket" }
+payload_compression = "none"
+
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "127.0.0.1", port = 5001 }
+    "#;
+
+    #[test]
+    fn test_payload_compression() -> Result<()> {
+        let data = parse(PAYLOAD_COMPRESSION_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].payload_compression(),
+            crate::subscription::PayloadCompression::Zstd
+        );
+        assert_eq!(
+            data.outputs()[1].payload_compression(),
+            crate::subscription::PayloadCompression::Gzip
+        );
+        assert_eq!(
+            data.outputs()[2].payload_compression(),
+            crate::subscription::PayloadCompression::None
+        );
+        assert_eq!(
+            data.outputs()[3].payload_compression(),
+            crate::subscription::PayloadCompression::None
+        );
+
+        let lz4 = PAYLOAD_COMPRESSION_CONF.replace(r#""zstd""#, r#""lz4""#);
+        assert_eq!(
+            parse(&lz4, None)?.outputs()[0].payload_compression(),
+            crate::subscription::PayloadCompression::Lz4
+        );
+
+        let unknown = PAYLOAD_COMPRESSION_CONF.replace(r#""zstd""#, r#""brotli""#);
+        assert!(parse(&unknown, None).is_err());
+
+        // The driver must be able to tell consumers how payloads are compressed
+        let newline = PAYLOAD_COMPRESSION_CONF.replace(r#", framing = "length_prefixed""#, "");
+        assert!(parse(&newline, None).is_err());
+
+        let unix = PAYLOAD_COMPRESSION_CONF.replace(r#""none""#, r#""gzip""#);
+        assert!(parse(&unix, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trim_rendering() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert!(data.trim_rendering().is_empty());
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            r#"trim_rendering = ["message", "keywords", "message"]"#,
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(
+            data.trim_rendering(),
+            &BTreeSet::from([
+                crate::subscription::RenderingElement::Message,
+                crate::subscription::RenderingElement::Keywords,
+            ])
+        );
+
+        let invalid = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            r#"trim_rendering = ["culture"]"#,
+        );
+        assert!(parse(&invalid, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_enrollment_fallback() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert!(data.enrollment_fallback().is_empty());
+
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            r#"enrollment_fallback = ["locale", "data_locale", "read_existing_events"]"#,
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(
+            data.enrollment_fallback(),
+            &BTreeSet::from([
+                crate::subscription::ManifestOption::Locale,
+                crate::subscription::ManifestOption::DataLocale,
+                crate::subscription::ManifestOption::ReadExistingEvents,
+            ])
+        );
+
+        let invalid = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            r#"enrollment_fallback = ["query"]"#,
+        );
+        assert!(parse(&invalid, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_locale() -> Result<()> {
+        let content = REQUIRE_AUTH_CONF.replace(
+            r#"require_auth = "tls_cert""#,
+            "content_format = \"RenderedText\"\nlocale = \"fr-FR\"\ndata_locale = \"fr-FR\"",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.locale(), Some(&"fr-FR".to_string()));
+        assert_eq!(data.data_locale(), Some(&"fr-FR".to_string()));
+
+        for option in ["locale", "data_locale"] {
+            let invalid = REQUIRE_AUTH_CONF.replace(
+                r#"require_auth = "tls_cert""#,
+                &format!("{} = \"fr_FR\"", option),
+            );
+            assert!(parse(&invalid, None).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tenant() -> Result<()> {
+        let data = parse(MINIMAL_CONTENT, None)?;
+        assert_eq!(data.tenant(), None);
+
+        let content = MINIMAL_CONTENT.replace(
+            r#"name = "minimal""#,
+            "name = \"minimal\"\ntenant = \"team-a\"",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.tenant(), Some(&"team-a".to_string()));
+        assert_eq!(data.name(), "minimal");
+        assert_eq!(data.qualified_name(), "team-a/minimal");
+
+        let invalid = MINIMAL_CONTENT.replace(
+            r#"name = "minimal""#,
+            "name = \"minimal\"\ntenant = \"team/a\"",
+        );
+        assert!(parse(&invalid, None).is_err());
+        Ok(())
+    }
+
+    const MAX_QUEUE_AGE_CONF: &str = r#"
+uuid = "0c6f3a4e-2b8d-4f6a-9d3e-5a7b1c2d3e4f"
+name = "max_queue_age"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+name = "alerting"
+driver = "Tcp"
+format = "Json"
+config = { host = "127.0.0.1", port = 5000 }
+max_queue_age = 300
+
+[[outputs]]
+name = "archive"
+driver = "Files"
+format = "Raw"
+config = { path = "/tmp/events" }
+    "#;
+
+    #[test]
+    fn test_max_queue_age() -> Result<()> {
+        let data = parse(MAX_QUEUE_AGE_CONF, None)?;
+        assert_eq!(data.outputs()[0].max_queue_age(), Some(300));
+        assert_eq!(data.outputs()[1].max_queue_age(), None);
+
+        let zero = MAX_QUEUE_AGE_CONF.replace("max_queue_age = 300", "max_queue_age = 0");
+        assert!(parse(&zero, None).is_err());
+
+        let summary = MAX_QUEUE_AGE_CONF
+            .replace("max_queue_age = 300", "max_queue_age = 300\nsummary = true");
+        assert!(parse(&summary, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_timeout() -> Result<()> {
+        let data = parse(MAX_QUEUE_AGE_CONF, None)?;
+        assert_eq!(data.outputs()[0].write_timeout_ms(), None);
+
+        let content = MAX_QUEUE_AGE_CONF.replace(
+            "max_queue_age = 300",
+            "max_queue_age = 300\nwrite_timeout_ms = 2000",
+        );
+        let data = parse(&content, None)?;
+        assert_eq!(data.outputs()[0].write_timeout_ms(), Some(2000));
+        assert_eq!(data.outputs()[1].write_timeout_ms(), None);
+
+        let zero = MAX_QUEUE_AGE_CONF.replace(
+            "max_queue_age = 300",
+            "max_queue_age = 300\nwrite_timeout_ms = 0",
+        );
+        assert!(parse(&zero, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_collector_info() -> Result<()> {
+        let data = parse(MAX_QUEUE_AGE_CONF, None)?;
+        // Disabled by default
+        assert!(!data.outputs()[0].include_collector_info());
+
+        let content = MAX_QUEUE_AGE_CONF.replace(
+            "max_queue_age = 300",
+            "max_queue_age = 300\ninclude_collector_info = true",
+        );
+        let data = parse(&content, None)?;
+        assert!(data.outputs()[0].include_collector_info());
+        assert!(!data.outputs()[1].include_collector_info());
+
+        // Only formats producing Json objects are supported
+        let raw = MAX_QUEUE_AGE_CONF.replace(
+            "config = { path = \"/tmp/events\" }",
+            "config = { path = \"/tmp/events\" }\ninclude_collector_info = true",
+        );
+        assert!(parse(&raw, None).is_err());
+        Ok(())
+    }
+
+    fn check_conf(uuid: &str, name: &str) -> String {
+        format!(
+            r#"
+uuid = "{}"
+name = "{}"
+query = "<QueryList></QueryList>"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = {{ path = "/tmp/my.socket" }}
+"#,
+            uuid, name
+        )
+    }
 
-    use crate::subscription::InternalVersion;
+    #[test]
+    fn test_check_config_files() -> Result<()> {
+        const A: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a01";
+        const B: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a02";
+        const C: &str = "7c6a5b5e-1e31-4f0b-9e6f-6b8b6c9a0a03";
+
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.toml"), check_conf(A, "a"))?;
+        fs::write(dir.join("b.toml"), check_conf(B, "a"))?;
+        fs::write(dir.join("c.toml"), check_conf(C, "c"))?;
+
+        // Subscription names must be unique
+        let reports = check_config_files(&dir, None, true, |_| Ok(()))?;
+        let errors: Vec<usize> = reports.iter().map(|report| report.errors().len()).collect();
+        assert_eq!(errors, vec![0, 1, 0]);
+        assert!(reports[1].errors()[0].starts_with("Subscription name \"a\" is also used in"));
+
+        // Subscriptions may also be rejected by the caller
+        let reports = check_config_files(&dir, None, true, |data| {
+            if data.name() == "c" {
+                bail!("Invalid subscription");
+            }
+            Ok(())
+        })?;
+        assert_eq!(reports[2].errors(), ["Invalid subscription"]);
 
-    use super::*;
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 
-    const FULL_CONTENT: &str = r#"
-uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3275778"
-name = "my-subscription"
+    const FORMAT_OPTIONS_CONF: &str = r#"
+uuid = "5e2b7c1d-8f3a-4d6e-9b0c-2a4f6e8d1c3b"
+name = "format_options"
 
 query = """
-a wonderful query
+a very small query
 """
 
-[options]
-enabled = true
-uri = "toto"
-heartbeat_interval = 32
-connection_retry_count = 11
-connection_retry_interval = 12
-max_time = 13
-max_elements = 15
-max_envelope_size = 14
-read_existing_events = false
-content_format = "Raw" # or RenderedText
-ignore_channel_error = true
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "127.0.0.1", port = 5000 }
 
-[filter]
-operation = "Only" # or Except
-princs = ["toto@windomain.local", "tutu@windomain.local"]
+[[outputs]]
+driver = "Kafka"
+format = "Json"
+config = { topic = "my-kafka-topic", options = { "bootstrap.servers" = "localhost:9092" } }
+
+[outputs.format_options]
+pretty = true
+timestamp_field = "@timestamp"
+include_raw_xml = true
+    "#;
+
+    #[test]
+    fn test_format_options() -> Result<()> {
+        let data = parse(FORMAT_OPTIONS_CONF, None)?;
+
+        // The bare form uses the default options
+        assert_eq!(
+            *data.outputs()[0].format(),
+            crate::subscription::SubscriptionOutputFormat::Json
+        );
+        assert_eq!(
+            *data.outputs()[0].format_options(),
+            crate::subscription::FormatOptions::default()
+        );
+
+        assert_eq!(
+            *data.outputs()[1].format(),
+            crate::subscription::SubscriptionOutputFormat::Json
+        );
+        assert_eq!(
+            *data.outputs()[1].format_options(),
+            crate::subscription::FormatOptions::try_new(
+                true,
+                Some("@timestamp".to_string()),
+                true
+            )?
+        );
+
+        // Options can be partially set
+        let content = FORMAT_OPTIONS_CONF.replace("pretty = true\n", "");
+        let data = parse(&content, None)?;
+        assert!(!data.outputs()[1].format_options().pretty());
+        assert!(data.outputs()[1].format_options().include_raw_xml());
+
+        let unknown = FORMAT_OPTIONS_CONF.replace("pretty = true", "indent = 4");
+        assert!(parse(&unknown, None).is_err());
+
+        let invalid_field = FORMAT_OPTIONS_CONF.replace("\"@timestamp\"", "\"event..time\"");
+        assert!(parse(&invalid_field, None).is_err());
+
+        // Only the Json format supports options
+        let raw = FORMAT_OPTIONS_CONF.replacen("format = \"Json\"", "format = \"Raw\"", 2);
+        assert!(parse(&raw, None).is_err());
+
+        // Pretty-printed events span several lines
+        let newline = FORMAT_OPTIONS_CONF.replace(
+            "config = { host = \"127.0.0.1\", port = 5000 }",
+            "config = { host = \"127.0.0.1\", port = 5000 }\nformat_options = { pretty = true }",
+        );
+        assert!(parse(&newline, None).is_err());
+        let newline = FORMAT_OPTIONS_CONF.replace(
+            "config = { host = \"127.0.0.1\", port = 5000 }",
+            "config = { host = \"127.0.0.1\", port = 5000 }\nformat_options = { include_raw_xml = true }",
+        );
+        assert!(parse(&newline, None).is_ok());
+        Ok(())
+    }
+
+    const COMPAT_CONF: &str = r#"
+uuid = "9a3c1e5f-4b2d-4e6a-8c7f-1d2e3f4a5b6c"
+name = "compat"
+
+query = """
+a very small query
+"""
 
-## Files output with old config format
 [[outputs]]
-driver = "Files"
-format = "Json" # or "Raw"
-enabled = true
+driver = "Tcp"
+format = "Raw"
+config = { host = "127.0.0.1", port = 5000 }
 
-[outputs.config]
-base = "/tmp/"
-split_on_addr_index = 2
-append_node_name = true
-filename = "courgette"
+[[outputs]]
+driver = "Kafka"
+format = "Csv"
+format_config = { columns = ["System.EventID"], header = true }
+config = { topic = "events", options = { "bootstrap.servers" = "localhost:9092" } }
+    "#;
+
+    const GETTING_STARTED_CONF: &str = r#"
+uuid = "8e1d2c3b-4a5f-4e6d-9c7b-0a1f2e3d4c5b"
+name = "getting_started"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Files"
+format = "Raw"
+config = { path = "/data/logs/{ip}/{principal}/messages" }
 
-## Kafka output
 [[outputs]]
 driver = "Kafka"
+format = "RawJson"
+config = { topic = "my-kafka-topic", options = { "bootstrap.servers" = "localhost:9092" } }
+    "#;
+
+    #[test]
+    fn test_strict_compat() -> Result<()> {
+        let err = parse(COMPAT_CONF, None).unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("Output 0: Format raw can not be used with newline framing"));
+
+        // The escape hatch keeps incompatible outputs
+        let data = parse_with_compat(COMPAT_CONF, None, false)?;
+        assert_eq!(data.outputs().len(), 2);
+        let issues = compatibility_issues(&data);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[1].starts_with("Output 1: The header of format csv"));
+
+        // Raw events can be delimited by their length
+        let framed = COMPAT_CONF
+            .replace(
+                "port = 5000 }",
+                "port = 5000, framing = \"length_prefixed\" }",
+            )
+            .replace("header = true", "header = false");
+        assert!(compatibility_issues(&parse(&framed, None)?).is_empty());
+
+        let data = parse(GETTING_STARTED_CONF, None)?;
+        assert!(compatibility_issues(&data).is_empty());
+        Ok(())
+    }
+
+    const SYSLOG_TLS_CONF: &str = r#"
+uuid = "3f6d2b1a-9c8e-4d7f-a6b5-c4d3e2f1a0b9"
+name = "syslog_tls"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "SyslogTls"
+format = "Rfc5424"
+config = { host = "syslog.windomain.local", tls_certificate_authorities = "/etc/ca.pem", tls_certificate = "/etc/client.pem", tls_key = "/etc/client.key" }
+
+[[outputs]]
+driver = "SyslogTls"
+format = "Rfc5424"
+config = { host = "syslog.windomain.local", port = 10514, tls_certificate_authorities = ["/etc/ca.pem", "/etc/other-ca.pem"], tls_certificate = "/etc/client.pem", tls_key = "/etc/client.key" }
+    "#;
+
+    #[test]
+    fn test_syslog_tls() -> Result<()> {
+        let data = parse(SYSLOG_TLS_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::SyslogTls(
+                crate::subscription::SyslogTlsConfiguration::new(
+                    "syslog.windomain.local".to_string(),
+                    6514,
+                    vec!["/etc/ca.pem".to_string()],
+                    "/etc/client.pem".to_string(),
+                    "/etc/client.key".to_string(),
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::SyslogTls(config) => {
+                assert_eq!(config.port(), 10514);
+                assert_eq!(config.tls_certificate_authorities().len(), 2);
+                let tcp = config.tcp_configuration()?;
+                assert!(tcp.tls_enabled());
+                assert_eq!(
+                    tcp.framing(),
+                    crate::subscription::TcpFraming::OctetCounting
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        // Mutual authentication is required
+        let no_key = SYSLOG_TLS_CONF.replacen(", tls_key = \"/etc/client.key\"", "", 1);
+        assert!(parse(&no_key, None).is_err());
+
+        // The server certificate is verified using its hostname
+        let ip = SYSLOG_TLS_CONF.replacen("syslog.windomain.local", "192.168.58.10", 1);
+        assert!(parse(&ip, None).is_err());
+
+        // Framing is always octet counting
+        let framing = SYSLOG_TLS_CONF.replacen(
+            "tls_key = \"/etc/client.key\" }",
+            "tls_key = \"/etc/client.key\", framing = \"newline\" }",
+            1,
+        );
+        assert!(parse(&framing, None).is_err());
+
+        // Relays expect syslog messages
+        let json = SYSLOG_TLS_CONF.replacen("format = \"Rfc5424\"", "format = \"Json\"", 1);
+        assert!(parse(&json, None).is_err());
+        assert!(parse_with_compat(&json, None, false).is_ok());
+        Ok(())
+    }
+
+    const HTTP_CONF: &str = r#"
+uuid = "5c1e7a2b-3d4f-4a6b-8c9d-0e1f2a3b4c5d"
+name = "http"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Http"
+format = "Json"
+config = { url = "http://collector.windomain.local:8080/events" }
+
+[[outputs]]
+driver = "Http"
+format = "Json"
+config = { url = "https://collector.windomain.local/events", headers = { "Authorization" = "Bearer secret", "X-Source" = "openwec" }, batch_size = 500, batch_timeout_ms = 200, idle_flush_ms = 50, tls_certificate_authorities = "/etc/ca.pem", framing = "json_array" }
+    "#;
+
+    #[test]
+    fn test_http() -> Result<()> {
+        let data = parse(HTTP_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Http(
+                crate::subscription::HttpConfiguration::new(
+                    "http://collector.windomain.local:8080/events".to_string(),
+                    BTreeMap::new(),
+                    100,
+                    1000,
+                    Vec::new(),
+                    crate::subscription::HttpFraming::Newline,
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Http(config) => {
+                assert!(config.tls_enabled());
+                assert_eq!(config.headers().len(), 2);
+                assert_eq!(
+                    config.headers().get("Authorization"),
+                    Some(&"Bearer secret".to_string())
+                );
+                assert_eq!(config.batch_size(), 500);
+                assert_eq!(config.batch_timeout_ms(), 200);
+                assert_eq!(config.idle_flush_ms(), Some(50));
+                assert_eq!(
+                    config.tls_certificate_authorities(),
+                    &["/etc/ca.pem".to_string()]
+                );
+                assert_eq!(
+                    config.framing(),
+                    crate::subscription::HttpFraming::JsonArray
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        // Certificate authorities are required to verify the server
+        let no_ca = HTTP_CONF.replacen(", tls_certificate_authorities = \"/etc/ca.pem\"", "", 1);
+        assert!(parse(&no_ca, None).is_err());
+
+        let ftp = HTTP_CONF.replacen("http://", "ftp://", 1);
+        assert!(parse(&ftp, None).is_err());
+
+        let empty_batch = HTTP_CONF.replacen("batch_size = 500", "batch_size = 0", 1);
+        assert!(parse(&empty_batch, None).is_err());
+
+        // Batches must be sent after an idle time shorter than their timeout
+        let no_idle = HTTP_CONF.replacen("idle_flush_ms = 50", "idle_flush_ms = 0", 1);
+        assert!(parse(&no_idle, None).is_err());
+        let long_idle = HTTP_CONF.replacen("idle_flush_ms = 50", "idle_flush_ms = 200", 1);
+        assert!(parse(&long_idle, None).is_err());
+
+        let unknown = HTTP_CONF.replacen("/events\" }", "/events\", method = \"PUT\" }", 1);
+        assert!(parse(&unknown, None).is_err());
+
+        // A JSON array can only contain JSON documents
+        let raw = HTTP_CONF.replace("format = \"Json\"", "format = \"Raw\"");
+        let err = parse(&raw, None).unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("Output 1: Format raw can not be used with json_array framing"));
+        assert!(parse_with_compat(&raw, None, false).is_ok());
+        Ok(())
+    }
+
+    const SYSLOG_CONF: &str = r#"
+uuid = "7d2e4f6a-1b3c-4d5e-8f9a-0b1c2d3e4f5a"
+name = "syslog"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Syslog"
+format = "Json"
+config = { host = "192.168.58.20" }
+
+[[outputs]]
+driver = "Syslog"
+format = "Json"
+config = { host = "syslog.windomain.local", transport = "tls", facility = "local4", app_name = "wec", tls_certificate_authorities = "/etc/ca.pem", tls_certificate = "/etc/client.pem", tls_key = "/etc/client.key" }
+    "#;
+
+    #[test]
+    fn test_syslog() -> Result<()> {
+        let data = parse(SYSLOG_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Syslog(
+                crate::subscription::SyslogConfiguration::new(
+                    "192.168.58.20".to_string(),
+                    514,
+                    crate::subscription::SyslogTransport::Udp,
+                    crate::subscription::SyslogFacility::User,
+                    "openwec".to_string(),
+                    Vec::new(),
+                    None,
+                    None,
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Syslog(config) => {
+                assert_eq!(config.port(), 6514);
+                assert_eq!(
+                    config.transport(),
+                    crate::subscription::SyslogTransport::Tls
+                );
+                assert_eq!(config.facility().code(), 20);
+                assert_eq!(config.app_name(), "wec");
+                let tcp = config.tcp_configuration()?;
+                assert!(tcp.tls_enabled());
+                assert_eq!(tcp.tls_certificate(), Some(&"/etc/client.pem".to_string()));
+                assert_eq!(
+                    tcp.framing(),
+                    crate::subscription::TcpFraming::OctetCounting
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        let tcp = SYSLOG_CONF.replacen(
+            "config = { host = \"192.168.58.20\" }",
+            "config = { host = \"192.168.58.20\", port = 601, transport = \"tcp\" }",
+            1,
+        );
+        match parse(&tcp, None)?.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::Syslog(config) => {
+                assert_eq!(config.port(), 601);
+                assert!(!config.tcp_configuration()?.tls_enabled());
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        let facility = SYSLOG_CONF.replacen("local4", "local8", 1);
+        let err = parse(&facility, None).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid syslog facility \"local8\""));
+
+        let app_name = SYSLOG_CONF.replacen("app_name = \"wec\"", "app_name = \"open wec\"", 1);
+        assert!(parse(&app_name, None).is_err());
+
+        // TLS settings require the tls transport
+        let udp_tls = SYSLOG_CONF.replacen("transport = \"tls\"", "transport = \"udp\"", 1);
+        assert!(parse(&udp_tls, None).is_err());
+
+        // The server certificate is verified using its hostname
+        let tls_ip = SYSLOG_CONF.replacen("syslog.windomain.local", "192.168.58.20", 1);
+        assert!(parse(&tls_ip, None).is_err());
+
+        // Events would have two syslog headers
+        let rfc5424 = SYSLOG_CONF.replacen("format = \"Json\"", "format = \"Rfc5424\"", 1);
+        assert!(parse(&rfc5424, None).is_err());
+        assert!(parse_with_compat(&rfc5424, None, false).is_ok());
+        Ok(())
+    }
+
+    const FILES_ROTATION_CONF: &str = r#"
+uuid = "4c8a1f3e-9d2b-4e7a-b6c5-1f0e2d3c4b5a"
+name = "files_rotation"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Files"
 format = "Raw"
-enabled = false
+config = { path = "/var/events/{ip}/messages", rotation = { max_size_bytes = 104857600, max_age_secs = 86400 } }
 
-[outputs.config]
-topic = "my-topic"
+[[outputs]]
+driver = "Files"
+format = "Raw"
+config = { path = "/var/events/{ip}/other" }
+    "#;
 
-[outputs.config.options]
-"bootstrap.server" = "localhost:9092"
+    #[test]
+    fn test_files_rotation() -> Result<()> {
+        let data = parse(FILES_ROTATION_CONF, None)?;
+        match data.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::Files(config) => {
+                let rotation = config.rotation().expect("rotation is set");
+                assert_eq!(rotation.max_size_bytes(), Some(104857600));
+                assert_eq!(rotation.max_age_secs(), Some(86400));
+            }
+            _ => panic!("Wrong driver"),
+        }
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Files(config) => {
+                assert!(config.rotation().is_none());
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        let size_only = FILES_ROTATION_CONF.replacen(", max_age_secs = 86400", "", 1);
+        assert!(parse(&size_only, None).is_ok());
+
+        let empty = FILES_ROTATION_CONF.replacen(
+            "{ max_size_bytes = 104857600, max_age_secs = 86400 }",
+            "{}",
+            1,
+        );
+        assert!(parse(&empty, None).is_err());
+
+        let zero = FILES_ROTATION_CONF.replacen("max_age_secs = 86400", "max_age_secs = 0", 1);
+        assert!(parse(&zero, None).is_err());
+
+        let unknown = FILES_ROTATION_CONF.replacen("max_age_secs", "max_files", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_compression() -> Result<()> {
+        let compressions = |conf: &str| -> Result<Vec<crate::subscription::FilesCompression>> {
+            Ok(parse(conf, None)?
+                .outputs()
+                .iter()
+                .map(|output| match output.driver() {
+                    crate::subscription::SubscriptionOutputDriver::Files(config) => {
+                        config.compression()
+                    }
+                    _ => panic!("Wrong driver"),
+                })
+                .collect())
+        };
+        assert_eq!(
+            compressions(FILES_ROTATION_CONF)?,
+            vec![
+                crate::subscription::FilesCompression::None,
+                crate::subscription::FilesCompression::None
+            ]
+        );
+
+        let gzip = FILES_ROTATION_CONF.replacen(
+            "\"/var/events/{ip}/other\"",
+            "\"/var/events/{ip}/other\", compression = \"gzip\"",
+            1,
+        );
+        assert_eq!(
+            compressions(&gzip)?,
+            vec![
+                crate::subscription::FilesCompression::None,
+                crate::subscription::FilesCompression::Gzip
+            ]
+        );
+
+        let unknown = gzip.replacen("gzip", "zstd", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    const UNIX_STREAM_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "unix_stream"
+
+query = """
+a very small query
+"""
 
-## Tcp output
 [[outputs]]
-driver = "Tcp"
+driver = "UnixStream"
+format = "Json"
+config = { path = "/run/openwec/events.socket" }
+
+[[outputs]]
+driver = "UnixStream"
+format = "Raw"
+config = { path = "/run/openwec/raw.socket", framing = "length_prefixed", reconnect_initial_ms = 100, reconnect_max_ms = 5000 }
+    "#;
+
+    #[test]
+    fn test_unix_stream() -> Result<()> {
+        let data = parse(UNIX_STREAM_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::UnixStream(
+                crate::subscription::UnixStreamConfiguration::new(
+                    "/run/openwec/events.socket".to_string()
+                )
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::UnixStream(config) => {
+                assert_eq!(config.path(), "/run/openwec/raw.socket");
+                assert_eq!(
+                    config.framing(),
+                    crate::subscription::TcpFraming::LengthPrefixed
+                );
+                assert_eq!(config.reconnect_initial_ms(), Some(100));
+                assert_eq!(config.reconnect_max_ms(), Some(5000));
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        // XML events may contain line feeds
+        let newline =
+            UNIX_STREAM_CONF.replacen("framing = \"length_prefixed\"", "framing = \"newline\"", 1);
+        assert!(parse(&newline, None).is_err());
+
+        let lower =
+            UNIX_STREAM_CONF.replacen("reconnect_max_ms = 5000", "reconnect_max_ms = 10", 1);
+        assert!(parse(&lower, None).is_err());
+
+        let missing = UNIX_STREAM_CONF.replacen("path = \"/run/openwec/events.socket\"", "", 1);
+        assert!(parse(&missing, None).is_err());
+        Ok(())
+    }
+
+    const STD_STREAM_CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "std_stream"
+
+query = """
+a very small query
+"""
+
+[[outputs]]
+driver = "Stdout"
+format = "Json"
+
+[[outputs]]
+driver = "Stdout"
+format = "Nxlog"
+config = { flush_each = true }
+
+[[outputs]]
+driver = "Stderr"
 format = "RawJson"
-enabled = true
+config = {}
+    "#;
+
+    #[test]
+    fn test_std_stream() -> Result<()> {
+        let data = parse(STD_STREAM_CONF, None)?;
+        let mut flush_each = crate::subscription::StdStreamConfiguration::new();
+        flush_each.set_flush_each(true);
+        assert_eq!(
+            data.outputs()
+                .iter()
+                .map(|output| output.driver().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                crate::subscription::SubscriptionOutputDriver::Stdout(
+                    crate::subscription::StdStreamConfiguration::new()
+                ),
+                crate::subscription::SubscriptionOutputDriver::Stdout(flush_each),
+                crate::subscription::SubscriptionOutputDriver::Stderr(
+                    crate::subscription::StdStreamConfiguration::new()
+                ),
+            ]
+        );
+
+        // Events are written one per line
+        let raw = STD_STREAM_CONF.replacen("format = \"Json\"", "format = \"Raw\"", 1);
+        assert!(parse(&raw, None).is_err());
 
-[outputs.config]
-addr = "127.0.0.1"
-port = 8080
+        let unknown = STD_STREAM_CONF.replacen("flush_each", "flush", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
 
-[[outputs]]
-driver = "Tcp"
-format = "Json"
-enabled = true
+    const NATS_CONF: &str = r#"
+uuid = "3f6a2c1d-8e4b-4b7a-9c5d-2e1f0a9b8c7d"
+name = "nats"
 
-[outputs.config]
-host = "localhost"
-port = 8080
-tls_enabled = true
-tls_certificate_authorities = "test_path"
+query = """
+a very small query
+"""
 
 [[outputs]]
-driver = "Tcp"
+driver = "Nats"
 format = "Json"
-enabled = true
-
-[outputs.config]
-host = "localhost"
-port = 8080
-tls_enabled = true
-tls_certificate_authorities = ["test_path1", "test_path2"]
+config = { servers = "nats://nats.windomain.local:4222", subject = "openwec.events" }
 
 [[outputs]]
-driver = "Tcp"
-format = "Json"
-enabled = true
+driver = "Nats"
+format = "Raw"
+config = { servers = ["tls://nats1.windomain.local:4222", "tls://nats2.windomain.local:4222"], subject = "openwec.{ip}.{principal}.{node}", jetstream = true, credentials_file = "/etc/openwec/nats.creds", tls_certificate_authorities = "/etc/ca.pem" }
+    "#;
 
-[outputs.config]
-host = "localhost"
-port = 8080
-tls_enabled = true
-tls_certificate_authorities = ["test_path1", "test_path2"]
-tls_certificate = "test_cert_path"
-tls_key = "test_key_path"
+    #[test]
+    fn test_nats() -> Result<()> {
+        let data = parse(NATS_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Nats(
+                crate::subscription::NatsConfiguration::new(
+                    vec!["nats://nats.windomain.local:4222".to_string()],
+                    "openwec.events".to_string(),
+                    false,
+                    None,
+                    Vec::new(),
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Nats(config) => {
+                assert_eq!(
+                    config.servers(),
+                    &[
+                        "tls://nats1.windomain.local:4222".to_string(),
+                        "tls://nats2.windomain.local:4222".to_string()
+                    ]
+                );
+                assert_eq!(config.subject(), "openwec.{ip}.{principal}.{node}");
+                assert!(config.jetstream());
+                assert_eq!(
+                    config.credentials_file(),
+                    Some(&"/etc/openwec/nats.creds".to_string())
+                );
+                assert!(config.tls_enabled());
+                assert_eq!(
+                    config.tls_certificate_authorities(),
+                    &["/etc/ca.pem".to_string()]
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-## Redis output
-[[outputs]]
-driver = "Redis"
-format = "Nxlog"
-enabled = false
+        // Certificate authorities are required to verify the servers
+        let no_ca = NATS_CONF.replacen(", tls_certificate_authorities = \"/etc/ca.pem\"", "", 1);
+        assert!(parse(&no_ca, None).is_err());
 
-[outputs.config]
-addr = "localhost"
-list = "my-list"
+        let mixed = NATS_CONF.replacen("tls://nats2", "nats://nats2", 1);
+        assert!(parse(&mixed, None).is_err());
 
-## UnixDatagram output
-[[outputs]]
-format = "Raw"
-enabled =  true
-driver = "UnixDatagram"
+        let no_servers = NATS_CONF.replacen("\"nats://nats.windomain.local:4222\"", "[]", 1);
+        assert!(parse(&no_servers, None).is_err());
 
-[outputs.config]
-path = "/tmp/openwec.socket"
+        let unknown_token = NATS_CONF.replacen("{node}", "{tenant}", 1);
+        assert!(parse(&unknown_token, None).is_err());
+
+        let wildcard = NATS_CONF.replacen("openwec.events", "openwec.>", 1);
+        assert!(parse(&wildcard, None).is_err());
+
+        let empty_token = NATS_CONF.replacen("openwec.events", "openwec..events", 1);
+        assert!(parse(&empty_token, None).is_err());
+
+        let unknown = NATS_CONF.replacen("jetstream = true", "stream = \"events\"", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    const MQTT_CONF: &str = r#"
+uuid = "9a4c6e2b-1d3f-4a5b-8c7d-6e5f4a3b2c1d"
+name = "mqtt"
+
+query = """
+a very small query
+"""
 
-## Files output with new config format
 [[outputs]]
-driver = "Files"
-format = "Json" # or "Raw"
-enabled = true
+driver = "Mqtt"
+format = "Json"
+config = { broker = "mqtt.windomain.local", topic = "openwec/events", client_id = "openwec" }
 
-[outputs.config]
-path = "/whatever/you/{ip}/want/{principal}/{ip:2}/{node}/end"
+[[outputs]]
+driver = "Mqtt"
+format = "Raw"
+config = { broker = "mqtt.windomain.local", port = 8884, topic = "openwec/{ip}/{principal}", qos = 2, client_id = "openwec-raw", username = "openwec", password = "secret", tls_certificate_authorities = "/etc/ca.pem" }
     "#;
 
     #[test]
-    fn test_deserialize_full() -> Result<()> {
-        let revision = "My-revision".to_string();
-        let mut data = parse(FULL_CONTENT, Some(&revision))?;
-
-        let mut expected =
-            crate::subscription::SubscriptionData::new("my-subscription", "a wonderful query\n");
-        expected
-            .set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
-                "b00bf259-3ba9-4faf-b58e-d0e9a3275778",
-            )?))
-            .set_uri(Some("toto".to_string()))
-            .set_enabled(true)
-            .set_heartbeat_interval(32)
-            .set_connection_retry_count(11)
-            .set_connection_retry_interval(12)
-            .set_max_time(13)
-            .set_max_elements(Some(15))
-            .set_max_envelope_size(14)
-            .set_read_existing_events(false)
-            .set_content_format(crate::subscription::ContentFormat::Raw)
-            .set_ignore_channel_error(true)
-            .set_revision(Some(revision));
+    fn test_mqtt() -> Result<()> {
+        let data = parse(MQTT_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Mqtt(
+                crate::subscription::MqttConfiguration::new(
+                    "mqtt.windomain.local".to_string(),
+                    None,
+                    "openwec/events".to_string(),
+                    0,
+                    "openwec".to_string(),
+                    Vec::new(),
+                )?
+            )
+        );
+        match data.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::Mqtt(config) => {
+                assert_eq!(config.port(), crate::subscription::DEFAULT_MQTT_PORT);
+                assert!(!config.tls_enabled());
+            }
+            _ => panic!("Wrong driver"),
+        }
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Mqtt(config) => {
+                assert_eq!(config.broker(), "mqtt.windomain.local");
+                assert_eq!(config.port(), 8884);
+                assert_eq!(config.topic(), "openwec/{ip}/{principal}");
+                assert_eq!(config.qos(), 2);
+                assert_eq!(config.client_id(), "openwec-raw");
+                assert_eq!(config.username(), Some(&"openwec".to_string()));
+                assert_eq!(config.password(), Some(&"secret".to_string()));
+                assert!(config.tls_enabled());
+                assert_eq!(
+                    config.tls_certificate_authorities(),
+                    &["/etc/ca.pem".to_string()]
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-        let mut kafka_options = HashMap::new();
-        kafka_options.insert("bootstrap.server".to_string(), "localhost:9092".to_string());
+        // The default port depends on TLS
+        let tls_port = MQTT_CONF.replacen("port = 8884, ", "", 1);
+        match parse(&tls_port, None)?.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Mqtt(config) => {
+                assert_eq!(config.port(), crate::subscription::DEFAULT_MQTT_TLS_PORT)
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-        let outputs = vec![
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::Files(
-                    crate::subscription::FilesConfiguration::new(
-                        "/tmp/{ip:2}/{ip:3}/{ip}/{principal}/{node}/courgette".to_string(),
-                    ),
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Raw,
-                crate::subscription::SubscriptionOutputDriver::Kafka(
-                    crate::subscription::KafkaConfiguration::new(
-                        "my-topic".to_string(),
-                        kafka_options,
-                    ),
-                ),
-                false,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::RawJson,
-                crate::subscription::SubscriptionOutputDriver::Tcp(
-                    crate::subscription::TcpConfiguration::new(
-                        "127.0.0.1".to_string(),
-                        8080,
-                        false,
-                        Vec::new(),
-                        None,
-                        None,
-                    )?,
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::Tcp(
-                    crate::subscription::TcpConfiguration::new(
-                        "localhost".to_string(),
-                        8080,
-                        true,
-                        vec!["test_path".to_string()],
-                        None,
-                        None,
-                    )?,
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::Tcp(
-                    crate::subscription::TcpConfiguration::new(
-                        "localhost".to_string(),
-                        8080,
-                        true,
-                        vec!["test_path1".to_string(), "test_path2".to_string()],
-                        None,
-                        None,
-                    )?,
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::Tcp(
-                    crate::subscription::TcpConfiguration::new(
-                        "localhost".to_string(),
-                        8080,
-                        true,
-                        vec!["test_path1".to_string(), "test_path2".to_string()],
-                        Some("test_cert_path".to_string()),
-                        Some("test_key_path".to_string()),
-                    )?,
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Nxlog,
-                crate::subscription::SubscriptionOutputDriver::Redis(
-                    crate::subscription::RedisConfiguration::new(
-                        "localhost".to_string(),
-                        "my-list".to_string(),
-                    ),
-                ),
-                false,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Raw,
-                crate::subscription::SubscriptionOutputDriver::UnixDatagram(
-                    crate::subscription::UnixDatagramConfiguration::new(
-                        "/tmp/openwec.socket".to_string(),
-                    ),
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::Files(
-                    crate::subscription::FilesConfiguration::new(
-                        "/whatever/you/{ip}/want/{principal}/{ip:2}/{node}/end".to_string(),
-                    ),
-                ),
-                true,
-            ),
-        ];
+        let qos = MQTT_CONF.replacen("qos = 2", "qos = 3", 1);
+        let err = parse(&qos, None).unwrap_err();
+        assert!(format!("{:#}", err).contains("qos must be 0, 1 or 2, found 3"));
 
-        expected.set_outputs(outputs);
+        let negative_qos = MQTT_CONF.replacen("qos = 2", "qos = -1", 1);
+        assert!(parse(&negative_qos, None).is_err());
 
-        let mut targets = HashSet::new();
-        targets.insert("toto@windomain.local".to_string());
-        targets.insert("tutu@windomain.local".to_string());
-        let kind = crate::subscription::ClientFilterType::KerberosPrinc;
-        let flags = crate::subscription::ClientFilterFlags::empty();
-        let filter = crate::subscription::ClientFilter::try_new(crate::subscription::ClientFilterOperation::Only, kind, flags, targets)?;
+        let no_username = MQTT_CONF.replacen("username = \"openwec\", ", "", 1);
+        assert!(parse(&no_username, None).is_err());
 
-        expected.set_client_filter(Some(filter));
+        let wildcard = MQTT_CONF.replacen("openwec/events", "openwec/#", 1);
+        assert!(parse(&wildcard, None).is_err());
 
-        // The only difference between both subscriptions should be the
-        // internal version, so we set both the same value
-        let version = Uuid::new_v4();
-        // Must be done last
-        expected.set_internal_version(crate::subscription::InternalVersion(version.clone()));
-        data.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+        let unknown_token = MQTT_CONF.replacen("{principal}", "{subscription}", 1);
+        assert!(parse(&unknown_token, None).is_err());
 
-        assert_eq!(data, expected);
+        let no_client_id = MQTT_CONF.replacen(", client_id = \"openwec\"", "", 1);
+        assert!(parse(&no_client_id, None).is_err());
         Ok(())
     }
 
-    const MINIMAL_CONTENT: &str = r#"
-uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
-name = "minimal"
+    const EVENT_HUBS_CONF: &str = r#"
+uuid = "4b7e9d1c-2a3f-4e5d-9b8c-1f2e3d4c5b6a"
+name = "event-hubs"
 
 query = """
 a very small query
 """
 
 [[outputs]]
-driver = "UnixDatagram"
+driver = "EventHubs"
 format = "Json"
+config = { namespace = "openwec", hub = "events", connection_string = "Endpoint=sb://openwec.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=c2VjcmV0" }
 
-[outputs.config]
-path = "/tmp/my.socket"
+[[outputs]]
+driver = "EventHubs"
+format = "Raw"
+config = { namespace = "openwec.servicebus.chinacloudapi.cn", hub = "raw-events", managed_identity = true, partition_key = "{ip}" }
     "#;
 
     #[test]
-    fn test_serialize_minimal() -> Result<()> {
-        let mut data = parse(MINIMAL_CONTENT, None)?;
-
-        let mut expected =
-            crate::subscription::SubscriptionData::new("minimal", "a very small query\n");
-        expected
-            .set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
-                "b00bf259-3ba9-4faf-b58e-d0e9a3757798",
-            )?))
-            .set_outputs(vec![crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::UnixDatagram(
-                    crate::subscription::UnixDatagramConfiguration::new(
-                        "/tmp/my.socket".to_string(),
-                    ),
-                ),
-                true,
-            )]);
+    fn test_event_hubs() -> Result<()> {
+        let data = parse(EVENT_HUBS_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::EventHubs(
+                crate::subscription::EventHubsConfiguration::new(
+                    "openwec".to_string(),
+                    "events".to_string(),
+                    Some("Endpoint=sb://openwec.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=c2VjcmV0".to_string()),
+                    false,
+                    None,
+                )?
+            )
+        );
+        match data.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::EventHubs(config) => {
+                assert_eq!(
+                    config.fully_qualified_namespace(),
+                    "openwec.servicebus.windows.net"
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::EventHubs(config) => {
+                assert_eq!(
+                    config.fully_qualified_namespace(),
+                    "openwec.servicebus.chinacloudapi.cn"
+                );
+                assert_eq!(config.hub(), "raw-events");
+                assert_eq!(config.connection_string(), None);
+                assert!(config.managed_identity());
+                assert_eq!(config.partition_key(), Some(&"{ip}".to_string()));
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-        // Must be done last
-        let version = Uuid::new_v4();
-        data.set_internal_version(InternalVersion(version));
-        expected.set_internal_version(InternalVersion(version));
+        // Exactly one authentication method must be configured
+        let both = EVENT_HUBS_CONF.replacen(
+            "connection_string = ",
+            "managed_identity = true, connection_string = ",
+            1,
+        );
+        assert!(parse(&both, None).is_err());
 
-        assert_eq!(data, expected);
+        let none =
+            EVENT_HUBS_CONF.replacen("managed_identity = true", "managed_identity = false", 1);
+        let err = parse(&none, None).unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("either connection_string or managed_identity must be set"));
+
+        let no_endpoint = EVENT_HUBS_CONF.replacen("Endpoint=sb://", "Endpoint=https://", 1);
+        assert!(parse(&no_endpoint, None).is_err());
+
+        let url =
+            EVENT_HUBS_CONF.replacen("namespace = \"openwec\"", "namespace = \"sb://openwec\"", 1);
+        assert!(parse(&url, None).is_err());
+
+        let no_hub = EVENT_HUBS_CONF.replacen("hub = \"events\"", "hub = \"\"", 1);
+        assert!(parse(&no_hub, None).is_err());
+
+        let unknown_token = EVENT_HUBS_CONF.replacen("{ip}", "{subscription}", 1);
+        assert!(parse(&unknown_token, None).is_err());
+
+        let empty_key = EVENT_HUBS_CONF.replacen("\"{ip}\"", "\"\"", 1);
+        assert!(parse(&empty_key, None).is_err());
+
+        let unknown = EVENT_HUBS_CONF.replacen("partition_key", "partition_id", 1);
+        assert!(parse(&unknown, None).is_err());
         Ok(())
     }
 
-    const MISSING_UUID: &str = r#"
-name = "minimal"
+    const GRPC_CONF: &str = r#"
+uuid = "7c2e4a9f-5b1d-4e3c-8a6f-0d9b8c7e6f5a"
+name = "grpc"
 
 query = """
 a very small query
 """
 
 [[outputs]]
-driver = "UnixDatagram"
+driver = "Grpc"
 format = "Json"
+config = { endpoint = "http://ingest.windomain.local:50051" }
 
-[outputs.config]
-path = "/tmp/my.socket"
+[[outputs]]
+driver = "Grpc"
+format = "Protobuf"
+config = { endpoint = "https://ingest.windomain.local:443", tls_certificate_authorities = "/etc/ca.pem", metadata = { "authorization" = "Bearer secret", "x-source" = "openwec" } }
     "#;
 
     #[test]
-    #[should_panic(expected = "missing field `uuid`")]
-    fn test_serialize_missing_uuid() {
-        parse(MISSING_UUID, None).unwrap();
+    fn test_grpc() -> Result<()> {
+        let data = parse(GRPC_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Grpc(
+                crate::subscription::GrpcConfiguration::new(
+                    "http://ingest.windomain.local:50051".to_string(),
+                    BTreeMap::new(),
+                    Vec::new(),
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Grpc(config) => {
+                assert_eq!(config.endpoint(), "https://ingest.windomain.local:443");
+                assert!(config.tls_enabled());
+                assert_eq!(
+                    config.tls_certificate_authorities(),
+                    &["/etc/ca.pem".to_string()]
+                );
+                assert_eq!(
+                    config.metadata(),
+                    &BTreeMap::from([
+                        ("authorization".to_string(), "Bearer secret".to_string()),
+                        ("x-source".to_string(), "openwec".to_string()),
+                    ])
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
+
+        // Certificate authorities are required to verify the server
+        let no_ca = GRPC_CONF.replacen("tls_certificate_authorities = \"/etc/ca.pem\", ", "", 1);
+        assert!(parse(&no_ca, None).is_err());
+
+        let plain_ca = GRPC_CONF.replacen("https://", "http://", 1);
+        assert!(parse(&plain_ca, None).is_err());
+
+        let scheme = GRPC_CONF.replacen("http://", "grpc://", 1);
+        assert!(parse(&scheme, None).is_err());
+
+        let uppercase = GRPC_CONF.replacen("\"x-source\"", "\"X-Source\"", 1);
+        assert!(parse(&uppercase, None).is_err());
+
+        let reserved = GRPC_CONF.replacen("\"x-source\"", "\"grpc-timeout\"", 1);
+        assert!(parse(&reserved, None).is_err());
+
+        let binary = GRPC_CONF.replacen("\"x-source\"", "\"x-source-bin\"", 1);
+        assert!(parse(&binary, None).is_err());
+
+        let unknown = GRPC_CONF.replacen("metadata", "headers", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
     }
 
-    const MISSING_NAME: &str = r#"
-uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+    const SPLUNK_HEC_CONF: &str = r#"
+uuid = "3f6b2d8e-9c4a-4b1e-a7d5-2e8f0c6b4a19"
+name = "splunk"
 
 query = """
 a very small query
 """
 
 [[outputs]]
-driver = "UnixDatagram"
+driver = "SplunkHec"
 format = "Json"
+config = { url = "http://splunk.windomain.local:8088/services/collector/event", token = "11111111-2222-3333-4444-555555555555" }
 
-[outputs.config]
-path = "/tmp/my.socket"
+[[outputs]]
+driver = "SplunkHec"
+format = "Raw"
+config = { url = "https://splunk.windomain.local:8088/services/collector/event", token = "11111111-2222-3333-4444-555555555555", index = "wineventlog", sourcetype = "XmlWinEventLog", source = "openwec:{principal}", batch_size = 500, batch_timeout_ms = 200, idle_flush_ms = 50, tls_certificate_authorities = "/etc/ca.pem" }
     "#;
 
     #[test]
-    #[should_panic(expected = "missing field `name`")]
-    fn test_serialize_missing_name() {
-        parse(MISSING_NAME, None).unwrap();
-    }
+    fn test_splunk_hec() -> Result<()> {
+        let data = parse(SPLUNK_HEC_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::SplunkHec(
+                crate::subscription::SplunkHecConfiguration::new(
+                    "http://splunk.windomain.local:8088/services/collector/event".to_string(),
+                    "11111111-2222-3333-4444-555555555555".to_string(),
+                    None,
+                    None,
+                    None,
+                    DEFAULT_HTTP_BATCH_SIZE,
+                    DEFAULT_HTTP_BATCH_TIMEOUT_MS,
+                    Vec::new(),
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::SplunkHec(config) => {
+                assert!(config.tls_enabled());
+                assert_eq!(config.index().unwrap(), "wineventlog");
+                assert_eq!(config.sourcetype().unwrap(), "XmlWinEventLog");
+                assert_eq!(config.source().unwrap(), "openwec:{principal}");
+                assert_eq!(config.batch_size(), 500);
+                assert_eq!(config.batch_timeout_ms(), 200);
+                assert_eq!(config.idle_flush_ms(), Some(50));
+                assert_eq!(
+                    config.tls_certificate_authorities(),
+                    &["/etc/ca.pem".to_string()]
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-    const MISSING_QUERY: &str = r#"
-uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
-name = "minimal"
+        // Certificate authorities are required to verify the server
+        let no_ca =
+            SPLUNK_HEC_CONF.replacen(", tls_certificate_authorities = \"/etc/ca.pem\"", "", 1);
+        assert!(parse(&no_ca, None).is_err());
 
-[[outputs]]
-driver = "UnixDatagram"
-format = "Json"
+        let plain_ca = SPLUNK_HEC_CONF.replacen("https://", "http://", 1);
+        assert!(parse(&plain_ca, None).is_err());
 
-[outputs.config]
-path = "/tmp/my.socket"
-    "#;
+        let no_token =
+            SPLUNK_HEC_CONF.replacen(", token = \"11111111-2222-3333-4444-555555555555\"", "", 1);
+        assert!(parse(&no_token, None).is_err());
 
-    #[test]
-    #[should_panic(expected = "missing field `query`")]
-    fn test_serialize_missing_query() {
-        parse(MISSING_QUERY, None).unwrap();
-    }
+        let empty_token = SPLUNK_HEC_CONF.replacen("11111111-2222-3333-4444-555555555555", "", 1);
+        assert!(parse(&empty_token, None).is_err());
 
-    const MISSING_OUTPUTS: &str = r#"
-uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
-name = "minimal"
+        let empty_index = SPLUNK_HEC_CONF.replacen("\"wineventlog\"", "\"\"", 1);
+        assert!(parse(&empty_index, None).is_err());
 
-query = """
-a very small query
-"""
+        let unknown_token = SPLUNK_HEC_CONF.replacen("{principal}", "{channel}", 1);
+        assert!(parse(&unknown_token, None).is_err());
 
-outputs = []
-    "#;
+        let batch_size = SPLUNK_HEC_CONF.replacen("batch_size = 500", "batch_size = 0", 1);
+        assert!(parse(&batch_size, None).is_err());
 
-    #[test]
-    #[should_panic(expected = "Missing subscription outputs")]
-    fn test_serialize_missing_outputs() {
-        parse(MISSING_OUTPUTS, None).unwrap();
+        // Events must be text to be embedded in HEC envelopes
+        let binary = SPLUNK_HEC_CONF.replacen("format = \"Raw\"", "format = \"Msgpack\"", 1);
+        assert!(parse(&binary, None).is_err());
+
+        let unknown = SPLUNK_HEC_CONF.replacen("sourcetype", "source_type", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
     }
 
-    const RANDOM_FIELD: &str = r#"
-babar = "courgette"
-uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
-name = "minimal"
+    const LOKI_CONF: &str = r#"
+uuid = "8a1c5e3b-7d2f-4c6a-9e0b-4f3d2c1b0a98"
+name = "loki"
 
 query = """
 a very small query
 """
 
 [[outputs]]
-driver = "UnixDatagram"
+driver = "Loki"
 format = "Json"
+config = { url = "http://loki.windomain.local:3100", labels = { "job" = "openwec" } }
 
-[outputs.config]
-path = "/tmp/my.socket"
+[[outputs]]
+driver = "Loki"
+format = "Raw"
+config = { url = "https://loki.windomain.local/", labels = { "job" = "openwec", "env" = "prod" }, label_templates = { "host" = "{ip}", "collector" = "openwec-{node}" }, batch_size = 500, batch_timeout_ms = 200, idle_flush_ms = 50, tls_certificate_authorities = "/etc/ca.pem" }
     "#;
 
     #[test]
-    #[should_panic(expected = "unknown field `babar`")]
-    fn test_random_field() {
-        parse(RANDOM_FIELD, None).unwrap();
-    }
+    fn test_loki() -> Result<()> {
+        let data = parse(LOKI_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Loki(
+                crate::subscription::LokiConfiguration::new(
+                    "http://loki.windomain.local:3100".to_string(),
+                    BTreeMap::from([("job".to_string(), "openwec".to_string())]),
+                    BTreeMap::new(),
+                    false,
+                    DEFAULT_HTTP_BATCH_SIZE,
+                    DEFAULT_HTTP_BATCH_TIMEOUT_MS,
+                    Vec::new(),
+                )?
+            )
+        );
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Loki(config) => {
+                assert!(config.tls_enabled());
+                assert_eq!(
+                    config.push_url(),
+                    "https://loki.windomain.local/loki/api/v1/push"
+                );
+                assert_eq!(
+                    config.label_templates(),
+                    &BTreeMap::from([
+                        ("collector".to_string(), "openwec-{node}".to_string()),
+                        ("host".to_string(), "{ip}".to_string()),
+                    ])
+                );
+                assert_eq!(config.labels().len(), 2);
+                assert_eq!(config.batch_size(), 500);
+                assert_eq!(config.batch_timeout_ms(), 200);
+                assert_eq!(config.idle_flush_ms(), Some(50));
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-    const GETTING_STARTED_CONF: &str = r#"
-# conf/my-test-subscription.toml
+        // A stream per client is only created if it is allowed
+        let principal = LOKI_CONF.replacen("\"{ip}\"", "\"{principal}\"", 1);
+        assert!(parse(&principal, None).is_err());
+        let allowed = principal.replacen(
+            "batch_size = 500",
+            "allow_high_cardinality_labels = true, batch_size = 500",
+            1,
+        );
+        let data = parse(&allowed, None)?;
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Loki(config) => {
+                assert!(config.allow_high_cardinality_labels());
+                assert_eq!(config.label_templates()["host"], "{principal}");
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-# Unique identifier of the subscription
-uuid = "28fcc206-1336-4e4a-b76b-18b0ab46e585"
-# Unique name of the subscription
-name = "my-test-subscription"
+        let unknown_token = LOKI_CONF.replacen("{ip}", "{channel}", 1);
+        assert!(parse(&unknown_token, None).is_err());
+
+        let no_labels = LOKI_CONF.replacen(", labels = { \"job\" = \"openwec\" }", "", 1);
+        assert!(parse(&no_labels, None).is_err());
+
+        let invalid_name = LOKI_CONF.replacen("\"job\"", "\"job-name\"", 1);
+        assert!(parse(&invalid_name, None).is_err());
+
+        let reserved_name = LOKI_CONF.replacen("\"job\"", "\"__name__\"", 1);
+        assert!(parse(&reserved_name, None).is_err());
+
+        let duplicated = LOKI_CONF.replacen("\"host\"", "\"env\"", 1);
+        assert!(parse(&duplicated, None).is_err());
+
+        let empty_value = LOKI_CONF.replacen("\"openwec\" }", "\"\" }", 1);
+        assert!(parse(&empty_value, None).is_err());
+
+        let no_ca = LOKI_CONF.replacen(", tls_certificate_authorities = \"/etc/ca.pem\"", "", 1);
+        assert!(parse(&no_ca, None).is_err());
+
+        // Log lines are strings
+        let binary = LOKI_CONF.replacen("format = \"Raw\"", "format = \"Msgpack\"", 1);
+        assert!(parse(&binary, None).is_err());
+
+        let unknown = LOKI_CONF.replacen("label_templates", "templates", 1);
+        assert!(parse(&unknown, None).is_err());
+        Ok(())
+    }
+
+    const ZMQ_CONF: &str = r#"
+uuid = "3f6b2d8e-1c4a-4e7b-9d5f-8a2c6e0b4d17"
+name = "zmq"
 
-# Subscription query
 query = """
-<QueryList>
-    <Query Id="0">
-        <Select Path="Application">*</Select>
-        <Select Path="Security">*</Select>
-        <Select Path="Setup">*</Select>
-        <Select Path="System">*</Select>
-    </Query>
-</QueryList>
+a very small query
 """
 
-# Subscription outputs
 [[outputs]]
-driver = "Files"
-format = "Raw"
-config = { path = "/data/logs/{ip}/{principal}/messages" }
+driver = "Zmq"
+format = "Json"
+config = { endpoint = "tcp://127.0.0.1:5555" }
 
-# Subscription outputs
 [[outputs]]
-driver = "Kafka"
-format = "RawJson"
-# FIXME: `config.options` should be configured in OpenWEC settings (`outputs.kafka.options`)
-# to use only one kafka producer client for all kafka outputs
-config = { topic = "my-kafka-topic", options = { "bootstrap.servers" = "localhost:9092" } }
+driver = "Zmq"
+format = "Raw"
+config = { endpoint = "ipc:///run/openwec/events.sock", socket_type = "PUB", hwm = 10000 }
     "#;
-    const GETTING_STARTED_QUERY: &str = r#"<QueryList>
-    <Query Id="0">
-        <Select Path="Application">*</Select>
-        <Select Path="Security">*</Select>
-        <Select Path="Setup">*</Select>
-        <Select Path="System">*</Select>
-    </Query>
-</QueryList>
-"#;
 
     #[test]
-    fn test_getting_started_conf() -> Result<()> {
-        let mut data = parse(GETTING_STARTED_CONF, None)?;
-
-        let mut expected = crate::subscription::SubscriptionData::new(
-            "my-test-subscription",
-            GETTING_STARTED_QUERY,
+    fn test_zmq() -> Result<()> {
+        let data = parse(ZMQ_CONF, None)?;
+        assert_eq!(
+            data.outputs()[0].driver(),
+            &crate::subscription::SubscriptionOutputDriver::Zmq(
+                crate::subscription::ZmqConfiguration::new(
+                    "tcp://127.0.0.1:5555".to_string(),
+                    crate::subscription::ZmqSocketType::Push,
+                    None,
+                )?
+            )
         );
-        expected.set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
-            "28fcc206-1336-4e4a-b76b-18b0ab46e585",
-        )?));
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Zmq(config) => {
+                assert_eq!(config.endpoint(), "ipc:///run/openwec/events.sock");
+                assert_eq!(
+                    config.socket_type(),
+                    crate::subscription::ZmqSocketType::Pub
+                );
+                assert_eq!(config.hwm(), Some(10000));
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-        let mut kafka_options = HashMap::new();
-        kafka_options.insert(
-            "bootstrap.servers".to_string(),
-            "localhost:9092".to_string(),
-        );
+        let push = ZMQ_CONF.replacen("\"PUB\"", "\"push\"", 1);
+        match parse(&push, None)?.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Zmq(config) => {
+                assert_eq!(
+                    config.socket_type(),
+                    crate::subscription::ZmqSocketType::Push
+                );
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-        let outputs = vec![
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Raw,
-                crate::subscription::SubscriptionOutputDriver::Files(
-                    crate::subscription::FilesConfiguration::new(
-                        "/data/logs/{ip}/{principal}/messages".to_string(),
-                    ),
-                ),
-                true,
-            ),
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::RawJson,
-                crate::subscription::SubscriptionOutputDriver::Kafka(
-                    crate::subscription::KafkaConfiguration::new(
-                        "my-kafka-topic".to_string(),
-                        kafka_options,
-                    ),
-                ),
-                true,
-            ),
-        ];
+        let unknown_type = ZMQ_CONF.replacen("\"PUB\"", "\"DEALER\"", 1);
+        assert!(parse(&unknown_type, None).is_err());
 
-        expected.set_outputs(outputs);
+        let no_transport = ZMQ_CONF.replacen("tcp://", "", 1);
+        assert!(parse(&no_transport, None).is_err());
 
-        // The only difference between both subscriptions should be the
-        // internal version, so we set both the same value
-        let version = Uuid::new_v4();
-        // Must be done last
-        expected.set_internal_version(crate::subscription::InternalVersion(version.clone()));
-        data.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+        let inproc = ZMQ_CONF.replacen("tcp://", "inproc://", 1);
+        assert!(parse(&inproc, None).is_err());
 
-        assert_eq!(data, expected);
+        let no_hwm = ZMQ_CONF.replacen("hwm = 10000", "hwm = 0", 1);
+        assert!(parse(&no_hwm, None).is_err());
+
+        let unknown = ZMQ_CONF.replacen("hwm", "high_water_mark", 1);
+        assert!(parse(&unknown, None).is_err());
         Ok(())
     }
 
-    const CLIENT_FILTER_CONF: &str = r#"
-uuid = "28fcc206-1336-4e4a-b76b-18b0ab46e585"
-name = "my-test-subscription"
-query = "<QueryList></QueryList>"
+    const ENV_CONF: &str = r#"
+uuid = "5d8e1f2a-3b4c-4d5e-8f6a-7b8c9d0e1f2a"
+name = "env"
 
-[[outputs]]
-driver = "Files"
-format = "Raw"
-config = { path = "/data/logs/{ip}/{principal}/messages" }
+query = """
+<QueryList><Query Id="0"><Select Path="${Security}">*</Select></Query></QueryList>
+"""
 
-[filter]
-operation = "Only"
-type = "KerberosPrinc"
-flags = "GlobPattern | CaseInsensitive"
-targets = ["radis*@REALM"]
+[[outputs]]
+driver = "Kafka"
+format = "Json"
+config = { topic = "openwec", options = { "bootstrap.servers" = "${OPENWEC_TEST_KAFKA_SERVERS}", "ssl.ca.location" = "${OPENWEC_TEST_CA_DIR:-/etc/ssl}/ca.pem", "sasl.password" = "p$${word}" } }
 
+[[outputs]]
+driver = "Tcp"
+format = "Json"
+config = { host = "${OPENWEC_TEST_TCP_HOST}", port = 5000 }
     "#;
 
     #[test]
-    fn test_client_filter() -> Result<()> {
-        let mut data = parse(CLIENT_FILTER_CONF, None)?;
-
-        let mut expected = crate::subscription::SubscriptionData::new(
-            "my-test-subscription",
-            "<QueryList></QueryList>",
-        );
-        expected.set_uuid(crate::subscription::SubscriptionUuid(Uuid::from_str(
-            "28fcc206-1336-4e4a-b76b-18b0ab46e585",
-        )?));
-
-        let outputs = vec![
-            crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Raw,
-                crate::subscription::SubscriptionOutputDriver::Files(
-                    crate::subscription::FilesConfiguration::new(
-                        "/data/logs/{ip}/{principal}/messages".to_string(),
+    fn test_env_interpolation() -> Result<()> {
+        std::env::set_var("OPENWEC_TEST_KAFKA_SERVERS", "kafka1:9092,kafka2:9092");
+        std::env::set_var("OPENWEC_TEST_TCP_HOST", "logs.windomain.local");
+        std::env::remove_var("OPENWEC_TEST_CA_DIR");
+
+        let data = parse(ENV_CONF, None)?;
+        // Strings outside of outputs are not interpolated
+        assert!(data.query().contains("Path=\"${Security}\""));
+        match data.outputs()[0].driver() {
+            crate::subscription::SubscriptionOutputDriver::Kafka(config) => assert_eq!(
+                config.options(),
+                &HashMap::from([
+                    (
+                        "bootstrap.servers".to_string(),
+                        "kafka1:9092,kafka2:9092".to_string()
                     ),
-                ),
-                true,
+                    ("ssl.ca.location".to_string(), "/etc/ssl/ca.pem".to_string()),
+                    ("sasl.password".to_string(), "p${word}".to_string()),
+                ])
             ),
-        ];
-
-        expected.set_outputs(outputs);
-
-        let operation = crate::subscription::ClientFilterOperation::Only;
-        let kind = crate::subscription::ClientFilterType::KerberosPrinc;
-        let flags = crate::subscription::ClientFilterFlags::GlobPattern | crate::subscription::ClientFilterFlags::CaseInsensitive;
-
-        let mut targets = HashSet::new();
-        targets.insert("radis*@REALM".to_string());
+            _ => panic!("Wrong driver"),
+        }
+        match data.outputs()[1].driver() {
+            crate::subscription::SubscriptionOutputDriver::Tcp(config) => {
+                assert_eq!(config.host(), "logs.windomain.local")
+            }
+            _ => panic!("Wrong driver"),
+        }
 
-        let filter = crate::subscription::ClientFilter::try_new(operation, kind, flags, targets)?;
-        expected.set_client_filter(Some(filter));
+        let unset = ENV_CONF.replacen("OPENWEC_TEST_TCP_HOST", "OPENWEC_TEST_UNSET_HOST", 1);
+        let err = parse(&unset, None).unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("Environment variable OPENWEC_TEST_UNSET_HOST is not set"));
 
-        let version = Uuid::new_v4();
-        // Must be done last
-        expected.set_internal_version(crate::subscription::InternalVersion(version.clone()));
-        data.set_internal_version(crate::subscription::InternalVersion(version.clone()));
+        let unterminated =
+            ENV_CONF.replacen("${OPENWEC_TEST_TCP_HOST}", "${OPENWEC_TEST_TCP_HOST", 1);
+        assert!(parse(&unterminated, None).is_err());
 
-        assert_eq!(data, expected);
+        let invalid_name = ENV_CONF.replacen("OPENWEC_TEST_TCP_HOST", "OPENWEC-TEST", 1);
+        assert!(parse(&invalid_name, None).is_err());
         Ok(())
     }
 }
@@ -17,10 +17,11 @@ enum ImportExport {
     V1(v1::Subscriptions),
     V2(v2::Subscriptions),
     V3(v3::Subscriptions),
+    V4(v4::Subscriptions),
 }
 
 pub fn serialize(subscriptions: &[crate::subscription::SubscriptionData]) -> Result<String> {
-    let export = ImportExport::V3(subscriptions.into());
+    let export = ImportExport::V4(subscriptions.into());
     Ok(serde_json::to_string(&export)?)
 }
 
@@ -36,6 +37,9 @@ pub fn parse(content: &str) -> Result<Vec<crate::subscription::SubscriptionData>
         ImportExport::V3(subscriptions) => subscriptions
             .try_into()
             .context("Invalid subscription data")?,
+        ImportExport::V4(subscriptions) => subscriptions
+            .try_into()
+            .context("Invalid subscription data")?,
     };
     Ok(subscriptions)
 }
@@ -332,16 +336,6 @@ pub mod v2 {
         }
     }
 
-    // Used for export
-    impl From<crate::subscription::KafkaConfiguration> for KafkaConfiguration {
-        fn from(value: crate::subscription::KafkaConfiguration) -> Self {
-            Self {
-                topic: value.topic().to_string(),
-                options: value.options().clone(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct RedisConfiguration {
         pub addr: String,
@@ -354,15 +348,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::RedisConfiguration> for RedisConfiguration {
-        fn from(value: crate::subscription::RedisConfiguration) -> Self {
-            Self {
-                addr: value.addr().to_string(),
-                list: value.list().to_string(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct TcpConfiguration {
         pub addr: String,
@@ -389,19 +374,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::TcpConfiguration> for TcpConfiguration {
-        fn from(value: crate::subscription::TcpConfiguration) -> Self {
-            Self {
-                addr: value.host().to_string(),
-                port: value.port(),
-                tls_enabled: Some(value.tls_enabled()),
-                tls_certificate_authorities: value.tls_certificate_authorities().to_owned(),
-                tls_certificate: value.tls_certificate().cloned(),
-                tls_key: value.tls_key().cloned(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct FilesConfiguration {
         pub path: String,
@@ -413,14 +385,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::FilesConfiguration> for FilesConfiguration {
-        fn from(value: crate::subscription::FilesConfiguration) -> Self {
-            Self {
-                path: value.path().to_owned(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct UnixDatagramConfiguration {
         pub path: String,
@@ -432,14 +396,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::UnixDatagramConfiguration> for UnixDatagramConfiguration {
-        fn from(value: crate::subscription::UnixDatagramConfiguration) -> Self {
-            Self {
-                path: value.path().to_string(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) enum SubscriptionOutputDriver {
         Files(FilesConfiguration),
@@ -473,28 +429,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionOutputDriver> for SubscriptionOutputDriver {
-        fn from(value: crate::subscription::SubscriptionOutputDriver) -> Self {
-            match value {
-                crate::subscription::SubscriptionOutputDriver::Files(config) => {
-                    SubscriptionOutputDriver::Files(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::Kafka(config) => {
-                    SubscriptionOutputDriver::Kafka(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::Tcp(config) => {
-                    SubscriptionOutputDriver::Tcp(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::Redis(config) => {
-                    SubscriptionOutputDriver::Redis(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::UnixDatagram(config) => {
-                    SubscriptionOutputDriver::UnixDatagram(config.into())
-                }
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
     pub(super) enum SubscriptionOutputFormat {
         Json,
@@ -520,23 +454,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionOutputFormat> for SubscriptionOutputFormat {
-        fn from(value: crate::subscription::SubscriptionOutputFormat) -> Self {
-            match value {
-                crate::subscription::SubscriptionOutputFormat::Json => {
-                    SubscriptionOutputFormat::Json
-                }
-                crate::subscription::SubscriptionOutputFormat::Raw => SubscriptionOutputFormat::Raw,
-                crate::subscription::SubscriptionOutputFormat::RawJson => {
-                    SubscriptionOutputFormat::RawJson
-                }
-                crate::subscription::SubscriptionOutputFormat::Nxlog => {
-                    SubscriptionOutputFormat::Nxlog
-                }
-            }
-        }
-    }
-
     #[derive(Deserialize, Debug, Clone, Eq, PartialEq, Serialize)]
     pub(super) struct SubscriptionOutput {
         pub format: SubscriptionOutputFormat,
@@ -556,16 +473,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionOutput> for SubscriptionOutput {
-        fn from(value: crate::subscription::SubscriptionOutput) -> Self {
-            Self {
-                format: value.format().clone().into(),
-                driver: value.driver().clone().into(),
-                enabled: value.enabled(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
     pub(super) enum PrincsFilterOperation {
         Only,
@@ -581,15 +488,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::ClientFilterOperation> for PrincsFilterOperation {
-        fn from(value: crate::subscription::ClientFilterOperation) -> Self {
-            match value {
-                crate::subscription::ClientFilterOperation::Except => PrincsFilterOperation::Except,
-                crate::subscription::ClientFilterOperation::Only => PrincsFilterOperation::Only,
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
     pub(super) struct PrincsFilter {
         pub operation: Option<PrincsFilterOperation>,
@@ -603,15 +501,6 @@ pub mod v2 {
         }
     }
 
-    impl From<Option<crate::subscription::ClientFilter>> for PrincsFilter {
-        fn from(value: Option<crate::subscription::ClientFilter>) -> Self {
-            Self {
-                operation: value.as_ref().map(|f| f.operation().clone().into()),
-                princs: value.map_or(HashSet::new(), |f| f.targets().iter().cloned().map(String::from).collect()),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
     pub(super) enum ContentFormat {
         Raw,
@@ -627,15 +516,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::ContentFormat> for ContentFormat {
-        fn from(value: crate::subscription::ContentFormat) -> Self {
-            match value {
-                crate::subscription::ContentFormat::Raw => ContentFormat::Raw,
-                crate::subscription::ContentFormat::RenderedText => ContentFormat::RenderedText,
-            }
-        }
-    }
-
     #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
     pub(super) struct SubscriptionData {
         pub uuid: Uuid,
@@ -688,33 +568,6 @@ pub mod v2 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionData> for SubscriptionData {
-        fn from(value: crate::subscription::SubscriptionData) -> Self {
-            // Note: internal version is not exported nor set
-            Self {
-                uuid: value.uuid().0,
-                name: value.name().to_string(),
-                uri: value.uri().cloned(),
-                revision: value.revision().cloned(),
-                query: value.query().to_string(),
-                heartbeat_interval: value.heartbeat_interval(),
-                connection_retry_count: value.connection_retry_count(),
-                connection_retry_interval: value.connection_retry_interval(),
-                max_time: value.max_time(),
-                max_elements: value.max_elements(),
-                max_envelope_size: value.max_envelope_size(),
-                enabled: value.enabled(),
-                read_existing_events: value.read_existing_events(),
-                content_format: value.content_format().to_owned().into(),
-                ignore_channel_error: value.ignore_channel_error(),
-                locale: value.locale().cloned(),
-                data_locale: value.data_locale().cloned(),
-                filter: value.client_filter().cloned().into(),
-                outputs: value.outputs().iter().map(|o| o.clone().into()).collect(),
-            }
-        }
-    }
-
     #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
     pub(super) struct Subscriptions {
         pub subscriptions: Vec<SubscriptionData>,
@@ -733,13 +586,6 @@ pub mod v2 {
         }
     }
 
-    impl From<&[crate::subscription::SubscriptionData]> for Subscriptions {
-        fn from(value: &[crate::subscription::SubscriptionData]) -> Self {
-            Self {
-                subscriptions: value.iter().map(|s| s.clone().into()).collect(),
-            }
-        }
-    }
 }
 
 pub mod v3 {
@@ -763,16 +609,6 @@ pub mod v3 {
         }
     }
 
-    // Used for export
-    impl From<crate::subscription::KafkaConfiguration> for KafkaConfiguration {
-        fn from(value: crate::subscription::KafkaConfiguration) -> Self {
-            Self {
-                topic: value.topic().to_string(),
-                options: value.options().clone(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct RedisConfiguration {
         pub addr: String,
@@ -785,15 +621,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::RedisConfiguration> for RedisConfiguration {
-        fn from(value: crate::subscription::RedisConfiguration) -> Self {
-            Self {
-                addr: value.addr().to_string(),
-                list: value.list().to_string(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct TcpConfiguration {
         pub addr: String,
@@ -820,19 +647,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::TcpConfiguration> for TcpConfiguration {
-        fn from(value: crate::subscription::TcpConfiguration) -> Self {
-            Self {
-                addr: value.host().to_string(),
-                port: value.port(),
-                tls_enabled: Some(value.tls_enabled()),
-                tls_certificate_authorities: value.tls_certificate_authorities().to_owned(),
-                tls_certificate: value.tls_certificate().cloned(),
-                tls_key: value.tls_key().cloned(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct FilesConfiguration {
         pub path: String,
@@ -844,14 +658,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::FilesConfiguration> for FilesConfiguration {
-        fn from(value: crate::subscription::FilesConfiguration) -> Self {
-            Self {
-                path: value.path().to_owned(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) struct UnixDatagramConfiguration {
         pub path: String,
@@ -863,14 +669,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::UnixDatagramConfiguration> for UnixDatagramConfiguration {
-        fn from(value: crate::subscription::UnixDatagramConfiguration) -> Self {
-            Self {
-                path: value.path().to_string(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
     pub(super) enum SubscriptionOutputDriver {
         Files(FilesConfiguration),
@@ -904,28 +702,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionOutputDriver> for SubscriptionOutputDriver {
-        fn from(value: crate::subscription::SubscriptionOutputDriver) -> Self {
-            match value {
-                crate::subscription::SubscriptionOutputDriver::Files(config) => {
-                    SubscriptionOutputDriver::Files(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::Kafka(config) => {
-                    SubscriptionOutputDriver::Kafka(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::Tcp(config) => {
-                    SubscriptionOutputDriver::Tcp(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::Redis(config) => {
-                    SubscriptionOutputDriver::Redis(config.into())
-                }
-                crate::subscription::SubscriptionOutputDriver::UnixDatagram(config) => {
-                    SubscriptionOutputDriver::UnixDatagram(config.into())
-                }
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
     pub(super) enum SubscriptionOutputFormat {
         Json,
@@ -951,23 +727,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionOutputFormat> for SubscriptionOutputFormat {
-        fn from(value: crate::subscription::SubscriptionOutputFormat) -> Self {
-            match value {
-                crate::subscription::SubscriptionOutputFormat::Json => {
-                    SubscriptionOutputFormat::Json
-                }
-                crate::subscription::SubscriptionOutputFormat::Raw => SubscriptionOutputFormat::Raw,
-                crate::subscription::SubscriptionOutputFormat::RawJson => {
-                    SubscriptionOutputFormat::RawJson
-                }
-                crate::subscription::SubscriptionOutputFormat::Nxlog => {
-                    SubscriptionOutputFormat::Nxlog
-                }
-            }
-        }
-    }
-
     #[derive(Deserialize, Debug, Clone, Eq, PartialEq, Serialize)]
     pub(super) struct SubscriptionOutput {
         pub format: SubscriptionOutputFormat,
@@ -987,16 +746,6 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::SubscriptionOutput> for SubscriptionOutput {
-        fn from(value: crate::subscription::SubscriptionOutput) -> Self {
-            Self {
-                format: value.format().clone().into(),
-                driver: value.driver().clone().into(),
-                enabled: value.enabled(),
-            }
-        }
-    }
-
     #[derive(Debug, Clone, Eq, PartialEq, Display, Serialize, Deserialize, EnumString)]
     #[strum(serialize_all = "PascalCase", ascii_case_insensitive)]
     pub(super) enum ClientFilterOperation {
@@ -1013,13 +762,1691 @@ pub mod v3 {
         }
     }
 
-    impl From<crate::subscription::ClientFilterOperation> for ClientFilterOperation  {
-        fn from(value: crate::subscription::ClientFilterOperation) -> Self {
-            match value {
-                crate::subscription::ClientFilterOperation::Except => ClientFilterOperation::Except,
-                crate::subscription::ClientFilterOperation::Only => ClientFilterOperation::Only,
-            }
-        }
+
+    #[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Display, AsRefStr, EnumString)]
+    #[strum(ascii_case_insensitive)]
+    pub(super) enum ClientFilterType {
+        #[default]
+        KerberosPrinc,
+        TLSCertSubject,
+        MachineID,
+    }
+
+    impl From<ClientFilterType> for crate::subscription::ClientFilterType {
+        fn from(value: ClientFilterType) -> Self {
+            match value {
+                ClientFilterType::KerberosPrinc => crate::subscription::ClientFilterType::KerberosPrinc,
+                ClientFilterType::TLSCertSubject => crate::subscription::ClientFilterType::TLSCertSubject,
+                ClientFilterType::MachineID => crate::subscription::ClientFilterType::MachineID,
+            }
+        }
+    }
+
+    bitflags! {
+        #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+        pub(super) struct ClientFilterFlags: u32 {
+            const CaseInsensitive = 1 << 0;
+            const GlobPattern = 1 << 1;
+        }
+    }
+
+    impl From<ClientFilterFlags> for crate::subscription::ClientFilterFlags {
+        fn from(value: ClientFilterFlags) -> Self {
+            crate::subscription::ClientFilterFlags::from_bits(value.bits()).unwrap()
+        }
+    }
+
+    impl Display for ClientFilterFlags {
+        fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+            bitflags::parser::to_writer_strict(self, f)
+        }
+    }
+
+    impl Default for ClientFilterFlags {
+        fn default() -> Self {
+            Self::empty()
+        }
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct ClientFilter {
+        pub operation: ClientFilterOperation,
+        #[serde(rename = "type", default)]
+        pub kind: ClientFilterType,
+        #[serde(default)]
+        pub flags: ClientFilterFlags,
+        #[serde(alias = "cert_subjects", alias = "princs")]
+        pub targets: HashSet<String>,
+    }
+
+    impl TryFrom<ClientFilter> for crate::subscription::ClientFilter {
+        type Error = anyhow::Error;
+
+        fn try_from(value: ClientFilter) -> std::prelude::v1::Result<Self, Self::Error> {
+            crate::subscription::ClientFilter::try_new(value.operation.into(), value.kind.into(), value.flags.into(), value.targets)
+        }
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum ContentFormat {
+        Raw,
+        RenderedText,
+    }
+
+    impl From<ContentFormat> for crate::subscription::ContentFormat {
+        fn from(value: ContentFormat) -> Self {
+            match value {
+                ContentFormat::Raw => crate::subscription::ContentFormat::Raw,
+                ContentFormat::RenderedText => crate::subscription::ContentFormat::RenderedText,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
+    pub(super) struct SubscriptionData {
+        pub uuid: Uuid,
+        pub revision: Option<String>,
+        pub name: String,
+        pub uri: Option<String>,
+        pub query: String,
+        pub heartbeat_interval: u32,
+        pub connection_retry_count: u16,
+        pub connection_retry_interval: u32,
+        pub max_time: u32,
+        pub max_elements: Option<u32>,
+        pub max_envelope_size: u32,
+        pub enabled: bool,
+        pub read_existing_events: bool,
+        pub content_format: ContentFormat,
+        pub ignore_channel_error: bool,
+        pub locale: Option<String>,
+        pub data_locale: Option<String>,
+        pub filter: Option<ClientFilter>,
+        pub outputs: Vec<SubscriptionOutput>,
+    }
+
+    impl TryFrom<SubscriptionData> for crate::subscription::SubscriptionData {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SubscriptionData) -> Result<Self, Self::Error> {
+            let mut data = crate::subscription::SubscriptionData::new(&value.name, &value.query);
+            let outputs: Result<Vec<crate::subscription::SubscriptionOutput>, _> =
+                value.outputs.iter().map(|s| s.clone().try_into()).collect();
+
+            data.set_uuid(crate::subscription::SubscriptionUuid(value.uuid))
+                .set_uri(value.uri)
+                .set_heartbeat_interval(value.heartbeat_interval)
+                .set_connection_retry_count(value.connection_retry_count)
+                .set_connection_retry_interval(value.connection_retry_interval)
+                .set_max_time(value.max_time)
+                .set_max_elements(value.max_elements)
+                .set_max_envelope_size(value.max_envelope_size)
+                .set_enabled(value.enabled)
+                .set_read_existing_events(value.read_existing_events)
+                .set_content_format(value.content_format.into())
+                .set_ignore_channel_error(value.ignore_channel_error)
+                .set_locale(value.locale)
+                .set_data_locale(value.data_locale)
+                .set_outputs(outputs?)
+                .set_revision(value.revision);
+
+            if let Some(filter) = value.filter {
+                data.set_client_filter(Some(filter.try_into()?));
+            }
+
+            // Note: internal version is not exported nor set
+            Ok(data)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
+    pub(super) struct Subscriptions {
+        pub subscriptions: Vec<SubscriptionData>,
+    }
+
+    impl TryFrom<Subscriptions> for Vec<crate::subscription::SubscriptionData> {
+        type Error = anyhow::Error;
+
+        fn try_from(value: Subscriptions) -> Result<Self, Self::Error> {
+            let subscriptions: Result<Vec<crate::subscription::SubscriptionData>, _> = value
+                .subscriptions
+                .iter()
+                .map(|s| s.clone().try_into())
+                .collect();
+            subscriptions
+        }
+    }
+
+}
+
+pub mod v4 {
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use uuid::Uuid;
+    use strum::{Display, AsRefStr, EnumString};
+    use bitflags::bitflags;
+    use std::fmt::{Display, Formatter};
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct KafkaConfiguration {
+        pub topic: String,
+        pub options: HashMap<String, String>,
+        #[serde(default)]
+        pub headers: BTreeMap<String, String>,
+    }
+
+    // Used for import
+    impl TryFrom<KafkaConfiguration> for crate::subscription::KafkaConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: KafkaConfiguration) -> Result<Self, Self::Error> {
+            let mut config =
+                crate::subscription::KafkaConfiguration::new(value.topic, value.options);
+            config.set_headers(value.headers)?;
+            Ok(config)
+        }
+    }
+
+    // Used for export
+    impl From<crate::subscription::KafkaConfiguration> for KafkaConfiguration {
+        fn from(value: crate::subscription::KafkaConfiguration) -> Self {
+            Self {
+                topic: value.topic().to_string(),
+                options: value.options().clone(),
+                headers: value.headers().clone(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct RedisConfiguration {
+        pub addr: String,
+        pub list: String,
+        #[serde(default)]
+        pub mode: RedisMode,
+        #[serde(default)]
+        pub max_len: Option<u64>,
+    }
+
+    impl TryFrom<RedisConfiguration> for crate::subscription::RedisConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: RedisConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::RedisConfiguration::new(value.addr, value.list);
+            config.set_mode(value.mode.into(), value.max_len)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::RedisConfiguration> for RedisConfiguration {
+        fn from(value: crate::subscription::RedisConfiguration) -> Self {
+            Self {
+                addr: value.addr().to_string(),
+                list: value.list().to_string(),
+                mode: value.mode().into(),
+                max_len: value.max_len(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum RedisMode {
+        #[default]
+        List,
+        Stream,
+    }
+
+    impl From<RedisMode> for crate::subscription::RedisMode {
+        fn from(value: RedisMode) -> Self {
+            match value {
+                RedisMode::List => crate::subscription::RedisMode::List,
+                RedisMode::Stream => crate::subscription::RedisMode::Stream,
+            }
+        }
+    }
+
+    impl From<crate::subscription::RedisMode> for RedisMode {
+        fn from(value: crate::subscription::RedisMode) -> Self {
+            match value {
+                crate::subscription::RedisMode::List => RedisMode::List,
+                crate::subscription::RedisMode::Stream => RedisMode::Stream,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum TcpFraming {
+        #[default]
+        Newline,
+        LengthPrefixed,
+        OctetCounting,
+    }
+
+    impl From<TcpFraming> for crate::subscription::TcpFraming {
+        fn from(value: TcpFraming) -> Self {
+            match value {
+                TcpFraming::Newline => crate::subscription::TcpFraming::Newline,
+                TcpFraming::LengthPrefixed => crate::subscription::TcpFraming::LengthPrefixed,
+                TcpFraming::OctetCounting => crate::subscription::TcpFraming::OctetCounting,
+            }
+        }
+    }
+
+    impl From<crate::subscription::TcpFraming> for TcpFraming {
+        fn from(value: crate::subscription::TcpFraming) -> Self {
+            match value {
+                crate::subscription::TcpFraming::Newline => TcpFraming::Newline,
+                crate::subscription::TcpFraming::LengthPrefixed => TcpFraming::LengthPrefixed,
+                crate::subscription::TcpFraming::OctetCounting => TcpFraming::OctetCounting,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct TcpConfiguration {
+        pub addr: String,
+        pub port: u16,
+        pub tls_enabled: Option<bool>,
+        #[serde(default)]
+        pub tls_certificate_authorities: Vec<String>,
+        pub tls_certificate: Option<String>,
+        pub tls_key: Option<String>,
+        #[serde(default)]
+        pub framing: TcpFraming,
+        #[serde(default)]
+        pub reconnect_initial_ms: Option<u64>,
+        #[serde(default)]
+        pub reconnect_max_ms: Option<u64>,
+        #[serde(default)]
+        pub buffer_max_events: Option<u64>,
+        #[serde(default)]
+        pub tcp_keepalive_secs: Option<u64>,
+        #[serde(default)]
+        pub tcp_nodelay: bool,
+        #[serde(default)]
+        pub tls_pinned_sha256: Vec<String>,
+    }
+
+    impl TryFrom<TcpConfiguration> for crate::subscription::TcpConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: TcpConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::TcpConfiguration::new(
+                value.addr,
+                value.port,
+                value.tls_enabled.unwrap_or(false),
+                value.tls_certificate_authorities,
+                value.tls_certificate,
+                value.tls_key,
+            )?;
+            config.set_framing(value.framing.into());
+            config.set_reconnect(value.reconnect_initial_ms, value.reconnect_max_ms)?;
+            config.set_buffer_max_events(value.buffer_max_events)?;
+            config.set_tcp_keepalive_secs(value.tcp_keepalive_secs)?;
+            config.set_tcp_nodelay(value.tcp_nodelay);
+            config.set_tls_pinned_sha256(value.tls_pinned_sha256)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::TcpConfiguration> for TcpConfiguration {
+        fn from(value: crate::subscription::TcpConfiguration) -> Self {
+            Self {
+                addr: value.host().to_string(),
+                port: value.port(),
+                tls_enabled: Some(value.tls_enabled()),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_owned(),
+                tls_certificate: value.tls_certificate().cloned(),
+                tls_key: value.tls_key().cloned(),
+                framing: value.framing().into(),
+                reconnect_initial_ms: value.reconnect_initial_ms(),
+                reconnect_max_ms: value.reconnect_max_ms(),
+                buffer_max_events: value.buffer_max_events(),
+                tcp_keepalive_secs: value.tcp_keepalive_secs(),
+                tcp_nodelay: value.tcp_nodelay(),
+                tls_pinned_sha256: value.tls_pinned_sha256().to_owned(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct FilesRotation {
+        pub max_size_bytes: Option<u64>,
+        pub max_age_secs: Option<u64>,
+    }
+
+    impl TryFrom<FilesRotation> for crate::subscription::FilesRotation {
+        type Error = anyhow::Error;
+
+        fn try_from(value: FilesRotation) -> Result<Self, Self::Error> {
+            crate::subscription::FilesRotation::new(value.max_size_bytes, value.max_age_secs)
+        }
+    }
+
+    impl From<crate::subscription::FilesRotation> for FilesRotation {
+        fn from(value: crate::subscription::FilesRotation) -> Self {
+            Self {
+                max_size_bytes: value.max_size_bytes(),
+                max_age_secs: value.max_age_secs(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum FilesCompression {
+        #[default]
+        None,
+        Gzip,
+    }
+
+    impl From<FilesCompression> for crate::subscription::FilesCompression {
+        fn from(value: FilesCompression) -> Self {
+            match value {
+                FilesCompression::None => crate::subscription::FilesCompression::None,
+                FilesCompression::Gzip => crate::subscription::FilesCompression::Gzip,
+            }
+        }
+    }
+
+    impl From<crate::subscription::FilesCompression> for FilesCompression {
+        fn from(value: crate::subscription::FilesCompression) -> Self {
+            match value {
+                crate::subscription::FilesCompression::None => FilesCompression::None,
+                crate::subscription::FilesCompression::Gzip => FilesCompression::Gzip,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct FilesConfiguration {
+        pub path: String,
+        #[serde(default)]
+        pub rotation: Option<FilesRotation>,
+        #[serde(default)]
+        pub compression: FilesCompression,
+    }
+
+    impl TryFrom<FilesConfiguration> for crate::subscription::FilesConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: FilesConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::FilesConfiguration::new(value.path);
+            config.set_rotation(value.rotation.map(TryInto::try_into).transpose()?);
+            config.set_compression(value.compression.into());
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::FilesConfiguration> for FilesConfiguration {
+        fn from(value: crate::subscription::FilesConfiguration) -> Self {
+            Self {
+                path: value.path().to_owned(),
+                rotation: value.rotation().cloned().map(Into::into),
+                compression: value.compression().into(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct UnixDatagramConfiguration {
+        pub path: String,
+    }
+
+    impl From<UnixDatagramConfiguration> for crate::subscription::UnixDatagramConfiguration {
+        fn from(value: UnixDatagramConfiguration) -> Self {
+            crate::subscription::UnixDatagramConfiguration::new(value.path)
+        }
+    }
+
+    impl From<crate::subscription::UnixDatagramConfiguration> for UnixDatagramConfiguration {
+        fn from(value: crate::subscription::UnixDatagramConfiguration) -> Self {
+            Self {
+                path: value.path().to_string(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct UnixStreamConfiguration {
+        pub path: String,
+        pub framing: TcpFraming,
+        pub reconnect_initial_ms: Option<u64>,
+        pub reconnect_max_ms: Option<u64>,
+    }
+
+    impl TryFrom<UnixStreamConfiguration> for crate::subscription::UnixStreamConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: UnixStreamConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::UnixStreamConfiguration::new(value.path);
+            config.set_framing(value.framing.into());
+            config.set_reconnect(value.reconnect_initial_ms, value.reconnect_max_ms)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::UnixStreamConfiguration> for UnixStreamConfiguration {
+        fn from(value: crate::subscription::UnixStreamConfiguration) -> Self {
+            Self {
+                path: value.path().to_string(),
+                framing: value.framing().into(),
+                reconnect_initial_ms: value.reconnect_initial_ms(),
+                reconnect_max_ms: value.reconnect_max_ms(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct StdStreamConfiguration {
+        pub flush_each: bool,
+    }
+
+    impl From<StdStreamConfiguration> for crate::subscription::StdStreamConfiguration {
+        fn from(value: StdStreamConfiguration) -> Self {
+            let mut config = crate::subscription::StdStreamConfiguration::new();
+            config.set_flush_each(value.flush_each);
+            config
+        }
+    }
+
+    impl From<crate::subscription::StdStreamConfiguration> for StdStreamConfiguration {
+        fn from(value: crate::subscription::StdStreamConfiguration) -> Self {
+            Self {
+                flush_each: value.flush_each(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct NatsConfiguration {
+        pub servers: Vec<String>,
+        pub subject: String,
+        pub jetstream: bool,
+        pub credentials_file: Option<String>,
+        pub tls_certificate_authorities: Vec<String>,
+    }
+
+    impl TryFrom<NatsConfiguration> for crate::subscription::NatsConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: NatsConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::NatsConfiguration::new(
+                value.servers,
+                value.subject,
+                value.jetstream,
+                value.credentials_file,
+                value.tls_certificate_authorities,
+            )
+        }
+    }
+
+    impl From<crate::subscription::NatsConfiguration> for NatsConfiguration {
+        fn from(value: crate::subscription::NatsConfiguration) -> Self {
+            Self {
+                servers: value.servers().to_vec(),
+                subject: value.subject().to_string(),
+                jetstream: value.jetstream(),
+                credentials_file: value.credentials_file().cloned(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_vec(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct MqttConfiguration {
+        pub broker: String,
+        pub port: u16,
+        pub topic: String,
+        pub qos: u8,
+        pub client_id: String,
+        pub username: Option<String>,
+        pub password: Option<String>,
+        pub tls_certificate_authorities: Vec<String>,
+    }
+
+    impl TryFrom<MqttConfiguration> for crate::subscription::MqttConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: MqttConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::MqttConfiguration::new(
+                value.broker,
+                Some(value.port),
+                value.topic,
+                value.qos,
+                value.client_id,
+                value.tls_certificate_authorities,
+            )?;
+            config.set_credentials(value.username, value.password)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::MqttConfiguration> for MqttConfiguration {
+        fn from(value: crate::subscription::MqttConfiguration) -> Self {
+            Self {
+                broker: value.broker().to_string(),
+                port: value.port(),
+                topic: value.topic().to_string(),
+                qos: value.qos(),
+                client_id: value.client_id().to_string(),
+                username: value.username().cloned(),
+                password: value.password().cloned(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_vec(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct EventHubsConfiguration {
+        pub namespace: String,
+        pub hub: String,
+        pub connection_string: Option<String>,
+        pub managed_identity: bool,
+        pub partition_key: Option<String>,
+    }
+
+    impl TryFrom<EventHubsConfiguration> for crate::subscription::EventHubsConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: EventHubsConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::EventHubsConfiguration::new(
+                value.namespace,
+                value.hub,
+                value.connection_string,
+                value.managed_identity,
+                value.partition_key,
+            )
+        }
+    }
+
+    impl From<crate::subscription::EventHubsConfiguration> for EventHubsConfiguration {
+        fn from(value: crate::subscription::EventHubsConfiguration) -> Self {
+            Self {
+                namespace: value.namespace().to_string(),
+                hub: value.hub().to_string(),
+                connection_string: value.connection_string().cloned(),
+                managed_identity: value.managed_identity(),
+                partition_key: value.partition_key().cloned(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct GrpcConfiguration {
+        pub endpoint: String,
+        pub metadata: BTreeMap<String, String>,
+        pub tls_certificate_authorities: Vec<String>,
+    }
+
+    impl TryFrom<GrpcConfiguration> for crate::subscription::GrpcConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: GrpcConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::GrpcConfiguration::new(
+                value.endpoint,
+                value.metadata,
+                value.tls_certificate_authorities,
+            )
+        }
+    }
+
+    impl From<crate::subscription::GrpcConfiguration> for GrpcConfiguration {
+        fn from(value: crate::subscription::GrpcConfiguration) -> Self {
+            Self {
+                endpoint: value.endpoint().to_string(),
+                metadata: value.metadata().clone(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_vec(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct SplunkHecConfiguration {
+        pub url: String,
+        pub token: String,
+        pub index: Option<String>,
+        pub sourcetype: Option<String>,
+        pub source: Option<String>,
+        pub batch_size: u32,
+        pub batch_timeout_ms: u64,
+        #[serde(default)]
+        pub idle_flush_ms: Option<u64>,
+        pub tls_certificate_authorities: Vec<String>,
+    }
+
+    impl TryFrom<SplunkHecConfiguration> for crate::subscription::SplunkHecConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SplunkHecConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::SplunkHecConfiguration::new(
+                value.url,
+                value.token,
+                value.index,
+                value.sourcetype,
+                value.source,
+                value.batch_size,
+                value.batch_timeout_ms,
+                value.tls_certificate_authorities,
+            )?;
+            config.set_idle_flush_ms(value.idle_flush_ms)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::SplunkHecConfiguration> for SplunkHecConfiguration {
+        fn from(value: crate::subscription::SplunkHecConfiguration) -> Self {
+            Self {
+                url: value.url().to_string(),
+                token: value.token().to_string(),
+                index: value.index().cloned(),
+                sourcetype: value.sourcetype().cloned(),
+                source: value.source().cloned(),
+                batch_size: value.batch_size(),
+                batch_timeout_ms: value.batch_timeout_ms(),
+                idle_flush_ms: value.idle_flush_ms(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_vec(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct LokiConfiguration {
+        pub url: String,
+        pub labels: BTreeMap<String, String>,
+        pub label_templates: BTreeMap<String, String>,
+        pub allow_high_cardinality_labels: bool,
+        pub batch_size: u32,
+        pub batch_timeout_ms: u64,
+        #[serde(default)]
+        pub idle_flush_ms: Option<u64>,
+        pub tls_certificate_authorities: Vec<String>,
+    }
+
+    impl TryFrom<LokiConfiguration> for crate::subscription::LokiConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: LokiConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::LokiConfiguration::new(
+                value.url,
+                value.labels,
+                value.label_templates,
+                value.allow_high_cardinality_labels,
+                value.batch_size,
+                value.batch_timeout_ms,
+                value.tls_certificate_authorities,
+            )?;
+            config.set_idle_flush_ms(value.idle_flush_ms)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::LokiConfiguration> for LokiConfiguration {
+        fn from(value: crate::subscription::LokiConfiguration) -> Self {
+            Self {
+                url: value.url().to_string(),
+                labels: value.labels().clone(),
+                label_templates: value.label_templates().clone(),
+                allow_high_cardinality_labels: value.allow_high_cardinality_labels(),
+                batch_size: value.batch_size(),
+                batch_timeout_ms: value.batch_timeout_ms(),
+                idle_flush_ms: value.idle_flush_ms(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_vec(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct ZmqConfiguration {
+        pub endpoint: String,
+        pub socket_type: ZmqSocketType,
+        pub hwm: Option<u32>,
+    }
+
+    impl TryFrom<ZmqConfiguration> for crate::subscription::ZmqConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: ZmqConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::ZmqConfiguration::new(
+                value.endpoint,
+                value.socket_type.into(),
+                value.hwm,
+            )
+        }
+    }
+
+    impl From<crate::subscription::ZmqConfiguration> for ZmqConfiguration {
+        fn from(value: crate::subscription::ZmqConfiguration) -> Self {
+            Self {
+                endpoint: value.endpoint().to_string(),
+                socket_type: value.socket_type().into(),
+                hwm: value.hwm(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum ZmqSocketType {
+        #[default]
+        Push,
+        Pub,
+    }
+
+    impl From<ZmqSocketType> for crate::subscription::ZmqSocketType {
+        fn from(value: ZmqSocketType) -> Self {
+            match value {
+                ZmqSocketType::Push => crate::subscription::ZmqSocketType::Push,
+                ZmqSocketType::Pub => crate::subscription::ZmqSocketType::Pub,
+            }
+        }
+    }
+
+    impl From<crate::subscription::ZmqSocketType> for ZmqSocketType {
+        fn from(value: crate::subscription::ZmqSocketType) -> Self {
+            match value {
+                crate::subscription::ZmqSocketType::Push => ZmqSocketType::Push,
+                crate::subscription::ZmqSocketType::Pub => ZmqSocketType::Pub,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct SyslogTlsConfiguration {
+        pub host: String,
+        pub port: u16,
+        pub tls_certificate_authorities: Vec<String>,
+        pub tls_certificate: String,
+        pub tls_key: String,
+    }
+
+    impl TryFrom<SyslogTlsConfiguration> for crate::subscription::SyslogTlsConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SyslogTlsConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::SyslogTlsConfiguration::new(
+                value.host,
+                value.port,
+                value.tls_certificate_authorities,
+                value.tls_certificate,
+                value.tls_key,
+            )
+        }
+    }
+
+    impl From<crate::subscription::SyslogTlsConfiguration> for SyslogTlsConfiguration {
+        fn from(value: crate::subscription::SyslogTlsConfiguration) -> Self {
+            Self {
+                host: value.host().to_owned(),
+                port: value.port(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_owned(),
+                tls_certificate: value.tls_certificate().to_owned(),
+                tls_key: value.tls_key().to_owned(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum SyslogTransport {
+        #[default]
+        Udp,
+        Tcp,
+        Tls,
+    }
+
+    impl From<SyslogTransport> for crate::subscription::SyslogTransport {
+        fn from(value: SyslogTransport) -> Self {
+            match value {
+                SyslogTransport::Udp => crate::subscription::SyslogTransport::Udp,
+                SyslogTransport::Tcp => crate::subscription::SyslogTransport::Tcp,
+                SyslogTransport::Tls => crate::subscription::SyslogTransport::Tls,
+            }
+        }
+    }
+
+    impl From<crate::subscription::SyslogTransport> for SyslogTransport {
+        fn from(value: crate::subscription::SyslogTransport) -> Self {
+            match value {
+                crate::subscription::SyslogTransport::Udp => SyslogTransport::Udp,
+                crate::subscription::SyslogTransport::Tcp => SyslogTransport::Tcp,
+                crate::subscription::SyslogTransport::Tls => SyslogTransport::Tls,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct SyslogConfiguration {
+        pub host: String,
+        pub port: u16,
+        pub transport: SyslogTransport,
+        pub facility: String,
+        pub app_name: String,
+        pub tls_certificate_authorities: Vec<String>,
+        pub tls_certificate: Option<String>,
+        pub tls_key: Option<String>,
+    }
+
+    impl TryFrom<SyslogConfiguration> for crate::subscription::SyslogConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SyslogConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::SyslogConfiguration::new(
+                value.host,
+                value.port,
+                value.transport.into(),
+                value
+                    .facility
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid syslog facility {:?}", value.facility))?,
+                value.app_name,
+                value.tls_certificate_authorities,
+                value.tls_certificate,
+                value.tls_key,
+            )
+        }
+    }
+
+    impl From<crate::subscription::SyslogConfiguration> for SyslogConfiguration {
+        fn from(value: crate::subscription::SyslogConfiguration) -> Self {
+            Self {
+                host: value.host().to_owned(),
+                port: value.port(),
+                transport: value.transport().into(),
+                facility: value.facility().to_string(),
+                app_name: value.app_name().to_owned(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_owned(),
+                tls_certificate: value.tls_certificate().cloned(),
+                tls_key: value.tls_key().cloned(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum HttpFraming {
+        #[default]
+        Newline,
+        JsonArray,
+    }
+
+    impl From<HttpFraming> for crate::subscription::HttpFraming {
+        fn from(value: HttpFraming) -> Self {
+            match value {
+                HttpFraming::Newline => crate::subscription::HttpFraming::Newline,
+                HttpFraming::JsonArray => crate::subscription::HttpFraming::JsonArray,
+            }
+        }
+    }
+
+    impl From<crate::subscription::HttpFraming> for HttpFraming {
+        fn from(value: crate::subscription::HttpFraming) -> Self {
+            match value {
+                crate::subscription::HttpFraming::Newline => HttpFraming::Newline,
+                crate::subscription::HttpFraming::JsonArray => HttpFraming::JsonArray,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct HttpConfiguration {
+        pub url: String,
+        pub headers: BTreeMap<String, String>,
+        pub batch_size: u32,
+        pub batch_timeout_ms: u64,
+        #[serde(default)]
+        pub idle_flush_ms: Option<u64>,
+        pub tls_certificate_authorities: Vec<String>,
+        pub framing: HttpFraming,
+    }
+
+    impl TryFrom<HttpConfiguration> for crate::subscription::HttpConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: HttpConfiguration) -> Result<Self, Self::Error> {
+            let mut config = crate::subscription::HttpConfiguration::new(
+                value.url,
+                value.headers,
+                value.batch_size,
+                value.batch_timeout_ms,
+                value.tls_certificate_authorities,
+                value.framing.into(),
+            )?;
+            config.set_idle_flush_ms(value.idle_flush_ms)?;
+            Ok(config)
+        }
+    }
+
+    impl From<crate::subscription::HttpConfiguration> for HttpConfiguration {
+        fn from(value: crate::subscription::HttpConfiguration) -> Self {
+            Self {
+                url: value.url().to_owned(),
+                headers: value.headers().clone(),
+                batch_size: value.batch_size(),
+                batch_timeout_ms: value.batch_timeout_ms(),
+                idle_flush_ms: value.idle_flush_ms(),
+                tls_certificate_authorities: value.tls_certificate_authorities().to_owned(),
+                framing: value.framing().into(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) enum SubscriptionOutputDriver {
+        Files(FilesConfiguration),
+        Kafka(KafkaConfiguration),
+        Tcp(TcpConfiguration),
+        Redis(RedisConfiguration),
+        UnixDatagram(UnixDatagramConfiguration),
+        SyslogTls(SyslogTlsConfiguration),
+        Http(HttpConfiguration),
+        Syslog(SyslogConfiguration),
+        UnixStream(UnixStreamConfiguration),
+        Stdout(StdStreamConfiguration),
+        Stderr(StdStreamConfiguration),
+        Nats(NatsConfiguration),
+        Mqtt(MqttConfiguration),
+        EventHubs(EventHubsConfiguration),
+        Grpc(GrpcConfiguration),
+        SplunkHec(SplunkHecConfiguration),
+        Loki(LokiConfiguration),
+        Zmq(ZmqConfiguration),
+    }
+
+    impl TryFrom<SubscriptionOutputDriver> for crate::subscription::SubscriptionOutputDriver {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SubscriptionOutputDriver) -> Result<Self, Self::Error> {
+            Ok(match value {
+                SubscriptionOutputDriver::Files(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Files(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Kafka(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Kafka(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Tcp(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Tcp(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Redis(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Redis(config.try_into()?)
+                }
+                SubscriptionOutputDriver::UnixDatagram(config) => {
+                    crate::subscription::SubscriptionOutputDriver::UnixDatagram(config.into())
+                }
+                SubscriptionOutputDriver::SyslogTls(config) => {
+                    crate::subscription::SubscriptionOutputDriver::SyslogTls(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Http(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Http(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Syslog(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Syslog(config.try_into()?)
+                }
+                SubscriptionOutputDriver::UnixStream(config) => {
+                    crate::subscription::SubscriptionOutputDriver::UnixStream(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Stdout(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Stdout(config.into())
+                }
+                SubscriptionOutputDriver::Stderr(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Stderr(config.into())
+                }
+                SubscriptionOutputDriver::Nats(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Nats(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Mqtt(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Mqtt(config.try_into()?)
+                }
+                SubscriptionOutputDriver::EventHubs(config) => {
+                    crate::subscription::SubscriptionOutputDriver::EventHubs(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Grpc(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Grpc(config.try_into()?)
+                }
+                SubscriptionOutputDriver::SplunkHec(config) => {
+                    crate::subscription::SubscriptionOutputDriver::SplunkHec(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Loki(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Loki(config.try_into()?)
+                }
+                SubscriptionOutputDriver::Zmq(config) => {
+                    crate::subscription::SubscriptionOutputDriver::Zmq(config.try_into()?)
+                }
+            })
+        }
+    }
+
+    impl From<crate::subscription::SubscriptionOutputDriver> for SubscriptionOutputDriver {
+        fn from(value: crate::subscription::SubscriptionOutputDriver) -> Self {
+            match value {
+                crate::subscription::SubscriptionOutputDriver::Files(config) => {
+                    SubscriptionOutputDriver::Files(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Kafka(config) => {
+                    SubscriptionOutputDriver::Kafka(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Tcp(config) => {
+                    SubscriptionOutputDriver::Tcp(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Redis(config) => {
+                    SubscriptionOutputDriver::Redis(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::UnixDatagram(config) => {
+                    SubscriptionOutputDriver::UnixDatagram(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::SyslogTls(config) => {
+                    SubscriptionOutputDriver::SyslogTls(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Http(config) => {
+                    SubscriptionOutputDriver::Http(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Syslog(config) => {
+                    SubscriptionOutputDriver::Syslog(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::UnixStream(config) => {
+                    SubscriptionOutputDriver::UnixStream(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Stdout(config) => {
+                    SubscriptionOutputDriver::Stdout(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Stderr(config) => {
+                    SubscriptionOutputDriver::Stderr(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Nats(config) => {
+                    SubscriptionOutputDriver::Nats(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Mqtt(config) => {
+                    SubscriptionOutputDriver::Mqtt(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::EventHubs(config) => {
+                    SubscriptionOutputDriver::EventHubs(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Grpc(config) => {
+                    SubscriptionOutputDriver::Grpc(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::SplunkHec(config) => {
+                    SubscriptionOutputDriver::SplunkHec(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Loki(config) => {
+                    SubscriptionOutputDriver::Loki(config.into())
+                }
+                crate::subscription::SubscriptionOutputDriver::Zmq(config) => {
+                    SubscriptionOutputDriver::Zmq(config.into())
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum SubscriptionOutputFormat {
+        Json,
+        Raw,
+        RawJson,
+        Nxlog,
+        Csv(CsvConfiguration),
+        Rfc5424(Rfc5424Configuration),
+        Cef(CefConfiguration),
+        Leef,
+        Protobuf(Option<ProtobufConfiguration>),
+        Ecs,
+        Msgpack,
+        Template(TemplateConfiguration),
+        Avro,
+    }
+
+    impl TryFrom<SubscriptionOutputFormat> for crate::subscription::SubscriptionOutputFormat {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SubscriptionOutputFormat) -> Result<Self, Self::Error> {
+            Ok(match value {
+                SubscriptionOutputFormat::Json => {
+                    crate::subscription::SubscriptionOutputFormat::Json
+                }
+                SubscriptionOutputFormat::Raw => crate::subscription::SubscriptionOutputFormat::Raw,
+                SubscriptionOutputFormat::RawJson => {
+                    crate::subscription::SubscriptionOutputFormat::RawJson
+                }
+                SubscriptionOutputFormat::Nxlog => {
+                    crate::subscription::SubscriptionOutputFormat::Nxlog
+                }
+                SubscriptionOutputFormat::Csv(config) => {
+                    crate::subscription::SubscriptionOutputFormat::Csv(config.into())
+                }
+                SubscriptionOutputFormat::Rfc5424(config) => {
+                    crate::subscription::SubscriptionOutputFormat::Rfc5424(config.try_into()?)
+                }
+                SubscriptionOutputFormat::Cef(config) => {
+                    crate::subscription::SubscriptionOutputFormat::Cef(config.try_into()?)
+                }
+                SubscriptionOutputFormat::Leef => {
+                    crate::subscription::SubscriptionOutputFormat::Leef
+                }
+                SubscriptionOutputFormat::Protobuf(config) => {
+                    crate::subscription::SubscriptionOutputFormat::Protobuf(match config {
+                        Some(config) => Some(config.try_into()?),
+                        None => None,
+                    })
+                }
+                SubscriptionOutputFormat::Ecs => crate::subscription::SubscriptionOutputFormat::Ecs,
+                SubscriptionOutputFormat::Msgpack => {
+                    crate::subscription::SubscriptionOutputFormat::Msgpack
+                }
+                SubscriptionOutputFormat::Template(config) => {
+                    crate::subscription::SubscriptionOutputFormat::Template(config.try_into()?)
+                }
+                SubscriptionOutputFormat::Avro => {
+                    crate::subscription::SubscriptionOutputFormat::Avro
+                }
+            })
+        }
+    }
+
+    impl From<crate::subscription::SubscriptionOutputFormat> for SubscriptionOutputFormat {
+        fn from(value: crate::subscription::SubscriptionOutputFormat) -> Self {
+            match value {
+                crate::subscription::SubscriptionOutputFormat::Json => {
+                    SubscriptionOutputFormat::Json
+                }
+                crate::subscription::SubscriptionOutputFormat::Raw => SubscriptionOutputFormat::Raw,
+                crate::subscription::SubscriptionOutputFormat::RawJson => {
+                    SubscriptionOutputFormat::RawJson
+                }
+                crate::subscription::SubscriptionOutputFormat::Nxlog => {
+                    SubscriptionOutputFormat::Nxlog
+                }
+                crate::subscription::SubscriptionOutputFormat::Csv(config) => {
+                    SubscriptionOutputFormat::Csv(config.into())
+                }
+                crate::subscription::SubscriptionOutputFormat::Rfc5424(config) => {
+                    SubscriptionOutputFormat::Rfc5424(config.into())
+                }
+                crate::subscription::SubscriptionOutputFormat::Cef(config) => {
+                    SubscriptionOutputFormat::Cef(config.into())
+                }
+                crate::subscription::SubscriptionOutputFormat::Leef => {
+                    SubscriptionOutputFormat::Leef
+                }
+                crate::subscription::SubscriptionOutputFormat::Protobuf(config) => {
+                    SubscriptionOutputFormat::Protobuf(config.map(Into::into))
+                }
+                crate::subscription::SubscriptionOutputFormat::Ecs => SubscriptionOutputFormat::Ecs,
+                crate::subscription::SubscriptionOutputFormat::Msgpack => {
+                    SubscriptionOutputFormat::Msgpack
+                }
+                crate::subscription::SubscriptionOutputFormat::Template(config) => {
+                    SubscriptionOutputFormat::Template(config.into())
+                }
+                crate::subscription::SubscriptionOutputFormat::Avro => {
+                    SubscriptionOutputFormat::Avro
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct CsvConfiguration {
+        pub columns: Vec<String>,
+        pub delimiter: char,
+        pub header: bool,
+    }
+
+    impl From<CsvConfiguration> for crate::subscription::CsvConfiguration {
+        fn from(value: CsvConfiguration) -> Self {
+            crate::subscription::CsvConfiguration::new(value.columns, value.delimiter, value.header)
+        }
+    }
+
+    impl From<crate::subscription::CsvConfiguration> for CsvConfiguration {
+        fn from(value: crate::subscription::CsvConfiguration) -> Self {
+            Self {
+                columns: value.columns().to_vec(),
+                delimiter: value.delimiter(),
+                header: value.header(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct Rfc5424Configuration {
+        pub enterprise_number: u32,
+        pub structured_data: Vec<StructuredDataElement>,
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct StructuredDataElement {
+        pub id: String,
+        pub params: BTreeMap<String, String>,
+    }
+
+    impl TryFrom<Rfc5424Configuration> for crate::subscription::Rfc5424Configuration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: Rfc5424Configuration) -> Result<Self, Self::Error> {
+            let mut structured_data = Vec::new();
+            for element in value.structured_data {
+                structured_data.push(crate::subscription::StructuredDataElement::try_new(
+                    element.id,
+                    element.params,
+                )?);
+            }
+            crate::subscription::Rfc5424Configuration::try_new(
+                value.enterprise_number,
+                structured_data,
+            )
+        }
+    }
+
+    impl From<crate::subscription::Rfc5424Configuration> for Rfc5424Configuration {
+        fn from(value: crate::subscription::Rfc5424Configuration) -> Self {
+            Self {
+                enterprise_number: value.enterprise_number(),
+                structured_data: value
+                    .structured_data()
+                    .iter()
+                    .map(|element| StructuredDataElement {
+                        id: element.id().to_string(),
+                        params: element.params().clone(),
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct CefConfiguration {
+        pub version: u8,
+        pub device_vendor: String,
+        pub device_product: String,
+        pub device_version: Option<String>,
+    }
+
+    impl TryFrom<CefConfiguration> for crate::subscription::CefConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: CefConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::CefConfiguration::try_new(
+                value.version,
+                value.device_vendor,
+                value.device_product,
+                value.device_version,
+            )
+        }
+    }
+
+    impl From<crate::subscription::CefConfiguration> for CefConfiguration {
+        fn from(value: crate::subscription::CefConfiguration) -> Self {
+            Self {
+                version: value.version(),
+                device_vendor: value.device_vendor().to_string(),
+                device_product: value.device_product().to_string(),
+                device_version: value.device_version().cloned(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct ProtobufConfiguration {
+        pub descriptor_set: Vec<u8>,
+        pub message: String,
+    }
+
+    impl TryFrom<ProtobufConfiguration> for crate::subscription::ProtobufConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: ProtobufConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::ProtobufConfiguration::try_new(value.descriptor_set, value.message)
+        }
+    }
+
+    impl From<crate::subscription::ProtobufConfiguration> for ProtobufConfiguration {
+        fn from(value: crate::subscription::ProtobufConfiguration) -> Self {
+            Self {
+                descriptor_set: value.descriptor_set().to_vec(),
+                message: value.message().to_string(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct TemplateConfiguration {
+        pub template: String,
+    }
+
+    impl TryFrom<TemplateConfiguration> for crate::subscription::TemplateConfiguration {
+        type Error = anyhow::Error;
+
+        fn try_from(value: TemplateConfiguration) -> Result<Self, Self::Error> {
+            crate::subscription::TemplateConfiguration::try_new(value.template)
+        }
+    }
+
+    impl From<crate::subscription::TemplateConfiguration> for TemplateConfiguration {
+        fn from(value: crate::subscription::TemplateConfiguration) -> Self {
+            Self {
+                template: value.template().to_string(),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Eq, PartialEq, Serialize)]
+    pub(super) struct SubscriptionOutput {
+        pub format: SubscriptionOutputFormat,
+        pub driver: SubscriptionOutputDriver,
+        pub enabled: bool,
+        #[serde(default)]
+        pub source_first_seen: bool,
+        #[serde(default)]
+        pub summary: bool,
+        #[serde(default)]
+        pub dead_letter: bool,
+        #[serde(default)]
+        pub sampling: BTreeMap<EventLevel, u8>,
+        #[serde(default)]
+        pub field_map: FieldMap,
+        #[serde(default)]
+        pub provider_route: ProviderRoute,
+        #[serde(default)]
+        pub degradation: Option<OutputDegradation>,
+        #[serde(default)]
+        pub dedupe_on_change: Option<OutputDedupeOnChange>,
+        #[serde(default)]
+        pub payload_compression: PayloadCompression,
+        #[serde(default)]
+        pub max_queue_age: Option<u64>,
+        #[serde(default)]
+        pub include_collector_info: bool,
+        #[serde(default)]
+        pub write_timeout_ms: Option<u64>,
+        #[serde(default)]
+        pub format_options: FormatOptions,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum PayloadCompression {
+        #[default]
+        None,
+        Gzip,
+        Zstd,
+        Lz4,
+    }
+
+    impl From<PayloadCompression> for crate::subscription::PayloadCompression {
+        fn from(value: PayloadCompression) -> Self {
+            match value {
+                PayloadCompression::None => crate::subscription::PayloadCompression::None,
+                PayloadCompression::Gzip => crate::subscription::PayloadCompression::Gzip,
+                PayloadCompression::Zstd => crate::subscription::PayloadCompression::Zstd,
+                PayloadCompression::Lz4 => crate::subscription::PayloadCompression::Lz4,
+            }
+        }
+    }
+
+    impl From<crate::subscription::PayloadCompression> for PayloadCompression {
+        fn from(value: crate::subscription::PayloadCompression) -> Self {
+            match value {
+                crate::subscription::PayloadCompression::None => PayloadCompression::None,
+                crate::subscription::PayloadCompression::Gzip => PayloadCompression::Gzip,
+                crate::subscription::PayloadCompression::Zstd => PayloadCompression::Zstd,
+                crate::subscription::PayloadCompression::Lz4 => PayloadCompression::Lz4,
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Eq, PartialEq, Serialize)]
+    pub(super) struct OutputDedupeOnChange {
+        pub fields: Vec<String>,
+        pub max_sources: u32,
+    }
+
+    impl TryFrom<OutputDedupeOnChange> for crate::subscription::OutputDedupeOnChange {
+        type Error = anyhow::Error;
+
+        fn try_from(value: OutputDedupeOnChange) -> Result<Self, Self::Error> {
+            crate::subscription::OutputDedupeOnChange::try_new(value.fields, value.max_sources)
+        }
+    }
+
+    impl From<crate::subscription::OutputDedupeOnChange> for OutputDedupeOnChange {
+        fn from(value: crate::subscription::OutputDedupeOnChange) -> Self {
+            Self {
+                fields: value.fields().to_vec(),
+                max_sources: value.max_sources(),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+    pub(super) struct OutputDegradation {
+        pub send_timeout_ms: u64,
+        pub slow_responses: u32,
+        pub cooldown_secs: u64,
+    }
+
+    impl TryFrom<OutputDegradation> for crate::subscription::OutputDegradation {
+        type Error = anyhow::Error;
+
+        fn try_from(value: OutputDegradation) -> Result<Self, Self::Error> {
+            crate::subscription::OutputDegradation::try_new(
+                value.send_timeout_ms,
+                value.slow_responses,
+                value.cooldown_secs,
+            )
+        }
+    }
+
+    impl From<crate::subscription::OutputDegradation> for OutputDegradation {
+        fn from(value: crate::subscription::OutputDegradation) -> Self {
+            Self {
+                send_timeout_ms: value.send_timeout_ms(),
+                slow_responses: value.slow_responses(),
+                cooldown_secs: value.cooldown_secs(),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Default, Eq, PartialEq, Serialize)]
+    pub(super) struct FieldMap {
+        pub fields: BTreeMap<String, String>,
+        pub drop_unmapped: bool,
+    }
+
+    impl TryFrom<FieldMap> for crate::subscription::FieldMap {
+        type Error = anyhow::Error;
+
+        fn try_from(value: FieldMap) -> Result<Self, Self::Error> {
+            crate::subscription::FieldMap::try_new(value.fields, value.drop_unmapped)
+        }
+    }
+
+    impl From<crate::subscription::FieldMap> for FieldMap {
+        fn from(value: crate::subscription::FieldMap) -> Self {
+            Self {
+                fields: value.fields().clone(),
+                drop_unmapped: value.drop_unmapped(),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Default, Eq, PartialEq, Serialize)]
+    pub(super) struct FormatOptions {
+        pub pretty: bool,
+        pub timestamp_field: Option<String>,
+        pub include_raw_xml: bool,
+    }
+
+    impl TryFrom<FormatOptions> for crate::subscription::FormatOptions {
+        type Error = anyhow::Error;
+
+        fn try_from(value: FormatOptions) -> Result<Self, Self::Error> {
+            crate::subscription::FormatOptions::try_new(
+                value.pretty,
+                value.timestamp_field,
+                value.include_raw_xml,
+            )
+        }
+    }
+
+    impl From<crate::subscription::FormatOptions> for FormatOptions {
+        fn from(value: crate::subscription::FormatOptions) -> Self {
+            Self {
+                pretty: value.pretty(),
+                timestamp_field: value.timestamp_field().cloned(),
+                include_raw_xml: value.include_raw_xml(),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Default, Eq, PartialEq, Serialize)]
+    pub(super) enum ProviderRoute {
+        #[default]
+        All,
+        Matching(Vec<String>),
+        Unmatched(Vec<String>),
+    }
+
+    impl From<ProviderRoute> for crate::subscription::ProviderRoute {
+        fn from(value: ProviderRoute) -> Self {
+            match value {
+                ProviderRoute::All => crate::subscription::ProviderRoute::All,
+                ProviderRoute::Matching(patterns) => {
+                    crate::subscription::ProviderRoute::Matching(patterns)
+                }
+                ProviderRoute::Unmatched(patterns) => {
+                    crate::subscription::ProviderRoute::Unmatched(patterns)
+                }
+            }
+        }
+    }
+
+    impl From<crate::subscription::ProviderRoute> for ProviderRoute {
+        fn from(value: crate::subscription::ProviderRoute) -> Self {
+            match value {
+                crate::subscription::ProviderRoute::All => ProviderRoute::All,
+                crate::subscription::ProviderRoute::Matching(patterns) => {
+                    ProviderRoute::Matching(patterns)
+                }
+                crate::subscription::ProviderRoute::Unmatched(patterns) => {
+                    ProviderRoute::Unmatched(patterns)
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Deserialize, Serialize)]
+    pub(super) enum EventLevel {
+        LogAlways,
+        Critical,
+        Error,
+        Warning,
+        Information,
+        Verbose,
+    }
+
+    impl From<EventLevel> for crate::subscription::EventLevel {
+        fn from(value: EventLevel) -> Self {
+            match value {
+                EventLevel::LogAlways => crate::subscription::EventLevel::LogAlways,
+                EventLevel::Critical => crate::subscription::EventLevel::Critical,
+                EventLevel::Error => crate::subscription::EventLevel::Error,
+                EventLevel::Warning => crate::subscription::EventLevel::Warning,
+                EventLevel::Information => crate::subscription::EventLevel::Information,
+                EventLevel::Verbose => crate::subscription::EventLevel::Verbose,
+            }
+        }
+    }
+
+    impl From<crate::subscription::EventLevel> for EventLevel {
+        fn from(value: crate::subscription::EventLevel) -> Self {
+            match value {
+                crate::subscription::EventLevel::LogAlways => EventLevel::LogAlways,
+                crate::subscription::EventLevel::Critical => EventLevel::Critical,
+                crate::subscription::EventLevel::Error => EventLevel::Error,
+                crate::subscription::EventLevel::Warning => EventLevel::Warning,
+                crate::subscription::EventLevel::Information => EventLevel::Information,
+                crate::subscription::EventLevel::Verbose => EventLevel::Verbose,
+            }
+        }
+    }
+
+    impl TryFrom<SubscriptionOutput> for crate::subscription::SubscriptionOutput {
+        type Error = anyhow::Error;
+
+        fn try_from(value: SubscriptionOutput) -> Result<Self, Self::Error> {
+            let mut output = crate::subscription::SubscriptionOutput::new(
+                value.format.try_into()?,
+                value.driver.try_into()?,
+                value.enabled,
+            );
+            output.set_source_first_seen(value.source_first_seen);
+            output.set_summary(value.summary);
+            output.set_dead_letter(value.dead_letter);
+            output.set_sampling(
+                value
+                    .sampling
+                    .into_iter()
+                    .map(|(level, rate)| (level.into(), rate))
+                    .collect(),
+            );
+            output.set_field_map(value.field_map.try_into()?);
+            output.set_provider_route(value.provider_route.into());
+            output.set_degradation(value.degradation.map(TryInto::try_into).transpose()?);
+            output.set_dedupe_on_change(value.dedupe_on_change.map(TryInto::try_into).transpose()?);
+            output.set_payload_compression(value.payload_compression.into());
+            output.set_max_queue_age(value.max_queue_age);
+            output.set_include_collector_info(value.include_collector_info);
+            output.set_write_timeout_ms(value.write_timeout_ms);
+            output.set_format_options(value.format_options.try_into()?);
+            Ok(output)
+        }
+    }
+
+    impl From<crate::subscription::SubscriptionOutput> for SubscriptionOutput {
+        fn from(value: crate::subscription::SubscriptionOutput) -> Self {
+            Self {
+                format: value.format().clone().into(),
+                driver: value.driver().clone().into(),
+                enabled: value.enabled(),
+                source_first_seen: value.source_first_seen(),
+                summary: value.summary(),
+                dead_letter: value.dead_letter(),
+                sampling: value
+                    .sampling()
+                    .iter()
+                    .map(|(level, rate)| ((*level).into(), *rate))
+                    .collect(),
+                field_map: value.field_map().clone().into(),
+                provider_route: value.provider_route().clone().into(),
+                degradation: value.degradation().copied().map(Into::into),
+                dedupe_on_change: value.dedupe_on_change().cloned().map(Into::into),
+                payload_compression: value.payload_compression().into(),
+                max_queue_age: value.max_queue_age(),
+                include_collector_info: value.include_collector_info(),
+                write_timeout_ms: value.write_timeout_ms(),
+                format_options: value.format_options().clone().into(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq, Display, Serialize, Deserialize, EnumString)]
+    #[strum(serialize_all = "PascalCase", ascii_case_insensitive)]
+    pub(super) enum ClientFilterOperation {
+        Only,
+        Except,
+    }
+
+    impl From<ClientFilterOperation> for crate::subscription::ClientFilterOperation {
+        fn from(value: ClientFilterOperation) -> Self {
+            match value {
+                ClientFilterOperation::Except => crate::subscription::ClientFilterOperation::Except,
+                ClientFilterOperation::Only => crate::subscription::ClientFilterOperation::Only,
+            }
+        }
+    }
+
+    impl From<crate::subscription::ClientFilterOperation> for ClientFilterOperation  {
+        fn from(value: crate::subscription::ClientFilterOperation) -> Self {
+            match value {
+                crate::subscription::ClientFilterOperation::Except => ClientFilterOperation::Except,
+                crate::subscription::ClientFilterOperation::Only => ClientFilterOperation::Only,
+            }
+        }
     }
 
 
@@ -1030,6 +2457,8 @@ pub mod v3 {
         KerberosPrinc,
         TLSCertSubject,
         MachineID,
+        IpRange,
+        TLSCertSan,
     }
 
     impl From<ClientFilterType> for crate::subscription::ClientFilterType {
@@ -1038,6 +2467,8 @@ pub mod v3 {
                 ClientFilterType::KerberosPrinc => crate::subscription::ClientFilterType::KerberosPrinc,
                 ClientFilterType::TLSCertSubject => crate::subscription::ClientFilterType::TLSCertSubject,
                 ClientFilterType::MachineID => crate::subscription::ClientFilterType::MachineID,
+                ClientFilterType::IpRange => crate::subscription::ClientFilterType::IpRange,
+                ClientFilterType::TLSCertSan => crate::subscription::ClientFilterType::TLSCertSan,
             }
         }
     }
@@ -1048,6 +2479,8 @@ pub mod v3 {
                 crate::subscription::ClientFilterType::KerberosPrinc => ClientFilterType::KerberosPrinc,
                 crate::subscription::ClientFilterType::TLSCertSubject => ClientFilterType::TLSCertSubject,
                 crate::subscription::ClientFilterType::MachineID => ClientFilterType::MachineID,
+                crate::subscription::ClientFilterType::IpRange => ClientFilterType::IpRange,
+                crate::subscription::ClientFilterType::TLSCertSan => ClientFilterType::TLSCertSan,
             }
         }
     }
@@ -1057,6 +2490,7 @@ pub mod v3 {
         pub(super) struct ClientFilterFlags: u32 {
             const CaseInsensitive = 1 << 0;
             const GlobPattern = 1 << 1;
+            const Regex = 1 << 2;
         }
     }
 
@@ -1139,6 +2573,170 @@ pub mod v3 {
         }
     }
 
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum RequireAuth {
+        #[default]
+        Any,
+        Kerberos,
+        TlsCert,
+    }
+
+    impl From<RequireAuth> for crate::subscription::RequireAuth {
+        fn from(value: RequireAuth) -> Self {
+            match value {
+                RequireAuth::Any => crate::subscription::RequireAuth::Any,
+                RequireAuth::Kerberos => crate::subscription::RequireAuth::Kerberos,
+                RequireAuth::TlsCert => crate::subscription::RequireAuth::TlsCert,
+            }
+        }
+    }
+
+    impl From<crate::subscription::RequireAuth> for RequireAuth {
+        fn from(value: crate::subscription::RequireAuth) -> Self {
+            match value {
+                crate::subscription::RequireAuth::Any => RequireAuth::Any,
+                crate::subscription::RequireAuth::Kerberos => RequireAuth::Kerberos,
+                crate::subscription::RequireAuth::TlsCert => RequireAuth::TlsCert,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum EventsCompression {
+        #[default]
+        Sldc,
+        None,
+    }
+
+    impl From<EventsCompression> for crate::subscription::EventsCompression {
+        fn from(value: EventsCompression) -> Self {
+            match value {
+                EventsCompression::Sldc => crate::subscription::EventsCompression::Sldc,
+                EventsCompression::None => crate::subscription::EventsCompression::None,
+            }
+        }
+    }
+
+    impl From<crate::subscription::EventsCompression> for EventsCompression {
+        fn from(value: crate::subscription::EventsCompression) -> Self {
+            match value {
+                crate::subscription::EventsCompression::Sldc => EventsCompression::Sldc,
+                crate::subscription::EventsCompression::None => EventsCompression::None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum OutputMode {
+        #[default]
+        Parallel,
+        Sequential,
+    }
+
+    impl From<OutputMode> for crate::subscription::OutputMode {
+        fn from(value: OutputMode) -> Self {
+            match value {
+                OutputMode::Parallel => crate::subscription::OutputMode::Parallel,
+                OutputMode::Sequential => crate::subscription::OutputMode::Sequential,
+            }
+        }
+    }
+
+    impl From<crate::subscription::OutputMode> for OutputMode {
+        fn from(value: crate::subscription::OutputMode) -> Self {
+            match value {
+                crate::subscription::OutputMode::Parallel => OutputMode::Parallel,
+                crate::subscription::OutputMode::Sequential => OutputMode::Sequential,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum RenderingElement {
+        Message,
+        Level,
+        Task,
+        Opcode,
+        Channel,
+        Provider,
+        Keywords,
+    }
+
+    impl From<RenderingElement> for crate::subscription::RenderingElement {
+        fn from(value: RenderingElement) -> Self {
+            match value {
+                RenderingElement::Message => crate::subscription::RenderingElement::Message,
+                RenderingElement::Level => crate::subscription::RenderingElement::Level,
+                RenderingElement::Task => crate::subscription::RenderingElement::Task,
+                RenderingElement::Opcode => crate::subscription::RenderingElement::Opcode,
+                RenderingElement::Channel => crate::subscription::RenderingElement::Channel,
+                RenderingElement::Provider => crate::subscription::RenderingElement::Provider,
+                RenderingElement::Keywords => crate::subscription::RenderingElement::Keywords,
+            }
+        }
+    }
+
+    impl From<crate::subscription::RenderingElement> for RenderingElement {
+        fn from(value: crate::subscription::RenderingElement) -> Self {
+            match value {
+                crate::subscription::RenderingElement::Message => RenderingElement::Message,
+                crate::subscription::RenderingElement::Level => RenderingElement::Level,
+                crate::subscription::RenderingElement::Task => RenderingElement::Task,
+                crate::subscription::RenderingElement::Opcode => RenderingElement::Opcode,
+                crate::subscription::RenderingElement::Channel => RenderingElement::Channel,
+                crate::subscription::RenderingElement::Provider => RenderingElement::Provider,
+                crate::subscription::RenderingElement::Keywords => RenderingElement::Keywords,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+    pub(super) enum ManifestOption {
+        ContentFormat,
+        IgnoreChannelError,
+        ReadExistingEvents,
+        Cdata,
+        Locale,
+        DataLocale,
+        MaxElements,
+    }
+
+    impl From<ManifestOption> for crate::subscription::ManifestOption {
+        fn from(value: ManifestOption) -> Self {
+            match value {
+                ManifestOption::ContentFormat => crate::subscription::ManifestOption::ContentFormat,
+                ManifestOption::IgnoreChannelError => {
+                    crate::subscription::ManifestOption::IgnoreChannelError
+                }
+                ManifestOption::ReadExistingEvents => {
+                    crate::subscription::ManifestOption::ReadExistingEvents
+                }
+                ManifestOption::Cdata => crate::subscription::ManifestOption::Cdata,
+                ManifestOption::Locale => crate::subscription::ManifestOption::Locale,
+                ManifestOption::DataLocale => crate::subscription::ManifestOption::DataLocale,
+                ManifestOption::MaxElements => crate::subscription::ManifestOption::MaxElements,
+            }
+        }
+    }
+
+    impl From<crate::subscription::ManifestOption> for ManifestOption {
+        fn from(value: crate::subscription::ManifestOption) -> Self {
+            match value {
+                crate::subscription::ManifestOption::ContentFormat => ManifestOption::ContentFormat,
+                crate::subscription::ManifestOption::IgnoreChannelError => {
+                    ManifestOption::IgnoreChannelError
+                }
+                crate::subscription::ManifestOption::ReadExistingEvents => {
+                    ManifestOption::ReadExistingEvents
+                }
+                crate::subscription::ManifestOption::Cdata => ManifestOption::Cdata,
+                crate::subscription::ManifestOption::Locale => ManifestOption::Locale,
+                crate::subscription::ManifestOption::DataLocale => ManifestOption::DataLocale,
+                crate::subscription::ManifestOption::MaxElements => ManifestOption::MaxElements,
+            }
+        }
+    }
+
     #[derive(Debug, PartialEq, Clone, Eq, Deserialize, Serialize)]
     pub(super) struct SubscriptionData {
         pub uuid: Uuid,
@@ -1159,6 +2757,40 @@ pub mod v3 {
         pub locale: Option<String>,
         pub data_locale: Option<String>,
         pub filter: Option<ClientFilter>,
+        #[serde(default)]
+        pub require_auth: RequireAuth,
+        #[serde(default)]
+        pub compression: EventsCompression,
+        #[serde(default)]
+        pub output_mode: OutputMode,
+        #[serde(default)]
+        pub read_existing_events_since: Option<String>,
+        #[serde(default)]
+        pub ignore_channel_error_for: Option<Vec<String>>,
+        #[serde(default)]
+        pub connection_retry_jitter: Option<u8>,
+        #[serde(default)]
+        pub max_concurrent_retries: Option<u32>,
+        #[serde(default)]
+        pub max_queued_events: Option<u32>,
+        #[serde(default)]
+        pub max_queued_bytes: Option<u64>,
+        #[serde(default)]
+        pub max_concurrent_batches: Option<u32>,
+        #[serde(default)]
+        pub max_concurrent_clients: Option<u32>,
+        #[serde(default)]
+        pub dedup_window: Option<u32>,
+        #[serde(default)]
+        pub dedup_window_ttl: Option<u32>,
+        #[serde(default)]
+        pub event_ttl: Option<u32>,
+        #[serde(default)]
+        pub trim_rendering: Vec<RenderingElement>,
+        #[serde(default)]
+        pub enrollment_fallback: Vec<ManifestOption>,
+        #[serde(default)]
+        pub tenant: Option<String>,
         pub outputs: Vec<SubscriptionOutput>,
     }
 
@@ -1184,13 +2816,48 @@ pub mod v3 {
                 .set_ignore_channel_error(value.ignore_channel_error)
                 .set_locale(value.locale)
                 .set_data_locale(value.data_locale)
+                .set_require_auth(value.require_auth.into())
+                .set_compression(value.compression.into())
+                .set_output_mode(value.output_mode.into())
+                .set_connection_retry_jitter(value.connection_retry_jitter)
+                .set_max_concurrent_retries(value.max_concurrent_retries)
+                .set_max_queued_events(value.max_queued_events)
+                .set_max_queued_bytes(value.max_queued_bytes)
+                .set_max_concurrent_batches(value.max_concurrent_batches)
+                .set_max_concurrent_clients(value.max_concurrent_clients)
+                .set_dedup_window(value.dedup_window)
+                .set_dedup_window_ttl(value.dedup_window_ttl)
+                .set_event_ttl(value.event_ttl)
+                .set_trim_rendering(value.trim_rendering.into_iter().map(Into::into).collect())
+                .set_enrollment_fallback(
+                    value
+                        .enrollment_fallback
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                )
                 .set_outputs(outputs?)
                 .set_revision(value.revision);
 
+            if let Some(tenant) = value.tenant {
+                crate::subscription::check_tenant(&tenant)?;
+                data.set_tenant(Some(tenant));
+            }
+
             if let Some(filter) = value.filter {
                 data.set_client_filter(Some(filter.try_into()?));
             }
 
+            if let Some(since) = value.read_existing_events_since {
+                data.set_read_existing_events_since(Some(crate::utils::parse_rfc3339_utc(&since)?));
+            }
+
+            data.set_ignore_channel_error_for(
+                value
+                    .ignore_channel_error_for
+                    .map(|channels| channels.into_iter().collect()),
+            );
+
             // Note: internal version is not exported nor set
             Ok(data)
         }
@@ -1218,6 +2885,31 @@ pub mod v3 {
                 locale: value.locale().cloned(),
                 data_locale: value.data_locale().cloned(),
                 filter: value.client_filter().cloned().map(Into::into),
+                require_auth: value.require_auth().into(),
+                compression: value.compression().into(),
+                output_mode: value.output_mode().into(),
+                read_existing_events_since: value
+                    .read_existing_events_since()
+                    .map(|since| since.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)),
+                ignore_channel_error_for: value
+                    .ignore_channel_error_for()
+                    .map(|channels| channels.iter().cloned().collect()),
+                connection_retry_jitter: value.connection_retry_jitter(),
+                max_concurrent_retries: value.max_concurrent_retries(),
+                max_queued_events: value.max_queued_events(),
+                max_queued_bytes: value.max_queued_bytes(),
+                max_concurrent_batches: value.max_concurrent_batches(),
+                max_concurrent_clients: value.max_concurrent_clients(),
+                dedup_window: value.dedup_window(),
+                dedup_window_ttl: value.dedup_window_ttl(),
+                event_ttl: value.event_ttl(),
+                trim_rendering: value.trim_rendering().iter().map(|e| (*e).into()).collect(),
+                enrollment_fallback: value
+                    .enrollment_fallback()
+                    .iter()
+                    .map(|o| (*o).into())
+                    .collect(),
+                tenant: value.tenant().cloned(),
                 outputs: value.outputs().iter().map(|o| o.clone().into()).collect(),
             }
         }
@@ -1253,12 +2945,320 @@ pub mod v3 {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use std::collections::HashSet;
+    use std::{
+        collections::{BTreeMap, BTreeSet, HashSet},
+        path::PathBuf,
+    };
 
     use super::{parse, serialize};
 
     #[test]
     fn test_export_import() -> Result<()> {
+        let mut tcp_config = crate::subscription::TcpConfiguration::new(
+            "127.0.0.1".to_string(),
+            5000,
+            false,
+            vec![],
+            None,
+            None,
+        )?;
+        tcp_config.set_framing(crate::subscription::TcpFraming::LengthPrefixed);
+        tcp_config.set_reconnect(Some(100), Some(10_000))?;
+        tcp_config.set_buffer_max_events(Some(5000))?;
+        tcp_config.set_tcp_keepalive_secs(Some(30))?;
+        tcp_config.set_tcp_nodelay(true);
+        tcp_config.set_tls_pinned_sha256(vec![
+            "5c1f07c2e06c4c6e9a8f29e0e2b1ad1f33c9d3a6e0bfc0c9dbc4f7a1c1f6b4e2".to_string(),
+        ])?;
+        let mut output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Tcp(tcp_config),
+            true,
+        );
+        output.set_provider_route(crate::subscription::ProviderRoute::Matching(vec![
+            "Microsoft-Windows-Sysmon".to_string(),
+        ]));
+        output.set_degradation(Some(crate::subscription::OutputDegradation::try_new(
+            500, 3, 60,
+        )?));
+        output.set_dedupe_on_change(Some(crate::subscription::OutputDedupeOnChange::try_new(
+            vec!["EventData.Value".to_string()],
+            100,
+        )?));
+        output.set_payload_compression(crate::subscription::PayloadCompression::Zstd);
+        output.set_max_queue_age(Some(300));
+        output.set_include_collector_info(true);
+        output.set_write_timeout_ms(Some(2000));
+        output.set_format_options(crate::subscription::FormatOptions::try_new(
+            true,
+            Some("@timestamp".to_string()),
+            true,
+        )?);
+
+        let syslog_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Rfc5424(
+                crate::subscription::Rfc5424Configuration::try_new(32473, Vec::new())?,
+            ),
+            crate::subscription::SubscriptionOutputDriver::SyslogTls(
+                crate::subscription::SyslogTlsConfiguration::new(
+                    "syslog.windomain.local".to_string(),
+                    6514,
+                    vec!["/etc/openwec/ca.pem".to_string()],
+                    "/etc/openwec/client.pem".to_string(),
+                    "/etc/openwec/client.key".to_string(),
+                )?,
+            ),
+            true,
+        );
+
+        let cef_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Cef(
+                crate::subscription::CefConfiguration::try_new(
+                    1,
+                    "Windomain".to_string(),
+                    "WEC".to_string(),
+                    Some("2.0".to_string()),
+                )?,
+            ),
+            crate::subscription::SubscriptionOutputDriver::Tcp(
+                crate::subscription::TcpConfiguration::new(
+                    "127.0.0.1".to_string(),
+                    5001,
+                    false,
+                    vec![],
+                    None,
+                    None,
+                )?,
+            ),
+            true,
+        );
+
+        let leef_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Leef,
+            crate::subscription::SubscriptionOutputDriver::Tcp(
+                crate::subscription::TcpConfiguration::new(
+                    "127.0.0.1".to_string(),
+                    5002,
+                    false,
+                    vec![],
+                    None,
+                    None,
+                )?,
+            ),
+            true,
+        );
+
+        let mut descriptor_set = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        descriptor_set.pop();
+        descriptor_set.push("tests/protobuf/event.binpb");
+        let mut protobuf_tcp_config = crate::subscription::TcpConfiguration::new(
+            "127.0.0.1".to_string(),
+            5003,
+            false,
+            vec![],
+            None,
+            None,
+        )?;
+        protobuf_tcp_config.set_framing(crate::subscription::TcpFraming::LengthPrefixed);
+        let protobuf_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Protobuf(Some(
+                crate::subscription::ProtobufConfiguration::try_new(
+                    std::fs::read(descriptor_set)?,
+                    "openwec.test.Event".to_string(),
+                )?,
+            )),
+            crate::subscription::SubscriptionOutputDriver::Tcp(protobuf_tcp_config),
+            true,
+        );
+
+        let mut redis_config = crate::subscription::RedisConfiguration::new(
+            "localhost:6379".to_string(),
+            "events".to_string(),
+        );
+        redis_config.set_mode(crate::subscription::RedisMode::Stream, Some(10000))?;
+        let redis_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Redis(redis_config),
+            true,
+        );
+
+        let syslog_driver_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Syslog(
+                crate::subscription::SyslogConfiguration::new(
+                    "syslog.windomain.local".to_string(),
+                    6514,
+                    crate::subscription::SyslogTransport::Tls,
+                    crate::subscription::SyslogFacility::Local4,
+                    "wec".to_string(),
+                    vec!["/etc/openwec/ca.pem".to_string()],
+                    None,
+                    None,
+                )?,
+            ),
+            true,
+        );
+
+        let mut http_config = crate::subscription::HttpConfiguration::new(
+            "https://collector.windomain.local/events".to_string(),
+            BTreeMap::from([("X-Source".to_string(), "openwec".to_string())]),
+            500,
+            200,
+            vec!["/etc/openwec/ca.pem".to_string()],
+            crate::subscription::HttpFraming::JsonArray,
+        )?;
+        http_config.set_idle_flush_ms(Some(50))?;
+        let http_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Http(http_config),
+            true,
+        );
+
+        let mut unix_stream_config = crate::subscription::UnixStreamConfiguration::new(
+            "/run/openwec/events.socket".to_string(),
+        );
+        unix_stream_config.set_framing(crate::subscription::TcpFraming::LengthPrefixed);
+        unix_stream_config.set_reconnect(Some(100), None)?;
+        let unix_stream_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Msgpack,
+            crate::subscription::SubscriptionOutputDriver::UnixStream(unix_stream_config),
+            true,
+        );
+
+        let mut stdout_config = crate::subscription::StdStreamConfiguration::new();
+        stdout_config.set_flush_each(true);
+        let stdout_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Ecs,
+            crate::subscription::SubscriptionOutputDriver::Stdout(stdout_config),
+            true,
+        );
+
+        let stderr_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Template(
+                crate::subscription::TemplateConfiguration::try_new(
+                    r"{TimeCreated} {Computer} [{EventID}] \{{RenderingInfo}\}".to_string(),
+                )?,
+            ),
+            crate::subscription::SubscriptionOutputDriver::Stderr(
+                crate::subscription::StdStreamConfiguration::new(),
+            ),
+            true,
+        );
+
+        let nats_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Nats(
+                crate::subscription::NatsConfiguration::new(
+                    vec!["tls://nats.windomain.local:4222".to_string()],
+                    "openwec.{ip}.{principal}".to_string(),
+                    true,
+                    Some("/etc/openwec/nats.creds".to_string()),
+                    vec!["/etc/openwec/ca.pem".to_string()],
+                )?,
+            ),
+            true,
+        );
+
+        let mut mqtt_config = crate::subscription::MqttConfiguration::new(
+            "mqtt.windomain.local".to_string(),
+            None,
+            "openwec/{ip}".to_string(),
+            1,
+            "openwec".to_string(),
+            vec!["/etc/openwec/ca.pem".to_string()],
+        )?;
+        mqtt_config.set_credentials(Some("openwec".to_string()), Some("secret".to_string()))?;
+        let mqtt_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Mqtt(mqtt_config),
+            true,
+        );
+
+        let event_hubs_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Raw,
+            crate::subscription::SubscriptionOutputDriver::EventHubs(
+                crate::subscription::EventHubsConfiguration::new(
+                    "openwec".to_string(),
+                    "events".to_string(),
+                    None,
+                    true,
+                    Some("{ip}".to_string()),
+                )?,
+            ),
+            true,
+        );
+
+        let grpc_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Grpc(
+                crate::subscription::GrpcConfiguration::new(
+                    "https://ingest.windomain.local:443".to_string(),
+                    BTreeMap::from([("authorization".to_string(), "Bearer secret".to_string())]),
+                    vec!["/etc/openwec/ca.pem".to_string()],
+                )?,
+            ),
+            true,
+        );
+
+        let splunk_hec_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::SplunkHec(
+                crate::subscription::SplunkHecConfiguration::new(
+                    "https://splunk.windomain.local:8088/services/collector/event".to_string(),
+                    "11111111-2222-3333-4444-555555555555".to_string(),
+                    Some("wineventlog".to_string()),
+                    Some("XmlWinEventLog".to_string()),
+                    Some("openwec:{principal}".to_string()),
+                    100,
+                    1_000,
+                    vec!["/etc/openwec/ca.pem".to_string()],
+                )?,
+            ),
+            true,
+        );
+
+        let loki_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Loki(
+                crate::subscription::LokiConfiguration::new(
+                    "https://loki.windomain.local".to_string(),
+                    BTreeMap::from([("job".to_string(), "openwec".to_string())]),
+                    BTreeMap::from([("user".to_string(), "{principal}".to_string())]),
+                    true,
+                    100,
+                    1_000,
+                    vec!["/etc/openwec/ca.pem".to_string()],
+                )?,
+            ),
+            true,
+        );
+
+        let zmq_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Json,
+            crate::subscription::SubscriptionOutputDriver::Zmq(
+                crate::subscription::ZmqConfiguration::new(
+                    "tcp://127.0.0.1:5555".to_string(),
+                    crate::subscription::ZmqSocketType::Pub,
+                    Some(10_000),
+                )?,
+            ),
+            true,
+        );
+
+        let mut files_config = crate::subscription::FilesConfiguration::new(
+            "/var/events/{ip}/{principal}/messages".to_string(),
+        );
+        files_config.set_rotation(Some(crate::subscription::FilesRotation::new(
+            Some(100 * 1024 * 1024),
+            Some(86400),
+        )?));
+        files_config.set_compression(crate::subscription::FilesCompression::Gzip);
+        let files_output = crate::subscription::SubscriptionOutput::new(
+            crate::subscription::SubscriptionOutputFormat::Avro,
+            crate::subscription::SubscriptionOutputDriver::Files(files_config),
+            true,
+        );
+
         let mut subscription =
             crate::subscription::SubscriptionData::new("my-subscription", "my-query");
         let mut targets = HashSet::new();
@@ -1276,6 +3276,32 @@ mod tests {
             .set_max_time(1)
             .set_max_elements(Some(100))
             .set_read_existing_events(false)
+            .set_require_auth(crate::subscription::RequireAuth::TlsCert)
+            .set_compression(crate::subscription::EventsCompression::None)
+            .set_output_mode(crate::subscription::OutputMode::Sequential)
+            .set_read_existing_events_since(Some(crate::utils::parse_rfc3339_utc(
+                "2024-03-01T10:00:00Z",
+            )?))
+            .set_ignore_channel_error_for(Some(BTreeSet::from([
+                "Microsoft-Windows-Sysmon/Operational".to_string(),
+            ])))
+            .set_connection_retry_jitter(Some(20))
+            .set_max_concurrent_retries(Some(4))
+            .set_max_queued_events(Some(1000))
+            .set_max_queued_bytes(Some(1024 * 1024))
+            .set_max_concurrent_batches(Some(8))
+            .set_max_concurrent_clients(Some(500))
+            .set_dedup_window(Some(100_000))
+            .set_dedup_window_ttl(Some(3600))
+            .set_event_ttl(Some(3600))
+            .set_trim_rendering(BTreeSet::from([
+                crate::subscription::RenderingElement::Message,
+                crate::subscription::RenderingElement::Keywords,
+            ]))
+            .set_enrollment_fallback(BTreeSet::from([
+                crate::subscription::ManifestOption::Locale,
+                crate::subscription::ManifestOption::DataLocale,
+            ]))
             .set_uri(Some("toto".to_string()))
             .set_client_filter(Some(crate::subscription::ClientFilter::try_new(
                 crate::subscription::ClientFilterOperation::Except,
@@ -1283,21 +3309,29 @@ mod tests {
                 crate::subscription::ClientFilterFlags::empty(),
                 targets,
             )?))
-            .set_outputs(vec![crate::subscription::SubscriptionOutput::new(
-                crate::subscription::SubscriptionOutputFormat::Json,
-                crate::subscription::SubscriptionOutputDriver::Tcp(
-                    crate::subscription::TcpConfiguration::new(
-                        "127.0.0.1".to_string(),
-                        5000,
-                        false,
-                        vec![],
-                        None,
-                        None,
-                    )?,
-                ),
-                true,
-            )])
-            .set_revision(Some("1234".to_string()));
+            .set_outputs(vec![
+                output,
+                syslog_output,
+                cef_output,
+                leef_output,
+                protobuf_output,
+                redis_output,
+                http_output,
+                syslog_driver_output,
+                files_output,
+                unix_stream_output,
+                stdout_output,
+                stderr_output,
+                nats_output,
+                mqtt_output,
+                event_hubs_output,
+                grpc_output,
+                splunk_hec_output,
+                loki_output,
+                zmq_output,
+            ])
+            .set_revision(Some("1234".to_string()))
+            .set_tenant(Some("team-a".to_string()));
 
         let subscriptions = vec![subscription.clone()];
         let content = serialize(&subscriptions)?;
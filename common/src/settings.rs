@@ -1,4 +1,4 @@
-use anyhow::{Error, Result};
+use anyhow::{bail, Error, Result};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -256,17 +256,94 @@ impl Logging {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeReceivedSource {
+    // Wall clock, read each time an event batch is received
+    #[default]
+    System,
+    // Monotonic clock anchored to a single wall clock read at startup
+    MonotonicAnchored,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOverflowPolicy {
+    // Wait until enough queued events have been processed
+    #[default]
+    Block,
+    // Reject the oldest queued batches
+    DropOldest,
+    // Reject the incoming batch
+    DropNewest,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSkewPolicy {
+    // Keep the event and flag it as clock skewed
+    #[default]
+    Tag,
+    // Replace TimeCreated by the reception time, keeping the original value
+    Clamp,
+    // Drop the event
+    Drop,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxSubscriptionsPolicy {
+    // Refuse to load the subscriptions
+    #[default]
+    Error,
+    // Only load the first subscriptions (by name)
+    Warn,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DbStartupPolicy {
+    // Refuse to start
+    #[default]
+    Fail,
+    // Try to connect again until db_startup_timeout is reached
+    Retry,
+    // Start without database and persist data once it is available
+    Degraded,
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 #[serde(deny_unknown_fields)]
 pub struct Server {
     db_sync_interval: Option<u64>,
     flush_heartbeats_interval: Option<u64>,
     heartbeats_queue_size: Option<u64>,
+    heartbeats_retention_days: Option<u64>,
     node_name: Option<String>,
     keytab: Option<String>,
     tcp_keepalive_time: Option<u64>,
     tcp_keepalive_intvl: Option<u64>,
     tcp_keepalive_probes: Option<u32>,
+    #[serde(default)]
+    time_received_source: TimeReceivedSource,
+    max_clock_skew: Option<u64>,
+    #[serde(default)]
+    clock_skew_policy: ClockSkewPolicy,
+    max_queued_events: Option<u64>,
+    max_queued_bytes: Option<u64>,
+    #[serde(default)]
+    queue_overflow_policy: QueueOverflowPolicy,
+    max_subscriptions: Option<u32>,
+    #[serde(default)]
+    max_subscriptions_policy: MaxSubscriptionsPolicy,
+    no_sources_warning_delay: Option<u64>,
+    event_names: Option<bool>,
+    event_names_file: Option<String>,
+    #[serde(default)]
+    db_startup_policy: DbStartupPolicy,
+    db_startup_timeout: Option<u64>,
+    db_startup_max_retry_interval: Option<u64>,
+    subscriptions_cache_file: Option<String>,
 }
 
 impl Server {
@@ -286,6 +363,10 @@ impl Server {
         self.heartbeats_queue_size.unwrap_or(2048)
     }
 
+    pub fn heartbeats_retention_days(&self) -> Option<u64> {
+        self.heartbeats_retention_days
+    }
+
     pub fn keytab(&self) -> Option<&String> {
         self.keytab.as_ref()
     }
@@ -301,6 +382,88 @@ impl Server {
     pub fn tcp_keepalive_probes(&self) -> Option<u32> {
         self.tcp_keepalive_probes
     }
+
+    pub fn time_received_source(&self) -> TimeReceivedSource {
+        self.time_received_source
+    }
+
+    pub fn max_clock_skew(&self) -> Option<u64> {
+        self.max_clock_skew
+    }
+
+    pub fn clock_skew_policy(&self) -> ClockSkewPolicy {
+        self.clock_skew_policy
+    }
+
+    pub fn max_queued_events(&self) -> Option<u64> {
+        self.max_queued_events
+    }
+
+    pub fn max_queued_bytes(&self) -> Option<u64> {
+        self.max_queued_bytes
+    }
+
+    pub fn queue_overflow_policy(&self) -> QueueOverflowPolicy {
+        self.queue_overflow_policy
+    }
+
+    pub fn max_subscriptions(&self) -> Option<u32> {
+        self.max_subscriptions
+    }
+
+    pub fn max_subscriptions_policy(&self) -> MaxSubscriptionsPolicy {
+        self.max_subscriptions_policy
+    }
+
+    pub fn no_sources_warning_delay(&self) -> Option<u64> {
+        self.no_sources_warning_delay
+    }
+
+    pub fn event_names(&self) -> bool {
+        self.event_names.unwrap_or(false)
+    }
+
+    pub fn event_names_file(&self) -> Option<&String> {
+        self.event_names_file.as_ref()
+    }
+
+    pub fn db_startup_policy(&self) -> DbStartupPolicy {
+        self.db_startup_policy
+    }
+
+    pub fn db_startup_timeout(&self) -> u64 {
+        self.db_startup_timeout.unwrap_or(300)
+    }
+
+    pub fn db_startup_max_retry_interval(&self) -> u64 {
+        self.db_startup_max_retry_interval.unwrap_or(30)
+    }
+
+    pub fn subscriptions_cache_file(&self) -> Option<&String> {
+        self.subscriptions_cache_file.as_ref()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.max_queued_events == Some(0) {
+            bail!("server.max_queued_events must be greater than 0");
+        }
+        if self.max_queued_bytes == Some(0) {
+            bail!("server.max_queued_bytes must be greater than 0");
+        }
+        if self.max_subscriptions == Some(0) {
+            bail!("server.max_subscriptions must be greater than 0");
+        }
+        if self.heartbeats_retention_days == Some(0) {
+            bail!("server.heartbeats_retention_days must be greater than 0");
+        }
+        if self.no_sources_warning_delay == Some(0) {
+            bail!("server.no_sources_warning_delay must be greater than 0");
+        }
+        if self.db_startup_max_retry_interval == Some(0) {
+            bail!("server.db_startup_max_retry_interval must be greater than 0");
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -318,17 +481,36 @@ impl Cli {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PathAnonymization {
+    // Paths are logged as written
+    #[default]
+    Disabled,
+    // Principal and IP address are replaced by a hash
+    Hash,
+    // Only the first characters of principal and IP address are kept
+    Truncate,
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 #[serde(deny_unknown_fields)]
 pub struct FilesOutput {
     // Time after which an unused file descriptor is closed
     files_descriptor_close_timeout: Option<u64>,
+    // How principal and IP address are hidden in logged paths
+    #[serde(default)]
+    log_path_anonymization: PathAnonymization,
 }
 
 impl FilesOutput {
     pub fn files_descriptor_close_timeout(&self) -> u64 {
         self.files_descriptor_close_timeout.unwrap_or(600)
     }
+
+    pub fn log_path_anonymization(&self) -> PathAnonymization {
+        self.log_path_anonymization
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -422,6 +604,23 @@ impl Monitoring {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Health {
+    listen_address: String,
+    listen_port: u16,
+}
+
+impl Health {
+    pub fn listen_address(&self) -> &str {
+        &self.listen_address
+    }
+
+    pub fn listen_port(&self) -> u16 {
+        self.listen_port
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct Settings {
@@ -437,12 +636,16 @@ pub struct Settings {
     outputs: Outputs,
     #[serde(default)]
     monitoring: Option<Monitoring>,
+    #[serde(default)]
+    health: Option<Health>,
 }
 
 impl std::str::FromStr for Settings {
     type Err = anyhow::Error;
     fn from_str(content: &str) -> Result<Self> {
-        toml::from_str(content).map_err(anyhow::Error::from)
+        let settings: Settings = toml::from_str(content).map_err(anyhow::Error::from)?;
+        settings.server.validate()?;
+        Ok(settings)
     }
 }
 
@@ -482,6 +685,10 @@ impl Settings {
     pub fn monitoring(&self) -> Option<&Monitoring> {
         self.monitoring.as_ref()
     }
+
+    pub fn health(&self) -> Option<&Health> {
+        self.health.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -494,6 +701,22 @@ mod tests {
         tcp_keepalive_time = 3600
         tcp_keepalive_intvl = 1
         tcp_keepalive_probes = 10
+        time_received_source = "monotonic_anchored"
+        max_clock_skew = 300
+        clock_skew_policy = "clamp"
+        max_queued_events = 100000
+        max_queued_bytes = 268435456
+        queue_overflow_policy = "drop_oldest"
+        max_subscriptions = 1000
+        max_subscriptions_policy = "warn"
+        no_sources_warning_delay = 3600
+        heartbeats_retention_days = 30
+        event_names = true
+        event_names_file = "/etc/openwec/event_names.toml"
+        db_startup_policy = "degraded"
+        db_startup_timeout = 60
+        db_startup_max_retry_interval = 10
+        subscriptions_cache_file = "/var/lib/openwec/subscriptions.json"
 
         [database]
         type =  "SQLite"
@@ -548,8 +771,40 @@ mod tests {
         assert_eq!(s.server().tcp_keepalive_time(), 3600);
         assert_eq!(s.server().tcp_keepalive_intvl().unwrap(), 1);
         assert_eq!(s.server().tcp_keepalive_probes().unwrap(), 10);
+        assert_eq!(
+            s.server().time_received_source(),
+            TimeReceivedSource::MonotonicAnchored
+        );
+        assert_eq!(s.server().max_clock_skew(), Some(300));
+        assert_eq!(s.server().clock_skew_policy(), ClockSkewPolicy::Clamp);
+        assert_eq!(s.server().max_queued_events(), Some(100000));
+        assert_eq!(s.server().max_queued_bytes(), Some(268435456));
+        assert_eq!(
+            s.server().queue_overflow_policy(),
+            QueueOverflowPolicy::DropOldest
+        );
+        assert_eq!(s.server().max_subscriptions(), Some(1000));
+        assert_eq!(
+            s.server().max_subscriptions_policy(),
+            MaxSubscriptionsPolicy::Warn
+        );
+        assert_eq!(s.server().no_sources_warning_delay(), Some(3600));
+        assert_eq!(s.server().heartbeats_retention_days(), Some(30));
+        assert!(s.server().event_names());
+        assert_eq!(
+            s.server().event_names_file().unwrap(),
+            "/etc/openwec/event_names.toml"
+        );
+        assert_eq!(s.server().db_startup_policy(), DbStartupPolicy::Degraded);
+        assert_eq!(s.server().db_startup_timeout(), 60);
+        assert_eq!(s.server().db_startup_max_retry_interval(), 10);
+        assert_eq!(
+            s.server().subscriptions_cache_file().unwrap(),
+            "/var/lib/openwec/subscriptions.json"
+        );
 
         assert!(s.monitoring().is_none());
+        assert!(s.health().is_none());
     }
 
     const CONFIG_TLS_POSTGRES: &str = r#"
@@ -582,6 +837,40 @@ mod tests {
         listen_port = 9090
     "#;
 
+    #[test]
+    fn test_settings_max_queued_must_be_nonzero() {
+        for zero in ["max_queued_events = 0", "max_queued_bytes = 0"] {
+            let content =
+                CONFIG_KERBEROS_SQLITE.replace("max_clock_skew = 300", &format!("{}\n", zero));
+            assert!(Settings::from_str(&content).is_err());
+        }
+    }
+
+    #[test]
+    fn test_settings_max_subscriptions_must_be_nonzero() {
+        let content =
+            CONFIG_KERBEROS_SQLITE.replace("max_subscriptions = 1000", "max_subscriptions = 0");
+        assert!(Settings::from_str(&content).is_err());
+    }
+
+    #[test]
+    fn test_settings_no_sources_warning_delay_must_be_nonzero() {
+        let content = CONFIG_KERBEROS_SQLITE.replace(
+            "no_sources_warning_delay = 3600",
+            "no_sources_warning_delay = 0",
+        );
+        assert!(Settings::from_str(&content).is_err());
+    }
+
+    #[test]
+    fn test_settings_heartbeats_retention_days_must_be_nonzero() {
+        let content = CONFIG_KERBEROS_SQLITE.replace(
+            "heartbeats_retention_days = 30",
+            "heartbeats_retention_days = 0",
+        );
+        assert!(Settings::from_str(&content).is_err());
+    }
+
     #[test]
     fn test_settings_tls_postgres() {
         let s = Settings::from_str(CONFIG_TLS_POSTGRES).unwrap();
@@ -626,11 +915,36 @@ mod tests {
         assert_eq!(s.server().tcp_keepalive_time(), 7200);
         assert!(s.server().tcp_keepalive_intvl().is_none());
         assert!(s.server().tcp_keepalive_probes().is_none());
+        assert_eq!(s.server().time_received_source(), TimeReceivedSource::System);
+        assert!(s.server().max_clock_skew().is_none());
+        assert_eq!(s.server().clock_skew_policy(), ClockSkewPolicy::Tag);
+        assert!(s.server().max_queued_events().is_none());
+        assert!(s.server().max_queued_bytes().is_none());
+        assert_eq!(
+            s.server().queue_overflow_policy(),
+            QueueOverflowPolicy::Block
+        );
+        assert!(s.server().max_subscriptions().is_none());
+        assert_eq!(
+            s.server().max_subscriptions_policy(),
+            MaxSubscriptionsPolicy::Error
+        );
+        assert!(s.server().no_sources_warning_delay().is_none());
+        assert!(!s.server().event_names());
+        assert!(s.server().event_names_file().is_none());
+        assert_eq!(s.server().db_startup_policy(), DbStartupPolicy::Fail);
+        assert_eq!(s.server().db_startup_timeout(), 300);
+        assert_eq!(s.server().db_startup_max_retry_interval(), 30);
+        assert!(s.server().subscriptions_cache_file().is_none());
 
         assert_eq!(s.cli().read_only_subscriptions(), false);
 
         assert_eq!(s.outputs().garbage_collect_interval(), 600);
         assert_eq!(s.outputs().files().files_descriptor_close_timeout(), 600);
+        assert_eq!(
+            s.outputs().files().log_path_anonymization(),
+            PathAnonymization::Disabled
+        );
         assert!(s.outputs().kafka().options().is_empty());
 
         assert!(s.monitoring().is_some());
@@ -703,6 +1017,10 @@ mod tests {
         count_http_request_body_real_size_per_machine = true
         count_input_events_per_machine = true
         machines_refresh_interval = 10
+
+        [health]
+        listen_address = "127.0.0.1"
+        listen_port = 8080
     "#;
 
     #[test]
@@ -743,6 +1061,10 @@ mod tests {
             s.monitoring().unwrap().machines_refresh_interval(),
             10
         );
+
+        assert!(s.health().is_some());
+        assert_eq!(s.health().unwrap().listen_address(), "127.0.0.1");
+        assert_eq!(s.health().unwrap().listen_port(), 8080);
     }
 
     const CONFIG_TLS_POSTGRES_WITH_OUTPUTS: &str = r#"
@@ -774,6 +1096,7 @@ mod tests {
 
         [outputs.files]
         files_descriptor_close_timeout = 1
+        log_path_anonymization = "hash"
 
         [outputs.kafka]
         options = { "bootstrap.servers" = "localhost:9092" }
@@ -784,6 +1107,10 @@ mod tests {
         let s = Settings::from_str(CONFIG_TLS_POSTGRES_WITH_OUTPUTS).unwrap();
         assert_eq!(s.outputs().garbage_collect_interval(), 10);
         assert_eq!(s.outputs().files().files_descriptor_close_timeout(), 1);
+        assert_eq!(
+            s.outputs().files().log_path_anonymization(),
+            PathAnonymization::Hash
+        );
         let mut map = HashMap::new();
         map.insert("bootstrap.servers".to_owned(), "localhost:9092".to_owned());
         assert_eq!(s.outputs().kafka().options(), &map);
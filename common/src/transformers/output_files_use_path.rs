@@ -297,6 +297,17 @@ mod tests {
             "/base/openwec/{ip}/{principal}/{node}/test".to_string()
         );
 
+        // Variables of the base are kept as is
+        assert_eq!(
+            transform_files_config_to_path(
+                &Some("/base/{year}/{month}/{day}/{hour}".to_string()),
+                &None,
+                &None,
+                &None
+            )?,
+            "/base/{year}/{month}/{day}/{hour}/{ip}/{principal}/messages".to_string()
+        );
+
         Ok(())
     }
 }
@@ -1,5 +1,5 @@
 use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, Local, TimeZone, Utc};
 use log::warn;
 use openssl::hash::MessageDigest;
 use serde::{ser, Serializer};
@@ -13,6 +13,13 @@ pub fn timestamp_to_local_date(ts: i64) -> Result<DateTime<Local>> {
         .ok_or_else(|| anyhow!("Invalid or ambiguous timestamp"))
 }
 
+/// Parses an RFC 3339 timestamp, such as `2024-01-01T00:00:00Z`, into UTC
+pub fn parse_rfc3339_utc(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("{:?} is not an RFC 3339 timestamp", value))?
+        .with_timezone(&Utc))
+}
+
 pub fn serialize_timestamp<S>(timestamp: &Timestamp, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -0,0 +1,35 @@
+use std::process::Command;
+
+/// Exposes the build identifier of openwecd as `OPENWEC_BUILD`. It can be
+/// set by packagers, and defaults to the abbreviated hash of the git commit.
+/// Also generates the code of the messages and the client used by the Grpc
+/// output driver, and of the built-in message of the Protobuf format.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-env-changed=OPENWEC_BUILD");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+
+    let build = std::env::var("OPENWEC_BUILD").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|hash| hash.trim().to_string())
+            .filter(|hash| !hash.is_empty())
+    });
+    println!(
+        "cargo:rustc-env=OPENWEC_BUILD={}",
+        build.unwrap_or_else(|| "unknown".to_string())
+    );
+
+    println!("cargo:rerun-if-changed=proto/output.proto");
+    println!("cargo:rerun-if-changed=proto/event.proto");
+    // protox avoids depending on protoc
+    let file_descriptors = protox::compile(["output.proto", "event.proto"], ["proto"])?;
+    tonic_build::configure()
+        // The server is used by tests
+        .build_server(true)
+        .compile_fds(file_descriptors)?;
+    Ok(())
+}
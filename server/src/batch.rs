@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Limits the number of batches of a subscription being parsed and delivered
+/// at the same time.
+///
+/// Every Events request is handled in its own task, so a subscription
+/// receiving batches from many clients (or back-to-back batches from one
+/// client) processes them all at once. Once the limit is reached, new
+/// batches wait for a running one to finish, which keeps the memory and CPU
+/// used by a subscription predictable.
+#[derive(Debug, Clone)]
+pub struct BatchLimiter {
+    semaphore: Arc<Semaphore>,
+}
+
+impl BatchLimiter {
+    pub fn new(max_concurrent_batches: u32) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(
+                usize::try_from(max_concurrent_batches).unwrap_or(Semaphore::MAX_PERMITS),
+            )),
+        }
+    }
+
+    /// Waits until a batch can be processed. The batch is considered
+    /// processed when the returned permit is dropped.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit> {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("Batch limiter has been closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use tokio::task::JoinSet;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_batches_are_capped() -> Result<()> {
+        let limiter = BatchLimiter::new(3);
+        let started = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        // Holds the batches being processed until it is opened
+        let gate = Arc::new(Semaphore::new(0));
+
+        // As in handle_events, each batch is handled in its own task
+        let mut handles = JoinSet::new();
+        for _ in 0..10 {
+            let limiter = limiter.clone();
+            let started = started.clone();
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            let gate = gate.clone();
+            handles.spawn(async move {
+                let _permit = limiter.acquire().await?;
+                started.fetch_add(1, Ordering::SeqCst);
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                gate.acquire().await?.forget();
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok::<(), anyhow::Error>(())
+            });
+        }
+
+        // Only 3 batches are processed, the others are waiting
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 3);
+
+        // Waiting batches are processed once running ones are done
+        gate.add_permits(10);
+        while let Some(res) = handles.join_next().await {
+            res??;
+        }
+        assert_eq!(started.load(Ordering::SeqCst), 10);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+}
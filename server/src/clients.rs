@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Returns whether a client can send events for a subscription, given the
+/// number of distinct clients currently sending events for it.
+///
+/// A client which is already being serviced is always admitted, so that
+/// back-to-back batches of a client are never throttled by the cap.
+fn admits(active_clients: usize, max_concurrent_clients: u32, already_active: bool) -> bool {
+    already_active || active_clients < usize::try_from(max_concurrent_clients).unwrap_or(usize::MAX)
+}
+
+/// Limits the number of distinct clients sending events for a subscription
+/// at the same time.
+///
+/// Clients are identified by their principal. A client is active while at
+/// least one of its Events requests is being processed. Requests of new
+/// clients beyond the limit are rejected, and clients send their events
+/// again later.
+#[derive(Debug, Clone)]
+pub struct ClientLimiter {
+    max_concurrent_clients: u32,
+    // <principal> => <number of requests being processed>
+    active: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl ClientLimiter {
+    pub fn new(max_concurrent_clients: u32) -> Self {
+        Self {
+            max_concurrent_clients,
+            active: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Tries to admit a request of `client`. The request is considered
+    /// processed when the returned permit is dropped. Returns `None` if too
+    /// many other clients are being serviced.
+    pub fn try_acquire(&self, client: &str) -> Option<ClientPermit> {
+        let mut active = self.active.lock().unwrap();
+        if !admits(
+            active.len(),
+            self.max_concurrent_clients,
+            active.contains_key(client),
+        ) {
+            return None;
+        }
+        *active.entry(client.to_owned()).or_insert(0) += 1;
+        Some(ClientPermit {
+            client: client.to_owned(),
+            active: self.active.clone(),
+        })
+    }
+
+    #[cfg(test)]
+    fn active_clients(&self) -> usize {
+        self.active.lock().unwrap().len()
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientPermit {
+    client: String,
+    active: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Drop for ClientPermit {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admits() {
+        assert!(admits(0, 1, false));
+        assert!(admits(99, 100, false));
+        assert!(!admits(100, 100, false));
+        assert!(!admits(250, 100, false));
+        // Clients already being serviced are not throttled
+        assert!(admits(100, 100, true));
+        assert!(admits(250, 100, true));
+    }
+
+    #[test]
+    fn test_concurrent_clients_are_capped() {
+        let limiter = ClientLimiter::new(2);
+
+        let win10 = limiter.try_acquire("WIN10$@WINDOMAIN.LOCAL").unwrap();
+        let dc = limiter.try_acquire("DC$@WINDOMAIN.LOCAL").unwrap();
+        assert_eq!(limiter.active_clients(), 2);
+
+        // A third client is rejected, but active ones keep being serviced
+        assert!(limiter.try_acquire("SRV$@WINDOMAIN.LOCAL").is_none());
+        let win10_bis = limiter.try_acquire("WIN10$@WINDOMAIN.LOCAL").unwrap();
+        assert_eq!(limiter.active_clients(), 2);
+
+        // A client stays active until all its requests are processed
+        drop(win10);
+        assert!(limiter.try_acquire("SRV$@WINDOMAIN.LOCAL").is_none());
+        drop(win10_bis);
+        assert_eq!(limiter.active_clients(), 1);
+        let srv = limiter.try_acquire("SRV$@WINDOMAIN.LOCAL").unwrap();
+        assert_eq!(limiter.active_clients(), 2);
+
+        drop(dc);
+        drop(srv);
+        assert_eq!(limiter.active_clients(), 0);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{sync::OnceLock, time::Instant};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use common::settings::TimeReceivedSource;
+use log::{info, warn};
+
+static CLOCK: OnceLock<TimeReceivedClock> = OnceLock::new();
+
+/// Clock used to stamp the reception time of events.
+///
+/// With `MonotonicAnchored`, the wall clock is read only once (when the
+/// clock is created). Later timestamps are computed by adding the elapsed
+/// monotonic time to this anchor, so they never go backwards when the
+/// system clock is adjusted (NTP corrections for example).
+#[derive(Debug, Clone)]
+pub struct TimeReceivedClock {
+    source: TimeReceivedSource,
+    anchor_wall: DateTime<Utc>,
+    anchor_monotonic: Instant,
+}
+
+impl TimeReceivedClock {
+    pub fn new(source: TimeReceivedSource) -> Self {
+        Self::anchored_at(source, Utc::now(), Instant::now())
+    }
+
+    fn anchored_at(
+        source: TimeReceivedSource,
+        anchor_wall: DateTime<Utc>,
+        anchor_monotonic: Instant,
+    ) -> Self {
+        Self {
+            source,
+            anchor_wall,
+            anchor_monotonic,
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.time_at(Utc::now(), Instant::now())
+    }
+
+    fn time_at(&self, wall: DateTime<Utc>, monotonic: Instant) -> DateTime<Utc> {
+        match self.source {
+            TimeReceivedSource::System => wall,
+            TimeReceivedSource::MonotonicAnchored => {
+                let elapsed = monotonic.saturating_duration_since(self.anchor_monotonic);
+                // Elapsed time can not realistically overflow a TimeDelta
+                self.anchor_wall + TimeDelta::from_std(elapsed).unwrap_or(TimeDelta::zero())
+            }
+        }
+    }
+}
+
+/// Initializes the process wide clock. Must be called once at startup.
+pub fn init(source: TimeReceivedSource) {
+    info!("Events reception time source is {:?}", source);
+    if CLOCK.set(TimeReceivedClock::new(source)).is_err() {
+        warn!("Events reception clock has already been initialized");
+    }
+}
+
+/// Returns the reception time of an event using the configured clock.
+/// Falls back to the system clock if `init` has not been called.
+pub fn now() -> DateTime<Utc> {
+    match CLOCK.get() {
+        Some(clock) => clock.now(),
+        None => Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_backward_wall_clock_jump() {
+        let anchor_wall = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let anchor_monotonic = Instant::now();
+
+        let system =
+            TimeReceivedClock::anchored_at(TimeReceivedSource::System, anchor_wall, anchor_monotonic);
+        let monotonic = TimeReceivedClock::anchored_at(
+            TimeReceivedSource::MonotonicAnchored,
+            anchor_wall,
+            anchor_monotonic,
+        );
+
+        // Wall clock and monotonic clock advance together...
+        let readings = [
+            (anchor_wall + TimeDelta::seconds(10), anchor_monotonic + Duration::from_secs(10)),
+            // ...then the wall clock is set 30 seconds backwards...
+            (anchor_wall - TimeDelta::seconds(19), anchor_monotonic + Duration::from_secs(11)),
+            // ...and keeps going from there
+            (anchor_wall - TimeDelta::seconds(18), anchor_monotonic + Duration::from_secs(12)),
+        ];
+
+        let system_times: Vec<DateTime<Utc>> = readings
+            .iter()
+            .map(|(wall, instant)| system.time_at(*wall, *instant))
+            .collect();
+        let monotonic_times: Vec<DateTime<Utc>> = readings
+            .iter()
+            .map(|(wall, instant)| monotonic.time_at(*wall, *instant))
+            .collect();
+
+        // System clock timestamps follow the jump
+        assert!(system_times[1] < system_times[0]);
+
+        // Monotonic anchored timestamps never decrease
+        for window in monotonic_times.windows(2) {
+            assert!(window[0] <= window[1]);
+        }
+        assert_eq!(monotonic_times[0], anchor_wall + TimeDelta::seconds(10));
+        assert_eq!(monotonic_times[2], anchor_wall + TimeDelta::seconds(12));
+    }
+}
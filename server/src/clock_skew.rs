@@ -0,0 +1,208 @@
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use common::settings::{ClockSkewPolicy, Server};
+use log::debug;
+use metrics::counter;
+
+use crate::{
+    event::{ClockSkewInfo, EventData, EventMetadata},
+    monitoring::{
+        CLOCK_SKEW_POLICY, INPUT_EVENTS_CLOCK_SKEW, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID,
+    },
+};
+
+/// Detects events whose `TimeCreated` is too far ahead of their
+/// reception time and handles them according to the configured policy.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockSkewChecker {
+    max_skew: TimeDelta,
+    policy: ClockSkewPolicy,
+}
+
+impl ClockSkewChecker {
+    pub fn new(max_skew: TimeDelta, policy: ClockSkewPolicy) -> Self {
+        Self { max_skew, policy }
+    }
+
+    /// Returns None if clock skew is not checked
+    pub fn from_settings(server: &Server) -> Option<Self> {
+        server.max_clock_skew().map(|max_skew| {
+            Self::new(
+                TimeDelta::seconds(max_skew.try_into().unwrap_or(i64::MAX)),
+                server.clock_skew_policy(),
+            )
+        })
+    }
+
+    /// Applies the policy to a parsed event.
+    /// Returns false if the event must be dropped.
+    pub fn apply(&self, metadata: &EventMetadata, event_data: &mut EventData) -> bool {
+        let system = match event_data
+            .event_mut()
+            .and_then(|event| event.system.as_mut())
+        {
+            Some(system) => system,
+            None => return true,
+        };
+        let time_created = match system
+            .time_created
+            .as_ref()
+            .and_then(|time_created| DateTime::parse_from_rfc3339(time_created).ok())
+        {
+            Some(time_created) => time_created.with_timezone(&Utc),
+            None => return true,
+        };
+
+        let skew = time_created - metadata.time_received();
+        if skew <= self.max_skew {
+            return true;
+        }
+
+        debug!(
+            "Event from {} was created {}s in the future (TimeCreated: {:?})",
+            metadata.principal(),
+            skew.num_seconds(),
+            system.time_created
+        );
+        let policy_str: &'static str = match self.policy {
+            ClockSkewPolicy::Tag => "tag",
+            ClockSkewPolicy::Clamp => "clamp",
+            ClockSkewPolicy::Drop => "drop",
+        };
+        counter!(INPUT_EVENTS_CLOCK_SKEW,
+            SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+            SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+            CLOCK_SKEW_POLICY => policy_str)
+        .increment(1);
+
+        let original_time_created = match self.policy {
+            ClockSkewPolicy::Drop => return false,
+            ClockSkewPolicy::Tag => None,
+            ClockSkewPolicy::Clamp => system.time_created.replace(
+                metadata
+                    .time_received()
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            ),
+        };
+
+        if let Some(event) = event_data.event_mut() {
+            event.additional.clock_skew = Some(ClockSkewInfo {
+                skew: skew.num_seconds(),
+                original_time_created,
+            });
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use common::{settings, subscription::SubscriptionData};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        formats::json::JsonFormat,
+        output::{OutputDriversContext, OutputFormat},
+        subscription::Subscription,
+    };
+
+    fn event(time_created: &str) -> Arc<String> {
+        Arc::new(format!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing'/><EventID>4688</EventID><Level>0</Level><TimeCreated SystemTime='{}'/><Computer>win10.windomain.local</Computer></System></Event>",
+            time_created
+        ))
+    }
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    /// Applies the policy to a batch and returns the delivered Json events
+    fn deliver(policy: ClockSkewPolicy, events: &[Arc<String>]) -> Vec<Value> {
+        let metadata = metadata();
+        let checker = ClockSkewChecker::new(TimeDelta::seconds(300), policy);
+        events
+            .iter()
+            .filter_map(|raw| {
+                let mut event_data = EventData::new(raw.clone(), true);
+                if !checker.apply(&metadata, &mut event_data) {
+                    return None;
+                }
+                let json = JsonFormat.format(&metadata, &event_data).unwrap();
+                Some(serde_json::from_slice(&json).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_clock_skew_policies() {
+        let events = vec![
+            // In the past
+            event("2024-01-01T11:00:00.1234567Z"),
+            // In the future, but within the allowed skew
+            event("2024-01-01T12:04:00.1234567Z"),
+            // Too far in the future
+            event("2024-01-02T12:00:00.1234567Z"),
+        ];
+
+        let tagged = deliver(ClockSkewPolicy::Tag, &events);
+        assert_eq!(tagged.len(), 3);
+        assert!(tagged[0]["OpenWEC"].get("ClockSkew").is_none());
+        assert!(tagged[1]["OpenWEC"].get("ClockSkew").is_none());
+        assert_eq!(
+            tagged[2]["System"]["TimeCreated"],
+            "2024-01-02T12:00:00.1234567Z"
+        );
+        assert_eq!(tagged[2]["OpenWEC"]["ClockSkew"]["Skew"], 86400);
+        assert!(tagged[2]["OpenWEC"]["ClockSkew"]
+            .get("OriginalTimeCreated")
+            .is_none());
+
+        let clamped = deliver(ClockSkewPolicy::Clamp, &events);
+        assert_eq!(clamped.len(), 3);
+        assert_eq!(
+            clamped[0]["System"]["TimeCreated"],
+            "2024-01-01T11:00:00.1234567Z"
+        );
+        assert!(clamped[1]["OpenWEC"].get("ClockSkew").is_none());
+        assert_eq!(clamped[2]["System"]["TimeCreated"], "2024-01-01T12:00:00Z");
+        assert_eq!(clamped[2]["OpenWEC"]["ClockSkew"]["Skew"], 86400);
+        assert_eq!(
+            clamped[2]["OpenWEC"]["ClockSkew"]["OriginalTimeCreated"],
+            "2024-01-02T12:00:00.1234567Z"
+        );
+
+        let dropped = deliver(ClockSkewPolicy::Drop, &events);
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(
+            dropped[0]["System"]["TimeCreated"],
+            "2024-01-01T11:00:00.1234567Z"
+        );
+        assert_eq!(
+            dropped[1]["System"]["TimeCreated"],
+            "2024-01-01T12:04:00.1234567Z"
+        );
+        assert!(dropped
+            .iter()
+            .all(|event| event["OpenWEC"].get("ClockSkew").is_none()));
+    }
+}
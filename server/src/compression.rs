@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use anyhow::Result;
+use common::subscription::PayloadCompression;
+use flate2::{write::GzEncoder, Compression};
+
+/// Compresses a formatted event before it is handed to the transport
+pub fn compress(compression: PayloadCompression, payload: &[u8]) -> Result<Vec<u8>> {
+    Ok(match compression {
+        PayloadCompression::None => payload.to_vec(),
+        PayloadCompression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(payload)?;
+            encoder.finish()?
+        }
+        PayloadCompression::Zstd => zstd::encode_all(payload, 0)?,
+        // Use the LZ4 frame format, which is understood by most tools
+        PayloadCompression::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            encoder.write_all(payload)?;
+            encoder.finish()?
+        }
+    })
+}
+
+/// Decompresses a payload, as a consumer would
+#[cfg(test)]
+pub fn decompress(compression: PayloadCompression, payload: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut decompressed = Vec::new();
+    match compression {
+        PayloadCompression::None => decompressed.extend_from_slice(payload),
+        PayloadCompression::Gzip => {
+            flate2::read::GzDecoder::new(payload).read_to_end(&mut decompressed)?;
+        }
+        PayloadCompression::Zstd => decompressed = zstd::decode_all(payload)?,
+        PayloadCompression::Lz4 => {
+            lz4_flex::frame::FrameDecoder::new(payload).read_to_end(&mut decompressed)?;
+        }
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress() -> Result<()> {
+        let payload = "{\"EventID\":4624,\"Computer\":\"win10.windomain.local\"}".repeat(20);
+        for compression in [
+            PayloadCompression::None,
+            PayloadCompression::Gzip,
+            PayloadCompression::Zstd,
+            PayloadCompression::Lz4,
+        ] {
+            let compressed = compress(compression, payload.as_bytes())?;
+            if compression != PayloadCompression::None {
+                assert!(compressed.len() < payload.len());
+            }
+            assert_eq!(decompress(compression, &compressed)?, payload.as_bytes());
+        }
+        Ok(())
+    }
+}
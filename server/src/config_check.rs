@@ -0,0 +1,200 @@
+use std::path::Path;
+
+use anyhow::Result;
+use common::{
+    models::config::{check_config_files, FileReport},
+    settings::Settings,
+};
+
+use crate::{output::OutputDriversContext, subscription::Subscription};
+
+/// Validates the subscription config files found in `path`, using the same
+/// parser as `openwec subscriptions load` and building each subscription
+/// (including its outputs) as the server does when it loads it. Neither the
+/// database nor the network is used. Outputs whose format is not compatible
+/// with their driver are errors if `strict_compat` is set, warnings otherwise.
+pub fn check(settings: &Settings, path: &Path, strict_compat: bool) -> Result<Vec<FileReport>> {
+    let mut context = OutputDriversContext::new(settings.outputs());
+    check_config_files(path, None, strict_compat, |data| {
+        Subscription::from_data(data, &mut context).map(|_| ())
+    })
+}
+
+/// Runs the `--config-check` mode: prints a report of the config files
+/// found in `path` and returns the exit code of the process
+pub fn run(settings: &Settings, path: &Path, strict_compat: bool) -> i32 {
+    let reports = match check(settings, path, strict_compat) {
+        Ok(reports) => reports,
+        Err(err) => {
+            eprintln!("{:#}", err);
+            return 1;
+        }
+    };
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for report in reports.iter() {
+        if report.errors().is_empty() && report.warnings().is_empty() {
+            println!("{}: ok", report.path().display());
+        }
+        for error in report.errors() {
+            println!("{}: error: {}", report.path().display(), error);
+        }
+        for warning in report.warnings() {
+            println!("{}: warning: {}", report.path().display(), warning);
+        }
+        errors += report.errors().len();
+        warnings += report.warnings().len();
+    }
+    println!(
+        "Checked {} files: {} errors, {} warnings",
+        reports.len(),
+        errors,
+        warnings
+    );
+
+    if errors > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, str::FromStr};
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn subscription(uuid: &str, name: &str, enabled: bool) -> String {
+        format!(
+            r#"
+uuid = "{}"
+name = "{}"
+query = """
+<QueryList></QueryList>
+"""
+
+[options]
+enabled = {}
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+config = {{ path = "/tmp/{}.socket" }}
+"#,
+            uuid, name, enabled, name
+        )
+    }
+
+    #[tokio::test]
+    async fn test_config_check() -> Result<()> {
+        let settings = Settings::from_str(
+            r#"
+[server]
+keytab = "/etc/keytab"
+
+[database]
+type = "SQLite"
+path = "/tmp/openwec.sqlite"
+
+[[collectors]]
+hostname = "wec.windomain.local"
+listen_address = "0.0.0.0"
+
+[collectors.authentication]
+type = "Kerberos"
+service_principal_name = "HTTP/wec.windomain.local@WINDOMAIN.LOCAL"
+"#,
+        )?;
+
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("nested"))?;
+        fs::write(
+            dir.join("a.toml"),
+            subscription("b00bf259-3ba9-4faf-b58e-d0e9a3757798", "first", true),
+        )?;
+        fs::write(
+            dir.join("nested/b.toml"),
+            subscription("c6e6dd10-8b7e-4a57-9e7d-3c8a4a3f7d6e", "second", false),
+        )?;
+        fs::write(
+            dir.join("c.toml"),
+            subscription("4cc6cd7a-38c3-4fbe-8cd0-7d5a6e3c2d1b", "third", true),
+        )?;
+        // An unknown option
+        fs::write(
+            dir.join("d.toml"),
+            subscription("0d1f3c4e-5a6b-4c7d-8e9f-a0b1c2d3e4f5", "bad", true)
+                .replace("enabled =", "enable ="),
+        )?;
+
+        let reports = check(&settings, &dir, true)?;
+        let summary: Vec<(String, usize, usize)> = reports
+            .iter()
+            .map(|report| {
+                (
+                    report
+                        .path()
+                        .strip_prefix(&dir)
+                        .unwrap()
+                        .display()
+                        .to_string(),
+                    report.errors().len(),
+                    report.warnings().len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a.toml".to_string(), 0, 0),
+                ("c.toml".to_string(), 0, 0),
+                ("d.toml".to_string(), 1, 0),
+                ("nested/b.toml".to_string(), 0, 1),
+            ]
+        );
+        assert!(reports[2].errors()[0].contains("Failed to parse file"));
+        assert_ne!(run(&settings, &dir, true), 0);
+
+        // Without the bad file, the check succeeds despite warnings
+        fs::remove_file(dir.join("d.toml"))?;
+        assert_eq!(run(&settings, &dir, true), 0);
+
+        // Duplicated uuids are errors
+        fs::write(
+            dir.join("e.toml"),
+            subscription("b00bf259-3ba9-4faf-b58e-d0e9a3757798", "fourth", true),
+        )?;
+        let reports = check(&settings, &dir, true)?;
+        assert_eq!(reports[2].errors().len(), 1);
+        assert_ne!(run(&settings, &dir, true), 0);
+        fs::remove_file(dir.join("e.toml"))?;
+
+        // Incompatible format and driver are errors unless strict
+        // compatibility is disabled
+        fs::write(
+            dir.join("f.toml"),
+            subscription("7b2e4c6d-1a3f-4e5b-8c9d-0e1f2a3b4c5d", "fifth", true)
+                .replace("driver = \"UnixDatagram\"", "driver = \"Tcp\"")
+                .replace("format = \"Json\"", "format = \"Raw\"")
+                .replace(
+                    "path = \"/tmp/fifth.socket\"",
+                    "host = \"localhost\", port = 5000",
+                ),
+        )?;
+        let reports = check(&settings, &dir, true)?;
+        assert_eq!(reports[2].errors().len(), 1);
+        assert!(reports[2].errors()[0].contains("newline framing"));
+        assert_ne!(run(&settings, &dir, true), 0);
+        let reports = check(&settings, &dir, false)?;
+        assert!(reports[2].errors().is_empty());
+        assert_eq!(reports[2].warnings().len(), 1);
+        assert_eq!(run(&settings, &dir, false), 0);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,640 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use common::{
+    bookmark::BookmarkData,
+    database::{
+        schema::{Migration, Version},
+        Database, Db,
+    },
+    heartbeat::{HeartbeatData, HeartbeatKey, HeartbeatValue, HeartbeatsCache},
+    models::export,
+    settings::{DbStartupPolicy, Server},
+    subscription::{
+        SubscriptionData, SubscriptionMachine, SubscriptionMachineState, SubscriptionStatsCounters,
+    },
+};
+use log::{error, info, warn};
+use tokio::{
+    sync::RwLock,
+    time::{self, Instant},
+};
+
+// Delay before the second connection attempt, doubled after each failure
+const FIRST_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Connects to the database according to `server.db_startup_policy`.
+///
+/// `connect` must return a database whose schema is up to date.
+pub async fn connect<F, Fut>(settings: &Server, connect: F) -> Result<Db>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Db>> + Send,
+{
+    let max_retry_interval = Duration::from_secs(settings.db_startup_max_retry_interval());
+    match settings.db_startup_policy() {
+        DbStartupPolicy::Fail => connect().await,
+        DbStartupPolicy::Retry => {
+            let deadline = Instant::now() + Duration::from_secs(settings.db_startup_timeout());
+            let mut interval = FIRST_RETRY_INTERVAL;
+            loop {
+                let err = match connect().await {
+                    Ok(db) => return Ok(db),
+                    Err(err) => err,
+                };
+                if Instant::now() + interval >= deadline {
+                    return Err(err.context(format!(
+                        "Database is still unavailable after {} seconds",
+                        settings.db_startup_timeout()
+                    )));
+                }
+                warn!(
+                    "Failed to connect to database, retrying in {} seconds: {:?}",
+                    interval.as_secs(),
+                    err
+                );
+                time::sleep(interval).await;
+                interval = (interval * 2).min(max_retry_interval);
+            }
+        }
+        DbStartupPolicy::Degraded => {
+            let cache_file = settings.subscriptions_cache_file().cloned();
+            match connect().await {
+                Ok(db) => Ok(Arc::new(DeferredDatabase::new(Some(db), cache_file))),
+                Err(err) => {
+                    error!(
+                        "Failed to connect to database, starting in degraded mode: {:?}",
+                        err
+                    );
+                    let db = Arc::new(DeferredDatabase::new(None, cache_file));
+                    let reconnect_db = db.clone();
+                    tokio::spawn(async move {
+                        reconnect_task(reconnect_db, connect, max_retry_interval).await
+                    });
+                    Ok(db)
+                }
+            }
+        }
+    }
+}
+
+async fn reconnect_task<F, Fut>(db: Arc<DeferredDatabase>, connect: F, max_retry_interval: Duration)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Db>>,
+{
+    let mut interval = FIRST_RETRY_INTERVAL;
+    loop {
+        time::sleep(interval).await;
+        match connect().await {
+            Ok(inner) => match db.catch_up(inner).await {
+                Ok(()) => {
+                    info!("Database is available, leaving degraded mode");
+                    return;
+                }
+                Err(err) => warn!("Failed to persist deferred data: {:?}", err),
+            },
+            Err(err) => warn!("Database is still unavailable: {:?}", err),
+        }
+        interval = (interval * 2).min(max_retry_interval);
+    }
+}
+
+#[derive(Default)]
+struct Deferred {
+    // (machine, subscription) => bookmark
+    bookmarks: HashMap<(String, String), String>,
+    heartbeats: HeartbeatsCache,
+}
+
+/// Database used with the `degraded` startup policy.
+///
+/// Until the database is available, subscriptions are read from the cache
+/// file and bookmarks and heartbeats are kept in memory. They are persisted
+/// by `catch_up` once a connection has been established, and lost if the
+/// server stops before (they are not written to disk). Afterwards, every
+/// call is forwarded to the database and subscriptions are saved in the
+/// cache file each time they are retrieved.
+pub struct DeferredDatabase {
+    inner: RwLock<Option<Db>>,
+    deferred: Mutex<Deferred>,
+    cache_file: Option<String>,
+    // Content of the cache file, to avoid writing it again if nothing changed
+    cached: Mutex<Option<String>>,
+}
+
+impl DeferredDatabase {
+    fn new(inner: Option<Db>, cache_file: Option<String>) -> Self {
+        Self {
+            inner: RwLock::new(inner),
+            deferred: Mutex::new(Deferred::default()),
+            cache_file,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Persists deferred data in `inner`, and then uses it for every call
+    async fn catch_up(&self, inner: Db) -> Result<()> {
+        // Callers wait for the end of the catch-up instead of deferring
+        // more data
+        let mut guard = self.inner.write().await;
+        let (bookmarks, heartbeats) = {
+            let deferred = self.deferred.lock().unwrap();
+            (deferred.bookmarks.clone(), deferred.heartbeats.clone())
+        };
+        for ((machine, subscription), bookmark) in bookmarks.iter() {
+            inner
+                .store_bookmark(machine, subscription, bookmark)
+                .await
+                .context("Failed to store deferred bookmark")?;
+        }
+        if !heartbeats.is_empty() {
+            inner
+                .store_heartbeats(&heartbeats)
+                .await
+                .context("Failed to store deferred heartbeats")?;
+        }
+        info!(
+            "Persisted {} deferred bookmarks and {} deferred heartbeats",
+            bookmarks.len(),
+            heartbeats.len()
+        );
+        *self.deferred.lock().unwrap() = Deferred::default();
+        *guard = Some(inner);
+        Ok(())
+    }
+
+    async fn inner(&self) -> Result<Db> {
+        match self.inner.read().await.as_ref() {
+            Some(db) => Ok(db.clone()),
+            None => bail!("Database is unavailable (degraded mode)"),
+        }
+    }
+
+    fn save_cache(&self, subscriptions: &[SubscriptionData]) -> Result<()> {
+        let path = match &self.cache_file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let content = export::serialize(subscriptions)?;
+        let mut cached = self.cached.lock().unwrap();
+        if cached.as_ref() != Some(&content) {
+            std::fs::write(path, &content)
+                .with_context(|| format!("Failed to write subscriptions cache file {}", path))?;
+            *cached = Some(content);
+        }
+        Ok(())
+    }
+
+    fn load_cache(&self) -> Result<Vec<SubscriptionData>> {
+        let path = match &self.cache_file {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                warn!("Subscriptions cache file {} does not exist", path);
+                return Ok(Vec::new());
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read subscriptions cache file {}", path))
+            }
+        };
+        export::parse(&content)
+            .with_context(|| format!("Failed to parse subscriptions cache file {}", path))
+    }
+}
+
+#[async_trait]
+impl Database for DeferredDatabase {
+    async fn get_bookmark(&self, machine: &str, subscription: &str) -> Result<Option<String>> {
+        let guard = self.inner.read().await;
+        match guard.as_ref() {
+            Some(db) => db.get_bookmark(machine, subscription).await,
+            // Bookmarks stored in database are unknown: clients without a
+            // deferred bookmark are sent subscriptions without bookmark
+            None => Ok(self
+                .deferred
+                .lock()
+                .unwrap()
+                .bookmarks
+                .get(&(machine.to_string(), subscription.to_string()))
+                .cloned()),
+        }
+    }
+
+    async fn get_bookmarks(&self, subscription: &str) -> Result<Vec<BookmarkData>> {
+        self.inner().await?.get_bookmarks(subscription).await
+    }
+
+    async fn store_bookmark(
+        &self,
+        machine: &str,
+        subscription: &str,
+        bookmark: &str,
+    ) -> Result<()> {
+        let guard = self.inner.read().await;
+        match guard.as_ref() {
+            Some(db) => db.store_bookmark(machine, subscription, bookmark).await,
+            None => {
+                self.deferred.lock().unwrap().bookmarks.insert(
+                    (machine.to_string(), subscription.to_string()),
+                    bookmark.to_string(),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    async fn delete_bookmarks(
+        &self,
+        machine: Option<&str>,
+        subscription: Option<&str>,
+    ) -> Result<()> {
+        self.inner()
+            .await?
+            .delete_bookmarks(machine, subscription)
+            .await
+    }
+
+    async fn get_heartbeats(&self) -> Result<Vec<HeartbeatData>> {
+        self.inner().await?.get_heartbeats().await
+    }
+
+    async fn get_heartbeats_by_machine(
+        &self,
+        machine: &str,
+        subscription: Option<&str>,
+    ) -> Result<Vec<HeartbeatData>> {
+        self.inner()
+            .await?
+            .get_heartbeats_by_machine(machine, subscription)
+            .await
+    }
+
+    async fn get_heartbeats_by_ip(
+        &self,
+        ip: &str,
+        subscription: Option<&str>,
+    ) -> Result<Vec<HeartbeatData>> {
+        self.inner()
+            .await?
+            .get_heartbeats_by_ip(ip, subscription)
+            .await
+    }
+
+    async fn get_heartbeats_by_subscription(
+        &self,
+        subscription: &str,
+    ) -> Result<Vec<HeartbeatData>> {
+        self.inner()
+            .await?
+            .get_heartbeats_by_subscription(subscription)
+            .await
+    }
+
+    async fn store_heartbeat(
+        &self,
+        machine: &str,
+        ip: String,
+        subscription: &str,
+        is_event: bool,
+    ) -> Result<()> {
+        self.inner()
+            .await?
+            .store_heartbeat(machine, ip, subscription, is_event)
+            .await
+    }
+
+    async fn store_heartbeats(&self, heartbeats: &HeartbeatsCache) -> Result<()> {
+        let guard = self.inner.read().await;
+        match guard.as_ref() {
+            Some(db) => db.store_heartbeats(heartbeats).await,
+            None => {
+                let mut deferred = self.deferred.lock().unwrap();
+                for (key, value) in heartbeats.iter() {
+                    let last_event_seen = match deferred.heartbeats.get(key) {
+                        Some(previous) => value.last_event_seen.or(previous.last_event_seen),
+                        None => value.last_event_seen,
+                    };
+                    deferred.heartbeats.insert(
+                        HeartbeatKey {
+                            machine: key.machine.clone(),
+                            subscription: key.subscription.clone(),
+                        },
+                        HeartbeatValue {
+                            ip: value.ip.clone(),
+                            last_seen: value.last_seen,
+                            last_event_seen,
+                        },
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn delete_heartbeats_before(&self, last_seen_before: i64, limit: u32) -> Result<u64> {
+        self.inner()
+            .await?
+            .delete_heartbeats_before(last_seen_before, limit)
+            .await
+    }
+
+    async fn get_subscriptions(&self) -> Result<Vec<SubscriptionData>> {
+        let guard = self.inner.read().await;
+        match guard.as_ref() {
+            Some(db) => {
+                let subscriptions = db.get_subscriptions().await?;
+                if let Err(err) = self.save_cache(&subscriptions) {
+                    warn!("{:?}", err);
+                }
+                Ok(subscriptions)
+            }
+            None => self.load_cache(),
+        }
+    }
+
+    async fn get_subscription_by_identifier(
+        &self,
+        identifier: &str,
+    ) -> Result<Option<SubscriptionData>> {
+        self.inner()
+            .await?
+            .get_subscription_by_identifier(identifier)
+            .await
+    }
+
+    async fn store_subscription(&self, subscription: &SubscriptionData) -> Result<()> {
+        self.inner().await?.store_subscription(subscription).await
+    }
+
+    async fn delete_subscription(&self, uuid: &str) -> Result<()> {
+        self.inner().await?.delete_subscription(uuid).await
+    }
+
+    async fn setup_schema(&self) -> Result<()> {
+        self.inner().await?.setup_schema().await
+    }
+
+    async fn current_version(&self) -> Result<Option<Version>> {
+        self.inner().await?.current_version().await
+    }
+
+    async fn migrated_versions(&self) -> Result<BTreeSet<Version>> {
+        self.inner().await?.migrated_versions().await
+    }
+
+    async fn apply_migration(&self, version: Version) -> Result<()> {
+        self.inner().await?.apply_migration(version).await
+    }
+
+    async fn revert_migration(&self, version: Version) -> Result<()> {
+        self.inner().await?.revert_migration(version).await
+    }
+
+    async fn migrations(&self) -> BTreeMap<Version, Arc<dyn Migration + Send + Sync>> {
+        match self.inner.read().await.as_ref() {
+            Some(db) => db.migrations().await,
+            None => BTreeMap::new(),
+        }
+    }
+
+    async fn get_stats(
+        &self,
+        subscription: &str,
+        start_time: i64,
+    ) -> Result<SubscriptionStatsCounters> {
+        self.inner()
+            .await?
+            .get_stats(subscription, start_time)
+            .await
+    }
+
+    async fn get_machines(
+        &self,
+        subscription: &str,
+        start_time: i64,
+        state: Option<SubscriptionMachineState>,
+    ) -> Result<Vec<SubscriptionMachine>> {
+        self.inner()
+            .await?
+            .get_machines(subscription, start_time, state)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use anyhow::anyhow;
+    use common::database::{schema, schema::Migrator, sqlite::SQLiteDatabase};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("openwec-db-startup-{}-{}", name, Uuid::new_v4()))
+    }
+
+    async fn sqlite(path: &PathBuf) -> Result<Db> {
+        let mut db = SQLiteDatabase::new(path.to_str().unwrap()).await?;
+        schema::sqlite::register_migrations(&mut db);
+        let db: Db = Arc::new(db);
+        db.setup_schema().await?;
+        Migrator::new(db.clone()).up(None, false).await?;
+        Ok(db)
+    }
+
+    fn settings(policy: &str, cache_file: Option<&PathBuf>) -> Server {
+        let mut content = format!(
+            "db_startup_policy = \"{}\"\ndb_startup_timeout = 5\ndb_startup_max_retry_interval = 1\n",
+            policy
+        );
+        if let Some(cache_file) = cache_file {
+            content.push_str(&format!(
+                "subscriptions_cache_file = \"{}\"\n",
+                cache_file.display()
+            ));
+        }
+        toml::from_str(&content).unwrap()
+    }
+
+    /// Simulates a database which is down until `available` is set
+    fn connector(
+        path: PathBuf,
+        available: Arc<AtomicBool>,
+        attempts: Arc<AtomicUsize>,
+    ) -> impl Fn() -> std::pin::Pin<Box<dyn Future<Output = Result<Db>> + Send>> + Send + Sync {
+        move || {
+            let path = path.clone();
+            let available = available.clone();
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                if !available.load(Ordering::SeqCst) {
+                    return Err(anyhow!("Connection refused"));
+                }
+                sqlite(&path).await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fail_policy() -> Result<()> {
+        let available = Arc::new(AtomicBool::new(false));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let res = connect(
+            &settings("fail", None),
+            connector(temp_path("fail"), available, attempts.clone()),
+        )
+        .await;
+        assert!(res.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_policy() -> Result<()> {
+        let path = temp_path("retry");
+        let available = Arc::new(AtomicBool::new(false));
+        let attempts = Arc::new(AtomicUsize::new(0));
+
+        // The database comes back after a few attempts
+        let cloned_available = available.clone();
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(1500)).await;
+            cloned_available.store(true, Ordering::SeqCst);
+        });
+        let db = connect(
+            &settings("retry", None),
+            connector(path.clone(), available, attempts.clone()),
+        )
+        .await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(db.get_subscriptions().await?.is_empty());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_policy_timeout() -> Result<()> {
+        // The database never comes back
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let res = connect(
+            &settings("retry", None),
+            connector(
+                temp_path("retry"),
+                Arc::new(AtomicBool::new(false)),
+                attempts.clone(),
+            ),
+        )
+        .await;
+        assert!(res.is_err());
+        // Attempts at 0, 1, 2, 3 and 4 seconds
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_degraded_policy() -> Result<()> {
+        let path = temp_path("degraded");
+        let cache_file = temp_path("cache");
+
+        // Subscriptions are saved in the cache file while the database is
+        // available
+        let db = connect(
+            &settings("degraded", Some(&cache_file)),
+            connector(
+                path.clone(),
+                Arc::new(AtomicBool::new(true)),
+                Arc::new(AtomicUsize::new(0)),
+            ),
+        )
+        .await?;
+        let subscription = SubscriptionData::new("my-subscription", "query");
+        db.store_subscription(&subscription).await?;
+        assert_eq!(db.get_subscriptions().await?.len(), 1);
+        assert!(cache_file.exists());
+        drop(db);
+        std::fs::remove_file(&path)?;
+
+        // The server starts without database
+        let available = Arc::new(AtomicBool::new(false));
+        let db = connect(
+            &settings("degraded", Some(&cache_file)),
+            connector(
+                path.clone(),
+                available.clone(),
+                Arc::new(AtomicUsize::new(0)),
+            ),
+        )
+        .await?;
+        let subscriptions = db.get_subscriptions().await?;
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].uuid(), subscription.uuid());
+        assert!(db
+            .get_subscription_by_identifier("my-subscription")
+            .await
+            .is_err());
+
+        // Bookmarks and heartbeats are deferred
+        let uuid = subscription.uuid_string();
+        assert!(db
+            .get_bookmark("win10.windomain.local", &uuid)
+            .await?
+            .is_none());
+        db.store_bookmark("win10.windomain.local", &uuid, "bookmark")
+            .await?;
+        assert_eq!(
+            db.get_bookmark("win10.windomain.local", &uuid).await?,
+            Some("bookmark".to_string())
+        );
+        let mut heartbeats = HeartbeatsCache::new();
+        heartbeats.insert(
+            HeartbeatKey {
+                machine: "win10.windomain.local".to_string(),
+                subscription: uuid.clone(),
+            },
+            HeartbeatValue {
+                ip: "192.168.58.100".to_string(),
+                last_seen: 1,
+                last_event_seen: Some(1),
+            },
+        );
+        db.store_heartbeats(&heartbeats).await?;
+
+        // The database comes back: deferred data is persisted
+        let inner = sqlite(&path).await?;
+        inner.store_subscription(&subscription).await?;
+        available.store(true, Ordering::SeqCst);
+        for _ in 0..50 {
+            if db.get_heartbeats().await.is_ok() {
+                break;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(
+            inner.get_bookmark("win10.windomain.local", &uuid).await?,
+            Some("bookmark".to_string())
+        );
+        let stored_heartbeats = inner.get_heartbeats_by_subscription(&uuid).await?;
+        assert_eq!(stored_heartbeats.len(), 1);
+        assert_eq!(db.get_heartbeats().await?.len(), 1);
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(&cache_file)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Result;
+use base64::Engine;
+use futures_util::future::join_all;
+use log::warn;
+use metrics::counter;
+use serde::Serialize;
+
+use crate::{
+    event::EventMetadata,
+    monitoring::{OUTPUT_DRIVER, OUTPUT_DRIVER_FAILURES, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID},
+    output::Output,
+    subscription::Subscription,
+};
+
+/// Why events could not be delivered to an output
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// Events could not be formatted (they are wrapped in Raw format)
+    FormatError,
+    /// The output has been skipped because it is degraded
+    Degraded,
+}
+
+/// Context of the failure of an output. Failed writes are not dead-lettered:
+/// the batch is not acknowledged and the client sends it again later.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    reason: FailureReason,
+    driver: &'static str,
+    format: &'static str,
+    attempts: Option<u32>,
+    first_failure_at: Option<String>,
+    last_error: Option<String>,
+}
+
+impl Failure {
+    /// Events could not be formatted for `output`
+    pub fn format_error(output: &Output) -> Self {
+        let format: &'static str = output.format().into();
+        Self {
+            reason: FailureReason::FormatError,
+            driver: output.driver_kind(),
+            format: "raw",
+            attempts: None,
+            first_failure_at: None,
+            last_error: Some(format!("Failed to format event using {}", format)),
+        }
+    }
+
+    /// `output` has been skipped because it is degraded. If its last writes
+    /// failed, they are described in the record.
+    pub fn degraded(output: &Output) -> Self {
+        let streak = output.failure_streak();
+        Self {
+            reason: FailureReason::Degraded,
+            driver: output.driver_kind(),
+            format: output.format().into(),
+            attempts: streak.as_ref().map(|streak| streak.attempts()),
+            first_failure_at: streak
+                .as_ref()
+                .map(|streak| streak.first_failure_at().to_rfc3339()),
+            last_error: Some(
+                streak
+                    .map(|streak| streak.last_error().to_string())
+                    .unwrap_or_else(|| "Output is degraded".to_string()),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetterSubscription<'a> {
+    uuid: &'a str,
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetterRecord<'a> {
+    failure_reason: FailureReason,
+    driver: &'a str,
+    subscription: DeadLetterSubscription<'a>,
+    attempts: Option<u32>,
+    first_failure_at: Option<&'a str>,
+    last_error: Option<&'a str>,
+    ip_address: String,
+    principal: &'a str,
+    time_received: String,
+    /// Format of `event`
+    format: &'a str,
+    event: Cow<'a, str>,
+    /// Set to "base64" if `event` is not valid UTF-8 (binary formats)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<&'static str>,
+}
+
+/// Wraps each event that could not be delivered in a dead-letter record
+pub fn records(
+    metadata: &EventMetadata,
+    failure: &Failure,
+    events: &[Arc<Vec<u8>>],
+) -> Result<Vec<Arc<Vec<u8>>>> {
+    let mut records = Vec::with_capacity(events.len());
+    for event in events {
+        let (event, encoding) = match std::str::from_utf8(event) {
+            Ok(event) => (Cow::Borrowed(event), None),
+            Err(_) => (
+                Cow::Owned(base64::engine::general_purpose::STANDARD.encode(event.as_slice())),
+                Some("base64"),
+            ),
+        };
+        let record = DeadLetterRecord {
+            failure_reason: failure.reason,
+            driver: failure.driver,
+            subscription: DeadLetterSubscription {
+                uuid: metadata.subscription_uuid(),
+                name: metadata.subscription_name(),
+            },
+            attempts: failure.attempts,
+            first_failure_at: failure.first_failure_at.as_deref(),
+            last_error: failure.last_error.as_deref(),
+            ip_address: metadata.addr().ip().to_string(),
+            principal: metadata.principal(),
+            time_received: metadata.time_received().to_rfc3339(),
+            format: failure.format,
+            event,
+            encoding,
+        };
+        records.push(Arc::new(serde_json::to_vec(&record)?));
+    }
+    Ok(records)
+}
+
+/// Writes dead-letter records to the dead-letter outputs of a subscription.
+/// Failures are logged but do not prevent the batch from being acknowledged,
+/// and dead-letter records are never dead-lettered themselves.
+pub async fn write(
+    subscription: &Subscription,
+    metadata: &Arc<EventMetadata>,
+    records: Vec<Arc<Vec<u8>>>,
+) {
+    let records = Arc::new(records);
+    let writes = subscription
+        .outputs()
+        .iter()
+        .filter(|output| output.dead_letter())
+        .map(|output| {
+            let records = records.clone();
+            async move { (output, output.write(metadata.clone(), records).await) }
+        });
+    for (output, result) in join_all(writes).await {
+        if let Err(err) = result {
+            warn!(
+                "Failed to write dead-letter records to output {}: {:?}",
+                output.describe(),
+                err
+            );
+            counter!(OUTPUT_DRIVER_FAILURES,
+                SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+                SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+                OUTPUT_DRIVER => output.driver_kind())
+            .increment(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{
+        settings,
+        subscription::{
+            FilesConfiguration, SubscriptionData, SubscriptionOutput, SubscriptionOutputDriver,
+            SubscriptionOutputFormat, UnixDatagramConfiguration,
+        },
+    };
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::output::OutputDriversContext;
+
+    const EVENT: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing'/><EventID>4688</EventID><Computer>win10.windomain.local</Computer></System></Event>"#;
+
+    fn output(path: &str, dead_letter: bool) -> SubscriptionOutput {
+        let mut output = SubscriptionOutput::new(
+            SubscriptionOutputFormat::Raw,
+            SubscriptionOutputDriver::UnixDatagram(UnixDatagramConfiguration::new(
+                path.to_string(),
+            )),
+            true,
+        );
+        output.set_dead_letter(dead_letter);
+        output
+    }
+
+    #[tokio::test]
+    async fn test_degraded_record() -> Result<()> {
+        // Nothing listens on this socket: writes always fail
+        let path = std::env::temp_dir()
+            .join(format!("openwec-test-{}.socket", Uuid::new_v4()))
+            .display()
+            .to_string();
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![
+            output(&path, false),
+            output("/tmp/dead-letter.socket", true),
+        ]);
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        assert!(subscription.has_dead_letter_outputs());
+        let metadata = Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ));
+
+        let failing = &subscription.outputs()[0];
+        let events = Arc::new(vec![Arc::new(EVENT.as_bytes().to_vec())]);
+        assert!(failing.failure_streak().is_none());
+        for _ in 0..3 {
+            assert!(failing
+                .write(metadata.clone(), events.clone())
+                .await
+                .is_err());
+        }
+        let streak = failing.failure_streak().unwrap();
+        assert_eq!(streak.attempts(), 3);
+
+        let records = records(&metadata, &Failure::degraded(failing), &events)?;
+        assert_eq!(records.len(), 1);
+        let record: Value = serde_json::from_slice(&records[0])?;
+        assert_eq!(record["failure_reason"], "degraded");
+        assert_eq!(record["driver"], "unixdatagram");
+        assert_eq!(record["subscription"]["name"], "Test");
+        assert_eq!(
+            record["subscription"]["uuid"],
+            subscription.uuid_string().as_str()
+        );
+        assert_eq!(record["attempts"], 3);
+        assert_eq!(
+            record["first_failure_at"],
+            streak.first_failure_at().to_rfc3339()
+        );
+        assert!(record["last_error"].as_str().unwrap().contains(&path));
+        assert_eq!(record["principal"], "WIN10$@WINDOMAIN.LOCAL");
+        assert_eq!(record["format"], "raw");
+        // The original event is kept as is
+        assert_eq!(record["event"], EVENT);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_to_dead_letter_output() -> Result<()> {
+        // Nothing listens on this socket: writes always fail
+        let socket = std::env::temp_dir()
+            .join(format!("openwec-test-{}.socket", Uuid::new_v4()))
+            .display()
+            .to_string();
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        let mut dead_letter_output = SubscriptionOutput::new(
+            SubscriptionOutputFormat::Json,
+            SubscriptionOutputDriver::Files(FilesConfiguration::new(format!(
+                "{}/dead-letter",
+                dir.display()
+            ))),
+            true,
+        );
+        dead_letter_output.set_dead_letter(true);
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![output(&socket, false), dead_letter_output]);
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        let metadata = Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ));
+
+        let failing = &subscription.outputs()[0];
+        let events = Arc::new(vec![Arc::new(EVENT.as_bytes().to_vec())]);
+        assert!(failing
+            .write(metadata.clone(), events.clone())
+            .await
+            .is_err());
+        write(
+            &subscription,
+            &metadata,
+            records(&metadata, &Failure::degraded(failing), &events)?,
+        )
+        .await;
+
+        // The failed event lands in the file of the dead-letter output
+        let content = std::fs::read_to_string(dir.join("dead-letter"))?;
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let record: Value = serde_json::from_str(lines[0])?;
+        assert_eq!(record["failure_reason"], "degraded");
+        assert_eq!(record["driver"], "unixdatagram");
+        assert_eq!(record["attempts"], 1);
+        assert!(record["last_error"].as_str().unwrap().contains(&socket));
+        assert_eq!(record["event"], EVENT);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_format_error_record() -> Result<()> {
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![output("/tmp/events.socket", false)]);
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+
+        let failure = Failure::format_error(&subscription.outputs()[0]);
+        let records = records(&metadata, &failure, &[Arc::new(b"not an event".to_vec())])?;
+        // The configuration of the output, which may contain credentials,
+        // is not part of the record
+        assert!(!std::str::from_utf8(&records[0])?.contains("/tmp/events.socket"));
+        let record: Value = serde_json::from_slice(&records[0])?;
+        assert_eq!(record["failure_reason"], "format_error");
+        assert_eq!(record["driver"], "unixdatagram");
+        assert_eq!(record["attempts"], Value::Null);
+        assert_eq!(record["last_error"], "Failed to format event using raw");
+        assert_eq!(record["event"], "not an event");
+        assert_eq!(record["encoding"], Value::Null);
+
+        // Events of binary formats are encoded in base64
+        let binary_records = super::records(&metadata, &failure, &[Arc::new(vec![0x0a, 0xff])])?;
+        let record: Value = serde_json::from_slice(&binary_records[0])?;
+        assert_eq!(record["event"], "Cv8=");
+        assert_eq!(record["encoding"], "base64");
+        Ok(())
+    }
+}
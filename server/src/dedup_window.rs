@@ -0,0 +1,348 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use common::subscription::SubscriptionData;
+use log::debug;
+use metrics::counter;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    monitoring::{INPUT_EVENTS_DUPLICATES, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID},
+};
+
+/// Identifies an event emitted by a Windows machine
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventKey {
+    computer: String,
+    channel: String,
+    event_record_id: u64,
+}
+
+impl EventKey {
+    /// Returns None if the event could not be parsed or has no
+    /// `EventRecordID`
+    pub fn from_event_data(event_data: &EventData) -> Option<Self> {
+        let system = event_data.event()?.system.as_ref()?;
+        Some(Self {
+            computer: system.computer.clone(),
+            channel: system.channel.clone().unwrap_or_default(),
+            event_record_id: system.event_record_id?,
+        })
+    }
+}
+
+/// Keys of the events of a batch which were not dropped. They are recorded
+/// in the window once the batch has been written by all outputs.
+#[derive(Debug, Default)]
+pub struct BatchKeys {
+    // In the order of the events, so that the window forgets the oldest
+    // ones first
+    keys: Vec<EventKey>,
+    set: HashSet<EventKey>,
+}
+
+impl BatchKeys {
+    fn contains(&self, key: &EventKey) -> bool {
+        self.set.contains(key)
+    }
+
+    fn insert(&mut self, key: EventKey) {
+        if self.set.insert(key.clone()) {
+            self.keys.push(key);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    recorded: Instant,
+    // Logical time of the last recording of the key
+    seen: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    keys: HashMap<EventKey, Entry>,
+    // <logical time> => <key>, the least recently recorded key first
+    order: BTreeMap<u64, EventKey>,
+    clock: u64,
+}
+
+impl Entries {
+    fn is_expired(entry: &Entry, ttl: Option<Duration>, now: Instant) -> bool {
+        ttl.is_some_and(|ttl| now.saturating_duration_since(entry.recorded) >= ttl)
+    }
+
+    /// Forgets the keys which expired. Keys are recorded in chronological
+    /// order, so that they expire in the same order.
+    fn expire(&mut self, ttl: Option<Duration>, now: Instant) {
+        while let Some((_, key)) = self.order.first_key_value() {
+            if self
+                .keys
+                .get(key)
+                .is_some_and(|entry| !Self::is_expired(entry, ttl, now))
+            {
+                break;
+            }
+            if let Some((_, key)) = self.order.pop_first() {
+                self.keys.remove(&key);
+            }
+        }
+    }
+}
+
+/// Drops the events whose `(Computer, Channel, EventRecordID)` tuple has
+/// already been seen for the subscription, for example when a client sends
+/// a batch again after a bookmark glitch.
+///
+/// Tuples are only recorded once events have been written, so that a batch
+/// sent again by a client after a failure is not dropped. At most `size`
+/// tuples are kept: when the window is full, the least recently recorded one
+/// is forgotten. Tuples are also forgotten `ttl` after being recorded.
+#[derive(Debug, Clone)]
+pub struct DedupWindow {
+    size: usize,
+    ttl: Option<Duration>,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl DedupWindow {
+    pub fn new(size: u32, ttl: Option<u32>) -> Self {
+        Self {
+            size: usize::try_from(size).unwrap_or(usize::MAX),
+            ttl: ttl.map(|ttl| Duration::from_secs(ttl.into())),
+            entries: Arc::new(Mutex::new(Entries::default())),
+        }
+    }
+
+    /// Returns None if the subscription does not drop duplicated events
+    pub fn from_subscription(data: &SubscriptionData) -> Option<Self> {
+        data.dedup_window()
+            .map(|size| Self::new(size, data.dedup_window_ttl()))
+    }
+
+    /// Applies the window to a parsed event, given the keys of the events of
+    /// its batch which have been kept so far.
+    /// Returns false if the event must be dropped.
+    pub fn apply(
+        &self,
+        metadata: &EventMetadata,
+        event_data: &EventData,
+        batch: &mut BatchKeys,
+    ) -> bool {
+        // Events which can not be identified are always kept
+        let key = match EventKey::from_event_data(event_data) {
+            Some(key) => key,
+            None => return true,
+        };
+        if !batch.contains(&key) && !self.contains(&key, Instant::now()) {
+            batch.insert(key);
+            return true;
+        }
+
+        debug!(
+            "Drop duplicated event from {} (Computer: {}, Channel: {}, EventRecordID: {})",
+            metadata.principal(),
+            key.computer,
+            key.channel,
+            key.event_record_id
+        );
+        counter!(INPUT_EVENTS_DUPLICATES,
+            SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+            SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned())
+        .increment(1);
+        false
+    }
+
+    fn contains(&self, key: &EventKey, now: Instant) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .keys
+            .get(key)
+            .is_some_and(|entry| !Entries::is_expired(entry, self.ttl, now))
+    }
+
+    /// Records the keys of a batch which has been written
+    pub fn record(&self, batch: BatchKeys) {
+        self.record_at(batch, Instant::now())
+    }
+
+    fn record_at(&self, batch: BatchKeys, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.expire(self.ttl, now);
+        for key in batch.keys {
+            entries.clock += 1;
+            let seen = entries.clock;
+            if let Some(previous) = entries.keys.insert(
+                key.clone(),
+                Entry {
+                    recorded: now,
+                    seen,
+                },
+            ) {
+                entries.order.remove(&previous.seen);
+            }
+            entries.order.insert(seen, key);
+        }
+        while entries.keys.len() > self.size {
+            if let Some((_, key)) = entries.order.pop_first() {
+                entries.keys.remove(&key);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().keys.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::settings;
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn event(computer: &str, channel: &str, event_record_id: u64) -> EventData {
+        EventData::new(
+            Arc::new(format!(
+                "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing'/><EventID>4688</EventID><Level>0</Level><EventRecordID>{}</EventRecordID><Channel>{}</Channel><Computer>{}</Computer></System></Event>",
+                event_record_id, channel, computer
+            )),
+            true,
+        )
+    }
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        )
+    }
+
+    /// Applies the window to a batch, records it as written and returns the
+    /// EventRecordID of the kept events
+    fn deliver(window: &DedupWindow, events: &[EventData]) -> Vec<u64> {
+        let metadata = metadata();
+        let mut batch = BatchKeys::default();
+        let kept = events
+            .iter()
+            .filter(|event_data| window.apply(&metadata, event_data, &mut batch))
+            .map(|event_data| {
+                event_data
+                    .event()
+                    .and_then(|event| event.system.as_ref())
+                    .and_then(|system| system.event_record_id)
+                    .unwrap()
+            })
+            .collect();
+        window.record(batch);
+        kept
+    }
+
+    #[test]
+    fn test_duplicated_events_are_dropped() {
+        let window = DedupWindow::new(100, None);
+
+        let batch = [
+            event("win10.windomain.local", "Security", 1),
+            event("win10.windomain.local", "Security", 2),
+            // Duplicate within the batch
+            event("win10.windomain.local", "Security", 1),
+            // Same EventRecordID, but another channel or computer
+            event("win10.windomain.local", "System", 1),
+            event("dc.windomain.local", "Security", 1),
+        ];
+        assert_eq!(deliver(&window, &batch), vec![1, 2, 1, 1]);
+
+        // The batch is sent again, along with a new event
+        let batch = [
+            event("win10.windomain.local", "Security", 1),
+            event("win10.windomain.local", "Security", 2),
+            event("win10.windomain.local", "Security", 3),
+            event("win10.windomain.local", "System", 1),
+            event("dc.windomain.local", "Security", 1),
+        ];
+        assert_eq!(deliver(&window, &batch), vec![3]);
+        assert_eq!(window.len(), 5);
+
+        // Events which can not be identified are always kept
+        let unparsable = EventData::new(Arc::new("<Event>".to_string()), true);
+        let mut batch = BatchKeys::default();
+        assert!(window.apply(&metadata(), &unparsable, &mut batch));
+        assert!(window.apply(&metadata(), &unparsable, &mut batch));
+    }
+
+    #[test]
+    fn test_batches_are_recorded_once_written() {
+        let window = DedupWindow::new(100, None);
+        let metadata = metadata();
+        let events = [
+            event("win10.windomain.local", "Security", 1),
+            event("win10.windomain.local", "Security", 2),
+        ];
+
+        // The batch failed to be written, so it is not recorded and the
+        // client sends it again
+        let mut batch = BatchKeys::default();
+        assert!(events
+            .iter()
+            .all(|event_data| window.apply(&metadata, event_data, &mut batch)));
+        assert_eq!(window.len(), 0);
+        assert_eq!(deliver(&window, &events), vec![1, 2]);
+        assert_eq!(deliver(&window, &events), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_window_is_bounded() {
+        let window = DedupWindow::new(3, None);
+        let events: Vec<EventData> = (1..=5)
+            .map(|id| event("win10.windomain.local", "Security", id))
+            .collect();
+        assert_eq!(deliver(&window, &events), vec![1, 2, 3, 4, 5]);
+        assert_eq!(window.len(), 3);
+
+        // The least recently recorded events have been forgotten
+        assert_eq!(deliver(&window, &events[..2]), vec![1, 2]);
+        assert_eq!(window.len(), 3);
+        assert_eq!(deliver(&window, &events), vec![3, 4]);
+        assert_eq!(window.len(), 3);
+    }
+
+    #[test]
+    fn test_window_ttl() {
+        let window = DedupWindow::new(100, Some(60));
+        let key =
+            EventKey::from_event_data(&event("win10.windomain.local", "Security", 1)).unwrap();
+        let now = Instant::now();
+
+        let mut batch = BatchKeys::default();
+        batch.insert(key.clone());
+        window.record_at(batch, now);
+        assert!(window.contains(&key, now + Duration::from_secs(59)));
+        assert!(!window.contains(&key, now + Duration::from_secs(60)));
+
+        // Expired keys are forgotten when another batch is recorded
+        let other =
+            EventKey::from_event_data(&event("win10.windomain.local", "Security", 2)).unwrap();
+        let mut batch = BatchKeys::default();
+        batch.insert(other.clone());
+        window.record_at(batch, now + Duration::from_secs(61));
+        assert_eq!(window.len(), 1);
+        assert!(window.contains(&other, now + Duration::from_secs(61)));
+    }
+}
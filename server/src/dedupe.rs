@@ -0,0 +1,269 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use common::subscription::OutputDedupeOnChange;
+use log::warn;
+use metrics::counter;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    formats::{
+        csv::{cell, lookup},
+        json,
+    },
+    monitoring::{
+        OUTPUT_DEDUPE_SUPPRESSED_EVENTS, OUTPUT_DRIVER, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID,
+    },
+};
+
+/// Values of the selected fields of an event
+pub type FieldValues = Vec<String>;
+
+#[derive(Debug)]
+struct LastValues {
+    values: FieldValues,
+    // Logical time of the last event of the source
+    seen: u64,
+}
+
+#[derive(Debug, Default)]
+struct Sources {
+    last_values: HashMap<String, LastValues>,
+    clock: u64,
+}
+
+/// Keeps the events whose selected fields changed since the last event sent
+/// for the same source (principal).
+///
+/// Last values are only recorded once events have been written, so that a
+/// batch sent again by a client after a failure is not suppressed. When
+/// `max_sources` sources are known, the least recently seen one is forgotten.
+#[derive(Debug)]
+pub struct ChangeDetector {
+    fields: Vec<String>,
+    max_sources: usize,
+    sources: Mutex<Sources>,
+}
+
+impl ChangeDetector {
+    pub fn new(config: &OutputDedupeOnChange) -> Self {
+        Self {
+            fields: config.fields().to_vec(),
+            max_sources: config.max_sources() as usize,
+            sources: Mutex::new(Sources::default()),
+        }
+    }
+
+    /// Returns the values of the selected fields of an event, using the paths
+    /// of the Json format. Returns None if the event could not be parsed.
+    pub fn values(&self, metadata: &EventMetadata, data: &EventData) -> Option<FieldValues> {
+        let event = data.event()?;
+        if event.additional.error.is_some() {
+            return None;
+        }
+        let value = match json::to_value(event.clone(), metadata) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON: {:?}. Event was: {:?}",
+                    e, event
+                );
+                return None;
+            }
+        };
+        Some(
+            self.fields
+                .iter()
+                .map(|field| cell(lookup(&value, field)).into_owned())
+                .collect(),
+        )
+    }
+
+    /// Keeps the items whose values differ from the ones of the previous item
+    /// of the source. Items without values are always kept.
+    /// Returns the kept items and, if some values changed, the last ones which
+    /// must be recorded once the items have been written.
+    pub fn select<T: Clone>(
+        &self,
+        source: &str,
+        items: &[T],
+        values: &[Option<FieldValues>],
+    ) -> (Vec<T>, Option<FieldValues>) {
+        let recorded = {
+            let mut sources = self.sources.lock().unwrap();
+            sources.clock += 1;
+            let clock = sources.clock;
+            sources.last_values.get_mut(source).map(|last| {
+                last.seen = clock;
+                last.values.clone()
+            })
+        };
+
+        let mut last = recorded.as_ref();
+        let mut changed = false;
+        let mut kept = Vec::with_capacity(items.len());
+        for (item, values) in items.iter().zip(values) {
+            if let Some(values) = values {
+                if last == Some(values) {
+                    continue;
+                }
+                last = Some(values);
+                changed = true;
+            }
+            kept.push(item.clone());
+        }
+        (kept, if changed { last.cloned() } else { None })
+    }
+
+    /// Records the last values sent for a source
+    pub fn record(&self, source: &str, values: FieldValues) {
+        let mut sources = self.sources.lock().unwrap();
+        sources.clock += 1;
+        let seen = sources.clock;
+        if !sources.last_values.contains_key(source)
+            && sources.last_values.len() >= self.max_sources
+        {
+            let oldest = sources
+                .last_values
+                .iter()
+                .min_by_key(|(_, last)| last.seen)
+                .map(|(source, _)| source.clone());
+            if let Some(oldest) = oldest {
+                sources.last_values.remove(&oldest);
+            }
+        }
+        sources
+            .last_values
+            .insert(source.to_owned(), LastValues { values, seen });
+    }
+}
+
+pub fn count_suppressed(metadata: &EventMetadata, driver: &str, events: u64) {
+    counter!(OUTPUT_DEDUPE_SUPPRESSED_EVENTS,
+        SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+        SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+        OUTPUT_DRIVER => driver.to_owned())
+    .increment(events);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use anyhow::Result;
+    use common::{settings, subscription::SubscriptionData};
+
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    use super::*;
+
+    fn event(computer: &str, value: &str) -> Arc<String> {
+        Arc::new(format!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Config-Audit'/><EventID>100</EventID><Level>4</Level><Computer>{}</Computer></System><EventData><Data Name='Setting'>firewall</Data><Data Name='Value'>{}</Data></EventData></Event>",
+            computer, value
+        ))
+    }
+
+    /// Sends a batch of a source and returns the delivered events
+    fn deliver(
+        detector: &ChangeDetector,
+        metadata: &EventMetadata,
+        events: &[Arc<String>],
+        written: bool,
+    ) -> Vec<Arc<String>> {
+        let values: Vec<Option<FieldValues>> = events
+            .iter()
+            .map(|raw| detector.values(metadata, &EventData::new(raw.clone(), true)))
+            .collect();
+        let (kept, last) = detector.select(metadata.principal(), events, &values);
+        if written {
+            if let Some(last) = last {
+                detector.record(metadata.principal(), last);
+            }
+        }
+        kept
+    }
+
+    #[test]
+    fn test_dedupe_on_change() -> Result<()> {
+        let mut context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut context)?;
+        let addr = SocketAddr::from_str("192.168.58.100:5985")?;
+        let metadata = |principal: &str| {
+            EventMetadata::new(&addr, principal, None, &subscription, "1".to_string(), None)
+        };
+        let win10 = metadata("WIN10$@WINDOMAIN.LOCAL");
+        let win11 = metadata("WIN11$@WINDOMAIN.LOCAL");
+
+        let detector = ChangeDetector::new(&OutputDedupeOnChange::try_new(
+            vec![
+                "EventData.Setting".to_string(),
+                "EventData.Value".to_string(),
+            ],
+            10,
+        )?);
+
+        // Repeated unchanged events only pass through once
+        let on = event("win10", "on");
+        let off = event("win10", "off");
+        let batch = vec![on.clone(), on.clone(), on.clone()];
+        assert_eq!(deliver(&detector, &win10, &batch, true), vec![on.clone()]);
+        assert!(deliver(&detector, &win10, &batch, true).is_empty());
+
+        // Each source has its own last value
+        let on11 = event("win11", "on");
+        assert_eq!(
+            deliver(&detector, &win11, &[on11.clone(), on11.clone()], true),
+            vec![on11.clone()]
+        );
+
+        // Changes pass through, including a return to a previous value.
+        // Unparsable events are always delivered.
+        let invalid = Arc::new("not an event".to_string());
+        let batch = vec![
+            on.clone(),
+            off.clone(),
+            invalid.clone(),
+            off.clone(),
+            on.clone(),
+        ];
+        assert_eq!(
+            deliver(&detector, &win10, &batch, true),
+            vec![off.clone(), invalid.clone(), on.clone()]
+        );
+        assert!(deliver(&detector, &win11, &[on11.clone()], true).is_empty());
+
+        // A batch that could not be written is delivered again
+        assert_eq!(
+            deliver(&detector, &win10, &[off.clone()], false),
+            vec![off.clone()]
+        );
+        assert_eq!(
+            deliver(&detector, &win10, &[off.clone()], true),
+            vec![off.clone()]
+        );
+        assert!(deliver(&detector, &win10, &[off.clone()], true).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_sources() -> Result<()> {
+        let detector = ChangeDetector::new(&OutputDedupeOnChange::try_new(
+            vec!["EventData.Value".to_string()],
+            2,
+        )?);
+        let value = |v: &str| Some(vec![v.to_string()]);
+
+        detector.record("a", vec!["1".to_string()]);
+        detector.record("b", vec!["1".to_string()]);
+        // "a" is seen again, so "b" is the least recently seen source
+        assert!(detector.select("a", &[1], &[value("1")]).0.is_empty());
+        detector.record("c", vec!["1".to_string()]);
+
+        assert!(detector.select("a", &[1], &[value("1")]).0.is_empty());
+        assert!(detector.select("c", &[1], &[value("1")]).0.is_empty());
+        // "b" has been forgotten
+        assert_eq!(detector.select("b", &[1], &[value("1")]).0, vec![1]);
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use common::subscription::OutputDegradation;
+use log::{info, warn};
+use metrics::{counter, gauge};
+
+use crate::{
+    event::EventMetadata,
+    monitoring::{
+        OUTPUT_DEGRADED_GAUGE, OUTPUT_DEGRADED_SKIPPED_EVENTS, OUTPUT_DRIVER, SUBSCRIPTION_NAME,
+        SUBSCRIPTION_UUID,
+    },
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HealthTransition {
+    Degraded,
+    Recovered,
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    consecutive_slow: u32,
+    // Set while the output is degraded
+    degraded_until: Option<Instant>,
+}
+
+/// Tracks the response time of an output to skip it while it is degraded.
+///
+/// After `slow_responses` consecutive writes slower than `send_timeout_ms`,
+/// the output is skipped during `cooldown_secs`. Once the cooldown is over,
+/// writes are sent again: a fast successful write recovers the output and a
+/// slow one degrades it for another cooldown. Failed writes are handled as
+/// usual (the batch is not acknowledged) and only count when they are slow.
+#[derive(Debug)]
+pub struct OutputHealth {
+    send_timeout: Duration,
+    slow_responses: u32,
+    cooldown: Duration,
+    state: Mutex<HealthState>,
+}
+
+impl OutputHealth {
+    pub fn new(degradation: &OutputDegradation) -> Self {
+        Self {
+            send_timeout: Duration::from_millis(degradation.send_timeout_ms()),
+            slow_responses: degradation.slow_responses(),
+            cooldown: Duration::from_secs(degradation.cooldown_secs()),
+            state: Mutex::new(HealthState::default()),
+        }
+    }
+
+    /// Whether writes must skip the output at `now`
+    pub fn skip(&self, now: Instant) -> bool {
+        let state = self.state.lock().unwrap();
+        state.degraded_until.is_some_and(|until| now < until)
+    }
+
+    /// Records a write which took `elapsed` and completed at `now`.
+    /// Returns the state change of the output, if any.
+    pub fn record(
+        &self,
+        elapsed: Duration,
+        succeeded: bool,
+        now: Instant,
+    ) -> Option<HealthTransition> {
+        let mut state = self.state.lock().unwrap();
+        if elapsed > self.send_timeout {
+            state.consecutive_slow = state.consecutive_slow.saturating_add(1);
+            if state.consecutive_slow < self.slow_responses {
+                return None;
+            }
+            let was_degraded = state.degraded_until.is_some();
+            state.degraded_until = Some(now + self.cooldown);
+            if was_degraded {
+                None
+            } else {
+                Some(HealthTransition::Degraded)
+            }
+        } else if succeeded {
+            state.consecutive_slow = 0;
+            state
+                .degraded_until
+                .take()
+                .map(|_| HealthTransition::Recovered)
+        } else {
+            None
+        }
+    }
+}
+
+/// Logs a state change of an output and updates the associated metric
+pub fn report(metadata: &EventMetadata, driver: &str, transition: HealthTransition) {
+    let degraded = match transition {
+        HealthTransition::Degraded => {
+            warn!(
+                "Output {} of subscription {} is degraded and will be skipped",
+                driver,
+                metadata.subscription_name()
+            );
+            1.0
+        }
+        HealthTransition::Recovered => {
+            info!(
+                "Output {} of subscription {} has recovered",
+                driver,
+                metadata.subscription_name()
+            );
+            0.0
+        }
+    };
+    gauge!(OUTPUT_DEGRADED_GAUGE,
+        SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+        SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+        OUTPUT_DRIVER => driver.to_owned())
+    .set(degraded);
+}
+
+pub fn count_skipped(metadata: &EventMetadata, driver: &str, events: u64) {
+    counter!(OUTPUT_DEGRADED_SKIPPED_EVENTS,
+        SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+        SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+        OUTPUT_DRIVER => driver.to_owned())
+    .increment(events);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOW: Duration = Duration::from_millis(800);
+    const FAST: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn test_degraded_and_recovered() -> anyhow::Result<()> {
+        let health = OutputHealth::new(&OutputDegradation::try_new(500, 3, 30)?);
+        let start = Instant::now();
+
+        // Consecutive slow responses below the threshold are tolerated,
+        // and a fast response resets the count
+        assert_eq!(health.record(SLOW, true, start), None);
+        assert_eq!(health.record(SLOW, true, start), None);
+        assert_eq!(health.record(FAST, true, start), None);
+        assert_eq!(health.record(SLOW, true, start), None);
+        assert_eq!(health.record(SLOW, true, start), None);
+        assert!(!health.skip(start));
+
+        // The third consecutive slow response degrades the output
+        assert_eq!(
+            health.record(SLOW, true, start),
+            Some(HealthTransition::Degraded)
+        );
+        assert!(health.skip(start));
+        assert!(health.skip(start + Duration::from_secs(29)));
+
+        // After the cooldown, the output is probed again: a slow response
+        // degrades it for another cooldown
+        let probe = start + Duration::from_secs(30);
+        assert!(!health.skip(probe));
+        assert_eq!(health.record(SLOW, true, probe), None);
+        assert!(health.skip(probe + Duration::from_secs(29)));
+
+        // A fast response after the cooldown recovers the output
+        let probe = probe + Duration::from_secs(30);
+        assert!(!health.skip(probe));
+        assert_eq!(
+            health.record(FAST, true, probe),
+            Some(HealthTransition::Recovered)
+        );
+        assert!(!health.skip(probe));
+
+        // The slow responses count starts over
+        assert_eq!(health.record(SLOW, true, probe), None);
+        assert!(!health.skip(probe));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_failures() -> anyhow::Result<()> {
+        let health = OutputHealth::new(&OutputDegradation::try_new(500, 2, 30)?);
+        let start = Instant::now();
+
+        // A slow failure counts as a slow response
+        assert_eq!(health.record(SLOW, false, start), None);
+        assert_eq!(
+            health.record(SLOW, false, start),
+            Some(HealthTransition::Degraded)
+        );
+
+        // A fast failure does not recover the output
+        let probe = start + Duration::from_secs(30);
+        assert_eq!(health.record(FAST, false, probe), None);
+        assert_eq!(
+            health.record(FAST, true, probe),
+            Some(HealthTransition::Recovered)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    error::Error,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use common::subscription::EventHubsConfiguration;
+use futures::future::join_all;
+use log::{debug, warn};
+use rdkafka::{
+    client::OAuthToken,
+    producer::{FutureProducer, FutureRecord},
+    util::Timeout,
+    ClientConfig, ClientContext,
+};
+use serde::Deserialize;
+
+use crate::{event::EventMetadata, output::OutputDriver};
+
+/// Port of the Kafka endpoint of Event Hubs namespaces
+const KAFKA_PORT: u16 = 9093;
+/// Largest message accepted by Event Hubs (standard tier). Larger events
+/// are rejected by the producer.
+const MAX_MESSAGE_BYTES: usize = 1_046_528;
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Azure Instance Metadata Service, which provides the tokens of the
+/// managed identity of the host
+const IMDS_ADDR: ([u8; 4], u16) = ([169, 254, 169, 254], 80);
+const IMDS_TIMEOUT: Duration = Duration::from_secs(10);
+// Name associated with tokens, which is not checked by Event Hubs
+const OAUTH_PRINCIPAL_NAME: &str = "openwec";
+
+#[derive(Deserialize)]
+struct ManagedIdentityToken {
+    access_token: String,
+    // Seconds since the Unix epoch
+    expires_on: String,
+}
+
+/// Parses the response of the Instance Metadata Service to a token request
+fn parse_token_response(response: &[u8]) -> Result<OAuthToken> {
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let body_start = match parsed.parse(response)? {
+        httparse::Status::Complete(len) => len,
+        httparse::Status::Partial => bail!("Truncated response"),
+    };
+    let body = &response[body_start..];
+    if parsed.code != Some(200) {
+        bail!(
+            "Instance Metadata Service responded with status {:?}: {}",
+            parsed.code,
+            String::from_utf8_lossy(body)
+        );
+    }
+    let token: ManagedIdentityToken =
+        serde_json::from_slice(body).context("Failed to parse managed identity token")?;
+    let expires_on: i64 = token
+        .expires_on
+        .parse()
+        .with_context(|| format!("Invalid token expiration {:?}", token.expires_on))?;
+    Ok(OAuthToken {
+        token: token.access_token,
+        principal_name: OAUTH_PRINCIPAL_NAME.to_owned(),
+        lifetime_ms: expires_on * 1000,
+    })
+}
+
+/// Requests a token of the managed identity of the host for `resource`.
+/// This is called by librdkafka threads, so blocking is fine.
+fn fetch_managed_identity_token(resource: &str) -> Result<OAuthToken> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::from(IMDS_ADDR), IMDS_TIMEOUT)
+        .context("Failed to connect to the Instance Metadata Service")?;
+    stream.set_read_timeout(Some(IMDS_TIMEOUT))?;
+    stream.set_write_timeout(Some(IMDS_TIMEOUT))?;
+    // HTTP/1.0 responses end with the connection
+    write!(
+        stream,
+        "GET /metadata/identity/oauth2/token?api-version=2018-02-01&resource={} HTTP/1.0\r\n\
+         Host: 169.254.169.254\r\n\
+         Metadata: true\r\n\r\n",
+        url::form_urlencoded::byte_serialize(resource.as_bytes()).collect::<String>()
+    )?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    parse_token_response(&response)
+}
+
+/// Provides the tokens of the managed identity of the host when the
+/// OAUTHBEARER mechanism is used
+struct EventHubsContext {
+    resource: String,
+}
+
+impl ClientContext for EventHubsContext {
+    const ENABLE_REFRESH_OAUTH_TOKEN: bool = true;
+
+    fn generate_oauth_token(
+        &self,
+        _oauthbearer_config: Option<&str>,
+    ) -> std::result::Result<OAuthToken, Box<dyn Error>> {
+        debug!("Request a managed identity token for {}", self.resource);
+        fetch_managed_identity_token(&self.resource).map_err(|e| {
+            warn!(
+                "Failed to get a managed identity token for {}: {:#}",
+                self.resource, e
+            );
+            format!("{:#}", e).into()
+        })
+    }
+}
+
+fn client_config(config: &EventHubsConfiguration) -> ClientConfig {
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set(
+            "bootstrap.servers",
+            format!("{}:{}", config.fully_qualified_namespace(), KAFKA_PORT),
+        )
+        .set("security.protocol", "SASL_SSL")
+        .set("delivery.timeout.ms", "30000")
+        .set("message.max.bytes", MAX_MESSAGE_BYTES.to_string());
+    match config.connection_string() {
+        Some(connection_string) => client_config
+            .set("sasl.mechanism", "PLAIN")
+            .set("sasl.username", "$ConnectionString")
+            .set("sasl.password", connection_string),
+        None => client_config.set("sasl.mechanism", "OAUTHBEARER"),
+    };
+    client_config
+}
+
+/// Part of a partition key template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PartitionKeyPart {
+    Text(String),
+    Ip,
+    Principal,
+    Node,
+}
+
+/// Parses a partition key template, whose tokens have been checked when the
+/// configuration has been loaded. `\{` and `\}` are literal braces.
+fn parse_partition_key(partition_key: &str) -> Result<Vec<PartitionKeyPart>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = partition_key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    text.push(escaped);
+                }
+            }
+            '{' => {
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => token.push(c),
+                        None => bail!("Unclosed token in {:?}", partition_key),
+                    }
+                }
+                if !text.is_empty() {
+                    parts.push(PartitionKeyPart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(match token.trim() {
+                    "ip" => PartitionKeyPart::Ip,
+                    "principal" => PartitionKeyPart::Principal,
+                    "node" => PartitionKeyPart::Node,
+                    other => bail!("Unknown token {{{}}} in {:?}", other, partition_key),
+                });
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(PartitionKeyPart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Builds the partition key of the events of a client
+fn render_partition_key(parts: &[PartitionKeyPart], metadata: &EventMetadata) -> String {
+    let mut key = String::new();
+    for part in parts {
+        match part {
+            PartitionKeyPart::Text(text) => key.push_str(text),
+            PartitionKeyPart::Ip => key.push_str(&metadata.addr().ip().to_string()),
+            PartitionKeyPart::Principal => key.push_str(metadata.principal()),
+            PartitionKeyPart::Node => match metadata.node_name() {
+                Some(node_name) => key.push_str(node_name),
+                None => {
+                    warn!("node name is not configured on this node but is used to build a partition key in EventHubs driver");
+                    key.push_str("{node}");
+                }
+            },
+        }
+    }
+    key
+}
+
+pub struct OutputEventHubs {
+    config: EventHubsConfiguration,
+    partition_key: Option<Vec<PartitionKeyPart>>,
+    producer: FutureProducer<EventHubsContext>,
+}
+
+impl OutputEventHubs {
+    pub fn new(config: &EventHubsConfiguration) -> Result<Self> {
+        debug!(
+            "Initialize EventHubs driver for hub {} of namespace {}",
+            config.hub(),
+            config.namespace()
+        );
+        Self::with_client_config(config, &client_config(config))
+    }
+
+    fn with_client_config(
+        config: &EventHubsConfiguration,
+        client_config: &ClientConfig,
+    ) -> Result<Self> {
+        let context = EventHubsContext {
+            resource: format!("https://{}", config.fully_qualified_namespace()),
+        };
+        let partition_key = config
+            .partition_key()
+            .map(|partition_key| parse_partition_key(partition_key))
+            .transpose()
+            .context("Invalid partition_key")?;
+        Ok(OutputEventHubs {
+            config: config.clone(),
+            partition_key,
+            producer: client_config
+                .create_with_context(context)
+                .context("Failed to create Event Hubs producer")?,
+        })
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputEventHubs {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let key = self
+            .partition_key
+            .as_ref()
+            .map(|parts| render_partition_key(parts, &metadata));
+
+        // Records are enqueued in order, so that events which share a
+        // partition key keep their order
+        let futures = events.iter().map(|event| {
+            let mut record: FutureRecord<str, [u8]> =
+                FutureRecord::to(self.config.hub()).payload(event.as_slice());
+            if let Some(key) = &key {
+                record = record.key(key.as_str());
+            }
+            self.producer.send(record, Timeout::After(SEND_TIMEOUT))
+        });
+        for result in join_all(futures).await {
+            if let Err((e, _)) = result {
+                bail!(
+                    "Failed to send event to Event Hub {} of namespace {}: {}",
+                    self.config.hub(),
+                    self.config.namespace(),
+                    e
+                );
+            }
+        }
+        debug!(
+            "Sent {} events to Event Hub {}",
+            events.len(),
+            self.config.hub()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        str::FromStr,
+    };
+
+    use common::{settings, subscription::SubscriptionData};
+    use rdkafka::{
+        consumer::{Consumer, StreamConsumer},
+        mocking::MockCluster,
+        Message, Offset, TopicPartitionList,
+    };
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const HUB: &str = "events";
+    const PARTITIONS: i32 = 4;
+
+    fn event_metadata(addr: &str) -> Result<Arc<EventMetadata>> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)?;
+        Ok(Arc::new(EventMetadata::new(
+            &SocketAddr::from_str(addr)?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        )))
+    }
+
+    #[test]
+    fn test_partition_key() -> Result<()> {
+        assert_eq!(parse_partition_key("{ip}")?, vec![PartitionKeyPart::Ip]);
+        assert_eq!(
+            parse_partition_key("wec-{ node }/{principal}\\{x\\}")?,
+            vec![
+                PartitionKeyPart::Text("wec-".to_string()),
+                PartitionKeyPart::Node,
+                PartitionKeyPart::Text("/".to_string()),
+                PartitionKeyPart::Principal,
+                PartitionKeyPart::Text("{x}".to_string()),
+            ]
+        );
+        assert!(parse_partition_key("{ip").is_err());
+        assert!(parse_partition_key("{unknown}").is_err());
+
+        let metadata = event_metadata("192.168.58.100:5985")?;
+        let parts = parse_partition_key("{ip}-{principal}")?;
+        assert_eq!(
+            render_partition_key(&parts, &metadata),
+            "192.168.58.100-WIN10$@WINDOMAIN.LOCAL"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_events_are_partitioned_by_key() -> Result<()> {
+        let mock_cluster = MockCluster::new(1)?;
+        mock_cluster.create_topic(HUB, PARTITIONS, 1)?;
+
+        let config = EventHubsConfiguration::new(
+            "openwec".to_string(),
+            HUB.to_string(),
+            Some("Endpoint=sb://openwec.servicebus.windows.net/;SharedAccessKeyName=send;SharedAccessKey=c2VjcmV0".to_string()),
+            false,
+            Some("{ip}".to_string()),
+        )?;
+        let mut client_config = ClientConfig::new();
+        client_config.set("bootstrap.servers", mock_cluster.bootstrap_servers());
+        let output = OutputEventHubs::with_client_config(&config, &client_config)?;
+
+        let ips = [
+            "192.168.58.100",
+            "192.168.58.101",
+            "192.168.58.102",
+            "10.0.0.1",
+        ];
+        for ip in ips {
+            let events = (0..5)
+                .map(|index| Arc::new(format!("{}-{}", ip, index).into_bytes()))
+                .collect();
+            output
+                .write(event_metadata(&format!("{}:5985", ip))?, Arc::new(events))
+                .await?;
+        }
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", mock_cluster.bootstrap_servers())
+            .set("group.id", "openwec-test")
+            .create()?;
+        let mut partitions = TopicPartitionList::new();
+        for partition in 0..PARTITIONS {
+            partitions.add_partition_offset(HUB, partition, Offset::Beginning)?;
+        }
+        consumer.assign(&partitions)?;
+
+        // <key> => (<partitions>, <payloads>)
+        let mut received: BTreeMap<String, (BTreeSet<i32>, Vec<String>)> = BTreeMap::new();
+        for _ in 0..ips.len() * 5 {
+            let message = tokio::time::timeout(Duration::from_secs(10), consumer.recv()).await??;
+            let key = String::from_utf8(message.key().unwrap_or_default().to_vec())?;
+            let payload = String::from_utf8(message.payload().unwrap_or_default().to_vec())?;
+            let entry = received.entry(key).or_default();
+            entry.0.insert(message.partition());
+            entry.1.push(payload);
+        }
+
+        assert_eq!(received.len(), ips.len());
+        for ip in ips {
+            let (partitions, payloads) = &received[ip];
+            // All events of a client land in the same partition, in order
+            assert_eq!(partitions.len(), 1);
+            let expected: Vec<String> = (0..5).map(|index| format!("{}-{}", ip, index)).collect();
+            assert_eq!(payloads, &expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_token_response() -> Result<()> {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"access_token\":\"eyJ0eXAi\",\"expires_on\":\"1700000000\",\"resource\":\"https://openwec.servicebus.windows.net\",\"token_type\":\"Bearer\"}";
+        let token = parse_token_response(response)?;
+        assert_eq!(token.token, "eyJ0eXAi");
+        assert_eq!(token.lifetime_ms, 1_700_000_000_000);
+
+        let error = b"HTTP/1.1 400 Bad Request\r\n\r\n{\"error\":\"invalid_request\"}";
+        assert!(parse_token_response(error).is_err());
+        Ok(())
+    }
+}
@@ -1,30 +1,40 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use leon::Template;
 use log::{debug, info, warn};
 use tokio::sync::oneshot;
 use tokio::time::Instant;
 
 use crate::event::EventMetadata;
-use crate::output::OutputDriver;
+use crate::formats::collector::COLLECTOR_VERSION;
+use crate::output::{BinaryFileLayout, OutputDriver};
 use anyhow::{anyhow, bail, Context, Result};
-use common::subscription::FilesConfiguration;
+use common::settings::PathAnonymization;
+use common::subscription::{FilesCompression, FilesConfiguration, FilesRotation};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hex::ToHex;
+use sha1::{Digest, Sha1};
 use std::borrow::Cow;
 use std::cmp::min;
-use std::collections::HashMap;
-use std::fs::{create_dir_all, File, OpenOptions};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, rename, File, OpenOptions};
 use std::io::Write;
 use std::net::IpAddr;
+use std::path::Path;
 use std::sync::mpsc::{self, Receiver};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use std::{path::PathBuf, str::FromStr};
 
 pub struct OutputFilesContext {
     tx: mpsc::Sender<WriteFilesMessage>,
+    log_path_anonymization: PathAnonymization,
 }
 
 impl OutputFilesContext {
-    pub fn new() -> Self {
+    pub fn new(log_path_anonymization: PathAnonymization) -> Self {
         // Create a communication channel with the thread responsible for file management
         let (tx, rx) = mpsc::channel();
 
@@ -33,7 +43,10 @@ impl OutputFilesContext {
             run(rx);
         });
 
-        Self { tx }
+        Self {
+            tx,
+            log_path_anonymization,
+        }
     }
 
     pub fn clear(&mut self) {
@@ -64,29 +77,229 @@ enum WriteFilesMessage {
     Write(WriteMessage),
     GarbageCollect(u64),
     ClearHandles,
+    // Closes the given files, so that compressed streams are terminated
+    Close(Vec<PathBuf>),
     Stop,
 }
 
 #[derive(Debug)]
 pub struct WriteMessage {
     path: PathBuf,
-    content: String,
+    // Path written in logs, which may be anonymized
+    display_path: String,
+    // Written as is at the beginning of new files
+    header: Option<Arc<Vec<u8>>>,
+    rotation: Option<FilesRotation>,
+    compression: FilesCompression,
+    content: Vec<u8>,
     resp: oneshot::Sender<Result<()>>,
 }
 
+enum FileWriter {
+    Plain(File),
+    // Each time a file is opened, a new gzip member is appended to it
+    Gzip(GzEncoder<File>),
+}
+
+impl FileWriter {
+    fn new(file: File, compression: FilesCompression) -> Self {
+        match compression {
+            FilesCompression::None => FileWriter::Plain(file),
+            FilesCompression::Gzip => {
+                FileWriter::Gzip(GzEncoder::new(file, Compression::default()))
+            }
+        }
+    }
+
+    fn file(&self) -> &File {
+        match self {
+            FileWriter::Plain(file) => file,
+            FileWriter::Gzip(encoder) => encoder.get_ref(),
+        }
+    }
+
+    fn write_all(&mut self, content: &[u8]) -> std::io::Result<()> {
+        match self {
+            FileWriter::Plain(file) => file.write_all(content),
+            FileWriter::Gzip(encoder) => encoder.write_all(content),
+        }
+    }
+
+    /// Makes everything written so far readable from the file. Compressed
+    /// data which is still in the encoder is written using a sync flush.
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            FileWriter::Plain(file) => file.flush(),
+            FileWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+
+    /// Terminates the gzip stream. Nothing can be written afterwards.
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self {
+            FileWriter::Plain(file) => file.flush(),
+            FileWriter::Gzip(encoder) => encoder.try_finish(),
+        }
+    }
+}
+
 struct FileContainer {
-    pub file: File,
+    pub file: FileWriter,
     pub last_used: Instant,
+    pub display_path: String,
+    // Number of bytes in the file. For compressed files, this is updated
+    // once the content of each message has been flushed.
+    pub size: u64,
+    // Creation time of the file, used for age-based rotation
+    pub created: SystemTime,
 }
 
 impl FileContainer {
-    pub fn new(file: File, last_used: Instant) -> Self {
-        Self { file, last_used }
+    pub fn new(
+        file: File,
+        compression: FilesCompression,
+        last_used: Instant,
+        display_path: String,
+    ) -> Result<Self> {
+        let metadata = file.metadata()?;
+        Ok(Self {
+            file: FileWriter::new(file, compression),
+            last_used,
+            display_path,
+            size: metadata.len(),
+            // Files which existed before they were opened are considered
+            // as created now if the filesystem does not support creation time
+            created: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+        })
     }
 
     pub fn has_not_been_used_since(&self, instant: Instant) -> bool {
         self.last_used < instant
     }
+
+    pub fn write_all(&mut self, content: &[u8]) -> Result<()> {
+        self.file.write_all(content)?;
+        self.size += content.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        if let FileWriter::Gzip(_) = self.file {
+            self.size = self.file.file().metadata()?.len();
+        }
+        Ok(())
+    }
+
+    /// Terminates compressed streams before the file is closed
+    pub fn close(mut self) {
+        if let Err(e) = self.file.finish() {
+            warn!("Failed to close file {}: {}", self.display_path, e);
+        }
+    }
+
+    /// Renames the file to `<path>.<timestamp>` (or `<path without
+    /// .gz>.<timestamp>.gz`) and replaces it by a new empty file
+    pub fn rotate(&mut self, path: &Path, compression: FilesCompression) -> Result<()> {
+        // Everything written must be in the file before it is renamed
+        self.file.finish()?;
+        let now = SystemTime::now();
+        let rotated_path = rotated_path(path, now, compression);
+        let display_rotated_path = rotated_path_display(&self.display_path, now, compression);
+        info!(
+            "Rotate file {} to {}",
+            self.display_path, display_rotated_path
+        );
+        rename(path, &rotated_path).with_context(|| {
+            format!(
+                "Failed to rename {} to {}",
+                self.display_path, display_rotated_path
+            )
+        })?;
+        let file = open_file(path, &self.display_path)?;
+        self.file = FileWriter::new(file, compression);
+        self.size = 0;
+        self.created = now;
+        Ok(())
+    }
+}
+
+fn open_file(path: &Path, display_path: &str) -> Result<File> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("Failed to retrieve messages parent folder"))?;
+    // Create directory (if it does not already exist)
+    let display_parent = Path::new(display_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    debug!("Create directory {}", display_parent.display());
+    create_dir_all(parent)
+        .with_context(|| format!("Failed to create directory {}", display_parent.display()))?;
+    // Open file
+    debug!("Open file {}", display_path);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open file {}", display_path))
+}
+
+fn rotation_suffix(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y%m%dT%H%M%S%.6fZ")
+        .to_string()
+}
+
+fn rotated_path(path: &Path, time: SystemTime, compression: FilesCompression) -> PathBuf {
+    PathBuf::from(rotated_path_display(
+        &path.to_string_lossy(),
+        time,
+        compression,
+    ))
+}
+
+fn rotated_path_display(
+    display_path: &str,
+    time: SystemTime,
+    compression: FilesCompression,
+) -> String {
+    match compression {
+        FilesCompression::None => format!("{}.{}", display_path, rotation_suffix(time)),
+        FilesCompression::Gzip => {
+            let base = display_path
+                .strip_suffix(GZIP_SUFFIX)
+                .unwrap_or(display_path);
+            format!("{}.{}{}", base, rotation_suffix(time), GZIP_SUFFIX)
+        }
+    }
+}
+
+/// Whether a file of `size` bytes created at `created` must be rotated
+/// before `len` more bytes are written to it. Empty files are never
+/// rotated, so that idle clients do not produce empty files.
+fn must_rotate(
+    rotation: &FilesRotation,
+    size: u64,
+    created: SystemTime,
+    now: SystemTime,
+    len: u64,
+) -> bool {
+    if size == 0 {
+        return false;
+    }
+    if let Some(max_size_bytes) = rotation.max_size_bytes() {
+        if size.saturating_add(len) > max_size_bytes {
+            return true;
+        }
+    }
+    if let Some(max_age_secs) = rotation.max_age_secs() {
+        // The clock may have gone backwards
+        let age = now.duration_since(created).unwrap_or_default();
+        if age >= Duration::from_secs(max_age_secs) {
+            return true;
+        }
+    }
+    false
 }
 
 fn handle_message(
@@ -94,40 +307,68 @@ fn handle_message(
     message: &WriteMessage,
 ) -> Result<()> {
     let now = Instant::now();
-    let parent = message
-        .path
-        .parent()
-        .ok_or_else(|| anyhow!("Failed to retrieve messages parent folder"))?;
     let path = &message.path;
-    match file_handles.get_mut(path) {
-        Some(file_container) => {
-            debug!("File {} is already opened", path.display());
-            file_container.last_used = now;
-            // The path already exists in file_handles map
-            file_container.file.write_all(message.content.as_bytes())?;
-        }
-        None => {
-            // Create directory (if it does not already exist)
-            debug!("Create directory {}", parent.display());
-            create_dir_all(parent)?;
-            // Open file
-            debug!("Open file {}", path.display());
-            let file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)
-                .with_context(|| format!("Failed to open file {}", path.display()))?;
-
-            let mut file_container = FileContainer::new(file, now);
-            file_container.file.write_all(message.content.as_bytes())?;
-
-            // Insert it into file_buffers map
-            file_handles.insert(path.clone(), file_container);
+    let display_path = &message.display_path;
+    let file_container = match file_handles.entry(path.clone()) {
+        Entry::Occupied(entry) => {
+            debug!("File {} is already opened", display_path);
+            entry.into_mut()
+        }
+        Entry::Vacant(entry) => {
+            let file = open_file(path, display_path)?;
+            entry.insert(FileContainer::new(
+                file,
+                message.compression,
+                now,
+                display_path.clone(),
+            )?)
         }
     };
+    file_container.last_used = now;
+
+    if let Some(rotation) = &message.rotation {
+        if must_rotate(
+            rotation,
+            file_container.size,
+            file_container.created,
+            SystemTime::now(),
+            message.content.len() as u64,
+        ) {
+            if let Err(e) = file_container.rotate(path, message.compression) {
+                // The file may have been closed, it is opened again by the
+                // next write
+                file_handles.remove(path);
+                return Err(e);
+            }
+        }
+    }
+
+    // The header is written only once, when the file is created
+    if let Some(header) = &message.header {
+        if file_container.size == 0 {
+            file_container.write_all(header)?;
+        }
+    }
+    file_container.write_all(&message.content)?;
+    file_container.flush()?;
     Ok(())
 }
 
+fn close(file_handles: &mut HashMap<PathBuf, FileContainer>, paths: &[PathBuf]) {
+    for path in paths {
+        if let Some(file_container) = file_handles.remove(path) {
+            debug!("Closing file {}", file_container.display_path);
+            file_container.close();
+        }
+    }
+}
+
+fn close_all(file_handles: &mut HashMap<PathBuf, FileContainer>) {
+    for (_, file_container) in file_handles.drain() {
+        file_container.close();
+    }
+}
+
 fn garbage_collect(
     file_handles: &mut HashMap<PathBuf, FileContainer>,
     files_descriptor_close_timeout: u64,
@@ -141,12 +382,13 @@ fn garbage_collect(
     }
 
     for path in path_to_remove {
-        debug!(
-            "Closing file descriptor of {} because it has not been used since {} seconds.",
-            path.display(),
-            files_descriptor_close_timeout
-        );
-        file_handles.remove(&path);
+        if let Some(file_container) = file_handles.remove(&path) {
+            debug!(
+                "Closing file descriptor of {} because it has not been used since {} seconds.",
+                file_container.display_path, files_descriptor_close_timeout
+            );
+            file_container.close();
+        }
     }
 }
 
@@ -171,7 +413,11 @@ fn run(rx: Receiver<WriteFilesMessage>) {
             }
             Ok(WriteFilesMessage::ClearHandles) => {
                 debug!("Files handler thread received a ClearHandles command");
-                file_handles.clear();
+                close_all(&mut file_handles);
+            }
+            Ok(WriteFilesMessage::Close(paths)) => {
+                debug!("Files handler thread received a Close command");
+                close(&mut file_handles, &paths);
             }
             Ok(WriteFilesMessage::Stop) => {
                 debug!("Files handler thread received a stop command");
@@ -183,14 +429,35 @@ fn run(rx: Receiver<WriteFilesMessage>) {
             }
         }
     }
+    close_all(&mut file_handles);
     info!("Exiting Files output thread");
 }
 
 struct PathValues {
-    metadata: Arc<EventMetadata>
+    metadata: Arc<EventMetadata>,
+    anonymization: PathAnonymization,
 }
 
 impl PathValues {
+    /// Hides a value identifying the client (principal or IP address)
+    fn anonymize(&self, value: String) -> Cow<'_, str> {
+        match self.anonymization {
+            PathAnonymization::Disabled => Cow::from(value),
+            PathAnonymization::Hash => {
+                let mut hasher = Sha1::new();
+                hasher.update(value.as_bytes());
+                let mut hash = hasher.finalize().encode_hex::<String>();
+                hash.truncate(12);
+                Cow::from(hash)
+            }
+            PathAnonymization::Truncate => {
+                let mut truncated: String = value.chars().take(3).collect();
+                truncated.push('*');
+                Cow::from(truncated)
+            }
+        }
+    }
+
     fn split(&self, ip_str: &str, capacity: usize, index: u8, sep: char) -> String {
         let mut result = String::with_capacity(capacity);
         let mut count = 0;
@@ -250,9 +517,9 @@ impl PathValues {
 impl leon::Values for PathValues {
     fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
         if key == "ip" {
-            Some(Cow::from(self.metadata.addr().ip().to_string()))
+            Some(self.anonymize(self.metadata.addr().ip().to_string()))
         } else if key == "principal" {
-            Some(sanitize_name(self.metadata.principal()).into())
+            Some(self.anonymize(sanitize_name(self.metadata.principal())))
         } else if key == "node" {
             if let Some(node_name) = self.metadata.node_name() {
                 Some(node_name.as_str().into())
@@ -260,11 +527,55 @@ impl leon::Values for PathValues {
                 warn!("node name is not configured on this node but is used to build a path in Files driver");
                 Some("{node}".into())
             }
+        } else if key == "tenant" {
+            if let Some(tenant) = self.metadata.subscription_tenant() {
+                Some(tenant.as_str().into())
+            } else {
+                warn!(
+                    "subscription {} has no tenant but it is used to build a path in Files driver",
+                    self.metadata.subscription_name()
+                );
+                Some("{tenant}".into())
+            }
+        } else if key == "collector_version" {
+            Some(COLLECTOR_VERSION.into())
+        } else if key == "year" {
+            Some(
+                self.metadata
+                    .time_received()
+                    .format("%Y")
+                    .to_string()
+                    .into(),
+            )
+        } else if key == "month" {
+            Some(
+                self.metadata
+                    .time_received()
+                    .format("%m")
+                    .to_string()
+                    .into(),
+            )
+        } else if key == "day" {
+            Some(
+                self.metadata
+                    .time_received()
+                    .format("%d")
+                    .to_string()
+                    .into(),
+            )
+        } else if key == "hour" {
+            Some(
+                self.metadata
+                    .time_received()
+                    .format("%H")
+                    .to_string()
+                    .into(),
+            )
         } else if key.starts_with("ip:") {
             // unwrap is safe because we just checked that the string contains the separator
             let (_, index_str) = key.split_once(':').unwrap();
             if let Ok(index) = u8::from_str(index_str) {
-                Some(Cow::from(self.split_ip(&self.metadata.addr().ip(), index)))
+                Some(self.anonymize(self.split_ip(&self.metadata.addr().ip(), index)))
             } else {
                 None
             }
@@ -275,15 +586,25 @@ impl leon::Values for PathValues {
 }
  
 
+// Suffix added to the path of gzip compressed files
+const GZIP_SUFFIX: &str = ".gz";
+
 pub struct OutputFiles {
     config: FilesConfiguration,
+    header: Option<Arc<Vec<u8>>>,
+    // Set for binary formats, whose events are not written one per line
+    binary_layout: Option<BinaryFileLayout>,
     tx: mpsc::Sender<WriteFilesMessage>,
+    log_path_anonymization: PathAnonymization,
+    // Compressed files written by this output, which are closed when it is
+    // dropped so that their gzip streams are complete
+    compressed_paths: Mutex<HashSet<PathBuf>>,
 }
 
 impl OutputFiles {
     pub fn new(config: &FilesConfiguration, context: &Option<OutputFilesContext>) -> Result<Self> {
-        let tx = if let Some(files_context) = context {
-            files_context.tx.clone()
+        let files_context = if let Some(files_context) = context {
+            files_context
         } else {
             bail!("Files output context has not been initialized")
         };
@@ -291,22 +612,65 @@ impl OutputFiles {
         debug!("Initialize Files driver with config {:?}", config);
         Ok(OutputFiles {
             config: config.clone(),
-            tx: tx.clone(),
+            header: None,
+            binary_layout: None,
+            tx: files_context.tx.clone(),
+            log_path_anonymization: files_context.log_path_anonymization,
+            compressed_paths: Mutex::new(HashSet::new()),
         })
     }
 
-    fn build_path(
+    /// Sets a line to write at the beginning of each new file
+    pub fn with_header(mut self, header: Option<String>) -> Self {
+        self.header = header.map(|header| Arc::new(format!("{}\n", header).into_bytes()));
+        self
+    }
+
+    /// Sets the layout of files for binary formats, which replaces the
+    /// header and the line feed written after each event
+    pub fn with_binary_layout(mut self, binary_layout: Option<BinaryFileLayout>) -> Self {
+        if let Some(layout) = &binary_layout {
+            self.header = Some(Arc::new((layout.header)()));
+        }
+        self.binary_layout = binary_layout;
+        self
+    }
+
+    fn render_path(
         &self,
-        metadata: &Arc<EventMetadata>
-    ) -> Result<PathBuf> {
-        let values = PathValues { metadata: metadata.clone() };
+        metadata: &Arc<EventMetadata>,
+        anonymization: PathAnonymization,
+    ) -> Result<String> {
+        let values = PathValues {
+            metadata: metadata.clone(),
+            anonymization,
+        };
         // It would be cool to parse the template only once
         // However, Template::parse takes a reference to a str and has the same
         // lifetime than the str. I don't know how to store that...
         let template = Template::parse(self.config.path())?;
-        let path = template.render(&values)?;
+        let mut path = template.render(&values)?;
+        if self.config.compression() == FilesCompression::Gzip {
+            path.push_str(GZIP_SUFFIX);
+        }
+        Ok(path)
+    }
+
+    fn build_path(
+        &self,
+        metadata: &Arc<EventMetadata>
+    ) -> Result<PathBuf> {
+        let path = self.render_path(metadata, PathAnonymization::Disabled)?;
         Ok(PathBuf::from_str(&path)?)
     }
+
+    /// Returns the version of `path` that can be written in logs
+    fn build_display_path(&self, metadata: &Arc<EventMetadata>, path: &Path) -> Result<String> {
+        match self.log_path_anonymization {
+            PathAnonymization::Disabled => Ok(path.display().to_string()),
+            anonymization => self.render_path(metadata, anonymization),
+        }
+    }
 }
 
 #[async_trait]
@@ -314,24 +678,39 @@ impl OutputDriver for OutputFiles {
     async fn write(
         &self,
         metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
     ) -> Result<()> {
         // Build path
         let path = self.build_path(&metadata)?;
+        let display_path = self.build_display_path(&metadata, &path)?;
 
-        debug!("Computed path is {}", path.display());
+        debug!("Computed path is {}", display_path);
 
-        // Build the "content" string to write
-        let mut content = String::new();
-        for event in events.iter() {
-            content.push_str(event);
-            content.push('\n');
+        if self.config.compression() != FilesCompression::None {
+            self.compressed_paths.lock().unwrap().insert(path.clone());
         }
 
+        // Build the "content" to write
+        let content = match &self.binary_layout {
+            Some(layout) => (layout.encode)(&events)?,
+            None => {
+                let mut content = Vec::new();
+                for event in events.iter() {
+                    content.extend_from_slice(event);
+                    content.push(b'\n');
+                }
+                content
+            }
+        };
+
         // Create a oneshot channel to retrieve the result of the operation
         let (tx, rx) = oneshot::channel();
         self.tx.send(WriteFilesMessage::Write(WriteMessage {
             path,
+            display_path,
+            header: self.header.clone(),
+            rotation: self.config.rotation().cloned(),
+            compression: self.config.compression(),
             content,
             resp: tx,
         }))?;
@@ -343,6 +722,25 @@ impl OutputDriver for OutputFiles {
     }
 }
 
+impl Drop for OutputFiles {
+    fn drop(&mut self) {
+        let paths: Vec<PathBuf> = match self.compressed_paths.get_mut() {
+            Ok(paths) => paths.drain().collect(),
+            Err(_) => return,
+        };
+        if paths.is_empty() {
+            return;
+        }
+        debug!("Closing {} compressed files", paths.len());
+        if let Err(e) = self.tx.send(WriteFilesMessage::Close(paths)) {
+            warn!(
+                "Failed to send Close message to Files handler thread: {}",
+                e
+            );
+        }
+    }
+}
+
 fn sanitize_name(name: &str) -> String {
     // We only allow strings containing at most 255 chars within [a-z][A-Z][0-9][.-_@]
     let mut new_str = String::with_capacity(min(name.len(), 255));
@@ -378,6 +776,7 @@ fn sanitize_name(name: &str) -> String {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Read;
     use std::net::{IpAddr, SocketAddr};
 
     use common::{settings, subscription::{SubscriptionData, SubscriptionUuid}};
@@ -432,7 +831,7 @@ mod tests {
         let event_metadata_with_node = create_event_metadata(ip, principal, node);
 
 
-        let context = Some(OutputFilesContext::new());
+        let context = Some(OutputFilesContext::new(PathAnonymization::Disabled));
 
         let output_file = OutputFiles::new(&config, &context)?;
 
@@ -529,6 +928,327 @@ mod tests {
             output_file.build_path(&event_metadata_without_node)?,
             PathBuf::from_str("/base/COMPUTER@REALM/messages")?
         );
+
+        let config = FilesConfiguration::new("/base/{tenant}/{principal}/messages".to_string());
+        let output_file = OutputFiles::new(&config, &context)?;
+        assert_eq!(
+            output_file.build_path(&event_metadata_without_node)?,
+            PathBuf::from_str("/base/{tenant}/COMPUTER@REALM/messages")?
+        );
+
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut subscription_data = SubscriptionData::new("Test", "");
+        subscription_data.set_tenant(Some("team-a".to_string()));
+        let subscription = Subscription::from_data(subscription_data, &mut output_context)?;
+        let event_metadata_with_tenant = Arc::new(EventMetadata::new(
+            &SocketAddr::new(ip, 8080),
+            "COMPUTER$@REALM",
+            None,
+            &subscription,
+            subscription.public_version_string(),
+            None,
+        ));
+        assert_eq!(
+            output_file.build_path(&event_metadata_with_tenant)?,
+            PathBuf::from_str("/base/team-a/COMPUTER@REALM/messages")?
+        );
+
+        let config = FilesConfiguration::new("/base/{collector_version}/messages".to_string());
+        let output_file = OutputFiles::new(&config, &context)?;
+        assert_eq!(
+            output_file.build_path(&event_metadata_without_node)?,
+            PathBuf::from(format!("/base/{}/messages", env!("CARGO_PKG_VERSION")))
+        );
+
+        // Time variables are derived from the reception time of events, in UTC
+        let mut event_metadata = create_event_metadata("127.0.0.1".parse()?, principal, None)
+            .as_ref()
+            .clone();
+        event_metadata.set_time_received("2024-03-07T05:12:44Z".parse::<DateTime<Utc>>()?);
+        let event_metadata = Arc::new(event_metadata);
+        let config =
+            FilesConfiguration::new("/data/{year}/{month}/{day}/{hour}/{ip}/events".to_string());
+        let output_file = OutputFiles::new(&config, &context)?;
+        assert_eq!(
+            output_file.build_path(&event_metadata)?,
+            PathBuf::from_str("/data/2024/03/07/05/127.0.0.1/events")?
+        );
+
+        // The .gz suffix is added to compressed files
+        let event_metadata = create_event_metadata("127.0.0.1".parse()?, principal, None);
+        let mut config = FilesConfiguration::new("/base/{ip}/messages".to_string());
+        config.set_compression(FilesCompression::Gzip);
+        let output_file = OutputFiles::new(&config, &context)?;
+        assert_eq!(
+            output_file.build_path(&event_metadata)?,
+            PathBuf::from_str("/base/127.0.0.1/messages.gz")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_written_once_per_file() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        let path = dir.join("events.csv");
+        let mut file_handles = HashMap::new();
+
+        let message = |content: &str| {
+            let (tx, _rx) = oneshot::channel();
+            WriteMessage {
+                path: path.clone(),
+                display_path: path.display().to_string(),
+                header: Some(Arc::new(b"a,b\n".to_vec())),
+                rotation: None,
+                compression: FilesCompression::None,
+                content: content.as_bytes().to_vec(),
+                resp: tx,
+            }
+        };
+
+        handle_message(&mut file_handles, &message("1,2\n"))?;
+        handle_message(&mut file_handles, &message("3,4\n"))?;
+        // File descriptors are closed but the file already has content
+        file_handles.clear();
+        handle_message(&mut file_handles, &message("5,6\n"))?;
+
+        assert_eq!(std::fs::read_to_string(&path)?, "a,b\n1,2\n3,4\n5,6\n");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_must_rotate() -> Result<()> {
+        let created = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let after = |secs| created + Duration::from_secs(secs);
+
+        let by_size = FilesRotation::new(Some(1000), None)?;
+        assert!(!must_rotate(&by_size, 500, created, after(1_000_000), 500));
+        assert!(must_rotate(&by_size, 500, created, after(1), 501));
+        assert!(must_rotate(&by_size, 1000, created, after(1), 1));
+        // Content larger than the limit is written to an empty file
+        assert!(!must_rotate(&by_size, 0, created, after(1), 5000));
+
+        let by_age = FilesRotation::new(None, Some(3600))?;
+        assert!(!must_rotate(&by_age, 5000, created, after(3599), 5000));
+        assert!(must_rotate(&by_age, 1, created, after(3600), 1));
+        // Idle files are not rotated until something is written to them
+        assert!(!must_rotate(&by_age, 0, created, after(100_000), 1));
+        // The clock went backwards
+        assert!(!must_rotate(
+            &by_age,
+            1,
+            created,
+            created - Duration::from_secs(10),
+            1
+        ));
+
+        let both = FilesRotation::new(Some(1000), Some(3600))?;
+        assert!(!must_rotate(&both, 10, created, after(10), 10));
+        assert!(must_rotate(&both, 999, created, after(10), 10));
+        assert!(must_rotate(&both, 10, created, after(7200), 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotated_path() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        assert_eq!(
+            rotated_path(
+                Path::new("/base/127.0.0.1/messages"),
+                time,
+                FilesCompression::None
+            ),
+            PathBuf::from("/base/127.0.0.1/messages.20231114T221320.123456Z")
+        );
+        assert_eq!(
+            rotated_path_display("/base/a1b2c3d4e5f6/messages", time, FilesCompression::None),
+            "/base/a1b2c3d4e5f6/messages.20231114T221320.123456Z"
+        );
+        // The timestamp is inserted before the .gz suffix
+        assert_eq!(
+            rotated_path(
+                Path::new("/base/127.0.0.1/messages.gz"),
+                time,
+                FilesCompression::Gzip
+            ),
+            PathBuf::from("/base/127.0.0.1/messages.20231114T221320.123456Z.gz")
+        );
+    }
+
+    #[test]
+    fn test_rotation() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        let mut file_handles = HashMap::new();
+
+        let message = |name: &str, content: &str| {
+            let path = dir.join(name);
+            let (tx, _rx) = oneshot::channel();
+            WriteMessage {
+                path: path.clone(),
+                display_path: path.display().to_string(),
+                header: Some(Arc::new(b"a,b\n".to_vec())),
+                rotation: Some(FilesRotation::new(Some(12), None).unwrap()),
+                compression: FilesCompression::None,
+                content: content.as_bytes().to_vec(),
+                resp: tx,
+            }
+        };
+
+        // Each rendered path is rotated independently
+        handle_message(&mut file_handles, &message("first", "1,2\n"))?;
+        handle_message(&mut file_handles, &message("second", "1,2\n"))?;
+        handle_message(&mut file_handles, &message("first", "3,4\n"))?;
+        handle_message(&mut file_handles, &message("first", "5,6\n"))?;
+
+        let mut rotated: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        rotated.sort();
+        assert_eq!(rotated.len(), 3);
+        assert_eq!(rotated[0], dir.join("first"));
+        assert!(rotated[1]
+            .to_string_lossy()
+            .starts_with(&format!("{}.", dir.join("first").display())));
+        assert_eq!(rotated[2], dir.join("second"));
+
+        // No bytes are lost and the header is written in the new file
+        assert_eq!(std::fs::read_to_string(&rotated[1])?, "a,b\n1,2\n3,4\n");
+        assert_eq!(std::fs::read_to_string(dir.join("first"))?, "a,b\n5,6\n");
+        assert_eq!(std::fs::read_to_string(dir.join("second"))?, "a,b\n1,2\n");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_gzip_compression() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        let mut file_handles = HashMap::new();
+
+        let message = |name: &str, content: &str, rotation: Option<FilesRotation>| {
+            let path = dir.join(name);
+            let (tx, _rx) = oneshot::channel();
+            WriteMessage {
+                path: path.clone(),
+                display_path: path.display().to_string(),
+                header: Some(Arc::new(b"a,b\n".to_vec())),
+                rotation,
+                compression: FilesCompression::Gzip,
+                content: content.as_bytes().to_vec(),
+                resp: tx,
+            }
+        };
+        let decompress = |path: &Path| -> Result<String> {
+            let mut content = String::new();
+            flate2::read::MultiGzDecoder::new(File::open(path)?).read_to_string(&mut content)?;
+            Ok(content)
+        };
+
+        handle_message(&mut file_handles, &message("events.gz", "1,2\n", None))?;
+        // Written content is readable before the file is closed, even if the
+        // gzip stream is not terminated yet
+        let mut partial = Vec::new();
+        let res = flate2::read::MultiGzDecoder::new(File::open(dir.join("events.gz"))?)
+            .read_to_end(&mut partial);
+        assert!(res.is_err());
+        assert_eq!(partial, b"a,b\n1,2\n");
+
+        handle_message(&mut file_handles, &message("events.gz", "3,4\n", None))?;
+        // Reopening the file appends a new gzip member
+        close_all(&mut file_handles);
+        handle_message(&mut file_handles, &message("events.gz", "5,6\n", None))?;
+        close(&mut file_handles, &[dir.join("events.gz")]);
+        assert!(file_handles.is_empty());
+        assert_eq!(decompress(&dir.join("events.gz"))?, "a,b\n1,2\n3,4\n5,6\n");
+
+        // The size of compressed files is their size on disk
+        let rotation = || Some(FilesRotation::new(Some(100), None).unwrap());
+        handle_message(
+            &mut file_handles,
+            &message("rotated.gz", "1,2\n", rotation()),
+        )?;
+        let size = std::fs::metadata(dir.join("rotated.gz"))?.len();
+        assert!(size < 100);
+        let content = "x".repeat(101 - size as usize);
+        handle_message(
+            &mut file_handles,
+            &message("rotated.gz", &content, rotation()),
+        )?;
+        close_all(&mut file_handles);
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        paths.sort();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], dir.join("events.gz"));
+        // rotated.<timestamp>.gz sorts before rotated.gz
+        assert!(paths[1]
+            .to_string_lossy()
+            .starts_with(&format!("{}.2", dir.join("rotated").display())));
+        assert!(paths[1].to_string_lossy().ends_with(".gz"));
+        assert_eq!(paths[2], dir.join("rotated.gz"));
+        assert_eq!(decompress(&paths[1])?, "a,b\n1,2\n");
+        assert_eq!(
+            decompress(&dir.join("rotated.gz"))?,
+            format!("a,b\n{}", content)
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_log_path_anonymization() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        let config = FilesConfiguration::new(format!(
+            "{}/{{ip}}/{{principal}}/messages",
+            dir.display()
+        ));
+        let metadata =
+            create_event_metadata("192.168.58.100".parse()?, "WIN10$@WINDOMAIN.LOCAL", None);
+        let real_path = dir.join("192.168.58.100/WIN10@WINDOMAIN.LOCAL/messages");
+
+        let context = Some(OutputFilesContext::new(PathAnonymization::Hash));
+        let output_file = OutputFiles::new(&config, &context)?;
+        let path = output_file.build_path(&metadata)?;
+        assert_eq!(path, real_path);
+
+        // The logged path does not contain the principal nor the IP address
+        let display_path = output_file.build_display_path(&metadata, &path)?;
+        assert!(!display_path.contains("192.168"));
+        assert!(!display_path.contains("WIN10"));
+        let components: Vec<&str> = display_path
+            .strip_prefix(&format!("{}/", dir.display()))
+            .unwrap()
+            .split('/')
+            .collect();
+        assert_eq!(components.len(), 3);
+        for hash in &components[..2] {
+            assert_eq!(hash.len(), 12);
+            assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+        assert_eq!(components[2], "messages");
+        // Hashes are stable, so that logs of a client can still be correlated
+        assert_eq!(display_path, output_file.build_display_path(&metadata, &path)?);
+
+        let context = Some(OutputFilesContext::new(PathAnonymization::Truncate));
+        let truncated = OutputFiles::new(&config, &context)?;
+        assert_eq!(
+            truncated.build_display_path(&metadata, &path)?,
+            format!("{}/192*/WIN*/messages", dir.display())
+        );
+
+        // Events are written to the full path
+        output_file
+            .write(
+                metadata.clone(),
+                Arc::new(vec![Arc::new(b"event".to_vec())]),
+            )
+            .await?;
+        assert_eq!(std::fs::read_to_string(&real_path)?, "event\n");
+
+        std::fs::remove_dir_all(&dir)?;
         Ok(())
     }
 }
@@ -0,0 +1,633 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use common::subscription::GrpcConfiguration;
+use log::{debug, info, warn};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tonic::{
+    metadata::{AsciiMetadataKey, AsciiMetadataValue, MetadataMap},
+    transport::{Certificate, Channel, ClientTlsConfig, Endpoint},
+    Request, Status, Streaming,
+};
+
+use crate::{drivers::tcp::reconnect_delay, event::EventMetadata, output::OutputDriver};
+
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("openwec.output.v1");
+}
+
+use proto::{event_ingest_client::EventIngestClient, Event, EventBatch, IngestAck};
+
+const RECONNECT_INITIAL_MS: u64 = 500;
+const RECONNECT_MAX_MS: u64 = 30_000;
+const OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+// Time given to the service to acknowledge the remaining batches when the
+// output is removed
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct WriteGrpcMessage {
+    batch: EventBatch,
+    resp: oneshot::Sender<Result<()>>,
+}
+
+fn send_response(sender: oneshot::Sender<Result<()>>, msg: Result<()>) {
+    if let Err(e) = sender.send(msg) {
+        warn!(
+            "Failed to send gRPC write result because the receiver dropped. Result was: {:?}",
+            e
+        );
+    }
+}
+
+fn metadata_map(config: &GrpcConfiguration) -> Result<MetadataMap> {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in config.metadata() {
+        metadata.insert(
+            AsciiMetadataKey::from_bytes(key.as_bytes())
+                .with_context(|| format!("Invalid gRPC metadata key {:?}", key))?,
+            AsciiMetadataValue::try_from(value.as_str())
+                .with_context(|| format!("Invalid value of gRPC metadata {:?}", key))?,
+        );
+    }
+    Ok(metadata)
+}
+
+/// Bidirectional streaming call in which batches are sent and acknowledged
+struct Call {
+    sender: mpsc::UnboundedSender<EventBatch>,
+    acks: Streaming<IngestAck>,
+    // Writes whose batch has been sent but not acknowledged yet
+    pending: HashMap<u64, oneshot::Sender<Result<()>>>,
+}
+
+/// Connection handled by the task of a Grpc output
+struct Connection {
+    config: GrpcConfiguration,
+    metadata: MetadataMap,
+    client: Option<EventIngestClient<Channel>>,
+    call: Option<Call>,
+    // Identifier of the next batch
+    next_id: u64,
+    // Number of consecutive connection or stream failures
+    failures: u32,
+    // No connection is attempted before this instant
+    next_attempt: Option<Instant>,
+}
+
+impl Connection {
+    fn new(config: GrpcConfiguration, metadata: MetadataMap) -> Self {
+        Connection {
+            config,
+            metadata,
+            client: None,
+            call: None,
+            next_id: 0,
+            failures: 0,
+            next_attempt: None,
+        }
+    }
+
+    async fn connect(&self) -> Result<EventIngestClient<Channel>> {
+        let mut endpoint = Endpoint::from_shared(self.config.endpoint().to_owned())
+            .with_context(|| format!("Invalid gRPC endpoint {}", self.config.endpoint()))?;
+        if self.config.tls_enabled() {
+            let mut tls_config = ClientTlsConfig::new();
+            for certificate_authority_file in self.config.tls_certificate_authorities() {
+                let pem = std::fs::read(certificate_authority_file).with_context(|| {
+                    format!(
+                        "Failed to read certificate authority {}",
+                        certificate_authority_file
+                    )
+                })?;
+                tls_config = tls_config.ca_certificate(Certificate::from_pem(pem));
+            }
+            endpoint = endpoint.tls_config(tls_config)?;
+        }
+        Ok(EventIngestClient::new(endpoint.connect().await?))
+    }
+
+    /// Ends the current call. The writes whose batch has not been
+    /// acknowledged fail.
+    fn end_call(&mut self, reason: &str) {
+        if let Some(call) = self.call.take() {
+            for (_, resp) in call.pending {
+                send_response(
+                    resp,
+                    Err(anyhow!(
+                        "Batch has not been acknowledged by {}: {}",
+                        self.config.endpoint(),
+                        reason
+                    )),
+                );
+            }
+        }
+    }
+
+    /// Closes the connection and delays the next connection attempt
+    fn fail(&mut self, reason: &str) -> Duration {
+        self.end_call(reason);
+        self.client = None;
+        self.failures = self.failures.saturating_add(1);
+        let delay = reconnect_delay(RECONNECT_INITIAL_MS, RECONNECT_MAX_MS, self.failures);
+        self.next_attempt = Some(Instant::now() + delay);
+        delay
+    }
+
+    /// Whether the connection is down and must not be established again yet
+    fn waiting(&self) -> bool {
+        self.client.is_none()
+            && self
+                .next_attempt
+                .is_some_and(|next_attempt| Instant::now() < next_attempt)
+    }
+
+    /// Returns the current call, connecting and opening a new call if
+    /// required
+    async fn call(&mut self) -> Result<&mut Call> {
+        if self.client.is_none() {
+            match self.connect().await {
+                Ok(client) => self.client = Some(client),
+                Err(e) => {
+                    let reason = format!("{:#}", e);
+                    let delay = self.fail(&reason);
+                    warn!(
+                        "Failed to connect to {}: {}. Next attempt in {}ms",
+                        self.config.endpoint(),
+                        reason,
+                        delay.as_millis()
+                    );
+                    bail!(
+                        "Failed to connect to {}: {}",
+                        self.config.endpoint(),
+                        reason
+                    );
+                }
+            }
+        }
+        if self.call.is_none() {
+            let mut client = self
+                .client
+                .clone()
+                .ok_or_else(|| anyhow!("gRPC client of {} is unset!", self.config.endpoint()))?;
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let mut request = Request::new(UnboundedReceiverStream::new(receiver));
+            *request.metadata_mut() = self.metadata.clone();
+            let reason = match tokio::time::timeout(OPEN_TIMEOUT, client.ingest(request)).await {
+                Ok(Ok(response)) => {
+                    debug!("Opened gRPC stream to {}", self.config.endpoint());
+                    self.call = Some(Call {
+                        sender,
+                        acks: response.into_inner(),
+                        pending: HashMap::new(),
+                    });
+                    None
+                }
+                Ok(Err(status)) => Some(status.to_string()),
+                Err(_) => Some("timed out".to_string()),
+            };
+            if let Some(reason) = reason {
+                let delay = self.fail(&reason);
+                warn!(
+                    "Failed to open gRPC stream to {}: {}. Next attempt in {}ms",
+                    self.config.endpoint(),
+                    reason,
+                    delay.as_millis()
+                );
+                bail!(
+                    "Failed to open gRPC stream to {}: {}",
+                    self.config.endpoint(),
+                    reason
+                );
+            }
+        }
+        self.call
+            .as_mut()
+            .ok_or_else(|| anyhow!("gRPC stream to {} is unset!", self.config.endpoint()))
+    }
+
+    /// Sends the batch of a write, which is answered once the service has
+    /// acknowledged the batch
+    async fn send(&mut self, message: WriteGrpcMessage) {
+        let WriteGrpcMessage { mut batch, resp } = message;
+        if self.waiting() {
+            send_response(
+                resp,
+                Err(anyhow!(
+                    "Waiting before reconnecting to {}",
+                    self.config.endpoint()
+                )),
+            );
+            return;
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let call = match self.call().await {
+            Ok(call) => call,
+            Err(e) => {
+                send_response(resp, Err(e));
+                return;
+            }
+        };
+        batch.id = id;
+        if call.sender.send(batch).is_err() {
+            // The call is over: its end is handled with the acknowledgements
+            send_response(
+                resp,
+                Err(anyhow!(
+                    "gRPC stream to {} has been closed",
+                    self.config.endpoint()
+                )),
+            );
+            return;
+        }
+        call.pending.insert(id, resp);
+    }
+
+    /// Waits for the next acknowledgement of the current call
+    async fn next_ack(&mut self) -> Result<Option<IngestAck>, Status> {
+        match self.call.as_mut() {
+            Some(call) => call.acks.message().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Answers the write whose batch has been acknowledged, or handles the
+    /// end of the current call
+    fn acknowledge(&mut self, ack: Result<Option<IngestAck>, Status>) {
+        match ack {
+            Ok(Some(ack)) => {
+                let Some(resp) = self
+                    .call
+                    .as_mut()
+                    .and_then(|call| call.pending.remove(&ack.id))
+                else {
+                    warn!(
+                        "Received an acknowledgement of unknown batch {} from {}",
+                        ack.id,
+                        self.config.endpoint()
+                    );
+                    return;
+                };
+                if self.failures > 0 {
+                    info!(
+                        "gRPC connection to {} has been restored",
+                        self.config.endpoint()
+                    );
+                    self.failures = 0;
+                    self.next_attempt = None;
+                }
+                send_response(resp, Ok(()));
+            }
+            Ok(None) => {
+                // The next batch is sent in a new stream
+                debug!(
+                    "gRPC stream to {} has been ended by the service",
+                    self.config.endpoint()
+                );
+                self.end_call("the stream has been ended by the service");
+            }
+            Err(status) => {
+                let delay = self.fail(&status.to_string());
+                warn!(
+                    "gRPC stream to {} failed: {}. Next attempt in {}ms",
+                    self.config.endpoint(),
+                    status,
+                    delay.as_millis()
+                );
+            }
+        }
+    }
+
+    async fn close(mut self) {
+        let acknowledged = tokio::time::timeout(CLOSE_TIMEOUT, async {
+            while self
+                .call
+                .as_ref()
+                .is_some_and(|call| !call.pending.is_empty())
+            {
+                let ack = self.next_ack().await;
+                self.acknowledge(ack);
+            }
+        })
+        .await;
+        if acknowledged.is_err() {
+            warn!(
+                "Closing gRPC stream to {} before all batches have been acknowledged",
+                self.config.endpoint()
+            );
+        }
+        self.end_call("the output has been removed");
+        info!("Exiting gRPC output task ({})", self.config.endpoint());
+    }
+}
+
+async fn run(
+    mut connection: Connection,
+    mut task_rx: mpsc::Receiver<WriteGrpcMessage>,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            Some(message) = task_rx.recv() => connection.send(message).await,
+            ack = connection.next_ack() => connection.acknowledge(ack),
+            _ = cancellation_token.cancelled() => {
+                // The output has been removed (or disabled): messages which
+                // are already queued are sent before closing the stream
+                task_rx.close();
+                while let Some(message) = task_rx.recv().await {
+                    connection.send(message).await;
+                }
+                break;
+            }
+        };
+    }
+    connection.close().await;
+}
+
+pub struct OutputGrpc {
+    task_tx: mpsc::Sender<WriteGrpcMessage>,
+    task_ct: CancellationToken,
+}
+
+impl OutputGrpc {
+    pub fn new(config: &GrpcConfiguration) -> Result<Self> {
+        // Metadata is not logged because it may contain credentials
+        debug!("Initialize gRPC output with endpoint {}", config.endpoint());
+        let connection = Connection::new(config.clone(), metadata_map(config)?);
+
+        // Create a communication channel with the task responsible for the stream
+        let (task_tx, task_rx) = mpsc::channel(32);
+
+        // Use a CancellationToken to tell the task to end itself
+        let task_ct = CancellationToken::new();
+        let cloned_task_ct = task_ct.clone();
+
+        // Launch the task responsible for handling the gRPC stream
+        tokio::spawn(async move { run(connection, task_rx, cloned_task_ct).await });
+
+        Ok(OutputGrpc { task_tx, task_ct })
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputGrpc {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let batch = EventBatch {
+            // Set when the batch is sent
+            id: 0,
+            subscription: metadata.subscription_name().to_owned(),
+            ip: metadata.addr().ip().to_string(),
+            principal: metadata.principal().to_owned(),
+            events: events
+                .iter()
+                .map(|event| Event {
+                    payload: event.to_vec(),
+                })
+                .collect(),
+        };
+
+        // Create a oneshot channel to retrieve the result of the operation
+        let (tx, rx) = oneshot::channel();
+        self.task_tx
+            .send(WriteGrpcMessage { batch, resp: tx })
+            .await?;
+
+        // Wait for the batch to be acknowledged by the service
+        rx.await??;
+
+        Ok(())
+    }
+}
+
+impl Drop for OutputGrpc {
+    fn drop(&mut self) {
+        self.task_ct.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    };
+    use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+    use tonic::{transport::Server, Response};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+    use proto::event_ingest_server::{EventIngest, EventIngestServer};
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    fn events(events: &[&str]) -> Arc<Vec<Arc<Vec<u8>>>> {
+        Arc::new(
+            events
+                .iter()
+                .map(|event| Arc::new(event.as_bytes().to_vec()))
+                .collect(),
+        )
+    }
+
+    /// Forwards the batches received, along with the metadata of their
+    /// stream, and acknowledges them unless they contain an "unacked" event
+    struct Ingest {
+        received: mpsc::UnboundedSender<(Option<String>, EventBatch)>,
+    }
+
+    #[tonic::async_trait]
+    impl EventIngest for Ingest {
+        type IngestStream = ReceiverStream<Result<IngestAck, Status>>;
+
+        async fn ingest(
+            &self,
+            request: Request<Streaming<EventBatch>>,
+        ) -> Result<Response<Self::IngestStream>, Status> {
+            let source = request
+                .metadata()
+                .get("x-source")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned);
+            let mut stream = request.into_inner();
+            let received = self.received.clone();
+            let (acks, acks_rx) = mpsc::channel(32);
+            tokio::spawn(async move {
+                while let Ok(Some(batch)) = stream.message().await {
+                    let id = batch.id;
+                    let acknowledge = !payloads(&batch).iter().any(|event| event == "unacked");
+                    let _ = received.send((source.clone(), batch));
+                    if acknowledge && acks.send(Ok(IngestAck { id })).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(Response::new(ReceiverStream::new(acks_rx)))
+        }
+    }
+
+    /// Serves the ingest service on `listener` and returns the batches it
+    /// receives
+    fn serve(listener: TcpListener) -> mpsc::UnboundedReceiver<(Option<String>, EventBatch)> {
+        let (received, received_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(EventIngestServer::new(Ingest { received }))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+        });
+        received_rx
+    }
+
+    fn payloads(batch: &EventBatch) -> Vec<String> {
+        batch
+            .events
+            .iter()
+            .map(|event| String::from_utf8_lossy(&event.payload).into_owned())
+            .collect()
+    }
+
+    /// Forwards the first connection accepted by `listener` to `port`.
+    /// Aborting the returned task kills the connection.
+    fn proxy(listener: TcpListener, port: u16) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let (mut client, _) = listener.accept().await.unwrap();
+            let mut server = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+        })
+    }
+
+    #[tokio::test]
+    async fn test_grpc_stream() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let mut received = serve(listener);
+
+        let config = GrpcConfiguration::new(
+            format!("http://127.0.0.1:{}", port),
+            BTreeMap::from([("x-source".to_string(), "openwec".to_string())]),
+            Vec::new(),
+        )?;
+        let output = OutputGrpc::new(&config)?;
+        output
+            .write(metadata(), events(&["first", "second"]))
+            .await?;
+        output.write(metadata(), events(&["third"])).await?;
+
+        let mut batches = Vec::new();
+        for _ in 0..2 {
+            batches.push(
+                tokio::time::timeout(Duration::from_secs(10), received.recv())
+                    .await?
+                    .ok_or_else(|| anyhow!("Ingest service stopped"))?,
+            );
+        }
+        // Configured metadata is sent when the stream is opened
+        assert!(batches
+            .iter()
+            .all(|(source, _)| source.as_deref() == Some("openwec")));
+        let (_, first) = &batches[0];
+        assert_eq!(first.subscription, "Test");
+        assert_eq!(first.ip, "192.168.58.100");
+        assert_eq!(first.principal, "WIN10$@WINDOMAIN.LOCAL");
+        assert_eq!(payloads(first), vec!["first", "second"]);
+        assert_eq!(payloads(&batches[1].1), vec!["third"]);
+        assert_ne!(first.id, batches[1].1.id);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_server_killed() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let server_port = listener.local_addr()?.port();
+        let mut received = serve(listener);
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let connection = proxy(listener, server_port);
+
+        let config = GrpcConfiguration::new(
+            format!("http://127.0.0.1:{}", port),
+            BTreeMap::new(),
+            Vec::new(),
+        )?;
+        let output = OutputGrpc::new(&config)?;
+        output.write(metadata(), events(&["first"])).await?;
+        let (_, batch) = tokio::time::timeout(Duration::from_secs(10), received.recv())
+            .await?
+            .ok_or_else(|| anyhow!("Ingest service stopped"))?;
+        assert_eq!(payloads(&batch), vec!["first"]);
+
+        // The write is not over while its batch has not been acknowledged
+        let write = output.write(metadata(), events(&["unacked"]));
+        tokio::pin!(write);
+        tokio::select! {
+            result = &mut write => bail!("Write ended without acknowledgement: {:?}", result),
+            batch = tokio::time::timeout(Duration::from_secs(10), received.recv()) => {
+                let (_, batch) = batch?.ok_or_else(|| anyhow!("Ingest service stopped"))?;
+                assert_eq!(payloads(&batch), vec!["unacked"]);
+            }
+        }
+
+        // The server is killed mid-stream: the write fails so that the batch
+        // is sent again
+        connection.abort();
+        assert!(tokio::time::timeout(Duration::from_secs(10), write)
+            .await?
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_reconnect() -> Result<()> {
+        // Nothing listens to this port yet
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        drop(listener);
+
+        let config = GrpcConfiguration::new(
+            format!("http://127.0.0.1:{}", port),
+            BTreeMap::new(),
+            Vec::new(),
+        )?;
+        let output = OutputGrpc::new(&config)?;
+        assert!(output.write(metadata(), events(&["lost"])).await.is_err());
+        // No connection is attempted before the backoff delay
+        let mut received = serve(TcpListener::bind(("127.0.0.1", port)).await?);
+        assert!(output.write(metadata(), events(&["early"])).await.is_err());
+
+        tokio::time::sleep(Duration::from_millis(RECONNECT_INITIAL_MS + 100)).await;
+        output.write(metadata(), events(&["resumed"])).await?;
+        let (_, batch) = tokio::time::timeout(Duration::from_secs(10), received.recv())
+            .await?
+            .ok_or_else(|| anyhow!("Ingest service stopped"))?;
+        assert_eq!(payloads(&batch), vec!["resumed"]);
+        Ok(())
+    }
+}
@@ -1,17 +1,23 @@
 use anyhow::{bail, Result};
 use async_trait::async_trait;
-use common::{settings, subscription::KafkaConfiguration};
+use common::{
+    settings,
+    subscription::{KafkaConfiguration, PayloadCompression, SubscriptionOutputFormat},
+};
 use futures::future::join_all;
+use leon::Template;
 use log::debug;
 use rdkafka::{
+    message::{Header, OwnedHeaders},
     producer::{FutureProducer, FutureRecord},
     util::Timeout,
     ClientConfig,
 };
-use std::{sync::Arc, time::Duration};
+use std::{borrow::Cow, sync::Arc, time::Duration};
 
 use crate::{
-    event::EventMetadata,
+    compression::compress,
+    event::{EventFields, EventMetadata},
     output::OutputDriver,
 };
 
@@ -40,13 +46,23 @@ impl OutputKafkaContext {
     }
 }
 
+/// Header telling consumers how the payload is compressed
+pub const PAYLOAD_COMPRESSION_HEADER: &str = "openwec-payload-compression";
+
 pub struct OutputKafka {
     config: KafkaConfiguration,
+    format: &'static str,
+    compression: PayloadCompression,
     producer: FutureProducer,
 }
 
 impl OutputKafka {
-    pub fn new(config: &KafkaConfiguration, context: &Option<OutputKafkaContext>) -> Result<Self> {
+    pub fn new(
+        config: &KafkaConfiguration,
+        format: &SubscriptionOutputFormat,
+        compression: PayloadCompression,
+        context: &Option<OutputKafkaContext>,
+    ) -> Result<Self> {
         let producer = if config.options().is_empty() {
             if let Some(kafka_context) = context {
                 debug!("Initialize kafka output with config {:?}", config);
@@ -71,25 +87,69 @@ impl OutputKafka {
         };
         Ok(OutputKafka {
             config: config.clone(),
+            format: format.into(),
+            compression,
             producer,
         })
     }
-}
 
-#[async_trait]
-impl OutputDriver for OutputKafka {
-    async fn write(
+    /// Parses the templates of the configured headers
+    fn header_templates(&self) -> Result<Vec<(&str, Template<'_>)>> {
+        let mut templates = Vec::with_capacity(self.config.headers().len());
+        for (name, value) in self.config.headers() {
+            templates.push((name.as_str(), Template::parse(value)?));
+        }
+        Ok(templates)
+    }
+
+    async fn send(
         &self,
-        _metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        metadata: &EventMetadata,
+        events: &[Arc<Vec<u8>>],
+        fields: Option<&[Arc<EventFields>]>,
     ) -> Result<()> {
-        let mut futures = Vec::new();
+        let templates = self.header_templates()?;
+        // Payloads must outlive the futures sending them
+        let mut payloads = Vec::with_capacity(events.len());
         for event in events.iter() {
+            payloads.push(if self.compression == PayloadCompression::None {
+                Cow::Borrowed(event.as_slice())
+            } else {
+                Cow::Owned(compress(self.compression, event)?)
+            });
+        }
+        let mut futures = Vec::new();
+        for (index, payload) in payloads.iter().enumerate() {
             // We need to explicitly assign the Key type as ()
-            futures.push(self.producer.send::<(), _, _>(
-                FutureRecord::to(self.config.topic()).payload(event.as_ref()),
-                Timeout::After(Duration::from_secs(30)),
-            ));
+            let mut record: FutureRecord<(), [u8]> =
+                FutureRecord::to(self.config.topic()).payload(payload.as_ref());
+            let mut headers = None;
+            if !templates.is_empty() {
+                let values = HeaderValues {
+                    metadata,
+                    fields: fields.and_then(|fields| fields.get(index)).map(Arc::as_ref),
+                    format: self.format,
+                };
+                headers = Some(render_headers(&templates, &values)?);
+            }
+            if self.compression != PayloadCompression::None {
+                let compression: &'static str = self.compression.into();
+                headers = Some(
+                    headers
+                        .unwrap_or_else(|| OwnedHeaders::new_with_capacity(1))
+                        .insert(Header {
+                            key: PAYLOAD_COMPRESSION_HEADER,
+                            value: Some(compression),
+                        }),
+                );
+            }
+            if let Some(headers) = headers {
+                record = record.headers(headers);
+            }
+            futures.push(
+                self.producer
+                    .send(record, Timeout::After(Duration::from_secs(30))),
+            );
         }
 
         // Wait for all events to be sent and ack
@@ -105,3 +165,243 @@ impl OutputDriver for OutputKafka {
         Ok(())
     }
 }
+
+/// Values of the tokens that can be used in headers
+struct HeaderValues<'a> {
+    metadata: &'a EventMetadata,
+    // Only set if a token needs them
+    fields: Option<&'a EventFields>,
+    format: &'static str,
+}
+
+impl leon::Values for HeaderValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "subscription" => Some(self.metadata.subscription_name().into()),
+            "ip" => Some(self.metadata.addr().ip().to_string().into()),
+            "format" => Some(self.format.into()),
+            // Fields missing from the event are rendered as empty strings
+            "event_id" => Some(
+                self.fields
+                    .and_then(|fields| fields.event_id())
+                    .map(|event_id| event_id.to_string())
+                    .unwrap_or_default()
+                    .into(),
+            ),
+            "channel" => Some(
+                self.fields
+                    .and_then(|fields| fields.channel())
+                    .unwrap_or_default()
+                    .into(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+fn render_headers(
+    templates: &[(&str, Template<'_>)],
+    values: &HeaderValues,
+) -> Result<OwnedHeaders> {
+    let mut headers = OwnedHeaders::new_with_capacity(templates.len());
+    for (name, template) in templates {
+        let value = template.render(values)?;
+        headers = headers.insert(Header {
+            key: name,
+            value: Some(value.as_str()),
+        });
+    }
+    Ok(headers)
+}
+
+#[async_trait]
+impl OutputDriver for OutputKafka {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        self.send(&metadata, &events, None).await
+    }
+
+    async fn write_with_fields(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+        fields: Arc<Vec<Arc<EventFields>>>,
+    ) -> Result<()> {
+        self.send(&metadata, &events, Some(fields.as_slice())).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        net::SocketAddr,
+        str::FromStr,
+    };
+
+    use common::subscription::SubscriptionData;
+    use rdkafka::{
+        consumer::{Consumer, StreamConsumer},
+        message::Headers,
+        mocking::MockCluster,
+        Message, Offset, TopicPartitionList,
+    };
+
+    use super::*;
+    use crate::{
+        compression::decompress, output::OutputDriversContext, subscription::Subscription,
+    };
+
+    const TOPIC: &str = "events";
+
+    fn metadata() -> Result<Arc<EventMetadata>> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)?;
+        Ok(Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        )))
+    }
+
+    fn headers() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("subscription".to_string(), "{subscription}".to_string()),
+            ("source".to_string(), "{ip}".to_string()),
+            ("route".to_string(), "{channel}/{event_id}".to_string()),
+            ("format".to_string(), "{format}".to_string()),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_headers() -> Result<()> {
+        let mock_cluster = MockCluster::new(1)?;
+        mock_cluster.create_topic(TOPIC, 1, 1)?;
+
+        let mut config = KafkaConfiguration::new(
+            TOPIC.to_string(),
+            HashMap::from([(
+                "bootstrap.servers".to_string(),
+                mock_cluster.bootstrap_servers(),
+            )]),
+        );
+        config.set_headers(headers())?;
+        assert!(config.headers_need_event_fields());
+        let output = OutputKafka::new(
+            &config,
+            &SubscriptionOutputFormat::Json,
+            PayloadCompression::None,
+            &None,
+        )?;
+
+        let events = Arc::new(vec![
+            Arc::new(b"first".to_vec()),
+            Arc::new(b"second".to_vec()),
+        ]);
+        let fields = Arc::new(vec![
+            Arc::new(EventFields::new(Some(4688), Some("Security".to_string()))),
+            // An event whose fields could not be retrieved
+            Arc::new(EventFields::default()),
+        ]);
+        output
+            .write_with_fields(metadata()?, events, fields)
+            .await?;
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", mock_cluster.bootstrap_servers())
+            .set("group.id", "openwec-test")
+            .create()?;
+        let mut partitions = TopicPartitionList::new();
+        partitions.add_partition_offset(TOPIC, 0, Offset::Beginning)?;
+        consumer.assign(&partitions)?;
+
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let message = tokio::time::timeout(Duration::from_secs(10), consumer.recv()).await??;
+            let payload = String::from_utf8(message.payload().unwrap_or_default().to_vec())?;
+            let mut headers = BTreeMap::new();
+            if let Some(message_headers) = message.headers() {
+                for header in message_headers.iter() {
+                    headers.insert(
+                        header.key.to_string(),
+                        String::from_utf8(header.value.unwrap_or_default().to_vec())?,
+                    );
+                }
+            }
+            received.push((payload, headers));
+        }
+
+        let expected = |route: &str| {
+            BTreeMap::from([
+                ("format".to_string(), "json".to_string()),
+                ("route".to_string(), route.to_string()),
+                ("source".to_string(), "192.168.58.100".to_string()),
+                ("subscription".to_string(), "Test".to_string()),
+            ])
+        };
+        assert_eq!(
+            received,
+            vec![
+                ("first".to_string(), expected("Security/4688")),
+                ("second".to_string(), expected("/")),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_payload_compression() -> Result<()> {
+        let mock_cluster = MockCluster::new(1)?;
+        let compressions = [
+            PayloadCompression::Gzip,
+            PayloadCompression::Zstd,
+            PayloadCompression::Lz4,
+        ];
+        for (index, compression) in compressions.into_iter().enumerate() {
+            let topic = format!("{}-{}", TOPIC, index);
+            mock_cluster.create_topic(&topic, 1, 1)?;
+            let config = KafkaConfiguration::new(
+                topic.clone(),
+                HashMap::from([(
+                    "bootstrap.servers".to_string(),
+                    mock_cluster.bootstrap_servers(),
+                )]),
+            );
+            let output =
+                OutputKafka::new(&config, &SubscriptionOutputFormat::Json, compression, &None)?;
+            let event = "{\"EventID\":4624}".repeat(10);
+            output
+                .write(
+                    metadata()?,
+                    Arc::new(vec![Arc::new(event.clone().into_bytes())]),
+                )
+                .await?;
+
+            let consumer: StreamConsumer = ClientConfig::new()
+                .set("bootstrap.servers", mock_cluster.bootstrap_servers())
+                .set("group.id", "openwec-test")
+                .create()?;
+            let mut partitions = TopicPartitionList::new();
+            partitions.add_partition_offset(&topic, 0, Offset::Beginning)?;
+            consumer.assign(&partitions)?;
+
+            let message = tokio::time::timeout(Duration::from_secs(10), consumer.recv()).await??;
+            let header = message
+                .headers()
+                .and_then(|headers| headers.iter().find(|h| h.key == PAYLOAD_COMPRESSION_HEADER))
+                .and_then(|header| header.value)
+                .unwrap_or_default();
+            assert_eq!(header, compression.as_ref().as_bytes());
+            let payload = decompress(compression, message.payload().unwrap_or_default())?;
+            assert_eq!(String::from_utf8(payload)?, event);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::subscription::LokiConfiguration;
+use hyper::{
+    header::{HeaderName, HeaderValue, CONTENT_TYPE},
+    StatusCode,
+};
+use leon::Template;
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::{
+    drivers::http::{BatchRequests, Batcher, HttpConnection},
+    event::{EventFields, EventMetadata},
+    output::OutputDriver,
+};
+
+/// Labels identifying a stream
+type Labels = BTreeMap<String, String>;
+/// Timestamp (in nanoseconds since the epoch) and line of a log entry
+type Entry = [String; 2];
+
+/// Values of the tokens that can be used in label templates
+struct LabelValues<'a> {
+    metadata: &'a EventMetadata,
+}
+
+impl leon::Values for LabelValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "ip" => Some(self.metadata.addr().ip().to_string().into()),
+            "principal" => Some(self.metadata.principal().into()),
+            "node" => match self.metadata.node_name() {
+                Some(node_name) => Some(node_name.into()),
+                None => {
+                    warn!("node name is not configured on this node but is used to build a label in Loki driver");
+                    Some("{node}".into())
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Returns the labels of the stream receiving the events of a batch
+fn labels(config: &LokiConfiguration, metadata: &EventMetadata) -> Result<Labels> {
+    let mut labels = config.labels().clone();
+    for (name, template) in config.label_templates() {
+        let template = Template::parse(template)?;
+        labels.insert(name.clone(), template.render(&LabelValues { metadata })?);
+    }
+    Ok(labels)
+}
+
+/// Returns the timestamp of an event, in nanoseconds since the epoch. Events
+/// are timestamped with their creation time, or with their reception time if
+/// it is unknown.
+fn timestamp(time_created: Option<&str>, time_received: DateTime<Utc>) -> String {
+    let time_created =
+        time_created.and_then(|time_created| DateTime::parse_from_rfc3339(time_created).ok());
+    let nanos = match time_created {
+        Some(time) => time.timestamp_nanos_opt(),
+        None => time_received.timestamp_nanos_opt(),
+    };
+    // Dates which can not be represented are out of the range accepted by
+    // Loki anyway
+    nanos.unwrap_or_default().to_string()
+}
+
+#[derive(Serialize)]
+struct PushStream<'a> {
+    stream: &'a Labels,
+    values: Vec<&'a Entry>,
+}
+
+#[derive(Serialize)]
+struct PushRequest<'a> {
+    streams: Vec<PushStream<'a>>,
+}
+
+/// Builds the body of a push request. Entries sharing the same labels are
+/// sent in the same stream, in order.
+fn push_body(entries: &[(Arc<Labels>, Entry)]) -> Result<Vec<u8>> {
+    let mut streams: BTreeMap<&Labels, Vec<&Entry>> = BTreeMap::new();
+    for (labels, entry) in entries {
+        streams.entry(labels.as_ref()).or_default().push(entry);
+    }
+    Ok(serde_json::to_vec(&PushRequest {
+        streams: streams
+            .into_iter()
+            .map(|(stream, values)| PushStream { stream, values })
+            .collect(),
+    })?)
+}
+
+/// Push requests sent to Loki
+struct LokiRequests {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl BatchRequests for LokiRequests {
+    type Item = (Arc<Labels>, Entry);
+
+    const NAME: &'static str = "Loki";
+
+    fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+
+    fn body(&self, items: &[Self::Item]) -> Result<Vec<u8>> {
+        push_body(items)
+    }
+
+    fn is_retryable(&self, status: StatusCode) -> bool {
+        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// Loki explains why entries are rejected in plain text
+    fn describe_failure(&self, status: StatusCode, content: &[u8]) -> String {
+        format!(
+            "status {} ({})",
+            status,
+            String::from_utf8_lossy(content).trim()
+        )
+    }
+}
+
+pub struct OutputLoki {
+    config: LokiConfiguration,
+    batcher: Batcher<(Arc<Labels>, Entry)>,
+}
+
+impl OutputLoki {
+    pub fn new(config: &LokiConfiguration) -> Result<Self> {
+        debug!("Initialize Loki output with config {:?}", config);
+        let requests = LokiRequests {
+            headers: vec![(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+        };
+        Ok(OutputLoki {
+            config: config.clone(),
+            batcher: Batcher::new(
+                requests,
+                HttpConnection::new(&config.push_url(), config.tls_certificate_authorities())?,
+                config.batch_size(),
+                config.batch_timeout_ms(),
+                config.idle_flush_ms(),
+            ),
+        })
+    }
+
+    async fn send(
+        &self,
+        metadata: &EventMetadata,
+        events: &[Arc<Vec<u8>>],
+        fields: Option<&[Arc<EventFields>]>,
+    ) -> Result<()> {
+        let labels = Arc::new(labels(&self.config, metadata)?);
+        let entries = events
+            .iter()
+            .enumerate()
+            .map(|(index, event)| {
+                let time_created = fields
+                    .and_then(|fields| fields.get(index))
+                    .and_then(|event_fields| event_fields.time_created());
+                let entry = [
+                    timestamp(time_created, metadata.time_received()),
+                    String::from_utf8_lossy(event).into_owned(),
+                ];
+                (labels.clone(), entry)
+            })
+            .collect();
+        self.batcher.write(entries).await
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputLoki {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        self.send(&metadata, &events, None).await
+    }
+
+    async fn write_with_fields(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+        fields: Arc<Vec<Arc<EventFields>>>,
+    ) -> Result<()> {
+        self.send(&metadata, &events, Some(&fields)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use http_body_util::{BodyExt, Empty};
+    use hyper::{
+        body::{Bytes, Incoming},
+        service::service_fn,
+        Request, Response,
+    };
+    use hyper_util::rt::TokioIo;
+    use serde_json::json;
+    use tokio::{net::TcpListener, sync::mpsc};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn config(url: &str) -> Result<LokiConfiguration> {
+        LokiConfiguration::new(
+            url.to_string(),
+            BTreeMap::from([("job".to_string(), "openwec".to_string())]),
+            BTreeMap::from([
+                ("host".to_string(), "{ip}".to_string()),
+                ("collector".to_string(), "openwec-{node}".to_string()),
+                ("user".to_string(), "{principal}".to_string()),
+            ]),
+            true,
+            100,
+            50,
+            Vec::new(),
+        )
+    }
+
+    fn metadata(ip: &str, node_name: Option<&str>) -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str(&format!("{}:5985", ip)).unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            node_name.map(str::to_string),
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    fn entry(timestamp: &str, line: &str) -> Entry {
+        [timestamp.to_string(), line.to_string()]
+    }
+
+    /// Accepts connections and records the path and the body of the requests
+    /// received. The first `failures` requests are answered with 429 Too
+    /// Many Requests.
+    fn serve(listener: TcpListener, failures: usize) -> mpsc::UnboundedReceiver<(String, Bytes)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let failures = Arc::new(std::sync::atomic::AtomicUsize::new(failures));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                let failures = failures.clone();
+                let service = service_fn(move |request: Request<Incoming>| {
+                    let tx = tx.clone();
+                    let failures = failures.clone();
+                    async move {
+                        let path = request.uri().path().to_string();
+                        let content = request.into_body().collect().await?.to_bytes();
+                        tx.send((path, content)).unwrap();
+                        let failed = failures
+                            .fetch_update(
+                                std::sync::atomic::Ordering::SeqCst,
+                                std::sync::atomic::Ordering::SeqCst,
+                                |n| n.checked_sub(1),
+                            )
+                            .is_ok();
+                        let status = if failed {
+                            StatusCode::TOO_MANY_REQUESTS
+                        } else {
+                            StatusCode::NO_CONTENT
+                        };
+                        Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(status)
+                                .body(Empty::<Bytes>::new())
+                                .unwrap(),
+                        )
+                    }
+                });
+                tokio::spawn(async move {
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        rx
+    }
+
+    #[test]
+    fn test_loki_labels() -> Result<()> {
+        let config = config("http://loki.windomain.local:3100")?;
+        assert_eq!(
+            labels(&config, &metadata("192.168.58.100", Some("node-1")))?,
+            BTreeMap::from([
+                ("collector".to_string(), "openwec-node-1".to_string()),
+                ("host".to_string(), "192.168.58.100".to_string()),
+                ("job".to_string(), "openwec".to_string()),
+                ("user".to_string(), "WIN10$@WINDOMAIN.LOCAL".to_string()),
+            ])
+        );
+        // Unknown node names are left as is
+        assert_eq!(
+            labels(&config, &metadata("192.168.58.100", None))?["collector"],
+            "openwec-{node}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_loki_timestamp() {
+        let time_received = DateTime::parse_from_rfc3339("2022-09-22T07:50:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            timestamp(Some("2022-09-22T07:49:32.0356778Z"), time_received),
+            "1663832972035677800"
+        );
+        assert_eq!(timestamp(None, time_received), "1663833000000000000");
+        assert_eq!(
+            timestamp(Some("yesterday"), time_received),
+            "1663833000000000000"
+        );
+    }
+
+    #[test]
+    fn test_loki_push_body() -> Result<()> {
+        let win10 = Arc::new(BTreeMap::from([
+            ("host".to_string(), "192.168.58.100".to_string()),
+            ("job".to_string(), "openwec".to_string()),
+        ]));
+        let dc = Arc::new(BTreeMap::from([
+            ("host".to_string(), "192.168.58.101".to_string()),
+            ("job".to_string(), "openwec".to_string()),
+        ]));
+        let entries = vec![
+            (
+                win10.clone(),
+                entry("1663832972035677800", r#"{"EventID":4624}"#),
+            ),
+            (dc.clone(), entry("1663832972100000000", "<Event/>")),
+            (
+                win10.clone(),
+                entry("1663832973000000000", r#"{"EventID":4625}"#),
+            ),
+        ];
+        let body: serde_json::Value = serde_json::from_slice(&push_body(&entries)?)?;
+        assert_eq!(
+            body,
+            json!({
+                "streams": [
+                    {
+                        "stream": {"host": "192.168.58.100", "job": "openwec"},
+                        "values": [
+                            ["1663832972035677800", "{\"EventID\":4624}"],
+                            ["1663832973000000000", "{\"EventID\":4625}"]
+                        ]
+                    },
+                    {
+                        "stream": {"host": "192.168.58.101", "job": "openwec"},
+                        "values": [["1663832972100000000", "<Event/>"]]
+                    }
+                ]
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loki_push() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let mut requests = serve(listener, 1);
+
+        let output = OutputLoki::new(&config(&format!("http://127.0.0.1:{}/", port))?)?;
+        output
+            .write_with_fields(
+                metadata("192.168.58.100", Some("node-1")),
+                Arc::new(vec![Arc::new(b"<Event/>".to_vec())]),
+                Arc::new(vec![Arc::new(
+                    EventFields::default()
+                        .with_time_created(Some("2022-09-22T07:49:32Z".to_string())),
+                )]),
+            )
+            .await?;
+
+        // The first request is rejected with 429 and sent again
+        for _ in 0..2 {
+            let (path, content) = requests.recv().await.unwrap();
+            assert_eq!(path, "/loki/api/v1/push");
+            let body: serde_json::Value = serde_json::from_slice(&content)?;
+            assert_eq!(
+                body["streams"][0]["values"],
+                json!([["1663832972000000000", "<Event/>"]])
+            );
+            assert_eq!(body["streams"][0]["stream"]["host"], "192.168.58.100");
+        }
+        assert!(requests.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -1,5 +1,17 @@
+pub mod event_hubs;
 pub mod files;
+pub mod grpc;
+pub mod http;
 pub mod kafka;
+pub mod loki;
+pub mod mqtt;
+pub mod nats;
 pub mod tcp;
 pub mod redis;
+pub mod splunk_hec;
+pub mod std_stream;
+pub mod syslog;
+pub mod syslog_tls;
 pub mod unix;
+pub mod unix_stream;
+pub mod zmq;
@@ -0,0 +1,419 @@
+use std::{borrow::Cow, collections::HashSet, sync::Arc, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use common::subscription::MqttConfiguration;
+use leon::Template;
+use log::{debug, info, warn};
+use rumqttc::{
+    AsyncClient, ClientError, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS, Transport,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{sleep, timeout_at, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{event::EventMetadata, output::OutputDriver};
+
+/// Maximum number of publications waiting to be handled by the event loop
+const MQTT_REQUESTS_CAPACITY: usize = 100;
+const MQTT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// Maximum time a write waits for its messages to be acknowledged
+const MQTT_ACK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Number of connection failures after which a write fails
+const MQTT_CONNECTION_ATTEMPTS: u32 = 3;
+/// Delay before connecting again after a failure
+const MQTT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Values of the tokens that can be used in topics. Slashes and wildcards
+/// are replaced so that each value is part of a single topic level.
+struct TopicValues<'a> {
+    metadata: &'a EventMetadata,
+}
+
+fn topic_level(value: &str) -> String {
+    value.replace(|c: char| c == '/' || c == '+' || c == '#', "_")
+}
+
+impl leon::Values for TopicValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "ip" => Some(topic_level(&self.metadata.addr().ip().to_string()).into()),
+            "principal" => Some(topic_level(self.metadata.principal()).into()),
+            "node" => match self.metadata.node_name() {
+                Some(node_name) => Some(topic_level(node_name).into()),
+                None => {
+                    warn!("node name is not configured on this node but is used to build a topic in Mqtt driver");
+                    Some("{node}".into())
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+fn qos(level: u8) -> QoS {
+    match level {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        // The configuration only accepts 0, 1 or 2
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+/// Parameters of the PUBLISH packets of a batch of events
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct PublishParameters {
+    topic: String,
+    qos: QoS,
+    retain: bool,
+}
+
+fn publish_parameters(
+    config: &MqttConfiguration,
+    metadata: &EventMetadata,
+) -> Result<PublishParameters> {
+    let template = Template::parse(config.topic())?;
+    Ok(PublishParameters {
+        topic: template.render(&TopicValues { metadata })?,
+        qos: qos(config.qos()),
+        retain: false,
+    })
+}
+
+fn mqtt_options(config: &MqttConfiguration) -> Result<MqttOptions> {
+    let mut options = MqttOptions::new(config.client_id(), config.broker(), config.port());
+    options.set_keep_alive(MQTT_KEEP_ALIVE);
+    if let Some(username) = config.username() {
+        options.set_credentials(
+            username,
+            config.password().map(String::as_str).unwrap_or_default(),
+        );
+    }
+    if config.tls_enabled() {
+        let mut certificate_authorities = Vec::new();
+        for certificate_authority_file in config.tls_certificate_authorities() {
+            certificate_authorities.extend(
+                std::fs::read(certificate_authority_file)
+                    .with_context(|| format!("Failed to read {}", certificate_authority_file))?,
+            );
+        }
+        options.set_transport(Transport::tls(certificate_authorities, None, None));
+    }
+    Ok(options)
+}
+
+#[derive(Debug)]
+pub struct WriteMqttMessage {
+    parameters: PublishParameters,
+    events: Arc<Vec<Arc<Vec<u8>>>>,
+    resp: oneshot::Sender<Result<()>>,
+}
+
+fn send_response(sender: oneshot::Sender<Result<()>>, msg: Result<()>) {
+    if let Err(e) = sender.send(msg) {
+        warn!(
+            "Failed to send MQTT write result because the receiver dropped. Result was: {:?}",
+            e
+        );
+    }
+}
+
+/// MQTT session handled by the task of an output
+struct Connection {
+    options: MqttOptions,
+    client: AsyncClient,
+    eventloop: EventLoop,
+}
+
+impl Connection {
+    fn new(options: MqttOptions) -> Self {
+        let (client, eventloop) = AsyncClient::new(options.clone(), MQTT_REQUESTS_CAPACITY);
+        Connection {
+            options,
+            client,
+            eventloop,
+        }
+    }
+
+    /// Drops the session, including the publications which have not been
+    /// acknowledged, so that they are not mistaken for the ones of the
+    /// next write
+    fn reset(&mut self) {
+        *self = Connection::new(self.options.clone());
+    }
+
+    fn broker(&self) -> String {
+        let (host, port) = self.options.broker_address();
+        format!("{}:{}", host, port)
+    }
+
+    /// Publishes events and waits until they have been sent (QoS 0) or
+    /// acknowledged by the broker (QoS 1 and 2)
+    async fn publish(
+        &mut self,
+        parameters: &PublishParameters,
+        events: &Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        // Publications are queued until the event loop handles them, so
+        // they are made concurrently with polling it
+        let publisher = {
+            let client = self.client.clone();
+            let parameters = parameters.clone();
+            let events = events.clone();
+            tokio::spawn(async move {
+                for event in events.iter() {
+                    client
+                        .publish(
+                            &parameters.topic,
+                            parameters.qos,
+                            parameters.retain,
+                            event.to_vec(),
+                        )
+                        .await?;
+                }
+                Ok::<(), ClientError>(())
+            })
+        };
+        let result = self
+            .wait_acknowledgements(parameters.qos, events.len())
+            .await;
+        publisher.abort();
+        if result.is_err() {
+            self.reset();
+        }
+        result
+    }
+
+    async fn wait_acknowledgements(&mut self, qos: QoS, count: usize) -> Result<()> {
+        let deadline = Instant::now() + MQTT_ACK_TIMEOUT;
+        let mut sent = 0;
+        // Packet identifiers of the messages waiting for PUBACK (QoS 1) or
+        // PUBCOMP (QoS 2)
+        let mut inflight = HashSet::new();
+        let mut failures = 0;
+        while sent < count || !inflight.is_empty() {
+            let Ok(event) = timeout_at(deadline, self.eventloop.poll()).await else {
+                bail!(
+                    "{} messages have not been acknowledged by MQTT broker {} in time",
+                    count - sent + inflight.len(),
+                    self.broker()
+                );
+            };
+            match event {
+                Ok(Event::Outgoing(Outgoing::Publish(pkid))) => {
+                    // Messages which are still in flight are published again
+                    // by the event loop once the connection is restored
+                    if qos == QoS::AtMostOnce || inflight.insert(pkid) {
+                        sent += 1;
+                    }
+                }
+                Ok(Event::Incoming(Packet::PubAck(ack))) if qos == QoS::AtLeastOnce => {
+                    inflight.remove(&ack.pkid);
+                }
+                Ok(Event::Incoming(Packet::PubComp(comp))) if qos == QoS::ExactlyOnce => {
+                    inflight.remove(&comp.pkid);
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    failures += 1;
+                    if failures >= MQTT_CONNECTION_ATTEMPTS {
+                        bail!(
+                            "Failed to publish messages to MQTT broker {}: {}",
+                            self.broker(),
+                            e
+                        );
+                    }
+                    warn!(
+                        "Connection to MQTT broker {} failed ({}/{}), messages will be published again once it is restored: {}",
+                        self.broker(),
+                        failures,
+                        MQTT_CONNECTION_ATTEMPTS,
+                        e
+                    );
+                    sleep(MQTT_RECONNECT_DELAY).await;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(
+    options: MqttOptions,
+    mut task_rx: mpsc::Receiver<WriteMqttMessage>,
+    cancellation_token: CancellationToken,
+) {
+    let mut connection = Connection::new(options);
+
+    loop {
+        tokio::select! {
+            Some(message) = task_rx.recv() => {
+                let result = connection.publish(&message.parameters, &message.events).await;
+                send_response(message.resp, result);
+            },
+            // The event loop must be polled between writes to keep the
+            // connection alive
+            event = connection.eventloop.poll() => {
+                if let Err(e) = event {
+                    debug!("Connection to MQTT broker {} failed: {}", connection.broker(), e);
+                    sleep(MQTT_RECONNECT_DELAY).await;
+                }
+            },
+            _ = cancellation_token.cancelled() => {
+                // The output has been removed (or disabled): messages which
+                // are already queued are written before disconnecting
+                task_rx.close();
+                while let Some(message) = task_rx.recv().await {
+                    let result = connection.publish(&message.parameters, &message.events).await;
+                    send_response(message.resp, result);
+                }
+                break;
+            }
+        };
+    }
+    info!("Exiting MQTT output task ({})", connection.broker());
+}
+
+pub struct OutputMqtt {
+    config: MqttConfiguration,
+    task_tx: mpsc::Sender<WriteMqttMessage>,
+    task_ct: CancellationToken,
+}
+
+impl OutputMqtt {
+    pub fn new(config: &MqttConfiguration) -> Result<Self> {
+        debug!("Initialize MQTT output with config {:?}", config);
+        let options = mqtt_options(config)?;
+
+        let (task_tx, task_rx) = mpsc::channel(32);
+
+        // Use a CancellationToken to tell the task to end itself
+        let task_ct = CancellationToken::new();
+        let cloned_task_ct = task_ct.clone();
+
+        // Launch the task responsible for handling the MQTT session
+        tokio::spawn(async move { run(options, task_rx, cloned_task_ct).await });
+
+        Ok(OutputMqtt {
+            config: config.clone(),
+            task_tx,
+            task_ct,
+        })
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputMqtt {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let parameters = publish_parameters(&self.config, &metadata)?;
+
+        // Create a oneshot channel to retrieve the result of the operation
+        let (tx, rx) = oneshot::channel();
+        self.task_tx
+            .send(WriteMqttMessage {
+                parameters,
+                events,
+                resp: tx,
+            })
+            .await?;
+
+        // Wait for the result
+        rx.await??;
+
+        Ok(())
+    }
+}
+
+impl Drop for OutputMqtt {
+    fn drop(&mut self) {
+        self.task_ct.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn event_metadata(node_name: Option<String>) -> Result<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)?;
+        Ok(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            node_name,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    fn config(topic: &str, qos: u8) -> Result<MqttConfiguration> {
+        MqttConfiguration::new(
+            "mqtt.windomain.local".to_string(),
+            None,
+            topic.to_string(),
+            qos,
+            "openwec".to_string(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_mqtt_publish_parameters() -> Result<()> {
+        let metadata = event_metadata(Some("collector/1".to_string()))?;
+
+        assert_eq!(
+            publish_parameters(&config("openwec/events", 0)?, &metadata)?,
+            PublishParameters {
+                topic: "openwec/events".to_string(),
+                qos: QoS::AtMostOnce,
+                retain: false,
+            }
+        );
+        assert_eq!(
+            publish_parameters(&config("openwec/{ip}/{principal}/{node}", 1)?, &metadata)?,
+            PublishParameters {
+                topic: "openwec/192.168.58.100/WIN10$@WINDOMAIN.LOCAL/collector_1".to_string(),
+                qos: QoS::AtLeastOnce,
+                retain: false,
+            }
+        );
+        assert_eq!(
+            publish_parameters(&config("openwec/{ip}", 2)?, &metadata)?.qos,
+            QoS::ExactlyOnce
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mqtt_options() -> Result<()> {
+        let mut config = config("openwec/events", 1)?;
+        let options = mqtt_options(&config)?;
+        assert_eq!(options.client_id(), "openwec");
+        assert_eq!(
+            options.broker_address(),
+            ("mqtt.windomain.local".to_string(), 1883)
+        );
+        assert_eq!(options.credentials(), None);
+
+        config.set_credentials(Some("openwec".to_string()), Some("secret".to_string()))?;
+        let options = mqtt_options(&config)?;
+        assert_eq!(
+            options.credentials(),
+            Some(("openwec".to_string(), "secret".to_string()))
+        );
+        Ok(())
+    }
+}
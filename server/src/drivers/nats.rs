@@ -0,0 +1,230 @@
+use std::{borrow::Cow, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use async_nats::{jetstream, Client, ConnectOptions, ServerAddr};
+use async_trait::async_trait;
+use common::subscription::NatsConfiguration;
+use futures::future::try_join_all;
+use leon::Template;
+use log::{debug, warn};
+use tokio::sync::OnceCell;
+
+use crate::{event::EventMetadata, output::OutputDriver};
+
+/// Number of attempts to publish a message to JetStream before the write
+/// fails
+const JETSTREAM_PUBLISH_ATTEMPTS: u32 = 3;
+/// Delay between two attempts to publish a message to JetStream
+const JETSTREAM_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Values of the tokens that can be used in subjects. Dots, whitespaces and
+/// wildcards are replaced so that each value is a single subject token.
+struct SubjectValues<'a> {
+    metadata: &'a EventMetadata,
+}
+
+fn subject_token(value: &str) -> String {
+    value.replace(
+        |c: char| c == '.' || c == '*' || c == '>' || c.is_whitespace(),
+        "_",
+    )
+}
+
+impl leon::Values for SubjectValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "ip" => Some(subject_token(&self.metadata.addr().ip().to_string()).into()),
+            "principal" => Some(subject_token(self.metadata.principal()).into()),
+            "node" => match self.metadata.node_name() {
+                Some(node_name) => Some(subject_token(node_name).into()),
+                None => {
+                    warn!("node name is not configured on this node but is used to build a subject in Nats driver");
+                    Some("{node}".into())
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+pub struct OutputNats {
+    config: NatsConfiguration,
+    // Connected on the first write, so that subscriptions can be loaded
+    // while the servers are unreachable. The client then reconnects by
+    // itself.
+    client: OnceCell<Client>,
+}
+
+impl OutputNats {
+    pub fn new(config: &NatsConfiguration) -> Result<Self> {
+        debug!("Initialize Nats driver with config {:?}", config);
+        Ok(OutputNats {
+            config: config.clone(),
+            client: OnceCell::new(),
+        })
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let mut servers = Vec::with_capacity(self.config.servers().len());
+        for server in self.config.servers() {
+            servers.push(
+                server
+                    .parse::<ServerAddr>()
+                    .with_context(|| format!("Invalid NATS server {}", server))?,
+            );
+        }
+        let mut options = ConnectOptions::new();
+        if let Some(credentials_file) = self.config.credentials_file() {
+            options = options
+                .credentials_file(credentials_file)
+                .await
+                .with_context(|| format!("Failed to load NATS credentials {}", credentials_file))?;
+        }
+        if self.config.tls_enabled() {
+            options = options.require_tls(true);
+            for certificate_authority_file in self.config.tls_certificate_authorities() {
+                options = options.add_root_certificates(PathBuf::from(certificate_authority_file));
+            }
+        }
+        let client = options.connect(servers.as_slice()).await.with_context(|| {
+            format!(
+                "Failed to connect to NATS servers {:?}",
+                self.config.servers()
+            )
+        })?;
+        debug!("Connected to NATS servers {:?}", self.config.servers());
+        Ok(client)
+    }
+
+    fn subject(&self, metadata: &EventMetadata) -> Result<String> {
+        let template = Template::parse(self.config.subject())?;
+        Ok(template.render(&SubjectValues { metadata })?)
+    }
+}
+
+/// Publishes a message to JetStream and waits until it has been stored,
+/// retrying a bounded number of times
+async fn publish_with_ack(context: &jetstream::Context, subject: &str, event: &[u8]) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        let result = match context
+            .publish(subject.to_owned(), event.to_vec().into())
+            .await
+        {
+            Ok(ack) => ack.await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < JETSTREAM_PUBLISH_ATTEMPTS => {
+                warn!(
+                    "Failed to publish message to JetStream subject {} (attempt {}/{}): {}",
+                    subject, attempt, JETSTREAM_PUBLISH_ATTEMPTS, e
+                );
+                attempt += 1;
+                tokio::time::sleep(JETSTREAM_RETRY_DELAY).await;
+            }
+            Err(e) => bail!(
+                "Failed to publish message to JetStream subject {} after {} attempts: {}",
+                subject,
+                JETSTREAM_PUBLISH_ATTEMPTS,
+                e
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputNats {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let subject = self.subject(&metadata)?;
+        let client = self.client.get_or_try_init(|| self.connect()).await?;
+
+        if self.config.jetstream() {
+            let context = jetstream::new(client.clone());
+            try_join_all(
+                events
+                    .iter()
+                    .map(|event| publish_with_ack(&context, &subject, event)),
+            )
+            .await?;
+        } else {
+            for event in events.iter() {
+                client
+                    .publish(subject.clone(), event.to_vec().into())
+                    .await
+                    .with_context(|| format!("Failed to publish message to subject {}", subject))?;
+            }
+            // Make sure that messages have been sent to the server
+            client
+                .flush()
+                .await
+                .with_context(|| format!("Failed to flush messages of subject {}", subject))?;
+        }
+        debug!(
+            "Published {} messages to NATS subject {}",
+            events.len(),
+            subject
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn event_metadata(addr: &str, node_name: Option<String>) -> Result<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)?;
+        Ok(EventMetadata::new(
+            &SocketAddr::from_str(addr)?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            node_name,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    fn output(subject: &str) -> Result<OutputNats> {
+        OutputNats::new(&NatsConfiguration::new(
+            vec!["nats://localhost:4222".to_string()],
+            subject.to_string(),
+            false,
+            None,
+            Vec::new(),
+        )?)
+    }
+
+    #[test]
+    fn test_nats_subject() -> Result<()> {
+        let metadata = event_metadata("192.168.58.100:5985", Some("collector 1".to_string()))?;
+
+        assert_eq!(
+            output("openwec.events")?.subject(&metadata)?,
+            "openwec.events"
+        );
+        assert_eq!(
+            output("openwec.{ip}.{principal}.{node}")?.subject(&metadata)?,
+            "openwec.192_168_58_100.WIN10$@WINDOMAIN_LOCAL.collector_1"
+        );
+
+        let metadata = event_metadata("[2001:db8::1]:5985", None)?;
+        assert_eq!(
+            output("openwec.{ip}.{node}")?.subject(&metadata)?,
+            "openwec.2001:db8::1.{node}"
+        );
+        Ok(())
+    }
+}
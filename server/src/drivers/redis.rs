@@ -1,6 +1,7 @@
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use common::subscription::RedisConfiguration;
+use chrono::{DateTime, Utc};
+use common::subscription::{RedisConfiguration, RedisMode};
 use log::debug;
 
 use crate::{
@@ -13,20 +14,71 @@ use std::sync::Arc;
 
 pub struct OutputRedis {
     config: RedisConfiguration,
+    // Time (in seconds) after which events expire
+    event_ttl: Option<u32>,
     producer: redis::Client,
 }
 
 impl OutputRedis {
-    pub fn new(config: &RedisConfiguration) -> Result<Self> {
+    pub fn new(config: &RedisConfiguration, event_ttl: Option<u32>) -> Result<Self> {
         let client = redis::Client::open(format!("redis://{}/", config.addr()))
             .context("Could not open redis connection")?;
 
-        debug!("Initialize redis output with config {:?}", config);
+        debug!(
+            "Initialize redis output with config {:?} and event TTL {:?}",
+            config, event_ttl
+        );
         Ok(OutputRedis {
             config: config.clone(),
+            event_ttl,
             producer: client,
         })
     }
+
+    /// Builds the command appending an event, which is its last argument
+    fn command(&self) -> redis::Cmd {
+        match self.config.mode() {
+            RedisMode::List => {
+                let mut cmd = redis::cmd("LPUSH");
+                cmd.arg(self.config.list());
+                cmd
+            }
+            RedisMode::Stream => {
+                let mut cmd = redis::cmd("XADD");
+                cmd.arg(self.config.list());
+                if let Some(max_len) = self.config.max_len() {
+                    cmd.arg("MAXLEN").arg("~").arg(max_len);
+                }
+                cmd.arg("*").arg("data");
+                cmd
+            }
+        }
+    }
+
+    /// Builds the command making events expire, sent after each write.
+    /// Lists expire as a whole `event_ttl` seconds after the last write,
+    /// whereas the entries of streams which are older than `event_ttl`
+    /// seconds are removed (entry IDs start with their creation time in
+    /// milliseconds).
+    fn expire_command(&self, now: DateTime<Utc>) -> Option<redis::Cmd> {
+        let event_ttl = self.event_ttl?;
+        match self.config.mode() {
+            RedisMode::List => {
+                let mut cmd = redis::cmd("EXPIRE");
+                cmd.arg(self.config.list()).arg(event_ttl);
+                Some(cmd)
+            }
+            RedisMode::Stream => {
+                let min_id = now
+                    .timestamp_millis()
+                    .saturating_sub(i64::from(event_ttl) * 1000)
+                    .max(0);
+                let mut cmd = redis::cmd("XTRIM");
+                cmd.arg(self.config.list()).arg("MINID").arg(min_id);
+                Some(cmd)
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -34,10 +86,10 @@ impl OutputDriver for OutputRedis {
     async fn write(
         &self,
         _metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
     ) -> Result<()> {
         let mut results = FuturesUnordered::new();
-        let cmd = redis::cmd("LPUSH");
+        let cmd = self.command();
 
         for event in events.iter() {
             let mut redis_cmd = cmd.clone();
@@ -45,19 +97,166 @@ impl OutputDriver for OutputRedis {
 
             results.push(async move {
                 redis_cmd
-                    .arg(&[self.config.list(), event.as_ref()])
-                    .query_async::<_, Option<u32>>(&mut redis_connection)
+                    .arg(event.as_slice())
+                    .query_async::<_, redis::Value>(&mut redis_connection)
                     .await
             });
         }
 
         while let Some(result) = results.next().await {
             match result {
-                Ok(number_of_items) => debug!("Redis message sent: {:?}", number_of_items),
+                Ok(reply) => debug!("Redis message sent: {:?}", reply),
                 Err(e) => bail!(e),
             }
         }
 
+        if let Some(expire_cmd) = self.expire_command(Utc::now()) {
+            let mut redis_connection = self.producer.get_multiplexed_tokio_connection().await?;
+            let reply = expire_cmd
+                .query_async::<_, redis::Value>(&mut redis_connection)
+                .await?;
+            debug!("Redis expiration applied: {:?}", reply);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    fn cmd_args(cmd: &redis::Cmd) -> Vec<Vec<u8>> {
+        cmd.args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(arg) => arg.to_vec(),
+                redis::Arg::Cursor => panic!("Unexpected cursor"),
+            })
+            .collect()
+    }
+
+    fn args(config: &RedisConfiguration) -> Vec<Vec<u8>> {
+        let output = OutputRedis::new(config, None).unwrap();
+        cmd_args(&output.command())
+    }
+
+    #[test]
+    fn test_redis_command() -> Result<()> {
+        let mut config = RedisConfiguration::new("localhost".to_string(), "events".to_string());
+        assert_eq!(args(&config), vec![b"LPUSH".to_vec(), b"events".to_vec()]);
+
+        config.set_mode(RedisMode::Stream, None)?;
+        assert_eq!(
+            args(&config),
+            vec![
+                b"XADD".to_vec(),
+                b"events".to_vec(),
+                b"*".to_vec(),
+                b"data".to_vec()
+            ]
+        );
+
+        config.set_mode(RedisMode::Stream, Some(1000))?;
+        assert_eq!(
+            args(&config),
+            vec![
+                b"XADD".to_vec(),
+                b"events".to_vec(),
+                b"MAXLEN".to_vec(),
+                b"~".to_vec(),
+                b"1000".to_vec(),
+                b"*".to_vec(),
+                b"data".to_vec()
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_redis_expire_command() -> Result<()> {
+        let now = DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331Z")?.with_timezone(&Utc);
+        let mut config = RedisConfiguration::new("localhost".to_string(), "events".to_string());
+        assert!(OutputRedis::new(&config, None)?
+            .expire_command(now)
+            .is_none());
+
+        let output = OutputRedis::new(&config, Some(3600))?;
+        assert_eq!(
+            cmd_args(&output.expire_command(now).unwrap()),
+            vec![b"EXPIRE".to_vec(), b"events".to_vec(), b"3600".to_vec()]
+        );
+
+        config.set_mode(RedisMode::Stream, Some(1000))?;
+        let output = OutputRedis::new(&config, Some(3600))?;
+        assert_eq!(
+            cmd_args(&output.expire_command(now).unwrap()),
+            vec![
+                b"XTRIM".to_vec(),
+                b"events".to_vec(),
+                b"MINID".to_vec(),
+                (now.timestamp_millis() - 3_600_000)
+                    .to_string()
+                    .into_bytes()
+            ]
+        );
+        Ok(())
+    }
+
+    /// Requires a Redis server listening on $REDIS_ADDR
+    #[tokio::test]
+    async fn test_redis_event_ttl() -> Result<()> {
+        let addr = std::env::var("REDIS_ADDR").expect("$REDIS_ADDR is not set");
+        let client = redis::Client::open(format!("redis://{}/", addr))?;
+        let mut conn = client.get_multiplexed_tokio_connection().await?;
+        let list = format!("openwec-ttl-{}", uuid::Uuid::new_v4());
+        let stream = format!("openwec-ttl-stream-{}", uuid::Uuid::new_v4());
+        let events = Arc::new(vec![Arc::new(b"{\"EventID\":4624}".to_vec())]);
+
+        // The list expires after event_ttl seconds
+        let config = RedisConfiguration::new(addr.clone(), list.clone());
+        let output = OutputRedis::new(&config, Some(3600))?;
+        output.write(metadata(), events.clone()).await?;
+        let ttl: i64 = redis::cmd("TTL").arg(&list).query_async(&mut conn).await?;
+        assert!(ttl > 0 && ttl <= 3600, "Unexpected TTL {}", ttl);
+
+        // Stream entries older than event_ttl seconds are removed
+        let mut config = RedisConfiguration::new(addr, stream.clone());
+        config.set_mode(RedisMode::Stream, None)?;
+        let output = OutputRedis::new(&config, Some(1))?;
+        output.write(metadata(), events.clone()).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        output.write(metadata(), events).await?;
+        let len: i64 = redis::cmd("XLEN")
+            .arg(&stream)
+            .query_async(&mut conn)
+            .await?;
+        assert_eq!(len, 1);
+
+        redis::cmd("DEL")
+            .arg(&list)
+            .arg(&stream)
+            .query_async::<_, redis::Value>(&mut conn)
+            .await?;
         Ok(())
     }
 }
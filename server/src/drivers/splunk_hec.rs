@@ -0,0 +1,478 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::DateTime;
+use common::subscription::SplunkHecConfiguration;
+use hyper::{
+    header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    StatusCode,
+};
+use leon::Template;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::{
+    drivers::http::{BatchRequests, Batcher, HttpConnection},
+    event::{EventFields, EventMetadata},
+    output::OutputDriver,
+};
+
+/// Values of the tokens that can be used in the source of events
+struct SourceValues<'a> {
+    metadata: &'a EventMetadata,
+}
+
+impl leon::Values for SourceValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        match key {
+            "ip" => Some(self.metadata.addr().ip().to_string().into()),
+            "principal" => Some(self.metadata.principal().into()),
+            "node" => match self.metadata.node_name() {
+                Some(node_name) => Some(node_name.into()),
+                None => {
+                    warn!("node name is not configured on this node but is used to build a source in SplunkHec driver");
+                    Some("{node}".into())
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Formatted event, embedded as is if it is a JSON value
+#[derive(Serialize)]
+#[serde(untagged)]
+enum HecEventContent<'a> {
+    Json(&'a RawValue),
+    Text(Cow<'a, str>),
+}
+
+/// Envelope of an event sent to the HTTP Event Collector
+#[derive(Serialize)]
+struct HecEvent<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sourcetype: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    event: HecEventContent<'a>,
+}
+
+/// Converts the creation time of an event to the number of seconds since
+/// the epoch, with a millisecond precision
+fn hec_time(time_created: &str) -> Option<f64> {
+    match DateTime::parse_from_rfc3339(time_created) {
+        Ok(time) => Some(time.timestamp_millis() as f64 / 1000.0),
+        Err(e) => {
+            debug!("Could not parse TimeCreated {:?}: {}", time_created, e);
+            None
+        }
+    }
+}
+
+/// Wraps a formatted event in a HEC envelope. Events without creation time
+/// are timestamped by Splunk when they are received.
+fn hec_event(
+    config: &SplunkHecConfiguration,
+    source: Option<&str>,
+    event: &[u8],
+    time_created: Option<&str>,
+) -> Result<Vec<u8>> {
+    let event = match serde_json::from_slice::<&RawValue>(event) {
+        Ok(value) => HecEventContent::Json(value),
+        Err(_) => HecEventContent::Text(String::from_utf8_lossy(event)),
+    };
+    Ok(serde_json::to_vec(&HecEvent {
+        time: time_created.and_then(hec_time),
+        index: config.index().map(String::as_str),
+        sourcetype: config.sourcetype().map(String::as_str),
+        source,
+        event,
+    })?)
+}
+
+/// Builds the body of a request containing HEC events, one per line
+fn body(events: &[Vec<u8>]) -> Vec<u8> {
+    let mut content = Vec::with_capacity(events.iter().map(|event| event.len() + 1).sum());
+    for event in events {
+        content.extend_from_slice(event);
+        content.push(b'\n');
+    }
+    content
+}
+
+/// Body of the responses of the HTTP Event Collector
+#[derive(Debug, Deserialize)]
+struct HecResponse {
+    text: String,
+    code: i64,
+    #[serde(rename = "invalid-event-number")]
+    invalid_event_number: Option<u64>,
+}
+
+/// Describes why a request failed, using the HEC response if possible
+fn describe_failure(status: StatusCode, content: &[u8]) -> String {
+    match serde_json::from_slice::<HecResponse>(content) {
+        Ok(HecResponse {
+            text,
+            code,
+            invalid_event_number: Some(number),
+        }) => format!(
+            "status {}: {} (code {}, invalid event number {})",
+            status, text, code, number
+        ),
+        Ok(HecResponse { text, code, .. }) => {
+            format!("status {}: {} (code {})", status, text, code)
+        }
+        Err(_) => format!("status {}", status),
+    }
+}
+
+/// Requests sent to the HTTP Event Collector, containing serialized HEC
+/// events
+struct SplunkHecRequests {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SplunkHecRequests {
+    fn new(config: &SplunkHecConfiguration) -> Result<Self> {
+        let mut authorization = HeaderValue::from_str(&format!("Splunk {}", config.token()))?;
+        authorization.set_sensitive(true);
+        Ok(Self {
+            headers: vec![
+                (CONTENT_TYPE, HeaderValue::from_static("application/json")),
+                (AUTHORIZATION, authorization),
+            ],
+        })
+    }
+}
+
+impl BatchRequests for SplunkHecRequests {
+    type Item = Vec<u8>;
+
+    const NAME: &'static str = "Splunk HEC";
+
+    fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+
+    fn body(&self, items: &[Self::Item]) -> Result<Vec<u8>> {
+        Ok(body(items))
+    }
+
+    /// 503 Service Unavailable means that the HEC queue is full
+    fn is_retryable(&self, status: StatusCode) -> bool {
+        status == StatusCode::SERVICE_UNAVAILABLE || status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn describe_failure(&self, status: StatusCode, content: &[u8]) -> String {
+        describe_failure(status, content)
+    }
+}
+
+pub struct OutputSplunkHec {
+    config: SplunkHecConfiguration,
+    batcher: Batcher<Vec<u8>>,
+}
+
+impl OutputSplunkHec {
+    pub fn new(config: &SplunkHecConfiguration) -> Result<Self> {
+        // The token is not logged
+        debug!("Initialize Splunk HEC output with config {:?}", config);
+        Ok(OutputSplunkHec {
+            config: config.clone(),
+            batcher: Batcher::new(
+                SplunkHecRequests::new(config)?,
+                HttpConnection::new(config.url(), config.tls_certificate_authorities())?,
+                config.batch_size(),
+                config.batch_timeout_ms(),
+                config.idle_flush_ms(),
+            ),
+        })
+    }
+
+    fn source(&self, metadata: &EventMetadata) -> Result<Option<String>> {
+        match self.config.source() {
+            Some(source) => {
+                let template = Template::parse(source)?;
+                Ok(Some(template.render(&SourceValues { metadata })?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn send(
+        &self,
+        metadata: &EventMetadata,
+        events: &[Arc<Vec<u8>>],
+        fields: Option<&[Arc<EventFields>]>,
+    ) -> Result<()> {
+        let source = self.source(metadata)?;
+        let mut hec_events = Vec::with_capacity(events.len());
+        for (index, event) in events.iter().enumerate() {
+            let time_created = fields
+                .and_then(|fields| fields.get(index))
+                .and_then(|event_fields| event_fields.time_created());
+            hec_events.push(hec_event(
+                &self.config,
+                source.as_deref(),
+                event,
+                time_created,
+            )?);
+        }
+        self.batcher.write(hec_events).await
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputSplunkHec {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        self.send(&metadata, &events, None).await
+    }
+
+    async fn write_with_fields(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+        fields: Arc<Vec<Arc<EventFields>>>,
+    ) -> Result<()> {
+        self.send(&metadata, &events, Some(&fields)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use http_body_util::{BodyExt, Full};
+    use hyper::{
+        body::{Bytes, Incoming},
+        service::service_fn,
+        HeaderMap, Request, Response,
+    };
+    use hyper_util::rt::TokioIo;
+    use tokio::{net::TcpListener, sync::mpsc};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn config(url: &str) -> Result<SplunkHecConfiguration> {
+        SplunkHecConfiguration::new(
+            url.to_string(),
+            "11111111-2222-3333-4444-555555555555".to_string(),
+            Some("wineventlog".to_string()),
+            Some("XmlWinEventLog".to_string()),
+            Some("openwec:{principal}".to_string()),
+            1,
+            1_000,
+            Vec::new(),
+        )
+    }
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    /// Accepts connections and records the requests received. Requests are
+    /// answered with `responses` in order, and then with a success.
+    fn serve(
+        listener: TcpListener,
+        responses: Vec<(StatusCode, &'static str)>,
+    ) -> mpsc::UnboundedReceiver<(HeaderMap, Bytes)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let responses = Arc::new(std::sync::Mutex::new(responses.into_iter()));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                let responses = responses.clone();
+                let service = service_fn(move |request: Request<Incoming>| {
+                    let tx = tx.clone();
+                    let responses = responses.clone();
+                    async move {
+                        let headers = request.headers().clone();
+                        let content = request.into_body().collect().await?.to_bytes();
+                        tx.send((headers, content)).unwrap();
+                        let (status, body) = responses
+                            .lock()
+                            .unwrap()
+                            .next()
+                            .unwrap_or((StatusCode::OK, r#"{"text":"Success","code":0}"#));
+                        Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(status)
+                                .body(Full::new(Bytes::from(body)))
+                                .unwrap(),
+                        )
+                    }
+                });
+                tokio::spawn(async move {
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        rx
+    }
+
+    #[test]
+    fn test_splunk_hec_body() -> Result<()> {
+        let config = config("http://splunk.windomain.local:8088/services/collector/event")?;
+        let events = vec![
+            hec_event(
+                &config,
+                Some("openwec:WIN10$@WINDOMAIN.LOCAL"),
+                br#"{"System":{"EventID":4624}}"#,
+                Some("2022-09-22T07:49:32.0356778Z"),
+            )?,
+            // Events which are not JSON are sent as strings
+            hec_event(
+                &config,
+                None,
+                b"<Event>\n<System/>\n</Event>",
+                Some("2022-09-22T09:49:33+02:00"),
+            )?,
+            // Splunk timestamps events without creation time
+            hec_event(&config, None, br#"{"System":{"EventID":4625}}"#, None)?,
+        ];
+        assert_eq!(
+            String::from_utf8(body(&events))?,
+            concat!(
+                r#"{"time":1663832972.035,"index":"wineventlog","sourcetype":"XmlWinEventLog","source":"openwec:WIN10$@WINDOMAIN.LOCAL","event":{"System":{"EventID":4624}}}"#,
+                "\n",
+                r#"{"time":1663832973.0,"index":"wineventlog","sourcetype":"XmlWinEventLog","event":"<Event>\n<System/>\n</Event>"}"#,
+                "\n",
+                r#"{"index":"wineventlog","sourcetype":"XmlWinEventLog","event":{"System":{"EventID":4625}}}"#,
+                "\n",
+            )
+        );
+        assert!(body(&[]).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_failure() {
+        assert_eq!(
+            describe_failure(
+                StatusCode::FORBIDDEN,
+                br#"{"text":"Invalid token","code":4}"#
+            ),
+            "status 403 Forbidden: Invalid token (code 4)"
+        );
+        assert_eq!(
+            describe_failure(
+                StatusCode::BAD_REQUEST,
+                br#"{"text":"Invalid data format","code":6,"invalid-event-number":2}"#
+            ),
+            "status 400 Bad Request: Invalid data format (code 6, invalid event number 2)"
+        );
+        assert_eq!(
+            describe_failure(StatusCode::BAD_GATEWAY, b"<html></html>"),
+            "status 502 Bad Gateway"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_splunk_hec_retry() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let mut requests = serve(
+            listener,
+            vec![
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    r#"{"text":"Server is busy","code":9}"#,
+                ),
+                (StatusCode::TOO_MANY_REQUESTS, ""),
+            ],
+        );
+
+        let output = OutputSplunkHec::new(&config(&format!(
+            "http://127.0.0.1:{}/services/collector/event",
+            port
+        ))?)?;
+        output
+            .write_with_fields(
+                metadata(),
+                Arc::new(vec![Arc::new(br#"{"EventID":4624}"#.to_vec())]),
+                Arc::new(vec![Arc::new(
+                    EventFields::default()
+                        .with_time_created(Some("2022-09-22T07:49:32Z".to_string())),
+                )]),
+            )
+            .await?;
+
+        // The request is sent again until it succeeds
+        for _ in 0..3 {
+            let (headers, content) = requests.recv().await.unwrap();
+            assert_eq!(
+                headers.get("authorization").unwrap(),
+                "Splunk 11111111-2222-3333-4444-555555555555"
+            );
+            assert_eq!(headers.get("content-type").unwrap(), "application/json");
+            assert_eq!(
+                content,
+                Bytes::from(concat!(
+                    r#"{"time":1663832972.0,"index":"wineventlog","sourcetype":"XmlWinEventLog","source":"openwec:WIN10$@WINDOMAIN.LOCAL","event":{"EventID":4624}}"#,
+                    "\n"
+                ))
+            );
+        }
+        assert!(requests.try_recv().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_splunk_hec_error() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let mut requests = serve(
+            listener,
+            vec![(
+                StatusCode::FORBIDDEN,
+                r#"{"text":"Invalid token","code":4}"#,
+            )],
+        );
+
+        // Other failures are not retried
+        let output = OutputSplunkHec::new(&config(&format!(
+            "http://127.0.0.1:{}/services/collector/event",
+            port
+        ))?)?;
+        let err = output
+            .write(metadata(), Arc::new(vec![Arc::new(b"a".to_vec())]))
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid token (code 4)"));
+        assert_eq!(
+            requests.recv().await.unwrap().1,
+            Bytes::from(
+                "{\"index\":\"wineventlog\",\"sourcetype\":\"XmlWinEventLog\",\"source\":\"openwec:WIN10$@WINDOMAIN.LOCAL\",\"event\":\"a\"}\n"
+            )
+        );
+        assert!(requests.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    io::{BufWriter, Write},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use common::subscription::StdStreamConfiguration;
+use log::debug;
+
+use crate::{event::EventMetadata, output::OutputDriver};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StdStream {
+    Stdout,
+    Stderr,
+}
+
+/// Writes each event followed by a line feed. Unless `flush_each` is set,
+/// the writer is only flushed once all the events have been written.
+fn write_events<W: Write>(writer: W, events: &[Arc<Vec<u8>>], flush_each: bool) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    for event in events {
+        writer.write_all(event)?;
+        writer.write_all(b"\n")?;
+        if flush_each {
+            writer.flush()?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Prints events on the standard output or error of openwecd, one per line.
+/// The stream is locked while a batch is written so that events of
+/// concurrent outputs are never interleaved.
+pub struct OutputStdStream {
+    stream: StdStream,
+    config: StdStreamConfiguration,
+}
+
+impl OutputStdStream {
+    pub fn new(stream: StdStream, config: &StdStreamConfiguration) -> Self {
+        debug!("Initialize {:?} output with config {:?}", stream, config);
+        OutputStdStream {
+            stream,
+            config: config.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputStdStream {
+    async fn write(
+        &self,
+        _metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let stream = self.stream;
+        let flush_each = self.config.flush_each();
+        // Writing may block if the consumer of the stream is slow
+        tokio::task::spawn_blocking(move || match stream {
+            StdStream::Stdout => write_events(std::io::stdout().lock(), &events, flush_each),
+            StdStream::Stderr => write_events(std::io::stderr().lock(), &events, flush_each),
+        })
+        .await?
+        .with_context(|| format!("Failed to write events to {:?}", stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    const EVENT_4624: &str = r#"{"System":{"EventID":4624,"Computer":"win10.windomain.local"},"EventData":{"TargetUserName":"vagrant"}}"#;
+    const EVENT_4625: &str = r#"{"System":{"EventID":4625}}"#;
+
+    /// Records the data received between calls to `flush`
+    #[derive(Default)]
+    struct Recorder {
+        pending: Vec<u8>,
+        flushed: Vec<String>,
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if !self.pending.is_empty() {
+                let pending = std::mem::take(&mut self.pending);
+                self.flushed.push(String::from_utf8(pending).unwrap());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_events() -> Result<()> {
+        let events = vec![
+            Arc::new(EVENT_4624.as_bytes().to_vec()),
+            Arc::new(EVENT_4625.as_bytes().to_vec()),
+        ];
+
+        let mut recorder = Recorder::default();
+        write_events(&mut recorder, &events, false)?;
+        assert_eq!(
+            recorder.flushed,
+            vec![format!("{}\n{}\n", EVENT_4624, EVENT_4625)]
+        );
+
+        let mut recorder = Recorder::default();
+        write_events(&mut recorder, &events, true)?;
+        assert_eq!(
+            recorder.flushed,
+            vec![format!("{}\n", EVENT_4624), format!("{}\n", EVENT_4625)]
+        );
+        Ok(())
+    }
+}
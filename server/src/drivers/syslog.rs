@@ -0,0 +1,310 @@
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::SecondsFormat;
+use common::subscription::{
+    PayloadCompression, SyslogConfiguration, SyslogTransport, DEFAULT_RFC5424_ENTERPRISE_NUMBER,
+};
+use log::{debug, warn};
+use tokio::{
+    net::{lookup_host, UdpSocket},
+    sync::OnceCell,
+};
+
+use crate::{
+    drivers::tcp::OutputTcp,
+    event::EventMetadata,
+    formats::rfc5424::{escape, header_field},
+    output::OutputDriver,
+};
+
+// Messages are sent with the "informational" severity, the level of events
+// being unknown to drivers
+const SEVERITY: u8 = 6;
+
+// Identifies the structured data element describing the source of events
+const SD_ID: &str = "openwec";
+
+// Largest payload of a UDP datagram sent over IPv4
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Sends events wrapped in RFC 5424 messages to a syslog server. Messages
+/// are sent in UDP datagrams (RFC 5426), or framed using octet counting
+/// over TCP (RFC 6587) or TLS (RFC 5425).
+pub struct OutputSyslog {
+    config: SyslogConfiguration,
+    // Only set for the tcp and tls transports
+    tcp: Option<OutputTcp>,
+    // Resolved and bound on the first write of the udp transport, so that
+    // subscriptions can be loaded while the server can not be resolved
+    udp: OnceCell<(UdpSocket, SocketAddr)>,
+}
+
+impl OutputSyslog {
+    pub fn new(config: &SyslogConfiguration) -> Result<Self> {
+        debug!("Initialize Syslog output with config {:?}", config);
+        let tcp = match config.transport() {
+            SyslogTransport::Udp => None,
+            SyslogTransport::Tcp | SyslogTransport::Tls => Some(OutputTcp::new(
+                &config.tcp_configuration()?,
+                PayloadCompression::None,
+            )?),
+        };
+        Ok(OutputSyslog {
+            config: config.clone(),
+            tcp,
+            udp: OnceCell::new(),
+        })
+    }
+
+    /// Everything that precedes MSG, which is the same for all the events
+    /// of a batch. The source of events is described by a structured data
+    /// element.
+    fn header(&self, metadata: &EventMetadata) -> String {
+        let ip = metadata.addr().ip().to_string();
+        format!(
+            "<{}>1 {} {} {} - - [{}@{} principal=\"{}\" ip=\"{}\"] ",
+            self.config.facility().code() * 8 + SEVERITY,
+            metadata
+                .time_received()
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            header_field(Some(&ip), 255),
+            self.config.app_name(),
+            SD_ID,
+            DEFAULT_RFC5424_ENTERPRISE_NUMBER,
+            escape(metadata.principal()),
+            ip
+        )
+    }
+
+    async fn bind(&self) -> Result<(UdpSocket, SocketAddr)> {
+        let addr = lookup_host((self.config.host(), self.config.port()))
+            .await
+            .with_context(|| format!("Failed to resolve {}", self.config.host()))?
+            .next()
+            .ok_or_else(|| anyhow!("Failed to resolve {}", self.config.host()))?;
+        let local: SocketAddr = if addr.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(local)
+            .await
+            .context("Failed to create UDP socket")?;
+        debug!("Syslog datagrams are sent to {}", addr);
+        Ok((socket, addr))
+    }
+
+    async fn send_datagrams(&self, header: &str, events: &[Arc<Vec<u8>>]) -> Result<()> {
+        let (socket, addr) = self.udp.get_or_try_init(|| self.bind()).await?;
+
+        // MSG is truncated so that the message fits in a datagram
+        let max_msg_size = MAX_DATAGRAM_SIZE.saturating_sub(header.len());
+        let mut datagram = Vec::with_capacity(MAX_DATAGRAM_SIZE);
+        for event in events {
+            let msg = truncate_msg(event, max_msg_size);
+            if msg.len() < event.len() {
+                warn!(
+                    "Syslog message of {} bytes does not fit in a UDP datagram, its MSG part is truncated to {} bytes",
+                    header.len() + event.len(),
+                    msg.len()
+                );
+            }
+            datagram.clear();
+            datagram.extend_from_slice(header.as_bytes());
+            datagram.extend_from_slice(msg);
+            socket.send_to(&datagram, *addr).await.with_context(|| {
+                format!(
+                    "Failed to send UDP datagram to {}:{}",
+                    self.config.host(),
+                    self.config.port()
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Truncates `msg` to at most `max` bytes without splitting a UTF-8
+/// character
+fn truncate_msg(msg: &[u8], max: usize) -> &[u8] {
+    if msg.len() <= max {
+        return msg;
+    }
+    let mut end = max;
+    // Continuation bytes of UTF-8 characters are 0b10xxxxxx
+    while end > 0 && msg[end] & 0xC0 == 0x80 {
+        end -= 1;
+    }
+    &msg[..end]
+}
+
+#[async_trait]
+impl OutputDriver for OutputSyslog {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let header = self.header(&metadata);
+        let Some(tcp) = &self.tcp else {
+            return self.send_datagrams(&header, &events).await;
+        };
+
+        let messages: Vec<Arc<Vec<u8>>> = events
+            .iter()
+            .map(|event| {
+                let mut message = Vec::with_capacity(header.len() + event.len());
+                message.extend_from_slice(header.as_bytes());
+                message.extend_from_slice(event);
+                Arc::new(message)
+            })
+            .collect();
+        tcp.write(metadata, Arc::new(messages)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::{DateTime, Utc};
+    use common::{
+        settings,
+        subscription::{SubscriptionData, SyslogFacility},
+    };
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        Arc::new(metadata)
+    }
+
+    fn config(port: u16, transport: SyslogTransport) -> Result<SyslogConfiguration> {
+        SyslogConfiguration::new(
+            "127.0.0.1".to_string(),
+            port,
+            transport,
+            SyslogFacility::Local4,
+            "openwec".to_string(),
+            Vec::new(),
+            None,
+            None,
+        )
+    }
+
+    const HEADER: &str = "<166>1 2022-12-14T16:07:03.331000Z 192.168.58.100 openwec - - [openwec@32473 principal=\"WIN10$@WINDOMAIN.LOCAL\" ip=\"192.168.58.100\"] ";
+
+    #[tokio::test]
+    async fn test_syslog_udp() -> Result<()> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let port = socket.local_addr()?.port();
+        let output = OutputSyslog::new(&config(port, SyslogTransport::Udp)?)?;
+
+        // The second event does not fit in a datagram, and its MSG part can
+        // not be truncated in the middle of a character
+        let large = format!("a{}", "é".repeat(MAX_DATAGRAM_SIZE / 2));
+        let events = Arc::new(vec![
+            Arc::new(b"{\"EventID\":4624}".to_vec()),
+            Arc::new(large.as_bytes().to_vec()),
+        ]);
+        output.write(metadata(), events).await?;
+
+        let mut buf = vec![0; 2 * MAX_DATAGRAM_SIZE];
+        let len = socket.recv(&mut buf).await?;
+        assert_eq!(
+            std::str::from_utf8(&buf[..len])?,
+            format!("{}{{\"EventID\":4624}}", HEADER)
+        );
+
+        let len = socket.recv(&mut buf).await?;
+        assert_eq!(len, MAX_DATAGRAM_SIZE - 1);
+        assert!(std::str::from_utf8(&buf[..len])?.starts_with(HEADER));
+
+        // The socket is reused by the following writes
+        let local = output.udp.get().unwrap().0.local_addr()?;
+        let events = Arc::new(vec![Arc::new(b"{\"EventID\":4625}".to_vec())]);
+        output.write(metadata(), events).await?;
+        let len = socket.recv(&mut buf).await?;
+        assert_eq!(
+            std::str::from_utf8(&buf[..len])?,
+            format!("{}{{\"EventID\":4625}}", HEADER)
+        );
+        assert_eq!(output.udp.get().unwrap().0.local_addr()?, local);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_syslog_tcp() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let output = OutputSyslog::new(&config(port, SyslogTransport::Tcp)?)?;
+
+        let events = Arc::new(vec![
+            Arc::new(b"{\"EventID\":4624}".to_vec()),
+            Arc::new("é\nline".as_bytes().to_vec()),
+        ]);
+        output.write(metadata(), events.clone()).await?;
+
+        let (mut stream, _) = listener.accept().await?;
+        // Closes the connection
+        drop(output);
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).await?;
+
+        // Each message is preceded by its length in bytes and a space
+        let mut expected = Vec::new();
+        for event in events.iter() {
+            let message = [HEADER.as_bytes(), event].concat();
+            expected.extend_from_slice(format!("{} ", message.len()).as_bytes());
+            expected.extend_from_slice(&message);
+        }
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_syslog_header() -> Result<()> {
+        let mut config = config(514, SyslogTransport::Udp)?;
+        let output = OutputSyslog::new(&config)?;
+        assert_eq!(output.header(&metadata()), HEADER);
+
+        config = SyslogConfiguration::new(
+            "127.0.0.1".to_string(),
+            514,
+            SyslogTransport::Udp,
+            SyslogFacility::Kern,
+            "wec".to_string(),
+            Vec::new(),
+            None,
+            None,
+        )?;
+        let output = OutputSyslog::new(&config)?;
+        assert!(output.header(&metadata()).starts_with("<6>1 "));
+        assert!(output.header(&metadata()).contains(" wec - - "));
+        Ok(())
+    }
+}
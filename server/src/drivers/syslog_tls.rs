@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::subscription::{PayloadCompression, SyslogTlsConfiguration};
+use log::debug;
+
+use crate::{drivers::tcp::OutputTcp, event::EventMetadata, output::OutputDriver};
+
+/// Sends events to a syslog relay over TLS (RFC 5425). The connection is
+/// handled like a Tcp output with TLS enabled, a client certificate and
+/// octet counting framing, which RFC 5425 mandates.
+pub struct OutputSyslogTls {
+    tcp: OutputTcp,
+}
+
+impl OutputSyslogTls {
+    pub fn new(config: &SyslogTlsConfiguration) -> Result<Self> {
+        debug!("Initialize SyslogTls output with config {:?}", config);
+        Ok(OutputSyslogTls {
+            tcp: OutputTcp::new(&config.tcp_configuration()?, PayloadCompression::None)?,
+        })
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputSyslogTls {
+    async fn write(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        self.tcp.write(metadata, events).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+    use tokio_rustls::{
+        rustls::{server::WebPkiClientVerifier, RootCertStore, ServerConfig},
+        TlsAcceptor,
+    };
+
+    use super::*;
+    use crate::{
+        output::OutputDriversContext,
+        subscription::Subscription,
+        tls::{load_certs, load_priv_key},
+    };
+
+    fn cert_path(name: &str) -> String {
+        let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        path.pop();
+        path.push("tests/certs/syslog");
+        path.push(name);
+        path.display().to_string()
+    }
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    /// A syslog receiver which requires clients to present a certificate
+    /// signed by the test CA
+    fn acceptor() -> Result<TlsAcceptor> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&cert_path("ca.pem"))? {
+            roots.add(cert)?;
+        }
+        let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build()?;
+        let config = ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                load_certs(&cert_path("server.pem"))?,
+                load_priv_key(&cert_path("server.key"))?,
+            )?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    #[tokio::test]
+    async fn test_syslog_tls() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let acceptor = acceptor()?;
+
+        let config = SyslogTlsConfiguration::new(
+            "localhost".to_string(),
+            port,
+            vec![cert_path("ca.pem")],
+            cert_path("client.pem"),
+            cert_path("client.key"),
+        )?;
+        let output = OutputSyslogTls::new(&config)?;
+
+        let events = Arc::new(vec![
+            Arc::new(
+                "<14>1 2024-01-01T00:00:00Z win10 - - - - {\"EventID\":4624}"
+                    .as_bytes()
+                    .to_vec(),
+            ),
+            Arc::new(
+                "<14>1 2024-01-01T00:00:01Z win10 - - - - é\nline"
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        ]);
+        // Each message is preceded by its length in bytes and a space
+        let mut expected = Vec::new();
+        for event in events.iter() {
+            expected.extend_from_slice(format!("{} ", event.len()).as_bytes());
+            expected.extend_from_slice(event);
+        }
+
+        let len = expected.len();
+        let receiver = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut stream = acceptor.accept(stream).await?;
+            let client_certs = stream.get_ref().1.peer_certificates().map(|c| c.to_vec());
+            let mut data = vec![0; len];
+            stream.read_exact(&mut data).await?;
+            Ok::<_, anyhow::Error>((client_certs, data))
+        });
+
+        output.write(metadata(), events.clone()).await?;
+        let (client_certs, data) = receiver.await??;
+
+        // The client authenticated itself with its certificate
+        assert_eq!(client_certs, Some(load_certs(&cert_path("client.pem"))?));
+        assert_eq!(data, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_syslog_tls_configuration() -> Result<()> {
+        let config = SyslogTlsConfiguration::new(
+            "syslog.windomain.local".to_string(),
+            6514,
+            vec![cert_path("ca.pem")],
+            cert_path("client.pem"),
+            cert_path("client.key"),
+        )?;
+        let tcp = config.tcp_configuration()?;
+        assert!(tcp.tls_enabled());
+        assert_eq!(
+            tcp.framing(),
+            common::subscription::TcpFraming::OctetCounting
+        );
+        assert_eq!(tcp.tls_certificate(), Some(&cert_path("client.pem")));
+
+        // The server certificate is verified using its hostname
+        assert!(SyslogTlsConfiguration::new(
+            "127.0.0.1".to_string(),
+            6514,
+            vec![cert_path("ca.pem")],
+            cert_path("client.pem"),
+            cert_path("client.key"),
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -1,30 +1,51 @@
-use std::{pin::Pin, sync::Arc};
+use std::{collections::VecDeque, future, pin::Pin, sync::Arc, time::Duration};
 
 use crate::{
+    compression::compress,
     event::EventMetadata,
+    monitoring::{
+        OUTPUT_TCP_BUFFER_DROPPED_EVENTS, OUTPUT_TCP_BUFFER_EVENTS_GAUGE, SUBSCRIPTION_NAME,
+        SUBSCRIPTION_UUID,
+    },
     output::OutputDriver,
-    tls::{load_certs, load_priv_key},
+    tls::{load_certs, load_priv_key, PinnedServerCertVerifier},
 };
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use common::subscription::TcpConfiguration;
+use common::subscription::{
+    PayloadCompression, TcpConfiguration, TcpFraming, DEFAULT_TCP_RECONNECT_INITIAL_MS,
+    DEFAULT_TCP_RECONNECT_MAX_MS,
+};
 use log::{debug, info, warn};
+use metrics::{counter, gauge};
+use socket2::{SockRef, TcpKeepalive};
 use tokio::{
-    io::AsyncWrite,
+    io::{AsyncRead, AsyncWrite},
     net::TcpStream,
     sync::{mpsc, oneshot},
+    time::{sleep_until, Instant},
 };
 
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_rustls::{
-    rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+    rustls::{
+        crypto::aws_lc_rs::default_provider, pki_types::ServerName, ClientConfig, RootCertStore,
+    },
     TlsConnector,
 };
 use tokio_util::sync::CancellationToken;
 
+trait AsyncStream: AsyncRead + AsyncWrite + std::marker::Send {}
+
+impl<T: AsyncRead + AsyncWrite + std::marker::Send> AsyncStream for T {}
+
+type Stream = Pin<Box<dyn AsyncStream>>;
+
 #[derive(Debug)]
 pub struct WriteTCPMessage {
-    content: String,
+    metadata: Arc<EventMetadata>,
+    // Framed events
+    events: Vec<Vec<u8>>,
     resp: oneshot::Sender<Result<()>>,
 }
 
@@ -37,9 +58,7 @@ fn send_response(sender: oneshot::Sender<Result<()>>, msg: Result<()>) {
     }
 }
 
-pub async fn connect(
-    config: &TcpConfiguration,
-) -> Result<Pin<Box<dyn AsyncWrite + std::marker::Send>>> {
+pub async fn connect(config: &TcpConfiguration) -> Result<Stream> {
     if config.tls_enabled() {
         let mut certificate_authorities = Vec::new();
         for certificate_authority_file in config.tls_certificate_authorities() {
@@ -48,7 +67,24 @@ pub async fn connect(
         let mut root_cert_store = RootCertStore::empty();
         root_cert_store.add_parsable_certificates(certificate_authorities.clone());
 
-        let tls_config_builder = ClientConfig::builder().with_root_certificates(root_cert_store);
+        let tls_config_builder = if config.tls_pinned_sha256().is_empty() {
+            ClientConfig::builder().with_root_certificates(root_cert_store)
+        } else {
+            let provider = Arc::new(default_provider());
+            // Server certificates are only verified against certificate
+            // authorities if some are configured
+            let roots = if config.tls_certificate_authorities().is_empty() {
+                None
+            } else {
+                Some(root_cert_store)
+            };
+            let verifier =
+                PinnedServerCertVerifier::new(roots, config.tls_pinned_sha256(), provider.clone())?;
+            ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()?
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+        };
 
         let tls_config = if let Some(tls_certificate_file) = config.tls_certificate() {
             let tls_certificate = load_certs(tls_certificate_file)?;
@@ -64,13 +100,378 @@ pub async fn connect(
         let stream = TcpStream::connect((config.host(), config.port()))
             .await
             .context("Failed to establish TCP connection")?;
+        set_socket_options(&stream, config)?;
         Ok(Box::pin(connector.connect(dnsname, stream).await?))
     } else {
-        Ok(Box::pin(
-            TcpStream::connect((config.host(), config.port()))
-                .await
-                .context("Failed to establish TCP connection")?,
-        ))
+        let stream = TcpStream::connect((config.host(), config.port()))
+            .await
+            .context("Failed to establish TCP connection")?;
+        set_socket_options(&stream, config)?;
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Applies `tcp_keepalive_secs` and `tcp_nodelay` to a connected socket
+fn set_socket_options(stream: &TcpStream, config: &TcpConfiguration) -> Result<()> {
+    if config.tcp_nodelay() {
+        stream
+            .set_nodelay(true)
+            .context("Failed to set TCP_NODELAY")?;
+    }
+    if let Some(secs) = config.tcp_keepalive_secs() {
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(secs))
+            .with_interval(Duration::from_secs(secs));
+        SockRef::from(stream)
+            .set_tcp_keepalive(&keepalive)
+            .context("Failed to enable TCP keepalive")?;
+    }
+    Ok(())
+}
+
+/// Resolves when the connection has been closed by the peer or detected as
+/// dead, for example by TCP keepalive probes. The peer is not expected to
+/// send anything, so received data is discarded. Never resolves if there is
+/// no connection.
+async fn closed(stream: &mut Option<Stream>) -> Result<()> {
+    let Some(stream) = stream.as_mut() else {
+        return future::pending().await;
+    };
+    let mut buf = [0u8; 1024];
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) => return Ok(()),
+            Ok(_) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Delay before the next connection attempt after `failures` consecutive
+/// failures: the initial delay is doubled after each failure, up to the
+/// maximum delay.
+pub fn reconnect_delay(initial_ms: u64, max_ms: u64, failures: u32) -> Duration {
+    let factor = 1u64
+        .checked_shl(failures.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    Duration::from_millis(initial_ms.saturating_mul(factor).min(max_ms))
+}
+
+/// Events kept in memory while the connection is down, in the order in
+/// which they were written. The oldest events are dropped when it is full.
+struct Buffer {
+    events: VecDeque<Vec<u8>>,
+    max_events: usize,
+    // Number of events dropped since the output was created
+    dropped: u64,
+}
+
+impl Buffer {
+    fn new(max_events: usize) -> Self {
+        Buffer {
+            events: VecDeque::new(),
+            max_events,
+            dropped: 0,
+        }
+    }
+
+    /// Appends events, dropping the oldest ones if there is not enough
+    /// room. Returns the number of dropped events.
+    fn extend(&mut self, events: Vec<Vec<u8>>) -> u64 {
+        let mut dropped = 0;
+        for event in events {
+            if self.events.len() >= self.max_events {
+                self.events.pop_front();
+                dropped += 1;
+            }
+            self.events.push_back(event);
+        }
+        self.dropped += dropped;
+        dropped
+    }
+
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Buffered events, from the oldest to the newest
+    fn content(&self) -> Vec<u8> {
+        self.events
+            .iter()
+            .flat_map(|event| event.iter().copied())
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// Connection handled by the task of a TCP output
+struct Connection {
+    config: TcpConfiguration,
+    stream: Option<Stream>,
+    // Number of consecutive connection or write failures
+    failures: u32,
+    // No connection is attempted before this instant
+    next_attempt: Option<Instant>,
+    // Only set if buffer_max_events is configured
+    buffer: Option<Buffer>,
+    // Metadata of the last written events, used to label the buffer gauge
+    metadata: Option<Arc<EventMetadata>>,
+}
+
+impl Connection {
+    fn new(config: TcpConfiguration) -> Self {
+        let buffer = config
+            .buffer_max_events()
+            .map(|max| Buffer::new(usize::try_from(max).unwrap_or(usize::MAX)));
+        Connection {
+            config,
+            stream: None,
+            failures: 0,
+            next_attempt: None,
+            buffer,
+            metadata: None,
+        }
+    }
+
+    /// Closes the connection and delays the next connection attempt
+    fn fail(&mut self) -> Duration {
+        self.stream = None;
+        self.failures = self.failures.saturating_add(1);
+        let delay = reconnect_delay(
+            self.config
+                .reconnect_initial_ms()
+                .unwrap_or(DEFAULT_TCP_RECONNECT_INITIAL_MS),
+            self.config
+                .reconnect_max_ms()
+                .unwrap_or(DEFAULT_TCP_RECONNECT_MAX_MS),
+            self.failures,
+        );
+        self.next_attempt = Some(Instant::now() + delay);
+        delay
+    }
+
+    /// Whether the connection is down and must not be established again yet
+    fn waiting(&self) -> bool {
+        self.stream.is_none()
+            && self
+                .next_attempt
+                .is_some_and(|next_attempt| Instant::now() < next_attempt)
+    }
+
+    /// Instant at which buffered events must be sent again, if the
+    /// connection is down
+    fn retry_at(&self) -> Option<Instant> {
+        match &self.buffer {
+            Some(buffer) if !buffer.is_empty() && self.stream.is_none() => {
+                Some(self.next_attempt.unwrap_or_else(Instant::now))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reports the number of buffered events
+    fn update_buffer_gauge(&self) {
+        if let (Some(buffer), Some(metadata)) = (&self.buffer, &self.metadata) {
+            gauge!(OUTPUT_TCP_BUFFER_EVENTS_GAUGE,
+                SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+                SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned())
+            .set(buffer.len() as f64);
+        }
+    }
+
+    async fn send(&mut self, content: &[u8]) -> Result<()> {
+        if self.waiting() {
+            bail!(
+                "Waiting before reconnecting to {}:{}",
+                self.config.host(),
+                self.config.port()
+            );
+        }
+
+        // Establish TCP connection if not already done
+        if self.stream.is_none() {
+            match connect(&self.config).await {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                }
+                Err(e) => {
+                    let delay = self.fail();
+                    warn!(
+                        "Failed to connect to {}:{}: {}. Next attempt in {}ms",
+                        self.config.host(),
+                        self.config.port(),
+                        e,
+                        delay.as_millis()
+                    );
+                    bail!(
+                        "Failed to connect to {}:{}: {}",
+                        self.config.host(),
+                        self.config.port(),
+                        e
+                    );
+                }
+            };
+        }
+        // This should never fail
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => bail!(
+                "TCP stream of {}:{} is unset!",
+                self.config.host(),
+                self.config.port()
+            ),
+        };
+
+        // Write data to stream. Flushing makes sure that data buffered by the
+        // TLS session has been sent as well.
+        if let Err(e) = stream.write_all(content).await {
+            self.fail();
+            bail!(
+                "Failed to write in TCP connection ({}:{}): {}",
+                self.config.host(),
+                self.config.port(),
+                e
+            );
+        }
+        if let Err(e) = stream.flush().await {
+            self.fail();
+            bail!(
+                "Failed to flush TCP connection ({}:{}): {}",
+                self.config.host(),
+                self.config.port(),
+                e
+            );
+        }
+
+        if self.failures > 0 {
+            info!(
+                "TCP connection to {}:{} has been restored",
+                self.config.host(),
+                self.config.port()
+            );
+            self.failures = 0;
+            self.next_attempt = None;
+        }
+        Ok(())
+    }
+
+    /// Sends buffered events in order. They are kept in the buffer if they
+    /// can not be sent.
+    async fn flush_buffer(&mut self) {
+        let content = match &self.buffer {
+            Some(buffer) if !buffer.is_empty() => buffer.content(),
+            _ => return,
+        };
+        if self.waiting() {
+            return;
+        }
+        let result = self.send(&content).await;
+        if let Some(buffer) = self.buffer.as_mut() {
+            match result {
+                Ok(()) => {
+                    debug!(
+                        "Sent {} buffered events to {}:{}",
+                        buffer.len(),
+                        self.config.host(),
+                        self.config.port()
+                    );
+                    buffer.clear();
+                }
+                Err(e) => warn!(
+                    "Failed to send {} buffered events, they are kept in memory: {}",
+                    buffer.len(),
+                    e
+                ),
+            }
+        }
+        self.update_buffer_gauge();
+    }
+
+    async fn write_message(&mut self, message: WriteTCPMessage) {
+        self.metadata = Some(message.metadata.clone());
+        let Some(buffer) = self.buffer.as_mut() else {
+            let result = self.send(&message.events.concat()).await;
+            send_response(message.resp, result);
+            return;
+        };
+
+        // Buffered events are considered written
+        let dropped = buffer.extend(message.events);
+        if dropped > 0 {
+            warn!(
+                "Buffer of TCP output {}:{} is full, {} oldest events have been dropped ({} since startup)",
+                self.config.host(),
+                self.config.port(),
+                dropped,
+                buffer.dropped
+            );
+            counter!(OUTPUT_TCP_BUFFER_DROPPED_EVENTS,
+                SUBSCRIPTION_NAME => message.metadata.subscription_qualified_name(),
+                SUBSCRIPTION_UUID => message.metadata.subscription_uuid().to_owned())
+            .increment(dropped);
+        }
+        self.update_buffer_gauge();
+        self.flush_buffer().await;
+        send_response(message.resp, Ok(()));
+    }
+
+    /// Forgets a connection which has been closed by the peer, so that the
+    /// next write establishes a new one instead of failing
+    fn disconnected(&mut self, result: Result<()>) {
+        self.stream = None;
+        match result {
+            Ok(()) => warn!(
+                "TCP connection to {}:{} has been closed by the peer",
+                self.config.host(),
+                self.config.port()
+            ),
+            Err(e) => warn!(
+                "TCP connection to {}:{} has been lost: {}",
+                self.config.host(),
+                self.config.port(),
+                e
+            ),
+        }
+    }
+
+    async fn close(mut self) {
+        // Last chance for buffered events
+        self.next_attempt = None;
+        self.flush_buffer().await;
+        if let Some(buffer) = &self.buffer {
+            if !buffer.is_empty() {
+                warn!(
+                    "{} buffered events could not be sent to {}:{} and are lost",
+                    buffer.len(),
+                    self.config.host(),
+                    self.config.port()
+                );
+            }
+        }
+        // Remaining buffered events are lost with the connection
+        if let Some(buffer) = self.buffer.as_mut() {
+            buffer.clear();
+        }
+        self.update_buffer_gauge();
+
+        if let Some(mut stream) = self.stream {
+            if let Err(e) = stream.shutdown().await {
+                warn!(
+                    "Failed to close TCP connection ({}:{}): {}",
+                    self.config.host(),
+                    self.config.port(),
+                    e
+                );
+            }
+        }
+        info!("Exiting TCP output task ({:?})", self.config);
     }
 }
 
@@ -79,58 +480,82 @@ pub async fn run(
     mut task_rx: mpsc::Receiver<WriteTCPMessage>,
     cancellation_token: CancellationToken,
 ) {
-    let mut stream_opt: Option<Pin<Box<dyn AsyncWrite + std::marker::Send>>> = None;
+    let mut connection = Connection::new(config);
 
     loop {
+        let retry_at = connection.retry_at();
         tokio::select! {
             Some(message) = task_rx.recv() => {
-                // Establish TCP connection if not already done
-                if stream_opt.is_none() {
-                    match connect(&config).await {
-                        Ok(stream) => {
-                            stream_opt = Some(stream);
-                        },
-                        Err(e) => {
-                            warn!("Failed to connect to {}:{}: {}", config.host(), config.port(), e);
-                            send_response(message.resp, Err(anyhow!(format!("Failed to connect to {}:{}: {}", config.host(), config.port(), e))));
-                            continue;
-                        }
-                    };
-                }
-                // This should never fail
-                let stream = match stream_opt.as_mut() {
-                    Some(stream) => stream,
-                    None => {
-                        warn!("TCP stream is unset !");
-                        send_response(message.resp, Err(anyhow!(format!("TCP stream of {}:{} is unset!", config.host(), config.port()))));
-                        continue;
-                    }
-                };
-
-                // Write data to stream
-                if let Err(e) = stream.write_all(message.content.as_bytes()).await {
-                    stream_opt = None;
-                    send_response(message.resp, Err(anyhow!(format!("Failed to write in TCP connection ({}:{}): {}", config.host(), config.port(), e))));
-                    continue;
-                }
-
-                send_response(message.resp, Ok(()));
+                connection.write_message(message).await;
+            },
+            _ = sleep_until(retry_at.unwrap_or_else(Instant::now)), if retry_at.is_some() => {
+                connection.flush_buffer().await;
+            },
+            result = closed(&mut connection.stream) => {
+                connection.disconnected(result);
             },
             _ = cancellation_token.cancelled() => {
+                // The output has been removed (or disabled): messages which
+                // are already queued are written before closing the
+                // connection
+                task_rx.close();
+                while let Some(message) = task_rx.recv().await {
+                    connection.write_message(message).await;
+                }
                 break;
             }
         };
     }
-    info!("Exiting TCP output task ({:?})", config);
+    connection.close().await;
+}
+
+/// Appends an event to `content`, delimited according to `framing`
+pub fn frame(framing: TcpFraming, event: &[u8], content: &mut Vec<u8>) -> Result<()> {
+    match framing {
+        TcpFraming::Newline => {
+            content.extend_from_slice(event);
+            content.push(b'\n');
+        }
+        TcpFraming::LengthPrefixed => {
+            let len = u32::try_from(event.len())
+                .context("Event is too large to be prefixed by its length")?;
+            content.extend_from_slice(&len.to_be_bytes());
+            content.extend_from_slice(event);
+        }
+        TcpFraming::OctetCounting => {
+            content.extend_from_slice(format!("{} ", event.len()).as_bytes());
+            content.extend_from_slice(event);
+        }
+    }
+    Ok(())
+}
+
+/// Appends a compressed event to `content`. The compressed payload is
+/// preceded by its length (including the algorithm byte) as a 4-byte
+/// big-endian integer and by the byte identifying the algorithm.
+fn frame_compressed(
+    compression: PayloadCompression,
+    event: &[u8],
+    content: &mut Vec<u8>,
+) -> Result<()> {
+    let payload = compress(compression, event)?;
+    let len = u32::try_from(payload.len() + 1)
+        .context("Event is too large to be prefixed by its length")?;
+    content.extend_from_slice(&len.to_be_bytes());
+    content.push(compression.id());
+    content.extend_from_slice(&payload);
+    Ok(())
 }
 
 pub struct OutputTcp {
+    framing: TcpFraming,
+    compression: PayloadCompression,
     task_tx: mpsc::Sender<WriteTCPMessage>,
     task_ct: CancellationToken,
 }
 
 impl OutputTcp {
-    pub fn new(config: &TcpConfiguration) -> Result<Self> {
+    pub fn new(config: &TcpConfiguration, compression: PayloadCompression) -> Result<Self> {
         debug!("Initialize TCP output with config {:?}", config,);
 
         // Create a communication channel with the task responsible for file management
@@ -146,7 +571,12 @@ impl OutputTcp {
         // Launch the task responsible for handling the TCP connection
         tokio::spawn(async move { run(config_cloned, task_rx, cloned_task_ct).await });
 
-        Ok(OutputTcp { task_tx, task_ct })
+        Ok(OutputTcp {
+            framing: config.framing(),
+            compression,
+            task_tx,
+            task_ct,
+        })
     }
 }
 
@@ -154,20 +584,29 @@ impl OutputTcp {
 impl OutputDriver for OutputTcp {
     async fn write(
         &self,
-        _metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
     ) -> Result<()> {
-        // Build the "content" string to write
-        let mut content = String::new();
+        // Frame each event, so that they can be buffered separately
+        let mut framed = Vec::with_capacity(events.len());
         for event in events.iter() {
-            content.push_str(event);
-            content.push('\n');
+            let mut content = Vec::new();
+            if self.compression == PayloadCompression::None {
+                frame(self.framing, event, &mut content)?;
+            } else {
+                frame_compressed(self.compression, event, &mut content)?;
+            }
+            framed.push(content);
         }
 
         // Create a oneshot channel to retrieve the result of the operation
         let (tx, rx) = oneshot::channel();
         self.task_tx
-            .send(WriteTCPMessage { content, resp: tx })
+            .send(WriteTCPMessage {
+                metadata,
+                events: framed,
+                resp: tx,
+            })
             .await?;
 
         // Wait for the result
@@ -182,3 +621,294 @@ impl Drop for OutputTcp {
         self.task_ct.cancel();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        compression::decompress, output::OutputDriversContext, subscription::Subscription,
+    };
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    /// Splits a stream of framed events
+    fn unframe(framing: TcpFraming, mut data: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        while !data.is_empty() {
+            let (event, rest) = match framing {
+                TcpFraming::Newline => {
+                    let end = data.iter().position(|c| *c == b'\n').unwrap();
+                    (&data[..end], &data[end + 1..])
+                }
+                TcpFraming::LengthPrefixed => {
+                    let len = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+                    (&data[4..4 + len], &data[4 + len..])
+                }
+                TcpFraming::OctetCounting => {
+                    let space = data.iter().position(|c| *c == b' ').unwrap();
+                    let len: usize = std::str::from_utf8(&data[..space])
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    (&data[space + 1..space + 1 + len], &data[space + 1 + len..])
+                }
+            };
+            events.push(String::from_utf8(event.to_vec()).unwrap());
+            data = rest;
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_tcp_framing() -> Result<()> {
+        let events = Arc::new(vec![
+            Arc::new(b"{\"EventID\":4624}".to_vec()),
+            // Events may contain line feeds (Raw or Csv formats)
+            Arc::new(b"<Event>\n<System/>\n</Event>".to_vec()),
+            Arc::new("é 12 ".as_bytes().to_vec()),
+        ]);
+
+        for framing in [
+            TcpFraming::Newline,
+            TcpFraming::LengthPrefixed,
+            TcpFraming::OctetCounting,
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let port = listener.local_addr()?.port();
+
+            let mut config = TcpConfiguration::new(
+                "127.0.0.1".to_string(),
+                port,
+                false,
+                Vec::new(),
+                None,
+                None,
+            )?;
+            config.set_framing(framing);
+            let output = OutputTcp::new(&config, PayloadCompression::None)?;
+
+            output.write(metadata(), events.clone()).await?;
+            output.write(metadata(), events.clone()).await?;
+
+            let (mut stream, _) = listener.accept().await?;
+            // Closes the connection
+            drop(output);
+            let mut data = Vec::new();
+            stream.read_to_end(&mut data).await?;
+
+            let received = unframe(framing, &data);
+            let expected: Vec<String> = events
+                .iter()
+                .chain(events.iter())
+                .map(|event| String::from_utf8(event.to_vec()).unwrap())
+                .collect();
+            if framing == TcpFraming::Newline {
+                // Line feeds inside events can not be told apart from delimiters
+                assert_eq!(received.len(), 10);
+                assert_eq!(received.join("\n"), expected.join("\n"));
+            } else {
+                assert_eq!(received, expected);
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tcp_payload_compression() -> Result<()> {
+        let events = Arc::new(vec![
+            Arc::new("{\"EventID\":4624}".repeat(10).into_bytes()),
+            Arc::new(b"<Event>\n<System/>\n</Event>".to_vec()),
+        ]);
+
+        for compression in [
+            PayloadCompression::Gzip,
+            PayloadCompression::Zstd,
+            PayloadCompression::Lz4,
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let port = listener.local_addr()?.port();
+
+            let mut config = TcpConfiguration::new(
+                "127.0.0.1".to_string(),
+                port,
+                false,
+                Vec::new(),
+                None,
+                None,
+            )?;
+            config.set_framing(TcpFraming::LengthPrefixed);
+            let output = OutputTcp::new(&config, compression)?;
+            output.write(metadata(), events.clone()).await?;
+
+            let (mut stream, _) = listener.accept().await?;
+            // Closes the connection
+            drop(output);
+            let mut data = Vec::new();
+            stream.read_to_end(&mut data).await?;
+
+            // A consumer splits frames using their length prefix, then reads
+            // the algorithm byte to decompress the payload
+            let mut received = Vec::new();
+            let mut data = data.as_slice();
+            while !data.is_empty() {
+                let len = u32::from_be_bytes(data[..4].try_into()?) as usize;
+                let frame = &data[4..4 + len];
+                assert_eq!(frame[0], compression.id());
+                let event = decompress(compression, &frame[1..])?;
+                received.push(String::from_utf8(event)?);
+                data = &data[4 + len..];
+            }
+            let expected: Vec<String> = events
+                .iter()
+                .map(|event| String::from_utf8(event.to_vec()).unwrap())
+                .collect();
+            assert_eq!(received, expected);
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_reconnect_delay() {
+        let delays: Vec<u64> = (1..=8)
+            .map(|failures| reconnect_delay(500, 30_000, failures).as_millis() as u64)
+            .collect();
+        assert_eq!(
+            delays,
+            vec![500, 1000, 2000, 4000, 8000, 16_000, 30_000, 30_000]
+        );
+        // The delay does not overflow after many failures
+        assert_eq!(
+            reconnect_delay(500, 30_000, u32::MAX),
+            Duration::from_millis(30_000)
+        );
+        assert_eq!(
+            reconnect_delay(u64::MAX, u64::MAX, 100),
+            Duration::from_millis(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_tcp_buffer_overflow() {
+        let mut buffer = Buffer::new(3);
+        assert_eq!(buffer.extend(vec![b"1".to_vec(), b"2".to_vec()]), 0);
+        // The oldest events are dropped
+        assert_eq!(
+            buffer.extend(vec![b"3".to_vec(), b"4".to_vec(), b"5".to_vec()]),
+            2
+        );
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.content(), b"345");
+        assert_eq!(buffer.extend(vec![b"6".to_vec()]), 1);
+        assert_eq!(buffer.content(), b"456");
+        assert_eq!(buffer.dropped, 3);
+        buffer.clear();
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_buffer_flushed_on_reconnect() -> Result<()> {
+        // Nothing listens to this port yet
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        drop(listener);
+
+        let mut config =
+            TcpConfiguration::new("127.0.0.1".to_string(), port, false, Vec::new(), None, None)?;
+        config.set_reconnect(Some(10), Some(20))?;
+        config.set_buffer_max_events(Some(3))?;
+        let output = OutputTcp::new(&config, PayloadCompression::None)?;
+
+        // Writes succeed while the connection is down
+        for i in 0..5 {
+            let events = Arc::new(vec![Arc::new(format!("event{}", i).into_bytes())]);
+            output.write(metadata(), events).await?;
+        }
+
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let (mut stream, _) = listener.accept().await?;
+        // Closes the connection
+        drop(output);
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).await?;
+
+        // Buffered events are sent in order, without the dropped ones
+        assert_eq!(
+            unframe(TcpFraming::Newline, &data),
+            vec!["event2", "event3", "event4"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tcp_socket_options() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+
+        let mut config =
+            TcpConfiguration::new("127.0.0.1".to_string(), port, false, Vec::new(), None, None)?;
+
+        // Options are left untouched by default
+        let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+        set_socket_options(&stream, &config)?;
+        assert!(!stream.nodelay()?);
+        assert!(!SockRef::from(&stream).keepalive()?);
+
+        config.set_tcp_keepalive_secs(Some(30))?;
+        config.set_tcp_nodelay(true);
+        let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+        set_socket_options(&stream, &config)?;
+        assert!(stream.nodelay()?);
+        let socket = SockRef::from(&stream);
+        assert!(socket.keepalive()?);
+        assert_eq!(socket.keepalive_time()?, Duration::from_secs(30));
+        assert_eq!(socket.keepalive_interval()?, Duration::from_secs(30));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connection_closed_by_peer() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+
+        let config =
+            TcpConfiguration::new("127.0.0.1".to_string(), port, false, Vec::new(), None, None)?;
+        let output = OutputTcp::new(&config, PayloadCompression::None)?;
+
+        let events = Arc::new(vec![Arc::new(b"event0".to_vec())]);
+        output.write(metadata(), events).await?;
+        let (stream, _) = listener.accept().await?;
+
+        // The peer closes the connection, which is noticed before the next
+        // write
+        drop(stream);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let events = Arc::new(vec![Arc::new(b"event1".to_vec())]);
+        output.write(metadata(), events).await?;
+        let (mut stream, _) = listener.accept().await?;
+        // Closes the connection
+        drop(output);
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).await?;
+
+        assert_eq!(unframe(TcpFraming::Newline, &data), vec!["event1"]);
+        Ok(())
+    }
+}
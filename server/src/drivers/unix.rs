@@ -18,7 +18,7 @@ use tokio_util::sync::CancellationToken;
 
 #[derive(Debug)]
 pub struct WriteUnixDatagramMessage {
-    events: Arc<Vec<Arc<String>>>,
+    events: Arc<Vec<Arc<Vec<u8>>>>,
     resp: oneshot::Sender<Result<()>>,
 }
 
@@ -73,7 +73,7 @@ pub async fn run(
                 };
 
                 for event in message.events.iter() {
-                    if let Err(e) = dgram.send(event.as_bytes()).await {
+                    if let Err(e) = dgram.send(event).await {
                         dgram_opt = None;
                         send_response(message.resp, Err(anyhow!(format!("Failed to write to UnixDatagram ({}): {}", path, e))));
                         continue 'mainloop;
@@ -117,7 +117,7 @@ impl OutputDriver for OutputUnixDatagram {
     async fn write(
         &self,
         _metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
     ) -> Result<()> {
         let (tx, rx) = oneshot::channel();
         self.task_tx
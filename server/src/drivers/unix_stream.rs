@@ -0,0 +1,331 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    drivers::tcp::{frame, reconnect_delay},
+    event::EventMetadata,
+    output::OutputDriver,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use common::subscription::{
+    TcpFraming, UnixStreamConfiguration, DEFAULT_TCP_RECONNECT_INITIAL_MS,
+    DEFAULT_TCP_RECONNECT_MAX_MS,
+};
+use log::{debug, info, warn};
+use tokio::{
+    io::AsyncWriteExt,
+    net::UnixStream,
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug)]
+pub struct WriteUnixStreamMessage {
+    content: Vec<u8>,
+    resp: oneshot::Sender<Result<()>>,
+}
+
+fn send_response(sender: oneshot::Sender<Result<()>>, msg: Result<()>) {
+    if let Err(e) = sender.send(msg) {
+        warn!(
+            "Failed to send UnixStream write result because the receiver dropped. Result was: {:?}",
+            e
+        );
+    }
+}
+
+/// Connection handled by the task of a UnixStream output
+struct Connection {
+    config: UnixStreamConfiguration,
+    stream: Option<UnixStream>,
+    // Number of consecutive connection or write failures
+    failures: u32,
+    // No connection is attempted before this instant
+    next_attempt: Option<Instant>,
+}
+
+impl Connection {
+    fn new(config: UnixStreamConfiguration) -> Self {
+        Connection {
+            config,
+            stream: None,
+            failures: 0,
+            next_attempt: None,
+        }
+    }
+
+    /// Closes the connection and delays the next connection attempt
+    fn fail(&mut self) -> Duration {
+        self.stream = None;
+        self.failures = self.failures.saturating_add(1);
+        let delay = reconnect_delay(
+            self.config
+                .reconnect_initial_ms()
+                .unwrap_or(DEFAULT_TCP_RECONNECT_INITIAL_MS),
+            self.config
+                .reconnect_max_ms()
+                .unwrap_or(DEFAULT_TCP_RECONNECT_MAX_MS),
+            self.failures,
+        );
+        self.next_attempt = Some(Instant::now() + delay);
+        delay
+    }
+
+    async fn send(&mut self, content: &[u8]) -> Result<()> {
+        if self.stream.is_none()
+            && self
+                .next_attempt
+                .is_some_and(|next_attempt| Instant::now() < next_attempt)
+        {
+            bail!("Waiting before reconnecting to {}", self.config.path());
+        }
+
+        // Connect to the socket if not already done
+        if self.stream.is_none() {
+            match UnixStream::connect(self.config.path()).await {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                }
+                Err(e) => {
+                    let delay = self.fail();
+                    warn!(
+                        "Failed to connect to {}: {}. Next attempt in {}ms",
+                        self.config.path(),
+                        e,
+                        delay.as_millis()
+                    );
+                    bail!("Failed to connect to {}: {}", self.config.path(), e);
+                }
+            };
+        }
+        // This should never fail
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => bail!("UnixStream of {} is unset!", self.config.path()),
+        };
+
+        if let Err(e) = stream.write_all(content).await {
+            self.fail();
+            bail!(
+                "Failed to write to UnixStream ({}): {}",
+                self.config.path(),
+                e
+            );
+        }
+
+        if self.failures > 0 {
+            info!("Connection to {} has been restored", self.config.path());
+            self.failures = 0;
+            self.next_attempt = None;
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(
+    config: UnixStreamConfiguration,
+    mut task_rx: mpsc::Receiver<WriteUnixStreamMessage>,
+    cancellation_token: CancellationToken,
+) {
+    let mut connection = Connection::new(config);
+
+    loop {
+        tokio::select! {
+            Some(message) = task_rx.recv() => {
+                let result = connection.send(&message.content).await;
+                send_response(message.resp, result);
+            },
+            _ = cancellation_token.cancelled() => {
+                // Messages which are already queued are written before
+                // closing the connection
+                task_rx.close();
+                while let Some(message) = task_rx.recv().await {
+                    let result = connection.send(&message.content).await;
+                    send_response(message.resp, result);
+                }
+                break;
+            }
+        };
+    }
+    if let Some(mut stream) = connection.stream {
+        if let Err(e) = stream.shutdown().await {
+            warn!(
+                "Failed to close UnixStream ({}): {}",
+                connection.config.path(),
+                e
+            );
+        }
+    }
+    info!(
+        "Exiting UnixStream output task ({})",
+        connection.config.path()
+    );
+}
+
+/// Writes events to a Unix domain socket of type `SOCK_STREAM`. Unlike
+/// datagrams, events are not limited by the size of the socket buffer.
+pub struct OutputUnixStream {
+    framing: TcpFraming,
+    task_tx: mpsc::Sender<WriteUnixStreamMessage>,
+    task_ct: CancellationToken,
+}
+
+impl OutputUnixStream {
+    pub fn new(config: &UnixStreamConfiguration) -> Result<Self> {
+        debug!("Initialize UnixStream output with config {:?}", config);
+
+        let (task_tx, task_rx) = mpsc::channel(32);
+
+        let task_ct = CancellationToken::new();
+        let cloned_task_ct = task_ct.clone();
+
+        let config_cloned = config.clone();
+
+        tokio::spawn(async move { run(config_cloned, task_rx, cloned_task_ct).await });
+
+        Ok(OutputUnixStream {
+            framing: config.framing(),
+            task_tx,
+            task_ct,
+        })
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputUnixStream {
+    async fn write(
+        &self,
+        _metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let mut content = Vec::new();
+        for event in events.iter() {
+            frame(self.framing, event, &mut content)?;
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.task_tx
+            .send(WriteUnixStreamMessage { content, resp: tx })
+            .await?;
+
+        rx.await??;
+
+        Ok(())
+    }
+}
+
+impl Drop for OutputUnixStream {
+    fn drop(&mut self) {
+        self.task_ct.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use tokio::{io::AsyncReadExt, net::UnixListener};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("openwec-test-{}.socket", Uuid::new_v4()))
+    }
+
+    /// Reads an event preceded by its length
+    async fn read_frame(stream: &mut UnixStream) -> Result<Vec<u8>> {
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).await?;
+        let mut event = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut event).await?;
+        Ok(event)
+    }
+
+    #[tokio::test]
+    async fn test_unix_stream_large_event() -> Result<()> {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path)?;
+
+        let mut config = UnixStreamConfiguration::new(path.display().to_string());
+        config.set_framing(TcpFraming::LengthPrefixed);
+        let output = OutputUnixStream::new(&config)?;
+
+        // Much larger than the default buffer of datagram sockets
+        let large: Vec<u8> = (0..512 * 1024).map(|i| (i % 251) as u8).collect();
+        let events = Arc::new(vec![
+            Arc::new(large.clone()),
+            Arc::new(b"{\"EventID\":4624}".to_vec()),
+        ]);
+        let write = tokio::spawn(async move {
+            let result = output.write(metadata(), events).await;
+            (output, result)
+        });
+
+        let (mut stream, _) = listener.accept().await?;
+        assert_eq!(read_frame(&mut stream).await?, large);
+        assert_eq!(read_frame(&mut stream).await?, b"{\"EventID\":4624}");
+
+        let (output, result) = write.await?;
+        result?;
+        // Closes the connection
+        drop(output);
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await?;
+        assert!(rest.is_empty());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unix_stream_reconnect() -> Result<()> {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path)?;
+
+        let mut config = UnixStreamConfiguration::new(path.display().to_string());
+        config.set_reconnect(Some(10), Some(10))?;
+        let output = OutputUnixStream::new(&config)?;
+        let event = |content: &str| Arc::new(vec![Arc::new(content.as_bytes().to_vec())]);
+
+        output.write(metadata(), event("first")).await?;
+        let (mut stream, _) = listener.accept().await?;
+        let mut buf = [0; 6];
+        stream.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"first\n");
+
+        // The peer goes away
+        drop(stream);
+        assert!(output.write(metadata(), event("lost")).await.is_err());
+
+        // The connection is established again once the delay has elapsed
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        output.write(metadata(), event("second")).await?;
+        let (mut stream, _) = listener.accept().await?;
+        drop(output);
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).await?;
+        assert_eq!(data, b"second\n");
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
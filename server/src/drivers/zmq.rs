@@ -0,0 +1,209 @@
+use std::{sync::Arc, thread};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use common::{
+    settings::QueueOverflowPolicy,
+    subscription::{ZmqConfiguration, ZmqSocketType},
+};
+use log::{debug, info, warn};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{event::EventMetadata, output::OutputDriver, queue};
+
+/// Time (in milliseconds) during which the messages still queued when the
+/// output is stopped are sent before being discarded
+const ZMQ_LINGER_MS: i32 = 5_000;
+
+#[derive(Debug)]
+struct WriteZmqMessage {
+    events: Arc<Vec<Arc<Vec<u8>>>>,
+    resp: oneshot::Sender<Result<()>>,
+}
+
+/// Sends each event as a message. When the queue of the socket is full (its
+/// high-water mark is reached), the write waits if queues block on
+/// overflow, and fails otherwise so that the batch is not acknowledged.
+fn send_events(
+    socket: &zmq::Socket,
+    endpoint: &str,
+    events: &[Arc<Vec<u8>>],
+    policy: QueueOverflowPolicy,
+) -> Result<()> {
+    for event in events {
+        match socket.send(event.as_slice(), zmq::DONTWAIT) {
+            Ok(()) => (),
+            Err(zmq::Error::EAGAIN) if policy == QueueOverflowPolicy::Block => socket
+                .send(event.as_slice(), 0)
+                .with_context(|| format!("Failed to send event to {}", endpoint))?,
+            Err(zmq::Error::EAGAIN) => bail!(
+                "High-water mark of the ZeroMQ socket connected to {} is reached",
+                endpoint
+            ),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to send event to {}", endpoint))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// ZeroMQ sockets can not be shared between threads: a dedicated thread
+/// owns the socket and sends the events of every write
+fn run(
+    socket: zmq::Socket,
+    endpoint: String,
+    policy: QueueOverflowPolicy,
+    mut task_rx: mpsc::Receiver<WriteZmqMessage>,
+) {
+    while let Some(message) = task_rx.blocking_recv() {
+        let result = send_events(&socket, &endpoint, &message.events, policy);
+        if let Err(e) = message.resp.send(result) {
+            warn!(
+                "Failed to send Zmq write result because the receiver dropped. Result was: {:?}",
+                e
+            );
+        }
+    }
+    info!("Exiting Zmq output thread ({})", endpoint);
+}
+
+pub struct OutputZmq {
+    // The thread exits once the output is dropped
+    task_tx: mpsc::Sender<WriteZmqMessage>,
+}
+
+impl OutputZmq {
+    pub fn new(config: &ZmqConfiguration) -> Result<Self> {
+        Self::with_policy(config, queue::overflow_policy())
+    }
+
+    fn with_policy(config: &ZmqConfiguration, policy: QueueOverflowPolicy) -> Result<Self> {
+        debug!("Initialize Zmq driver with config {:?}", config);
+        let context = zmq::Context::new();
+        let socket = context.socket(match config.socket_type() {
+            ZmqSocketType::Push => zmq::PUSH,
+            ZmqSocketType::Pub => zmq::PUB,
+        })?;
+        if let Some(hwm) = config.hwm() {
+            socket.set_sndhwm(i32::try_from(hwm)?)?;
+        }
+        socket.set_linger(ZMQ_LINGER_MS)?;
+        // Connecting does not wait for the peer: messages are queued (up to
+        // the high-water mark) until it is reachable
+        socket
+            .connect(config.endpoint())
+            .with_context(|| format!("Failed to connect to {}", config.endpoint()))?;
+
+        let (task_tx, task_rx) = mpsc::channel(32);
+        let endpoint = config.endpoint().to_string();
+        thread::Builder::new()
+            .name("zmq-output".to_string())
+            .spawn(move || run(socket, endpoint, policy, task_rx))
+            .context("Failed to start Zmq output thread")?;
+
+        Ok(OutputZmq { task_tx })
+    }
+}
+
+#[async_trait]
+impl OutputDriver for OutputZmq {
+    async fn write(
+        &self,
+        _metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+    ) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.task_tx
+            .send(WriteZmqMessage { events, resp: tx })
+            .await?;
+
+        rx.await??;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use anyhow::anyhow;
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{
+        event::EventData,
+        formats::json::JsonFormat,
+        output::{OutputDriversContext, OutputFormat},
+        subscription::Subscription,
+    };
+
+    const EVENT_4688: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='NewProcessName'>C:\Windows\System32\cmd.exe</Data></EventData></Event>"#;
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_zmq_loopback() -> Result<()> {
+        let context = zmq::Context::new();
+        let pull = context.socket(zmq::PULL)?;
+        pull.bind("tcp://127.0.0.1:*")?;
+        pull.set_rcvtimeo(5_000)?;
+        let endpoint = pull
+            .get_last_endpoint()?
+            .map_err(|_| anyhow!("Endpoint is not valid UTF-8"))?;
+
+        let metadata = metadata();
+        let event = JsonFormat
+            .format(
+                &metadata,
+                &EventData::new(Arc::new(EVENT_4688.to_string()), true),
+            )
+            .unwrap();
+        let output = OutputZmq::new(&ZmqConfiguration::new(endpoint, ZmqSocketType::Push, None)?)?;
+        output
+            .write(metadata, Arc::new(vec![event.clone(), event.clone()]))
+            .await?;
+
+        // Each event is received as a message with a single frame
+        for _ in 0..2 {
+            let frames = pull.recv_multipart(0)?;
+            assert_eq!(frames, vec![event.to_vec()]);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zmq_hwm() -> Result<()> {
+        // Nothing listens on this endpoint, so messages stay queued
+        let config = ZmqConfiguration::new(
+            "tcp://127.0.0.1:1".to_string(),
+            ZmqSocketType::Push,
+            Some(1),
+        )?;
+        let output = OutputZmq::with_policy(&config, QueueOverflowPolicy::DropNewest)?;
+        let events: Vec<Arc<Vec<u8>>> = (0..100)
+            .map(|i| Arc::new(format!("event {}", i).into_bytes()))
+            .collect();
+
+        let err = output
+            .write(metadata(), Arc::new(events))
+            .await
+            .expect_err("high-water mark must be reached");
+        assert!(format!("{:#}", err).contains("High-water mark"));
+        Ok(())
+    }
+}
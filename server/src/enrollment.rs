@@ -0,0 +1,324 @@
+use std::{collections::HashSet, net::SocketAddr, sync::Mutex};
+
+use log::{debug, info, warn};
+use metrics::counter;
+
+use crate::{
+    monitoring::{ENROLLMENT_FAILURES_COUNTER, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID},
+    soap::{SubscriptionEnd, STATUS_DELIVERY_FAILURE, STATUS_SOURCE_SHUTTING_DOWN},
+    subscription::Subscription,
+};
+
+// Options of the manifest as named in the reasons given by clients. Longer
+// names come first so that "DataLocale" is not reported as "Locale".
+const MANIFEST_OPTIONS: [&str; 14] = [
+    "ReadExistingEvents",
+    "IgnoreChannelError",
+    "SubscriptionName",
+    "MaxEnvelopeSize",
+    "ConnectionRetry",
+    "ContentFormat",
+    "MaxElements",
+    "Compression",
+    "DataLocale",
+    "Heartbeats",
+    "Bookmark",
+    "MaxTime",
+    "Locale",
+    "CDATA",
+];
+
+/// Clients which rejected the manifest of a subscription. They are sent
+/// the reduced manifest of the subscription until it is reloaded.
+#[derive(Debug, Default)]
+pub struct EnrollmentFailures {
+    clients: Mutex<HashSet<String>>,
+}
+
+impl EnrollmentFailures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, client: &str) -> bool {
+        self.clients.lock().unwrap().contains(client)
+    }
+
+    pub fn insert(&self, client: &str) {
+        self.clients.lock().unwrap().insert(client.to_owned());
+    }
+}
+
+/// Returns the manifest option mentioned in the reason given by a client
+/// for ending a subscription, if any
+pub fn rejected_option(reason: &str) -> Option<&'static str> {
+    let reason = reason.to_lowercase();
+    MANIFEST_OPTIONS
+        .into_iter()
+        .find(|option| reason.contains(&option.to_lowercase()))
+}
+
+/// Handles a SubscriptionEnd message sent by `principal`. Unless the client
+/// is shutting down or failed to reach us, it rejected the manifest of the
+/// subscription: the failure is logged and counted, and the client is sent
+/// the reduced manifest next time if the subscription has a fallback.
+pub fn handle_subscription_end(
+    subscription: &Subscription,
+    principal: &str,
+    addr: &SocketAddr,
+    end: &SubscriptionEnd,
+) {
+    match end.status.as_deref() {
+        Some(STATUS_SOURCE_SHUTTING_DOWN) => {
+            debug!(
+                "{}:{} ({}) ended subscription {} ({}) because it is shutting down",
+                addr.ip(),
+                addr.port(),
+                principal,
+                subscription.data().name(),
+                subscription.uuid_string(),
+            );
+            return;
+        }
+        Some(STATUS_DELIVERY_FAILURE) => {
+            warn!(
+                "{}:{} ({}) ended subscription {} ({}) because it could not deliver events: {}",
+                addr.ip(),
+                addr.port(),
+                principal,
+                subscription.data().name(),
+                subscription.uuid_string(),
+                end.reason.as_deref().unwrap_or("unknown reason"),
+            );
+            return;
+        }
+        _ => (),
+    }
+
+    let reason = end.reason.as_deref().unwrap_or("unknown reason");
+    match rejected_option(reason) {
+        Some(option) => warn!(
+            "{}:{} ({}) rejected the manifest of subscription {} ({}), probably because of option {}: {}",
+            addr.ip(),
+            addr.port(),
+            principal,
+            subscription.data().name(),
+            subscription.uuid_string(),
+            option,
+            reason
+        ),
+        None => warn!(
+            "{}:{} ({}) rejected the manifest of subscription {} ({}): {}",
+            addr.ip(),
+            addr.port(),
+            principal,
+            subscription.data().name(),
+            subscription.uuid_string(),
+            reason
+        ),
+    }
+
+    counter!(ENROLLMENT_FAILURES_COUNTER,
+        SUBSCRIPTION_NAME => subscription.data().qualified_name(),
+        SUBSCRIPTION_UUID => subscription.uuid_string())
+    .increment(1);
+
+    if !subscription.data().enrollment_fallback().is_empty() {
+        info!(
+            "{} will be sent the fallback manifest of subscription {} ({})",
+            principal,
+            subscription.data().name(),
+            subscription.uuid_string(),
+        );
+        subscription.enrollment_failures().insert(principal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        str::FromStr,
+        sync::{atomic::AtomicU64, Arc},
+    };
+
+    use common::{
+        settings,
+        subscription::{ManifestOption, SubscriptionData},
+    };
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+
+    use super::*;
+    use crate::output::OutputDriversContext;
+
+    /// Records counters by name, and the labels of the registered counters
+    #[derive(Default)]
+    struct CountersRecorder {
+        counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+        labels: Mutex<Vec<(String, String)>>,
+    }
+
+    impl CountersRecorder {
+        fn get(&self, name: &str) -> u64 {
+            self.counters
+                .lock()
+                .unwrap()
+                .get(name)
+                .map(|counter| counter.load(std::sync::atomic::Ordering::Relaxed))
+                .unwrap_or_default()
+        }
+    }
+
+    impl Recorder for CountersRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            self.labels.lock().unwrap().extend(
+                key.labels()
+                    .map(|label| (label.key().to_string(), label.value().to_string())),
+            );
+            let counter = self
+                .counters
+                .lock()
+                .unwrap()
+                .entry(key.name().to_string())
+                .or_default()
+                .clone();
+            Counter::from_arc(counter)
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    fn new_subscription(enrollment_fallback: BTreeSet<ManifestOption>) -> Subscription {
+        new_subscription_with_tenant(enrollment_fallback, None)
+    }
+
+    fn new_subscription_with_tenant(
+        enrollment_fallback: BTreeSet<ManifestOption>,
+        tenant: Option<&str>,
+    ) -> Subscription {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_enrollment_fallback(enrollment_fallback)
+            .set_tenant(tenant.map(str::to_string));
+        Subscription::from_data(data, &mut output_context).unwrap()
+    }
+
+    fn end(status: &str, reason: &str) -> SubscriptionEnd {
+        SubscriptionEnd {
+            status: Some(format!(
+                "http://schemas.xmlsoap.org/ws/2004/08/eventing/{}",
+                status
+            )),
+            reason: Some(reason.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rejected_option() {
+        assert_eq!(
+            rejected_option("The DataLocale option value is not valid."),
+            Some("DataLocale")
+        );
+        assert_eq!(
+            rejected_option("The locale option is not supported"),
+            Some("Locale")
+        );
+        assert_eq!(rejected_option("Access is denied."), None);
+    }
+
+    #[test]
+    fn test_subscription_end() {
+        let addr = SocketAddr::from_str("192.168.58.100:5985").unwrap();
+        let subscription = new_subscription(BTreeSet::from([
+            ManifestOption::Locale,
+            ManifestOption::DataLocale,
+        ]));
+        let recorder = CountersRecorder::default();
+
+        metrics::with_local_recorder(&recorder, || {
+            // Clients shutting down did not reject anything
+            handle_subscription_end(
+                &subscription,
+                "WIN10$@WINDOMAIN.LOCAL",
+                &addr,
+                &end("SourceShuttingDown", "The computer is shutting down."),
+            );
+            assert_eq!(recorder.get(ENROLLMENT_FAILURES_COUNTER), 0);
+            assert!(!subscription
+                .enrollment_failures()
+                .contains("WIN10$@WINDOMAIN.LOCAL"));
+
+            handle_subscription_end(
+                &subscription,
+                "WIN10$@WINDOMAIN.LOCAL",
+                &addr,
+                &end(
+                    "SourceCancelling",
+                    "The DataLocale option value is not valid.",
+                ),
+            );
+        });
+
+        // The failure is counted and the client will get the fallback
+        // manifest
+        assert_eq!(recorder.get(ENROLLMENT_FAILURES_COUNTER), 1);
+        assert!(subscription
+            .enrollment_failures()
+            .contains("WIN10$@WINDOMAIN.LOCAL"));
+        assert!(!subscription
+            .enrollment_failures()
+            .contains("WIN11$@WINDOMAIN.LOCAL"));
+
+        // Without fallback, the failure is only logged and counted
+        let subscription = new_subscription(BTreeSet::new());
+        let recorder = CountersRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            handle_subscription_end(
+                &subscription,
+                "WIN10$@WINDOMAIN.LOCAL",
+                &addr,
+                &end("SourceCancelling", "Access is denied."),
+            );
+        });
+        assert_eq!(recorder.get(ENROLLMENT_FAILURES_COUNTER), 1);
+        assert!(!subscription
+            .enrollment_failures()
+            .contains("WIN10$@WINDOMAIN.LOCAL"));
+    }
+
+    #[test]
+    fn test_tenant_labels() {
+        let addr = SocketAddr::from_str("192.168.58.100:5985").unwrap();
+        for (tenant, name) in [(None, "Test"), (Some("team-a"), "team-a/Test")] {
+            let subscription = new_subscription_with_tenant(BTreeSet::new(), tenant);
+            let recorder = CountersRecorder::default();
+            metrics::with_local_recorder(&recorder, || {
+                handle_subscription_end(
+                    &subscription,
+                    "WIN10$@WINDOMAIN.LOCAL",
+                    &addr,
+                    &end("SourceCancelling", "Access is denied."),
+                );
+            });
+            // Subscriptions of different tenants may share a name, so
+            // their labels are namespaced
+            assert!(recorder
+                .labels
+                .lock()
+                .unwrap()
+                .contains(&(SUBSCRIPTION_NAME.to_string(), name.to_string())));
+        }
+    }
+}
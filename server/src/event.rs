@@ -6,7 +6,7 @@ use serde::Serialize;
 use std::{collections::HashMap, fmt::Display, net::SocketAddr, sync::Arc};
 use strum::IntoStaticStr;
 
-use crate::subscription::Subscription;
+use crate::{clock, subscription::Subscription};
 
 #[derive(Debug, Default, Serialize, Clone)]
 pub struct EventDataType {
@@ -304,9 +304,20 @@ fn parse_user_data(user_data_node: &Node) -> Result<DataType> {
     Ok(DataType::UserData(data))
 }
 
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ClockSkewInfo {
+    // Difference in seconds between TimeCreated and the reception time
+    pub skew: i64,
+    // Original TimeCreated, only set when it has been replaced
+    pub original_time_created: Option<String>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Additional {
     pub error: Option<ErrorInfo>,
+    pub clock_skew: Option<ClockSkewInfo>,
+    // Human-readable name of the event, if enabled and known
+    pub event_name: Option<Arc<String>>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -505,6 +516,7 @@ pub struct EventMetadata {
     subscription_uuid: String,
     subscription_version: String,
     subscription_name: String,
+    subscription_tenant: Option<String>,
     subscription_uri: Option<String>,
     subscription_client_revision: Option<String>,
     subscription_server_revision: Option<String>,
@@ -523,10 +535,11 @@ impl EventMetadata {
             addr: *addr,
             principal: principal.to_owned(),
             node_name,
-            time_received: Utc::now(),
+            time_received: clock::now(),
             subscription_uuid: subscription.data().uuid_string(),
             subscription_version: public_version,
             subscription_name: subscription.data().name().to_owned(),
+            subscription_tenant: subscription.data().tenant().cloned(),
             subscription_uri: subscription.data().uri().cloned(),
             subscription_client_revision: client_revision,
             subscription_server_revision: subscription.data().revision().cloned(),
@@ -567,6 +580,19 @@ impl EventMetadata {
         self.subscription_name.as_ref()
     }
 
+    pub fn subscription_tenant(&self) -> Option<&String> {
+        self.subscription_tenant.as_ref()
+    }
+
+    /// Name of the subscription prefixed by its tenant, if any, as used in
+    /// metric labels
+    pub fn subscription_qualified_name(&self) -> String {
+        match self.subscription_tenant() {
+            Some(tenant) => format!("{}/{}", tenant, self.subscription_name()),
+            None => self.subscription_name.clone(),
+        }
+    }
+
     pub fn subscription_uri(&self) -> Option<&String> {
         self.subscription_uri.as_ref()
     }
@@ -602,6 +628,57 @@ impl EventData {
     pub fn event(&self) -> Option<&Event> {
         self.event.as_ref()
     }
+
+    pub fn event_mut(&mut self) -> Option<&mut Event> {
+        self.event.as_mut()
+    }
+}
+
+/// Fields of a parsed event that output drivers may use, whatever the
+/// format of the event (for example in Kafka headers)
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct EventFields {
+    event_id: Option<u32>,
+    channel: Option<String>,
+    time_created: Option<String>,
+}
+
+impl EventFields {
+    pub fn new(event_id: Option<u32>, channel: Option<String>) -> Self {
+        Self {
+            event_id,
+            channel,
+            time_created: None,
+        }
+    }
+
+    pub fn with_time_created(mut self, time_created: Option<String>) -> Self {
+        self.time_created = time_created;
+        self
+    }
+
+    pub fn event_id(&self) -> Option<u32> {
+        self.event_id
+    }
+
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    /// Value of `System.TimeCreated` (RFC 3339)
+    pub fn time_created(&self) -> Option<&str> {
+        self.time_created.as_deref()
+    }
+}
+
+impl From<&EventData> for EventFields {
+    fn from(event_data: &EventData) -> Self {
+        match event_data.event().and_then(|event| event.system.as_ref()) {
+            Some(system) => Self::new(Some(system.event_id), system.channel.clone())
+                .with_time_created(system.time_created.clone()),
+            None => Self::default(),
+        }
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use common::settings::Server;
+use log::{info, warn};
+
+use crate::event::EventData;
+
+static EVENT_NAMES: OnceLock<EventNames> = OnceLock::new();
+
+const BUILTIN_EVENT_NAMES: &str = include_str!("event_names.toml");
+
+/// Human-readable names of events, by provider and EventID.
+///
+/// The same EventID may mean different things depending on the provider, so
+/// names are always looked up using both. Provider names are compared
+/// case-insensitively.
+#[derive(Debug, Default)]
+pub struct EventNames {
+    names: HashMap<String, HashMap<u32, Arc<String>>>,
+}
+
+impl EventNames {
+    /// Returns the built-in table
+    pub fn builtin() -> Self {
+        let mut event_names = Self::default();
+        event_names
+            .extend(BUILTIN_EVENT_NAMES)
+            .expect("Built-in event names table is invalid");
+        event_names
+    }
+
+    /// Adds the names of a TOML table with one sub-table per provider,
+    /// mapping EventIDs to names. Existing names are replaced.
+    pub fn extend(&mut self, content: &str) -> Result<()> {
+        let providers: HashMap<String, HashMap<String, String>> = toml::from_str(content)?;
+        for (provider, names) in providers {
+            let provider_names = self.names.entry(provider.to_lowercase()).or_default();
+            for (event_id, name) in names {
+                let event_id: u32 = event_id.parse().with_context(|| {
+                    format!("Invalid EventID {:?} of provider {:?}", event_id, provider)
+                })?;
+                provider_names.insert(event_id, Arc::new(name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, provider: &str, event_id: u32) -> Option<&Arc<String>> {
+        self.names.get(&provider.to_lowercase())?.get(&event_id)
+    }
+
+    /// Sets the name of a parsed event, if it is known
+    pub fn apply(&self, event_data: &mut EventData) {
+        let event = match event_data.event_mut() {
+            Some(event) => event,
+            None => return,
+        };
+        let name = event.system.as_ref().and_then(|system| {
+            system
+                .provider
+                .name
+                .as_ref()
+                .and_then(|provider| self.get(provider, system.event_id))
+        });
+        event.additional.event_name = name.cloned();
+    }
+}
+
+/// Loads the event names table if it is enabled. Must be called once at startup.
+pub fn init(settings: &Server) -> Result<()> {
+    if !settings.event_names() {
+        return Ok(());
+    }
+    let mut event_names = EventNames::builtin();
+    if let Some(path) = settings.event_names_file() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read event names file {}", path))?;
+        event_names
+            .extend(&content)
+            .with_context(|| format!("Failed to load event names file {}", path))?;
+    }
+    info!("Events are enriched with their names");
+    if EVENT_NAMES.set(event_names).is_err() {
+        warn!("Event names have already been initialized");
+    }
+    Ok(())
+}
+
+/// Returns the event names table, if enabled
+pub fn get() -> Option<&'static EventNames> {
+    EVENT_NAMES.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_names() -> Result<()> {
+        let mut event_names = EventNames::builtin();
+        assert_eq!(
+            event_names
+                .get("Microsoft-Windows-Security-Auditing", 4624)
+                .map(|name| name.as_str()),
+            Some("An account was successfully logged on")
+        );
+        // Names are scoped by provider
+        assert!(event_names.get("Microsoft-Windows-Sysmon", 4624).is_none());
+        assert!(event_names
+            .get("microsoft-windows-security-auditing", 4688)
+            .is_some());
+
+        event_names.extend(
+            r#"
+            ["Microsoft-Windows-Security-Auditing"]
+            4624 = "Logon"
+
+            ["Microsoft-Windows-Sysmon"]
+            1 = "Process creation"
+            "#,
+        )?;
+        assert_eq!(
+            event_names
+                .get("Microsoft-Windows-Security-Auditing", 4624)
+                .map(|name| name.as_str()),
+            Some("Logon")
+        );
+        // Other built-in names are kept
+        assert!(event_names
+            .get("Microsoft-Windows-Security-Auditing", 4625)
+            .is_some());
+        assert_eq!(
+            event_names
+                .get("Microsoft-Windows-Sysmon", 1)
+                .map(|name| name.as_str()),
+            Some("Process creation")
+        );
+
+        assert!(event_names
+            .extend("[\"Microsoft-Windows-Sysmon\"]\nprocess = \"Process creation\"")
+            .is_err());
+        Ok(())
+    }
+}
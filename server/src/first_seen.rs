@@ -0,0 +1,125 @@
+use std::{collections::HashSet, future::Future, sync::Mutex};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::event::EventMetadata;
+
+/// Sources (principals) known to have already sent events for a subscription.
+///
+/// A source is considered "first seen" when it is neither known in memory nor
+/// has a bookmark stored in database. Bookmarks are only stored once every
+/// output has successfully processed a batch, so the synthetic "first seen"
+/// event is not emitted again after a restart.
+#[derive(Debug, Default)]
+pub struct KnownSources {
+    sources: Mutex<HashSet<String>>,
+}
+
+impl KnownSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, source: &str) -> bool {
+        self.sources.lock().unwrap().contains(source)
+    }
+
+    pub fn insert(&self, source: &str) {
+        self.sources.lock().unwrap().insert(source.to_owned());
+    }
+
+    /// Returns whether `source` is seen for the first time. `has_bookmark` is
+    /// only awaited if the source is not already known in memory.
+    pub async fn is_first_seen<F>(&self, source: &str, has_bookmark: F) -> Result<bool>
+    where
+        F: Future<Output = Result<bool>>,
+    {
+        if self.contains(source) {
+            return Ok(false);
+        }
+        if has_bookmark.await? {
+            self.insert(source);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FirstSeenSubscription<'a> {
+    #[serde(rename = "Uuid")]
+    uuid: &'a str,
+    #[serde(rename = "Name")]
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct FirstSeenEvent<'a> {
+    #[serde(rename = "Type")]
+    event_type: &'static str,
+    #[serde(rename = "IpAddress")]
+    ip_address: String,
+    #[serde(rename = "Principal")]
+    principal: &'a str,
+    #[serde(rename = "TimeReceived")]
+    time_received: String,
+    #[serde(rename = "Subscription")]
+    subscription: FirstSeenSubscription<'a>,
+    #[serde(rename = "Node", skip_serializing_if = "Option::is_none")]
+    node: Option<&'a String>,
+}
+
+/// Serializes the synthetic event sent to "source first seen" outputs
+pub fn format(metadata: &EventMetadata) -> Result<String> {
+    let event = FirstSeenEvent {
+        event_type: "SourceFirstSeen",
+        ip_address: metadata.addr().ip().to_string(),
+        principal: metadata.principal(),
+        time_received: metadata.time_received().to_rfc3339(),
+        subscription: FirstSeenSubscription {
+            uuid: metadata.subscription_uuid(),
+            name: metadata.subscription_name(),
+        },
+        node: metadata.node_name(),
+    };
+    Ok(serde_json::to_string(&event)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn receive_batch(
+        known: &KnownSources,
+        bookmarks: &mut HashSet<String>,
+        source: &str,
+    ) -> Result<bool> {
+        let has_bookmark = bookmarks.contains(source);
+        let first_seen = known
+            .is_first_seen(source, async move { Ok(has_bookmark) })
+            .await?;
+        // Outputs succeeded: bookmark is stored and the source is remembered
+        bookmarks.insert(source.to_owned());
+        known.insert(source);
+        Ok(first_seen)
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_once_per_source() -> Result<()> {
+        let mut bookmarks = HashSet::new();
+
+        let known = KnownSources::new();
+        assert!(receive_batch(&known, &mut bookmarks, "DC1$@WINDOMAIN.LOCAL").await?);
+        assert!(!receive_batch(&known, &mut bookmarks, "DC1$@WINDOMAIN.LOCAL").await?);
+        assert!(receive_batch(&known, &mut bookmarks, "DC2$@WINDOMAIN.LOCAL").await?);
+        assert!(!receive_batch(&known, &mut bookmarks, "DC2$@WINDOMAIN.LOCAL").await?);
+
+        // Restart: in-memory state is lost but bookmarks are kept in database
+        let known = KnownSources::new();
+        assert!(!receive_batch(&known, &mut bookmarks, "DC1$@WINDOMAIN.LOCAL").await?);
+        assert!(!receive_batch(&known, &mut bookmarks, "DC2$@WINDOMAIN.LOCAL").await?);
+        assert!(receive_batch(&known, &mut bookmarks, "DC3$@WINDOMAIN.LOCAL").await?);
+        Ok(())
+    }
+}
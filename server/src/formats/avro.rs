@@ -0,0 +1,503 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use log::warn;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    formats::json::parse_time_created,
+    output::{BinaryFileLayout, OutputFormat},
+};
+
+/// Schema of the events written by the Avro format. It is embedded in the
+/// header of Object Container Files.
+const AVRO_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "Event",
+  "namespace": "openwec",
+  "doc": "Windows event received by OpenWEC",
+  "fields": [
+    {"name": "EventID", "type": ["null", "long"], "default": null},
+    {"name": "Computer", "type": ["null", "string"], "default": null},
+    {"name": "Channel", "type": ["null", "string"], "default": null},
+    {"name": "Provider", "type": ["null", "string"], "default": null},
+    {
+      "name": "TimeCreated",
+      "type": ["null", {"type": "long", "logicalType": "timestamp-micros"}],
+      "default": null
+    },
+    {"name": "Level", "type": ["null", "int"], "default": null},
+    {"name": "RawXml", "type": "string", "doc": "Event as received, in XML"}
+  ]
+}
+"#;
+
+/// Parsing Canonical Form of `AVRO_SCHEMA`, whose fingerprint identifies
+/// the schema of single-object encoded events
+const AVRO_CANONICAL_SCHEMA: &str = r#"{"name":"openwec.Event","type":"record","fields":[{"name":"EventID","type":["null","long"]},{"name":"Computer","type":["null","string"]},{"name":"Channel","type":["null","string"]},{"name":"Provider","type":["null","string"]},{"name":"TimeCreated","type":["null","long"]},{"name":"Level","type":["null","int"]},{"name":"RawXml","type":"string"}]}"#;
+
+const SCHEMA_FINGERPRINT: u64 = rabin_fingerprint(AVRO_CANONICAL_SCHEMA.as_bytes());
+
+// Single-object encoded events start with this marker, followed by the
+// fingerprint of their schema
+const SINGLE_OBJECT_MARKER: [u8; 2] = [0xc3, 0x01];
+const SINGLE_OBJECT_HEADER_LEN: usize = 10;
+
+const CONTAINER_MAGIC: &[u8] = b"Obj\x01";
+
+/// Sync marker written after each block of Object Container Files. It is
+/// fixed, rather than generated for each file, so that files created before
+/// a restart can still be appended to.
+const CONTAINER_SYNC_MARKER: [u8; 16] = [
+    0x8c, 0x0b, 0xcc, 0xac, 0x41, 0x9d, 0x3d, 0x71, 0x58, 0x6c, 0xb6, 0x66, 0xd6, 0xe3, 0x39, 0x18,
+];
+
+/// Returns the Avro schema of the events written by the Avro format
+pub fn schema() -> &'static str {
+    AVRO_SCHEMA
+}
+
+/// Returns the CRC-64-AVRO fingerprint of the schema, which is written
+/// (in little-endian) in the header of single-object encoded events
+pub fn schema_fingerprint() -> u64 {
+    SCHEMA_FINGERPRINT
+}
+
+/// Encodes the main fields of events and their XML in Avro, using a fixed
+/// schema. Events are single-object encoded, and the Files driver writes
+/// them in Object Container Files.
+pub struct AvroFormat;
+
+impl OutputFormat for AvroFormat {
+    fn format(&self, _metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let system = event.system.as_ref();
+        let raw = data.raw();
+
+        let mut bytes = Vec::with_capacity(SINGLE_OBJECT_HEADER_LEN + raw.len() + 256);
+        bytes.extend_from_slice(&SINGLE_OBJECT_MARKER);
+        bytes.extend_from_slice(&SCHEMA_FINGERPRINT.to_le_bytes());
+        encode_optional_long(system.map(|system| system.event_id.into()), &mut bytes);
+        encode_optional_string(system.map(|system| system.computer.as_str()), &mut bytes);
+        encode_optional_string(
+            system.and_then(|system| system.channel.as_deref()),
+            &mut bytes,
+        );
+        encode_optional_string(
+            system.and_then(|system| system.provider.name.as_deref()),
+            &mut bytes,
+        );
+        encode_optional_long(
+            system
+                .and_then(|system| system.time_created.as_deref())
+                .and_then(parse_time_created)
+                .map(|time| time.timestamp_micros()),
+            &mut bytes,
+        );
+        // int and long share the same encoding
+        encode_optional_long(
+            system.and_then(|system| system.level).map(Into::into),
+            &mut bytes,
+        );
+        encode_bytes(raw.as_bytes(), &mut bytes);
+        Some(Arc::new(bytes))
+    }
+
+    fn binary_file_layout(&self) -> Option<BinaryFileLayout> {
+        Some(BinaryFileLayout {
+            header: container_header,
+            encode: container_block,
+        })
+    }
+}
+
+/// Header of Object Container Files, containing the schema
+fn container_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(AVRO_SCHEMA.len() + 64);
+    header.extend_from_slice(CONTAINER_MAGIC);
+    // File metadata, a map written in a single block
+    encode_long(2, &mut header);
+    encode_bytes(b"avro.schema", &mut header);
+    encode_bytes(AVRO_SCHEMA.as_bytes(), &mut header);
+    encode_bytes(b"avro.codec", &mut header);
+    encode_bytes(b"null", &mut header);
+    encode_long(0, &mut header);
+    header.extend_from_slice(&CONTAINER_SYNC_MARKER);
+    header
+}
+
+/// Data block of Object Container Files containing single-object encoded
+/// `events`
+fn container_block(events: &[Arc<Vec<u8>>]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for event in events {
+        if event.len() < SINGLE_OBJECT_HEADER_LEN
+            || event[..2] != SINGLE_OBJECT_MARKER
+            || event[2..SINGLE_OBJECT_HEADER_LEN] != SCHEMA_FINGERPRINT.to_le_bytes()
+        {
+            bail!("Event is not encoded with the schema of the Avro format");
+        }
+        data.extend_from_slice(&event[SINGLE_OBJECT_HEADER_LEN..]);
+    }
+
+    let mut block = Vec::with_capacity(data.len() + 36);
+    encode_long(events.len().try_into()?, &mut block);
+    encode_long(data.len().try_into()?, &mut block);
+    block.extend_from_slice(&data);
+    block.extend_from_slice(&CONTAINER_SYNC_MARKER);
+    Ok(block)
+}
+
+/// Writes a long as a zig-zag encoded variable-length integer
+fn encode_long(value: i64, out: &mut Vec<u8>) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Writes bytes or a string, preceded by their length
+fn encode_bytes(value: &[u8], out: &mut Vec<u8>) {
+    encode_long(value.len() as i64, out);
+    out.extend_from_slice(value);
+}
+
+// Optional values are unions whose first branch is null
+
+fn encode_optional_long(value: Option<i64>, out: &mut Vec<u8>) {
+    match value {
+        Some(value) => {
+            encode_long(1, out);
+            encode_long(value, out);
+        }
+        None => encode_long(0, out),
+    }
+}
+
+fn encode_optional_string(value: Option<&str>, out: &mut Vec<u8>) {
+    match value {
+        Some(value) => {
+            encode_long(1, out);
+            encode_bytes(value.as_bytes(), out);
+        }
+        None => encode_long(0, out),
+    }
+}
+
+/// CRC-64-AVRO (Rabin) fingerprint, as defined by the Avro specification
+const fn rabin_fingerprint(bytes: &[u8]) -> u64 {
+    const EMPTY: u64 = 0xc15d_213a_a4d7_a795;
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut fp = i as u64;
+        let mut j = 0;
+        while j < 8 {
+            fp = (fp >> 1) ^ (EMPTY & (fp & 1).wrapping_neg());
+            j += 1;
+        }
+        table[i] = fp;
+        i += 1;
+    }
+
+    let mut fp = EMPTY;
+    let mut i = 0;
+    while i < bytes.len() {
+        fp = (fp >> 8) ^ table[((fp ^ bytes[i] as u64) & 0xff) as usize];
+        i += 1;
+    }
+    fp
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::SocketAddr, str::FromStr};
+
+    use apache_avro::{from_avro_datum, types::Value as AvroValue, Reader, Schema};
+    use chrono::{DateTime, Utc};
+    use common::{
+        settings::{self, PathAnonymization},
+        subscription::{FilesConfiguration, SubscriptionData},
+    };
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        drivers::files::{OutputFiles, OutputFilesContext},
+        output::{OutputDriver, OutputDriversContext},
+        subscription::Subscription,
+    };
+
+    const EVENT_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114690</EventRecordID><Correlation ActivityID='{8A3C1B5E-0F43-0001-5A1C-3C8A430FD901}'/><Execution ProcessID='696' ThreadID='4440'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='TargetUserName'>vagrant</Data></EventData></Event>"#;
+    const EVENT_111: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-EventForwarder'/><EventID>111</EventID><TimeCreated SystemTime='2023-02-14T09:14:23.175Z'/><Computer>win10.windomain.local</Computer></System><SubscriptionBookmarkEvent><SubscriptionId></SubscriptionId></SubscriptionBookmarkEvent></Event>"#;
+
+    fn metadata() -> Arc<EventMetadata> {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        Arc::new(metadata)
+    }
+
+    fn format(raw: &str) -> Arc<Vec<u8>> {
+        let event_data = EventData::new(Arc::new(raw.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+        AvroFormat.format(&metadata(), &event_data).unwrap()
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct AvroEvent {
+        event_id: Option<i64>,
+        computer: Option<String>,
+        channel: Option<String>,
+        provider: Option<String>,
+        time_created: Option<i64>,
+        level: Option<i64>,
+        raw_xml: String,
+    }
+
+    fn long(value: AvroValue) -> i64 {
+        match value {
+            AvroValue::Int(value) => value.into(),
+            AvroValue::Long(value) | AvroValue::TimestampMicros(value) => value,
+            _ => panic!("Unexpected value {:?}", value),
+        }
+    }
+
+    fn string(value: AvroValue) -> String {
+        match value {
+            AvroValue::String(value) => value,
+            _ => panic!("Unexpected value {:?}", value),
+        }
+    }
+
+    /// Converts a record decoded by apache-avro
+    fn event(value: AvroValue) -> AvroEvent {
+        let mut fields: HashMap<String, AvroValue> = match value {
+            AvroValue::Record(fields) => fields.into_iter().collect(),
+            _ => panic!("Unexpected value {:?}", value),
+        };
+        let mut optional = |name: &str| match fields.remove(name) {
+            Some(AvroValue::Union(0, value)) if *value == AvroValue::Null => None,
+            Some(AvroValue::Union(1, value)) => Some(*value),
+            value => panic!("Unexpected value {:?} for {}", value, name),
+        };
+        AvroEvent {
+            event_id: optional("EventID").map(long),
+            computer: optional("Computer").map(string),
+            channel: optional("Channel").map(string),
+            provider: optional("Provider").map(string),
+            time_created: optional("TimeCreated").map(long),
+            level: optional("Level").map(long),
+            raw_xml: string(fields.remove("RawXml").unwrap()),
+        }
+    }
+
+    fn read_single_object(bytes: &[u8]) -> AvroEvent {
+        assert_eq!(bytes[..2], [0xc3, 0x01]);
+        assert_eq!(bytes[2..10], schema_fingerprint().to_le_bytes());
+        let schema = Schema::parse_str(schema()).unwrap();
+        let mut datum = &bytes[10..];
+        let event = event(from_avro_datum(&schema, &mut datum, None).unwrap());
+        assert!(datum.is_empty());
+        event
+    }
+
+    /// Returns the schema and the events of an Object Container File
+    fn read_container(bytes: &[u8]) -> (Schema, Vec<AvroEvent>) {
+        let reader = Reader::new(bytes).unwrap();
+        let schema = reader.writer_schema().clone();
+        let events = reader.map(|value| event(value.unwrap())).collect();
+        (schema, events)
+    }
+
+    /// Returns the Parsing Canonical Form of a schema using only records,
+    /// unions and primitive types
+    fn canonical_form(schema: &Value, namespace: Option<&str>) -> String {
+        match schema {
+            Value::String(_) => schema.to_string(),
+            Value::Array(branches) => format!(
+                "[{}]",
+                branches
+                    .iter()
+                    .map(|branch| canonical_form(branch, namespace))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+            Value::Object(object) if object["type"] == "record" => {
+                let namespace = object
+                    .get("namespace")
+                    .and_then(Value::as_str)
+                    .or(namespace);
+                let name = object["name"].as_str().unwrap();
+                let fullname = match namespace {
+                    Some(namespace) => format!("{}.{}", namespace, name),
+                    None => name.to_string(),
+                };
+                let fields: Vec<String> = object["fields"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|field| {
+                        format!(
+                            r#"{{"name":{},"type":{}}}"#,
+                            field["name"],
+                            canonical_form(&field["type"], namespace)
+                        )
+                    })
+                    .collect();
+                format!(
+                    r#"{{"name":{},"type":"record","fields":[{}]}}"#,
+                    Value::String(fullname),
+                    fields.join(",")
+                )
+            }
+            // Logical types are stripped
+            Value::Object(object) => canonical_form(&object["type"], namespace),
+            _ => panic!("Unsupported schema {}", schema),
+        }
+    }
+
+    #[test]
+    fn test_schema() -> Result<()> {
+        Schema::parse_str(schema())?;
+        let schema: Value = serde_json::from_str(schema())?;
+        assert_eq!(canonical_form(&schema, None), AVRO_CANONICAL_SCHEMA);
+
+        // Test vectors of the Avro specification
+        assert_eq!(rabin_fingerprint(br#""null""#), 0x63dd24e7cc258f8a);
+        assert_eq!(
+            schema_fingerprint(),
+            rabin_fingerprint(AVRO_CANONICAL_SCHEMA.as_bytes())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_long() {
+        for (value, expected) in [
+            (0, vec![0x00]),
+            (-1, vec![0x01]),
+            (1, vec![0x02]),
+            (-64, vec![0x7f]),
+            (64, vec![0x80, 0x01]),
+            (4624, vec![0xa0, 0x48]),
+        ] {
+            let mut bytes = Vec::new();
+            encode_long(value, &mut bytes);
+            assert_eq!(bytes, expected, "{}", value);
+        }
+        for value in [0, -1, 4624, i64::MIN, i64::MAX, 1_670_947_611_064_360] {
+            let mut bytes = Vec::new();
+            encode_long(value, &mut bytes);
+            assert_eq!(
+                from_avro_datum(&Schema::Long, &mut &bytes[..], None).unwrap(),
+                AvroValue::Long(value)
+            );
+        }
+    }
+
+    #[test]
+    fn test_avro_format() {
+        assert_eq!(
+            read_single_object(&format(EVENT_4624)),
+            AvroEvent {
+                event_id: Some(4624),
+                computer: Some("win10.windomain.local".to_string()),
+                channel: Some("Security".to_string()),
+                provider: Some("Microsoft-Windows-Security-Auditing".to_string()),
+                // 2022-12-14T16:06:51.064360Z
+                time_created: Some(1_671_034_011_064_360),
+                level: Some(0),
+                raw_xml: EVENT_4624.to_string(),
+            }
+        );
+
+        // Missing fields are null
+        assert_eq!(
+            read_single_object(&format(EVENT_111)),
+            AvroEvent {
+                event_id: Some(111),
+                computer: Some("win10.windomain.local".to_string()),
+                channel: None,
+                provider: Some("Microsoft-Windows-EventForwarder".to_string()),
+                time_created: Some(1_676_366_063_175_000),
+                level: None,
+                raw_xml: EVENT_111.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_container_block() -> Result<()> {
+        let events = vec![format(EVENT_4624), format(EVENT_111)];
+        let mut file = container_header();
+        file.extend(container_block(&events)?);
+        file.extend(container_block(&events[..1])?);
+
+        let (schema, read) = read_container(&file);
+        assert_eq!(schema, Schema::parse_str(AVRO_SCHEMA)?);
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[0], read_single_object(&events[0]));
+        assert_eq!(read[1], read_single_object(&events[1]));
+        assert_eq!(read[2], read_single_object(&events[0]));
+
+        // Only events of the Avro format can be written
+        assert!(container_block(&[Arc::new(b"event".to_vec())]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_avro_files() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        let config = FilesConfiguration::new(format!("{}/{{ip}}/events.avro", dir.display()));
+        let context = Some(OutputFilesContext::new(PathAnonymization::Disabled));
+        let output = OutputFiles::new(&config, &context)?
+            .with_binary_layout(AvroFormat.binary_file_layout());
+
+        output
+            .write(
+                metadata(),
+                Arc::new(vec![format(EVENT_4624), format(EVENT_111)]),
+            )
+            .await?;
+        output
+            .write(metadata(), Arc::new(vec![format(EVENT_111)]))
+            .await?;
+
+        // The header is only written once and events are not followed by
+        // line feeds
+        let file = std::fs::read(dir.join("192.168.58.100/events.avro"))?;
+        let (schema, events) = read_container(&file);
+        assert_eq!(schema, Schema::parse_str(AVRO_SCHEMA)?);
+        let event_ids: Vec<Option<i64>> = events.iter().map(|event| event.event_id).collect();
+        assert_eq!(event_ids, vec![Some(4624), Some(111), Some(111)]);
+        assert_eq!(events[0].channel.as_deref(), Some("Security"));
+        assert_eq!(events[2].raw_xml, EVENT_111);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
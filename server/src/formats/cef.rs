@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use common::subscription::CefConfiguration;
+use log::warn;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    output::OutputFormat,
+};
+
+pub struct CefFormat {
+    config: CefConfiguration,
+}
+
+impl CefFormat {
+    pub fn new(config: &CefConfiguration) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+}
+
+impl OutputFormat for CefFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let system = event.system.as_ref();
+        let message = event
+            .rendering_info
+            .as_ref()
+            .and_then(|rendering_info| rendering_info.message.as_deref());
+        let event_id = system
+            .map(|system| system.event_id.to_string())
+            .unwrap_or_default();
+
+        // Use the enriched event name if any, then the first line of the
+        // rendered message
+        let name = match (event.additional.event_name.as_ref(), message) {
+            (Some(event_name), _) => event_name.to_string(),
+            (None, Some(message)) if !message.trim().is_empty() => message
+                .trim()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            _ => format!(
+                "{} {}",
+                system
+                    .and_then(|system| system.provider.name.as_deref())
+                    .unwrap_or("Event"),
+                event_id
+            ),
+        };
+
+        let time = system
+            .and_then(|system| system.time_created.as_deref())
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|| metadata.time_received());
+
+        let mut extensions = vec![
+            ("rt", time.timestamp_millis().to_string()),
+            ("dvc", metadata.addr().ip().to_string()),
+        ];
+        if let Some(system) = system {
+            extensions.push(("dvchost", system.computer.clone()));
+            if let Some(event_record_id) = system.event_record_id {
+                extensions.push(("externalId", event_record_id.to_string()));
+            }
+            if let Some(channel) = system.channel.as_ref() {
+                extensions.push(("cs1Label", "Channel".to_string()));
+                extensions.push(("cs1", channel.clone()));
+            }
+            if let Some(provider) = system.provider.name.as_ref() {
+                extensions.push(("cs2Label", "Provider".to_string()));
+                extensions.push(("cs2", provider.clone()));
+            }
+        }
+        extensions.push(("cs3Label", "Subscription".to_string()));
+        extensions.push(("cs3", metadata.subscription_name().to_string()));
+        if let Some(message) = message {
+            extensions.push(("msg", message.to_string()));
+        }
+
+        let extensions = extensions
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, escape_extension(value)))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let record = format!(
+            "CEF:{}|{}|{}|{}|{}|{}|{}|{}",
+            self.config.version(),
+            escape_header(self.config.device_vendor()),
+            escape_header(self.config.device_product()),
+            escape_header(
+                self.config
+                    .device_version()
+                    .map(String::as_str)
+                    .unwrap_or(env!("CARGO_PKG_VERSION"))
+            ),
+            escape_header(&event_id),
+            escape_header(&name),
+            severity(system.and_then(|system| system.level)),
+            extensions
+        );
+        Some(Arc::new(record.into_bytes()))
+    }
+}
+
+/// Maps the level of a Windows event to a CEF or LEEF severity (0 to 10)
+pub fn severity(level: Option<u8>) -> u8 {
+    match level {
+        // Critical
+        Some(1) => 10,
+        // Error
+        Some(2) => 7,
+        // Warning
+        Some(3) => 5,
+        // Verbose
+        Some(5) => 1,
+        // LogAlways, Information and unknown levels
+        _ => 3,
+    }
+}
+
+/// Escapes a header field: `\` and `|` must be preceded by a backslash and
+/// line breaks are not allowed
+fn escape_header(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\r' | '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes an extension value: `\` and `=` must be preceded by a backslash
+/// and line breaks are encoded as `\r` and `\n`
+fn escape_extension(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | '=' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use chrono::Utc;
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const EVENT_5719: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='NETLOGON'/><EventID Qualifiers='0'>5719</EventID><Version>0</Version><Level>2</Level><Task>0</Task><Opcode>0</Opcode><Keywords>0x80000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:04:59.0817047Z'/><EventRecordID>9466</EventRecordID><Correlation/><Execution ProcessID='0' ThreadID='0'/><Channel>System</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data>WINDOMAIN</Data><Data>%%1311</Data><Binary>5E0000C0</Binary></EventData><RenderingInfo Culture='en-US'><Message>This computer was not able to set up a secure session with a domain controller in domain WINDOMAIN.
+ADDITIONAL INFO = 0xc000005e</Message><Level>Error</Level><Task></Task><Opcode>Info</Opcode><Channel></Channel><Provider></Provider><Keywords><Keyword>Classic</Keyword></Keywords></RenderingInfo></Event>"#;
+
+    const EVENT_4688: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='NewProcessName'>C:\Windows\System32\cmd.exe</Data></EventData></Event>"#;
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            chrono::DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    #[test]
+    fn test_cef_format() {
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT_5719.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+
+        let formatter = CefFormat::new(
+            &CefConfiguration::try_new(
+                0,
+                "Windomain".to_string(),
+                "WEC".to_string(),
+                Some("2.0".to_string()),
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap(),
+            "CEF:0|Windomain|WEC|2.0|5719|This computer was not able to set up a secure session with a domain controller in domain WINDOMAIN.|7|rt=1671033899081 dvc=192.168.58.100 dvchost=win10.windomain.local externalId=9466 cs1Label=Channel cs1=System cs2Label=Provider cs2=NETLOGON cs3Label=Subscription cs3=Test msg=This computer was not able to set up a secure session with a domain controller in domain WINDOMAIN.\\nADDITIONAL INFO \\= 0xc000005e"
+        );
+
+        // Without rendering info, the name is built from the provider and
+        // the device version defaults to the OpenWEC version
+        let event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        let formatter = CefFormat::new(&CefConfiguration::default());
+        assert_eq!(
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap(),
+            format!("CEF:0|OpenWEC|OpenWEC|{}|4688|Microsoft-Windows-Security-Auditing 4688|3|rt=1671034011064 dvc=192.168.58.100 dvchost=win10.windomain.local externalId=114689 cs1Label=Channel cs1=Security cs2Label=Provider cs2=Microsoft-Windows-Security-Auditing cs3Label=Subscription cs3=Test", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_header("a|b\\c\r\nd=e"), "a\\|b\\\\c  d=e");
+        assert_eq!(escape_extension("a|b\\c\r\nd=e"), "a|b\\\\c\\r\\nd\\=e");
+    }
+}
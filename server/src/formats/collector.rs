@@ -0,0 +1,111 @@
+use serde_json::{Map, Value};
+
+use crate::event::EventMetadata;
+
+/// Version of openwecd
+pub const COLLECTOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Build identifier of openwecd (see build.rs)
+pub const COLLECTOR_BUILD: &str = env!("OPENWEC_BUILD");
+
+/// Key under which the collector information is added to events
+pub const COLLECTOR_KEY: &str = "collector";
+
+/// Adds the version, the build and the node name of the collector which
+/// received the event under the `collector` key of `value`. The node is
+/// left out if `server.node_name` is not configured.
+pub fn insert(value: &mut Value, metadata: &EventMetadata) {
+    let mut collector = Map::new();
+    collector.insert(
+        "version".to_owned(),
+        Value::String(COLLECTOR_VERSION.to_owned()),
+    );
+    collector.insert(
+        "build".to_owned(),
+        Value::String(COLLECTOR_BUILD.to_owned()),
+    );
+    if let Some(node_name) = metadata.node_name() {
+        collector.insert("node".to_owned(), Value::String(node_name.clone()));
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert(COLLECTOR_KEY.to_owned(), Value::Object(collector));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use common::{
+        settings,
+        subscription::{FieldMap, FormatOptions, SubscriptionData, SubscriptionOutputFormat},
+    };
+
+    use super::*;
+    use crate::{
+        event::EventData,
+        output::{get_formatter, OutputDriversContext},
+        subscription::Subscription,
+    };
+
+    const EVENT_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114690</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='TargetUserName'>vagrant</Data></EventData></Event>"#;
+
+    fn metadata(node_name: Option<&str>) -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            node_name.map(str::to_owned),
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        )
+    }
+
+    fn format(
+        format: &SubscriptionOutputFormat,
+        collector_info: bool,
+        metadata: &EventMetadata,
+    ) -> Value {
+        let event_data = EventData::new(Arc::new(EVENT_4624.to_string()), true);
+        let formatter = get_formatter(
+            format,
+            &FieldMap::default(),
+            collector_info,
+            &FormatOptions::default(),
+        );
+        let result = formatter.format(metadata, &event_data).unwrap();
+        serde_json::from_slice(&result).unwrap()
+    }
+
+    #[test]
+    fn test_collector_info() {
+        let metadata = metadata(Some("openwec"));
+        for output_format in [
+            SubscriptionOutputFormat::Json,
+            SubscriptionOutputFormat::Nxlog,
+        ] {
+            let value = format(&output_format, true, &metadata);
+            assert_eq!(
+                value[COLLECTOR_KEY],
+                serde_json::json!({
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "build": COLLECTOR_BUILD,
+                    "node": "openwec",
+                })
+            );
+
+            // Disabled by default
+            let value = format(&output_format, false, &metadata);
+            assert!(value.get(COLLECTOR_KEY).is_none());
+        }
+
+        // The node is left out if it is not configured
+        let value = format(&SubscriptionOutputFormat::Json, true, &metadata(None));
+        assert_eq!(value[COLLECTOR_KEY]["version"], COLLECTOR_VERSION);
+        assert!(value[COLLECTOR_KEY].get("node").is_none());
+    }
+}
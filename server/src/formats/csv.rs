@@ -0,0 +1,240 @@
+use std::{borrow::Cow, sync::Arc};
+
+use common::subscription::{CsvConfiguration, FieldMap};
+use log::warn;
+use serde_json::Value;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    formats::{field_map, json},
+    output::OutputFormat,
+};
+
+pub struct CsvFormat {
+    config: CsvConfiguration,
+    field_map: FieldMap,
+}
+
+impl CsvFormat {
+    pub fn new(config: &CsvConfiguration, field_map: &FieldMap) -> Self {
+        Self {
+            config: config.clone(),
+            field_map: field_map.clone(),
+        }
+    }
+
+    fn row<'a, I>(&self, cells: I) -> String
+    where
+        I: Iterator<Item = Cow<'a, str>>,
+    {
+        let mut row = String::new();
+        for (index, cell) in cells.enumerate() {
+            if index > 0 {
+                row.push(self.config.delimiter());
+            }
+            row.push_str(&escape(&cell, self.config.delimiter()));
+        }
+        row
+    }
+}
+
+impl OutputFormat for CsvFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        // Columns refer to renamed fields
+        let value = match json::to_value(event.clone(), metadata) {
+            Ok(value) if self.field_map.is_empty() => value,
+            Ok(value) => field_map::apply(&self.field_map, value),
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON: {:?}. Event was: {:?}",
+                    e, event
+                );
+                return None;
+            }
+        };
+        let cells = self
+            .config
+            .columns()
+            .iter()
+            .map(|column| cell(lookup_column(&value, column)));
+        Some(Arc::new(self.row(cells).into_bytes()))
+    }
+
+    fn header(&self) -> Option<String> {
+        if self.config.header() {
+            Some(self.row(self.config.columns().iter().map(|c| Cow::from(c.as_str()))))
+        } else {
+            None
+        }
+    }
+}
+
+/// Retrieves the value of a dotted path (`System.EventID`). Array elements
+/// can be accessed using their index (`EventData.Data.0`).
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |current, key| match current {
+        Value::Object(map) => map.get(key),
+        Value::Array(array) => key.parse::<usize>().ok().and_then(|i| array.get(i)),
+        _ => None,
+    })
+}
+
+/// Retrieves the value of a column. Columns without a dot which are not
+/// top-level fields refer to fields of `System`, so that `EventID` is the
+/// same as `System.EventID`.
+pub fn lookup_column<'a>(value: &'a Value, column: &str) -> Option<&'a Value> {
+    match lookup(value, column) {
+        None if !column.contains('.') => value.get("System")?.get(column),
+        found => found,
+    }
+}
+
+pub fn cell(value: Option<&Value>) -> Cow<'_, str> {
+    match value {
+        None | Some(Value::Null) => Cow::from(""),
+        Some(Value::String(s)) => Cow::from(s.as_str()),
+        Some(Value::Number(n)) => Cow::from(n.to_string()),
+        Some(Value::Bool(b)) => Cow::from(b.to_string()),
+        // Nested values are encoded in JSON
+        Some(other) => Cow::from(other.to_string()),
+    }
+}
+
+/// Quotes a field according to RFC 4180 if it contains the delimiter,
+/// a double quote or a line break
+fn escape(field: &str, delimiter: char) -> Cow<'_, str> {
+    if field.contains(|c| c == delimiter || c == '"' || c == '\r' || c == '\n') {
+        Cow::from(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::from(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use chrono::Utc;
+    use common::{
+        settings,
+        subscription::{CsvConfiguration, FieldMap, SubscriptionData, SubscriptionUuid},
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        event::{EventData, EventMetadata},
+        formats::csv::CsvFormat,
+        output::{OutputDriversContext, OutputFormat},
+        subscription::Subscription,
+    };
+
+    const EVENT: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='NewProcessName'>C:\Windows\System32\cmd.exe</Data><Data Name='CommandLine'>cmd.exe /c "echo a,b"</Data><Data Name='Comment'>line1
+line2</Data></EventData></Event>"#;
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut subscription_data = SubscriptionData::new("Test", "");
+        subscription_data.set_uuid(SubscriptionUuid(
+            Uuid::from_str("8B18D83D-2964-4F35-AC3B-6F4E6FFA727B").unwrap(),
+        ));
+        let subscription = Subscription::from_data(subscription_data, &mut output_context).unwrap();
+
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            chrono::DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    #[test]
+    fn test_csv_format() {
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+
+        let columns = vec![
+            "System.EventID".to_string(),
+            "EventData.NewProcessName".to_string(),
+            "EventData.CommandLine".to_string(),
+            "EventData.Missing".to_string(),
+            "System.Execution".to_string(),
+            "OpenWEC.Principal".to_string(),
+            "EventData.Comment".to_string(),
+        ];
+
+        let formatter = CsvFormat::new(
+            &CsvConfiguration::new(columns.clone(), ',', false),
+            &FieldMap::default(),
+        );
+        assert_eq!(formatter.header(), None);
+        let result =
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+        assert_eq!(
+            result.as_str(),
+            r#"4688,C:\Windows\System32\cmd.exe,"cmd.exe /c ""echo a,b""",,"{""ProcessID"":4,""ThreadID"":196}",WIN10$@WINDOMAIN.LOCAL,"line1
+line2""#
+        );
+
+        let formatter = CsvFormat::new(
+            &CsvConfiguration::new(columns, ';', true),
+            &FieldMap::default(),
+        );
+        assert_eq!(
+            formatter.header().unwrap(),
+            "System.EventID;EventData.NewProcessName;EventData.CommandLine;EventData.Missing;System.Execution;OpenWEC.Principal;EventData.Comment"
+        );
+        let result =
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+        assert_eq!(
+            result.as_str(),
+            r#"4688;C:\Windows\System32\cmd.exe;"cmd.exe /c ""echo a,b""";;"{""ProcessID"":4,""ThreadID"":196}";WIN10$@WINDOMAIN.LOCAL;"line1
+line2""#
+        );
+    }
+
+    #[test]
+    fn test_csv_system_columns() {
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT.to_string()), true);
+
+        let columns = vec![
+            "TimeCreated".to_string(),
+            "Computer".to_string(),
+            "EventID".to_string(),
+            "Level".to_string(),
+            "Missing".to_string(),
+            "EventData.CommandLine".to_string(),
+            "EventData.Comment".to_string(),
+        ];
+        let formatter = CsvFormat::new(
+            &CsvConfiguration::new(columns, ',', true),
+            &FieldMap::default(),
+        );
+        assert_eq!(
+            formatter.header().unwrap(),
+            "TimeCreated,Computer,EventID,Level,Missing,EventData.CommandLine,EventData.Comment"
+        );
+        let result =
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+        assert_eq!(
+            result.as_str(),
+            "2022-12-14T16:06:51.0643605Z,win10.windomain.local,4688,0,,\"cmd.exe /c \"\"echo a,b\"\"\",\"line1\nline2\""
+        );
+    }
+}
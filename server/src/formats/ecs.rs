@@ -0,0 +1,366 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    event::{DataType, DebugDataType, Event, EventData, EventMetadata, ProcessingErrorDataType},
+    output::OutputFormat,
+};
+
+/// Maps events to the Elastic Common Schema (ECS). Windows specific fields
+/// which have no equivalent in ECS are kept under `winlog`, using the names
+/// of Winlogbeat.
+pub struct EcsFormat;
+
+impl OutputFormat for EcsFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        match serde_json::to_vec(&EcsEvent::new(event, metadata)) {
+            Ok(bytes) => Some(Arc::new(bytes)),
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON with EcsFormat: {:?}. Event was: {:?}",
+                    e, event
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Formats a time in RFC 3339, as expected by Elasticsearch
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+#[derive(Debug, Serialize)]
+struct EcsEvent<'a> {
+    #[serde(rename = "@timestamp")]
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    event: EventField<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<Host<'a>>,
+    winlog: Winlog<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+    openwec: OpenWec<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct EventField<'a> {
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<u8>,
+    // Human-readable name of the event, if enabled and known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<&'a str>,
+    created: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Host<'a> {
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Process {
+    pid: u32,
+    thread: Thread,
+}
+
+#[derive(Debug, Serialize)]
+struct Thread {
+    id: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct User<'a> {
+    identifier: &'a str,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Winlog<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider_guid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_source_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    opcode: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keywords: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<Process>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    activity_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    related_activity_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<User<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    event_data: BTreeMap<String, &'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_data: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug_data: Option<&'a DebugDataType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    processing_error_data: Option<&'a ProcessingErrorDataType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary_event_data: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Error {
+    #[serde(rename = "type")]
+    error_type: &'static str,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenWecSubscription<'a> {
+    uuid: &'a str,
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenWec<'a> {
+    ip: String,
+    principal: &'a str,
+    subscription: OpenWecSubscription<'a>,
+}
+
+impl<'a> EcsEvent<'a> {
+    fn new(event: &'a Event, metadata: &'a EventMetadata) -> Self {
+        let system = event.system.as_ref();
+
+        // Events without a valid creation time are timestamped with their
+        // reception time
+        let time_created = system
+            .and_then(|system| system.time_created.as_deref())
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|| metadata.time_received());
+
+        let mut winlog = Winlog::default();
+        if let Some(system) = system {
+            winlog.channel = system.channel.as_deref();
+            winlog.provider_guid = system.provider.guid.as_deref();
+            winlog.event_source_name = system.provider.event_source_name.as_deref();
+            winlog.record_id = system.event_record_id;
+            winlog.version = system.version;
+            winlog.task = system.task;
+            winlog.opcode = system.opcode;
+            winlog.keywords = system.keywords.as_deref();
+            winlog.process = system.execution.as_ref().map(|execution| Process {
+                pid: execution.process_id,
+                thread: Thread {
+                    id: execution.thread_id,
+                },
+            });
+            if let Some(correlation) = system.correlation.as_ref() {
+                winlog.activity_id = correlation.activity_id.as_deref();
+                winlog.related_activity_id = correlation.related_activity_id.as_deref();
+            }
+            winlog.user = system
+                .user_id
+                .as_deref()
+                .map(|identifier| User { identifier });
+        }
+        match &event.data {
+            DataType::EventData(data) => {
+                for (name, value) in data.named_data.iter() {
+                    winlog.event_data.insert(name.clone(), value);
+                }
+                // Unnamed data are numbered like Winlogbeat does
+                for (index, value) in data.unamed_data.iter().enumerate() {
+                    winlog
+                        .event_data
+                        .insert(format!("param{}", index + 1), value);
+                }
+                if let Some(binary) = data.binary.as_ref() {
+                    winlog.event_data.insert("Binary".to_string(), binary);
+                }
+            }
+            DataType::UserData(data) => winlog.user_data = Some(data),
+            DataType::DebugData(data) => winlog.debug_data = Some(data),
+            DataType::ProcessingErrorData(data) => winlog.processing_error_data = Some(data),
+            DataType::BinaryEventData(data) => winlog.binary_event_data = Some(data),
+            DataType::Unknown => (),
+        }
+
+        EcsEvent {
+            timestamp: timestamp(&time_created),
+            message: event
+                .rendering_info
+                .as_ref()
+                .and_then(|rendering_info| rendering_info.message.as_deref()),
+            event: EventField {
+                kind: "event",
+                code: system.map(|system| system.event_id.to_string()),
+                provider: system.and_then(|system| system.provider.name.as_deref()),
+                severity: system.and_then(|system| system.level),
+                action: event.additional.event_name.as_deref().map(String::as_str),
+                created: timestamp(&metadata.time_received()),
+            },
+            host: system.map(|system| Host {
+                name: &system.computer,
+            }),
+            winlog,
+            error: event.additional.error.as_ref().map(|error| Error {
+                error_type: (&error.error_type).into(),
+                message: error.error_type.to_string(),
+            }),
+            openwec: OpenWec {
+                ip: metadata.addr().ip().to_string(),
+                principal: metadata.principal(),
+                subscription: OpenWecSubscription {
+                    uuid: metadata.subscription_uuid(),
+                    name: metadata.subscription_name(),
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const EVENT_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114690</EventRecordID><Correlation ActivityID='{8A3C1B5E-0F43-0001-5A1C-3C8A430FD901}'/><Execution ProcessID='696' ThreadID='4440'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='TargetUserName'>vagrant</Data><Data Name='LogonType'>5</Data></EventData><RenderingInfo Culture='en-US'><Message>An account was successfully logged on.</Message><Level>Information</Level><Task>Logon</Task><Opcode>Info</Opcode><Channel>Security</Channel><Provider>Microsoft Windows security auditing.</Provider><Keywords><Keyword>Audit Success</Keyword></Keywords></RenderingInfo></Event>"#;
+
+    const EVENT_5719: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='NETLOGON'/><EventID Qualifiers='0'>5719</EventID><Version>0</Version><Level>2</Level><Task>0</Task><Opcode>0</Opcode><Keywords>0x80000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:04:59.0817047Z'/><EventRecordID>9466</EventRecordID><Correlation/><Execution ProcessID='0' ThreadID='0'/><Channel>System</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data>WINDOMAIN</Data><Data>The RPC server is unavailable.</Data><Binary>BA0600C0</Binary></EventData></Event>"#;
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    fn format(metadata: &EventMetadata, event: &str) -> Value {
+        let event_data = EventData::new(Arc::new(event.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+        let result = EcsFormat.format(metadata, &event_data).unwrap();
+        serde_json::from_slice(&result).unwrap()
+    }
+
+    #[test]
+    fn test_ecs_format() {
+        let metadata = metadata();
+        assert_eq!(
+            format(&metadata, EVENT_4624),
+            json!({
+                "@timestamp": "2022-12-14T16:06:51.064360500Z",
+                "message": "An account was successfully logged on.",
+                "event": {
+                    "kind": "event",
+                    "code": "4624",
+                    "provider": "Microsoft-Windows-Security-Auditing",
+                    "severity": 0,
+                    "created": "2022-12-14T16:07:03.331Z",
+                },
+                "host": {
+                    "name": "win10.windomain.local",
+                },
+                "winlog": {
+                    "channel": "Security",
+                    "provider_guid": "{54849625-5478-4994-a5ba-3e3b0328c30d}",
+                    "record_id": 114690,
+                    "version": 2,
+                    "task": 12544,
+                    "opcode": 0,
+                    "keywords": "0x8020000000000000",
+                    "process": {
+                        "pid": 696,
+                        "thread": {
+                            "id": 4440,
+                        },
+                    },
+                    "activity_id": "{8A3C1B5E-0F43-0001-5A1C-3C8A430FD901}",
+                    "event_data": {
+                        "LogonType": "5",
+                        "TargetUserName": "vagrant",
+                    },
+                },
+                "openwec": {
+                    "ip": "192.168.58.100",
+                    "principal": "WIN10$@WINDOMAIN.LOCAL",
+                    "subscription": {
+                        "uuid": metadata.subscription_uuid(),
+                        "name": "Test",
+                    },
+                },
+            })
+        );
+
+        // Unnamed data are numbered
+        let value = format(&metadata, EVENT_5719);
+        assert_eq!(value["@timestamp"], "2022-12-14T16:04:59.081704700Z");
+        assert_eq!(value["event"]["code"], "5719");
+        assert_eq!(value["event"]["severity"], 2);
+        assert!(value.get("message").is_none());
+        assert_eq!(
+            value["winlog"]["event_data"],
+            json!({
+                "param1": "WINDOMAIN",
+                "param2": "The RPC server is unavailable.",
+                "Binary": "BA0600C0",
+            })
+        );
+    }
+
+    #[test]
+    fn test_ecs_timestamp() {
+        let metadata = metadata();
+        // Events created with a time offset are converted to UTC
+        let event = EVENT_5719.replace("2022-12-14T16:04:59.0817047Z", "2022-12-14T17:04:59+01:00");
+        let value = format(&metadata, &event);
+        assert_eq!(value["@timestamp"], "2022-12-14T16:04:59Z");
+
+        // The reception time is used when the creation time is invalid
+        let event = EVENT_5719.replace("2022-12-14T16:04:59.0817047Z", "yesterday");
+        let value = format(&metadata, &event);
+        assert_eq!(value["@timestamp"], "2022-12-14T16:07:03.331Z");
+    }
+}
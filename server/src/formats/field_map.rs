@@ -0,0 +1,352 @@
+use std::sync::Arc;
+
+use common::subscription::{FieldMap, FormatOptions, RAW_XML_KEY};
+use log::{debug, warn};
+use serde_json::{Map, Value};
+
+use crate::{
+    event::{Event, EventData, EventMetadata},
+    formats::collector,
+    output::OutputFormat,
+};
+
+type ToValue = fn(Event, &EventMetadata) -> serde_json::Result<Value>;
+
+/// Wraps a format based on the Json structure and renames its fields
+/// according to a field map. Information about the collector is added
+/// afterwards if `collector_info` is set.
+///
+/// The raw XML of events is added before fields are renamed, so that it can
+/// be renamed like any other field, whereas the timestamp field is added
+/// afterwards since its path is already the expected one.
+pub struct MappedFormat {
+    to_value: ToValue,
+    field_map: FieldMap,
+    collector_info: bool,
+    options: FormatOptions,
+}
+
+impl MappedFormat {
+    pub fn new(to_value: ToValue, field_map: &FieldMap, collector_info: bool) -> Self {
+        Self {
+            to_value,
+            field_map: field_map.clone(),
+            collector_info,
+            options: FormatOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: &FormatOptions) -> Self {
+        self.options = options.clone();
+        self
+    }
+}
+
+impl OutputFormat for MappedFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let mut value = match (self.to_value)(event.clone(), metadata) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON: {:?}. Event was: {:?}",
+                    e, event
+                );
+                return None;
+            }
+        };
+        if self.options.include_raw_xml() {
+            insert(
+                &mut value,
+                &format!("OpenWEC.{}", RAW_XML_KEY),
+                Value::String(data.raw().to_string()),
+            );
+        }
+        let mut value = apply(&self.field_map, value);
+        if self.collector_info {
+            collector::insert(&mut value, metadata);
+        }
+        if let Some(timestamp_field) = self.options.timestamp_field() {
+            if let Some(time_created) = event
+                .system
+                .as_ref()
+                .and_then(|system| system.time_created.as_ref())
+            {
+                insert(
+                    &mut value,
+                    timestamp_field,
+                    Value::String(time_created.clone()),
+                );
+            }
+        }
+        let result = if self.options.pretty() {
+            serde_json::to_vec_pretty(&value)
+        } else {
+            serde_json::to_vec(&value)
+        };
+        match result {
+            Ok(bytes) => Some(Arc::new(bytes)),
+            Err(e) => {
+                warn!("Failed to serialize renamed event in JSON: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Renames the fields of `value` according to `field_map`.
+///
+/// Mapped fields are removed from their original location and inserted at
+/// their target path. Unmapped fields are kept under their original names,
+/// unless `drop_unmapped` is set. If a target path is already used by an
+/// unmapped field, the mapped field always wins.
+pub fn apply(field_map: &FieldMap, mut value: Value) -> Value {
+    let mut mapped = Vec::with_capacity(field_map.fields().len());
+    for (source, target) in field_map.fields() {
+        if let Some(field) = take(&mut value, source) {
+            mapped.push((target, field));
+        }
+    }
+
+    let mut result = if field_map.drop_unmapped() {
+        Value::Object(Map::new())
+    } else {
+        value
+    };
+    for (target, field) in mapped {
+        if insert(&mut result, target, field) {
+            debug!("Renamed field {} replaced an existing field", target);
+        }
+    }
+    result
+}
+
+/// Removes the field at the dotted `path` and returns it
+fn take(value: &mut Value, path: &str) -> Option<Value> {
+    let (parents, key) = match path.rsplit_once('.') {
+        Some((parents, key)) => (Some(parents), key),
+        None => (None, path),
+    };
+    let mut current = value;
+    if let Some(parents) = parents {
+        for segment in parents.split('.') {
+            current = current.as_object_mut()?.get_mut(segment)?;
+        }
+    }
+    current.as_object_mut()?.remove(key)
+}
+
+/// Inserts `field` at the dotted `path`, creating intermediate objects.
+/// Returns whether an existing value has been replaced.
+fn insert(value: &mut Value, path: &str, field: Value) -> bool {
+    let mut replaced = false;
+    let mut current = value;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        if !current.is_object() {
+            replaced = true;
+            *current = Value::Object(Map::new());
+        }
+        // current is an object
+        let map = current.as_object_mut().expect("Value must be an object");
+        if segments.peek().is_none() {
+            return map.insert(segment.to_owned(), field).is_some() || replaced;
+        }
+        current = map
+            .entry(segment.to_owned())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, net::SocketAddr, str::FromStr};
+
+    use common::{
+        settings,
+        subscription::{SubscriptionData, SubscriptionOutputFormat},
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        output::{get_formatter, OutputDriversContext},
+        subscription::Subscription,
+    };
+
+    const EVENT_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114690</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='TargetUserName'>vagrant</Data></EventData></Event>"#;
+
+    fn field_map(fields: &[(&str, &str)], drop_unmapped: bool) -> FieldMap {
+        let fields: BTreeMap<String, String> = fields
+            .iter()
+            .map(|(source, target)| (source.to_string(), target.to_string()))
+            .collect();
+        FieldMap::try_new(fields, drop_unmapped).unwrap()
+    }
+
+    fn event() -> Value {
+        json!({
+            "System": {
+                "EventID": 4624,
+                "Computer": "win10.windomain.local",
+                "Channel": "Security"
+            },
+            "EventData": {
+                "TargetUserName": "vagrant"
+            },
+            "host": "unmapped host",
+            "OpenWEC": {
+                "IpAddress": "192.168.58.100"
+            }
+        })
+    }
+
+    #[test]
+    fn test_rename_fields() {
+        let map = field_map(
+            &[
+                ("System.Computer", "host"),
+                ("System.EventID", "event_code"),
+                ("EventData.TargetUserName", "user.name"),
+                ("System.Missing", "missing"),
+            ],
+            false,
+        );
+        assert_eq!(
+            apply(&map, event()),
+            json!({
+                "System": {
+                    "Channel": "Security"
+                },
+                "EventData": {},
+                // Mapped fields win over unmapped fields
+                "host": "win10.windomain.local",
+                "event_code": 4624,
+                "user": {
+                    "name": "vagrant"
+                },
+                "OpenWEC": {
+                    "IpAddress": "192.168.58.100"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_drop_unmapped() {
+        let map = field_map(
+            &[
+                ("System.Computer", "host"),
+                ("System.EventID", "event_code"),
+                ("OpenWEC.IpAddress", "source.ip"),
+            ],
+            true,
+        );
+        assert_eq!(
+            apply(&map, event()),
+            json!({
+                "host": "win10.windomain.local",
+                "event_code": 4624,
+                "source": {
+                    "ip": "192.168.58.100"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_collisions_are_deterministic() {
+        // The target path goes through an unmapped scalar field,
+        // which is replaced
+        let map = field_map(&[("System.Computer", "host.name")], false);
+        let expected = json!({
+            "System": {
+                "EventID": 4624,
+                "Channel": "Security"
+            },
+            "EventData": {
+                "TargetUserName": "vagrant"
+            },
+            "host": {
+                "name": "win10.windomain.local"
+            },
+            "OpenWEC": {
+                "IpAddress": "192.168.58.100"
+            }
+        });
+        for _ in 0..10 {
+            assert_eq!(apply(&map, event()), expected);
+        }
+        assert!(insert(&mut event(), "host.name", json!("a")));
+        assert!(insert(&mut event(), "host", json!("a")));
+        assert!(!insert(&mut event(), "hostname", json!("a")));
+    }
+
+    fn format(field_map: &FieldMap, options: &FormatOptions) -> String {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        let event_data = EventData::new(Arc::new(EVENT_4624.to_string()), true);
+        let formatter = get_formatter(&SubscriptionOutputFormat::Json, field_map, false, options);
+        let result = formatter.format(&metadata, &event_data).unwrap();
+        String::from_utf8(result.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_format_options() {
+        // Without options, events are written on a single line
+        let compact = format(&FieldMap::default(), &FormatOptions::default());
+        assert!(!compact.contains('\n'));
+        let value: Value = serde_json::from_str(&compact).unwrap();
+        assert!(value.get("@timestamp").is_none());
+        assert!(value["OpenWEC"].get(RAW_XML_KEY).is_none());
+
+        let options = FormatOptions::try_new(true, Some("@timestamp".to_string()), true).unwrap();
+        let pretty = format(&FieldMap::default(), &options);
+        assert!(pretty.contains("\n  \"@timestamp\": \"2022-12-14T16:06:51.0643605Z\""));
+        let value: Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(value["OpenWEC"][RAW_XML_KEY], EVENT_4624);
+
+        // Pretty printing only changes the layout
+        let mut pretty_value = value.clone();
+        pretty_value.as_object_mut().unwrap().remove("@timestamp");
+        pretty_value["OpenWEC"]
+            .as_object_mut()
+            .unwrap()
+            .remove(RAW_XML_KEY);
+        assert_eq!(
+            pretty_value,
+            serde_json::from_str::<Value>(&compact).unwrap()
+        );
+
+        // The raw XML can be renamed, and the timestamp field is kept even
+        // if unmapped fields are dropped
+        let map = field_map(&[("OpenWEC.RawXml", "event.original")], true);
+        let value: Value = serde_json::from_str(&format(&map, &options)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "event": {
+                    "original": EVENT_4624
+                },
+                "@timestamp": "2022-12-14T16:06:51.0643605Z"
+            })
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use log::warn;
 use serde::Serialize;
 
@@ -11,11 +12,11 @@ use crate::{
 pub struct JsonFormat;
 
 impl OutputFormat for JsonFormat {
-    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<String>> {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
         if let Some(event) = data.event() {
             let json_event = JsonEvent::new(event.clone(), metadata);
-            match serde_json::to_string(&json_event) {
-                Ok(str) => Some(Arc::new(str)),
+            match serde_json::to_vec(&json_event) {
+                Ok(bytes) => Some(Arc::new(bytes)),
                 Err(e) => {
                     warn!(
                         "Failed to serialize event in JSON: {:?}. Event was: {:?}",
@@ -31,6 +32,14 @@ impl OutputFormat for JsonFormat {
     }
 }
 
+/// Returns the Json representation of an event, as written by the Json format
+pub fn to_value(
+    event: crate::event::Event,
+    metadata: &EventMetadata,
+) -> serde_json::Result<serde_json::Value> {
+    serde_json::to_value(JsonEvent::new(event, metadata))
+}
+
 #[derive(Debug, Serialize)]
 struct EventDataType {
     #[serde(flatten, skip_serializing_if = "HashMap::is_empty")]
@@ -202,6 +211,8 @@ impl From<crate::event::ErrorInfo> for ErrorInfo {
 struct JsonEvent {
     #[serde(rename = "System", skip_serializing_if = "Option::is_none")]
     system: Option<System>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_name: Option<Arc<String>>,
     #[serde(flatten, skip_serializing_if = "DataType::is_unknown")]
     data: DataType,
     #[serde(rename = "RenderingInfo", skip_serializing_if = "Option::is_none")]
@@ -214,6 +225,7 @@ impl JsonEvent {
     pub fn new(event: crate::event::Event, metadata: &EventMetadata) -> Self {
         Self {
             system: event.system.map(Into::into),
+            event_name: event.additional.event_name.clone(),
             data: event.data.into(),
             rendering_info: event.rendering_info.map(Into::into),
             additional: Additional::new(event.additional, metadata),
@@ -221,6 +233,26 @@ impl JsonEvent {
     }
 }
 
+#[derive(Debug, Serialize)]
+struct ClockSkewInfo {
+    #[serde(rename = "Skew")]
+    skew: i64,
+    #[serde(
+        rename = "OriginalTimeCreated",
+        skip_serializing_if = "Option::is_none"
+    )]
+    original_time_created: Option<String>,
+}
+
+impl From<crate::event::ClockSkewInfo> for ClockSkewInfo {
+    fn from(value: crate::event::ClockSkewInfo) -> Self {
+        Self {
+            skew: value.skew,
+            original_time_created: value.original_time_created,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct Additional {
     #[serde(rename = "IpAddress")]
@@ -235,6 +267,8 @@ struct Additional {
     node: Option<String>,
     #[serde(rename = "Error", skip_serializing_if = "Option::is_none")]
     error: Option<ErrorInfo>,
+    #[serde(rename = "ClockSkew", skip_serializing_if = "Option::is_none")]
+    clock_skew: Option<ClockSkewInfo>,
 }
 
 impl Additional {
@@ -253,6 +287,7 @@ impl Additional {
                 server_revision: metadata.subscription_server_revision().cloned(),
             },
             error: additional.error.map(Into::into),
+            clock_skew: additional.clock_skew.map(Into::into),
         }
     }
 }
@@ -372,6 +407,9 @@ struct System {
     keywords: Option<String>,
     #[serde(rename = "TimeCreated", skip_serializing_if = "Option::is_none")]
     time_created: Option<String>,
+    // TimeCreated as received, if it has been changed by its normalization
+    #[serde(rename = "TimeCreated_raw", skip_serializing_if = "Option::is_none")]
+    time_created_raw: Option<String>,
     #[serde(rename = "EventRecordID", skip_serializing_if = "Option::is_none")]
     event_record_id: Option<u64>,
     #[serde(rename = "Correlation", skip_serializing_if = "Option::is_none")]
@@ -390,6 +428,14 @@ struct System {
 
 impl From<crate::event::System> for System {
     fn from(value: crate::event::System) -> Self {
+        // The received value is only kept if the normalization changed it
+        let (time_created, time_created_raw) = match value.time_created {
+            Some(raw) => match normalize_time_created(&raw) {
+                Some(normalized) if normalized != raw => (Some(normalized), Some(raw)),
+                _ => (Some(raw), None),
+            },
+            None => (None, None),
+        };
         Self {
             provider: value.provider.into(),
             event_id: value.event_id,
@@ -399,7 +445,8 @@ impl From<crate::event::System> for System {
             task: value.task,
             opcode: value.opcode,
             keywords: value.keywords,
-            time_created: value.time_created,
+            time_created,
+            time_created_raw,
             event_record_id: value.event_record_id,
             correlation: value.correlation.map(Into::into),
             execution: value.execution.map(Into::into),
@@ -411,6 +458,53 @@ impl From<crate::event::System> for System {
     }
 }
 
+/// Number of 100-nanosecond intervals between 1601-01-01 (the FILETIME
+/// epoch) and 1970-01-01
+const FILETIME_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+
+/// Whether a `TimeCreated` value is a FILETIME rather than RFC 3339
+fn is_filetime(raw: &str) -> bool {
+    !raw.is_empty() && raw.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Parses a `TimeCreated` value, either in RFC 3339 or as a FILETIME.
+/// Returns None if it can not be parsed.
+pub fn parse_time_created(raw: &str) -> Option<DateTime<Utc>> {
+    if is_filetime(raw) {
+        let ticks = raw.parse::<i64>().ok()?.checked_sub(FILETIME_UNIX_EPOCH)?;
+        DateTime::from_timestamp(
+            ticks.div_euclid(10_000_000),
+            u32::try_from(ticks.rem_euclid(10_000_000) * 100).ok()?,
+        )
+    } else {
+        Some(DateTime::parse_from_rfc3339(raw).ok()?.with_timezone(&Utc))
+    }
+}
+
+/// Normalizes a `TimeCreated` value, either in RFC 3339 or as a FILETIME,
+/// into RFC 3339 in UTC, keeping its sub-second precision.
+/// Returns None if it can not be parsed.
+fn normalize_time_created(raw: &str) -> Option<String> {
+    let time = parse_time_created(raw)?;
+    let digits = if is_filetime(raw) {
+        // FILETIME has a precision of 100 nanoseconds
+        7
+    } else {
+        raw.split_once('.')
+            .map_or(0, |(_, fraction)| {
+                fraction.bytes().take_while(u8::is_ascii_digit).count()
+            })
+            .min(9)
+    };
+    let seconds = time.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let seconds = seconds.strip_suffix('Z')?;
+    if digits == 0 {
+        return Some(format!("{}Z", seconds));
+    }
+    let nanos = format!("{:09}", time.timestamp_subsec_nanos());
+    Some(format!("{}.{}Z", seconds, &nanos[..digits]))
+}
+
 #[derive(Debug, Default, Serialize, Clone)]
 struct RenderingInfo {
     #[serde(rename = "Message", skip_serializing_if = "Option::is_none")]
@@ -448,18 +542,23 @@ impl From<crate::event::RenderingInfo> for RenderingInfo {
 
 #[cfg(test)]
 mod tests {
-    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+    use std::{collections::BTreeSet, net::SocketAddr, str::FromStr, sync::Arc};
 
     use chrono::Utc;
-    use common::{settings, subscription::{SubscriptionData, SubscriptionUuid}};
+    use common::{
+        settings,
+        subscription::{ContentFormat, RenderingElement, SubscriptionData, SubscriptionUuid},
+    };
     use serde_json::Value;
     use uuid::Uuid;
 
     use crate::{
         event::{EventData, EventMetadata},
-        formats::json::JsonFormat,
+        event_names::EventNames,
+        formats::{json::JsonFormat, raw::RawFormat},
         output::{OutputDriversContext, OutputFormat},
         subscription::Subscription,
+        trim::RenderingTrimmer,
     };
 
     const EVENT_4688: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='SubjectUserName'>WIN10$</Data><Data Name='SubjectDomainName'>WINDOMAIN</Data><Data Name='SubjectLogonId'>0x3e7</Data><Data Name='NewProcessId'>0x3a8</Data><Data Name='NewProcessName'>C:\Program Files (x86)\Microsoft\EdgeUpdate\MicrosoftEdgeUpdate.exe</Data><Data Name='TokenElevationType'>%%1936</Data><Data Name='ProcessId'>0x240</Data><Data Name='CommandLine'></Data><Data Name='TargetUserSid'>S-1-0-0</Data><Data Name='TargetUserName'>-</Data><Data Name='TargetDomainName'>-</Data><Data Name='TargetLogonId'>0x0</Data><Data Name='ParentProcessName'>C:\Windows\System32\services.exe</Data><Data Name='MandatoryLabel'>S-1-16-16384</Data></EventData><RenderingInfo Culture='en-US'><Message>A new process has been created.
@@ -530,7 +629,7 @@ Type 3 is a limited token with administrative privileges removed and administrat
         let formatter: JsonFormat = JsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_4688_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
@@ -577,7 +676,7 @@ Licensing Status=
         let formatter: JsonFormat = JsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_1003_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
@@ -623,7 +722,7 @@ If this computer is a domain controller for the specified domain, it sets up the
         let formatter: JsonFormat = JsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_5719_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
@@ -663,7 +762,7 @@ If this computer is a domain controller for the specified domain, it sets up the
         let formatter: JsonFormat = JsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_6013_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
@@ -703,7 +802,7 @@ If this computer is a domain controller for the specified domain, it sets up the
         let formatter: JsonFormat = JsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_1100_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
@@ -743,9 +842,185 @@ If this computer is a domain controller for the specified domain, it sets up the
         let formatter: JsonFormat = JsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_111_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
     }
+
+    #[test]
+    fn test_serialize_event_name() {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        let event_name = |event_names: &EventNames, raw: &str| {
+            let mut event_data = EventData::new(Arc::new(raw.to_string()), true);
+            event_names.apply(&mut event_data);
+            let result = JsonFormat.format(&metadata, &event_data).unwrap();
+            let value: Value = serde_json::from_slice(&result).unwrap();
+            value
+                .get("event_name")
+                .map(|name| name.as_str().unwrap().to_string())
+        };
+
+        let mut event_names = EventNames::builtin();
+        assert_eq!(
+            event_name(&event_names, EVENT_4688),
+            Some("A new process has been created".to_string())
+        );
+        // Unknown events do not get the field
+        assert_eq!(event_name(&event_names, EVENT_1003), None);
+
+        event_names
+            .extend(
+                r#"
+                ["Microsoft-Windows-Security-Auditing"]
+                4688 = "Process creation"
+
+                ["Microsoft-Windows-Security-SPP"]
+                1003 = "Licensing status check completed"
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            event_name(&event_names, EVENT_4688),
+            Some("Process creation".to_string())
+        );
+        assert_eq!(
+            event_name(&event_names, EVENT_1003),
+            Some("Licensing status check completed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_serialize_trimmed_rendering() {
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_trim_rendering(BTreeSet::from([
+            RenderingElement::Message,
+            RenderingElement::Keywords,
+        ]));
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription = Subscription::from_data(data.clone(), &mut output_context).unwrap();
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        let expected: Value = serde_json::from_str(EVENT_4688_JSON).unwrap();
+
+        let trimmer = RenderingTrimmer::from_subscription(&data).unwrap();
+        let mut event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        trimmer.apply(&mut event_data);
+        let value: Value =
+            serde_json::from_slice(&JsonFormat.format(&metadata, &event_data).unwrap()).unwrap();
+
+        let rendering_info = value.get("RenderingInfo").unwrap();
+        assert!(rendering_info.get("Message").is_none());
+        assert!(rendering_info.get("Keywords").is_none());
+        assert_eq!(rendering_info["Level"], "Information");
+        assert_eq!(rendering_info["Culture"], "en-US");
+        // Structured data is preserved
+        assert_eq!(value["System"], expected["System"]);
+        assert_eq!(value["EventData"], expected["EventData"]);
+        // Raw content is not affected
+        assert_eq!(
+            RawFormat.format(&metadata, &event_data).unwrap().as_slice(),
+            EVENT_4688.as_bytes()
+        );
+
+        // Trimming every sub-element removes RenderingInfo
+        let trimmer = RenderingTrimmer::new(BTreeSet::from([
+            RenderingElement::Message,
+            RenderingElement::Level,
+            RenderingElement::Task,
+            RenderingElement::Opcode,
+            RenderingElement::Channel,
+            RenderingElement::Provider,
+            RenderingElement::Keywords,
+        ]));
+        let mut event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        trimmer.apply(&mut event_data);
+        let value: Value =
+            serde_json::from_slice(&JsonFormat.format(&metadata, &event_data).unwrap()).unwrap();
+        assert!(value.get("RenderingInfo").is_none());
+        assert_eq!(value["EventData"], expected["EventData"]);
+
+        // Nothing is trimmed when events are not rendered
+        data.set_content_format(ContentFormat::Raw);
+        assert!(RenderingTrimmer::from_subscription(&data).is_none());
+    }
+
+    #[test]
+    fn test_serialize_time_created() {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        let time_created = |raw: &str| {
+            let event = EVENT_4688.replace(
+                "SystemTime='2022-12-14T16:06:51.0643605Z'",
+                &format!("SystemTime='{}'", raw),
+            );
+            let event_data = EventData::new(Arc::new(event), true);
+            let result = JsonFormat.format(&metadata, &event_data).unwrap();
+            let value: Value = serde_json::from_slice(&result).unwrap();
+            (
+                value["System"]["TimeCreated"].as_str().unwrap().to_string(),
+                value["System"]["TimeCreated_raw"]
+                    .as_str()
+                    .map(str::to_string),
+            )
+        };
+
+        // Converted to UTC, keeping sub-second precision
+        assert_eq!(
+            time_created("2022-12-14T17:06:51.0643605+01:00"),
+            (
+                "2022-12-14T16:06:51.0643605Z".to_string(),
+                Some("2022-12-14T17:06:51.0643605+01:00".to_string())
+            )
+        );
+        // The received value is not duplicated if it is already normalized
+        assert_eq!(
+            time_created("2022-12-14T16:06:51.0643605Z"),
+            ("2022-12-14T16:06:51.0643605Z".to_string(), None)
+        );
+        assert_eq!(
+            time_created("2022-12-14t16:06:51z"),
+            (
+                "2022-12-14T16:06:51Z".to_string(),
+                Some("2022-12-14t16:06:51z".to_string())
+            )
+        );
+        // FILETIME (100-nanosecond intervals since 1601-01-01)
+        assert_eq!(
+            time_created("133155076110643605"),
+            (
+                "2022-12-14T16:06:51.0643605Z".to_string(),
+                Some("133155076110643605".to_string())
+            )
+        );
+        // Values which can not be parsed are kept as is
+        assert_eq!(time_created("yesterday"), ("yesterday".to_string(), None));
+    }
 }
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::warn;
+
+use crate::{
+    event::{DataType, EventData, EventMetadata},
+    formats::cef::severity,
+    output::OutputFormat,
+};
+
+// Java SimpleDateFormat pattern of devTime, as expected by QRadar
+const DEV_TIME_FORMAT: &str = "yyyy-MM-dd'T'HH:mm:ss.SSSXXX";
+
+pub struct LeefFormat;
+
+impl OutputFormat for LeefFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let system = event.system.as_ref();
+
+        let dev_time = system
+            .and_then(|system| system.time_created.as_deref())
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|| metadata.time_received())
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+
+        let mut attributes = vec![
+            ("devTime".to_string(), dev_time),
+            ("devTimeFormat".to_string(), DEV_TIME_FORMAT.to_string()),
+            (
+                "sev".to_string(),
+                severity(system.and_then(|system| system.level)).to_string(),
+            ),
+            ("src".to_string(), metadata.addr().ip().to_string()),
+        ];
+        if let Some(system) = system {
+            attributes.push(("identHostName".to_string(), system.computer.clone()));
+            if let Some(event_record_id) = system.event_record_id {
+                attributes.push(("EventRecordID".to_string(), event_record_id.to_string()));
+            }
+            if let Some(channel) = system.channel.as_ref() {
+                attributes.push(("Channel".to_string(), channel.clone()));
+            }
+            if let Some(provider) = system.provider.name.as_ref() {
+                attributes.push(("Provider".to_string(), provider.clone()));
+            }
+        }
+        attributes.push((
+            "Subscription".to_string(),
+            metadata.subscription_name().to_string(),
+        ));
+        if let Some(event_name) = event.additional.event_name.as_ref() {
+            attributes.push(("EventName".to_string(), event_name.to_string()));
+        }
+        if let DataType::EventData(data) = &event.data {
+            // Sort named data so that the output does not depend on the
+            // order of the HashMap
+            let mut named_data: Vec<(&String, &String)> = data.named_data.iter().collect();
+            named_data.sort();
+            for (name, value) in named_data {
+                attributes.push((key(name), value.clone()));
+            }
+        }
+        if let Some(message) = event
+            .rendering_info
+            .as_ref()
+            .and_then(|rendering_info| rendering_info.message.as_ref())
+        {
+            attributes.push(("Message".to_string(), message.clone()));
+        }
+
+        let attributes = attributes
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, escape(value)))
+            .collect::<Vec<String>>()
+            .join("\t");
+
+        // Attributes are delimited by tabs, declared as "x09" in the header
+        let record = format!(
+            "LEEF:2.0|Microsoft|Windows|{}|{}|x09|{}",
+            env!("CARGO_PKG_VERSION"),
+            system
+                .map(|system| system.event_id.to_string())
+                .unwrap_or_default(),
+            attributes
+        );
+        Some(Arc::new(record.into_bytes()))
+    }
+}
+
+/// Attribute keys can not contain spaces, `=` or the delimiter
+fn key(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Escapes an attribute value: `\` and tabs are preceded by a backslash,
+/// line breaks are encoded as `\r` and `\n` and NUL characters left over
+/// from UTF-16 strings are removed
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            '\0' => (),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use chrono::Utc;
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const EVENT_4688: &str = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='NewProcessName'>C:\\Users\\José\\cmd.exe</Data><Data Name='CommandLine'>cmd.exe /c \"echo a\tb\"</Data></EventData><RenderingInfo Culture='fr-FR'><Message>Un nouveau processus a été créé.\n\nSujet :</Message><Level>Information</Level><Task>Process Creation</Task><Opcode>Info</Opcode><Channel>Security</Channel><Provider>Microsoft Windows security auditing.</Provider><Keywords><Keyword>Audit Success</Keyword></Keywords></RenderingInfo></Event>";
+
+    const EVENT_5719: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='NETLOGON'/><EventID Qualifiers='0'>5719</EventID><Version>0</Version><Level>2</Level><Task>0</Task><Opcode>0</Opcode><Keywords>0x80000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:04:59.0817047Z'/><EventRecordID>9466</EventRecordID><Correlation/><Execution ProcessID='0' ThreadID='0'/><Channel>System</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data>WINDOMAIN</Data></EventData></Event>"#;
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            chrono::DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    #[test]
+    fn test_leef_format() {
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+
+        let result =
+            String::from_utf8(LeefFormat.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+        let (header, attributes) = result.split_at(result.find("x09|").unwrap() + 4);
+        assert_eq!(
+            header,
+            format!(
+                "LEEF:2.0|Microsoft|Windows|{}|4688|x09|",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert_eq!(
+            attributes.split('\t').collect::<Vec<&str>>(),
+            vec![
+                "devTime=2022-12-14T16:06:51.064Z",
+                "devTimeFormat=yyyy-MM-dd'T'HH:mm:ss.SSSXXX",
+                "sev=3",
+                "src=192.168.58.100",
+                "identHostName=win10.windomain.local",
+                "EventRecordID=114689",
+                "Channel=Security",
+                "Provider=Microsoft-Windows-Security-Auditing",
+                "Subscription=Test",
+                "CommandLine=cmd.exe /c \"echo a\\tb\"",
+                "NewProcessName=C:\\\\Users\\\\José\\\\cmd.exe",
+                "Message=Un nouveau processus a été créé.\\n\\nSujet :",
+            ]
+        );
+
+        // Without rendering info nor named data
+        let event_data = EventData::new(Arc::new(EVENT_5719.to_string()), true);
+        let result =
+            String::from_utf8(LeefFormat.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+        assert_eq!(
+            result.as_str(),
+            format!(
+                "LEEF:2.0|Microsoft|Windows|{}|5719|x09|devTime=2022-12-14T16:04:59.081Z\tdevTimeFormat=yyyy-MM-dd'T'HH:mm:ss.SSSXXX\tsev=7\tsrc=192.168.58.100\tidentHostName=win10.windomain.local\tEventRecordID=9466\tChannel=System\tProvider=NETLOGON\tSubscription=Test",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\tb\\c\r\nd=e\0"), "a\\tb\\\\c\\r\\nd=e");
+        assert_eq!(key("Target User=Name\t"), "Target_User_Name_");
+    }
+}
@@ -1,4 +1,15 @@
+pub mod avro;
+pub mod cef;
+pub mod collector;
+pub mod csv;
+pub mod ecs;
+pub mod field_map;
 pub mod json;
+pub mod leef;
+pub mod msgpack;
+pub mod nxlog;
+pub mod protobuf;
 pub mod raw;
 pub mod raw_json;
-pub mod nxlog;
\ No newline at end of file
+pub mod rfc5424;
+pub mod template;
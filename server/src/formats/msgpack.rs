@@ -0,0 +1,337 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use log::warn;
+use serde_json::{Number, Value};
+
+use crate::{
+    event::{EventData, EventMetadata},
+    formats::json,
+    output::OutputFormat,
+};
+
+/// Encodes events in MessagePack, using the same structure as the Json
+/// format. Map keys are sorted so that an event is always encoded the same
+/// way.
+pub struct MsgpackFormat;
+
+impl OutputFormat for MsgpackFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let value = match json::to_value(event.clone(), metadata) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON: {:?}. Event was: {:?}",
+                    e, event
+                );
+                return None;
+            }
+        };
+        let mut bytes = Vec::new();
+        match encode(&value, &mut bytes) {
+            Ok(()) => Some(Arc::new(bytes)),
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in MessagePack: {:?}. Event was: {:?}",
+                    e, event
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Writes the MessagePack representation of `value` in `out`, always using
+/// the smallest representation of numbers and lengths
+fn encode(value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(number) => encode_number(number, out),
+        Value::String(string) => encode_str(string, out)?,
+        Value::Array(array) => {
+            encode_length(array.len(), 0x90, 16, [None, Some(0xdc), Some(0xdd)], out)?;
+            for item in array {
+                encode(item, out)?;
+            }
+        }
+        Value::Object(object) => {
+            encode_length(object.len(), 0x80, 16, [None, Some(0xde), Some(0xdf)], out)?;
+            let mut entries: Vec<(&String, &Value)> = object.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, item) in entries {
+                encode_str(key, out)?;
+                encode(item, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_str(string: &str, out: &mut Vec<u8>) -> Result<()> {
+    encode_length(
+        string.len(),
+        0xa0,
+        32,
+        [Some(0xd9), Some(0xda), Some(0xdb)],
+        out,
+    )?;
+    out.extend_from_slice(string.as_bytes());
+    Ok(())
+}
+
+/// Writes the length of a string, an array or a map. Short lengths are
+/// stored in the marker itself (`fix_marker`), larger ones are written
+/// after the marker of their size (8, 16 or 32 bits).
+fn encode_length(
+    len: usize,
+    fix_marker: u8,
+    fix_max: usize,
+    markers: [Option<u8>; 3],
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    match (len, markers) {
+        (len, _) if len < fix_max => out.push(fix_marker | len as u8),
+        (len, [Some(marker), _, _]) if len <= u8::MAX as usize => {
+            out.push(marker);
+            out.push(len as u8);
+        }
+        (len, [_, Some(marker), _]) if len <= u16::MAX as usize => {
+            out.push(marker);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        (len, [_, _, Some(marker)]) if len <= u32::MAX as usize => {
+            out.push(marker);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+        (len, _) => bail!("Length {} is too large for MessagePack", len),
+    }
+    Ok(())
+}
+
+fn encode_number(number: &Number, out: &mut Vec<u8>) {
+    if let Some(n) = number.as_u64() {
+        if n < 0x80 {
+            out.push(n as u8);
+        } else if n <= u8::MAX as u64 {
+            out.push(0xcc);
+            out.push(n as u8);
+        } else if n <= u16::MAX as u64 {
+            out.push(0xcd);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        } else if n <= u32::MAX as u64 {
+            out.push(0xce);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        } else {
+            out.push(0xcf);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    } else if let Some(n) = number.as_i64() {
+        // Only negative numbers are left
+        if n >= -32 {
+            out.push(n as u8);
+        } else if n >= i8::MIN as i64 {
+            out.push(0xd0);
+            out.push(n as u8);
+        } else if n >= i16::MIN as i64 {
+            out.push(0xd1);
+            out.extend_from_slice(&(n as i16).to_be_bytes());
+        } else if n >= i32::MIN as i64 {
+            out.push(0xd2);
+            out.extend_from_slice(&(n as i32).to_be_bytes());
+        } else {
+            out.push(0xd3);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    } else {
+        out.push(0xcb);
+        out.extend_from_slice(&number.as_f64().unwrap_or_default().to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use chrono::{DateTime, Utc};
+    use common::{settings, subscription::SubscriptionData};
+    use serde_json::{json, Map};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const EVENT_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114690</EventRecordID><Correlation ActivityID='{8A3C1B5E-0F43-0001-5A1C-3C8A430FD901}'/><Execution ProcessID='696' ThreadID='4440'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='TargetUserName'>vagrant</Data><Data Name='LogonType'>5</Data><Data Name='ProcessName'>C:\Windows\System32\services.exe</Data></EventData><RenderingInfo Culture='en-US'><Message>An account was successfully logged on.</Message><Level>Information</Level><Task>Logon</Task><Opcode>Info</Opcode><Channel>Security</Channel><Provider>Microsoft Windows security auditing.</Provider><Keywords><Keyword>Audit Success</Keyword></Keywords></RenderingInfo></Event>"#;
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    /// Minimal MessagePack decoder, supporting what `encode` produces
+    fn decode(bytes: &mut &[u8]) -> Value {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> &'a [u8] {
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+            head
+        }
+        fn uint(bytes: &mut &[u8], size: usize) -> u64 {
+            take(bytes, size)
+                .iter()
+                .fold(0, |acc, byte| (acc << 8) | *byte as u64)
+        }
+        fn string(bytes: &mut &[u8], len: usize) -> Value {
+            Value::String(String::from_utf8(take(bytes, len).to_vec()).unwrap())
+        }
+        fn array(bytes: &mut &[u8], len: usize) -> Value {
+            Value::Array((0..len).map(|_| decode(bytes)).collect())
+        }
+        fn map(bytes: &mut &[u8], len: usize) -> Value {
+            let mut map = Map::new();
+            for _ in 0..len {
+                let key = match decode(bytes) {
+                    Value::String(key) => key,
+                    other => panic!("Unexpected key {:?}", other),
+                };
+                map.insert(key, decode(bytes));
+            }
+            Value::Object(map)
+        }
+
+        let marker = take(bytes, 1)[0];
+        match marker {
+            0x00..=0x7f => json!(marker),
+            0x80..=0x8f => map(bytes, (marker & 0x0f) as usize),
+            0x90..=0x9f => array(bytes, (marker & 0x0f) as usize),
+            0xa0..=0xbf => string(bytes, (marker & 0x1f) as usize),
+            0xc0 => Value::Null,
+            0xc2 => json!(false),
+            0xc3 => json!(true),
+            0xcb => json!(f64::from_bits(uint(bytes, 8))),
+            0xcc => json!(uint(bytes, 1)),
+            0xcd => json!(uint(bytes, 2)),
+            0xce => json!(uint(bytes, 4)),
+            0xcf => json!(uint(bytes, 8)),
+            0xd0 => json!(uint(bytes, 1) as u8 as i8),
+            0xd1 => json!(uint(bytes, 2) as u16 as i16),
+            0xd2 => json!(uint(bytes, 4) as u32 as i32),
+            0xd3 => json!(uint(bytes, 8) as i64),
+            0xd9 => {
+                let len = uint(bytes, 1) as usize;
+                string(bytes, len)
+            }
+            0xda => {
+                let len = uint(bytes, 2) as usize;
+                string(bytes, len)
+            }
+            0xdb => {
+                let len = uint(bytes, 4) as usize;
+                string(bytes, len)
+            }
+            0xdc => {
+                let len = uint(bytes, 2) as usize;
+                array(bytes, len)
+            }
+            0xdd => {
+                let len = uint(bytes, 4) as usize;
+                array(bytes, len)
+            }
+            0xde => {
+                let len = uint(bytes, 2) as usize;
+                map(bytes, len)
+            }
+            0xdf => {
+                let len = uint(bytes, 4) as usize;
+                map(bytes, len)
+            }
+            0xe0..=0xff => json!(marker as i8),
+            _ => panic!("Unsupported marker {:#x}", marker),
+        }
+    }
+
+    fn to_msgpack(value: &Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encode(value, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            to_msgpack(&json!({"b": [true, null, -1], "a": "x"})),
+            vec![0x82, 0xa1, b'a', 0xa1, b'x', 0xa1, b'b', 0x93, 0xc3, 0xc0, 0xff]
+        );
+        assert_eq!(to_msgpack(&json!(127)), vec![0x7f]);
+        assert_eq!(to_msgpack(&json!(128)), vec![0xcc, 0x80]);
+        assert_eq!(to_msgpack(&json!(4624)), vec![0xcd, 0x12, 0x10]);
+        assert_eq!(
+            to_msgpack(&json!(114690)),
+            vec![0xce, 0x00, 0x01, 0xc0, 0x02]
+        );
+        assert_eq!(to_msgpack(&json!(-33)), vec![0xd0, 0xdf]);
+        assert_eq!(to_msgpack(&json!(-129)), vec![0xd1, 0xff, 0x7f]);
+        assert_eq!(
+            to_msgpack(&json!(0.5)),
+            vec![0xcb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0]
+        );
+
+        // The length of strings is written after the marker from 32 bytes
+        let string = "a".repeat(32);
+        assert_eq!(to_msgpack(&json!(string))[..2], [0xd9, 32]);
+        let string = "a".repeat(256);
+        assert_eq!(to_msgpack(&json!(string))[..3], [0xda, 0x01, 0x00]);
+
+        // Arrays and maps do not have 8-bit lengths
+        let array = vec![0; 16];
+        assert_eq!(to_msgpack(&json!(array))[..3], [0xdc, 0x00, 0x10]);
+        let map: Map<String, Value> = (0..16).map(|i| (i.to_string(), json!(i))).collect();
+        assert_eq!(to_msgpack(&Value::Object(map))[..3], [0xde, 0x00, 0x10]);
+    }
+
+    #[test]
+    fn test_msgpack_format() {
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT_4624.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+
+        let bytes = MsgpackFormat.format(&metadata, &event_data).unwrap();
+        let mut remaining = &bytes[..];
+        let value = decode(&mut remaining);
+        assert!(remaining.is_empty());
+        assert_eq!(
+            value,
+            json::to_value(event_data.event().unwrap().clone(), &metadata).unwrap()
+        );
+        assert_eq!(value["System"]["EventID"], 4624);
+        assert_eq!(value["EventData"]["TargetUserName"], "vagrant");
+
+        // Encoding does not depend on the order of fields
+        for _ in 0..10 {
+            let event_data = EventData::new(Arc::new(EVENT_4624.to_string()), true);
+            assert_eq!(MsgpackFormat.format(&metadata, &event_data).unwrap(), bytes);
+        }
+    }
+}
@@ -16,11 +16,11 @@ const AUDIT_FAILURE_MASK: i64 = 4503599627370496;
 pub struct NxlogFormat;
 
 impl OutputFormat for NxlogFormat {
-    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<String>> {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
         if let Some(event) = data.event() {
             let json_event = NxlogEvent::new(event.clone(), metadata);
-            match serde_json::to_string(&json_event) {
-                Ok(str) => Some(Arc::new(str)),
+            match serde_json::to_vec(&json_event) {
+                Ok(bytes) => Some(Arc::new(bytes)),
                 Err(e) => {
                     warn!(
                         "Failed to serialize event in JSON with NxlogFormat: {:?}. Event was: {:?}. Metadata was: {:?}. Raw event was: {:?}.",
@@ -36,6 +36,14 @@ impl OutputFormat for NxlogFormat {
     }
 }
 
+/// Returns the Json representation of an event, as written by the Nxlog format
+pub fn to_value(
+    event: crate::event::Event,
+    metadata: &EventMetadata,
+) -> serde_json::Result<serde_json::Value> {
+    serde_json::to_value(NxlogEvent::new(event, metadata))
+}
+
 #[derive(Debug, Serialize)]
 struct EventDataType {
     #[serde(flatten, skip_serializing_if = "HashMap::is_empty")]
@@ -254,6 +262,26 @@ impl NxlogEvent {
     }
 }
 
+#[derive(Debug, Serialize)]
+struct ClockSkewInfo {
+    #[serde(rename = "Skew")]
+    skew: i64,
+    #[serde(
+        rename = "OriginalTimeCreated",
+        skip_serializing_if = "Option::is_none"
+    )]
+    original_time_created: Option<String>,
+}
+
+impl From<crate::event::ClockSkewInfo> for ClockSkewInfo {
+    fn from(value: crate::event::ClockSkewInfo) -> Self {
+        Self {
+            skew: value.skew,
+            original_time_created: value.original_time_created,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct Additional {
     #[serde(rename = "IpAddress")]
@@ -268,6 +296,8 @@ struct Additional {
     node: Option<String>,
     #[serde(rename = "Error", skip_serializing_if = "Option::is_none")]
     error: Option<ErrorInfo>,
+    #[serde(rename = "ClockSkew", skip_serializing_if = "Option::is_none")]
+    clock_skew: Option<ClockSkewInfo>,
 }
 
 impl Additional {
@@ -286,6 +316,7 @@ impl Additional {
                 server_revision: metadata.subscription_server_revision().cloned(),
             },
             error: additional.error.map(Into::into),
+            clock_skew: additional.clock_skew.map(Into::into),
         }
     }
 }
@@ -590,7 +621,7 @@ mod tests {
         let formatter = NxlogFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(expected_json).unwrap();
 
         assert_eq!(event_json_value, expected_value);
@@ -0,0 +1,359 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::SecondsFormat;
+use common::subscription::{ProtobufConfiguration, PROTOBUF_EVENT_DATA_FIELD};
+use log::warn;
+use prost::{bytes::Bytes, Message};
+use prost_reflect::{
+    Cardinality, DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor, Value,
+};
+
+use crate::{
+    event::{DataType, Event, EventData, EventMetadata},
+    formats::json::parse_time_created,
+    output::OutputFormat,
+};
+
+/// Built-in message, defined in `proto/event.proto`
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("openwec.event.v1");
+}
+
+pub struct ProtobufFormat {
+    // None when events are encoded using the built-in message
+    descriptor: Option<Result<MessageDescriptor>>,
+}
+
+impl ProtobufFormat {
+    pub fn new(config: &Option<ProtobufConfiguration>) -> Self {
+        Self {
+            descriptor: config
+                .as_ref()
+                .map(ProtobufConfiguration::message_descriptor),
+        }
+    }
+
+    fn encode(
+        &self,
+        descriptor: &MessageDescriptor,
+        metadata: &EventMetadata,
+        event: &Event,
+    ) -> Result<Vec<u8>> {
+        let mut message = DynamicMessage::new(descriptor.clone());
+        for field in descriptor.fields() {
+            let value = if field.name() == PROTOBUF_EVENT_DATA_FIELD && field.is_map() {
+                event_data(event)
+            } else {
+                match field_value(field.name(), metadata, event) {
+                    Some(text) => Some(
+                        convert(&field, &text)
+                            .with_context(|| format!("Failed to fill field {}", field.name()))?,
+                    ),
+                    None => None,
+                }
+            };
+            match value {
+                Some(value) => message
+                    .try_set_field(&field, value)
+                    .map_err(|err| anyhow!("Failed to set field {}: {}", field.name(), err))?,
+                None if field.cardinality() == Cardinality::Required => {
+                    bail!("Required field {} is missing", field.name())
+                }
+                None => (),
+            }
+        }
+        Ok(message.encode_to_vec())
+    }
+}
+
+impl OutputFormat for ProtobufFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let descriptor = match &self.descriptor {
+            Some(Ok(descriptor)) => descriptor,
+            Some(Err(err)) => {
+                warn!("Failed to load protobuf message descriptor: {:?}", err);
+                return None;
+            }
+            None => return Some(Arc::new(builtin_event(event, &data.raw()).encode_to_vec())),
+        };
+        match self.encode(descriptor, metadata, event) {
+            Ok(bytes) => Some(Arc::new(bytes)),
+            Err(err) => {
+                warn!(
+                    "Failed to encode event as protobuf message {}: {:?}",
+                    descriptor.full_name(),
+                    err
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Fills the built-in message with the fields of `event`
+fn builtin_event(event: &Event, raw: &str) -> proto::Event {
+    let mut message = proto::Event {
+        raw_xml: raw.as_bytes().to_vec(),
+        ..Default::default()
+    };
+    if let Some(system) = &event.system {
+        message.event_id = system.event_id;
+        message.computer = system.computer.clone();
+        message.provider = system.provider.name.clone();
+        message.channel = system.channel.clone();
+        message.level = system.level.map(Into::into);
+        message.task = system.task.map(Into::into);
+        message.opcode = system.opcode.map(Into::into);
+        message.keywords = system
+            .keywords
+            .as_deref()
+            .and_then(|keywords| number(keywords).ok());
+        message.event_record_id = system.event_record_id;
+        message.time_created = system
+            .time_created
+            .as_deref()
+            .and_then(parse_time_created)
+            .and_then(|time| time.timestamp_nanos_opt());
+    }
+    message
+}
+
+fn event_data(event: &Event) -> Option<Value> {
+    match &event.data {
+        DataType::EventData(data) => Some(Value::Map(
+            data.named_data
+                .iter()
+                .map(|(name, value)| (MapKey::String(name.clone()), Value::String(value.clone())))
+                .collect::<HashMap<MapKey, Value>>(),
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the text value of the event field mapped to the message field `name`
+fn field_value(name: &str, metadata: &EventMetadata, event: &Event) -> Option<String> {
+    let system = event.system.as_ref();
+    let execution = system.and_then(|system| system.execution.as_ref());
+    match name {
+        "computer" => system.map(|system| system.computer.clone()),
+        "event_id" => system.map(|system| system.event_id.to_string()),
+        "provider" => system.and_then(|system| system.provider.name.clone()),
+        "provider_guid" => system.and_then(|system| system.provider.guid.clone()),
+        "channel" => system.and_then(|system| system.channel.clone()),
+        "level" => system.and_then(|system| system.level.map(|level| level.to_string())),
+        "task" => system.and_then(|system| system.task.map(|task| task.to_string())),
+        "opcode" => system.and_then(|system| system.opcode.map(|opcode| opcode.to_string())),
+        "keywords" => system.and_then(|system| system.keywords.clone()),
+        "version" => system.and_then(|system| system.version.map(|version| version.to_string())),
+        "event_record_id" => {
+            system.and_then(|system| system.event_record_id.map(|id| id.to_string()))
+        }
+        "time_created" => system.and_then(|system| system.time_created.clone()),
+        "process_id" => execution.map(|execution| execution.process_id.to_string()),
+        "thread_id" => execution.map(|execution| execution.thread_id.to_string()),
+        "activity_id" => system
+            .and_then(|system| system.correlation.as_ref())
+            .and_then(|correlation| correlation.activity_id.clone()),
+        "user_id" => system.and_then(|system| system.user_id.clone()),
+        "message" => event
+            .rendering_info
+            .as_ref()
+            .and_then(|rendering_info| rendering_info.message.clone()),
+        "event_name" => event
+            .additional
+            .event_name
+            .as_ref()
+            .map(|event_name| event_name.to_string()),
+        "ip_address" => Some(metadata.addr().ip().to_string()),
+        "principal" => Some(metadata.principal().to_string()),
+        "time_received" => Some(
+            metadata
+                .time_received()
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+        ),
+        "subscription_uuid" => Some(metadata.subscription_uuid().to_string()),
+        "subscription_name" => Some(metadata.subscription_name().to_string()),
+        "node" => metadata.node_name().cloned(),
+        _ => None,
+    }
+}
+
+/// Converts a text value to the type of `field`
+fn convert(field: &FieldDescriptor, text: &str) -> Result<Value> {
+    if field.is_list() || field.is_map() {
+        bail!("Repeated fields are not supported");
+    }
+    Ok(match field.kind() {
+        Kind::String => Value::String(text.to_string()),
+        Kind::Bytes => Value::Bytes(Bytes::from(text.as_bytes().to_vec())),
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(u32::try_from(number(text)?)?),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(number(text)?),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(i32::try_from(number(text)?)?),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(i64::try_from(number(text)?)?),
+        kind => bail!("Fields of type {:?} are not supported", kind),
+    })
+}
+
+/// Parses a decimal or hexadecimal (such as keywords) number
+fn number(text: &str) -> Result<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .with_context(|| format!("Invalid number {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+
+    use chrono::Utc;
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const EVENT_4688: &str = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='NewProcessName'>C:\\Users\\José\\cmd.exe</Data><Data Name='CommandLine'>cmd.exe /c \"echo a\tb\"</Data></EventData></Event>";
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            chrono::DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    fn config(message: &str) -> ProtobufConfiguration {
+        let mut path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        path.pop();
+        path.push("tests/protobuf/event.binpb");
+        ProtobufConfiguration::try_new(std::fs::read(path).unwrap(), message.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_protobuf_format() {
+        let config = config("openwec.test.Event");
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+
+        let bytes = ProtobufFormat::new(&Some(config.clone()))
+            .format(&metadata, &event_data)
+            .unwrap();
+        let message =
+            DynamicMessage::decode(config.message_descriptor().unwrap(), bytes.as_slice()).unwrap();
+
+        let field = |name: &str| message.get_field_by_name(name).unwrap().into_owned();
+        assert_eq!(
+            field("computer"),
+            Value::String("win10.windomain.local".to_string())
+        );
+        assert_eq!(field("event_id"), Value::U32(4688));
+        assert_eq!(
+            field("provider"),
+            Value::String("Microsoft-Windows-Security-Auditing".to_string())
+        );
+        assert_eq!(field("channel"), Value::String("Security".to_string()));
+        assert_eq!(field("level"), Value::U32(0));
+        assert_eq!(field("event_record_id"), Value::U64(114689));
+        assert_eq!(field("keywords"), Value::U64(0x8020000000000000));
+        assert_eq!(
+            field("time_created"),
+            Value::String("2022-12-14T16:06:51.0643605Z".to_string())
+        );
+        assert_eq!(
+            field("ip_address"),
+            Value::String("192.168.58.100".to_string())
+        );
+        assert_eq!(
+            field("subscription_name"),
+            Value::String("Test".to_string())
+        );
+        assert!(!message.has_field_by_name("comment"));
+
+        let event_data = field("event_data");
+        let event_data = event_data.as_map().unwrap();
+        assert_eq!(event_data.len(), 2);
+        assert_eq!(
+            event_data.get(&MapKey::String("NewProcessName".to_string())),
+            Some(&Value::String("C:\\Users\\José\\cmd.exe".to_string()))
+        );
+        assert_eq!(
+            event_data.get(&MapKey::String("CommandLine".to_string())),
+            Some(&Value::String("cmd.exe /c \"echo a\tb\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_protobuf_builtin_message() {
+        let event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        let bytes = ProtobufFormat::new(&None)
+            .format(&metadata(), &event_data)
+            .unwrap();
+
+        let message = proto::Event::decode(bytes.as_slice()).unwrap();
+        assert_eq!(
+            message,
+            proto::Event {
+                event_id: 4688,
+                computer: "win10.windomain.local".to_string(),
+                provider: Some("Microsoft-Windows-Security-Auditing".to_string()),
+                channel: Some("Security".to_string()),
+                level: Some(0),
+                task: Some(13312),
+                opcode: Some(0),
+                keywords: Some(0x8020000000000000),
+                event_record_id: Some(114689),
+                // 2022-12-14T16:06:51.0643605Z
+                time_created: Some(1_671_034_011_064_360_500),
+                raw_xml: EVENT_4688.as_bytes().to_vec(),
+            }
+        );
+        // The raw XML is kept byte for byte
+        assert_eq!(String::from_utf8(message.raw_xml).unwrap(), EVENT_4688);
+
+        // Missing fields are left unset
+        let event = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-EventForwarder'/><EventID>111</EventID><Computer>win10.windomain.local</Computer></System></Event>";
+        let event_data = EventData::new(Arc::new(event.to_string()), true);
+        let bytes = ProtobufFormat::new(&None)
+            .format(&metadata(), &event_data)
+            .unwrap();
+        let message = proto::Event::decode(bytes.as_slice()).unwrap();
+        assert_eq!(message.event_id, 111);
+        assert_eq!(message.channel, None);
+        assert_eq!(message.level, None);
+        assert_eq!(message.time_created, None);
+    }
+
+    #[test]
+    fn test_protobuf_missing_required_field() {
+        // ticket_id is required but can not be filled
+        let config = config("openwec.test.Ticket");
+        let event_data = EventData::new(Arc::new(EVENT_4688.to_string()), true);
+        assert!(ProtobufFormat::new(&Some(config))
+            .format(&metadata(), &event_data)
+            .is_none());
+    }
+}
@@ -8,8 +8,8 @@ use crate::{
 pub struct RawFormat;
 
 impl OutputFormat for RawFormat {
-    fn format(&self, _metadata: &EventMetadata, data: &EventData) -> Option<Arc<String>> {
-        Some(data.raw())
+    fn format(&self, _metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        Some(Arc::new(data.raw().as_bytes().to_vec()))
     }
 }
 
@@ -94,6 +94,6 @@ Type 3 is a limited token with administrative privileges removed and administrat
         let formatter = RawFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        assert_eq!(result.as_str(), EVENT_4688);
+        assert_eq!(result.as_slice(), EVENT_4688.as_bytes());
     }
 }
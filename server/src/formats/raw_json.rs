@@ -10,6 +10,9 @@ use crate::{
 
 pub struct RawJsonFormat;
 
+/// Keys and their order are part of the documented format. The raw event is
+/// always a JSON string, so that consumers never have to deal with XML
+/// delimiters or unescaped control characters.
 #[derive(Serialize)]
 struct RawJson {
     meta: Metadata,
@@ -66,13 +69,13 @@ impl Metadata {
 }
 
 impl OutputFormat for RawJsonFormat {
-    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<String>> {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
         let event = RawJson {
             meta: Metadata::new(metadata),
             data: data.raw(),
         };
-        match serde_json::to_string(&event) {
-            Ok(str) => Some(Arc::new(str)),
+        match serde_json::to_vec(&event) {
+            Ok(bytes) => Some(Arc::new(bytes)),
             Err(e) => {
                 warn!("Failed to format event in Raw Json: {:?}.", e);
                 None
@@ -164,9 +167,48 @@ Type 3 is a limited token with administrative privileges removed and administrat
         let formatter = RawJsonFormat;
         let result = formatter.format(&metadata, &event_data).unwrap();
 
-        let event_json_value: Value = serde_json::from_str(&result).unwrap();
+        let event_json_value: Value = serde_json::from_slice(&result).unwrap();
         let expected_value: Value = serde_json::from_str(EVENT_4688_JSON).unwrap();
 
         assert_eq!(event_json_value, expected_value);
     }
+
+    const EVENT_4104: &str = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><EventID>4104</EventID><Computer>win10.windomain.local</Computer></System><EventData><Data Name='ScriptBlockText'>Write-Host \"Hello\"\r\n$path = \"C:\\Temp\\a b\"\n\tGet-Item $path</Data></EventData></Event>";
+    const EVENT_4104_JSON: &str = r#"{"meta":{"IpAddress":"192.168.58.100","TimeReceived":"2022-12-14T16:07:03.331+00:00","Principal":"WIN10$@WINDOMAIN.LOCAL","Subscription":{"Uuid":"8B18D83D-2964-4F35-AC3B-6F4E6FFA727B","Version":"188BB736-9441-5C66-188B-B73694415C66","Name":"Test"}},"data":"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><EventID>4104</EventID><Computer>win10.windomain.local</Computer></System><EventData><Data Name='ScriptBlockText'>Write-Host \"Hello\"\r\n$path = \"C:\\Temp\\a b\"\n\tGet-Item $path</Data></EventData></Event>"}"#;
+
+    #[test]
+    fn test_raw_json_exact_output() {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut subscription_data = SubscriptionData::new("Test", "");
+        subscription_data.set_uuid(SubscriptionUuid(
+            Uuid::from_str("8B18D83D-2964-4F35-AC3B-6F4E6FFA727B").unwrap(),
+        ));
+        let subscription = Subscription::from_data(subscription_data, &mut output_context).unwrap();
+
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            chrono::DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+
+        // The raw event is not parsed
+        let event_data = EventData::new(Arc::new(EVENT_4104.to_string()), false);
+        let result = RawJsonFormat.format(&metadata, &event_data).unwrap();
+
+        // Keys, their order and the escaping of quotes, backslashes and
+        // line breaks are stable
+        assert_eq!(String::from_utf8(result.to_vec()).unwrap(), EVENT_4104_JSON);
+
+        // The raw event is retrieved as is
+        let value: Value = serde_json::from_slice(&result).unwrap();
+        assert_eq!(value["data"].as_str().unwrap(), EVENT_4104);
+    }
 }
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use common::subscription::{FieldMap, Rfc5424Configuration};
+use log::warn;
+use serde_json::Value;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    formats::{
+        csv::{cell, lookup},
+        field_map, json,
+    },
+    output::OutputFormat,
+};
+
+// Events are sent with the "user-level messages" facility
+const FACILITY: u8 = 1;
+
+pub struct Rfc5424Format {
+    config: Rfc5424Configuration,
+    field_map: FieldMap,
+}
+
+impl Rfc5424Format {
+    pub fn new(config: &Rfc5424Configuration, field_map: &FieldMap) -> Self {
+        Self {
+            config: config.clone(),
+            field_map: field_map.clone(),
+        }
+    }
+
+    fn structured_data(&self, value: &Value) -> String {
+        if self.config.structured_data().is_empty() {
+            return "-".to_string();
+        }
+        let mut structured_data = String::new();
+        for element in self.config.structured_data() {
+            structured_data.push('[');
+            structured_data.push_str(element.id());
+            structured_data.push('@');
+            structured_data.push_str(&self.config.enterprise_number().to_string());
+            for (name, path) in element.params() {
+                // Absent fields are omitted
+                match lookup(value, path) {
+                    None | Some(Value::Null) => continue,
+                    param => {
+                        structured_data.push(' ');
+                        structured_data.push_str(name);
+                        structured_data.push_str("=\"");
+                        structured_data.push_str(&escape(&cell(param)));
+                        structured_data.push('"');
+                    }
+                }
+            }
+            structured_data.push(']');
+        }
+        structured_data
+    }
+}
+
+impl OutputFormat for Rfc5424Format {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        // Structured data refer to renamed fields
+        let value = match json::to_value(event.clone(), metadata) {
+            Ok(value) if self.field_map.is_empty() => value,
+            Ok(value) => field_map::apply(&self.field_map, value),
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON: {:?}. Event was: {:?}",
+                    e, event
+                );
+                return None;
+            }
+        };
+        let system = event.system.as_ref();
+
+        // RFC 5424 timestamps have at most 6 digits of fractional seconds
+        let timestamp = system
+            .and_then(|system| system.time_created.as_deref())
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|| metadata.time_received())
+            .to_rfc3339_opts(SecondsFormat::Micros, true);
+
+        let message = format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            FACILITY * 8 + severity(system.and_then(|system| system.level)),
+            timestamp,
+            header_field(system.map(|system| system.computer.as_str()), 255),
+            header_field(
+                system.and_then(|system| system.provider.name.as_deref()),
+                48
+            ),
+            header_field(
+                system
+                    .and_then(|system| system.execution.as_ref())
+                    .map(|execution| execution.process_id.to_string())
+                    .as_deref(),
+                128
+            ),
+            header_field(
+                system.map(|system| system.event_id.to_string()).as_deref(),
+                32
+            ),
+            self.structured_data(&value),
+            value
+        );
+        Some(Arc::new(message.into_bytes()))
+    }
+}
+
+/// Maps the level of a Windows event to a syslog severity
+fn severity(level: Option<u8>) -> u8 {
+    match level {
+        // Critical
+        Some(1) => 2,
+        // Error
+        Some(2) => 3,
+        // Warning
+        Some(3) => 4,
+        // Verbose
+        Some(5) => 7,
+        // LogAlways, Information and unknown levels
+        _ => 6,
+    }
+}
+
+/// Header fields are made of printable US-ASCII characters, `-` meaning
+/// that the value is unknown
+pub fn header_field(value: Option<&str>, max_len: usize) -> String {
+    let field: String = value
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max_len)
+        .collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+/// Escapes a PARAM-VALUE: `"`, `\` and `]` must be preceded by a backslash
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, net::SocketAddr, str::FromStr, sync::Arc};
+
+    use chrono::Utc;
+    use common::{
+        settings,
+        subscription::{
+            FieldMap, Rfc5424Configuration, StructuredDataElement, SubscriptionData,
+            SubscriptionUuid,
+        },
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        event::{EventData, EventMetadata},
+        formats::rfc5424::Rfc5424Format,
+        output::{OutputDriversContext, OutputFormat},
+        subscription::Subscription,
+    };
+
+    const EVENT: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4688</EventID><Version>2</Version><Level>0</Level><Task>13312</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114689</EventRecordID><Correlation/><Execution ProcessID='4' ThreadID='196'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='NewProcessName'>C:\Windows\System32\cmd.exe</Data><Data Name='CommandLine'>cmd.exe /c "echo [a]"</Data></EventData></Event>"#;
+
+    fn metadata() -> EventMetadata {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut subscription_data = SubscriptionData::new("Test", "");
+        subscription_data.set_uuid(SubscriptionUuid(
+            Uuid::from_str("8B18D83D-2964-4F35-AC3B-6F4E6FFA727B").unwrap(),
+        ));
+        let subscription = Subscription::from_data(subscription_data, &mut output_context).unwrap();
+
+        let mut metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        metadata.set_time_received(
+            chrono::DateTime::parse_from_rfc3339("2022-12-14T17:07:03.331+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        metadata
+    }
+
+    fn element(id: &str, params: &[(&str, &str)]) -> StructuredDataElement {
+        StructuredDataElement::try_new(
+            id.to_string(),
+            params
+                .iter()
+                .map(|(name, path)| (name.to_string(), path.to_string()))
+                .collect::<BTreeMap<String, String>>(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rfc5424_format() {
+        let metadata = metadata();
+        let event_data = EventData::new(Arc::new(EVENT.to_string()), true);
+        assert!(event_data.event().unwrap().additional.error.is_none());
+
+        let config = Rfc5424Configuration::try_new(
+            32473,
+            vec![
+                element(
+                    "openwec",
+                    &[
+                        ("subscription", "OpenWEC.Subscription.Name"),
+                        ("ip", "OpenWEC.IpAddress"),
+                    ],
+                ),
+                element(
+                    "winlog",
+                    &[
+                        ("event_id", "System.EventID"),
+                        ("channel", "System.Channel"),
+                        ("process", "EventData.NewProcessName"),
+                        ("command_line", "EventData.CommandLine"),
+                        ("missing", "EventData.Missing"),
+                    ],
+                ),
+            ],
+        )
+        .unwrap();
+        let formatter = Rfc5424Format::new(&config, &FieldMap::default());
+        let result =
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+
+        let (header, message) = result.split_once(" {").unwrap();
+        assert_eq!(
+            header,
+            r#"<14>1 2022-12-14T16:06:51.064360Z win10.windomain.local Microsoft-Windows-Security-Auditing 4 4688 [openwec@32473 ip="192.168.58.100" subscription="Test"][winlog@32473 channel="Security" command_line="cmd.exe /c \"echo [a\]\"" event_id="4688" process="C:\\Windows\\System32\\cmd.exe"]"#
+        );
+        // The message is the event in Json format
+        let message: serde_json::Value = serde_json::from_str(&format!("{{{}", message)).unwrap();
+        assert_eq!(message["System"]["EventID"], 4688);
+
+        // Without structured data
+        let formatter = Rfc5424Format::new(&Rfc5424Configuration::default(), &FieldMap::default());
+        let result =
+            String::from_utf8(formatter.format(&metadata, &event_data).unwrap().to_vec()).unwrap();
+        assert!(result.starts_with(
+            "<14>1 2022-12-14T16:06:51.064360Z win10.windomain.local Microsoft-Windows-Security-Auditing 4 4688 - {"
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Result;
+use common::subscription::TemplateConfiguration;
+use leon::Template;
+use log::warn;
+use serde_json::Value;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    formats::{csv, json},
+    output::OutputFormat,
+};
+
+/// Renders a user-provided template for each event. Tokens refer to fields
+/// of the Json format, like the columns of the Csv format.
+pub struct TemplateFormat {
+    config: TemplateConfiguration,
+}
+
+impl TemplateFormat {
+    pub fn new(config: &TemplateConfiguration) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    fn render(&self, value: &Value) -> Result<String> {
+        // The template has been validated when the subscription was loaded,
+        // but Template borrows the string so it can not be stored
+        let template = Template::parse(self.config.template())?;
+        Ok(template.render(&EventValues(value))?)
+    }
+}
+
+impl OutputFormat for TemplateFormat {
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>> {
+        let event = match data.event() {
+            Some(event) => event,
+            None => {
+                warn!("Failed to retrieve parsed event");
+                return None;
+            }
+        };
+        let value = match json::to_value(event.clone(), metadata) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(
+                    "Failed to serialize event in JSON: {:?}. Event was: {:?}",
+                    e, event
+                );
+                return None;
+            }
+        };
+        match self.render(&value) {
+            Ok(line) => Some(Arc::new(line.into_bytes())),
+            Err(e) => {
+                warn!(
+                    "Failed to render template {:?}: {:?}",
+                    self.config.template(),
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+struct EventValues<'a>(&'a Value);
+
+impl leon::Values for EventValues<'_> {
+    fn get_value(&self, key: &str) -> Option<Cow<'_, str>> {
+        // The rendered message is what users expect from RenderingInfo
+        let key = if key == "RenderingInfo" {
+            "RenderingInfo.Message"
+        } else {
+            key
+        };
+        // Fields missing from the event are rendered as empty strings
+        Some(csv::cell(csv::lookup_column(self.0, key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::{settings, subscription::SubscriptionData};
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    const EVENT_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-a5ba-3e3b0328c30d}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2022-12-14T16:06:51.0643605Z'/><EventRecordID>114690</EventRecordID><Correlation/><Execution ProcessID='696' ThreadID='4440'/><Channel>Security</Channel><Computer>win10.windomain.local</Computer><Security/></System><EventData><Data Name='TargetUserName'>vagrant</Data><Data Name='LogonType'>5</Data></EventData><RenderingInfo Culture='en-US'><Message>An account was successfully logged on.</Message><Level>Information</Level><Task>Logon</Task><Opcode>Info</Opcode><Channel>Security</Channel><Provider>Microsoft Windows security auditing.</Provider><Keywords><Keyword>Audit Success</Keyword></Keywords></RenderingInfo></Event>"#;
+
+    fn format(template: &str) -> String {
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription =
+            Subscription::from_data(SubscriptionData::new("Test", ""), &mut output_context)
+                .unwrap();
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985").unwrap(),
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+        let event_data = EventData::new(Arc::new(EVENT_4624.to_string()), true);
+        let config = TemplateConfiguration::try_new(template.to_string()).unwrap();
+        let result = TemplateFormat::new(&config)
+            .format(&metadata, &event_data)
+            .unwrap();
+        String::from_utf8(result.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_template_format() {
+        assert_eq!(
+            format("{TimeCreated} {Computer} [{EventID}] {RenderingInfo}"),
+            "2022-12-14T16:06:51.0643605Z win10.windomain.local [4624] An account was successfully logged on."
+        );
+        assert_eq!(
+            format(
+                "user={EventData.TargetUserName} type={EventData.LogonType} ip={OpenWEC.IpAddress}"
+            ),
+            "user=vagrant type=5 ip=192.168.58.100"
+        );
+    }
+
+    #[test]
+    fn test_template_missing_fields() {
+        assert_eq!(
+            format(
+                "{Computer}|{Container}|{EventData.Unknown}|{Hostname}|{RenderingInfo.Keywords}"
+            ),
+            r#"win10.windomain.local||||["Audit Success"]"#
+        );
+    }
+
+    #[test]
+    fn test_template_escaped_braces() {
+        assert_eq!(format(r"\{{EventID}\} \{Computer\}"), "{4624} {Computer}");
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use common::{
+    database::{schema_is_up_to_date, Db},
+    settings::Health,
+};
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    http::status::StatusCode,
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response,
+};
+use hyper_util::rt::TokioIo;
+use log::{debug, info, warn};
+use tokio::net::TcpListener;
+
+use crate::subscription::Subscriptions;
+
+/// State checked by the readiness endpoint
+pub struct HealthState {
+    // Set once the database is connected and its schema has been checked,
+    // i.e. at the end of the startup phase
+    db: OnceLock<Db>,
+    subscriptions: Subscriptions,
+}
+
+impl HealthState {
+    pub fn new(subscriptions: Subscriptions) -> Self {
+        Self {
+            db: OnceLock::new(),
+            subscriptions,
+        }
+    }
+
+    /// Marks the end of the startup phase
+    pub fn set_db(&self, db: Db) {
+        if self.db.set(db).is_err() {
+            warn!("Health state database has already been set");
+        }
+    }
+
+    async fn check_readiness(&self) -> Result<()> {
+        let db = self
+            .db
+            .get()
+            .ok_or_else(|| anyhow!("Database is not initialized yet"))?;
+        if !schema_is_up_to_date(db.clone())
+            .await
+            .context("Database is unreachable")?
+        {
+            bail!("Database schema is not up to date");
+        }
+        if self.subscriptions.read().unwrap().is_empty() {
+            bail!("No subscription is loaded");
+        }
+        Ok(())
+    }
+}
+
+pub async fn init(settings: &Health, state: Arc<HealthState>) -> Result<()> {
+    let addr = SocketAddr::from((
+        IpAddr::from_str(settings.listen_address()).expect("Failed to parse health.listen_address"),
+        settings.listen_port(),
+    ));
+
+    let addr = spawn(addr, state).await?;
+    info!("Starting health server on {}", addr);
+    Ok(())
+}
+
+/// Binds the given address and launches a task serving the health endpoints.
+/// Returns the address the server is actually listening on.
+async fn spawn(addr: SocketAddr, state: Arc<HealthState>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind health server on {}", addr))?;
+    let local_addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, client_addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    warn!("Could not get health client: {:?}", err);
+                    continue;
+                }
+            };
+            debug!("Received health TCP connection from {}", client_addr);
+
+            let state = state.clone();
+            tokio::spawn(async move {
+                let io = TokioIo::new(stream);
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(io, service_fn(move |req| handle(state.clone(), req)))
+                    .await
+                {
+                    debug!("Error while serving health connection: {:?}", err);
+                }
+            });
+        }
+    });
+
+    Ok(local_addr)
+}
+
+async fn handle(
+    state: Arc<HealthState>,
+    req: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let (status, body) = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/healthz") => (StatusCode::OK, "OK".to_owned()),
+        (&Method::GET, "/readyz") => match state.check_readiness().await {
+            Ok(()) => (StatusCode::OK, "OK".to_owned()),
+            Err(err) => {
+                debug!("Readiness check failed: {:?}", err);
+                (StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", err))
+            }
+        },
+        (_, "/healthz") | (_, "/readyz") => (StatusCode::METHOD_NOT_ALLOWED, String::new()),
+        _ => (StatusCode::NOT_FOUND, String::new()),
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .body(Full::new(Bytes::from(body)))
+        .expect("Failed to build health response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        database::{schema, schema::Migrator, sqlite::SQLiteDatabase},
+        settings::Outputs,
+        subscription::{SubscriptionData, SubscriptionUuid},
+    };
+    use std::{collections::HashMap, path::PathBuf, sync::RwLock};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+    use uuid::Uuid;
+
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("openwec-health-{}-{}", name, Uuid::new_v4()))
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> Result<u16> {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(
+                format!(
+                    "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                    path
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let status = response
+            .split_whitespace()
+            .nth(1)
+            .ok_or_else(|| anyhow!("Invalid response: {}", response))?;
+        Ok(status.parse()?)
+    }
+
+    fn subscription() -> (SubscriptionUuid, Arc<Subscription>) {
+        let mut output_context = OutputDriversContext::new(&Outputs::default());
+        let data = SubscriptionData::new("my-subscription", "query");
+        let uuid = *data.uuid();
+        let subscription = Subscription::from_data(data, &mut output_context).unwrap();
+        (uuid, Arc::new(subscription))
+    }
+
+    #[tokio::test]
+    async fn test_health_working_db() -> Result<()> {
+        let path = temp_path("working");
+        let mut db = SQLiteDatabase::new(path.to_str().unwrap()).await?;
+        schema::sqlite::register_migrations(&mut db);
+        let db: Db = Arc::new(db);
+
+        let subscriptions: Subscriptions = Arc::new(RwLock::new(HashMap::new()));
+        let state = Arc::new(HealthState::new(subscriptions.clone()));
+        let addr = spawn("127.0.0.1:0".parse()?, state.clone()).await?;
+
+        // Startup phase: the server is alive but not ready
+        assert_eq!(get(addr, "/healthz").await?, 200);
+        assert_eq!(get(addr, "/readyz").await?, 503);
+
+        // Migrations have not been applied yet
+        db.setup_schema().await?;
+        state.set_db(db.clone());
+        assert_eq!(get(addr, "/readyz").await?, 503);
+
+        // No subscription is loaded
+        Migrator::new(db.clone()).up(None, false).await?;
+        assert_eq!(get(addr, "/readyz").await?, 503);
+
+        let (uuid, subscription) = subscription();
+        subscriptions.write().unwrap().insert(uuid, subscription);
+        assert_eq!(get(addr, "/healthz").await?, 200);
+        assert_eq!(get(addr, "/readyz").await?, 200);
+
+        assert_eq!(get(addr, "/metrics").await?, 404);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_broken_db() -> Result<()> {
+        // Connections are opened lazily: every query fails because the
+        // parent directory of the database file does not exist
+        let path = temp_path("broken").join("db.sqlite");
+        let mut db = SQLiteDatabase::new(path.to_str().unwrap()).await?;
+        schema::sqlite::register_migrations(&mut db);
+        let db: Db = Arc::new(db);
+
+        let (uuid, subscription) = subscription();
+        let subscriptions = Arc::new(RwLock::new(HashMap::from([(uuid, subscription)])));
+        let state = Arc::new(HealthState::new(subscriptions));
+        state.set_db(db);
+        let addr = spawn("127.0.0.1:0".parse()?, state).await?;
+
+        assert_eq!(get(addr, "/healthz").await?, 200);
+        assert_eq!(get(addr, "/readyz").await?, 503);
+        Ok(())
+    }
+}
@@ -5,13 +5,18 @@ use std::{
 
 use anyhow::{Context, Result};
 use common::{
-    database::Db,
+    database::{prune_heartbeats, Db},
     heartbeat::{HeartbeatKey, HeartbeatValue, HeartbeatsCache},
 };
 use log::{debug, error, info};
 use tokio::{select, sync::mpsc, time};
 use tokio_util::sync::CancellationToken;
 
+/// Interval between two prunings of the heartbeats which are too old
+const PRUNE_HEARTBEATS_INTERVAL: Duration = Duration::from_secs(3600);
+/// Maximum number of heartbeats deleted by a single query
+const PRUNE_HEARTBEATS_BATCH_SIZE: u32 = 1000;
+
 pub async fn store_heartbeat(
     heartbeat_tx: mpsc::Sender<WriteHeartbeatMessage>,
     machine: &str,
@@ -128,3 +133,29 @@ pub async fn heartbeat_task(
     }
     info!("Heartbeat task exited");
 }
+
+/// Periodically deletes the heartbeats of sources which have not been seen
+/// for `retention_days` days
+pub async fn prune_heartbeats_task(db: Db, retention_days: u64) -> Result<()> {
+    info!(
+        "Heartbeats pruning task started (retention: {} days)",
+        retention_days
+    );
+    let retention = retention_days.saturating_mul(24 * 3600);
+    let mut interval = time::interval(PRUNE_HEARTBEATS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let last_seen_before: i64 = now.saturating_sub(retention).try_into()?;
+        match prune_heartbeats(db.as_ref(), last_seen_before, PRUNE_HEARTBEATS_BATCH_SIZE).await {
+            Ok(0) => debug!("No heartbeats to prune"),
+            Ok(count) => info!(
+                "Pruned {} heartbeats not seen for {} days",
+                count, retention_days
+            ),
+            Err(e) => error!("Could not prune heartbeats: {:?}", e),
+        }
+    }
+}
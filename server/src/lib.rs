@@ -1,9 +1,24 @@
 #![allow(clippy::too_many_arguments)]
 #![deny(unsafe_code)]
 
+mod batch;
+mod clients;
+mod clock;
+mod clock_skew;
+mod compression;
+pub mod config_check;
+mod db_startup;
+mod dead_letter;
+mod dedup_window;
+mod dedupe;
+mod degradation;
 mod drivers;
+mod enrollment;
 mod event;
+mod event_names;
+mod first_seen;
 mod formats;
+mod health;
 mod heartbeat;
 mod kerberos;
 mod logging;
@@ -12,20 +27,30 @@ mod monitoring;
 mod multipart;
 mod output;
 mod proxy_protocol;
-mod sldc;
+mod queue;
+mod retry;
+mod routing;
+mod sampling;
+pub mod sldc;
 mod soap;
+mod sources;
+mod stale;
 mod subscription;
+mod summary;
 mod tls;
+mod trim;
 
 use anyhow::{anyhow, bail, Context, Result};
 use common::database::{db_from_settings, schema_is_up_to_date, Db};
 use common::encoding::decode_utf16le;
 use common::settings::{Authentication, Kerberos, Monitoring, Tls};
+use common::subscription::AuthenticationMethod;
 use common::settings::{Collector, Server as ServerSettings, Settings};
 use core::pin::Pin;
 use futures::Future;
 use futures_util::future::join_all;
-use heartbeat::{heartbeat_task, WriteHeartbeatMessage};
+use health::HealthState;
+use heartbeat::{heartbeat_task, prune_heartbeats_task, WriteHeartbeatMessage};
 use http_body_util::combinators::BoxBody;
 use http_body_util::{BodyExt, Empty, Full};
 use hyper::body::{Body, Bytes, Incoming};
@@ -48,6 +73,7 @@ use monitoring::{
 use quick_xml::writer::Writer;
 use soap::Serializable;
 use socket2::{SockRef, TcpKeepalive};
+use sources::no_sources_task;
 use std::boxed::Box;
 use std::collections::HashMap;
 use std::convert::Infallible;
@@ -58,7 +84,7 @@ use std::sync::Mutex;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use std::{env, future, mem};
-use subscription::{reload_subscriptions_task, Subscriptions};
+use subscription::{check_max_subscriptions, reload_subscriptions_task, Subscriptions};
 use tokio::io::AsyncRead;
 use tokio::net::TcpListener;
 use tokio::pin;
@@ -70,7 +96,7 @@ use tokio_util::sync::CancellationToken;
 
 use crate::logging::ACCESS_LOGGER;
 use crate::proxy_protocol::read_proxy_header;
-use crate::tls::{make_config, subject_from_cert};
+use crate::tls::{make_config, san_from_cert, subject_from_cert};
 
 pub enum RequestCategory {
     Enumerate(String),
@@ -98,13 +124,26 @@ pub struct RequestData {
     category: RequestCategory,
     uri: String,
     method: String,
+    authentication_method: AuthenticationMethod,
+    // DNS and URI SubjectAltName entries of the client certificate
+    san: Arc<Vec<String>>,
 }
 
 impl RequestData {
-    fn new(principal: &str, remote_addr: &SocketAddr, req: &Request<Incoming>) -> Result<Self> {
+    fn new(
+        principal: &str,
+        remote_addr: &SocketAddr,
+        auth_ctx: &AuthenticationContext,
+        req: &Request<Incoming>,
+    ) -> Result<Self> {
         Ok(RequestData {
             principal: principal.to_owned(),
             remote_addr: remote_addr.to_owned(),
+            authentication_method: auth_ctx.method(),
+            san: match auth_ctx {
+                AuthenticationContext::Tls(_, _, san) => san.clone(),
+                AuthenticationContext::Kerberos(_) => Arc::new(Vec::new()),
+            },
             category: RequestCategory::try_from(req)?,
             method: req.method().to_string(),
             uri: req.uri().to_string(),
@@ -133,14 +172,34 @@ impl RequestData {
     pub fn method(&self) -> &str {
         &self.method
     }
+
+    /// Get the authentication mechanism used by the client
+    pub fn authentication_method(&self) -> AuthenticationMethod {
+        self.authentication_method
+    }
+
+    /// Get the DNS and URI SubjectAltName entries of the client certificate.
+    /// It is empty for clients authenticated using Kerberos.
+    pub fn san(&self) -> &[String] {
+        &self.san
+    }
 }
 
 #[derive(Debug, Clone)]
 /// Kerberos : state
-/// Tls : subject, thumbprint
+/// Tls : subject, thumbprint, DNS and URI SubjectAltName entries
 pub enum AuthenticationContext {
     Kerberos(Arc<Mutex<kerberos::State>>),
-    Tls(String, String),
+    Tls(String, String, Arc<Vec<String>>),
+}
+
+impl AuthenticationContext {
+    pub fn method(&self) -> AuthenticationMethod {
+        match self {
+            AuthenticationContext::Kerberos(_) => AuthenticationMethod::Kerberos,
+            AuthenticationContext::Tls(..) => AuthenticationMethod::TlsCert,
+        }
+    }
 }
 
 fn empty() -> BoxBody<Bytes, Infallible> {
@@ -205,7 +264,7 @@ async fn get_request_payload(
     http_request_body_network_size_bytes_counter.increment(data.len().try_into()?);
 
     let message = match auth_ctx {
-        AuthenticationContext::Tls(_, _) => tls::get_request_payload(parts, data).await?,
+        AuthenticationContext::Tls(..) => tls::get_request_payload(parts, data).await?,
         AuthenticationContext::Kerberos(conn_state) => {
             kerberos::get_request_payload(conn_state.to_owned(), parts, data).await?
         }
@@ -241,7 +300,7 @@ async fn create_response(
     payload: Option<String>,
 ) -> Result<Response<BoxBody<Bytes, Infallible>>> {
     match auth_ctx {
-        AuthenticationContext::Tls(_, _) => {
+        AuthenticationContext::Tls(..) => {
             if payload.is_some() {
                 response = response.header(CONTENT_TYPE, "application/soap+xml;charset=UTF-16");
             }
@@ -296,7 +355,7 @@ async fn authenticate(
     addr: &SocketAddr,
 ) -> Result<(String, Builder)> {
     match auth_ctx {
-        AuthenticationContext::Tls(subject, _) => {
+        AuthenticationContext::Tls(subject, _, _) => {
             // if subject is empty, show unauthorized error
             if subject.is_empty() {
                 log_auth_error(addr, req, "Empty certificate".to_owned(), true);
@@ -518,7 +577,7 @@ async fn handle(
 
     debug!("Successfully authenticated {}", principal);
 
-    let request_data = match RequestData::new(&principal, &addr, &req) {
+    let request_data = match RequestData::new(&principal, &addr, &auth_ctx, &req) {
         Ok(request_data) => request_data,
         Err(e) => {
             error!("Failed to compute request data: {:?}", e);
@@ -1000,9 +1059,16 @@ fn create_tls_server(
 
                 let subject =
                     subject_from_cert(cert.as_ref()).expect("Could not parse client certificate");
+                let san = san_from_cert(cert.as_ref()).unwrap_or_else(|err| {
+                    warn!(
+                        "Could not read SubjectAltName of the certificate of {}: {:?}",
+                        real_client_addr, err
+                    );
+                    Vec::new()
+                });
 
                 // Initialize Authentication context once for each TCP connection
-                let auth_ctx = AuthenticationContext::Tls(subject, thumbprint);
+                let auth_ctx = AuthenticationContext::Tls(subject, thumbprint, Arc::new(san));
 
                 // Hyper needs a wrapper for the stream
                 let io = TokioIo::new(stream);
@@ -1106,6 +1172,19 @@ async fn force_shutdown_timeout(ct: CancellationToken) {
     tokio::time::sleep(Duration::from_secs(10)).await;
 }
 
+/// Initializes the database and checks that its schema is up to date
+async fn connect_db(settings: &Settings) -> Result<Db> {
+    let db: Db = db_from_settings(settings)
+        .await
+        .context("Failed to initialize database")?;
+
+    match schema_is_up_to_date(db.clone()).await {
+        Ok(true) => Ok(db),
+        Ok(false) => bail!("Schema needs to be updated. Please check migration guide and then run `openwec db upgrade`"),
+        Err(err) => Err(err.context("An error occurred while checking schema version.\nHelp: You may need to run `openwec db init` to setup your database.")),
+    }
+}
+
 pub async fn run(settings: Settings, verbosity: u8) {
     // Initialize loggers
     if let Err(e) = logging::init(&settings, verbosity) {
@@ -1120,18 +1199,39 @@ pub async fn run(settings: Settings, verbosity: u8) {
     // See https://github.com/tokio-rs/tokio/issues/4730
     std::thread::spawn(move || monitoring_thread(rt_handle));
 
-    let db: Db = db_from_settings(&settings)
-        .await
-        .expect("Failed to initialize database");
+    let subscriptions = Arc::new(RwLock::new(HashMap::new()));
 
-    // Check that database schema is up to date
-    match schema_is_up_to_date(db.clone()).await {
-        Ok(true) => (),
-        Ok(false) => panic!("Schema needs to be updated. Please check migration guide and then run `openwec db upgrade`"),
-        Err(err) => panic!("An error occurred while checking schema version: {:?}.\nHelp: You may need to run `openwec db init` to setup your database.", err),
+    // The health server is started before connecting to the database so
+    // that it reports the server as not ready during the startup phase
+    let health_state = Arc::new(HealthState::new(subscriptions.clone()));
+    if let Some(health_settings) = settings.health() {
+        health::init(health_settings, health_state.clone())
+            .await
+            .expect("Failed to initialize health server");
+    }
+
+    let db_settings = settings.clone();
+    let db: Db = match db_startup::connect(settings.server(), move || {
+        let settings = db_settings.clone();
+        async move { connect_db(&settings).await }
+    })
+    .await
+    {
+        Ok(db) => db,
+        Err(err) => panic!("{:?}", err),
     };
+    health_state.set_db(db.clone());
 
-    let subscriptions = Arc::new(RwLock::new(HashMap::new()));
+    clock::init(settings.server().time_received_source());
+    if let Err(err) = event_names::init(settings.server()) {
+        panic!("Failed to load event names: {:?}", err);
+    }
+    queue::init(settings.server());
+
+    // Refuse to start rather than loading an unreasonable number of subscriptions
+    if let Err(err) = check_max_subscriptions(&db, settings.server()).await {
+        panic!("Failed to load subscriptions: {:?}", err);
+    }
 
     if let Some(monitoring_settings) = settings.monitoring() {
         monitoring::init(&db, subscriptions.clone(), monitoring_settings).expect("Failed to initialize metrics exporter");
@@ -1139,6 +1239,7 @@ pub async fn run(settings: Settings, verbosity: u8) {
 
     let reload_interval = settings.server().db_sync_interval();
     let outputs_settings = settings.outputs().clone();
+    let server_settings = settings.server().clone();
     let update_task_db = db.clone();
     let update_task_subscriptions = subscriptions.clone();
     // Launch a task responsible for updating subscriptions
@@ -1148,10 +1249,26 @@ pub async fn run(settings: Settings, verbosity: u8) {
             update_task_subscriptions,
             reload_interval,
             outputs_settings,
+            server_settings,
         )
         .await
     });
 
+    if let Some(delay) = settings.server().no_sources_warning_delay() {
+        let no_sources_task_db = db.clone();
+        let no_sources_task_subscriptions = subscriptions.clone();
+        // Launch a task responsible for warning about subscriptions without sources
+        tokio::spawn(async move {
+            no_sources_task(no_sources_task_db, no_sources_task_subscriptions, delay).await
+        });
+    }
+
+    if let Some(retention_days) = settings.server().heartbeats_retention_days() {
+        let prune_task_db = db.clone();
+        // Launch a task responsible for deleting old heartbeats
+        tokio::spawn(async move { prune_heartbeats_task(prune_task_db, retention_days).await });
+    }
+
     // To reduce database load, heartbeats are not saved immediately.
     // Heartbeats data "to store" are cached in memory before being saved in database periodically.
     // To "store" a heartbeat, request handlers send a message to the heartbeat task
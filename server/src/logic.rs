@@ -1,26 +1,41 @@
 use crate::{
-    event::{EventData, EventMetadata},
+    clock_skew::ClockSkewChecker,
+    dead_letter::{self, Failure},
+    dedup_window::{BatchKeys, DedupWindow},
+    dedupe::{self, FieldValues},
+    degradation, enrollment,
+    event::{EventData, EventFields, EventMetadata},
+    event_names, first_seen,
     heartbeat::{store_heartbeat, WriteHeartbeatMessage},
     monitoring::{
         INPUT_EVENTS_COUNTER, INPUT_EVENT_BYTES_COUNTER, INPUT_EVENT_PARSING_FAILURES,
-        INPUT_EVENT_PARSING_FAILURE_ERROR_TYPE, INPUT_MESSAGES_COUNTER, MACHINE, MESSAGES_ACTION,
-        MESSAGES_ACTION_ENUMERATE, MESSAGES_ACTION_EVENTS, MESSAGES_ACTION_HEARTBEAT,
-        OUTPUT_DRIVER, OUTPUT_DRIVER_FAILURES, OUTPUT_FORMAT, OUTPUT_FORMAT_FAILURES,
-        SUBSCRIPTION_NAME, SUBSCRIPTION_UUID,
+        INPUT_EVENT_PARSING_FAILURE_ERROR_TYPE, INPUT_HEARTBEATS_COUNTER, INPUT_MESSAGES_COUNTER,
+        MACHINE, MESSAGES_ACTION, MESSAGES_ACTION_ENUMERATE, MESSAGES_ACTION_EVENTS,
+        MESSAGES_ACTION_HEARTBEAT, OUTPUT_DRIVER, OUTPUT_DRIVER_FAILURES, OUTPUT_FORMAT,
+        OUTPUT_FORMAT_FAILURES, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID,
     },
-    output::get_formatter,
+    output::{get_formatter, FormatKey, Output},
+    queue, routing, sampling,
     soap::{
         Body, Header, Message, OptionSetValue, Subscription as SoapSubscription, SubscriptionBody,
         ACTION_ACK, ACTION_END, ACTION_ENUMERATE, ACTION_ENUMERATE_RESPONSE, ACTION_EVENTS,
         ACTION_HEARTBEAT, ACTION_SUBSCRIBE, ACTION_SUBSCRIPTION_END, ANONYMOUS, RESOURCE_EVENT_LOG,
     },
+    stale,
     subscription::{Subscription, Subscriptions},
+    summary,
+    trim::RenderingTrimmer,
     AuthenticationContext, RequestCategory, RequestData,
 };
+use chrono::{DateTime, Utc};
 use common::{
+    bookmark,
     database::Db,
     settings::{Collector, Monitoring, Server},
-    subscription::{SubscriptionOutputFormat, SubscriptionUuid},
+    subscription::{
+        ClientContext, EventsCompression, ManifestOption, OutputMode, SubscriptionData,
+        SubscriptionUuid,
+    },
 };
 use hyper::http::status::StatusCode;
 use log::{debug, error, warn};
@@ -55,16 +70,93 @@ impl Response {
     }
 }
 
+/// Returns whether `option` is left out of the manifest. Options are only
+/// left out of the fallback manifest.
+fn is_omitted(
+    subscription_data: &SubscriptionData,
+    fallback: bool,
+    option: ManifestOption,
+) -> bool {
+    fallback && subscription_data.enrollment_fallback().contains(&option)
+}
+
+fn create_subscription_options(
+    subscription_data: &SubscriptionData,
+    fallback: bool,
+) -> HashMap<String, OptionSetValue> {
+    let omitted = |option| is_omitted(subscription_data, fallback, option);
+
+    let mut options = HashMap::new();
+    options.insert(
+        "SubscriptionName".to_string(),
+        OptionSetValue::String(subscription_data.name().to_string()),
+    );
+    // Clients do not compress their batches unless asked to
+    if subscription_data.compression() == EventsCompression::Sldc {
+        options.insert(
+            "Compression".to_string(),
+            OptionSetValue::String("SLDC".to_string()),
+        );
+    }
+    if !omitted(ManifestOption::ContentFormat) {
+        options.insert(
+            "ContentFormat".to_string(),
+            OptionSetValue::String(subscription_data.content_format().to_string()),
+        );
+    }
+    if !omitted(ManifestOption::IgnoreChannelError) {
+        options.insert(
+            "IgnoreChannelError".to_string(),
+            OptionSetValue::Boolean(subscription_data.client_ignore_channel_error()),
+        );
+    }
+    if !omitted(ManifestOption::Cdata) {
+        options.insert("CDATA".to_string(), OptionSetValue::Boolean(true));
+    }
+
+    // Add ReadExistingEvents option
+    if subscription_data.read_existing_events() && !omitted(ManifestOption::ReadExistingEvents) {
+        options.insert(
+            "ReadExistingEvents".to_string(),
+            OptionSetValue::Boolean(true),
+        );
+    }
+    options
+}
+
+fn create_subscription_header(subscription_data: &SubscriptionData, fallback: bool) -> Header {
+    Header::new(
+        ANONYMOUS.to_string(),
+        RESOURCE_EVENT_LOG.to_string(),
+        ACTION_SUBSCRIBE.to_string(),
+        subscription_data.max_envelope_size(),
+        None,
+        None,
+        None,
+        Some(1),
+        create_subscription_options(subscription_data, fallback),
+    )
+}
+
 fn create_subscription_body(
     subscription: &Arc<Subscription>,
     bookmark: Option<String>,
     collector: &Collector,
     collector_hostname: &str,
     auth_ctx: &AuthenticationContext,
+    principal: &str,
+    fallback: bool,
 ) -> SubscriptionBody {
-    let public_version = subscription.public_version_string();
+    // Clients must see the fallback manifest as a new version of the
+    // subscription
+    let public_version = if fallback {
+        subscription.fallback_public_version_string()
+    } else {
+        subscription.public_version_string()
+    };
     let identifier = subscription.uuid_string();
     let subscription_data = subscription.data();
+    let omitted = |option| is_omitted(subscription_data, fallback, option);
 
     SubscriptionBody {
         heartbeat_interval: subscription_data.heartbeat_interval() as u64,
@@ -72,7 +164,16 @@ fn create_subscription_body(
         public_version: public_version.clone(),
         revision: subscription_data.revision().cloned(),
         bookmark,
-        query: subscription_data.query().to_owned(),
+        // The cutoff has been applied to the query when the subscription was
+        // loaded, so this only fails for subscriptions stored by hand
+        query: subscription_data.client_query().unwrap_or_else(|err| {
+            warn!(
+                "Failed to leave out events older than read_existing_events_since from the query of subscription {}: {:?}",
+                subscription_data.name(),
+                err
+            );
+            subscription_data.query().to_owned()
+        }),
         address: match auth_ctx {
             AuthenticationContext::Kerberos(_) => format!(
                 "http://{}:{}/wsman/subscriptions/{}",
@@ -80,7 +181,7 @@ fn create_subscription_body(
                 collector.advertized_port(),
                 identifier
             ),
-            AuthenticationContext::Tls(_, _) => format!(
+            AuthenticationContext::Tls(..) => format!(
                 "https://{}:{}/wsman/subscriptions/{}",
                 collector_hostname,
                 collector.advertized_port(),
@@ -88,16 +189,24 @@ fn create_subscription_body(
             ),
         },
         connection_retry_count: subscription_data.connection_retry_count(),
-        connection_retry_interval: subscription_data.connection_retry_interval(),
+        connection_retry_interval: subscription_data.client_connection_retry_interval(principal),
         max_time: subscription_data.max_time(),
-        max_elements: subscription_data.max_elements(),
+        max_elements: subscription_data
+            .max_elements()
+            .filter(|_| !omitted(ManifestOption::MaxElements)),
         max_envelope_size: subscription_data.max_envelope_size(),
         thumbprint: match auth_ctx {
-            AuthenticationContext::Tls(_, thumbprint) => Some(thumbprint.clone()),
+            AuthenticationContext::Tls(_, thumbprint, _) => Some(thumbprint.clone()),
             AuthenticationContext::Kerberos(_) => None,
         },
-        locale: subscription_data.locale().cloned(),
-        data_locale: subscription_data.data_locale().cloned(),
+        locale: subscription_data
+            .locale()
+            .filter(|_| !omitted(ManifestOption::Locale))
+            .cloned(),
+        data_locale: subscription_data
+            .data_locale()
+            .filter(|_| !omitted(ManifestOption::DataLocale))
+            .cloned(),
     }
 }
 
@@ -181,7 +290,11 @@ async fn handle_enumerate(
 
         // Skip subscriptions that filter out this principal
         let machine_id = message.header().machine_id().map(|m| m.as_str());
-        if !subscription_data.is_active_for(request_data.principal(), machine_id) {
+        if !subscription_data.is_active_for_context(
+            &ClientContext::new(request_data.principal(), machine_id)
+                .with_ip(Some(request_data.remote_addr().ip()))
+                .with_san(request_data.san()),
+        ) {
             debug!(
                 "Skip subscription \"{}\" ({}) which client filter {:?} rejects {} ({})",
                 subscription_data.name(),
@@ -193,60 +306,47 @@ async fn handle_enumerate(
             continue;
         }
 
-        debug!(
-            "Include subscription \"{}\" ({})",
-            subscription_data.name(),
-            subscription_data.uuid()
-        );
-
-        let mut options = HashMap::new();
-        options.insert(
-            "SubscriptionName".to_string(),
-            OptionSetValue::String(subscription_data.name().to_string()),
-        );
-        options.insert(
-            "Compression".to_string(),
-            OptionSetValue::String("SLDC".to_string()),
-        );
-        options.insert(
-            "ContentFormat".to_string(),
-            OptionSetValue::String(subscription_data.content_format().to_string()),
-        );
-        options.insert(
-            "IgnoreChannelError".to_string(),
-            OptionSetValue::Boolean(subscription_data.ignore_channel_error()),
-        );
-        options.insert("CDATA".to_string(), OptionSetValue::Boolean(true));
-
-        // Add ReadExistingEvents option
-        if subscription_data.read_existing_events() {
-            options.insert(
-                "ReadExistingEvents".to_string(),
-                OptionSetValue::Boolean(true),
+        // Skip subscriptions that require another authentication mechanism
+        if !subscription_data
+            .require_auth()
+            .allows(request_data.authentication_method())
+        {
+            debug!(
+                "Skip subscription \"{}\" ({}) which requires {} authentication but {} used {:?}",
+                subscription_data.name(),
+                subscription_data.uuid(),
+                subscription_data.require_auth(),
+                request_data.principal(),
+                request_data.authentication_method(),
             );
+            continue;
         }
 
-        let header = Header::new(
-            ANONYMOUS.to_string(),
-            RESOURCE_EVENT_LOG.to_string(),
-            ACTION_SUBSCRIBE.to_string(),
-            subscription_data.max_envelope_size(),
-            None,
-            None,
-            None,
-            Some(1),
-            options,
+        // Clients which rejected the manifest get the fallback one, if any
+        let fallback = !subscription_data.enrollment_fallback().is_empty()
+            && subscription
+                .enrollment_failures()
+                .contains(request_data.principal());
+
+        debug!(
+            "Include subscription \"{}\" ({}){}",
+            subscription_data.name(),
+            subscription_data.uuid(),
+            if fallback {
+                " with its fallback manifest"
+            } else {
+                ""
+            }
         );
 
-        let mut bookmark: Option<String> = db
-            .get_bookmark(request_data.principal(), &subscription_data.uuid_string())
-            .await
-            .context("Failed to retrieve current bookmark from database")?;
+        let header = create_subscription_header(subscription_data, fallback);
 
-        if bookmark.is_none() && subscription_data.read_existing_events() {
-            bookmark =
-                Some("http://schemas.dmtf.org/wbem/wsman/1/wsman/bookmark/earliest".to_string())
-        }
+        let bookmark = bookmark::bookmark_to_send(
+            db.get_bookmark(request_data.principal(), &subscription_data.uuid_string())
+                .await
+                .context("Failed to retrieve current bookmark from database")?,
+            subscription_data.read_existing_events(),
+        );
 
         debug!(
             "Load bookmark of {} for subscription {}: {:?}",
@@ -255,10 +355,18 @@ async fn handle_enumerate(
             bookmark
         );
 
-        let body = create_subscription_body(&subscription, bookmark, collector, &collector_hostname, auth_ctx,);
+        let body = create_subscription_body(
+            &subscription,
+            bookmark,
+            collector,
+            &collector_hostname,
+            auth_ctx,
+            request_data.principal(),
+            fallback,
+        );
 
         res_subscriptions.push(SoapSubscription {
-            version: subscription.public_version_string(),
+            version: body.public_version.clone(),
             header,
             body,
         });
@@ -272,6 +380,46 @@ async fn handle_enumerate(
     ))
 }
 
+fn handle_subscription_end(
+    subscriptions: Subscriptions,
+    request_data: &RequestData,
+    message: &Message,
+) -> Result<Response> {
+    let subscription_uuid = if let Some(identifier) = message.header().identifier() {
+        SubscriptionUuid(Uuid::parse_str(identifier)?)
+    } else {
+        error!("Could not find identifier in message header");
+        return Ok(Response::err(StatusCode::BAD_REQUEST));
+    };
+
+    let subscription = {
+        let subscriptions = subscriptions.read().unwrap();
+        match subscriptions.get(&subscription_uuid) {
+            Some(subscription) => subscription.to_owned(),
+            None => {
+                debug!(
+                    "Received SubscriptionEnd from {}:{} ({}) for unknown subscription {}",
+                    request_data.remote_addr().ip(),
+                    request_data.remote_addr().port(),
+                    request_data.principal(),
+                    subscription_uuid
+                );
+                return Ok(Response::err(StatusCode::OK));
+            }
+        }
+    };
+
+    if let Some(Body::SubscriptionEnd(end)) = &message.body {
+        enrollment::handle_subscription_end(
+            &subscription,
+            request_data.principal(),
+            request_data.remote_addr(),
+            end,
+        );
+    }
+    Ok(Response::err(StatusCode::OK))
+}
+
 async fn handle_heartbeat(
     subscriptions: Subscriptions,
     heartbeat_tx: mpsc::Sender<WriteHeartbeatMessage>,
@@ -303,7 +451,11 @@ async fn handle_heartbeat(
     };
 
     let machine_id = message.header().machine_id().map(|m| m.as_str());
-    if !subscription.data().is_active_for(request_data.principal(), machine_id) {
+    if !subscription.data().is_active_for_context(
+        &ClientContext::new(request_data.principal(), machine_id)
+            .with_ip(Some(request_data.remote_addr().ip()))
+            .with_san(request_data.san()),
+    ) {
         debug!(
             "Received Heartbeat from {}:{} ({}, {}) for subscription {} ({}) but the client is not allowed to use the subscription.",
             request_data.remote_addr().ip(),
@@ -316,6 +468,24 @@ async fn handle_heartbeat(
         return Ok(Response::err(StatusCode::FORBIDDEN));
     }
 
+    if !subscription
+        .data()
+        .require_auth()
+        .allows(request_data.authentication_method())
+    {
+        debug!(
+            "Received Heartbeat from {}:{} ({}) for subscription {} ({}) but the client authentication method {:?} is not allowed ({} is required).",
+            request_data.remote_addr().ip(),
+            request_data.remote_addr().port(),
+            request_data.principal(),
+            subscription.data().name(),
+            subscription.uuid_string(),
+            request_data.authentication_method(),
+            subscription.data().require_auth(),
+        );
+        return Ok(Response::err(StatusCode::FORBIDDEN));
+    }
+
     debug!(
         "Received Heartbeat from {}:{} ({:?}) for subscription {} ({})",
         request_data.remote_addr().ip(),
@@ -336,21 +506,249 @@ async fn handle_heartbeat(
     .context("Failed to store heartbeat")?;
 
     counter!(INPUT_MESSAGES_COUNTER, MESSAGES_ACTION => MESSAGES_ACTION_HEARTBEAT).increment(1);
+    counter!(INPUT_HEARTBEATS_COUNTER,
+        SUBSCRIPTION_NAME => subscription.data().qualified_name(),
+        SUBSCRIPTION_UUID => subscription.uuid_string())
+    .increment(1);
 
     Ok(Response::ok(ACTION_ACK, None))
 }
 
+/// Events formatted using one format and field map
+struct FormattedEvents {
+    content: Arc<Vec<Arc<Vec<u8>>>>,
+    /// `System/Level` of each formatted event (only used for sampling)
+    levels: Vec<Option<u8>>,
+    /// Provider of each formatted event (only used for provider routing)
+    providers: Vec<Option<String>>,
+    /// Raw events that could not be formatted (only used for dead letters)
+    failed: Vec<Arc<Vec<u8>>>,
+    /// Fields of each formatted event (only set if events are parsed)
+    fields: Vec<Arc<EventFields>>,
+    /// Each formatted event (only used for change detection and max_queue_age)
+    data: Vec<Arc<EventData>>,
+    /// Index in the batch of each formatted event (only used in sequential
+    /// output mode)
+    indexes: Vec<usize>,
+}
+
+/// Applies the provider routing, the sampling, the maximum queue age and the
+/// change detection of `output` to `items`, which match the formatted events
+/// one to one.
+/// Returns None if the output receives all the events. Otherwise, returns the
+/// selected items and the values to record for change detection once they
+/// have been written.
+fn select_events<T: Clone>(
+    output: &Output,
+    metadata: &EventMetadata,
+    items: &[T],
+    formatted: &FormattedEvents,
+) -> Option<(Vec<T>, Option<FieldValues>)> {
+    if output.router().is_none()
+        && output.sampler().is_none()
+        && output.age_limit().is_none()
+        && output.change_detector().is_none()
+    {
+        return None;
+    }
+    // Events are selected using their index. Provider routing is applied
+    // before sampling, and change detection only sees the remaining events.
+    let all: Vec<usize> = (0..items.len()).collect();
+    let (mut indexes, levels) = match output.router() {
+        Some(router) => router.route(&all, &formatted.levels, &formatted.providers),
+        None => (all, formatted.levels.clone()),
+    };
+    if let Some(sampler) = output.sampler() {
+        let (kept, dropped) = sampler.sample(&indexes, &levels);
+        sampling::count_dropped(metadata, &dropped);
+        indexes = kept;
+    }
+    if let Some(age_limit) = output.age_limit() {
+        let created: Vec<Option<DateTime<Utc>>> = indexes
+            .iter()
+            .map(|index| {
+                formatted
+                    .data
+                    .get(*index)
+                    .and_then(|data| stale::time_created(data))
+            })
+            .collect();
+        let (kept, dropped) = age_limit.select(&indexes, &created, Utc::now());
+        if dropped > 0 {
+            stale::count_dropped(metadata, output.driver_kind(), dropped);
+        }
+        indexes = kept;
+    }
+    let mut last_values = None;
+    if let Some(detector) = output.change_detector() {
+        let values: Vec<Option<FieldValues>> = indexes
+            .iter()
+            .map(|index| {
+                formatted
+                    .data
+                    .get(*index)
+                    .and_then(|data| detector.values(metadata, data))
+            })
+            .collect();
+        let (kept, last) = detector.select(metadata.principal(), &indexes, &values);
+        dedupe::count_suppressed(
+            metadata,
+            output.driver_kind(),
+            (indexes.len() - kept.len()) as u64,
+        );
+        indexes = kept;
+        last_values = last;
+    }
+    let items = indexes
+        .into_iter()
+        .map(|index| items[index].clone())
+        .collect();
+    Some((items, last_values))
+}
+
+/// Returns the items at the given positions
+fn pick<T: Clone>(items: &[T], positions: &[usize]) -> Vec<T> {
+    positions
+        .iter()
+        .filter_map(|position| items.get(*position).cloned())
+        .collect()
+}
+
+/// Events of a batch to be written by an output
+struct OutputWrite {
+    output: Output,
+    content: Arc<Vec<Arc<Vec<u8>>>>,
+    fields: Option<Arc<Vec<Arc<EventFields>>>>,
+    /// Index in the batch of each event (only set in sequential output mode
+    /// for outputs receiving the events of the batch)
+    indexes: Option<Vec<usize>>,
+    /// Values to record for change detection once the events are written
+    last_values: Option<FieldValues>,
+}
+
+impl OutputWrite {
+    fn new(output: Output, content: Arc<Vec<Arc<Vec<u8>>>>) -> Self {
+        Self {
+            output,
+            content,
+            fields: None,
+            indexes: None,
+            last_values: None,
+        }
+    }
+}
+
+/// Writes events to an output
+async fn write_events(
+    output: &Output,
+    metadata: &Arc<EventMetadata>,
+    content: Arc<Vec<Arc<Vec<u8>>>>,
+    fields: Option<Arc<Vec<Arc<EventFields>>>>,
+) -> Result<(), OutputDriverError> {
+    output
+        .write_with_fields(metadata.clone(), content, fields)
+        .await
+        .with_context(|| format!("Failed to write event to output {}", output.describe()))
+        .map_err(|e| OutputDriverError {
+            driver: output.driver(),
+            error: e,
+        })
+}
+
+/// Records the values of change detection once events have been written,
+/// so that a batch sent again after a failure is not considered unchanged
+fn record_last_values(write: OutputWrite, metadata: &EventMetadata) {
+    if let (Some(last_values), Some(detector)) = (write.last_values, write.output.change_detector())
+    {
+        detector.record(metadata.principal(), last_values);
+    }
+}
+
+/// Writes all the events of a batch to an output, independently of the
+/// other outputs
+async fn write_output(
+    write: OutputWrite,
+    metadata: Arc<EventMetadata>,
+) -> Result<(), OutputDriverError> {
+    write_events(
+        &write.output,
+        &metadata,
+        write.content.clone(),
+        write.fields.clone(),
+    )
+    .await?;
+    record_last_values(write, &metadata);
+    Ok(())
+}
+
+/// Writes the events of a batch one at a time: each event is written by
+/// every output, in declaration order, before the next one. Outputs which
+/// do not receive the events of the batch (summaries and "source first seen"
+/// events) are written first. Writing stops at the first failure, so that
+/// an event is never written by an output if a previous output failed.
+async fn write_sequentially(
+    writes: Vec<OutputWrite>,
+    metadata: Arc<EventMetadata>,
+) -> Result<(), OutputDriverError> {
+    for write in writes.iter().filter(|write| write.indexes.is_none()) {
+        write_events(
+            &write.output,
+            &metadata,
+            write.content.clone(),
+            write.fields.clone(),
+        )
+        .await?;
+    }
+
+    let count = writes
+        .iter()
+        .filter_map(|write| write.indexes.as_ref()?.last())
+        .max()
+        .map_or(0, |last| last + 1);
+    // Position of the next event to write for each output
+    let mut positions = vec![0; writes.len()];
+    for index in 0..count {
+        for (write, position) in writes.iter().zip(positions.iter_mut()) {
+            // Events may not be received by every output
+            if write
+                .indexes
+                .as_ref()
+                .and_then(|indexes| indexes.get(*position))
+                != Some(&index)
+            {
+                continue;
+            }
+            let content = Arc::new(vec![write.content[*position].clone()]);
+            let fields = write
+                .fields
+                .as_ref()
+                .map(|fields| Arc::new(fields.get(*position).cloned().into_iter().collect()));
+            *position += 1;
+            write_events(&write.output, &metadata, content, fields).await?;
+        }
+    }
+
+    for write in writes {
+        record_last_values(write, &metadata);
+    }
+    Ok(())
+}
+
 fn get_formatted_events(
     events: &[Arc<String>],
     need_to_parse_event: bool,
-    formats: &HashSet<SubscriptionOutputFormat>,
+    formats: &HashSet<FormatKey>,
     metadata: &Arc<EventMetadata>,
-) -> HashMap<SubscriptionOutputFormat, Arc<Vec<Arc<String>>>> {
+    clock_skew: Option<ClockSkewChecker>,
+    dedup_window: Option<&DedupWindow>,
+    rendering_trimmer: Option<&RenderingTrimmer>,
+) -> (HashMap<FormatKey, FormattedEvents>, BatchKeys) {
     let mut events_data = Vec::with_capacity(events.len());
+    let mut dedup_keys = BatchKeys::default();
     for raw in events.iter() {
         // EventData parses the raw event into an Event struct
         // (once for all formatters).
-        let event_data = EventData::new(raw.clone(), need_to_parse_event);
+        let mut event_data = EventData::new(raw.clone(), need_to_parse_event);
 
         if need_to_parse_event {
             // Count failures
@@ -359,7 +757,7 @@ fn get_formatted_events(
                     if let Some(error) = &event.additional.error {
                         let error_type_str: &'static str = error.error_type.clone().into();
                         counter!(INPUT_EVENT_PARSING_FAILURES,
-                            SUBSCRIPTION_NAME => metadata.subscription_name().to_owned(),
+                            SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
                             SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
                             INPUT_EVENT_PARSING_FAILURE_ERROR_TYPE => error_type_str)
                         .increment(1);
@@ -368,7 +766,7 @@ fn get_formatted_events(
                 }
                 None => {
                     counter!(INPUT_EVENT_PARSING_FAILURES,
-                        SUBSCRIPTION_NAME => metadata.subscription_name().to_owned(),
+                        SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
                         SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
                         INPUT_EVENT_PARSING_FAILURE_ERROR_TYPE => "Unknown")
                     .increment(1);
@@ -379,30 +777,81 @@ fn get_formatted_events(
                 }
             }
         }
-        events_data.push(event_data)
+
+        if let Some(checker) = clock_skew {
+            if !checker.apply(metadata, &mut event_data) {
+                continue;
+            }
+        }
+        if let Some(window) = dedup_window {
+            if !window.apply(metadata, &event_data, &mut dedup_keys) {
+                continue;
+            }
+        }
+        if let Some(event_names) = event_names::get() {
+            event_names.apply(&mut event_data);
+        }
+        if let Some(trimmer) = rendering_trimmer {
+            trimmer.apply(&mut event_data);
+        }
+        events_data.push(Arc::new(event_data))
     }
 
-    let mut formatted_events: HashMap<SubscriptionOutputFormat, Arc<Vec<Arc<String>>>> =
-        HashMap::new();
-    for format in formats {
+    let mut formatted_events: HashMap<FormatKey, FormattedEvents> = HashMap::new();
+    for format_key in formats {
         let mut content = Vec::new();
-        let formatter = get_formatter(format);
-        for event_data in events_data.iter() {
-            if let Some(str) = formatter.format(metadata, event_data) {
-                content.push(str.clone())
+        let mut levels = Vec::new();
+        let mut providers = Vec::new();
+        let mut failed = Vec::new();
+        let mut fields = Vec::new();
+        let mut data = Vec::new();
+        let mut indexes = Vec::new();
+        let formatter = get_formatter(
+            format_key.format(),
+            format_key.field_map(),
+            format_key.include_collector_info(),
+            format_key.format_options(),
+        );
+        for (index, event_data) in events_data.iter().enumerate() {
+            if let Some(formatted) = formatter.format(metadata, event_data) {
+                content.push(formatted);
+                levels.push(
+                    event_data
+                        .event()
+                        .and_then(|event| event.system.as_ref())
+                        .and_then(|system| system.level),
+                );
+                providers.push(routing::provider(event_data));
+                if need_to_parse_event {
+                    fields.push(Arc::new(EventFields::from(event_data.as_ref())));
+                }
+                data.push(event_data.clone());
+                indexes.push(index);
             } else {
-                let format_str: &'static str = format.into();
+                let format_str: &'static str = format_key.format().into();
                 counter!(OUTPUT_FORMAT_FAILURES,
-                    SUBSCRIPTION_NAME => metadata.subscription_name().to_owned(),
+                    SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
                     SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
                     OUTPUT_FORMAT => format_str)
                 .increment(1);
                 warn!("Failed to format an event using {}", format_str);
+                failed.push(Arc::new(event_data.raw().as_bytes().to_vec()));
             }
         }
-        formatted_events.insert(format.clone(), Arc::new(content));
+        formatted_events.insert(
+            format_key.clone(),
+            FormattedEvents {
+                content: Arc::new(content),
+                levels,
+                providers,
+                failed,
+                fields,
+                data,
+                indexes,
+            },
+        );
     }
-    formatted_events
+    (formatted_events, dedup_keys)
 }
 
 async fn handle_events(
@@ -435,7 +884,11 @@ async fn handle_events(
         };
 
         let machine_id = message.header().machine_id().map(|m| m.as_str());
-        if !subscription.data().is_active_for(request_data.principal(), machine_id) {
+        if !subscription.data().is_active_for_context(
+            &ClientContext::new(request_data.principal(), machine_id)
+                .with_ip(Some(request_data.remote_addr().ip()))
+                .with_san(request_data.san()),
+        ) {
             debug!(
                 "Received Events from {}:{} ({}, {}) for subscription {} ({}) but the client is not allowed to use this subscription.",
                 request_data.remote_addr().ip(),
@@ -448,6 +901,24 @@ async fn handle_events(
             return Ok(Response::err(StatusCode::FORBIDDEN));
         }
 
+        if !subscription
+            .data()
+            .require_auth()
+            .allows(request_data.authentication_method())
+        {
+            debug!(
+                "Received Events from {}:{} ({}) for subscription {} ({}) but the client authentication method {:?} is not allowed ({} is required).",
+                request_data.remote_addr().ip(),
+                request_data.remote_addr().port(),
+                request_data.principal(),
+                subscription.data().name(),
+                subscription.uuid_string(),
+                request_data.authentication_method(),
+                subscription.data().require_auth(),
+            );
+            return Ok(Response::err(StatusCode::FORBIDDEN));
+        }
+
         // Retrieve the public version sent by the client, not the one stored in memory
         let public_version = if let Some(public_version) = message.header().version() {
             public_version
@@ -456,6 +927,26 @@ async fn handle_events(
             return Ok(Response::err(StatusCode::BAD_REQUEST));
         };
 
+        // Only a limited number of clients may send events for the
+        // subscription at the same time
+        let _client_permit = match subscription.client_limiter() {
+            Some(limiter) => match limiter.try_acquire(request_data.principal()) {
+                Some(permit) => Some(permit),
+                None => {
+                    debug!(
+                        "Reject events from {}:{} ({}) for subscription {} ({}) because too many clients are being serviced",
+                        request_data.remote_addr().ip(),
+                        request_data.remote_addr().port(),
+                        request_data.principal(),
+                        subscription.data().name(),
+                        subscription.uuid_string()
+                    );
+                    return Ok(Response::err(StatusCode::SERVICE_UNAVAILABLE));
+                }
+            },
+            None => None,
+        };
+
         debug!(
             "Received {} events from {}:{} ({}) for subscription {} ({})",
             events.len(),
@@ -471,13 +962,13 @@ async fn handle_events(
         let events_counter = match monitoring {
             Some(monitoring_conf) if monitoring_conf.count_input_events_per_machine() => {
                 counter!(INPUT_EVENTS_COUNTER,
-                    SUBSCRIPTION_NAME => subscription.data().name().to_owned(),
+                    SUBSCRIPTION_NAME => subscription.data().qualified_name(),
                     SUBSCRIPTION_UUID => subscription.uuid_string(),
                     MACHINE => request_data.principal().to_string())
             }
             _ => {
                 counter!(INPUT_EVENTS_COUNTER,
-                    SUBSCRIPTION_NAME => subscription.data().name().to_owned(),
+                    SUBSCRIPTION_NAME => subscription.data().qualified_name(),
                     SUBSCRIPTION_UUID => subscription.uuid_string())
             }
         };
@@ -486,22 +977,51 @@ async fn handle_events(
         let event_size_counter = match monitoring {
             Some(monitoring_conf) if monitoring_conf.count_input_event_bytes_per_machine() => {
                 counter!(INPUT_EVENT_BYTES_COUNTER,
-                    SUBSCRIPTION_NAME => subscription.data().name().to_owned(),
+                    SUBSCRIPTION_NAME => subscription.data().qualified_name(),
                     SUBSCRIPTION_UUID => subscription.uuid_string(),
                     MACHINE => request_data.principal().to_string())
             }
             _ => {
                 counter!(INPUT_EVENT_BYTES_COUNTER,
-                    SUBSCRIPTION_NAME => subscription.data().name().to_owned(),
+                    SUBSCRIPTION_NAME => subscription.data().qualified_name(),
                     SUBSCRIPTION_UUID => subscription.uuid_string())
             }
         };
-        event_size_counter.increment(
-            events
-                .iter()
-                .fold(0, |acc, event| acc + event.len())
-                .try_into()?,
-        );
+        let events_size: u64 = events
+            .iter()
+            .fold(0, |acc, event| acc + event.len())
+            .try_into()?;
+        event_size_counter.increment(events_size);
+
+        // Events are kept in memory until they have been written by all the
+        // outputs of the subscription, which is bounded by the queues
+        let mut queue_guards = Vec::new();
+        for queue in [queue::global(), subscription.queue()]
+            .into_iter()
+            .flatten()
+        {
+            match queue.push(events.len().try_into()?, events_size).await {
+                Some(guard) => queue_guards.push(guard),
+                None => {
+                    debug!(
+                        "Reject events from {}:{} ({}) for subscription {} ({}) because the queue is full",
+                        request_data.remote_addr().ip(),
+                        request_data.remote_addr().port(),
+                        request_data.principal(),
+                        subscription.data().name(),
+                        subscription.uuid_string()
+                    );
+                    return Ok(Response::err(StatusCode::SERVICE_UNAVAILABLE));
+                }
+            }
+        }
+
+        // Wait until the subscription can process another batch. Queued
+        // batches are still accounted in the event queues while waiting.
+        let _batch_permit = match subscription.batch_limiter() {
+            Some(limiter) => Some(limiter.acquire().await?),
+            None => None,
+        };
 
         let metadata = Arc::new(EventMetadata::new(
             request_data.remote_addr(),
@@ -512,24 +1032,42 @@ async fn handle_events(
             message.header().revision().cloned(),
         ));
 
-        let need_to_parse_event = subscription
-            .formats()
-            .iter()
-            .any(|format| format.needs_parsed_event());
+        let clock_skew = ClockSkewChecker::from_settings(server);
+        let rendering_trimmer = RenderingTrimmer::from_subscription(subscription.data());
+
+        // Sampling relies on the level of events, provider routing on their
+        // provider, change detection on their fields, clock skew checking
+        // and maximum queue age on their creation time and the dedup window
+        // on their EventRecordID, which requires parsing them
+        let need_to_parse_event = clock_skew.is_some()
+            || subscription.dedup_window().is_some()
+            || subscription.has_sampling_outputs()
+            || subscription.has_age_limited_outputs()
+            || subscription.has_routed_outputs()
+            || subscription.has_event_fields_outputs()
+            || subscription.has_dedupe_outputs()
+            || subscription
+                .formats()
+                .iter()
+                .any(|format_key| format_key.format().needs_parsed_event());
 
-        let formatted_events = if need_to_parse_event {
+        let (formatted_events, dedup_keys) = if need_to_parse_event {
             // Parsing events takes time. In addition, if a formatter needs parsed events,
             // it probably performs some serialization which takes time and should be done in a
             // blocking task.
             let task_events = events.clone();
             let task_formats = subscription.formats().clone();
             let task_metadata = metadata.clone();
+            let task_dedup_window = subscription.dedup_window().cloned();
             tokio::task::spawn_blocking(move || {
                 get_formatted_events(
                     &task_events,
                     need_to_parse_event,
                     &task_formats,
                     &task_metadata,
+                    clock_skew,
+                    task_dedup_window.as_ref(),
+                    rendering_trimmer.as_ref(),
                 )
             })
             .await?
@@ -539,52 +1077,214 @@ async fn handle_events(
                 need_to_parse_event,
                 subscription.formats(),
                 &metadata,
+                clock_skew,
+                subscription.dedup_window(),
+                rendering_trimmer.as_ref(),
             )
         };
 
+        // A source is first seen if it is not known in memory and has no bookmark
+        // stored in database for this subscription
+        let source_first_seen_event = if subscription.has_source_first_seen_outputs() {
+            let first_seen = subscription
+                .known_sources()
+                .is_first_seen(request_data.principal(), async {
+                    Ok(db
+                        .get_bookmark(request_data.principal(), &subscription.uuid_string())
+                        .await
+                        .context("Failed to retrieve bookmark")?
+                        .is_some())
+                })
+                .await?;
+            if first_seen {
+                debug!(
+                    "Source {}:{} ({}) is seen for the first time for subscription {} ({})",
+                    request_data.remote_addr().ip(),
+                    request_data.remote_addr().port(),
+                    request_data.principal(),
+                    subscription.data().name(),
+                    subscription.uuid_string()
+                );
+                Some(Arc::new(vec![Arc::new(
+                    first_seen::format(&metadata)?.into_bytes(),
+                )]))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        // Summary records are computed from the events sent by the client only
+        let summary_event = if subscription.has_summary_outputs() {
+            Some(Arc::new(vec![Arc::new(
+                summary::format(&metadata, events)?.into_bytes(),
+            )]))
+        } else {
+            None
+        };
+
+        let has_dead_letter_outputs = subscription.has_dead_letter_outputs();
+        let mut dead_letters = Vec::new();
         let mut handles = JoinSet::new();
 
+        let sequential = subscription.data().output_mode() == OutputMode::Sequential;
+        let mut sequential_writes = Vec::new();
+
         // Spawn tasks to write events to every outputs of the subscription
         for output in subscription.outputs() {
             let output_cloned = output.clone();
+            // Dead-letter outputs only receive failure records, once all
+            // the other outputs are done
+            if output_cloned.dead_letter() {
+                continue;
+            }
+            let write = if output_cloned.source_first_seen() {
+                // This output only receives the "source first seen" event
+                match &source_first_seen_event {
+                    Some(event) => OutputWrite::new(output_cloned, event.clone()),
+                    None => continue,
+                }
+            } else if output_cloned.summary() {
+                // This output only receives the summary of the batch
+                match &summary_event {
+                    Some(event) => OutputWrite::new(output_cloned, event.clone()),
+                    None => continue,
+                }
+            } else {
+                let formatted = formatted_events
+                    .get(output_cloned.format_key())
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Could not get formatted event for format {:?}",
+                            output_cloned.format_key()
+                        )
+                    })?;
+                if has_dead_letter_outputs && !formatted.failed.is_empty() {
+                    dead_letters.extend(dead_letter::records(
+                        &metadata,
+                        &Failure::format_error(&output_cloned),
+                        &formatted.failed,
+                    )?);
+                }
+                // Events, their fields and their index in the batch are
+                // selected together, using their position
+                let all: Vec<usize> = (0..formatted.content.len()).collect();
+                let (positions, last_values) =
+                    match select_events(&output_cloned, &metadata, &all, formatted) {
+                        Some((positions, last_values)) => (Some(positions), last_values),
+                        None => (None, None),
+                    };
+                let content = match &positions {
+                    Some(positions) => Arc::new(pick(&formatted.content, positions)),
+                    None => formatted.content.clone(),
+                };
+                let fields = output_cloned.needs_event_fields().then(|| {
+                    Arc::new(match &positions {
+                        Some(positions) => pick(&formatted.fields, positions),
+                        None => formatted.fields.clone(),
+                    })
+                });
+                let indexes = sequential.then(|| match &positions {
+                    Some(positions) => pick(&formatted.indexes, positions),
+                    None => formatted.indexes.clone(),
+                });
+                OutputWrite {
+                    output: output_cloned,
+                    content,
+                    fields,
+                    indexes,
+                    last_values,
+                }
+            };
+
+            // Degraded outputs are skipped (but the batch is still acknowledged)
+            if write.output.is_degraded() {
+                degradation::count_skipped(
+                    &metadata,
+                    write.output.driver_kind(),
+                    write.content.len() as u64,
+                );
+                if has_dead_letter_outputs {
+                    dead_letters.extend(dead_letter::records(
+                        &metadata,
+                        &Failure::degraded(&write.output),
+                        &write.content,
+                    )?);
+                }
+                continue;
+            }
+
+            if sequential {
+                // Outputs are written one after the other, once all of them
+                // are known
+                sequential_writes.push(write);
+                continue;
+            }
+
+            // Writing to a failing output is a retry: wait until the number of
+            // running retries of the subscription is below the limit
+            let retry_permit = match subscription.retry_limiter() {
+                Some(limiter) => limiter.acquire_for(&write.output).await?,
+                None => None,
+            };
+
             let metadata_cloned = metadata.clone();
-            let content = formatted_events
-                .get(output_cloned.format())
-                .ok_or_else(|| {
-                    anyhow!(
-                        "Could not get formatted event for format {:?}",
-                        output_cloned.format()
-                    )
-                })?
-                .clone();
+            handles.spawn(async move {
+                let _retry_permit = retry_permit;
+                write_output(write, metadata_cloned).await
+            });
+        }
 
+        if sequential && !sequential_writes.is_empty() {
+            // Outputs are written by a single task, which counts as one retry
+            // if any of them is failing
+            let retry_permit = match subscription.retry_limiter() {
+                Some(limiter)
+                    if sequential_writes
+                        .iter()
+                        .any(|write| write.output.is_failing()) =>
+                {
+                    Some(limiter.acquire().await?)
+                }
+                _ => None,
+            };
+            let metadata_cloned = metadata.clone();
             handles.spawn(async move {
-                output_cloned
-                    .write(metadata_cloned, content)
-                    .await
-                    .with_context(|| {
-                        format!(
-                            "Failed to write event to output {}",
-                            output_cloned.describe()
-                        )
-                    })
-                    .map_err(|e| OutputDriverError {
-                        driver: output_cloned.driver(),
-                        error: e,
-                    })
+                let _retry_permit = retry_permit;
+                write_sequentially(sequential_writes, metadata_cloned).await
             });
         }
 
-        // Wait for all tasks to finish
+        // Wait for all tasks to finish, unless the batch is evicted from
+        // a queue in the meantime
         let mut succeed = true;
-        while let Some(res) = handles.join_next().await {
+        let evicted = queue::evicted(&queue_guards);
+        tokio::pin!(evicted);
+        loop {
+            let res = tokio::select! {
+                res = handles.join_next() => match res {
+                    Some(res) => res,
+                    None => break,
+                },
+                _ = &mut evicted => {
+                    warn!(
+                        "Events from {} for subscription {} ({}) have been evicted from a full queue",
+                        request_data.principal(),
+                        subscription.data().name(),
+                        subscription.uuid_string()
+                    );
+                    // Dropping the JoinSet aborts the running writes
+                    return Ok(Response::err(StatusCode::SERVICE_UNAVAILABLE));
+                }
+            };
             match res {
                 Ok(Ok(())) => (),
                 Ok(Err(err)) => {
                     succeed = false;
                     warn!("Failed to process output and send event: {:?}", err.error);
                     counter!(OUTPUT_DRIVER_FAILURES,
-                        SUBSCRIPTION_NAME => subscription.data().name().to_owned(),
+                        SUBSCRIPTION_NAME => subscription.data().qualified_name(),
                         SUBSCRIPTION_UUID => subscription.uuid_string(),
                         OUTPUT_DRIVER => err.driver.clone())
                     .increment(1);
@@ -593,7 +1293,7 @@ async fn handle_events(
                     succeed = false;
                     warn!("Something bad happened with a process task: {:?}", err);
                     counter!(OUTPUT_DRIVER_FAILURES,
-                        SUBSCRIPTION_NAME => subscription.data().name().to_owned(),
+                        SUBSCRIPTION_NAME => subscription.data().qualified_name(),
                         SUBSCRIPTION_UUID => subscription.uuid_string(),
                         OUTPUT_DRIVER => "Unknown")
                     .increment(1);
@@ -601,10 +1301,25 @@ async fn handle_events(
             }
         }
 
+        if !dead_letters.is_empty() {
+            dead_letter::write(&subscription, &metadata, dead_letters).await;
+        }
+
         if !succeed {
             return Ok(Response::err(StatusCode::SERVICE_UNAVAILABLE));
         }
 
+        if source_first_seen_event.is_some() {
+            subscription
+                .known_sources()
+                .insert(request_data.principal());
+        }
+
+        // Events sent again by the client are dropped from now on
+        if let Some(window) = subscription.dedup_window() {
+            window.record(dedup_keys);
+        }
+
         let bookmark = message
             .header()
             .bookmarks()
@@ -660,8 +1375,11 @@ pub async fn handle_message(
         handle_enumerate(collector, &db, subscriptions, request_data, auth_ctx, message)
             .await
             .context("Failed to handle Enumerate action")
-    } else if action == ACTION_END || action == ACTION_SUBSCRIPTION_END {
+    } else if action == ACTION_END {
         Ok(Response::err(StatusCode::OK))
+    } else if action == ACTION_SUBSCRIPTION_END {
+        handle_subscription_end(subscriptions, request_data, message)
+            .context("Failed to handle SubscriptionEnd action")
     } else if action == ACTION_HEARTBEAT {
         handle_heartbeat(subscriptions, heartbeat_tx, request_data, message)
             .await
@@ -682,3 +1400,284 @@ pub async fn handle_message(
         Err(anyhow!("Unsupported message {}", action))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet, io::Cursor, net::SocketAddr, str::FromStr, sync::Mutex,
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use chrono::TimeZone;
+    use common::{
+        settings::Outputs,
+        subscription::{
+            SubscriptionOutput, SubscriptionOutputDriver, SubscriptionOutputFormat,
+            UnixDatagramConfiguration,
+        },
+    };
+    use quick_xml::Writer;
+
+    use super::*;
+    use crate::{
+        output::{OutputDriver, OutputDriversContext},
+        soap::Serializable,
+    };
+
+    const COLLECTOR: &str = r#"
+        hostname = "wec.windomain.local"
+        listen_address = "0.0.0.0"
+
+        [authentication]
+        type = "Kerberos"
+        service_principal_name = "http/wec.windomain.local@WINDOMAIN.LOCAL"
+    "#;
+
+    fn subscription_body(data: SubscriptionData, fallback: bool) -> Result<String> {
+        let collector: Collector = toml::from_str(COLLECTOR)?;
+        let mut output_context = OutputDriversContext::new(&Outputs::default());
+        let subscription = Arc::new(Subscription::from_data(data, &mut output_context)?);
+        let body = create_subscription_body(
+            &subscription,
+            None,
+            &collector,
+            "wec.windomain.local",
+            &AuthenticationContext::Tls(
+                "win10.windomain.local".to_string(),
+                "ABCD".to_string(),
+                Arc::new(Vec::new()),
+            ),
+            "win10.windomain.local",
+            fallback,
+        );
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        body.serialize(&mut writer)?;
+        Ok(String::from_utf8(writer.into_inner().into_inner())?)
+    }
+
+    #[test]
+    fn test_subscription_body_locales() -> Result<()> {
+        let mut data = SubscriptionData::new("Test", "");
+        let xml = subscription_body(data.clone(), false)?;
+        assert!(!xml.contains("<w:Locale"));
+        assert!(!xml.contains("<p:DataLocale"));
+
+        data.set_content_format(common::subscription::ContentFormat::RenderedText)
+            .set_locale(Some("fr-FR".to_string()))
+            .set_data_locale(Some("fr-CA".to_string()));
+        let xml = subscription_body(data.clone(), false)?;
+        assert!(xml.contains(r#"<w:Locale xml:lang="fr-FR" s:mustUnderstand="false"/>"#));
+        assert!(xml.contains(r#"<p:DataLocale xml:lang="fr-CA" s:mustUnderstand="false"/>"#));
+
+        // Locales are left out of the fallback manifest if requested
+        data.set_enrollment_fallback(BTreeSet::from([ManifestOption::Locale]));
+        let xml = subscription_body(data.clone(), true)?;
+        assert!(!xml.contains("<w:Locale"));
+        assert!(xml.contains(r#"<p:DataLocale xml:lang="fr-CA" s:mustUnderstand="false"/>"#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscription_body_read_existing_events_since() -> Result<()> {
+        let query =
+            r#"<QueryList><Query Id="0"><Select Path="Security">*</Select></Query></QueryList>"#;
+        let mut data = SubscriptionData::new("Test", query);
+        data.set_read_existing_events(true);
+        let xml = subscription_body(data.clone(), false)?;
+        assert!(xml.contains(r#"<Select Path="Security">*</Select></Query>"#));
+        assert!(!xml.contains("<Suppress"));
+
+        data.set_read_existing_events_since(Some(
+            Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap(),
+        ));
+        let xml = subscription_body(data.clone(), false)?;
+        assert!(xml.contains(r#"<Select Path="Security">*</Select><Suppress Path="Security">*[System[TimeCreated[@SystemTime&lt;'2024-03-01T10:00:00.000Z']]]</Suppress></Query>"#));
+        // The stored query is unchanged
+        assert_eq!(data.query(), query);
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscription_body_connection_retry_jitter() -> Result<()> {
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_connection_retry_count(5)
+            .set_connection_retry_interval(60);
+        let xml = subscription_body(data.clone(), false)?;
+        assert!(xml.contains(r#"<w:ConnectionRetry Total="5">PT60.0S</w:ConnectionRetry>"#));
+
+        // Each client is advertised its own interval
+        data.set_connection_retry_jitter(Some(50));
+        let interval = data.client_connection_retry_interval("win10.windomain.local");
+        assert!((30..=90).contains(&interval));
+        let xml = subscription_body(data.clone(), false)?;
+        assert!(xml.contains(&format!(
+            r#"<w:ConnectionRetry Total="5">PT{}.0S</w:ConnectionRetry>"#,
+            interval
+        )));
+        Ok(())
+    }
+
+    fn subscription_header(data: &SubscriptionData) -> Result<String> {
+        let header = create_subscription_header(data, false);
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        header.serialize(&mut writer)?;
+        Ok(String::from_utf8(writer.into_inner().into_inner())?)
+    }
+
+    #[test]
+    fn test_subscription_header_compression() -> Result<()> {
+        let mut data = SubscriptionData::new("Test", "");
+        let xml = subscription_header(&data)?;
+        assert!(xml.contains(r#"<w:Option Name="Compression">SLDC</w:Option>"#));
+
+        data.set_compression(EventsCompression::None);
+        let xml = subscription_header(&data)?;
+        assert!(!xml.contains(r#"Name="Compression""#));
+        // Other options are still sent
+        assert!(xml.contains(r#"<w:Option Name="SubscriptionName">Test</w:Option>"#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscription_header_ignore_channel_error_for() -> Result<()> {
+        let query = r#"<QueryList><Query Id="0"><Select Path="Security">*</Select><Select Path="Microsoft-Windows-Sysmon/Operational">*</Select></Query></QueryList>"#;
+        let mut data = SubscriptionData::new("Test", query);
+        let xml = subscription_header(&data)?;
+        assert!(xml.contains(r#"<w:Option Name="IgnoreChannelError" xsi:nil="true"/>"#));
+
+        // Errors of the Security channel must not be ignored
+        data.set_ignore_channel_error_for(Some(BTreeSet::from([
+            "Microsoft-Windows-Sysmon/Operational".to_string(),
+        ])));
+        let xml = subscription_header(&data)?;
+        assert!(xml.contains(r#"<w:Option Name="IgnoreChannelError" xsi:nil="false"/>"#));
+
+        data.set_ignore_channel_error(false)
+            .set_ignore_channel_error_for(Some(BTreeSet::from([
+                "Microsoft-Windows-Sysmon/Operational".to_string(),
+                "Security".to_string(),
+            ])));
+        let xml = subscription_header(&data)?;
+        assert!(xml.contains(r#"<w:Option Name="IgnoreChannelError" xsi:nil="true"/>"#));
+        // The query is unchanged
+        let xml = subscription_body(data, false)?;
+        assert!(xml.contains(r#"<Select Path="Security">*</Select><Select Path="Microsoft-Windows-Sysmon/Operational">*</Select></Query>"#));
+        Ok(())
+    }
+
+    /// Logs every write as "<name>:<events>", after an optional delay
+    struct MockDriver {
+        name: &'static str,
+        delay: Duration,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl OutputDriver for MockDriver {
+        async fn write(
+            &self,
+            _metadata: Arc<EventMetadata>,
+            events: Arc<Vec<Arc<Vec<u8>>>>,
+        ) -> Result<()> {
+            tokio::time::sleep(self.delay).await;
+            let events: Vec<String> = events
+                .iter()
+                .map(|event| String::from_utf8_lossy(event).into_owned())
+                .collect();
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{}:{}", self.name, events.join(",")));
+            Ok(())
+        }
+    }
+
+    /// Returns the metadata of a batch and a write of 3 events for outputs
+    /// A and B, in this order. A is slower than B.
+    fn mock_writes(
+        log: &Arc<Mutex<Vec<String>>>,
+    ) -> Result<(Arc<EventMetadata>, Vec<OutputWrite>)> {
+        let output = SubscriptionOutput::new(
+            SubscriptionOutputFormat::Raw,
+            SubscriptionOutputDriver::UnixDatagram(UnixDatagramConfiguration::new(
+                "/tmp/unused.socket".to_string(),
+            )),
+            true,
+        );
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![output.clone(), output]);
+        let mut output_context = OutputDriversContext::new(&Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        let metadata = Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ));
+        let content: Arc<Vec<Arc<Vec<u8>>>> = Arc::new(
+            (0..3)
+                .map(|i| Arc::new(format!("e{}", i).into_bytes()))
+                .collect(),
+        );
+        let writes = subscription
+            .outputs()
+            .iter()
+            .zip([("A", 50), ("B", 0)])
+            .map(|(output, (name, delay))| {
+                let mut output = output.clone();
+                output.set_driver(Arc::new(MockDriver {
+                    name,
+                    delay: Duration::from_millis(delay),
+                    log: log.clone(),
+                }));
+                OutputWrite {
+                    output,
+                    content: content.clone(),
+                    fields: None,
+                    indexes: Some(vec![0, 1, 2]),
+                    last_values: None,
+                }
+            })
+            .collect();
+        Ok((metadata, writes))
+    }
+
+    #[tokio::test]
+    async fn test_output_mode_parallel() -> Result<()> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (metadata, writes) = mock_writes(&log)?;
+
+        // As in handle_events, each output is written by its own task
+        let mut handles = JoinSet::new();
+        for write in writes {
+            handles.spawn(write_output(write, metadata.clone(), false));
+        }
+        while let Some(res) = handles.join_next().await {
+            assert!(res?.is_ok());
+        }
+
+        // Each output receives the whole batch at once, and B does not wait
+        // for A
+        assert_eq!(*log.lock().unwrap(), vec!["B:e0,e1,e2", "A:e0,e1,e2"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_output_mode_sequential() -> Result<()> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (metadata, writes) = mock_writes(&log)?;
+
+        assert!(write_sequentially(writes, metadata, false).await.is_ok());
+
+        // Each event is written by every output, in declaration order,
+        // before the next one
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["A:e0", "B:e0", "A:e1", "B:e1", "A:e2", "B:e2"]
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,8 @@
 use clap::{arg, command};
 use common::settings::{Settings, DEFAULT_CONFIG_FILE};
 
-use server::run;
-use std::env;
+use server::{config_check, run};
+use std::{env, path::Path};
 
 #[tokio::main]
 async fn main() {
@@ -14,6 +14,11 @@ async fn main() {
                 .required(false),
         )
         .arg(arg!(-v --verbosity ... "Sets the level of verbosity"))
+        .arg(
+            arg!(--"config-check" <PATH> "Validates the subscription config files found in PATH, then exits")
+                .required(false),
+        )
+        .arg(arg!(--"no-strict-compat" "With --config-check, only warn about outputs whose format is not compatible with their driver"))
         .get_matches();
 
     let config_file = matches.get_one::<String>("config");
@@ -25,6 +30,18 @@ async fn main() {
         }
     };
 
+    // Neither the database nor the network is used
+    if let Some(path) = matches.get_one::<String>("config-check") {
+        let no_strict_compat = matches
+            .get_one::<bool>("no-strict-compat")
+            .expect("Defaulted by clap");
+        std::process::exit(config_check::run(
+            &settings,
+            Path::new(path),
+            !no_strict_compat,
+        ));
+    }
+
     let verbosity = matches.get_count("verbosity");
 
     run(settings, verbosity).await;
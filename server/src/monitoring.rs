@@ -11,11 +11,12 @@ use metrics::{describe_counter, describe_gauge, describe_histogram, gauge, Unit}
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
 use tokio::time;
 
-use crate::subscription::Subscriptions;
+use crate::{sources::set_sources_gauge, subscription::Subscriptions};
 
 // input metrics
 
 pub const INPUT_MESSAGES_COUNTER: &str = "openwec_input_messages_total";
+pub const INPUT_HEARTBEATS_COUNTER: &str = "openwec_input_heartbeats_total";
 pub const MESSAGES_ACTION: &str = "action";
 pub const MESSAGES_ACTION_HEARTBEAT: &str = "heartbeat";
 pub const MESSAGES_ACTION_EVENTS: &str = "events";
@@ -29,6 +30,9 @@ pub const MACHINE: &str = "machine";
 pub const INPUT_EVENT_BYTES_COUNTER: &str = "openwec_input_event_bytes_total";
 pub const INPUT_EVENT_PARSING_FAILURES: &str = "openwec_input_event_parsing_failures_total";
 pub const INPUT_EVENT_PARSING_FAILURE_ERROR_TYPE: &str = "type";
+pub const INPUT_EVENTS_CLOCK_SKEW: &str = "openwec_input_events_clock_skew_total";
+pub const CLOCK_SKEW_POLICY: &str = "policy";
+pub const INPUT_EVENTS_DUPLICATES: &str = "openwec_input_duplicate_events_total";
 
 // http metrics
 
@@ -47,13 +51,31 @@ pub const HTTP_REQUEST_BODY_REAL_SIZE_BYTES_COUNTER: &str =
 
 pub const OUTPUT_DRIVER_FAILURES: &str = "openwec_output_driver_failures_total";
 pub const OUTPUT_DRIVER: &str = "driver";
+pub const OUTPUT_EVENTS_COUNTER: &str = "openwec_output_events_total";
+pub const OUTPUT_ERRORS_COUNTER: &str = "openwec_output_errors_total";
 pub const OUTPUT_FORMAT_FAILURES: &str = "openwec_output_format_failures_total";
 pub const OUTPUT_FORMAT: &str = "format";
+pub const OUTPUT_SAMPLING_DROPPED_EVENTS: &str = "openwec_output_sampling_dropped_events_total";
+pub const EVENT_LEVEL: &str = "level";
+pub const OUTPUT_DEGRADED_GAUGE: &str = "openwec_output_degraded";
+pub const OUTPUT_DEGRADED_SKIPPED_EVENTS: &str = "openwec_output_degraded_skipped_events_total";
+pub const OUTPUT_DEDUPE_SUPPRESSED_EVENTS: &str = "openwec_output_dedupe_suppressed_events_total";
+pub const OUTPUT_STALE_DROPPED_EVENTS: &str = "openwec_output_stale_dropped_events_total";
+pub const OUTPUT_TCP_BUFFER_DROPPED_EVENTS: &str = "openwec_output_tcp_buffer_dropped_events_total";
+pub const OUTPUT_TCP_BUFFER_EVENTS_GAUGE: &str = "openwec_output_tcp_buffer_events";
+
+// queue metrics
+
+pub const QUEUE_OVERFLOW_COUNTER: &str = "openwec_queue_overflow_total";
+pub const QUEUE_SCOPE: &str = "scope";
+pub const QUEUE_OVERFLOW_POLICY: &str = "policy";
 
 // machines metrics
 
 pub const MACHINES_GAUGE: &str = "openwec_machines";
 pub const MACHINES_STATE: &str = "state";
+pub const SUBSCRIPTION_SOURCES_GAUGE: &str = "openwec_subscription_sources";
+pub const ENROLLMENT_FAILURES_COUNTER: &str = "openwec_enrollment_failures_total";
 
 pub fn init(db: &Db, subscriptions: Subscriptions, settings: &Monitoring) -> Result<()> {
     let refresh_interval = settings.machines_refresh_interval();
@@ -103,11 +125,26 @@ pub fn init(db: &Db, subscriptions: Subscriptions, settings: &Monitoring) -> Res
         Unit::Count,
         "The total number of messages received by openwec"
     );
+    describe_counter!(
+        INPUT_HEARTBEATS_COUNTER,
+        Unit::Count,
+        "The total number of heartbeats received by openwec"
+    );
     describe_counter!(
         INPUT_EVENT_PARSING_FAILURES,
         Unit::Count,
         "The total number of event parsing failures"
     );
+    describe_counter!(
+        INPUT_EVENTS_CLOCK_SKEW,
+        Unit::Count,
+        "The total number of events created too far in the future"
+    );
+    describe_counter!(
+        INPUT_EVENTS_DUPLICATES,
+        Unit::Count,
+        "The total number of events dropped by the dedup window of subscriptions"
+    );
 
     // http
     describe_counter!(
@@ -137,11 +174,63 @@ pub fn init(db: &Db, subscriptions: Subscriptions, settings: &Monitoring) -> Res
         Unit::Count,
         "The total number of output driver failures"
     );
+    describe_counter!(
+        OUTPUT_EVENTS_COUNTER,
+        Unit::Count,
+        "The total number of events written by output drivers"
+    );
+    describe_counter!(
+        OUTPUT_ERRORS_COUNTER,
+        Unit::Count,
+        "The total number of failed writes of output drivers"
+    );
     describe_counter!(
         OUTPUT_FORMAT_FAILURES,
         Unit::Count,
         "The total number of output format failures"
     );
+    describe_counter!(
+        OUTPUT_SAMPLING_DROPPED_EVENTS,
+        Unit::Count,
+        "The total number of events dropped by outputs sampling"
+    );
+    describe_gauge!(
+        OUTPUT_DEGRADED_GAUGE,
+        Unit::Count,
+        "Whether an output is degraded (1) or not (0)"
+    );
+    describe_counter!(
+        OUTPUT_DEGRADED_SKIPPED_EVENTS,
+        Unit::Count,
+        "The total number of events not sent to degraded outputs"
+    );
+    describe_counter!(
+        OUTPUT_DEDUPE_SUPPRESSED_EVENTS,
+        Unit::Count,
+        "The total number of unchanged events not sent to outputs using dedupe_on_change"
+    );
+    describe_counter!(
+        OUTPUT_STALE_DROPPED_EVENTS,
+        Unit::Count,
+        "The total number of events older than max_queue_age not sent to outputs"
+    );
+    describe_counter!(
+        OUTPUT_TCP_BUFFER_DROPPED_EVENTS,
+        Unit::Count,
+        "The total number of events dropped because the buffer of a disconnected TCP output was full"
+    );
+    describe_gauge!(
+        OUTPUT_TCP_BUFFER_EVENTS_GAUGE,
+        Unit::Count,
+        "The number of events kept in the buffer of a disconnected TCP output"
+    );
+
+    // queue
+    describe_counter!(
+        QUEUE_OVERFLOW_COUNTER,
+        Unit::Count,
+        "The total number of events that overflowed a queue limit"
+    );
 
     // machines
     describe_gauge!(
@@ -149,6 +238,16 @@ pub fn init(db: &Db, subscriptions: Subscriptions, settings: &Monitoring) -> Res
         Unit::Count,
         "The number of machines known by openwec"
     );
+    describe_gauge!(
+        SUBSCRIPTION_SOURCES_GAUGE,
+        Unit::Count,
+        "The number of sources enrolled in a subscription"
+    );
+    describe_counter!(
+        ENROLLMENT_FAILURES_COUNTER,
+        Unit::Count,
+        "The total number of subscription manifests rejected by clients"
+    );
 
     Ok(())
 }
@@ -173,7 +272,7 @@ async fn refresh_machines_task(
                     let subscriptions_unlocked = subscriptions.read().unwrap();
                     let mut subscriptions_data = Vec::with_capacity(subscriptions_unlocked.len());
                     for (_, subscription) in subscriptions.read().unwrap().iter() {
-                        subscriptions_data.push((subscription.uuid_string(), subscription.data().name().to_string(), subscription.data().heartbeat_interval()));
+                        subscriptions_data.push((subscription.uuid_string(), subscription.data().qualified_name(), subscription.data().heartbeat_interval()));
                     }
                     subscriptions_data
                 };
@@ -210,6 +309,12 @@ async fn refresh_machines_task(
                         SUBSCRIPTION_UUID => subscription_uuid.clone(),
                         MACHINES_STATE => dead_str)
                         .set(stats.dead_machines_count() as f64);
+
+                    set_sources_gauge(
+                        &subscription_uuid,
+                        &subscription_name,
+                        stats.enrolled_machines_count(),
+                    );
                 }
             }
         }
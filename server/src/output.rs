@@ -1,22 +1,56 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use common::{
     settings::Outputs,
-    subscription::{SubscriptionData, SubscriptionOutputDriver, SubscriptionOutputFormat},
+    subscription::{
+        FieldMap, FormatOptions, PayloadCompression, SubscriptionData, SubscriptionOutput,
+        SubscriptionOutputDriver, SubscriptionOutputFormat,
+    },
 };
+use metrics::counter;
 
 use crate::{
+    dedupe::ChangeDetector,
+    degradation::{self, OutputHealth},
     drivers::{
+        event_hubs::OutputEventHubs,
         files::{OutputFiles, OutputFilesContext},
+        grpc::OutputGrpc,
+        http::OutputHttp,
         kafka::{OutputKafka, OutputKafkaContext},
+        loki::OutputLoki,
+        mqtt::OutputMqtt,
+        nats::OutputNats,
         redis::OutputRedis,
+        splunk_hec::OutputSplunkHec,
+        std_stream::{OutputStdStream, StdStream},
+        syslog::OutputSyslog,
+        syslog_tls::OutputSyslogTls,
         tcp::OutputTcp,
         unix::OutputUnixDatagram,
+        unix_stream::OutputUnixStream,
+        zmq::OutputZmq,
+    },
+    event::{EventData, EventFields, EventMetadata},
+    formats::{
+        avro::AvroFormat, cef::CefFormat, csv::CsvFormat, ecs::EcsFormat, field_map::MappedFormat,
+        json, json::JsonFormat, leef::LeefFormat, msgpack::MsgpackFormat, nxlog,
+        nxlog::NxlogFormat, protobuf::ProtobufFormat, raw::RawFormat, raw_json::RawJsonFormat,
+        rfc5424::Rfc5424Format, template::TemplateFormat,
+    },
+    monitoring::{
+        OUTPUT_DRIVER, OUTPUT_ERRORS_COUNTER, OUTPUT_EVENTS_COUNTER, SUBSCRIPTION_NAME,
+        SUBSCRIPTION_UUID,
     },
-    event::{EventData, EventMetadata},
-    formats::{json::JsonFormat, nxlog::NxlogFormat, raw::RawFormat, raw_json::RawJsonFormat},
+    routing::ProviderRouter,
+    sampling::LevelSampler,
+    stale::QueueAgeLimit,
 };
 
 pub struct OutputDriversContext {
@@ -42,7 +76,9 @@ impl OutputDriversContext {
         //      AND one output did not configure kafka options (such as bootstrap.servers)
 
         if Self::need_files_context(subscriptions) && self.files.is_none() {
-            self.files = Some(OutputFilesContext::new());
+            self.files = Some(OutputFilesContext::new(
+                self.settings.files().log_path_anonymization(),
+            ));
         }
 
         if Self::need_kafka_context(subscriptions) && self.kafka.is_none() {
@@ -96,45 +132,186 @@ impl OutputDriversContext {
     }
 }
 
+/// Events are formatted once per distinct format, field map, collector
+/// information setting and format options
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FormatKey {
+    format: SubscriptionOutputFormat,
+    field_map: FieldMap,
+    include_collector_info: bool,
+    format_options: FormatOptions,
+}
+
+impl FormatKey {
+    pub fn new(output: &SubscriptionOutput) -> Self {
+        Self {
+            format: output.format().clone(),
+            field_map: output.field_map().clone(),
+            include_collector_info: output.include_collector_info(),
+            format_options: output.format_options().clone(),
+        }
+    }
+
+    pub fn format(&self) -> &SubscriptionOutputFormat {
+        &self.format
+    }
+
+    pub fn field_map(&self) -> &FieldMap {
+        &self.field_map
+    }
+
+    pub fn include_collector_info(&self) -> bool {
+        self.include_collector_info
+    }
+
+    pub fn format_options(&self) -> &FormatOptions {
+        &self.format_options
+    }
+}
+
+/// Consecutive failed writes of an output
+#[derive(Debug, Clone)]
+pub struct FailureStreak {
+    attempts: u32,
+    first_failure_at: DateTime<Utc>,
+    last_error: String,
+}
+
+impl FailureStreak {
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn first_failure_at(&self) -> DateTime<Utc> {
+        self.first_failure_at
+    }
+
+    pub fn last_error(&self) -> &str {
+        &self.last_error
+    }
+}
+
 #[derive(Clone)]
 pub struct Output {
-    format: SubscriptionOutputFormat,
+    format_key: FormatKey,
     driver: Arc<dyn OutputDriver + Send + Sync>,
     // Only used for "describe()"
     subscription_output_driver: SubscriptionOutputDriver,
+    source_first_seen: bool,
+    summary: bool,
+    dead_letter: bool,
+    // Whether the driver needs the fields of each event
+    event_fields: bool,
+    sampler: Option<Arc<LevelSampler>>,
+    age_limit: Option<QueueAgeLimit>,
+    router: Option<Arc<ProviderRouter>>,
+    // Last values are shared between clones
+    change_detector: Option<Arc<ChangeDetector>>,
+    // Only set if a degradation policy is configured (shared between clones)
+    health: Option<Arc<OutputHealth>>,
+    // Set while the last writes failed (shared between clones)
+    failures: Arc<Mutex<Option<FailureStreak>>>,
+    write_timeout: Option<Duration>,
 }
 
 impl Output {
     pub fn new(
-        format: &SubscriptionOutputFormat,
-        driver: &SubscriptionOutputDriver,
+        output: &SubscriptionOutput,
+        event_ttl: Option<u32>,
         context: &mut OutputDriversContext,
     ) -> Result<Self> {
+        let driver = output.driver();
+        let compression = output.payload_compression();
+        if compression != PayloadCompression::None && !driver.supports_payload_compression() {
+            bail!(
+                "Driver {:?} can not tell consumers how payloads are compressed",
+                driver
+            );
+        }
         let output_driver: Arc<dyn OutputDriver + Send + Sync> = match driver {
             SubscriptionOutputDriver::Files(config) => {
-                Arc::new(OutputFiles::new(config, &context.files)?)
+                let formatter = get_formatter(
+                    output.format(),
+                    output.field_map(),
+                    output.include_collector_info(),
+                    output.format_options(),
+                );
+                Arc::new(
+                    OutputFiles::new(config, &context.files)?
+                        .with_header(formatter.header())
+                        .with_binary_layout(formatter.binary_file_layout()),
+                )
             }
-            SubscriptionOutputDriver::Kafka(config) => {
-                Arc::new(OutputKafka::new(config, &context.kafka)?)
+            SubscriptionOutputDriver::Kafka(config) => Arc::new(OutputKafka::new(
+                config,
+                output.format(),
+                compression,
+                &context.kafka,
+            )?),
+            SubscriptionOutputDriver::Tcp(config) => Arc::new(OutputTcp::new(config, compression)?),
+            SubscriptionOutputDriver::Redis(config) => {
+                Arc::new(OutputRedis::new(config, event_ttl)?)
             }
-            SubscriptionOutputDriver::Tcp(config) => Arc::new(OutputTcp::new(config)?),
-            SubscriptionOutputDriver::Redis(config) => Arc::new(OutputRedis::new(config)?),
             SubscriptionOutputDriver::UnixDatagram(config) => {
                 Arc::new(OutputUnixDatagram::new(config)?)
             }
+            SubscriptionOutputDriver::SyslogTls(config) => Arc::new(OutputSyslogTls::new(config)?),
+            SubscriptionOutputDriver::Http(config) => Arc::new(OutputHttp::new(config)?),
+            SubscriptionOutputDriver::Syslog(config) => Arc::new(OutputSyslog::new(config)?),
+            SubscriptionOutputDriver::UnixStream(config) => {
+                Arc::new(OutputUnixStream::new(config)?)
+            }
+            SubscriptionOutputDriver::Stdout(config) => {
+                Arc::new(OutputStdStream::new(StdStream::Stdout, config))
+            }
+            SubscriptionOutputDriver::Stderr(config) => {
+                Arc::new(OutputStdStream::new(StdStream::Stderr, config))
+            }
+            SubscriptionOutputDriver::Nats(config) => Arc::new(OutputNats::new(config)?),
+            SubscriptionOutputDriver::Mqtt(config) => Arc::new(OutputMqtt::new(config)?),
+            SubscriptionOutputDriver::EventHubs(config) => Arc::new(OutputEventHubs::new(config)?),
+            SubscriptionOutputDriver::Grpc(config) => Arc::new(OutputGrpc::new(config)?),
+            SubscriptionOutputDriver::SplunkHec(config) => Arc::new(OutputSplunkHec::new(config)?),
+            SubscriptionOutputDriver::Loki(config) => Arc::new(OutputLoki::new(config)?),
+            SubscriptionOutputDriver::Zmq(config) => Arc::new(OutputZmq::new(config)?),
         };
 
         Ok(Self {
             driver: output_driver,
-            format: format.clone(),
+            format_key: FormatKey::new(output),
             subscription_output_driver: driver.clone(),
+            source_first_seen: output.source_first_seen(),
+            summary: output.summary(),
+            dead_letter: output.dead_letter(),
+            event_fields: match driver {
+                SubscriptionOutputDriver::Kafka(config) => config.headers_need_event_fields(),
+                // The time of events is sent along with them
+                SubscriptionOutputDriver::SplunkHec(_) | SubscriptionOutputDriver::Loki(_) => true,
+                _ => false,
+            },
+            sampler: if output.sampling().is_empty() {
+                None
+            } else {
+                Some(Arc::new(LevelSampler::new(output.sampling())))
+            },
+            age_limit: output.max_queue_age().map(QueueAgeLimit::new),
+            router: ProviderRouter::new(output.provider_route())?.map(Arc::new),
+            change_detector: output
+                .dedupe_on_change()
+                .map(|config| Arc::new(ChangeDetector::new(config))),
+            health: output
+                .degradation()
+                .map(|degradation| Arc::new(OutputHealth::new(degradation))),
+            failures: Arc::new(Mutex::new(None)),
+            write_timeout: output.write_timeout_ms().map(Duration::from_millis),
         })
     }
 
     pub fn describe(&self) -> String {
         format!(
             "format: {:?}, driver: {:?}",
-            self.format, self.subscription_output_driver
+            self.format_key.format(),
+            self.subscription_output_driver
         )
     }
 
@@ -142,16 +319,143 @@ impl Output {
         format!("{:?}", self.subscription_output_driver)
     }
 
+    /// Kind of driver (without its configuration, which may contain
+    /// credentials), used to label metrics
+    pub fn driver_kind(&self) -> &'static str {
+        (&self.subscription_output_driver).into()
+    }
+
     pub async fn write(
         &self,
         metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
     ) -> Result<()> {
-        self.driver.write(metadata, events).await
+        self.write_with_fields(metadata, events, None).await
+    }
+
+    /// Writes events along with their fields, which must be given if
+    /// `needs_event_fields()` is true
+    pub async fn write_with_fields(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+        fields: Option<Arc<Vec<Arc<EventFields>>>>,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let count = events.len();
+        let write = async {
+            match fields {
+                Some(fields) => {
+                    self.driver
+                        .write_with_fields(metadata.clone(), events, fields)
+                        .await
+                }
+                None => self.driver.write(metadata.clone(), events).await,
+            }
+        };
+        // A blocked driver must not hold the clients of the subscription
+        // forever: the write is abandoned and the batch is not acknowledged
+        let result = match self.write_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, write).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!(
+                    "Write to output {} timed out after {}ms",
+                    self.subscription_output_driver.as_ref(),
+                    timeout.as_millis()
+                )),
+            },
+            None => write.await,
+        };
+        {
+            let mut failures = self.failures.lock().unwrap();
+            match &result {
+                Ok(()) => *failures = None,
+                Err(err) => {
+                    let streak = failures.get_or_insert_with(|| FailureStreak {
+                        attempts: 0,
+                        first_failure_at: Utc::now(),
+                        last_error: String::new(),
+                    });
+                    streak.attempts = streak.attempts.saturating_add(1);
+                    streak.last_error = format!("{:#}", err);
+                }
+            }
+        }
+        count_write(
+            &metadata,
+            self.subscription_output_driver.as_ref(),
+            count,
+            result.is_ok(),
+        );
+        if let Some(health) = &self.health {
+            if let Some(transition) = health.record(start.elapsed(), result.is_ok(), Instant::now())
+            {
+                degradation::report(&metadata, self.driver_kind(), transition);
+            }
+        }
+        result
+    }
+
+    /// Whether this output is degraded and must be skipped
+    pub fn is_degraded(&self) -> bool {
+        self.health
+            .as_ref()
+            .is_some_and(|health| health.skip(Instant::now()))
+    }
+
+    /// Whether the last write to this output failed
+    pub fn is_failing(&self) -> bool {
+        self.failures.lock().unwrap().is_some()
+    }
+
+    #[cfg(test)]
+    pub fn set_driver(&mut self, driver: Arc<dyn OutputDriver + Send + Sync>) {
+        self.driver = driver;
+    }
+
+    /// Returns the consecutive failed writes, if the last write failed
+    pub fn failure_streak(&self) -> Option<FailureStreak> {
+        self.failures.lock().unwrap().clone()
     }
 
     pub fn format(&self) -> &SubscriptionOutputFormat {
-        &self.format
+        self.format_key.format()
+    }
+
+    pub fn format_key(&self) -> &FormatKey {
+        &self.format_key
+    }
+
+    pub fn source_first_seen(&self) -> bool {
+        self.source_first_seen
+    }
+
+    pub fn summary(&self) -> bool {
+        self.summary
+    }
+
+    pub fn dead_letter(&self) -> bool {
+        self.dead_letter
+    }
+
+    pub fn needs_event_fields(&self) -> bool {
+        self.event_fields
+    }
+
+    pub fn sampler(&self) -> Option<&LevelSampler> {
+        self.sampler.as_deref()
+    }
+
+    pub fn age_limit(&self) -> Option<&QueueAgeLimit> {
+        self.age_limit.as_ref()
+    }
+
+    pub fn router(&self) -> Option<&ProviderRouter> {
+        self.router.as_deref()
+    }
+
+    pub fn change_detector(&self) -> Option<&ChangeDetector> {
+        self.change_detector.as_deref()
     }
 }
 
@@ -161,21 +465,246 @@ pub trait OutputDriver {
     async fn write(
         &self,
         metadata: Arc<EventMetadata>,
-        events: Arc<Vec<Arc<String>>>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
     ) -> Result<()>;
+
+    /// Write a batch of events along with the fields of each event.
+    /// Drivers which do not use these fields only implement `write`.
+    async fn write_with_fields(
+        &self,
+        metadata: Arc<EventMetadata>,
+        events: Arc<Vec<Arc<Vec<u8>>>>,
+        _fields: Arc<Vec<Arc<EventFields>>>,
+    ) -> Result<()> {
+        self.write(metadata, events).await
+    }
 }
 
 pub trait OutputFormat {
-    /// Formats an event.
+    /// Formats an event. Text formats are encoded in UTF-8.
     /// If something wrong happens, formatter is allowed to return None.
-    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<String>>;
+    fn format(&self, metadata: &EventMetadata, data: &EventData) -> Option<Arc<Vec<u8>>>;
+
+    /// Returns a line written once at the beginning of each file
+    /// by the Files driver.
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the layout of the files written by the Files driver for
+    /// binary formats, whose events can not be written one per line.
+    fn binary_file_layout(&self) -> Option<BinaryFileLayout> {
+        None
+    }
 }
 
-pub fn get_formatter(format: &SubscriptionOutputFormat) -> Box<dyn OutputFormat> {
+/// Layout of the files written by the Files driver for a binary format
+#[derive(Debug, Clone, Copy)]
+pub struct BinaryFileLayout {
+    /// Returns the bytes written once at the beginning of each file
+    pub header: fn() -> Vec<u8>,
+    /// Turns a batch of formatted events into the bytes appended to a file
+    pub encode: fn(&[Arc<Vec<u8>>]) -> Result<Vec<u8>>,
+}
+
+/// Counts the events written by an output driver, or its failed write.
+/// Only the kind of the driver is used as label, so that the cardinality
+/// does not depend on its configuration.
+fn count_write(metadata: &EventMetadata, driver: &str, events: usize, succeeded: bool) {
+    if succeeded {
+        counter!(OUTPUT_EVENTS_COUNTER,
+            SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+            SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+            OUTPUT_DRIVER => driver.to_owned())
+        .increment(events as u64);
+    } else {
+        counter!(OUTPUT_ERRORS_COUNTER,
+            SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+            SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+            OUTPUT_DRIVER => driver.to_owned())
+        .increment(1);
+    }
+}
+
+pub fn get_formatter(
+    format: &SubscriptionOutputFormat,
+    field_map: &FieldMap,
+    collector_info: bool,
+    format_options: &FormatOptions,
+) -> Box<dyn OutputFormat> {
     match format {
+        SubscriptionOutputFormat::Json
+            if !field_map.is_empty() || collector_info || !format_options.is_empty() =>
+        {
+            Box::new(
+                MappedFormat::new(json::to_value, field_map, collector_info)
+                    .with_options(format_options),
+            )
+        }
         SubscriptionOutputFormat::Json => Box::new(JsonFormat),
         SubscriptionOutputFormat::Raw => Box::new(RawFormat),
         SubscriptionOutputFormat::RawJson => Box::new(RawJsonFormat),
+        SubscriptionOutputFormat::Nxlog if !field_map.is_empty() || collector_info => Box::new(
+            MappedFormat::new(nxlog::to_value, field_map, collector_info),
+        ),
         SubscriptionOutputFormat::Nxlog => Box::new(NxlogFormat),
+        SubscriptionOutputFormat::Csv(config) => Box::new(CsvFormat::new(config, field_map)),
+        SubscriptionOutputFormat::Rfc5424(config) => {
+            Box::new(Rfc5424Format::new(config, field_map))
+        }
+        SubscriptionOutputFormat::Cef(config) => Box::new(CefFormat::new(config)),
+        SubscriptionOutputFormat::Leef => Box::new(LeefFormat),
+        SubscriptionOutputFormat::Protobuf(config) => Box::new(ProtobufFormat::new(config)),
+        SubscriptionOutputFormat::Ecs => Box::new(EcsFormat),
+        SubscriptionOutputFormat::Msgpack => Box::new(MsgpackFormat),
+        SubscriptionOutputFormat::Template(config) => Box::new(TemplateFormat::new(config)),
+        SubscriptionOutputFormat::Avro => Box::new(AvroFormat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use common::subscription::UnixDatagramConfiguration;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tokio::net::UnixDatagram;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::subscription::Subscription;
+
+    fn events(count: usize) -> Arc<Vec<Arc<Vec<u8>>>> {
+        Arc::new(
+            (0..count)
+                .map(|i| Arc::new(format!("event {}", i).into_bytes()))
+                .collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_output_metrics() -> Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("openwec-test-{}.socket", Uuid::new_v4()))
+            .display()
+            .to_string();
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![SubscriptionOutput::new(
+            SubscriptionOutputFormat::Raw,
+            SubscriptionOutputDriver::UnixDatagram(UnixDatagramConfiguration::new(path.clone())),
+            true,
+        )]);
+        let mut output_context = OutputDriversContext::new(&Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        let metadata = Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ));
+        let output = &subscription.outputs()[0];
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        {
+            // Metrics are recorded by this thread, which performs the writes
+            let _guard = metrics::set_default_local_recorder(&recorder);
+
+            // Nothing listens on the socket yet
+            assert!(output.write(metadata.clone(), events(2)).await.is_err());
+
+            let _listener = UnixDatagram::bind(&path)?;
+            output.write(metadata.clone(), events(2)).await?;
+            output.write(metadata, events(1)).await?;
+        }
+
+        let rendered = handle.render();
+        let find = |name: &str| {
+            rendered
+                .lines()
+                .find(|line| line.starts_with(name))
+                .unwrap_or_else(|| panic!("{} is missing", name))
+                .to_owned()
+        };
+        let line = find(OUTPUT_EVENTS_COUNTER);
+        assert!(line.contains(&format!(
+            "{}=\"{}\"",
+            SUBSCRIPTION_UUID,
+            subscription.uuid_string()
+        )));
+        assert!(line.contains(&format!("{}=\"Test\"", SUBSCRIPTION_NAME)));
+        assert!(line.contains(&format!("{}=\"unixdatagram\"", OUTPUT_DRIVER)));
+        assert!(line.ends_with(" 3"));
+        let line = find(OUTPUT_ERRORS_COUNTER);
+        assert!(line.contains(&format!("{}=\"unixdatagram\"", OUTPUT_DRIVER)));
+        assert!(line.ends_with(" 1"));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    /// Never completes a write, like a sink which stopped reading
+    struct BlockingDriver;
+
+    #[async_trait]
+    impl OutputDriver for BlockingDriver {
+        async fn write(
+            &self,
+            _metadata: Arc<EventMetadata>,
+            _events: Arc<Vec<Arc<Vec<u8>>>>,
+        ) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_write_timeout() -> Result<()> {
+        let mut output = SubscriptionOutput::new(
+            SubscriptionOutputFormat::Raw,
+            SubscriptionOutputDriver::UnixDatagram(UnixDatagramConfiguration::new(
+                "/tmp/unused.socket".to_string(),
+            )),
+            true,
+        );
+        output.set_write_timeout_ms(Some(50));
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![output]);
+        let mut output_context = OutputDriversContext::new(&Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        let metadata = Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ));
+        let mut output = subscription.outputs()[0].clone();
+        output.driver = Arc::new(BlockingDriver);
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        {
+            let _guard = metrics::set_default_local_recorder(&recorder);
+            let start = Instant::now();
+            let err = output
+                .write(metadata, events(2))
+                .await
+                .expect_err("write must time out");
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert!(format!("{:#}", err).contains("timed out after 50ms"));
+        }
+        assert_eq!(output.failure_streak().map(|s| s.attempts()), Some(1));
+
+        let rendered = handle.render();
+        let line = rendered
+            .lines()
+            .find(|line| line.starts_with(OUTPUT_ERRORS_COUNTER))
+            .expect("errors counter is missing");
+        assert!(line.ends_with(" 1"));
+        assert!(!rendered.contains(OUTPUT_EVENTS_COUNTER));
+        Ok(())
     }
 }
@@ -0,0 +1,398 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use common::{
+    settings::{QueueOverflowPolicy, Server},
+    subscription::SubscriptionData,
+};
+use futures::future::select_all;
+use log::{info, warn};
+use metrics::counter;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::monitoring::{QUEUE_OVERFLOW_COUNTER, QUEUE_OVERFLOW_POLICY, QUEUE_SCOPE};
+
+static GLOBAL_QUEUE: OnceLock<Arc<EventQueue>> = OnceLock::new();
+static OVERFLOW_POLICY: OnceLock<QueueOverflowPolicy> = OnceLock::new();
+
+pub const SCOPE_GLOBAL: &str = "global";
+pub const SCOPE_SUBSCRIPTION: &str = "subscription";
+
+#[derive(Debug)]
+struct QueuedBatch {
+    id: u64,
+    events: u64,
+    bytes: u64,
+    evicted: CancellationToken,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    events: u64,
+    bytes: u64,
+    next_id: u64,
+    // Queued batches, oldest first
+    batches: VecDeque<QueuedBatch>,
+}
+
+/// Bounds the number of events and bytes of the batches being processed at
+/// the same time.
+///
+/// A batch stays in memory until all the outputs of its subscription have
+/// written it. Without limit, a stalled output makes memory usage grow with
+/// every batch sent by clients.
+#[derive(Debug)]
+pub struct EventQueue {
+    scope: &'static str,
+    max_events: Option<u64>,
+    max_bytes: Option<u64>,
+    policy: QueueOverflowPolicy,
+    state: Mutex<QueueState>,
+    released: Notify,
+    // Total number of events that overflowed
+    overflows: AtomicU64,
+}
+
+impl EventQueue {
+    pub fn new(
+        scope: &'static str,
+        max_events: Option<u64>,
+        max_bytes: Option<u64>,
+        policy: QueueOverflowPolicy,
+    ) -> Self {
+        Self {
+            scope,
+            max_events,
+            max_bytes,
+            policy,
+            state: Mutex::new(QueueState::default()),
+            released: Notify::new(),
+            overflows: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the queue of a subscription, if it has configured limits
+    pub fn for_subscription(data: &SubscriptionData) -> Option<Arc<Self>> {
+        if data.max_queued_events().is_none() && data.max_queued_bytes().is_none() {
+            return None;
+        }
+        Some(Arc::new(Self::new(
+            SCOPE_SUBSCRIPTION,
+            data.max_queued_events().map(u64::from),
+            data.max_queued_bytes(),
+            overflow_policy(),
+        )))
+    }
+
+    fn fits(&self, state: &QueueState, events: u64, bytes: u64) -> bool {
+        // A batch larger than a limit would never fit: it is accepted
+        // when nothing else is queued
+        if state.batches.is_empty() {
+            return true;
+        }
+        let events_fit = match self.max_events {
+            Some(max) => state.events + events <= max,
+            None => true,
+        };
+        let bytes_fit = match self.max_bytes {
+            Some(max) => state.bytes + bytes <= max,
+            None => true,
+        };
+        events_fit && bytes_fit
+    }
+
+    fn count_overflow(&self, events: u64) {
+        let total = self.overflows.fetch_add(events, Ordering::Relaxed) + events;
+        warn!(
+            "{} events overflowed the {} queue (policy: {}, total: {})",
+            events,
+            self.scope,
+            policy_str(self.policy),
+            total
+        );
+        counter!(QUEUE_OVERFLOW_COUNTER,
+            QUEUE_SCOPE => self.scope,
+            QUEUE_OVERFLOW_POLICY => policy_str(self.policy))
+        .increment(events);
+    }
+
+    /// Queues a batch of `events` events weighing `bytes` bytes, applying the
+    /// overflow policy if the queue is full. Returns None if the batch has
+    /// been rejected. The batch is removed from the queue when the returned
+    /// guard is dropped.
+    pub async fn push(self: &Arc<Self>, events: u64, bytes: u64) -> Option<QueueGuard> {
+        let mut overflowed = false;
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            // Register for notifications before looking at the state, so that
+            // a batch released in between is not missed
+            released.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap();
+                if !self.fits(&state, events, bytes) {
+                    match self.policy {
+                        QueueOverflowPolicy::Block => {
+                            if !overflowed {
+                                overflowed = true;
+                                self.count_overflow(events);
+                            }
+                        }
+                        QueueOverflowPolicy::DropNewest => {
+                            self.count_overflow(events);
+                            return None;
+                        }
+                        QueueOverflowPolicy::DropOldest => {
+                            while !self.fits(&state, events, bytes) {
+                                // fits() is always true for an empty queue
+                                let oldest =
+                                    state.batches.pop_front().expect("Queue can not be empty");
+                                state.events -= oldest.events;
+                                state.bytes -= oldest.bytes;
+                                oldest.evicted.cancel();
+                                self.count_overflow(oldest.events);
+                            }
+                        }
+                    }
+                }
+
+                if self.fits(&state, events, bytes) {
+                    let id = state.next_id;
+                    state.next_id += 1;
+                    state.events += events;
+                    state.bytes += bytes;
+                    let evicted = CancellationToken::new();
+                    state.batches.push_back(QueuedBatch {
+                        id,
+                        events,
+                        bytes,
+                        evicted: evicted.clone(),
+                    });
+                    return Some(QueueGuard {
+                        queue: self.clone(),
+                        id,
+                        evicted,
+                    });
+                }
+            }
+            released.await;
+        }
+    }
+}
+
+/// A batch of events held in a queue
+#[derive(Debug)]
+pub struct QueueGuard {
+    queue: Arc<EventQueue>,
+    id: u64,
+    evicted: CancellationToken,
+}
+
+impl QueueGuard {
+    /// Whether the batch has been evicted by a newer one (`drop_oldest` policy)
+    pub fn is_evicted(&self) -> bool {
+        self.evicted.is_cancelled()
+    }
+}
+
+impl Drop for QueueGuard {
+    fn drop(&mut self) {
+        {
+            let mut state = self.queue.state.lock().unwrap();
+            // Evicted batches have already been removed
+            if let Some(index) = state.batches.iter().position(|batch| batch.id == self.id) {
+                if let Some(batch) = state.batches.remove(index) {
+                    state.events -= batch.events;
+                    state.bytes -= batch.bytes;
+                }
+            }
+        }
+        self.queue.released.notify_waiters();
+    }
+}
+
+/// Completes as soon as one of the batches has been evicted
+pub async fn evicted(guards: &[QueueGuard]) {
+    if guards.is_empty() {
+        return std::future::pending().await;
+    }
+    select_all(
+        guards
+            .iter()
+            .map(|guard| Box::pin(guard.evicted.cancelled())),
+    )
+    .await;
+}
+
+fn policy_str(policy: QueueOverflowPolicy) -> &'static str {
+    match policy {
+        QueueOverflowPolicy::Block => "block",
+        QueueOverflowPolicy::DropOldest => "drop_oldest",
+        QueueOverflowPolicy::DropNewest => "drop_newest",
+    }
+}
+
+/// Initializes the process wide queue. Must be called once at startup.
+pub fn init(settings: &Server) {
+    if OVERFLOW_POLICY
+        .set(settings.queue_overflow_policy())
+        .is_err()
+    {
+        warn!("Queue overflow policy has already been initialized");
+    }
+    if settings.max_queued_events().is_none() && settings.max_queued_bytes().is_none() {
+        return;
+    }
+    info!(
+        "Queued events are limited to {:?} events and {:?} bytes (policy: {})",
+        settings.max_queued_events(),
+        settings.max_queued_bytes(),
+        policy_str(settings.queue_overflow_policy())
+    );
+    let queue = EventQueue::new(
+        SCOPE_GLOBAL,
+        settings.max_queued_events(),
+        settings.max_queued_bytes(),
+        settings.queue_overflow_policy(),
+    );
+    if GLOBAL_QUEUE.set(Arc::new(queue)).is_err() {
+        warn!("Global queue has already been initialized");
+    }
+}
+
+/// Returns the process wide queue, if limits are configured
+pub fn global() -> Option<&'static Arc<EventQueue>> {
+    GLOBAL_QUEUE.get()
+}
+
+/// Returns what happens when a queue is full, also applied by drivers
+/// whose sink has its own bounded queue
+pub fn overflow_policy() -> QueueOverflowPolicy {
+    OVERFLOW_POLICY.get().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    fn queue(policy: QueueOverflowPolicy) -> Arc<EventQueue> {
+        Arc::new(EventQueue::new(SCOPE_GLOBAL, Some(10), Some(1000), policy))
+    }
+
+    fn queued(queue: &EventQueue) -> (u64, u64, usize) {
+        let state = queue.state.lock().unwrap();
+        (state.events, state.bytes, state.batches.len())
+    }
+
+    fn overflows(queue: &EventQueue) -> u64 {
+        queue.overflows.load(Ordering::Relaxed)
+    }
+
+    #[tokio::test]
+    async fn test_block() {
+        let queue = queue(QueueOverflowPolicy::Block);
+        let first = queue.push(6, 100).await.unwrap();
+        let second = queue.push(4, 100).await.unwrap();
+        assert_eq!(queued(&queue), (10, 200, 2));
+
+        // The queue is full: the batch waits
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(5, 100).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        assert_eq!(queued(&queue), (10, 200, 2));
+        assert_eq!(overflows(&queue), 5);
+
+        // Releasing the first batch makes room for the waiting one
+        drop(first);
+        let third = timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(!second.is_evicted());
+        assert!(!third.is_evicted());
+        assert_eq!(queued(&queue), (9, 200, 2));
+        assert_eq!(overflows(&queue), 5);
+
+        drop(second);
+        drop(third);
+        assert_eq!(queued(&queue), (0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest() {
+        let queue = queue(QueueOverflowPolicy::DropNewest);
+        let first = queue.push(6, 100).await.unwrap();
+
+        // Too many events, then too many bytes
+        assert!(queue.push(5, 100).await.is_none());
+        assert!(queue.push(1, 950).await.is_none());
+        assert!(!first.is_evicted());
+        assert_eq!(queued(&queue), (6, 100, 1));
+        assert_eq!(overflows(&queue), 6);
+
+        let second = queue.push(4, 100).await.unwrap();
+        assert_eq!(queued(&queue), (10, 200, 2));
+        drop(first);
+        drop(second);
+        assert_eq!(queued(&queue), (0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let queue = queue(QueueOverflowPolicy::DropOldest);
+        let first = queue.push(4, 100).await.unwrap();
+        let second = queue.push(4, 100).await.unwrap();
+
+        // Both oldest batches must be evicted to make room
+        let third = queue.push(8, 100).await.unwrap();
+        assert!(first.is_evicted());
+        assert!(second.is_evicted());
+        assert!(!third.is_evicted());
+        assert_eq!(queued(&queue), (8, 100, 1));
+        assert_eq!(overflows(&queue), 8);
+        timeout(
+            Duration::from_secs(5),
+            evicted(std::slice::from_ref(&first)),
+        )
+        .await
+        .unwrap();
+
+        // Evicted batches are not counted twice
+        drop(first);
+        drop(second);
+        assert_eq!(queued(&queue), (8, 100, 1));
+        drop(third);
+        assert_eq!(queued(&queue), (0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_batch() {
+        for policy in [
+            QueueOverflowPolicy::Block,
+            QueueOverflowPolicy::DropOldest,
+            QueueOverflowPolicy::DropNewest,
+        ] {
+            let queue = queue(policy);
+            // A batch larger than the limit is accepted in an empty queue
+            let batch = queue.push(20, 100).await.unwrap();
+            assert_eq!(queued(&queue), (20, 100, 1));
+            assert_eq!(overflows(&queue), 0);
+            drop(batch);
+            assert_eq!(queued(&queue), (0, 0, 0));
+        }
+    }
+}
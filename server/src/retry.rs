@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::output::Output;
+
+/// Limits the number of batches being retried at the same time on the
+/// failing outputs of a subscription.
+///
+/// OpenWEC does not retry writes by itself: a batch that could not be
+/// written is not acknowledged and the client sends it again later. Every
+/// write to an output whose previous write failed is therefore a retry.
+/// Once the limit is reached, new retries wait for a running one to finish
+/// instead of adding more load to a struggling output.
+#[derive(Debug, Clone)]
+pub struct RetryLimiter {
+    semaphore: Arc<Semaphore>,
+}
+
+impl RetryLimiter {
+    pub fn new(max_concurrent_retries: u32) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(
+                usize::try_from(max_concurrent_retries).unwrap_or(Semaphore::MAX_PERMITS),
+            )),
+        }
+    }
+
+    /// Waits until a retry can be started. The retry is considered
+    /// finished when the returned permit is dropped.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit> {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("Retry limiter has been closed")
+    }
+
+    /// Returns a permit if writing to `output` is a retry, None otherwise
+    pub async fn acquire_for(&self, output: &Output) -> Result<Option<OwnedSemaphorePermit>> {
+        if output.is_failing() {
+            Ok(Some(self.acquire().await?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use anyhow::anyhow;
+    use tokio::task::JoinSet;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_retries_are_capped() -> Result<()> {
+        let limiter = RetryLimiter::new(3);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        // As in handle_events, a permit is acquired before spawning the task
+        // writing to the output
+        let mut handles = JoinSet::new();
+        for _ in 0..50 {
+            let permit = limiter.acquire().await?;
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            handles.spawn(async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                // A failing write to a flaky output
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                drop(permit);
+                Err::<(), _>(anyhow!("Output is unavailable"))
+            });
+        }
+
+        let mut failures = 0;
+        while let Some(res) = handles.join_next().await {
+            if res?.is_err() {
+                failures += 1;
+            }
+        }
+        assert_eq!(failures, 50);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 3);
+        assert!(max_in_flight.load(Ordering::SeqCst) > 0);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+}
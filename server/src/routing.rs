@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use common::subscription::ProviderRoute;
+use glob::{MatchOptions, Pattern};
+
+use crate::event::EventData;
+
+/// Selects the events sent to an output depending on their provider
+/// (`route_by_provider` subscription option).
+#[derive(Debug)]
+pub struct ProviderRouter {
+    patterns: Vec<Pattern>,
+    // Whether the output receives the events matching the patterns,
+    // or the ones matching none of them (default bucket)
+    matching: bool,
+}
+
+impl ProviderRouter {
+    /// Returns None if the output receives all events
+    pub fn new(route: &ProviderRoute) -> Result<Option<Self>> {
+        let matching = match route {
+            ProviderRoute::All => return Ok(None),
+            ProviderRoute::Matching(_) => true,
+            ProviderRoute::Unmatched(_) => false,
+        };
+        let mut patterns = Vec::with_capacity(route.patterns().len());
+        for pattern in route.patterns() {
+            patterns.push(
+                Pattern::new(pattern)
+                    .with_context(|| format!("Invalid provider pattern {:?}", pattern))?,
+            );
+        }
+        Ok(Some(Self { patterns, matching }))
+    }
+
+    /// Whether an event of the given provider is sent to the output.
+    /// Events whose provider is unknown only go to the default bucket.
+    pub fn routes(&self, provider: Option<&str>) -> bool {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        let matches = provider.is_some_and(|provider| {
+            self.patterns
+                .iter()
+                .any(|pattern| pattern.matches_with(provider, options))
+        });
+        matches == self.matching
+    }
+
+    /// Keeps the formatted events sent to the output, along with their levels.
+    /// `levels` and `providers` contain the value of each event.
+    pub fn route<T: Clone>(
+        &self,
+        events: &[T],
+        levels: &[Option<u8>],
+        providers: &[Option<String>],
+    ) -> (Vec<T>, Vec<Option<u8>>) {
+        let mut kept_events = Vec::with_capacity(events.len());
+        let mut kept_levels = Vec::with_capacity(events.len());
+        for ((event, level), provider) in events.iter().zip(levels).zip(providers) {
+            if self.routes(provider.as_deref()) {
+                kept_events.push(event.clone());
+                kept_levels.push(*level);
+            }
+        }
+        (kept_events, kept_levels)
+    }
+}
+
+/// Returns the provider of a parsed event (`System/Provider/@Name`)
+pub fn provider(event_data: &EventData) -> Option<String> {
+    event_data
+        .event()
+        .and_then(|event| event.system.as_ref())
+        .and_then(|system| system.provider.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common::models::config::parse;
+
+    use super::*;
+
+    const CONF: &str = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "route_by_provider"
+query = "a very small query"
+
+[[outputs]]
+name = "sysmon"
+driver = "UnixDatagram"
+format = "Raw"
+config = { path = "/tmp/sysmon.socket" }
+
+[[outputs]]
+name = "sysmon-archive"
+driver = "UnixDatagram"
+format = "Raw"
+config = { path = "/tmp/sysmon-archive.socket" }
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Raw"
+config = { path = "/tmp/others.socket" }
+
+[route_by_provider]
+"microsoft-windows-sysmon" = ["sysmon", "sysmon-archive"]
+"#;
+
+    fn event(provider: &str) -> Arc<String> {
+        Arc::new(format!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='{}'/><EventID>1</EventID><Level>4</Level><Computer>win10.windomain.local</Computer></System></Event>",
+            provider
+        ))
+    }
+
+    #[test]
+    fn test_route_by_provider() -> Result<()> {
+        let data = parse(CONF, None)?;
+        let events = vec![
+            event("Microsoft-Windows-Sysmon"),
+            event("Microsoft-Windows-Security-Auditing"),
+            Arc::new("not an event".to_string()),
+            event("Microsoft-Windows-Sysmon"),
+        ];
+        let levels: Vec<Option<u8>> = vec![Some(4); events.len()];
+        let providers: Vec<Option<String>> = events
+            .iter()
+            .map(|raw| provider(&EventData::new(raw.clone(), true)))
+            .collect();
+        assert_eq!(providers[2], None);
+
+        let delivered: Vec<Vec<Arc<String>>> = data
+            .outputs()
+            .iter()
+            .map(|output| {
+                let router = ProviderRouter::new(output.provider_route())
+                    .unwrap()
+                    .unwrap();
+                router.route(&events, &levels, &providers).0
+            })
+            .collect();
+
+        // Sysmon events only reach the Sysmon outputs
+        let sysmon = vec![events[0].clone(), events[3].clone()];
+        assert_eq!(delivered[0], sysmon);
+        assert_eq!(delivered[1], sysmon);
+        // Other events, including the ones with an unknown provider,
+        // reach the default output
+        assert_eq!(delivered[2], vec![events[1].clone(), events[2].clone()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_events() -> Result<()> {
+        assert!(ProviderRouter::new(&ProviderRoute::All)?.is_none());
+        let router =
+            ProviderRouter::new(&ProviderRoute::Matching(vec!["Microsoft-*".to_string()]))?
+                .unwrap();
+        assert!(router.routes(Some("Microsoft-Windows-Sysmon")));
+        assert!(!router.routes(Some("Sysmon")));
+        assert!(!router.routes(None));
+        Ok(())
+    }
+}
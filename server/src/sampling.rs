@@ -0,0 +1,147 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use common::subscription::EventLevel;
+use metrics::counter;
+
+use crate::{
+    event::EventMetadata,
+    monitoring::{
+        EVENT_LEVEL, OUTPUT_SAMPLING_DROPPED_EVENTS, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID,
+    },
+};
+
+/// Keeps a configured percentage of events for each level.
+///
+/// Sampling is deterministic: each level accumulates its rate for every
+/// received event and an event is kept each time the accumulator reaches 100.
+/// With a rate of 10, exactly one event out of ten is kept.
+#[derive(Debug)]
+pub struct LevelSampler {
+    rates: BTreeMap<EventLevel, u8>,
+    credits: Mutex<HashMap<EventLevel, u32>>,
+}
+
+impl LevelSampler {
+    pub fn new(rates: &BTreeMap<EventLevel, u8>) -> Self {
+        Self {
+            rates: rates.clone(),
+            credits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns whether an event of the given level should be kept.
+    /// Events of unconfigured levels are always kept.
+    fn keep(&self, level: EventLevel) -> bool {
+        let rate = match self.rates.get(&level) {
+            Some(rate) => u32::from(*rate),
+            None => return true,
+        };
+        let mut credits = self.credits.lock().unwrap();
+        let credit = credits.entry(level).or_insert(0);
+        *credit += rate;
+        if *credit >= 100 {
+            *credit -= 100;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Samples a batch of formatted events. `levels` contains the `System/Level`
+    /// value of each event. Events without a known level are always kept.
+    /// Returns the kept events and the number of dropped events for each level.
+    pub fn sample<T: Clone>(
+        &self,
+        events: &[T],
+        levels: &[Option<u8>],
+    ) -> (Vec<T>, BTreeMap<EventLevel, u64>) {
+        let mut kept = Vec::with_capacity(events.len());
+        let mut dropped = BTreeMap::new();
+        for (event, level) in events.iter().zip(levels.iter()) {
+            match level.and_then(EventLevel::from_value) {
+                Some(level) if !self.keep(level) => {
+                    *dropped.entry(level).or_insert(0) += 1;
+                }
+                _ => kept.push(event.clone()),
+            }
+        }
+        (kept, dropped)
+    }
+}
+
+pub fn count_dropped(metadata: &EventMetadata, dropped: &BTreeMap<EventLevel, u64>) {
+    for (level, count) in dropped {
+        let level_str: &'static str = level.into();
+        counter!(OUTPUT_SAMPLING_DROPPED_EVENTS,
+            SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+            SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+            EVENT_LEVEL => level_str)
+        .increment(*count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_sampling_by_level() {
+        let mut rates = BTreeMap::new();
+        rates.insert(EventLevel::Critical, 100);
+        rates.insert(EventLevel::Error, 100);
+        rates.insert(EventLevel::Information, 10);
+        rates.insert(EventLevel::Verbose, 0);
+        let sampler = LevelSampler::new(&rates);
+
+        // Feed several batches with a mix of levels, including events
+        // without level and events with unconfigured levels
+        let mut total_kept: BTreeMap<String, usize> = BTreeMap::new();
+        let mut total_dropped: BTreeMap<EventLevel, u64> = BTreeMap::new();
+        for _ in 0..10 {
+            let mut events = Vec::new();
+            let mut levels = Vec::new();
+            for i in 0..50 {
+                let level = match i % 5 {
+                    0 => Some(1),
+                    1 => Some(2),
+                    2 => Some(3),
+                    3 => None,
+                    _ => Some(4),
+                };
+                events.push(Arc::new(format!("{:?}", level)));
+                levels.push(level);
+            }
+            // A single verbose event per batch
+            events.push(Arc::new(format!("{:?}", Some(5))));
+            levels.push(Some(5));
+
+            let (kept, dropped) = sampler.sample(&events, &levels);
+            for event in kept {
+                *total_kept.entry(event.to_string()).or_insert(0) += 1;
+            }
+            for (level, count) in dropped {
+                *total_dropped.entry(level).or_insert(0) += count;
+            }
+        }
+
+        // High severity events are never dropped
+        assert_eq!(total_kept.get("Some(1)"), Some(&100));
+        assert_eq!(total_kept.get("Some(2)"), Some(&100));
+        // Unconfigured levels and events without level are kept
+        assert_eq!(total_kept.get("Some(3)"), Some(&100));
+        assert_eq!(total_kept.get("None"), Some(&100));
+        // Information events are sampled at 10%
+        assert_eq!(total_kept.get("Some(4)"), Some(&10));
+        assert_eq!(total_kept.get("Some(5)"), None);
+
+        let mut expected_dropped = BTreeMap::new();
+        expected_dropped.insert(EventLevel::Information, 90);
+        expected_dropped.insert(EventLevel::Verbose, 10);
+        assert_eq!(total_dropped, expected_dropped);
+    }
+}
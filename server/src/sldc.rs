@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use anyhow::{bail, Context, Result};
 use bitreader::BitReader;
 use log::debug;
@@ -11,21 +13,167 @@ const CTRLSYMB_RESET_1: u16 = 0b1111111110101;
 const CTRLSYMB_RESET_2: u16 = 0b1111111110110;
 const CTRLSYMB_END_MARKER: u16 = 0b1111111111111;
 
+// Size of the history buffer defined by ECMA-321
+const HISTORY_SIZE: usize = 1024;
+// Largest history buffer accepted for non-standard encoders, whose
+// displacements must fit in 16 bits
+const MAX_HISTORY_SIZE: usize = 1 << 16;
+
+// Size of the chunks of decompressed data given to the writer
+const OUTPUT_CHUNK_SIZE: usize = 8192;
+
+/// Default maximum size of decompressed data. A Copy Pointer of 23 bits can
+/// expand to 271 bytes, so that a small payload may otherwise be turned into
+/// a huge amount of data.
+pub const DEFAULT_MAX_OUTPUT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Decompressed data, stored both in the rotating history buffer used to
+/// resolve Copy Pointers and in a chunk waiting to be given to the writer
+struct Decoded<'a, W: Write> {
+    out: &'a mut W,
+    chunk: Vec<u8>,
+    written: usize,
+    max_output_size: usize,
+    history_buffer: Vec<u8>,
+    history_index: usize,
+    window: usize,
+}
+
+impl<'a, W: Write> Decoded<'a, W> {
+    fn new(out: &'a mut W, max_output_size: usize, window: usize) -> Self {
+        Decoded {
+            out,
+            chunk: Vec::with_capacity(OUTPUT_CHUNK_SIZE),
+            written: 0,
+            max_output_size,
+            history_buffer: vec![0; window],
+            history_index: 0,
+            window,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.written + self.chunk.len()
+    }
+
+    fn reset_history(&mut self) {
+        self.history_buffer = vec![0; self.window];
+    }
+
+    fn push(&mut self, c: u8) -> Result<()> {
+        if self.len() >= self.max_output_size {
+            bail!(
+                "Decompressed data exceeds the maximum size of {} bytes",
+                self.max_output_size
+            );
+        }
+        // Store c in result buffer
+        self.chunk.push(c);
+        // Store c in rotating history buffer
+        *self
+            .history_buffer
+            .get_mut(self.history_index)
+            .context("Index not found in history buffer")? = c;
+        self.history_index = (self.history_index + 1) % self.window;
+        if self.chunk.len() >= OUTPUT_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn copy(&mut self, displacement: usize, size: usize) -> Result<()> {
+        let end = displacement
+            .checked_add(size)
+            .context("Copy Pointer is out of bounds")?;
+        for k in displacement..end {
+            // Find c in history_buffer
+            let c = *self
+                .history_buffer
+                .get(k % self.window)
+                .context("Index not found in history buffer")?;
+            self.push(c)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.out
+            .write_all(&self.chunk)
+            .context("Failed to write decompressed data")?;
+        self.written += self.chunk.len();
+        self.chunk.clear();
+        Ok(())
+    }
+}
+
 pub fn decompress(compressed_bytes: &[u8]) -> Result<Vec<u8>> {
+    decompress_with_window(compressed_bytes, HISTORY_SIZE)
+}
+
+/// Decompresses data produced by an encoder using a history buffer of
+/// `window` bytes instead of the 1024 bytes of ECMA-321. Displacement
+/// Fields are then `log2(window)` bits long.
+fn decompress_with_window(compressed_bytes: &[u8], window: usize) -> Result<Vec<u8>> {
+    let mut res: Vec<u8> = Vec::new();
+    decode(compressed_bytes, &mut res, DEFAULT_MAX_OUTPUT_SIZE, window)?;
+    Ok(res)
+}
+
+/// Decompresses `compressed_bytes` into `out`. Decompressed data is written
+/// by chunks as it is decoded, so that it never needs to be kept entirely
+/// in memory.
+pub fn decompress_to_writer<W: Write>(compressed_bytes: &[u8], out: &mut W) -> Result<()> {
+    decompress_to_writer_with_limit(compressed_bytes, out, DEFAULT_MAX_OUTPUT_SIZE)
+}
+
+/// Same as `decompress_to_writer`, but fails as soon as more than
+/// `max_output_size` bytes would be decompressed.
+///
+/// Compressed data may come from anyone able to send requests, so this
+/// function must return an error rather than panic whatever its input.
+pub fn decompress_to_writer_with_limit<W: Write>(
+    compressed_bytes: &[u8],
+    out: &mut W,
+    max_output_size: usize,
+) -> Result<()> {
+    decode(compressed_bytes, out, max_output_size, HISTORY_SIZE)
+}
+
+fn check_window(window: usize) -> Result<()> {
+    if !window.is_power_of_two() || !(2..=MAX_HISTORY_SIZE).contains(&window) {
+        bail!(
+            "Invalid SLDC history buffer size {}: it must be a power of two between 2 and {}",
+            window,
+            MAX_HISTORY_SIZE
+        );
+    }
+    Ok(())
+}
+
+fn decode<W: Write>(
+    compressed_bytes: &[u8],
+    out: &mut W,
+    max_output_size: usize,
+    window: usize,
+) -> Result<()> {
     // Implemented according to ECMA-321
     debug!(
         "Try to decompress SLDC data ({} compressed bytes)",
         compressed_bytes.len()
     );
 
+    check_window(window)?;
+    // Number of bits of Displacement Fields
+    let displacement_size = window.trailing_zeros() as u8;
+
     let mut reader = BitReader::new(compressed_bytes);
-    let mut res: Vec<u8> = Vec::new();
+    let mut decoded = Decoded::new(out, max_output_size, window);
 
     let mut scheme_1 = false;
     let mut scheme_2 = false;
-    let mut history_buffer: Vec<u8> = vec![0; 1024];
-    let mut history_index: usize = 0;
     while reader.remaining() > 0 {
+        let position = reader.position();
+
         // Try to find a control symbol
         if reader.peek_u16(9)? == 0x1FF {
             // 0b111111111
@@ -36,19 +184,20 @@ pub fn decompress(compressed_bytes: &[u8]) -> Result<Vec<u8>> {
                 CTRLSYMB_SCHEME_2 => scheme_2 = true,
                 CTRLSYMB_FILE_MARK => (),
                 CTRLSYMB_END_OF_RECORD => {
+                    decoded.flush()?;
                     debug!(
                         "SLDC decompression succeed ({} uncompressed bytes)",
-                        res.len()
+                        decoded.len()
                     );
-                    return Ok(res);
+                    return Ok(());
                 }
                 CTRLSYMB_RESET_1 => {
                     scheme_1 = true;
-                    history_buffer = vec![0; 1024];
+                    decoded.reset_history();
                 }
                 CTRLSYMB_RESET_2 => {
                     scheme_2 = true;
-                    history_buffer = vec![0; 1024];
+                    decoded.reset_history();
                 }
                 CTRLSYMB_END_MARKER => (),
                 _ => bail!("Found invalid control symbol"),
@@ -59,11 +208,7 @@ pub fn decompress(compressed_bytes: &[u8]) -> Result<Vec<u8>> {
                 // This is a Literal 1 Data Symbol
                 // The next 8 bits represent the data byte
                 let c = reader.read_u8(8)?;
-                // Store c in result buffer
-                res.push(c);
-                // Store c in rotating history buffer
-                history_buffer[history_index] = c;
-                history_index = (history_index + 1) % 1024;
+                decoded.push(c)?;
             } else {
                 // This a Copy Pointer Data Symbol
                 // We need to read the MCF (Match Count Field) in order
@@ -99,47 +244,199 @@ pub fn decompress(compressed_bytes: &[u8]) -> Result<Vec<u8>> {
                     bail!("Found invalid Match Count Field value");
                 };
 
-                let displacement_field = reader.read_u16(10)?;
-
-                for k in displacement_field..displacement_field + size {
-                    // Find c in history_buffer
-                    let c = *history_buffer
-                        .get((k % 1024) as usize)
-                        .context("Index not found in history buffer")?;
-                    // Store c in result buffer
-                    res.push(c);
-                    // Store c in rotating history buffer
-                    history_buffer[history_index] = c;
-                    history_index = (history_index + 1) % 1024;
-                }
+                let displacement_field = reader.read_u16(displacement_size)?;
+                decoded.copy(displacement_field as usize, size as usize)?;
             }
         } else if scheme_2 {
             let c = reader.read_u8(8)?;
             if c == 0xff {
                 reader.skip(1)?;
             }
-            // Store c in result buffer
-            res.push(c);
-            // Store c in rotating history buffer
-            history_buffer[history_index] = c;
-            history_index = (history_index + 1) % 1024;
+            decoded.push(c)?;
         } else {
             bail!("Could not uncompress data");
         }
+
+        // Every symbol is at least 8 bits long, so a reader which did not
+        // move would loop forever
+        if reader.position() <= position {
+            bail!("SLDC decompression did not make progress");
+        }
     }
 
     bail!("Missing END_OF_RECORD control symbol !");
 }
 
-// We only need to decompress data. Therefore, compress function
-// is not to be implemented for now.
-// pub fn compress(bytes: &Vec<u8>) -> Vec<u8> {}
+const MIN_MATCH_SIZE: usize = 2;
+// The largest Match Count Field is `0b1111 11101111`
+const MAX_MATCH_SIZE: usize = 32 + 0b11101111;
+// Number of previous occurrences of a 2-byte prefix examined when looking
+// for the longest match
+const MAX_MATCH_CANDIDATES: usize = 256;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    used: u8,
+}
+
+impl BitWriter {
+    fn new(capacity: usize) -> Self {
+        BitWriter {
+            bytes: Vec::with_capacity(capacity),
+            current: 0,
+            used: 0,
+        }
+    }
+
+    /// Writes the `count` least significant bits of `value`, most
+    /// significant bit first
+    fn write(&mut self, value: u16, count: u8) {
+        for i in (0..count).rev() {
+            self.current = (self.current << 1) | ((value >> i) & 1) as u8;
+            self.used += 1;
+            if self.used == 8 {
+                self.bytes.push(self.current);
+                self.current = 0;
+                self.used = 0;
+            }
+        }
+    }
+
+    /// Pads the last byte with zeros, then appends zero bytes until the
+    /// length is a multiple of 4 bytes
+    fn finish(mut self) -> Vec<u8> {
+        if self.used > 0 {
+            self.write(0, 8 - self.used);
+        }
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+        self.bytes
+    }
+}
+
+fn write_copy_pointer(
+    writer: &mut BitWriter,
+    size: usize,
+    displacement: usize,
+    displacement_size: u8,
+) {
+    // Copy Pointer Data Symbols start with a 1
+    writer.write(1, 1);
+    // Match Count Field
+    match size {
+        2..=3 => writer.write((size - 2) as u16, 2),
+        4..=7 => writer.write(0b1000 | (size - 4) as u16, 4),
+        8..=15 => writer.write(0b110000 | (size - 8) as u16, 6),
+        16..=31 => writer.write(0b11100000 | (size - 16) as u16, 8),
+        _ => writer.write(0b111100000000 | (size - 32) as u16, 12),
+    }
+    // Displacement Field
+    writer.write(displacement as u16, displacement_size);
+}
+
+fn prefix(bytes: &[u8], pos: usize) -> usize {
+    ((bytes[pos] as usize) << 8) | bytes[pos + 1] as usize
+}
+
+/// Compresses `bytes` in a single SLDC record using Scheme 1 (ECMA-321).
+///
+/// The history buffer is reset at the beginning of the record, so that
+/// byte `i` of the input is stored at index `i % 1024` of the history
+/// buffer and a Copy Pointer may refer to any of the 1024 previous bytes.
+pub fn compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    compress_with_window(bytes, HISTORY_SIZE)
+}
+
+/// Compresses `bytes` using a history buffer of `window` bytes, to be
+/// decompressed with `decompress_with_window`
+fn compress_with_window(bytes: &[u8], window: usize) -> Result<Vec<u8>> {
+    debug!(
+        "Try to compress SLDC data ({} uncompressed bytes)",
+        bytes.len()
+    );
+
+    check_window(window)?;
+    let displacement_size = window.trailing_zeros() as u8;
+
+    let mut writer = BitWriter::new(bytes.len() / 2 + 8);
+    // Select Scheme 1 with an empty history buffer
+    writer.write(CTRLSYMB_RESET_1, 13);
+
+    // Last position at which each 2-byte prefix has been seen, and previous
+    // position with the same prefix for each position
+    let mut head: Vec<Option<usize>> = vec![None; 1 << 16];
+    let mut previous: Vec<Option<usize>> = vec![None; bytes.len()];
+
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let mut best_size = 0;
+        let mut best_start = 0;
+        if pos + MIN_MATCH_SIZE <= bytes.len() {
+            let max_size = MAX_MATCH_SIZE.min(bytes.len() - pos);
+            let mut candidate = head[prefix(bytes, pos)];
+            let mut candidates = 0;
+            while let Some(start) = candidate {
+                if pos - start > window || candidates == MAX_MATCH_CANDIDATES {
+                    break;
+                }
+                // The match may overlap the bytes being copied, which the
+                // decompressor handles by copying one byte at a time
+                let size = bytes[start..]
+                    .iter()
+                    .zip(&bytes[pos..pos + max_size])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if size > best_size {
+                    best_size = size;
+                    best_start = start;
+                    if size == max_size {
+                        break;
+                    }
+                }
+                candidate = previous[start];
+                candidates += 1;
+            }
+        }
+
+        let size = if best_size >= MIN_MATCH_SIZE {
+            write_copy_pointer(
+                &mut writer,
+                best_size,
+                best_start % window,
+                displacement_size,
+            );
+            best_size
+        } else {
+            // Literal 1 Data Symbol: a 0 followed by the data byte
+            writer.write(0, 1);
+            writer.write(bytes[pos] as u16, 8);
+            1
+        };
+
+        for (offset, previous) in previous[pos..pos + size].iter_mut().enumerate() {
+            let i = pos + offset;
+            if i + MIN_MATCH_SIZE <= bytes.len() {
+                let key = prefix(bytes, i);
+                *previous = head[key];
+                head[key] = Some(i);
+            }
+        }
+        pos += size;
+    }
+
+    writer.write(CTRLSYMB_END_OF_RECORD, 13);
+    let res = writer.finish();
+    debug!("SLDC compression succeed ({} compressed bytes)", res.len());
+    Ok(res)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use hex::FromHex;
 
+    const HEARTBEAT_COMPRESSED: &str = "ffabfdfc3c001cc003a00114006e001d80065001b0006f001c281a20001e0006da079bb2019cc003d000880068001d00074a098e8002f000bc0077001de8722ea1e8ce87e6f001c80067a1b8c80030000c28862fa298d68ae73a239868622da0af602845dc1730f8c2739800c748f32d1fcc349d176c7de03072118ef4b3e0270d322b8e29a661001900064a5f99698e73001a690667f08f265f9891665c8da6ea84f98ea3bfc0d01325634e80033754c4c5962f49d618e3756bc63d5afc24473acaf8162387c0d7336d56cc75a137e5293357b45d60b37edadf863543e00680224589f06e196c1b8948c36572a58747de17a4e5553c0818570c99874c39a2c5d91c4c76953659ec6a001cd53702e748b8e6003ad8970fc1cad2e70cab26fcbba2f001080036b6d912dbe42cd522d0011adca30000dedd634a03916dce2db568d2c7246b7f916dde450010edd244b6e8dee2244b8ae14818d99f06d709b0796b71d0009b72bc3f58a60ca0024dc7fb3ac6df9cbcb8eab968069dba3ad60eb7cb3a000ab9a3713163d4c75864ceba66cd4673c8ee3edd1231b78fc0288c651e6ce384cc52a1d9c284a79e397e04620f5377e0de51d290675dfeff2c41b12f721934ae0bf3c29b29e783a8dd9b4cf34bf37e475e1947618302c6920f409463e367fcc06e32569ce97efe8667c0a2617ee593956cf0e2186bdfc91ee4ab614c3bec2d3c5c3cdd68532e41d385884a76073d58a7e24b00d6bafa3706197e7e0509705309bb4b4c35bde01e1d6cbb8f10759be487937c8d7191a5c24836691bdabf17b10c0033b67e2ce22db1f17f10adb635b76e2e81a5b746b713215b8f83f5e4a83508133883f88cb45f0eae738940cf9954cb74116d904a7f15f1c41707c64186608997d4c3f4dc9ca7bc3f504126fd3f12f2ba56439a738c73778ced8646cdd645b68e2e01a51bc83469c0d041a2ee07a1b5194859b648945b20d24f03a10b382f9804e00013f3459cb3caf02c089f1f0648b9b3f15f0d690e36a428ca91e43a4be0721cd45c619b846f527c2498bb88b9b4f16e6120c0d832e24ff98c2e8001c732e9baf3f8c6be2c0c0c7a93fe06238641e2598a95fd36d7d4df5db3a57a4a16031b54dc5db9aca7c671f813e43cb228eb2f2e1dec47066b2fe232e1d41bb2cc9972dc9a2cba74cba22fb139cebc268b3b9b6c6a73b0dea6c39d91cc3683175a8ce57b7c45073b588cb30f8d2d6e30a2c8bed2276e32ce3b6c39032ae58e762ee2e616dba48d6eb22652fc12037ca7236b7c8d2e0246b8290ae1e39ca3a37ca578c7b1137a252e0f5106d1a6b0014b8baf86c0cb72fd7fd8a287400245d74c3b6a71b2636a215aacc8003cf1d048bc6cb39ef63f371de0dfb5dc116f5b8ea8d97bfc4603e007fd0000e20b0000";
+
     #[test]
     fn decompress_test_string() -> Result<()> {
         let test_string_compressed = Vec::from_hex("ffb3a32b9ba1039ba3934b733ffd0000")?;
@@ -149,9 +446,217 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn compress_test_string() -> Result<()> {
+        let compressed = compress("test string".as_bytes())?;
+        assert_eq!(compressed.len() % 4, 0);
+        assert_eq!(decompress(&compressed)?, "test string".as_bytes());
+
+        // Round trip of the Scheme 2 vector
+        let test_string = decompress(&Vec::from_hex("ffb3a32b9ba1039ba3934b733ffd0000")?)?;
+        assert_eq!(decompress(&compress(&test_string)?)?, test_string);
+        Ok(())
+    }
+
+    #[test]
+    fn compress_repetitions() -> Result<()> {
+        for size in [0, 1, 2, 3, 4, 271, 272, 1024, 1025, 5000] {
+            let bytes = vec![b'a'; size];
+            let compressed = compress(&bytes)?;
+            assert_eq!(decompress(&compressed)?, bytes);
+            if size > 256 {
+                assert!(compressed.len() < size / 4);
+            }
+        }
+        // Longest matches, on both sides of the end of the history buffer
+        let bytes: Vec<u8> = (0..4000).map(|i| ((i % 1000) % 251) as u8).collect();
+        assert_eq!(decompress(&compress(&bytes)?)?, bytes);
+        Ok(())
+    }
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    #[test]
+    fn compress_fuzz() -> Result<()> {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let size = (rng.next() % 4096) as usize;
+            // Small alphabets produce many matches, large ones many literals
+            let alphabet = 1 + rng.next() % 256;
+            let mut bytes: Vec<u8> = Vec::with_capacity(size);
+            while bytes.len() < size {
+                if bytes.len() > 8 && rng.next() % 4 == 3 {
+                    // Repeat a previous sequence, possibly overlapping
+                    let start = (rng.next() % bytes.len() as u64) as usize;
+                    let len = (rng.next() % 300) as usize;
+                    for offset in 0..len {
+                        let c = bytes[start + offset];
+                        bytes.push(c);
+                    }
+                } else {
+                    bytes.push((rng.next() % alphabet) as u8);
+                }
+            }
+
+            let compressed = compress(&bytes)?;
+            assert_eq!(decompress(&compressed)?, bytes);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn compress_heartbeat_round_trip() -> Result<()> {
+        let heartbeat_compressed = Vec::from_hex(HEARTBEAT_COMPRESSED)?;
+        let heartbeat = decompress(&heartbeat_compressed)?;
+        let compressed = compress(&heartbeat)?;
+        assert_eq!(decompress(&compressed)?, heartbeat);
+        assert!(compressed.len() < heartbeat.len());
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_heartbeat_to_writer() -> Result<()> {
+        let heartbeat_compressed = Vec::from_hex(HEARTBEAT_COMPRESSED)?;
+        let mut heartbeat: Vec<u8> = Vec::new();
+        decompress_to_writer(&heartbeat_compressed, &mut heartbeat)?;
+        assert_eq!(heartbeat, decompress(&heartbeat_compressed)?);
+
+        // Larger than a chunk
+        let bytes: Vec<u8> = (0..5 * OUTPUT_CHUNK_SIZE / 2)
+            .map(|i| (i % 97) as u8)
+            .collect();
+        let compressed = compress(&bytes)?;
+        let mut writer = std::io::Cursor::new(Vec::new());
+        decompress_to_writer(&compressed, &mut writer)?;
+        assert_eq!(writer.into_inner(), bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_truncated() -> Result<()> {
+        let heartbeat_compressed = Vec::from_hex(HEARTBEAT_COMPRESSED)?;
+        // The END_OF_RECORD control symbol is followed by 2 bytes of padding
+        // and 4 trailing bytes
+        for len in 0..heartbeat_compressed.len() - 7 {
+            assert!(decompress(&heartbeat_compressed[..len]).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_all_ones() {
+        for len in 0..64 {
+            assert!(decompress(&vec![0xff; len]).is_err());
+        }
+        // END_MARKER control symbols only
+        let mut writer = BitWriter::new(0);
+        for _ in 0..100 {
+            writer.write(CTRLSYMB_END_MARKER, 13);
+        }
+        assert!(decompress(&writer.finish()).is_err());
+    }
+
+    #[test]
+    fn decompress_adversarial() -> Result<()> {
+        // Data symbols before any scheme is selected
+        let mut writer = BitWriter::new(0);
+        writer.write(0, 9);
+        writer.write(CTRLSYMB_END_OF_RECORD, 13);
+        assert!(decompress(&writer.finish()).is_err());
+
+        // Reserved Match Count Field
+        let mut writer = BitWriter::new(0);
+        writer.write(CTRLSYMB_RESET_1, 13);
+        writer.write(0b1_1111_1111_0000, 13);
+        writer.write(0, 10);
+        writer.write(CTRLSYMB_END_OF_RECORD, 13);
+        assert!(decompress(&writer.finish()).is_err());
+
+        // Copy Pointers reaching the end of the history buffer, referring to
+        // bytes which were never written
+        let mut writer = BitWriter::new(0);
+        writer.write(CTRLSYMB_RESET_1, 13);
+        for _ in 0..10 {
+            write_copy_pointer(&mut writer, MAX_MATCH_SIZE, HISTORY_SIZE - 1, 10);
+        }
+        writer.write(CTRLSYMB_END_OF_RECORD, 13);
+        let res = decompress(&writer.finish())?;
+        assert_eq!(res.len(), 10 * MAX_MATCH_SIZE);
+
+        // Decompression bomb
+        let mut writer = BitWriter::new(0);
+        writer.write(CTRLSYMB_RESET_1, 13);
+        for _ in 0..1000 {
+            write_copy_pointer(&mut writer, MAX_MATCH_SIZE, 0, 10);
+        }
+        writer.write(CTRLSYMB_END_OF_RECORD, 13);
+        let bomb = writer.finish();
+        let mut res = Vec::new();
+        assert!(decompress_to_writer_with_limit(&bomb, &mut res, 100_000).is_err());
+        assert!(res.len() <= 100_000);
+        let mut res = Vec::new();
+        decompress_to_writer_with_limit(&bomb, &mut res, 1000 * MAX_MATCH_SIZE)?;
+        assert_eq!(res.len(), 1000 * MAX_MATCH_SIZE);
+
+        // Random data must never cause a panic
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..1000 {
+            let size = (rng.next() % 512) as usize;
+            let mut bytes: Vec<u8> = (0..size).map(|_| rng.next() as u8).collect();
+            if rng.next() % 2 == 1 {
+                // Start with a valid header
+                bytes.splice(0..0, [0xff, 0xab]);
+            }
+            let _ = decompress(&bytes);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_window() -> Result<()> {
+        for window in [0, 1, 1000, 1025, 3072, 1 << 17] {
+            assert!(decompress_with_window(&[], window).is_err());
+            assert!(compress_with_window(&[], window).is_err());
+        }
+
+        // Blocks repeated 1500 bytes later can only be matched with a 2048
+        // bytes window
+        let mut rng = XorShift(0xd1b5_4a32_d192_ed03);
+        let block: Vec<u8> = (0..1500).map(|_| rng.next() as u8).collect();
+        let bytes = [&block[..], &block[..], &block[..]].concat();
+
+        let compressed = compress_with_window(&bytes, 2048)?;
+        assert_eq!(decompress_with_window(&compressed, 2048)?, bytes);
+        assert_ne!(decompress(&compressed).ok(), Some(bytes.clone()));
+        assert!(compressed.len() < compress(&bytes)?.len() / 2);
+
+        // Copy Pointer with an 11 bits Displacement Field
+        let mut writer = BitWriter::new(0);
+        writer.write(CTRLSYMB_RESET_1, 13);
+        for c in 0..1100 {
+            writer.write(0, 1);
+            writer.write(c % 256, 8);
+        }
+        write_copy_pointer(&mut writer, 4, 1030, 11);
+        writer.write(CTRLSYMB_END_OF_RECORD, 13);
+        let res = decompress_with_window(&writer.finish(), 2048)?;
+        assert_eq!(res.len(), 1104);
+        assert_eq!(res[1100..], [6, 7, 8, 9]);
+        Ok(())
+    }
+
     #[test]
     fn decompress_heartbeat() -> Result<()> {
-        let heartbeat_compressed = Vec::from_hex("ffabfdfc3c001cc003a00114006e001d80065001b0006f001c281a20001e0006da079bb2019cc003d000880068001d00074a098e8002f000bc0077001de8722ea1e8ce87e6f001c80067a1b8c80030000c28862fa298d68ae73a239868622da0af602845dc1730f8c2739800c748f32d1fcc349d176c7de03072118ef4b3e0270d322b8e29a661001900064a5f99698e73001a690667f08f265f9891665c8da6ea84f98ea3bfc0d01325634e80033754c4c5962f49d618e3756bc63d5afc24473acaf8162387c0d7336d56cc75a137e5293357b45d60b37edadf863543e00680224589f06e196c1b8948c36572a58747de17a4e5553c0818570c99874c39a2c5d91c4c76953659ec6a001cd53702e748b8e6003ad8970fc1cad2e70cab26fcbba2f001080036b6d912dbe42cd522d0011adca30000dedd634a03916dce2db568d2c7246b7f916dde450010edd244b6e8dee2244b8ae14818d99f06d709b0796b71d0009b72bc3f58a60ca0024dc7fb3ac6df9cbcb8eab968069dba3ad60eb7cb3a000ab9a3713163d4c75864ceba66cd4673c8ee3edd1231b78fc0288c651e6ce384cc52a1d9c284a79e397e04620f5377e0de51d290675dfeff2c41b12f721934ae0bf3c29b29e783a8dd9b4cf34bf37e475e1947618302c6920f409463e367fcc06e32569ce97efe8667c0a2617ee593956cf0e2186bdfc91ee4ab614c3bec2d3c5c3cdd68532e41d385884a76073d58a7e24b00d6bafa3706197e7e0509705309bb4b4c35bde01e1d6cbb8f10759be487937c8d7191a5c24836691bdabf17b10c0033b67e2ce22db1f17f10adb635b76e2e81a5b746b713215b8f83f5e4a83508133883f88cb45f0eae738940cf9954cb74116d904a7f15f1c41707c64186608997d4c3f4dc9ca7bc3f504126fd3f12f2ba56439a738c73778ced8646cdd645b68e2e01a51bc83469c0d041a2ee07a1b5194859b648945b20d24f03a10b382f9804e00013f3459cb3caf02c089f1f0648b9b3f15f0d690e36a428ca91e43a4be0721cd45c619b846f527c2498bb88b9b4f16e6120c0d832e24ff98c2e8001c732e9baf3f8c6be2c0c0c7a93fe06238641e2598a95fd36d7d4df5db3a57a4a16031b54dc5db9aca7c671f813e43cb228eb2f2e1dec47066b2fe232e1d41bb2cc9972dc9a2cba74cba22fb139cebc268b3b9b6c6a73b0dea6c39d91cc3683175a8ce57b7c45073b588cb30f8d2d6e30a2c8bed2276e32ce3b6c39032ae58e762ee2e616dba48d6eb22652fc12037ca7236b7c8d2e0246b8290ae1e39ca3a37ca578c7b1137a252e0f5106d1a6b0014b8baf86c0cb72fd7fd8a287400245d74c3b6a71b2636a215aacc8003cf1d048bc6cb39ef63f371de0dfb5dc116f5b8ea8d97bfc4603e007fd0000e20b0000")?;
+        let heartbeat_compressed = Vec::from_hex(HEARTBEAT_COMPRESSED)?;
         let heartbeat = Vec::from_hex("fffe3c0073003a0045006e00760065006c006f0070006500200078006d006c006e0073003a0073003d00220068007400740070003a002f002f007700770077002e00770033002e006f00720067002f0032003000300033002f00300035002f0073006f00610070002d0065006e00760065006c006f00700065002200200078006d006c006e0073003a0061003d00220068007400740070003a002f002f0073006300680065006d00610073002e0078006d006c0073006f00610070002e006f00720067002f00770073002f0032003000300034002f00300038002f00610064006400720065007300730069006e0067002200200078006d006c006e0073003a0065003d00220068007400740070003a002f002f0073006300680065006d00610073002e0078006d006c0073006f00610070002e006f00720067002f00770073002f0032003000300034002f00300038002f006500760065006e00740069006e0067002200200078006d006c006e0073003a0077003d00220068007400740070003a002f002f0073006300680065006d00610073002e0064006d00740066002e006f00720067002f007700620065006d002f00770073006d0061006e002f0031002f00770073006d0061006e002e007800730064002200200078006d006c006e0073003a0070003d00220068007400740070003a002f002f0073006300680065006d00610073002e006d006900630072006f0073006f00660074002e0063006f006d002f007700620065006d002f00770073006d0061006e002f0031002f00770073006d0061006e002e0078007300640022003e003c0073003a004800650061006400650072003e003c0061003a0054006f003e0068007400740070003a002f002f007300720076002e00770069006e0064006f006d00610069006e002e006c006f00630061006c003a0035003900380035002f00770073006d0061006e002f0073007500620073006300720069007000740069006f006e0073002f00420036004200440042004200350039002d0046004200300037002d0034004500450035002d0038003400310046002d004500420045004300390044003600370043004400440034002f0031003c002f0061003a0054006f003e003c006d003a004d0061006300680069006e00650049004400200078006d006c006e0073003a006d003d00220068007400740070003a002f002f0073006300680065006d00610073002e006d006900630072006f0073006f00660074002e0063006f006d002f007700620065006d002f00770073006d0061006e002f0031002f006d0061006300680069006e006500690064002200200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200660061006c007300650022003e00770069006e00310030002e00770069006e0064006f006d00610069006e002e006c006f00630061006c003c002f006d003a004d0061006300680069006e006500490044003e003c0061003a005200650070006c00790054006f003e003c0061003a004100640064007200650073007300200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200740072007500650022003e0068007400740070003a002f002f0073006300680065006d00610073002e0078006d006c0073006f00610070002e006f00720067002f00770073002f0032003000300034002f00300038002f00610064006400720065007300730069006e0067002f0072006f006c0065002f0061006e006f006e0079006d006f00750073003c002f0061003a0041006400640072006500730073003e003c002f0061003a005200650070006c00790054006f003e003c0061003a0041006300740069006f006e00200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200740072007500650022003e0068007400740070003a002f002f0073006300680065006d00610073002e0064006d00740066002e006f00720067002f007700620065006d002f00770073006d0061006e002f0031002f00770073006d0061006e002f004800650061007200740062006500610074003c002f0061003a0041006300740069006f006e003e003c0077003a004d006100780045006e00760065006c006f0070006500530069007a006500200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200740072007500650022003e003500310032003000300030003c002f0077003a004d006100780045006e00760065006c006f0070006500530069007a0065003e003c0061003a004d00650073007300610067006500490044003e0075007500690064003a00450045004300300034004600370034002d0041003200370044002d0034004300330041002d0041004500460035002d004200430035004200460035003400330035003900420041003c002f0061003a004d00650073007300610067006500490044003e003c0077003a004c006f00630061006c006500200078006d006c003a006c0061006e0067003d00220065006e002d00550053002200200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200660061006c0073006500220020002f003e003c0070003a0044006100740061004c006f00630061006c006500200078006d006c003a006c0061006e0067003d00220065006e002d00550053002200200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200660061006c0073006500220020002f003e003c0070003a00530065007300730069006f006e0049006400200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200660061006c007300650022003e0075007500690064003a00390038003100430035003300300046002d0042004500320041002d0034004100410042002d0042004100430042002d003600460042003400430044003100410031003400410042003c002f0070003a00530065007300730069006f006e00490064003e003c0070003a004f007000650072006100740069006f006e0049004400200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200660061006c007300650022003e0075007500690064003a00450041003200450045003500360036002d0032004300430031002d0034003900410030002d0041003700320036002d004200430045003700440043003300350036004500320032003c002f0070003a004f007000650072006100740069006f006e00490044003e003c0070003a00530065007100750065006e006300650049006400200073003a006d0075007300740055006e006400650072007300740061006e0064003d002200660061006c007300650022003e0031003c002f0070003a00530065007100750065006e0063006500490064003e003c0077003a004f007000650072006100740069006f006e00540069006d0065006f00750074003e0050005400360030002e0030003000300053003c002f0077003a004f007000650072006100740069006f006e00540069006d0065006f00750074003e003c0065003a004900640065006e00740069006600690065007200200078006d006c006e0073003a0065003d00220068007400740070003a002f002f0073006300680065006d00610073002e0078006d006c0073006f00610070002e006f00720067002f00770073002f0032003000300034002f00300038002f006500760065006e00740069006e00670022003e00320031003900430035003300350033002d0035004600330044002d0034004300440037002d0041003600340034002d004600360042003600390045003500370043003100430031003c002f0065003a004900640065006e007400690066006900650072003e003c0077003a00410063006b005200650071007500650073007400650064002f003e003c002f0073003a004800650061006400650072003e003c0073003a0042006f00640079003e003c0077003a004500760065006e00740073003e003c002f0077003a004500760065006e00740073003e003c002f0073003a0042006f00640079003e003c002f0073003a0045006e00760065006c006f00700065003e00")?;
 
         assert_eq!(decompress(&heartbeat_compressed)?, heartbeat);
@@ -40,6 +40,11 @@ pub const ACTION_SUBSCRIPTION_END: &str =
 pub const ACTION_HEARTBEAT: &str = "http://schemas.dmtf.org/wbem/wsman/1/wsman/Heartbeat";
 pub const ACTION_ACK: &str = "http://schemas.dmtf.org/wbem/wsman/1/wsman/Ack";
 
+pub const STATUS_SOURCE_SHUTTING_DOWN: &str =
+    "http://schemas.xmlsoap.org/ws/2004/08/eventing/SourceShuttingDown";
+pub const STATUS_DELIVERY_FAILURE: &str =
+    "http://schemas.xmlsoap.org/ws/2004/08/eventing/DeliveryFailure";
+
 pub fn new_uuid() -> String {
     format!("uuid:{}", Uuid::new_v4().to_string().to_uppercase())
 }
@@ -532,6 +537,15 @@ impl Serializable for Header {
 pub enum Body {
     EnumerateResponse(Vec<Subscription>),
     Events(Vec<Arc<String>>),
+    SubscriptionEnd(SubscriptionEnd),
+}
+
+/// Sent by clients when a subscription ends on their side, for example
+/// because they could not apply its manifest
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SubscriptionEnd {
+    pub status: Option<String>,
+    pub reason: Option<String>,
 }
 
 impl Serializable for Body {
@@ -739,6 +753,29 @@ fn parse_body_events(node: Node) -> Result<Vec<Arc<String>>> {
     Ok(events)
 }
 
+fn parse_body_subscription_end(node: Node) -> Result<SubscriptionEnd> {
+    ensure!(
+        node.has_tag_name((SOAP_ENVELOPE_NS, "Body")),
+        "Invalid Body tag"
+    );
+    let subscription_end = node
+        .first_element_child()
+        .ok_or_else(|| anyhow!("Malformed SubscriptionEnd body"))?;
+    ensure!(
+        subscription_end.has_tag_name((EVENTING_NS, "SubscriptionEnd")),
+        "Invalid SubscriptionEnd tag"
+    );
+    let mut body = SubscriptionEnd::default();
+    for child in subscription_end.children() {
+        if child.has_tag_name((EVENTING_NS, "Status")) {
+            body.status = child.text().map(|text| text.trim().to_owned());
+        } else if child.has_tag_name((EVENTING_NS, "Reason")) {
+            body.reason = child.text().map(|text| text.trim().to_owned());
+        }
+    }
+    Ok(body)
+}
+
 pub fn parse(payload: &str) -> Result<Message> {
     // This is only used if we need to replace invalid XML characters, but it must
     // be declared here because of scope level.
@@ -798,18 +835,24 @@ pub fn parse(payload: &str) -> Result<Message> {
     let mut body = None;
 
     // Parse body depending on Action field
-    if header
+    let action = header
         .action
         .as_ref()
-        .ok_or_else(|| anyhow!("Missing Action header"))?
-        == ACTION_EVENTS
-    {
+        .ok_or_else(|| anyhow!("Missing Action header"))?;
+    if action == ACTION_EVENTS {
         body = Some(Body::Events(
             parse_body_events(
                 body_node_opt.ok_or_else(|| anyhow!("Missing Body for Events message"))?,
             )
             .context("Failed to parse Body section for Events action")?,
         ));
+    } else if action == ACTION_SUBSCRIPTION_END {
+        body = Some(Body::SubscriptionEnd(
+            parse_body_subscription_end(
+                body_node_opt.ok_or_else(|| anyhow!("Missing Body for SubscriptionEnd message"))?,
+            )
+            .context("Failed to parse Body section for SubscriptionEnd action")?,
+        ));
     }
 
     Ok(Message { header, body })
@@ -870,4 +913,43 @@ mod tests {
             _ => panic!("Wrong body type"),
         }
     }
+
+    #[test]
+    fn subscription_end() {
+        let payload = r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:e="http://schemas.xmlsoap.org/ws/2004/08/eventing" xmlns:w="http://schemas.dmtf.org/wbem/wsman/1/wsman.xsd">
+	<s:Header>
+		<a:To>http://srv.windomain.local:5985/wsman/subscriptions/219C5353-5F3D-4CD7-A644-F6B69E57C1C1</a:To>
+		<a:Action s:mustUnderstand="true">http://schemas.xmlsoap.org/ws/2004/08/eventing/SubscriptionEnd</a:Action>
+		<a:MessageID>uuid:31652DEB-C9E8-45D6-B3E8-90AC64D48422</a:MessageID>
+		<e:Identifier>219C5353-5F3D-4CD7-A644-F6B69E57C1C1</e:Identifier>
+	</s:Header>
+	<s:Body>
+		<e:SubscriptionEnd>
+			<e:SubscriptionManager><a:Address>http://srv.windomain.local:5985/wsman/subscriptions/219C5353-5F3D-4CD7-A644-F6B69E57C1C1</a:Address></e:SubscriptionManager>
+			<e:Status>http://schemas.xmlsoap.org/ws/2004/08/eventing/SourceCancelling</e:Status>
+			<e:Reason xml:lang="en-US">The DataLocale option value is not valid.</e:Reason>
+		</e:SubscriptionEnd>
+	</s:Body>
+</s:Envelope>"#;
+        let message = parse(payload).unwrap();
+        assert_eq!(
+            message.header().identifier().map(String::as_str),
+            Some("219C5353-5F3D-4CD7-A644-F6B69E57C1C1")
+        );
+        match message.body {
+            Some(Body::SubscriptionEnd(end)) => {
+                assert_eq!(
+                    end,
+                    SubscriptionEnd {
+                        status: Some(
+                            "http://schemas.xmlsoap.org/ws/2004/08/eventing/SourceCancelling"
+                                .to_string()
+                        ),
+                        reason: Some("The DataLocale option value is not valid.".to_string()),
+                    }
+                );
+            }
+            _ => panic!("Wrong body type"),
+        }
+    }
 }
@@ -0,0 +1,277 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use common::database::Db;
+use log::{debug, info, warn};
+use metrics::gauge;
+use tokio::time;
+
+use crate::{
+    monitoring::{SUBSCRIPTION_NAME, SUBSCRIPTION_SOURCES_GAUGE, SUBSCRIPTION_UUID},
+    subscription::Subscriptions,
+};
+
+// Maximum interval (in seconds) between two checks of the enrolled sources
+const MAX_CHECK_INTERVAL: u64 = 60;
+
+pub fn set_sources_gauge(uuid: &str, name: &str, sources: i64) {
+    gauge!(SUBSCRIPTION_SOURCES_GAUGE,
+        SUBSCRIPTION_NAME => name.to_owned(),
+        SUBSCRIPTION_UUID => uuid.to_owned())
+    .set(sources as f64);
+}
+
+#[derive(Debug)]
+struct NoSources {
+    // Time from which the subscription has no enrolled source
+    since: i64,
+    warned: bool,
+}
+
+/// Tracks the subscriptions without any enrolled source, so that a warning
+/// is logged once they have had none for `delay` seconds.
+#[derive(Debug)]
+pub struct NoSourcesTracker {
+    delay: i64,
+    subscriptions: HashMap<String, NoSources>,
+}
+
+impl NoSourcesTracker {
+    pub fn new(delay: u64) -> Self {
+        Self {
+            delay: delay.try_into().unwrap_or(i64::MAX),
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    /// Updates the number of enrolled sources of a subscription. Returns true
+    /// when the subscription has just reached `delay` seconds without source.
+    pub fn update(&mut self, uuid: &str, sources: i64, now: i64) -> bool {
+        if sources > 0 {
+            self.subscriptions.remove(uuid);
+            return false;
+        }
+        let state = self
+            .subscriptions
+            .entry(uuid.to_owned())
+            .or_insert(NoSources {
+                since: now,
+                warned: false,
+            });
+        if state.warned || now - state.since < self.delay {
+            return false;
+        }
+        state.warned = true;
+        true
+    }
+
+    /// Forgets the subscriptions which are no longer loaded
+    pub fn retain(&mut self, uuids: &HashSet<String>) {
+        self.subscriptions.retain(|uuid, _| uuids.contains(uuid));
+    }
+
+    /// Sets the sources gauge of a subscription and warns if it has had no
+    /// enrolled source for too long. Returns true if a warning was logged.
+    pub fn report(&mut self, uuid: &str, name: &str, sources: i64, now: i64) -> bool {
+        set_sources_gauge(uuid, name, sources);
+        if !self.update(uuid, sources, now) {
+            return false;
+        }
+        warn!(
+            "Subscription \"{}\" ({}) has had no enrolled source for {} seconds. Its client filter may not match any client.",
+            name, uuid, self.delay
+        );
+        true
+    }
+}
+
+/// Reports the enrolled sources of every loaded subscription, using the
+/// heartbeats seen during the last heartbeat interval
+async fn check_sources(
+    db: &Db,
+    subscriptions: &Subscriptions,
+    tracker: &mut NoSourcesTracker,
+    now: i64,
+) {
+    // We can't await with the lock on "subscriptions"
+    // So we first copy all data we need from "subscriptions"
+    let subscriptions_data: Vec<(String, String, u32)> = subscriptions
+        .read()
+        .unwrap()
+        .values()
+        .map(|subscription| {
+            (
+                subscription.uuid_string(),
+                subscription.data().qualified_name(),
+                subscription.data().heartbeat_interval(),
+            )
+        })
+        .collect();
+    tracker.retain(
+        &subscriptions_data
+            .iter()
+            .map(|(uuid, _, _)| uuid.clone())
+            .collect(),
+    );
+
+    for (uuid, name, heartbeat_interval) in subscriptions_data {
+        let stats = match db.get_stats(&uuid, now - (heartbeat_interval as i64)).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                warn!(
+                    "Failed to retrieve stats of subscription {} ({}): {:?}",
+                    name, uuid, e
+                );
+                continue;
+            }
+        };
+        tracker.report(&uuid, &name, stats.enrolled_machines_count(), now);
+    }
+}
+
+pub async fn no_sources_task(db: Db, subscriptions: Subscriptions, delay: u64) -> Result<()> {
+    info!("Starting subscriptions sources check task");
+    let mut tracker = NoSourcesTracker::new(delay);
+    let mut check = time::interval(Duration::from_secs(delay.min(MAX_CHECK_INTERVAL)));
+    loop {
+        check.tick().await;
+        debug!("Checking subscriptions enrolled sources");
+
+        let now: i64 = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs()
+            .try_into()?;
+        check_sources(&db, &subscriptions, &mut tracker, now).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use common::{
+        database::{schema, schema::Migrator, sqlite::SQLiteDatabase},
+        heartbeat::{HeartbeatKey, HeartbeatValue, HeartbeatsCache},
+        settings::Outputs,
+        subscription::{
+            ClientFilter, ClientFilterFlags, ClientFilterOperation, ClientFilterType,
+            SubscriptionData,
+        },
+    };
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    #[test]
+    fn test_no_sources_tracker() {
+        let mut tracker = NoSourcesTracker::new(60);
+        assert!(!tracker.update("a", 0, 1000));
+        assert!(!tracker.update("a", 0, 1059));
+        assert!(tracker.update("a", 0, 1060));
+        // The warning is only reported once
+        assert!(!tracker.update("a", 0, 2000));
+
+        // A source enrolls, then leaves again
+        assert!(!tracker.update("a", 2, 2100));
+        assert!(!tracker.update("a", 0, 2200));
+        assert!(tracker.update("a", 0, 2260));
+
+        // Unloaded subscriptions are forgotten
+        assert!(!tracker.update("b", 0, 3000));
+        tracker.retain(&HashSet::from(["a".to_string()]));
+        assert!(!tracker.update("b", 0, 3060));
+        assert!(tracker.update("b", 0, 3120));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_without_matching_source() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("openwec-sources-{}", Uuid::new_v4()));
+        let mut db = SQLiteDatabase::new(path.to_str().unwrap()).await?;
+        schema::sqlite::register_migrations(&mut db);
+        let db: Db = Arc::new(db);
+        db.setup_schema().await?;
+        Migrator::new(db.clone()).up(None, false).await?;
+
+        let mut filtered = SubscriptionData::new("Test", "");
+        filtered.set_client_filter(Some(
+            ClientFilter::try_new(
+                ClientFilterOperation::Only,
+                ClientFilterType::default(),
+                ClientFilterFlags::default(),
+                HashSet::from(["DC$@WINDOMAIN.LOCAL".to_string()]),
+            )
+            .unwrap(),
+        ));
+        let other = SubscriptionData::new("Other", "");
+        let filtered_uuid = filtered.uuid_string();
+        let other_uuid = other.uuid_string();
+
+        let mut output_context = OutputDriversContext::new(&Outputs::default());
+        let subscriptions: Subscriptions = Arc::new(RwLock::new(HashMap::new()));
+        for data in [filtered, other] {
+            db.store_subscription(&data).await?;
+            let uuid = *data.uuid();
+            let subscription = Subscription::from_data(data, &mut output_context)?;
+            subscriptions
+                .write()
+                .unwrap()
+                .insert(uuid, Arc::new(subscription));
+        }
+
+        // Clients rejected by the client filter never send heartbeats, so
+        // only the other subscription has an enrolled source
+        let mut heartbeats = HeartbeatsCache::new();
+        heartbeats.insert(
+            HeartbeatKey {
+                machine: "WIN10$@WINDOMAIN.LOCAL".to_string(),
+                subscription: other_uuid.clone(),
+            },
+            HeartbeatValue {
+                ip: "192.168.58.100".to_string(),
+                last_seen: 4000,
+                last_event_seen: None,
+            },
+        );
+        db.store_heartbeats(&heartbeats).await?;
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let mut tracker = NoSourcesTracker::new(3600);
+        {
+            // Metrics are recorded by this thread, which performs the checks
+            let _guard = metrics::set_default_local_recorder(&recorder);
+
+            check_sources(&db, &subscriptions, &mut tracker, 1000).await;
+            check_sources(&db, &subscriptions, &mut tracker, 4599).await;
+            assert!(!tracker.subscriptions[&filtered_uuid].warned);
+            // The stale subscription warning is logged after the configured delay
+            check_sources(&db, &subscriptions, &mut tracker, 4600).await;
+            assert!(tracker.subscriptions[&filtered_uuid].warned);
+            assert!(!tracker.subscriptions.contains_key(&other_uuid));
+        }
+
+        let rendered = handle.render();
+        let gauge = |uuid: &str| {
+            rendered
+                .lines()
+                .find(|line| {
+                    line.starts_with(SUBSCRIPTION_SOURCES_GAUGE)
+                        && line.contains(&format!("{}=\"{}\"", SUBSCRIPTION_UUID, uuid))
+                })
+                .expect("sources gauge is missing")
+                .to_owned()
+        };
+        let line = gauge(&filtered_uuid);
+        assert!(line.contains(&format!("{}=\"Test\"", SUBSCRIPTION_NAME)));
+        assert!(line.ends_with(" 0"));
+        assert!(gauge(&other_uuid).ends_with(" 1"));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
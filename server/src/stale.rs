@@ -0,0 +1,181 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use metrics::counter;
+
+use crate::{
+    event::{EventData, EventMetadata},
+    monitoring::{
+        OUTPUT_DRIVER, OUTPUT_STALE_DROPPED_EVENTS, SUBSCRIPTION_NAME, SUBSCRIPTION_UUID,
+    },
+};
+
+/// Drops the events which are too old to be worth delivering to an output.
+///
+/// OpenWEC does not buffer events: while an output is down, batches are not
+/// acknowledged and clients send them again later. Once the output recovers,
+/// the events created more than `max_age` ago are dropped instead of being
+/// delivered stale. Events whose creation time is unknown are always kept.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueAgeLimit {
+    max_age: TimeDelta,
+}
+
+impl QueueAgeLimit {
+    pub fn new(max_age: u64) -> Self {
+        Self {
+            max_age: TimeDelta::seconds(max_age.try_into().unwrap_or(i64::MAX)),
+        }
+    }
+
+    /// Selects the events which are not older than `max_age` at `now`.
+    /// `created` contains the creation time of each event.
+    /// Returns the kept events and the number of dropped events.
+    pub fn select<T: Clone>(
+        &self,
+        events: &[T],
+        created: &[Option<DateTime<Utc>>],
+        now: DateTime<Utc>,
+    ) -> (Vec<T>, u64) {
+        let mut kept = Vec::with_capacity(events.len());
+        let mut dropped = 0;
+        for (event, created) in events.iter().zip(created.iter()) {
+            match created {
+                Some(created) if now - *created > self.max_age => dropped += 1,
+                _ => kept.push(event.clone()),
+            }
+        }
+        (kept, dropped)
+    }
+}
+
+/// Returns the `TimeCreated` of a parsed event
+pub fn time_created(event_data: &EventData) -> Option<DateTime<Utc>> {
+    event_data
+        .event()
+        .and_then(|event| event.system.as_ref())
+        .and_then(|system| system.time_created.as_ref())
+        .and_then(|time_created| DateTime::parse_from_rfc3339(time_created).ok())
+        .map(|time_created| time_created.with_timezone(&Utc))
+}
+
+pub fn count_dropped(metadata: &EventMetadata, driver: &str, events: u64) {
+    counter!(OUTPUT_STALE_DROPPED_EVENTS,
+        SUBSCRIPTION_NAME => metadata.subscription_qualified_name(),
+        SUBSCRIPTION_UUID => metadata.subscription_uuid().to_owned(),
+        OUTPUT_DRIVER => driver.to_owned())
+    .increment(events);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc};
+
+    use anyhow::Result;
+    use common::{
+        settings,
+        subscription::{
+            SubscriptionData, SubscriptionOutput, SubscriptionOutputDriver,
+            SubscriptionOutputFormat, UnixDatagramConfiguration,
+        },
+    };
+    use tokio::net::UnixDatagram;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn event(id: u32, time_created: DateTime<Utc>) -> String {
+        format!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing'/><EventID>{}</EventID><TimeCreated SystemTime='{}'/><Computer>win10.windomain.local</Computer></System></Event>",
+            id,
+            time_created.to_rfc3339()
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stale_events_dropped_on_recovery() -> Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("openwec-test-{}.socket", Uuid::new_v4()))
+            .display()
+            .to_string();
+        let mut output = SubscriptionOutput::new(
+            SubscriptionOutputFormat::Raw,
+            SubscriptionOutputDriver::UnixDatagram(UnixDatagramConfiguration::new(path.clone())),
+            true,
+        );
+        output.set_max_queue_age(Some(300));
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![output]);
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+        assert!(subscription.has_age_limited_outputs());
+        let metadata = Arc::new(EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        ));
+        let output = &subscription.outputs()[0];
+        let age_limit = output.age_limit().unwrap();
+
+        // The output is down: nothing listens on the socket, so the batch
+        // is not acknowledged and the client keeps its events
+        let outage_start = Utc::now() - TimeDelta::seconds(600);
+        let mut events = vec![
+            event(4624, outage_start),
+            event(4625, outage_start + TimeDelta::seconds(60)),
+        ];
+        assert!(output
+            .write(
+                metadata.clone(),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| Arc::new(event.as_bytes().to_vec()))
+                        .collect()
+                )
+            )
+            .await
+            .is_err());
+        assert!(output.is_failing());
+
+        // Ten minutes later, the output recovers and the client sends the
+        // events created during the outage
+        let listener = UnixDatagram::bind(&path)?;
+        let now = Utc::now();
+        events.push(event(4634, now - TimeDelta::seconds(200)));
+        events.push(event(4647, now - TimeDelta::seconds(10)));
+        let created: Vec<Option<DateTime<Utc>>> = events
+            .iter()
+            .map(|raw| time_created(&EventData::new(Arc::new(raw.clone()), true)))
+            .collect();
+        let (kept, dropped) = age_limit.select(&events, &created, now);
+        assert_eq!(dropped, 2);
+        assert_eq!(kept, events[2..]);
+
+        output
+            .write(
+                metadata,
+                Arc::new(
+                    kept.into_iter()
+                        .map(|event| Arc::new(event.into_bytes()))
+                        .collect(),
+                ),
+            )
+            .await?;
+        assert!(!output.is_failing());
+        let mut buf = vec![0; 4096];
+        for expected in events[2..].iter() {
+            let len = listener.recv(&mut buf).await?;
+            assert_eq!(std::str::from_utf8(&buf[..len])?, expected);
+        }
+
+        // Events without creation time are kept
+        let (kept, dropped) = age_limit.select(&["unparsed"], &[None], now);
+        assert_eq!((kept, dropped), (vec!["unparsed"], 0));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
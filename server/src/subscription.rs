@@ -1,11 +1,8 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use common::{
     database::Db,
-    settings::Outputs,
-    subscription::{
-        InternalVersion, PublicVersion, SubscriptionData, SubscriptionOutputFormat,
-        SubscriptionUuid,
-    },
+    settings::{MaxSubscriptionsPolicy, Outputs, Server},
+    subscription::{InternalVersion, PublicVersion, SubscriptionData, SubscriptionUuid},
 };
 use itertools::Itertools;
 use log::{debug, info, warn};
@@ -19,15 +16,32 @@ use tokio::{
     time,
 };
 
-use crate::output::{Output, OutputDriversContext};
+use crate::{
+    batch::BatchLimiter,
+    clients::ClientLimiter,
+    dedup_window::DedupWindow,
+    enrollment::EnrollmentFailures,
+    first_seen::KnownSources,
+    output::{FormatKey, Output, OutputDriversContext},
+    queue::EventQueue,
+    retry::RetryLimiter,
+};
 
 pub struct Subscription {
     data: SubscriptionData,
     // Subscription public version is a bit expensive to compute, so we
     // store the result in memory
     public_version: PublicVersion,
+    fallback_public_version: PublicVersion,
     outputs: Vec<Output>,
-    formats: HashSet<SubscriptionOutputFormat>,
+    formats: HashSet<FormatKey>,
+    known_sources: KnownSources,
+    retry_limiter: Option<RetryLimiter>,
+    queue: Option<Arc<EventQueue>>,
+    batch_limiter: Option<BatchLimiter>,
+    client_limiter: Option<ClientLimiter>,
+    dedup_window: Option<DedupWindow>,
+    enrollment_failures: EnrollmentFailures,
 }
 
 impl Subscription {
@@ -41,11 +55,17 @@ impl Subscription {
         self.public_version.to_string().to_uppercase()
     }
 
+    /// Get the public version of the manifest sent to clients which
+    /// rejected the subscription.
+    pub fn fallback_public_version_string(&self) -> String {
+        self.fallback_public_version.to_string().to_uppercase()
+    }
+
     pub fn data(&self) -> &SubscriptionData {
         &self.data
     }
 
-    pub fn formats(&self) -> &HashSet<SubscriptionOutputFormat> {
+    pub fn formats(&self) -> &HashSet<FormatKey> {
         &self.formats
     }
 
@@ -53,6 +73,72 @@ impl Subscription {
         &self.outputs
     }
 
+    pub fn known_sources(&self) -> &KnownSources {
+        &self.known_sources
+    }
+
+    pub fn retry_limiter(&self) -> Option<&RetryLimiter> {
+        self.retry_limiter.as_ref()
+    }
+
+    pub fn batch_limiter(&self) -> Option<&BatchLimiter> {
+        self.batch_limiter.as_ref()
+    }
+
+    pub fn client_limiter(&self) -> Option<&ClientLimiter> {
+        self.client_limiter.as_ref()
+    }
+
+    pub fn dedup_window(&self) -> Option<&DedupWindow> {
+        self.dedup_window.as_ref()
+    }
+
+    pub fn enrollment_failures(&self) -> &EnrollmentFailures {
+        &self.enrollment_failures
+    }
+
+    pub fn queue(&self) -> Option<&Arc<EventQueue>> {
+        self.queue.as_ref()
+    }
+
+    pub fn has_sampling_outputs(&self) -> bool {
+        self.outputs.iter().any(|output| output.sampler().is_some())
+    }
+
+    pub fn has_age_limited_outputs(&self) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| output.age_limit().is_some())
+    }
+
+    pub fn has_routed_outputs(&self) -> bool {
+        self.outputs.iter().any(|output| output.router().is_some())
+    }
+
+    pub fn has_dedupe_outputs(&self) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| output.change_detector().is_some())
+    }
+
+    pub fn has_source_first_seen_outputs(&self) -> bool {
+        self.outputs.iter().any(|output| output.source_first_seen())
+    }
+
+    pub fn has_summary_outputs(&self) -> bool {
+        self.outputs.iter().any(|output| output.summary())
+    }
+
+    pub fn has_dead_letter_outputs(&self) -> bool {
+        self.outputs.iter().any(|output| output.dead_letter())
+    }
+
+    pub fn has_event_fields_outputs(&self) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| output.needs_event_fields())
+    }
+
     fn create_outputs(
         data: &SubscriptionData,
         context: &mut OutputDriversContext,
@@ -60,33 +146,142 @@ impl Subscription {
         let mut outputs = Vec::new();
         for output_data in data.outputs() {
             if output_data.enabled() {
-                outputs.push(Output::new(
-                    output_data.format(),
-                    output_data.driver(),
-                    context,
-                )?);
+                outputs.push(Output::new(output_data, data.event_ttl(), context)?);
             }
         }
         Ok(outputs)
     }
 
     pub fn from_data(data: SubscriptionData, context: &mut OutputDriversContext) -> Result<Self> {
-        let mut formats: HashSet<SubscriptionOutputFormat> = HashSet::new();
+        let outputs = Self::create_outputs(&data, context)?;
+        Self::with_outputs(data, outputs)
+    }
+
+    /// Builds the new version of `previous`. Outputs which have not been
+    /// modified keep running: only the drivers of the outputs which have
+    /// been removed (or disabled) are torn down.
+    pub fn reload(
+        previous: &Subscription,
+        data: SubscriptionData,
+        context: &mut OutputDriversContext,
+    ) -> Result<(Self, OutputsDiff)> {
+        let diff = OutputsDiff::new(previous.data(), &data);
+        // previous.outputs() only contains the enabled outputs
+        let previous_outputs: HashMap<usize, &Output> = previous
+            .data()
+            .outputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.enabled())
+            .map(|(index, _)| index)
+            .zip(previous.outputs())
+            .collect();
+
+        let mut outputs = Vec::new();
+        for (index, output_data) in data.outputs().iter().enumerate() {
+            if !output_data.enabled() {
+                continue;
+            }
+            let kept = diff
+                .kept
+                .iter()
+                .find(|(_, new_index)| *new_index == index)
+                .and_then(|(previous_index, _)| previous_outputs.get(previous_index));
+            match kept {
+                Some(output) => outputs.push((*output).clone()),
+                None => outputs.push(Output::new(output_data, data.event_ttl(), context)?),
+            }
+        }
+        Ok((Self::with_outputs(data, outputs)?, diff))
+    }
+
+    fn with_outputs(data: SubscriptionData, outputs: Vec<Output>) -> Result<Self> {
+        let mut formats: HashSet<FormatKey> = HashSet::new();
         for output in data.outputs() {
-            formats.insert(output.format().clone());
+            // "Source first seen", "summary" and "dead letter" outputs do
+            // not receive the events themselves
+            if output.enabled()
+                && !output.source_first_seen()
+                && !output.summary()
+                && !output.dead_letter()
+            {
+                formats.insert(FormatKey::new(output));
+            }
         }
-        let outputs = Self::create_outputs(&data, context)?;
+        let retry_limiter = data.max_concurrent_retries().map(RetryLimiter::new);
+        let queue = EventQueue::for_subscription(&data);
+        let batch_limiter = data.max_concurrent_batches().map(BatchLimiter::new);
+        let client_limiter = data.max_concurrent_clients().map(ClientLimiter::new);
+        let dedup_window = DedupWindow::from_subscription(&data);
         let subscription = Subscription {
             public_version: data.public_version()?,
+            fallback_public_version: data.fallback_public_version()?,
             data,
             outputs,
             formats,
+            known_sources: KnownSources::new(),
+            retry_limiter,
+            queue,
+            batch_limiter,
+            client_limiter,
+            dedup_window,
+            enrollment_failures: EnrollmentFailures::new(),
         };
 
         Ok(subscription)
     }
 }
 
+/// Changes applied to the enabled outputs of a subscription when it is
+/// reloaded. Indexes refer to `SubscriptionData::outputs()`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OutputsDiff {
+    /// (previous index, new index) of the outputs which keep running
+    pub kept: Vec<(usize, usize)>,
+    /// Outputs which are added or enabled
+    pub started: Vec<usize>,
+    /// Outputs which are removed or disabled
+    pub stopped: Vec<usize>,
+}
+
+impl OutputsDiff {
+    pub fn new(previous: &SubscriptionData, new: &SubscriptionData) -> Self {
+        let mut diff = OutputsDiff::default();
+        let mut remaining: Vec<usize> = previous
+            .outputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.enabled())
+            .map(|(index, _)| index)
+            .collect();
+        for (index, output) in new.outputs().iter().enumerate() {
+            if !output.enabled() {
+                continue;
+            }
+            // Outputs making events expire are started again when the
+            // event TTL changes
+            if previous.event_ttl() != new.event_ttl() && output.driver().supports_event_ttl() {
+                diff.started.push(index);
+                continue;
+            }
+            // Each previous output may only be reused once
+            match remaining
+                .iter()
+                .position(|previous_index| &previous.outputs()[*previous_index] == output)
+            {
+                Some(position) => diff.kept.push((remaining.remove(position), index)),
+                None => diff.started.push(index),
+            }
+        }
+        diff.stopped = remaining;
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.started.is_empty() && self.stopped.is_empty()
+    }
+}
+
 /// In-memory map of currently active subscriptions
 /// <subscription_uuid> => <subscription>
 pub type Subscriptions = Arc<RwLock<HashMap<SubscriptionUuid, Arc<Subscription>>>>;
@@ -96,6 +291,7 @@ pub async fn reload_subscriptions_task(
     subscriptions: Subscriptions,
     reload_interval: u64,
     outputs_settings: Outputs,
+    server_settings: Server,
 ) {
     info!("reload_subscriptions task started");
     let mut reload = time::interval(Duration::from_secs(reload_interval));
@@ -113,7 +309,7 @@ pub async fn reload_subscriptions_task(
             // First tick happens instantly
             _ = reload.tick() => {
                 debug!("Update subscriptions from db (interval tick)");
-                if let Err(e) = reload_subscriptions(db.clone(), subscriptions.clone(), &mut context, true).await {
+                if let Err(e) = reload_subscriptions(db.clone(), subscriptions.clone(), &mut context, &server_settings, true).await {
                     warn!("Failed to update subscriptions on interval tick: {:?}", e);
                     continue;
                 }
@@ -127,7 +323,7 @@ pub async fn reload_subscriptions_task(
             },
             _ = sighup.recv() => {
                 info!("Update subscriptions from db (signal)");
-                if let Err(e) = reload_subscriptions(db.clone(), subscriptions.clone(), &mut context, false).await {
+                if let Err(e) = reload_subscriptions(db.clone(), subscriptions.clone(), &mut context, &server_settings, false).await {
                     warn!("Failed to update subscriptions on SIGHUP: {:?}", e);
                     continue;
                 }
@@ -136,16 +332,128 @@ pub async fn reload_subscriptions_task(
     }
 }
 
+/// Replaces the in-memory representation of a single subscription, leaving
+/// all other subscriptions (and their in-memory state) untouched.
+///
+/// On failure, the previous version of the subscription is kept.
+fn reload_subscription(
+    mem_subscriptions: &mut HashMap<SubscriptionUuid, Arc<Subscription>>,
+    context: &mut OutputDriversContext,
+    subscription_data: SubscriptionData,
+) -> Result<()> {
+    // This may happen in 2 situations:
+    // 1. This is a new subscription. We must add it to the in-memory subscriptions.
+    // 2. The subscription has been updated. We must replace the old subscription
+    //      with the new one in the in memory subscriptions.
+
+    // Make sure that the context is initialized for the active output drivers
+    // of this subscription
+    context
+        .initialize_missing(std::slice::from_ref(&subscription_data))
+        .with_context(|| {
+            format!(
+                "Failed to initialize output drivers context of subscription {}",
+                subscription_data.name()
+            )
+        })?;
+
+    let name = subscription_data.name().to_owned();
+    let new_subscription = match mem_subscriptions.get(subscription_data.uuid()) {
+        Some(previous) => {
+            let (subscription, diff) =
+                Subscription::reload(previous, subscription_data, context)
+                    .with_context(|| format!("Failed to reload subscription {}", name))?;
+            if !diff.is_empty() {
+                info!(
+                    "Outputs of subscription {}: {} kept, {} started, {} stopped",
+                    name,
+                    diff.kept.len(),
+                    diff.started.len(),
+                    diff.stopped.len()
+                );
+            }
+            Arc::new(subscription)
+        }
+        None => Arc::new(
+            Subscription::from_data(subscription_data, context)
+                .with_context(|| format!("Failed to reload subscription {}", name))?,
+        ),
+    };
+
+    // mem_subscriptions is indexed on uuid, which stays the same after an update
+    match mem_subscriptions.insert(*new_subscription.data().uuid(), new_subscription) {
+        Some(_) => info!("Subscription {} has been updated", name),
+        None => info!("Subscription {} has been created", name),
+    }
+    Ok(())
+}
+
+/// Keeps the active subscriptions that may be loaded according to the
+/// `server.max_subscriptions` setting.
+fn limit_subscriptions(
+    mut subscriptions: Vec<SubscriptionData>,
+    settings: &Server,
+) -> Result<Vec<SubscriptionData>> {
+    let max = match settings.max_subscriptions() {
+        Some(max) => max as usize,
+        None => return Ok(subscriptions),
+    };
+    if subscriptions.len() <= max {
+        return Ok(subscriptions);
+    }
+    match settings.max_subscriptions_policy() {
+        MaxSubscriptionsPolicy::Error => bail!(
+            "{} active subscriptions exceed the limit of {} (server.max_subscriptions)",
+            subscriptions.len(),
+            max
+        ),
+        MaxSubscriptionsPolicy::Warn => {
+            warn!(
+                "{} active subscriptions exceed the limit of {} (server.max_subscriptions): only the first {} (by name) are loaded",
+                subscriptions.len(),
+                max,
+                max
+            );
+            subscriptions.sort_by(|a, b| a.name().cmp(b.name()));
+            subscriptions.truncate(max);
+            Ok(subscriptions)
+        }
+    }
+}
+
+/// Checks, before anything is loaded, that the active subscriptions stored
+/// in database do not exceed the `server.max_subscriptions` setting
+pub async fn check_max_subscriptions(db: &Db, settings: &Server) -> Result<()> {
+    let active_subscriptions = db
+        .get_subscriptions()
+        .await?
+        .into_iter()
+        .filter(|subscription| subscription.is_active())
+        .collect();
+    limit_subscriptions(active_subscriptions, settings)?;
+    Ok(())
+}
+
 async fn reload_subscriptions(
     db: Db,
     mem_subscriptions: Subscriptions,
     context: &mut OutputDriversContext,
+    server_settings: &Server,
     keep_already_existing: bool,
 ) -> Result<()> {
-    let db_subscriptions = db.get_subscriptions().await?;
-
-    // Make sure that the context is initialized for every active output drivers
-    context.initialize_missing(&db_subscriptions).context("Failed to initialize output drivers context")?;
+    let mut db_subscriptions = Vec::new();
+    for subscription_data in db.get_subscriptions().await? {
+        if !subscription_data.is_active() {
+            debug!(
+                "Subscription {} is disabled or have no enabled outputs",
+                subscription_data.name(),
+            );
+            continue;
+        }
+        db_subscriptions.push(subscription_data);
+    }
+    // On error, subscriptions are left as they are
+    let db_subscriptions = limit_subscriptions(db_subscriptions, server_settings)?;
 
     let mut active_subscriptions: HashSet<InternalVersion> =
         HashSet::with_capacity(db_subscriptions.len());
@@ -167,56 +475,33 @@ async fn reload_subscriptions(
         .collect();
 
     for subscription_data in db_subscriptions {
-        if !subscription_data.is_active() {
-            debug!(
-                "Subscription {} is disabled or have no enabled outputs",
-                subscription_data.name(),
-            );
-            continue;
-        }
-
         let internal_version = subscription_data.internal_version();
-
-        active_subscriptions.insert(internal_version);
+        let uuid = *subscription_data.uuid();
 
         // Update the in memory representation of this subscription if necessary
         match mem_subscriptions_internal_version.get(&internal_version) {
             Some(_) => {
                 // This subscription has not been changed. Nothing to do
+                active_subscriptions.insert(internal_version);
             }
             None => {
                 debug!(
                     "Subscription internal version {} not found in the in memory subscriptions",
                     internal_version
                 );
-                // The internal version of this subscription does not exist in the in-memory
-                // subscriptions HashMap. This may happen in 2 situations:
-                // 1. This is a new subscription. We must add it to the in-memory subscriptions.
-                // 2. The subscription has been updated. We must remove the old subscription and add the new one to the
-                //      in memory subscriptions.
-
-                // `subscription.uuid()` stays the same after an update
-                let old_subscription: Option<Arc<Subscription>> = {
-                    mem_subscriptions
-                        .values()
-                        .find(|old_subscription| {
-                            subscription_data.uuid() == old_subscription.data().uuid()
-                        })
-                        .cloned()
-                };
-
-                if let Some(old_subscription) = old_subscription {
-                    info!("Subscription {} has been updated", subscription_data.name());
-                    mem_subscriptions.remove(old_subscription.data().uuid());
-                } else {
-                    info!("Subscription {} has been created", subscription_data.name());
+                // Errors only affect the subscription being reloaded: its
+                // previous in-memory version (if any) is kept as is.
+                match reload_subscription(&mut mem_subscriptions, context, subscription_data) {
+                    Ok(()) => {
+                        active_subscriptions.insert(internal_version);
+                    }
+                    Err(e) => {
+                        warn!("{:?}", e);
+                        if let Some(old_subscription) = mem_subscriptions.get(&uuid) {
+                            active_subscriptions.insert(old_subscription.data().internal_version());
+                        }
+                    }
                 }
-
-                // Initialize the new subscription and add it to in-memory subscriptions
-                let new_subscription =
-                    Arc::new(Subscription::from_data(subscription_data.clone(), context)?);
-                // mem_subscriptions is indexed on public version
-                mem_subscriptions.insert(*new_subscription.data().uuid(), new_subscription);
             }
         }
     }
@@ -269,3 +554,245 @@ async fn reload_subscriptions(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, str::FromStr};
+
+    use common::{
+        models::config::{config_files, load_file, parse},
+        settings::{self, Settings},
+    };
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn server_settings(max_subscriptions: u32, policy: &str) -> Result<Server> {
+        let settings = Settings::from_str(&format!(
+            r#"
+[server]
+keytab = "/etc/keytab"
+max_subscriptions = {}
+max_subscriptions_policy = "{}"
+
+[database]
+type = "SQLite"
+path = "/tmp/openwec.sqlite"
+
+[[collectors]]
+hostname = "wec.windomain.local"
+listen_address = "0.0.0.0"
+
+[collectors.authentication]
+type = "Kerberos"
+service_principal_name = "HTTP/wec.windomain.local@WINDOMAIN.LOCAL"
+"#,
+            max_subscriptions, policy
+        ))?;
+        Ok(settings.server().clone())
+    }
+
+    fn load_dir(path: &Path) -> Result<Vec<SubscriptionData>> {
+        let mut subscriptions = Vec::new();
+        for file in config_files(path)? {
+            subscriptions.push(load_file(&file, None, true)?);
+        }
+        Ok(subscriptions)
+    }
+
+    #[test]
+    fn test_max_subscriptions() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("openwec-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        for name in ["c", "a", "b"] {
+            fs::write(
+                dir.join(format!("{}.toml", name)),
+                format!(
+                    r#"
+uuid = "{}"
+name = "{}"
+query = "<QueryList></QueryList>"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Raw"
+config = {{ path = "/tmp/{}.socket" }}
+"#,
+                    Uuid::new_v4(),
+                    name,
+                    name
+                ),
+            )?;
+        }
+        let subscriptions = load_dir(&dir)?;
+        assert_eq!(subscriptions.len(), 3);
+
+        // Exceeding the limit is an error by default
+        let err =
+            limit_subscriptions(subscriptions.clone(), &server_settings(2, "error")?).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "3 active subscriptions exceed the limit of 2 (server.max_subscriptions)"
+        );
+
+        // With the "warn" policy, only the first subscriptions are kept
+        let kept = limit_subscriptions(subscriptions.clone(), &server_settings(2, "warn")?)?;
+        let names: Vec<&str> = kept
+            .iter()
+            .map(|subscription| subscription.name())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        // A set under the limit loads normally
+        fs::remove_file(dir.join("c.toml"))?;
+        let subscriptions = load_dir(&dir)?;
+        let kept = limit_subscriptions(subscriptions, &server_settings(2, "error")?)?;
+        assert_eq!(kept.len(), 2);
+        let mut context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut mem_subscriptions: HashMap<SubscriptionUuid, Arc<Subscription>> = HashMap::new();
+        for subscription in kept {
+            reload_subscription(&mut mem_subscriptions, &mut context, subscription)?;
+        }
+        assert_eq!(mem_subscriptions.len(), 2);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_single_subscription() -> Result<()> {
+        let mut context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut mem_subscriptions: HashMap<SubscriptionUuid, Arc<Subscription>> = HashMap::new();
+
+        let mut target = SubscriptionData::new("target", "");
+        let other = SubscriptionData::new("other", "");
+        reload_subscription(&mut mem_subscriptions, &mut context, target.clone())?;
+        reload_subscription(&mut mem_subscriptions, &mut context, other.clone())?;
+        assert_eq!(mem_subscriptions.len(), 2);
+
+        let other_before = mem_subscriptions[other.uuid()].clone();
+        other_before.known_sources().insert("win10.windomain.local");
+        let target_before = mem_subscriptions[target.uuid()].clone();
+
+        target.set_heartbeat_interval(1234);
+        reload_subscription(&mut mem_subscriptions, &mut context, target.clone())?;
+        assert_eq!(mem_subscriptions.len(), 2);
+
+        // Only the targeted subscription has been swapped
+        let target_after = &mem_subscriptions[target.uuid()];
+        assert!(!Arc::ptr_eq(&target_before, target_after));
+        assert_eq!(target_after.data(), &target);
+        assert_ne!(target_after.data(), target_before.data());
+
+        // Other subscriptions are left untouched, including their in-memory state
+        let other_after = &mem_subscriptions[other.uuid()];
+        assert!(Arc::ptr_eq(&other_before, other_after));
+        assert_eq!(other_after.data(), &other);
+        assert!(other_after
+            .known_sources()
+            .contains("win10.windomain.local"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_disabled_output() -> Result<()> {
+        let uuid = Uuid::new_v4();
+        let config = |enabled: bool| {
+            format!(
+                r#"
+uuid = "{}"
+name = "outputs"
+query = "<QueryList></QueryList>"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Raw"
+config = {{ path = "/tmp/openwec-raw.socket" }}
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Json"
+enabled = {}
+config = {{ path = "/tmp/openwec-json.socket" }}
+"#,
+                uuid, enabled
+            )
+        };
+        let mut context = OutputDriversContext::new(&settings::Outputs::default());
+        let mut mem_subscriptions: HashMap<SubscriptionUuid, Arc<Subscription>> = HashMap::new();
+
+        let both = parse(&config(true), None)?;
+        reload_subscription(&mut mem_subscriptions, &mut context, both.clone())?;
+        let before = mem_subscriptions[both.uuid()].clone();
+        assert_eq!(before.outputs().len(), 2);
+        assert_eq!(before.formats().len(), 2);
+
+        // Disabling the second output only stops that output
+        let disabled = parse(&config(false), None)?;
+        let diff = OutputsDiff::new(&both, &disabled);
+        assert_eq!(
+            diff,
+            OutputsDiff {
+                kept: vec![(0, 0)],
+                started: vec![],
+                stopped: vec![1],
+            }
+        );
+
+        reload_subscription(&mut mem_subscriptions, &mut context, disabled.clone())?;
+        let after = mem_subscriptions[disabled.uuid()].clone();
+        assert_eq!(after.outputs().len(), 1);
+        assert_eq!(after.formats().len(), 1);
+        assert_eq!(
+            after.outputs()[0].describe(),
+            before.outputs()[0].describe()
+        );
+
+        // Enabling it again starts it back, without touching the first one
+        let diff = OutputsDiff::new(&disabled, &both);
+        assert_eq!(
+            diff,
+            OutputsDiff {
+                kept: vec![(0, 0)],
+                started: vec![1],
+                stopped: vec![],
+            }
+        );
+        assert!(OutputsDiff::new(&both, &both).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload_event_ttl() -> Result<()> {
+        let config = r#"
+uuid = "b00bf259-3ba9-4faf-b58e-d0e9a3757798"
+name = "outputs"
+query = "<QueryList></QueryList>"
+
+[[outputs]]
+driver = "UnixDatagram"
+format = "Raw"
+config = { path = "/tmp/openwec-raw.socket" }
+
+[[outputs]]
+driver = "Redis"
+format = "Json"
+config = { addr = "localhost:6379", list = "events" }
+"#;
+        let without_ttl = parse(config, None)?;
+        let with_ttl = parse(&format!("{}\n[options]\nevent_ttl = 3600\n", config), None)?;
+
+        // Only the outputs making events expire are started again
+        let diff = OutputsDiff::new(&without_ttl, &with_ttl);
+        assert_eq!(
+            diff,
+            OutputsDiff {
+                kept: vec![(0, 0)],
+                started: vec![1],
+                stopped: vec![1],
+            }
+        );
+        assert!(OutputsDiff::new(&with_ttl, &with_ttl).is_empty());
+        Ok(())
+    }
+}
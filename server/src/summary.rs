@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::event::EventMetadata;
+
+#[derive(Debug, Serialize)]
+struct SummarySubscription<'a> {
+    #[serde(rename = "Uuid")]
+    uuid: &'a str,
+    #[serde(rename = "Name")]
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchSummary<'a> {
+    #[serde(rename = "Type")]
+    record_type: &'static str,
+    #[serde(rename = "Events")]
+    events: usize,
+    #[serde(rename = "Bytes")]
+    bytes: usize,
+    #[serde(rename = "IpAddress")]
+    ip_address: String,
+    #[serde(rename = "Principal")]
+    principal: &'a str,
+    #[serde(rename = "TimeReceived")]
+    time_received: String,
+    #[serde(rename = "Subscription")]
+    subscription: SummarySubscription<'a>,
+    #[serde(rename = "Node", skip_serializing_if = "Option::is_none")]
+    node: Option<&'a String>,
+}
+
+/// Serializes the record sent to "summary" outputs for a received batch.
+/// `events` must only contain the events sent by the client: records
+/// generated by openwec (summaries, "source first seen" events) are never
+/// summarized.
+pub fn format(metadata: &EventMetadata, events: &[Arc<String>]) -> Result<String> {
+    let summary = BatchSummary {
+        record_type: "BatchSummary",
+        events: events.len(),
+        bytes: events.iter().map(|event| event.len()).sum(),
+        ip_address: metadata.addr().ip().to_string(),
+        principal: metadata.principal(),
+        time_received: metadata.time_received().to_rfc3339(),
+        subscription: SummarySubscription {
+            uuid: metadata.subscription_uuid(),
+            name: metadata.subscription_name(),
+        },
+        node: metadata.node_name(),
+    };
+    Ok(serde_json::to_string(&summary)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, net::SocketAddr, str::FromStr};
+
+    use common::{
+        settings,
+        subscription::{
+            SubscriptionData, SubscriptionOutput, SubscriptionOutputDriver,
+            SubscriptionOutputFormat, UnixDatagramConfiguration,
+        },
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{output::OutputDriversContext, subscription::Subscription};
+
+    fn output(format: SubscriptionOutputFormat, path: &str, summary: bool) -> SubscriptionOutput {
+        let mut output = SubscriptionOutput::new(
+            format,
+            SubscriptionOutputDriver::UnixDatagram(UnixDatagramConfiguration::new(
+                path.to_string(),
+            )),
+            true,
+        );
+        output.set_summary(summary);
+        output
+    }
+
+    #[tokio::test]
+    async fn test_one_summary_per_batch() -> Result<()> {
+        let mut data = SubscriptionData::new("Test", "");
+        data.set_outputs(vec![
+            output(SubscriptionOutputFormat::Json, "/tmp/events.socket", false),
+            output(SubscriptionOutputFormat::Raw, "/tmp/summary.socket", true),
+        ]);
+        let mut output_context = OutputDriversContext::new(&settings::Outputs::default());
+        let subscription = Subscription::from_data(data, &mut output_context)?;
+
+        // Events are only formatted for regular outputs: summary records
+        // are never fed back as events
+        assert!(subscription.has_summary_outputs());
+        assert_eq!(
+            subscription
+                .formats()
+                .iter()
+                .map(|format_key| format_key.format().clone())
+                .collect::<HashSet<_>>(),
+            HashSet::from([SubscriptionOutputFormat::Json])
+        );
+
+        let metadata = EventMetadata::new(
+            &SocketAddr::from_str("192.168.58.100:5985")?,
+            "WIN10$@WINDOMAIN.LOCAL",
+            None,
+            &subscription,
+            "188BB736-9441-5C66-188B-B73694415C66".to_string(),
+            None,
+        );
+
+        let batches = vec![
+            vec![Arc::new("a".repeat(10)), Arc::new("b".repeat(20))],
+            vec![Arc::new("c".repeat(5))],
+            vec![
+                Arc::new("d".repeat(1)),
+                Arc::new("e".repeat(2)),
+                Arc::new("f".repeat(3)),
+            ],
+        ];
+
+        let mut summaries = Vec::new();
+        for batch in batches.iter() {
+            summaries.push(format(&metadata, batch)?);
+        }
+
+        // One summary record per batch
+        assert_eq!(summaries.len(), batches.len());
+        let expected = [(2, 30), (1, 5), (3, 6)];
+        for (summary, (events, bytes)) in summaries.iter().zip(expected) {
+            let value: Value = serde_json::from_str(summary)?;
+            assert_eq!(value["Type"], "BatchSummary");
+            assert_eq!(value["Events"], events);
+            assert_eq!(value["Bytes"], bytes);
+            assert_eq!(value["Principal"], "WIN10$@WINDOMAIN.LOCAL");
+            assert_eq!(value["IpAddress"], "192.168.58.100");
+            assert_eq!(value["Subscription"]["Name"], "Test");
+            assert_eq!(
+                value["Subscription"]["Uuid"],
+                subscription.uuid_string().as_str()
+            );
+            assert!(value.get("Node").is_none());
+        }
+        Ok(())
+    }
+}
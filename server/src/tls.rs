@@ -3,13 +3,25 @@ use common::encoding::encode_utf16le;
 use hex::ToHex;
 use log::{debug, info};
 use sha1::{Digest, Sha1};
+use sha2::Sha256;
 use std::fs;
 use std::io::BufReader;
 use std::sync::Arc;
+use thiserror::Error;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::WebPkiServerVerifier;
 use tokio_rustls::rustls::crypto::aws_lc_rs::{default_provider, ALL_CIPHER_SUITES};
-use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::crypto::{
+    verify_tls12_signature, verify_tls13_signature, CryptoProvider, WebPkiSupportedAlgorithms,
+};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
 use tokio_rustls::rustls::server::WebPkiClientVerifier;
-use tokio_rustls::rustls::{RootCertStore, ServerConfig, ALL_VERSIONS};
+use tokio_rustls::rustls::{
+    DigitallySignedStruct, OtherError, RootCertStore, ServerConfig, SignatureScheme, ALL_VERSIONS,
+};
+use x509_parser::extensions::GeneralName;
 use x509_parser::oid_registry::OidRegistry;
 use x509_parser::prelude::{FromDer, X509Certificate};
 
@@ -64,6 +76,107 @@ pub fn compute_thumbprint(cert_content: &[u8]) -> String {
     shasum_it.encode_hex::<String>()
 }
 
+/// SHA-256 of entire certificate, as a lowercase hexadecimal string
+pub fn compute_sha256_fingerprint(cert_content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(cert_content);
+    let shasum_it = hasher.finalize();
+
+    shasum_it.encode_hex::<String>()
+}
+
+/// Whether the SHA-256 fingerprint of a certificate is one of `pins`, which
+/// are lowercase hexadecimal strings
+pub fn matches_pinned_sha256(cert_content: &[u8], pins: &[String]) -> bool {
+    let fingerprint = compute_sha256_fingerprint(cert_content);
+    pins.iter().any(|pin| *pin == fingerprint)
+}
+
+#[derive(Debug, Error)]
+#[error("server certificate SHA-256 fingerprint {0} does not match any of tls_pinned_sha256")]
+struct PinnedSha256Mismatch(String);
+
+/// Server certificate verifier used by outputs with pinned fingerprints.
+/// The server certificate must match one of the pins. It is also verified
+/// against the trusted certificate authorities, if any.
+#[derive(Debug)]
+pub struct PinnedServerCertVerifier {
+    ca_verifier: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<String>,
+    supported_algs: WebPkiSupportedAlgorithms,
+}
+
+impl PinnedServerCertVerifier {
+    /// `roots` are the trusted certificate authorities, if any
+    pub fn new(
+        roots: Option<RootCertStore>,
+        pins: &[String],
+        provider: Arc<CryptoProvider>,
+    ) -> Result<Self> {
+        let ca_verifier = match roots {
+            Some(roots) => Some(
+                WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                    .build()?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            ca_verifier,
+            pins: pins.to_vec(),
+            supported_algs: provider.signature_verification_algorithms,
+        })
+    }
+}
+
+impl ServerCertVerifier for PinnedServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        if let Some(ca_verifier) = &self.ca_verifier {
+            ca_verifier.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+        if !matches_pinned_sha256(end_entity.as_ref(), &self.pins) {
+            return Err(tokio_rustls::rustls::Error::Other(OtherError(Arc::new(
+                PinnedSha256Mismatch(compute_sha256_fingerprint(end_entity.as_ref())),
+            ))));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported_algs.supported_schemes()
+    }
+}
+
 pub struct TlsConfig {
     pub server: Arc<ServerConfig>,
     pub thumbprint: String,
@@ -158,6 +271,23 @@ pub fn subject_from_cert(cert: &[u8]) -> Result<String> {
     bail!("CommonName not found")
 }
 
+/// Get the DNS and URI SubjectAltName entries of a certificate
+pub fn san_from_cert(cert: &[u8]) -> Result<Vec<String>> {
+    let cert = X509Certificate::from_der(cert)?.1;
+    let Some(san) = cert.subject_alternative_name()? else {
+        return Ok(Vec::new());
+    };
+    Ok(san
+        .value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(name) | GeneralName::URI(name) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect())
+}
+
 /// Read and decode request payload
 pub async fn get_request_payload(
     parts: hyper::http::request::Parts,
@@ -196,10 +326,12 @@ pub async fn get_response_payload(payload: String) -> Result<Vec<u8>> {
 mod tests {
     use std::path::PathBuf;
 
+    const CA_CERTIFICATE: &[u8] = b"0\x82\x02\xb50\x82\x02;\xa0\x03\x02\x01\x02\x02\x14g\x0c\x8d\xf7\t*\xb9\x04\x9b\xb2\x13\xf0H\xe9\x9a\x0fW5\xb0\x050\n\x06\x08*\x86H\xce=\x04\x03\x020W1\x0f0\r\x06\x03U\x04\x03\x13\x06WEF-CA1\x140\x12\x06\x03U\x04\n\x13\x0bstage.local1\x0b0\t\x06\x03U\x04\x06\x13\x02FR1\x0e0\x0c\x06\x03U\x04\x08\x13\x05state1\x110\x0f\x06\x03U\x04\x07\x13\x08location0\x1e\x17\r230825080210Z\x17\r230924080210Z0W1\x0f0\r\x06\x03U\x04\x03\x13\x06WEF-CA1\x140\x12\x06\x03U\x04\n\x13\x0bstage.local1\x0b0\t\x06\x03U\x04\x06\x13\x02FR1\x0e0\x0c\x06\x03U\x04\x08\x13\x05state1\x110\x0f\x06\x03U\x04\x07\x13\x08location0v0\x10\x06\x07*\x86H\xce=\x02\x01\x06\x05+\x81\x04\0\"\x03b\0\x04\xd8(@\xad\x9c\xa3\xe3\xb3\x14_\x8a-\xa3\x0fz\xbc_7|\x9cac\xc4`\x8f\xff\x0e\xe9\xadj:\x7fP\xdb\xf3\xb3\xdb$\xb5\xd9\xf4Xo\xae\xfa\xadlD\xdc+x\xf7s=\xbdi\x11\xc4u\0@\xdf\xc2\x86\xdb\xbe\xc4\x1f\x9bcc\xe8\xacnI\xe68\xa5vI\x9b\x99\xab\xc4\xa8\x10s\xe7\xcb\x7f\xa9\xc4\xf0\xc4\x97\x0b\xa3\x81\xc70\x81\xc40\x1d\x06\x03U\x1d\x0e\x04\x16\x04\x14\xfcL\x81rZ\x85\xd8\x1bI\xe4\xc3\xa7\x8bR\x1e\xb3\x19\xa9\xc4\x170\x81\x94\x06\x03U\x1d#\x04\x81\x8c0\x81\x89\x80\x14\xfcL\x81rZ\x85\xd8\x1bI\xe4\xc3\xa7\x8bR\x1e\xb3\x19\xa9\xc4\x17\xa1[\xa4Y0W1\x0f0\r\x06\x03U\x04\x03\x13\x06WEF-CA1\x140\x12\x06\x03U\x04\n\x13\x0bstage.local1\x0b0\t\x06\x03U\x04\x06\x13\x02FR1\x0e0\x0c\x06\x03U\x04\x08\x13\x05state1\x110\x0f\x06\x03U\x04\x07\x13\x08location\x82\x14g\x0c\x8d\xf7\t*\xb9\x04\x9b\xb2\x13\xf0H\xe9\x9a\x0fW5\xb0\x050\x0c\x06\x03U\x1d\x13\x04\x050\x03\x01\x01\xff0\n\x06\x08*\x86H\xce=\x04\x03\x02\x03h\00e\x021\0\x85)\xf7F\x88\xb5b\xdc&8\xfd\xae\xbe}\xd5Y\x83\x1ft\xe6\xf6\xdb!\xfco\x13\x17\xf0YM\\\xbb\x9c\xff\x12\xa2)\xc8\xc3\xb1u\x9eW,*1\xe2h\x020\x16\xe2|\xe0\x1cPJ\xde\x9d\"\xfa\xc3\ty\x06\x04\xf7\xe67z\x93\xa6tp9\xde\xa2\xee\xcfM\x95\x02DQzx$g\xc9\xf0\xaf\xf7;Vk\xef\xad{";
+
     #[test]
     /// Test thumprint computation
     fn test_thumbprint() {
-        let cert = b"0\x82\x02\xb50\x82\x02;\xa0\x03\x02\x01\x02\x02\x14g\x0c\x8d\xf7\t*\xb9\x04\x9b\xb2\x13\xf0H\xe9\x9a\x0fW5\xb0\x050\n\x06\x08*\x86H\xce=\x04\x03\x020W1\x0f0\r\x06\x03U\x04\x03\x13\x06WEF-CA1\x140\x12\x06\x03U\x04\n\x13\x0bstage.local1\x0b0\t\x06\x03U\x04\x06\x13\x02FR1\x0e0\x0c\x06\x03U\x04\x08\x13\x05state1\x110\x0f\x06\x03U\x04\x07\x13\x08location0\x1e\x17\r230825080210Z\x17\r230924080210Z0W1\x0f0\r\x06\x03U\x04\x03\x13\x06WEF-CA1\x140\x12\x06\x03U\x04\n\x13\x0bstage.local1\x0b0\t\x06\x03U\x04\x06\x13\x02FR1\x0e0\x0c\x06\x03U\x04\x08\x13\x05state1\x110\x0f\x06\x03U\x04\x07\x13\x08location0v0\x10\x06\x07*\x86H\xce=\x02\x01\x06\x05+\x81\x04\0\"\x03b\0\x04\xd8(@\xad\x9c\xa3\xe3\xb3\x14_\x8a-\xa3\x0fz\xbc_7|\x9cac\xc4`\x8f\xff\x0e\xe9\xadj:\x7fP\xdb\xf3\xb3\xdb$\xb5\xd9\xf4Xo\xae\xfa\xadlD\xdc+x\xf7s=\xbdi\x11\xc4u\0@\xdf\xc2\x86\xdb\xbe\xc4\x1f\x9bcc\xe8\xacnI\xe68\xa5vI\x9b\x99\xab\xc4\xa8\x10s\xe7\xcb\x7f\xa9\xc4\xf0\xc4\x97\x0b\xa3\x81\xc70\x81\xc40\x1d\x06\x03U\x1d\x0e\x04\x16\x04\x14\xfcL\x81rZ\x85\xd8\x1bI\xe4\xc3\xa7\x8bR\x1e\xb3\x19\xa9\xc4\x170\x81\x94\x06\x03U\x1d#\x04\x81\x8c0\x81\x89\x80\x14\xfcL\x81rZ\x85\xd8\x1bI\xe4\xc3\xa7\x8bR\x1e\xb3\x19\xa9\xc4\x17\xa1[\xa4Y0W1\x0f0\r\x06\x03U\x04\x03\x13\x06WEF-CA1\x140\x12\x06\x03U\x04\n\x13\x0bstage.local1\x0b0\t\x06\x03U\x04\x06\x13\x02FR1\x0e0\x0c\x06\x03U\x04\x08\x13\x05state1\x110\x0f\x06\x03U\x04\x07\x13\x08location\x82\x14g\x0c\x8d\xf7\t*\xb9\x04\x9b\xb2\x13\xf0H\xe9\x9a\x0fW5\xb0\x050\x0c\x06\x03U\x1d\x13\x04\x050\x03\x01\x01\xff0\n\x06\x08*\x86H\xce=\x04\x03\x02\x03h\00e\x021\0\x85)\xf7F\x88\xb5b\xdc&8\xfd\xae\xbe}\xd5Y\x83\x1ft\xe6\xf6\xdb!\xfco\x13\x17\xf0YM\\\xbb\x9c\xff\x12\xa2)\xc8\xc3\xb1u\x9eW,*1\xe2h\x020\x16\xe2|\xe0\x1cPJ\xde\x9d\"\xfa\xc3\ty\x06\x04\xf7\xe67z\x93\xa6tp9\xde\xa2\xee\xcfM\x95\x02DQzx$g\xc9\xf0\xaf\xf7;Vk\xef\xad{";
+        let cert = CA_CERTIFICATE;
         let thumbprint = crate::tls::compute_thumbprint(cert).to_uppercase();
         assert_eq!(thumbprint, "6A4720A83504B818C86BAC099D3A4BEDE89945D9");
 
@@ -208,6 +340,54 @@ mod tests {
         assert_eq!(thumbprint, "EAE93EA8A4CC386849A873D3B9E5EE57886A1603");
     }
 
+    #[test]
+    /// Test SHA-256 fingerprint comparison
+    fn test_matches_pinned_sha256() {
+        use super::*;
+
+        let fingerprint = "12ccbc7ff0db6733c1d042bbdf42fd9344311f2d2322a8450197a2d7d7d71033";
+        assert_eq!(compute_sha256_fingerprint(CA_CERTIFICATE), fingerprint);
+        assert!(matches_pinned_sha256(
+            CA_CERTIFICATE,
+            &[fingerprint.to_string()]
+        ));
+        assert!(matches_pinned_sha256(
+            CA_CERTIFICATE,
+            &["00".repeat(32), fingerprint.to_string()]
+        ));
+        assert!(!matches_pinned_sha256(CA_CERTIFICATE, &["00".repeat(32)]));
+        assert!(!matches_pinned_sha256(CA_CERTIFICATE, &[]));
+        // SHA-1 thumbprints are not SHA-256 fingerprints
+        assert!(!matches_pinned_sha256(
+            CA_CERTIFICATE,
+            &["6a4720a83504b818c86bac099d3a4bede89945d9".to_string()]
+        ));
+
+        // Without certificate authorities, only the fingerprint is verified
+        let cert = CertificateDer::from(CA_CERTIFICATE);
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let verifier = PinnedServerCertVerifier::new(
+            None,
+            &[fingerprint.to_string()],
+            Arc::new(default_provider()),
+        )
+        .unwrap();
+        assert!(verifier
+            .verify_server_cert(&cert, &[], &server_name, &[], UnixTime::now())
+            .is_ok());
+
+        let verifier =
+            PinnedServerCertVerifier::new(None, &["00".repeat(32)], Arc::new(default_provider()))
+                .unwrap();
+        let err = verifier
+            .verify_server_cert(&cert, &[], &server_name, &[], UnixTime::now())
+            .unwrap_err();
+        assert!(err.to_string().contains(&format!(
+            "server certificate SHA-256 fingerprint {} does not match any of tls_pinned_sha256",
+            fingerprint
+        )));
+    }
+
     #[test]
     /// Test retrieving subject name from certificate
     fn test_get_subject_from_cert() {
@@ -234,6 +414,25 @@ mod tests {
         assert_eq!(subject, subject_from_cert(certificate).unwrap());
     }
 
+    #[test]
+    /// Test retrieving SubjectAltName entries from certificate
+    fn test_get_san_from_cert() {
+        use super::*;
+
+        // Subject is "legacy-name". IP and email entries are ignored.
+        let certificate = b"0\x82\x02\x020\x82\x01\xa7\xa0\x03\x02\x01\x02\x02\x14/\xe8\x97\xf5\x1b\"\xb9\xc0\x0e\xf7|V#:e6\x9c\x83\xf8\xbf0\n\x06\x08*\x86H\xce=\x04\x03\x020,1\x140\x12\x06\x03U\x04\x03\x0c\x0blegacy-name1\x140\x12\x06\x03U\x04\n\x0c\x0bstage.local0\x1e\x17\r261016081803Z\x17\r361013081803Z0,1\x140\x12\x06\x03U\x04\x03\x0c\x0blegacy-name1\x140\x12\x06\x03U\x04\n\x0c\x0bstage.local0Y0\x13\x06\x07*\x86H\xce=\x02\x01\x06\x08*\x86H\xce=\x03\x01\x07\x03B\0\x04\xca\xf6\xcc\xf7\x17E\x1e\xb66\xf2\xc0:9\xd8a6\x84!\r\x8c\xbc\x06\xf1]\x1f[\x8a\xe81s<\x1c*\x94\x11\xbe]\x03\x05Tk\x04\xc9\xe6\x9f\xca\xef\xa2\xbd\xac\xecB\xb8\x9eQ\xfd,\xba\xf5\xc3\xd9;\xb5a\xa3\x81\xa60\x81\xa30\x1d\x06\x03U\x1d\x0e\x04\x16\x04\x14gZ\xb4\x87\xb5\xdb<\xe8\xd6\x04\"l\xd7\x8f\xe4\xad)\x8d\xc5\x900\x1f\x06\x03U\x1d#\x04\x180\x16\x80\x14gZ\xb4\x87\xb5\xdb<\xe8\xd6\x04\"l\xd7\x8f\xe4\xad)\x8d\xc5\x900\x0f\x06\x03U\x1d\x13\x01\x01\xff\x04\x050\x03\x01\x01\xff0P\x06\x03U\x1d\x11\x04I0G\x82\x15WIN10.windomain.local\x86\x11urn:openwec:win10\x87\x04\n\0\0\x01\x81\x15admin@windomain.local0\n\x06\x08*\x86H\xce=\x04\x03\x02\x03I\00F\x02!\0\x9c\xf2\xa56\xdd\xeb \x17\xa9\xce12\0z\x7f\xdc\x9avM\xd2\xbd:\xf9\x87U\x91\xfc\xc3e3%\xfa\x02!\0\xd0\x01<\xc6\xaa@5>m\xd3]-\xd5\x97\x92\xe9\xf9\xbdS\xea\x9f\x9c\xd1\x12\xf6\xa5\xb8H\xc9\xbf((";
+        assert_eq!(subject_from_cert(certificate).unwrap(), "legacy-name");
+        assert_eq!(
+            san_from_cert(certificate).unwrap(),
+            vec!["WIN10.windomain.local", "urn:openwec:win10"]
+        );
+
+        // No SubjectAltName extension
+        let certificate = b"0\x82\x01\x9b0\x82\x01A\xa0\x03\x02\x01\x02\x02\x14\x1a\xcf\xf3\xc3f^\x92\x8c\xc1\xd0\x8fZ\xefZ\x05j\x01\xff\xeaH0\n\x06\x08*\x86H\xce=\x04\x03\x020&1\x0e0\x0c\x06\x03U\x04\x03\x0c\x05win101\x140\x12\x06\x03U\x04\n\x0c\x0bstage.local0\x1e\x17\r261016081926Z\x17\r361013081926Z0&1\x0e0\x0c\x06\x03U\x04\x03\x0c\x05win101\x140\x12\x06\x03U\x04\n\x0c\x0bstage.local0Y0\x13\x06\x07*\x86H\xce=\x02\x01\x06\x08*\x86H\xce=\x03\x01\x07\x03B\0\x04\xfc\xc1\xb6b\x87\x01 \xf2\xad\xdf\xdc\xa4!\xca1\x01\xe6\xdd'\xef,\xf6X`\xa4\xd4AB\x8cW\xdc\n&\"\xd3\x12\xb9\xb2E|h\x08]\x82\xf6\x9e\xcc\xaa{A\x7f\xa3\xff\x19\xcea\xa81\xb3\xe2\xc4'J\x0c\xa3M0K0\x1d\x06\x03U\x1d\x0e\x04\x16\x04\x14\xbb\xb6\xcb\x1e\xf6\x9bc\xcfp}\x9d\x94s\x1f\xb2\x8c\x18\xd8X\xb00\x1f\x06\x03U\x1d#\x04\x180\x16\x80\x14\xbb\xb6\xcb\x1e\xf6\x9bc\xcfp}\x9d\x94s\x1f\xb2\x8c\x18\xd8X\xb00\t\x06\x03U\x1d\x13\x04\x020\00\n\x06\x08*\x86H\xce=\x04\x03\x02\x03H\00E\x02!\0\xb1n\x05\x92\xa6c\x1a\xe7\xacv\xf5\x18\xdb?pbK\xd7\xc2u\xfa\x1d\x03\xaan\xe8\xbew\xbe\x8e\xec\xf6\x02 o;\xed\x14v\x84*\x17\xa2\x0e\xfci\x98.\xe7}\xd2\xed\xfa\x9bh\xbc\xfd\x9e\xbb\xf1>\xa6\x8ed~\x89";
+        assert_eq!(subject_from_cert(certificate).unwrap(), "win10");
+        assert!(san_from_cert(certificate).unwrap().is_empty());
+    }
+
     #[test]
     #[should_panic(expected = "CommonName")] // XXX : panics not as we thought but still panics
     /// Test retrieving subject name from certificate
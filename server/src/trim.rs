@@ -0,0 +1,56 @@
+use std::collections::BTreeSet;
+
+use common::subscription::{ContentFormat, RenderingElement, SubscriptionData};
+use strum::VariantNames;
+
+use crate::event::EventData;
+
+/// Removes the configured sub-elements of RenderingInfo from parsed events,
+/// so that they are not formatted. Raw events are left untouched.
+#[derive(Debug, Clone)]
+pub struct RenderingTrimmer {
+    elements: BTreeSet<RenderingElement>,
+}
+
+impl RenderingTrimmer {
+    pub fn new(elements: BTreeSet<RenderingElement>) -> Self {
+        Self { elements }
+    }
+
+    /// Returns None if rendering info is not trimmed, either because the
+    /// subscription does not configure it or because events are not rendered
+    pub fn from_subscription(data: &SubscriptionData) -> Option<Self> {
+        if data.trim_rendering().is_empty() || data.content_format() != &ContentFormat::RenderedText
+        {
+            return None;
+        }
+        Some(Self::new(data.trim_rendering().clone()))
+    }
+
+    pub fn apply(&self, event_data: &mut EventData) {
+        let event = match event_data.event_mut() {
+            Some(event) => event,
+            None => return,
+        };
+        // Without any sub-element left, only the culture would remain
+        if self.elements.len() == RenderingElement::VARIANTS.len() {
+            event.rendering_info = None;
+            return;
+        }
+        let rendering_info = match event.rendering_info.as_mut() {
+            Some(rendering_info) => rendering_info,
+            None => return,
+        };
+        for element in self.elements.iter() {
+            match element {
+                RenderingElement::Message => rendering_info.message = None,
+                RenderingElement::Level => rendering_info.level = None,
+                RenderingElement::Task => rendering_info.task = None,
+                RenderingElement::Opcode => rendering_info.opcode = None,
+                RenderingElement::Channel => rendering_info.channel = None,
+                RenderingElement::Provider => rendering_info.provider = None,
+                RenderingElement::Keywords => rendering_info.keywords = None,
+            }
+        }
+    }
+}